@@ -0,0 +1,111 @@
+use std::borrow::Cow;
+
+use criterion::Criterion;
+use glam::{DVec3, IVec3};
+use valence::protocol::byte_angle::ByteAngle;
+use valence::protocol::encode::PacketEncoder;
+use valence::protocol::var_int::VarInt;
+use valence::protocol::var_long::VarLong;
+use valence_core::block_pos::BlockPos;
+use valence_core::ident;
+use valence_core::protocol::encode::{EncodedPacket, WritePacket, DEFAULT_COMPRESSION_LEVEL};
+use valence_core::protocol::packet::sound::{PlaySoundS2c, SoundCategory, SoundId};
+use valence_core::protocol::SharedPacket;
+use valence_entity::packet::EntityPositionS2c;
+use valence_instance::packet::{BlockUpdateS2c, ChunkDeltaUpdateS2c};
+
+const CLIENT_COUNT: usize = 200;
+const COMPRESSION_THRESHOLD: u32 = 256;
+
+/// Benches writing the same packets to many clients, encoding them once per
+/// client and once in total.
+pub fn broadcast(c: &mut Criterion) {
+    let mut group = c.benchmark_group("broadcast");
+
+    let mut encoders: Vec<_> = (0..CLIENT_COUNT)
+        .map(|_| {
+            let mut enc = PacketEncoder::new();
+            enc.set_compression(Some(COMPRESSION_THRESHOLD));
+            enc
+        })
+        .collect();
+
+    let position = EntityPositionS2c {
+        entity_id: VarInt(123),
+        position: DVec3::new(12.5, 64.0, -7.25),
+        yaw: ByteAngle(64),
+        pitch: ByteAngle(0),
+        on_ground: true,
+    };
+
+    let sound = PlaySoundS2c {
+        id: SoundId::Direct {
+            id: ident!("entity.player.levelup").into(),
+            range: None,
+        },
+        category: SoundCategory::Player,
+        position: IVec3::new(100, 512, -56),
+        volume: 1.0,
+        pitch: 1.0,
+        seed: 42,
+    };
+
+    let block = BlockUpdateS2c {
+        position: BlockPos::new(1, 2, 3),
+        block_id: VarInt(1),
+    };
+
+    // Large enough to be compressed.
+    let delta_blocks: Vec<_> = (0..1024).map(|i| VarLong(i * 31 % 4096)).collect();
+    let delta = ChunkDeltaUpdateS2c {
+        chunk_section_position: 0,
+        blocks: Cow::Borrowed(&delta_blocks),
+    };
+
+    group.bench_function("encode_per_client", |b| {
+        b.iter(|| {
+            for enc in &mut encoders {
+                enc.write_packet(&position);
+                enc.write_packet(&sound);
+                enc.write_packet(&block);
+                enc.write_packet(&delta);
+            }
+
+            for enc in &mut encoders {
+                enc.clear();
+            }
+        });
+    });
+
+    group.bench_function("encode_once", |b| {
+        b.iter(|| {
+            let packets = [
+                encode(&position),
+                encode(&sound),
+                encode(&block),
+                encode(&delta),
+            ];
+
+            for enc in &mut encoders {
+                for pkt in &packets {
+                    enc.write_encoded(pkt);
+                }
+            }
+
+            for enc in &mut encoders {
+                enc.clear();
+            }
+        });
+    });
+
+    group.finish();
+}
+
+fn encode<P: SharedPacket>(packet: &P) -> EncodedPacket {
+    EncodedPacket::new(
+        packet,
+        Some(COMPRESSION_THRESHOLD),
+        DEFAULT_COMPRESSION_LEVEL,
+    )
+    .unwrap()
+}
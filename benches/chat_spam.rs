@@ -0,0 +1,77 @@
+use std::hint::black_box;
+
+use criterion::Criterion;
+use valence::protocol::decode::PacketDecoder;
+use valence::protocol::var_int::VarInt;
+use valence_client::custom_payload::CustomPayloadC2s;
+use valence_client::packet::UpdateSignC2s;
+use valence_core::block_pos::BlockPos;
+use valence_core::ident;
+use valence_core::protocol::encode::{PacketWriter, WritePacket};
+use valence_core::protocol::packet::chat::{ChatMessageC2s, CommandExecutionC2s};
+use valence_core::protocol::Packet;
+
+const PACKET_COUNT: usize = 1000;
+
+/// Benches decoding the text-heavy packets a client spamming chat, commands,
+/// signs and plugin messages would send.
+pub fn chat_spam(c: &mut Criterion) {
+    let mut buf = vec![];
+    let mut w = PacketWriter::new(&mut buf, None);
+
+    for i in 0..PACKET_COUNT {
+        match i % 4 {
+            0 => w.write_packet(&ChatMessageC2s {
+                message: "spam spam spam spam spam spam spam spam",
+                timestamp: i as u64,
+                salt: 0,
+                signature: None,
+                message_count: VarInt(0),
+                acknowledgement: [0; 3],
+            }),
+            1 => w.write_packet(&CommandExecutionC2s {
+                command: "tp @s ~ ~10 ~",
+                timestamp: i as u64,
+                salt: 0,
+                argument_signatures: vec![],
+                message_count: VarInt(0),
+                acknowledgement: [0; 3],
+            }),
+            2 => w.write_packet(&UpdateSignC2s {
+                position: BlockPos::new(1, 2, 3),
+                is_front_text: true,
+                lines: ["spam", "spam", "spam", "spam"],
+            }),
+            _ => w.write_packet(&CustomPayloadC2s {
+                channel: ident!("minecraft:brand").into(),
+                data: b"vanilla".as_slice().into(),
+            }),
+        }
+    }
+
+    let mut decoder = PacketDecoder::new();
+
+    c.bench_function("chat_spam", |b| {
+        b.iter(|| {
+            decoder.queue_slice(&buf);
+
+            while let Some(frame) = decoder.try_next_packet().unwrap() {
+                match frame.id {
+                    ChatMessageC2s::ID => {
+                        black_box(frame.decode::<ChatMessageC2s>().unwrap());
+                    }
+                    CommandExecutionC2s::ID => {
+                        black_box(frame.decode::<CommandExecutionC2s>().unwrap());
+                    }
+                    UpdateSignC2s::ID => {
+                        black_box(frame.decode::<UpdateSignC2s>().unwrap());
+                    }
+                    CustomPayloadC2s::ID => {
+                        black_box(frame.decode::<CustomPayloadC2s>().unwrap());
+                    }
+                    id => unreachable!("unexpected packet {id}"),
+                }
+            }
+        });
+    });
+}
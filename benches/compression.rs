@@ -0,0 +1,171 @@
+use std::net::{IpAddr, Ipv4Addr};
+use std::num::NonZeroUsize;
+use std::time::Duration;
+
+use bevy_app::prelude::*;
+use criterion::Criterion;
+use rand::Rng;
+use uuid::Uuid;
+use valence::testing::{MockClientConnection, MockClientHelper};
+use valence::DefaultPlugins;
+use valence_biome::BiomeRegistry;
+use valence_block::BlockState;
+use valence_client::keepalive::KeepaliveSettings;
+use valence_client::{Client, ClientBundle, ClientBundleArgs};
+use valence_core::chunk_pos::ChunkPos;
+use valence_core::protocol::decode::PacketDecoder;
+use valence_core::protocol::encode::{PacketEncoder, PacketWriter, WritePacket};
+use valence_core::{ident, CoreSettings, Server};
+use valence_dimension::DimensionTypeRegistry;
+use valence_instance::chunk::{Chunk, UnloadedChunk};
+use valence_instance::packet::ChunkDataS2c;
+use valence_instance::Instance;
+use valence_network::NetworkPlugin;
+
+const CLIENT_COUNT: usize = 50;
+const VIEW_DIST: i32 = 4;
+const COMPRESSION_THRESHOLD: u32 = 256;
+
+/// Benches flushing clients that are each sent a full view distance of chunks,
+/// with packets compressed on one thread and on one thread per CPU core.
+pub fn compression(c: &mut Criterion) {
+    let chunk_packet = chunk_packet_bytes();
+    let mut dec = PacketDecoder::new();
+    dec.queue_slice(&chunk_packet);
+    let frame = dec.try_next_packet().unwrap().unwrap();
+    let chunk: ChunkDataS2c = frame.decode().unwrap();
+
+    let mut group = c.benchmark_group("compression");
+    group.sample_size(10);
+
+    for (name, threads) in [("one_thread", NonZeroUsize::new(1)), ("pool", None)] {
+        let (mut app, mut helpers) = setup(threads);
+        let mut clients = app.world.query::<&mut Client>();
+
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for mut client in clients.iter_mut(&mut app.world) {
+                    for z in -VIEW_DIST..=VIEW_DIST {
+                        for x in -VIEW_DIST..=VIEW_DIST {
+                            client.write_packet(&ChunkDataS2c {
+                                pos: ChunkPos::new(x, z),
+                                ..chunk.clone()
+                            });
+                        }
+                    }
+                }
+
+                app.update(); // The important part.
+
+                for helper in &mut helpers {
+                    helper.clear_received();
+                }
+            });
+        });
+    }
+
+    group.finish();
+}
+
+fn setup(compression_threads: Option<NonZeroUsize>) -> (App, Vec<MockClientHelper>) {
+    let mut app = App::new();
+
+    app.insert_resource(CoreSettings {
+        compression_threshold: Some(COMPRESSION_THRESHOLD),
+        compression_threads,
+        ..Default::default()
+    });
+
+    app.insert_resource(KeepaliveSettings {
+        period: Duration::MAX,
+    });
+
+    app.add_plugins(DefaultPlugins.build().disable::<NetworkPlugin>());
+
+    app.update(); // Initialize plugins.
+
+    let inst = Instance::new(
+        ident!("overworld"),
+        app.world.resource::<DimensionTypeRegistry>(),
+        app.world.resource::<BiomeRegistry>(),
+        app.world.resource::<Server>(),
+    );
+
+    let inst_ent = app.world.spawn(inst).id();
+
+    let mut helpers = vec![];
+
+    for i in 0..CLIENT_COUNT {
+        // Mock clients don't enable compression by default.
+        let conn = MockClientConnection::new();
+        let mut enc = PacketEncoder::new();
+        enc.set_compression(Some(COMPRESSION_THRESHOLD));
+
+        let mut bundle = ClientBundle::new(ClientBundleArgs {
+            username: format!("client_{i}"),
+            uuid: Uuid::from_u128(i as u128),
+            ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            properties: vec![],
+            conn: Box::new(conn.clone()),
+            enc,
+        });
+
+        bundle.player.location.0 = inst_ent;
+
+        app.world.spawn(bundle);
+        helpers.push(MockClientHelper::new(conn));
+    }
+
+    app.update();
+
+    (app, helpers)
+}
+
+/// Returns the uncompressed packet data of a chunk with some terrain in it.
+fn chunk_packet_bytes() -> Vec<u8> {
+    let mut app = App::new();
+
+    app.insert_resource(CoreSettings {
+        compression_threshold: None,
+        ..Default::default()
+    });
+    app.add_plugins(DefaultPlugins.build().disable::<NetworkPlugin>());
+    app.update();
+
+    let mut inst = Instance::new(
+        ident!("overworld"),
+        app.world.resource::<DimensionTypeRegistry>(),
+        app.world.resource::<BiomeRegistry>(),
+        app.world.resource::<Server>(),
+    );
+
+    let mut chunk = UnloadedChunk::with_height(inst.height());
+    let mut rng = rand::thread_rng();
+
+    for y in 0..64 {
+        for z in 0..16 {
+            for x in 0..16 {
+                let block = match rng.gen_range(0..100) {
+                    0 => BlockState::COAL_ORE,
+                    1 => BlockState::IRON_ORE,
+                    2..=9 => BlockState::GRAVEL,
+                    10..=19 => BlockState::ANDESITE,
+                    _ => BlockState::STONE,
+                };
+
+                chunk.set_block_state(x, y, z, block);
+            }
+        }
+    }
+
+    inst.insert_chunk([0, 0], chunk);
+
+    let mut buf = vec![];
+    inst.chunk([0, 0]).unwrap().write_init_packets(
+        PacketWriter::new(&mut buf, None),
+        ChunkPos::new(0, 0),
+        inst.info(),
+    );
+
+    buf
+}
@@ -2,9 +2,13 @@ use criterion::{criterion_group, criterion_main};
 
 mod anvil;
 mod block;
+mod broadcast;
+mod chat_spam;
+mod compression;
 mod decode_array;
 mod idle;
 mod many_players;
+mod nbt;
 mod packet;
 mod var_int;
 mod var_long;
@@ -13,12 +17,16 @@ criterion_group! {
     benches,
     // anvil::load,
     block::block,
+    broadcast::broadcast,
+    chat_spam::chat_spam,
+    compression::compression,
     decode_array::decode_array,
     idle::idle_update,
     packet::packet,
     var_int::var_int,
     var_long::var_long,
     many_players::many_players,
+    nbt::nbt,
 }
 
 criterion_main!(benches);
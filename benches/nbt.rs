@@ -0,0 +1,139 @@
+use std::borrow::Cow;
+use std::hint::black_box;
+
+use criterion::Criterion;
+use serde::Deserialize;
+use valence::nbt::serde::from_binary_slice;
+use valence::nbt::{compound, Compound, List};
+
+/// The parts of an Anvil chunk that are needed to load its blocks.
+#[derive(Deserialize)]
+struct Chunk<'a> {
+    #[serde(rename = "xPos")]
+    x_pos: i32,
+    #[serde(rename = "zPos")]
+    z_pos: i32,
+    #[serde(borrow)]
+    sections: Vec<Section<'a>>,
+}
+
+#[derive(Deserialize)]
+struct Section<'a> {
+    #[serde(rename = "Y")]
+    y: i8,
+    #[serde(borrow)]
+    block_states: BlockStates<'a>,
+}
+
+#[derive(Deserialize)]
+struct BlockStates<'a> {
+    #[serde(borrow)]
+    palette: Vec<BlockState<'a>>,
+    #[serde(default)]
+    data: Vec<i64>,
+}
+
+#[derive(Deserialize)]
+struct BlockState<'a> {
+    #[serde(rename = "Name", borrow)]
+    name: Cow<'a, str>,
+}
+
+pub fn nbt(c: &mut Criterion) {
+    let mut group = c.benchmark_group("nbt");
+
+    let mut buf = vec![];
+    chunk().to_binary(&mut buf, "").unwrap();
+
+    group.bench_function("Compound::from_binary", |b| {
+        b.iter(|| {
+            let mut r = black_box(buf.as_slice());
+            let _ = black_box(Compound::from_binary(&mut r).unwrap());
+        });
+    });
+
+    // Strings and unused fields are copied into the compound first.
+    group.bench_function("Compound::from_binary then deserialize", |b| {
+        b.iter(|| {
+            let mut r = black_box(buf.as_slice());
+            let (c, _) = Compound::from_binary(&mut r).unwrap();
+
+            read(&Chunk::deserialize(c).unwrap());
+        });
+    });
+
+    group.bench_function("from_binary_slice", |b| {
+        b.iter(|| {
+            let mut r = black_box(buf.as_slice());
+            let (chunk, _) = from_binary_slice::<Chunk>(&mut r).unwrap();
+
+            read(&chunk);
+        });
+    });
+}
+
+fn read(chunk: &Chunk) {
+    black_box((chunk.x_pos, chunk.z_pos));
+
+    for section in &chunk.sections {
+        black_box((section.y, &section.block_states.data));
+
+        for state in &section.block_states.palette {
+            black_box(&state.name);
+        }
+    }
+}
+
+/// Builds a chunk shaped like the ones in Anvil files, with fields that the
+/// typed structs don't read.
+fn chunk() -> Compound {
+    let sections = (-4..20)
+        .map(|y| {
+            let palette = ["stone", "dirt", "grass_block", "oak_log", "water", "air"]
+                .into_iter()
+                .map(|name| {
+                    compound! {
+                        "Name" => format!("minecraft:{name}"),
+                        "Properties" => compound! { "axis" => "y", "snowy" => "false" },
+                    }
+                })
+                .collect();
+
+            compound! {
+                "Y" => y as i8,
+                "block_states" => compound! {
+                    "palette" => List::Compound(palette),
+                    "data" => (0..256).map(|i| i * 0x0123_4567_89ab).collect::<Vec<i64>>(),
+                },
+                "biomes" => compound! {
+                    "palette" => List::String(vec!["minecraft:plains".into(), "minecraft:river".into()]),
+                    "data" => vec![0x1111_i64; 1],
+                },
+                "BlockLight" => vec![0_i8; 2048],
+                "SkyLight" => vec![-1_i8; 2048],
+            }
+        })
+        .collect();
+
+    compound! {
+        "DataVersion" => 3465,
+        "xPos" => 3,
+        "zPos" => -7,
+        "yPos" => -4,
+        "Status" => "minecraft:full",
+        "LastUpdate" => 123456_i64,
+        "sections" => List::Compound(sections),
+        "block_entities" => List::Compound(vec![compound! {
+            "id" => "minecraft:chest",
+            "x" => 50, "y" => 64, "z" => -110,
+            "Items" => List::Compound(vec![compound! {
+                "Slot" => 0_i8, "id" => "minecraft:diamond", "Count" => 3_i8,
+            }]),
+        }]),
+        "Heightmaps" => compound! {
+            "MOTION_BLOCKING" => vec![0x0102_0304_i64; 37],
+            "WORLD_SURFACE" => vec![0x0102_0304_i64; 37],
+        },
+        "structures" => compound! { "References" => compound! {}, "starts" => compound! {} },
+    }
+}
@@ -35,4 +35,27 @@ pub fn var_int(c: &mut Criterion) {
             },
         )
     });
+
+    // Mostly small numbers, like the block and biome IDs in chunk data.
+    let nums: Vec<_> = (0..4096)
+        .map(|_| VarInt(rng.gen::<i32>() >> rng.gen_range(0..32)))
+        .collect();
+    let mut buf = vec![];
+
+    group.bench_function("VarInt::encode_slice", |b| {
+        b.iter(|| {
+            buf.clear();
+            VarInt::encode_slice(black_box(&nums), &mut buf).unwrap();
+            black_box(&buf);
+        });
+    });
+
+    group.bench_function("VarInt::decode_slice", |b| {
+        b.iter(|| {
+            let mut r = black_box(buf.as_slice());
+            while !r.is_empty() {
+                black_box(VarInt::decode(&mut r).unwrap());
+            }
+        });
+    });
 }
@@ -35,4 +35,27 @@ pub fn var_long(c: &mut Criterion) {
             },
         )
     });
+
+    // Mostly small numbers, like the block and biome IDs in chunk data.
+    let nums: Vec<_> = (0..4096)
+        .map(|_| VarLong(rng.gen::<i64>() >> rng.gen_range(0..64)))
+        .collect();
+    let mut buf = vec![];
+
+    group.bench_function("VarLong::encode_slice", |b| {
+        b.iter(|| {
+            buf.clear();
+            VarLong::encode_slice(black_box(&nums), &mut buf).unwrap();
+            black_box(&buf);
+        });
+    });
+
+    group.bench_function("VarLong::decode_slice", |b| {
+        b.iter(|| {
+            let mut r = black_box(buf.as_slice());
+            while !r.is_empty() {
+                black_box(VarLong::decode(&mut r).unwrap());
+            }
+        });
+    });
 }
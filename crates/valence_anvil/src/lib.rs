@@ -19,19 +19,14 @@
 
 use std::collections::hash_map::Entry;
 use std::collections::{BTreeMap, HashMap, HashSet};
-use std::fs::File;
-use std::io::{ErrorKind, Read, Seek, SeekFrom};
-use std::num::NonZeroUsize;
+use std::io::ErrorKind;
 use std::path::PathBuf;
-use std::thread;
+use std::time::SystemTime;
+use std::{fs, thread};
 
-use anyhow::{bail, ensure};
 use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
-use byteorder::{BigEndian, ReadBytesExt};
-use flate2::bufread::{GzDecoder, ZlibDecoder};
 use flume::{Receiver, Sender};
-use lru::LruCache;
 use tracing::warn;
 use valence_biome::{BiomeId, BiomeRegistry};
 use valence_client::{Client, OldView, UpdateClientsSet, View};
@@ -40,9 +35,17 @@ use valence_core::ident::Ident;
 use valence_entity::{Location, OldLocation};
 use valence_instance::chunk::UnloadedChunk;
 use valence_instance::Instance;
-use valence_nbt::Compound;
+
+use crate::region::Region;
+pub use crate::region::{AnvilChunk, RegionError, RegionFolder};
+pub use crate::scan::ScannedBlockEntity;
 
 mod parse_chunk;
+pub mod player_data;
+pub mod poi;
+pub mod prune;
+mod region;
+mod scan;
 
 #[derive(Component, Debug)]
 pub struct AnvilLevel {
@@ -56,14 +59,40 @@ pub struct AnvilLevel {
     /// Chunks that need to be loaded. Chunks with `None` priority have already
     /// been sent to the anvil thread.
     pending: HashMap<ChunkPos, Option<Priority>>,
+    /// Chunks the server has modified since they were loaded. Chunks in this
+    /// set are handled according to the [`ReloadConflictPolicy`] passed to
+    /// [`AnvilLevel::reload_changed`].
+    ///
+    /// Valence does not track modifications automatically, so positions must
+    /// be inserted here by whatever modifies the chunks. A position is
+    /// removed when its chunk is unloaded or reloaded.
+    pub modified_chunks: HashSet<ChunkPos>,
+    /// Path to the "region" subdirectory in the world root.
+    region_root: PathBuf,
+    /// The timestamps of the loaded chunks, as found in the region file when
+    /// they were loaded.
+    loaded_timestamps: HashMap<ChunkPos, u32>,
+    /// The modification times of region files when they were last checked by
+    /// [`AnvilLevel::reload_changed`].
+    region_mtimes: HashMap<(i32, i32), SystemTime>,
     /// Sender for the chunk worker thread.
-    sender: Sender<ChunkPos>,
+    sender: Sender<WorkerRequest>,
     /// Receiver for the chunk worker thread.
     receiver: Receiver<(ChunkPos, WorkerResult)>,
 }
 
 type WorkerResult = anyhow::Result<Option<(UnloadedChunk, u32)>>;
 
+/// A request sent to the chunk worker thread.
+#[derive(Copy, Clone, Debug)]
+enum WorkerRequest {
+    /// Load the chunk at the position.
+    Load(ChunkPos),
+    /// Load the chunk at the position, discarding any cached region file data
+    /// first.
+    Reload(ChunkPos),
+}
+
 impl AnvilLevel {
     pub fn new(world_root: impl Into<PathBuf>, biomes: &BiomeRegistry) -> Self {
         let mut region_root = world_root.into();
@@ -74,11 +103,9 @@ impl AnvilLevel {
 
         Self {
             worker_state: Some(ChunkWorkerState {
-                regions: LruCache::new(LRU_CACHE_SIZE),
-                region_root,
+                region_folder: RegionFolder::new(region_root.clone()),
                 sender: finished_sender,
                 receiver: pending_receiver,
-                decompress_buf: vec![],
                 biome_to_id: biomes
                     .iter()
                     .map(|(id, name, _)| (name.to_string_ident(), id))
@@ -86,6 +113,10 @@ impl AnvilLevel {
             }),
             ignored_chunks: HashSet::new(),
             pending: HashMap::new(),
+            modified_chunks: HashSet::new(),
+            region_root,
+            loaded_timestamps: HashMap::new(),
+            region_mtimes: HashMap::new(),
             sender: pending_sender,
             receiver: finished_receiver,
         }
@@ -111,164 +142,111 @@ impl AnvilLevel {
             }
         }
     }
-}
-
-const LRU_CACHE_SIZE: NonZeroUsize = match NonZeroUsize::new(256) {
-    Some(n) => n,
-    None => unreachable!(),
-};
-
-/// The order in which chunks should be processed by the anvil worker. Smaller
-/// values are sent first.
-type Priority = u64;
 
-#[derive(Debug)]
-struct ChunkWorkerState {
-    /// Region files. An LRU cache is used to limit the number of open file
-    /// handles.
-    regions: LruCache<RegionPos, RegionEntry>,
-    /// Path to the "region" subdirectory in the world root.
-    region_root: PathBuf,
-    /// Sender of finished chunks.
-    sender: Sender<(ChunkPos, WorkerResult)>,
-    /// Receiver of pending chunks.
-    receiver: Receiver<ChunkPos>,
-    /// Scratch buffer for decompression.
-    decompress_buf: Vec<u8>,
-    /// Mapping of biome names to their biome ID.
-    biome_to_id: BTreeMap<Ident<String>, BiomeId>,
-}
-
-impl ChunkWorkerState {
-    fn get_chunk(&mut self, pos: ChunkPos) -> anyhow::Result<Option<AnvilChunk>> {
-        let region_x = pos.x.div_euclid(32);
-        let region_z = pos.z.div_euclid(32);
-
-        let region = match self.regions.get_mut(&(region_x, region_z)) {
-            Some(RegionEntry::Occupied(region)) => region,
-            Some(RegionEntry::Vacant) => return Ok(None),
-            None => {
-                let path = self
-                    .region_root
-                    .join(format!("r.{region_x}.{region_z}.mca"));
-
-                let mut file = match File::options().read(true).write(true).open(path) {
-                    Ok(file) => file,
-                    Err(e) if e.kind() == ErrorKind::NotFound => {
-                        self.regions.put((region_x, region_z), RegionEntry::Vacant);
-                        return Ok(None);
-                    }
-                    Err(e) => return Err(e.into()),
-                };
-
-                let mut header = [0; SECTOR_SIZE * 2];
-
-                file.read_exact(&mut header)?;
-
-                // TODO: this is ugly.
-                let res = self.regions.get_or_insert_mut((region_x, region_z), || {
-                    RegionEntry::Occupied(Region { file, header })
-                });
-
-                match res {
-                    RegionEntry::Occupied(r) => r,
-                    RegionEntry::Vacant => unreachable!(),
-                }
+    /// Reloads the chunks in `instance` which have changed on disk since they
+    /// were loaded. This is useful when the world is being edited by another
+    /// program while the server is running.
+    ///
+    /// Region files whose modification time hasn't changed since the last
+    /// call are skipped. For the rest, the timestamp of every loaded chunk in
+    /// the region header is compared against the timestamp the chunk had when
+    /// it was loaded. Changed chunks are read again on the anvil thread and
+    /// replace the chunks in the instance, which causes them to be resent to
+    /// clients in view. Chunks which were deleted from the region file are
+    /// left alone.
+    ///
+    /// Chunks in [`AnvilLevel::modified_chunks`] are skipped or overwritten
+    /// depending on `policy`.
+    ///
+    /// Returns the number of chunks queued for reloading. A
+    /// [`ChunkLoadEvent`] is sent once each of them has been reloaded.
+    pub fn reload_changed(
+        &mut self,
+        instance: &Instance,
+        policy: ReloadConflictPolicy,
+    ) -> Result<usize, RegionError> {
+        let mut by_region = BTreeMap::<_, Vec<_>>::new();
+
+        for (pos, _) in instance.chunks() {
+            if let Some(&timestamp) = self.loaded_timestamps.get(&pos) {
+                by_region
+                    .entry((pos.x.div_euclid(32), pos.z.div_euclid(32)))
+                    .or_default()
+                    .push((pos, timestamp));
             }
-        };
-
-        let chunk_idx = (pos.x.rem_euclid(32) + pos.z.rem_euclid(32) * 32) as usize;
-
-        let location_bytes = (&region.header[chunk_idx * 4..]).read_u32::<BigEndian>()?;
-        let timestamp = (&region.header[chunk_idx * 4 + SECTOR_SIZE..]).read_u32::<BigEndian>()?;
-
-        if location_bytes == 0 {
-            // No chunk exists at this position.
-            return Ok(None);
         }
 
-        let sector_offset = (location_bytes >> 8) as u64;
-        let sector_count = (location_bytes & 0xff) as usize;
+        let mut queued = 0;
 
-        // If the sector offset was <2, then the chunk data would be inside the region
-        // header. That doesn't make any sense.
-        ensure!(sector_offset >= 2, "invalid chunk sector offset");
+        for ((region_x, region_z), chunks) in by_region {
+            let path = self
+                .region_root
+                .join(format!("r.{region_x}.{region_z}.mca"));
 
-        // Seek to the beginning of the chunk's data.
-        region
-            .file
-            .seek(SeekFrom::Start(sector_offset * SECTOR_SIZE as u64))?;
+            let mtime = match fs::metadata(&path) {
+                Ok(meta) => meta.modified()?,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
 
-        let exact_chunk_size = region.file.read_u32::<BigEndian>()? as usize;
+            if self.region_mtimes.insert((region_x, region_z), mtime) == Some(mtime) {
+                continue;
+            }
 
-        // size of this chunk in sectors must always be >= the exact size.
-        ensure!(
-            sector_count * SECTOR_SIZE >= exact_chunk_size,
-            "invalid chunk size"
-        );
+            let Some(region) = Region::open(path)? else {
+                continue;
+            };
 
-        let mut data_buf = vec![0; exact_chunk_size].into_boxed_slice();
-        region.file.read_exact(&mut data_buf)?;
+            for (pos, timestamp) in chunks {
+                match region.chunk_timestamp(pos) {
+                    Some(t) if t != timestamp => {}
+                    _ => continue,
+                }
 
-        let mut r = data_buf.as_ref();
+                if policy == ReloadConflictPolicy::Skip && self.modified_chunks.contains(&pos) {
+                    continue;
+                }
 
-        self.decompress_buf.clear();
+                // Don't report the same change twice if this is called again before the chunk
+                // finishes loading.
+                self.loaded_timestamps.remove(&pos);
 
-        // What compression does the chunk use?
-        let mut nbt_slice = match r.read_u8()? {
-            // GZip
-            1 => {
-                let mut z = GzDecoder::new(r);
-                z.read_to_end(&mut self.decompress_buf)?;
-                self.decompress_buf.as_slice()
-            }
-            // Zlib
-            2 => {
-                let mut z = ZlibDecoder::new(r);
-                z.read_to_end(&mut self.decompress_buf)?;
-                self.decompress_buf.as_slice()
+                let _ = self.sender.send(WorkerRequest::Reload(pos));
+                queued += 1;
             }
-            // Uncompressed
-            3 => r,
-            // Unknown
-            b => bail!("unknown compression scheme number of {b}"),
-        };
-
-        let (data, _) = Compound::from_binary(&mut nbt_slice)?;
-
-        ensure!(nbt_slice.is_empty(), "not all chunk NBT data was read");
+        }
 
-        Ok(Some(AnvilChunk { data, timestamp }))
+        Ok(queued)
     }
 }
 
-struct AnvilChunk {
-    data: Compound,
-    timestamp: u32,
+/// What [`AnvilLevel::reload_changed`] should do with chunks that have changed
+/// on disk but were also modified by the server.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum ReloadConflictPolicy {
+    /// Keep the server's version of the chunk.
+    #[default]
+    Skip,
+    /// Replace the server's version of the chunk with the version on disk.
+    Overwrite,
 }
 
-/// X and Z positions of a region.
-type RegionPos = (i32, i32);
-
-#[allow(clippy::large_enum_variant)] // We're not moving this around.
-#[derive(Debug)]
-enum RegionEntry {
-    /// There is a region file loaded here.
-    Occupied(Region),
-    /// There is no region file at this position. Don't try to read it from the
-    /// filesystem again.
-    Vacant,
-}
+/// The order in which chunks should be processed by the anvil worker. Smaller
+/// values are sent first.
+type Priority = u64;
 
 #[derive(Debug)]
-struct Region {
-    file: File,
-    /// The first 8 KiB in the file.
-    header: [u8; SECTOR_SIZE * 2],
+struct ChunkWorkerState {
+    /// The "region" subdirectory in the world root.
+    region_folder: RegionFolder,
+    /// Sender of finished chunks.
+    sender: Sender<(ChunkPos, WorkerResult)>,
+    /// Receiver of pending chunks.
+    receiver: Receiver<WorkerRequest>,
+    /// Mapping of biome names to their biome ID.
+    biome_to_id: BTreeMap<Ident<String>, BiomeId>,
 }
 
-const SECTOR_SIZE: usize = 4096;
-
 pub struct AnvilPlugin;
 
 impl Plugin for AnvilPlugin {
@@ -298,14 +276,19 @@ fn init_anvil(mut query: Query<&mut AnvilLevel, (Added<AnvilLevel>, With<Instanc
 /// This needs to run in `PreUpdate` where the chunk viewer counts have been
 /// updated from the previous tick.
 fn remove_unviewed_chunks(
-    mut instances: Query<(Entity, &mut Instance, &AnvilLevel)>,
+    mut instances: Query<(Entity, &mut Instance, &mut AnvilLevel)>,
     mut unload_events: EventWriter<ChunkUnloadEvent>,
 ) {
     for (entity, mut inst, anvil) in &mut instances {
+        let anvil = anvil.into_inner();
+
         inst.retain_chunks(|pos, chunk| {
             if chunk.is_viewed_mut() || anvil.ignored_chunks.contains(&pos) {
                 true
             } else {
+                anvil.loaded_timestamps.remove(&pos);
+                anvil.modified_chunks.remove(&pos);
+
                 unload_events.send(ChunkUnloadEvent {
                     instance: entity,
                     pos,
@@ -326,7 +309,7 @@ fn update_client_views(
 
         if loc != &*old_loc || view != old_view || old_loc.is_added() {
             let Ok((inst, mut anvil)) = instances.get_mut(loc.0) else {
-                continue
+                continue;
             };
 
             let queue_pos = |pos| {
@@ -368,11 +351,17 @@ fn send_recv_chunks(
         // Insert the chunks that are finished loading into the instance and send load
         // events.
         for (pos, res) in anvil.receiver.drain() {
-            anvil.pending.remove(&pos);
+            // Chunks that aren't pending were reloaded by `reload_changed`. Don't bring
+            // them back if they were unloaded in the meantime.
+            if anvil.pending.remove(&pos).is_none() && inst.chunk(pos).is_none() {
+                continue;
+            }
 
             let status = match res {
                 Ok(Some((chunk, timestamp))) => {
                     inst.insert_chunk(pos, chunk);
+                    anvil.loaded_timestamps.insert(pos, timestamp);
+                    anvil.modified_chunks.remove(&pos);
                     ChunkLoadStatus::Success { timestamp }
                 }
                 Ok(None) => ChunkLoadStatus::Empty,
@@ -398,20 +387,28 @@ fn send_recv_chunks(
 
         // Send the sorted chunks to be loaded.
         for (_, pos) in to_send.drain(..) {
-            let _ = anvil.sender.try_send(pos);
+            let _ = anvil.sender.try_send(WorkerRequest::Load(pos));
         }
     }
 }
 
 fn anvil_worker(mut state: ChunkWorkerState) {
-    while let Ok(pos) = state.receiver.recv() {
+    while let Ok(req) = state.receiver.recv() {
+        let pos = match req {
+            WorkerRequest::Load(pos) => pos,
+            WorkerRequest::Reload(pos) => {
+                state.region_folder.forget_region(pos);
+                pos
+            }
+        };
+
         let res = get_chunk(pos, &mut state);
 
         let _ = state.sender.send((pos, res));
     }
 
     fn get_chunk(pos: ChunkPos, state: &mut ChunkWorkerState) -> WorkerResult {
-        let Some(anvil_chunk) = state.get_chunk(pos)? else {
+        let Some(anvil_chunk) = state.region_folder.get_chunk(pos)? else {
             return Ok(None);
         };
 
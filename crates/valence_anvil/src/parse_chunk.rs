@@ -1,12 +1,12 @@
 use std::borrow::Cow;
 use std::collections::BTreeMap;
 
-use num_integer::div_ceil;
 use thiserror::Error;
 use valence_biome::BiomeId;
 use valence_block::{BlockKind, PropName, PropValue};
 use valence_core::ident::Ident;
 use valence_instance::chunk::{Chunk, UnloadedChunk};
+use valence_nbt::packed::PackedLayout;
 use valence_nbt::{Compound, List, Value};
 
 #[derive(Clone, Debug, Error)]
@@ -69,7 +69,7 @@ pub(crate) fn parse_chunk(
     biome_map: &BTreeMap<Ident<String>, BiomeId>, // TODO: replace with biome registry arg.
 ) -> Result<UnloadedChunk, ParseChunkError> {
     let Some(Value::List(List::Compound(sections))) = nbt.remove("sections") else {
-        return Err(ParseChunkError::MissingSections)
+        return Err(ParseChunkError::MissingSections);
     };
 
     if sections.is_empty() {
@@ -96,7 +96,7 @@ pub(crate) fn parse_chunk(
 
     for mut section in sections {
         let Some(Value::Byte(sect_y)) = section.remove("Y") else {
-            return Err(ParseChunkError::MissingSectionY)
+            return Err(ParseChunkError::MissingSectionY);
         };
 
         let sect_y = (sect_y as i32 - min_sect_y) as u32;
@@ -106,11 +106,11 @@ pub(crate) fn parse_chunk(
         }
 
         let Some(Value::Compound(mut block_states)) = section.remove("block_states") else {
-            return Err(ParseChunkError::MissingBlockStates)
+            return Err(ParseChunkError::MissingBlockStates);
         };
 
         let Some(Value::List(List::Compound(palette))) = block_states.remove("palette") else {
-            return Err(ParseChunkError::MissingBlockPalette)
+            return Err(ParseChunkError::MissingBlockPalette);
         };
 
         if !(1..BLOCKS_PER_SECTION).contains(&palette.len()) {
@@ -121,11 +121,11 @@ pub(crate) fn parse_chunk(
 
         for mut block in palette {
             let Some(Value::String(name)) = block.remove("Name") else {
-                return Err(ParseChunkError::MissingBlockName)
+                return Err(ParseChunkError::MissingBlockName);
             };
 
             let Some(block_kind) = BlockKind::from_str(ident_path(&name)) else {
-                return Err(ParseChunkError::UnknownBlockName(name))
+                return Err(ParseChunkError::UnknownBlockName(name));
             };
 
             let mut state = block_kind.to_state();
@@ -133,15 +133,15 @@ pub(crate) fn parse_chunk(
             if let Some(Value::Compound(properties)) = block.remove("Properties") {
                 for (key, value) in properties {
                     let Value::String(value) = value else {
-                        return Err(ParseChunkError::BadPropValueType)
+                        return Err(ParseChunkError::BadPropValueType);
                     };
 
                     let Some(prop_name) = PropName::from_str(&key) else {
-                        return Err(ParseChunkError::UnknownPropName(key))
+                        return Err(ParseChunkError::UnknownPropName(key));
                     };
 
                     let Some(prop_value) = PropValue::from_str(&value) else {
-                        return Err(ParseChunkError::UnknownPropValue(value))
+                        return Err(ParseChunkError::UnknownPropValue(value));
                     };
 
                     state = state.set(prop_name, prop_value);
@@ -156,51 +156,40 @@ pub(crate) fn parse_chunk(
         } else {
             debug_assert!(converted_block_palette.len() > 1);
 
-            let Some(Value::LongArray(data)) = block_states.remove("data") else {
-                return Err(ParseChunkError::MissingBlockStateData)
+            let Some(data @ Value::LongArray(longs)) = block_states.get("data") else {
+                return Err(ParseChunkError::MissingBlockStateData);
             };
 
             let bits_per_idx = bit_width(converted_block_palette.len() - 1).max(4);
-            let idxs_per_long = 64 / bits_per_idx;
-            let long_count = div_ceil(BLOCKS_PER_SECTION, idxs_per_long);
-            let mask = 2_u64.pow(bits_per_idx as u32) - 1;
 
-            if long_count != data.len() {
+            if PackedLayout::Padded.long_count(bits_per_idx, BLOCKS_PER_SECTION) != longs.len() {
                 return Err(ParseChunkError::BadBlockLongCount);
             };
 
-            let mut i: u32 = 0;
-            for long in data {
-                let u64 = long as u64;
+            let idxs = data
+                .as_packed_bits(bits_per_idx, PackedLayout::Padded)
+                .expect("data is a long array")
+                .take(BLOCKS_PER_SECTION);
 
-                for j in 0..idxs_per_long {
-                    if i >= BLOCKS_PER_SECTION as u32 {
-                        break;
-                    }
+            for (i, idx) in (0..).zip(idxs) {
+                let Some(block) = converted_block_palette.get(idx as usize).cloned() else {
+                    return Err(ParseChunkError::BadBlockPaletteIndex);
+                };
 
-                    let idx = (u64 >> (bits_per_idx * j)) & mask;
+                let x = i % 16;
+                let z = i / 16 % 16;
+                let y = i / (16 * 16);
 
-                    let Some(block) = converted_block_palette.get(idx as usize).cloned() else {
-                        return Err(ParseChunkError::BadBlockPaletteIndex)
-                    };
-
-                    let x = i % 16;
-                    let z = i / 16 % 16;
-                    let y = i / (16 * 16);
-
-                    chunk.set_block_state(x, sect_y * 16 + y, z, block);
-
-                    i += 1;
-                }
+                chunk.set_block_state(x, sect_y * 16 + y, z, block);
             }
         }
 
         let Some(Value::Compound(biomes)) = section.get("biomes") else {
-            return Err(ParseChunkError::MissingBiomes)
+            return Err(ParseChunkError::MissingBiomes);
         };
 
         let Some(Value::List(List::String(palette))) = biomes.get("palette") else {
-            return Err(ParseChunkError::MissingBiomePalette)
+            return Err(ParseChunkError::MissingBiomePalette);
         };
 
         if !(1..BIOMES_PER_SECTION).contains(&palette.len()) {
@@ -211,7 +200,7 @@ pub(crate) fn parse_chunk(
 
         for biome_name in palette {
             let Ok(ident) = Ident::<Cow<str>>::new(biome_name) else {
-                return Err(ParseChunkError::BadBiomeName)
+                return Err(ParseChunkError::BadBiomeName);
             };
 
             converted_biome_palette
@@ -223,42 +212,31 @@ pub(crate) fn parse_chunk(
         } else {
             debug_assert!(converted_biome_palette.len() > 1);
 
-            let Some(Value::LongArray(data)) = biomes.get("data") else {
-                return Err(ParseChunkError::MissingBiomeData)
+            let Some(data @ Value::LongArray(longs)) = biomes.get("data") else {
+                return Err(ParseChunkError::MissingBiomeData);
             };
 
             let bits_per_idx = bit_width(converted_biome_palette.len() - 1);
-            let idxs_per_long = 64 / bits_per_idx;
-            let long_count = div_ceil(BIOMES_PER_SECTION, idxs_per_long);
-            let mask = 2_u64.pow(bits_per_idx as u32) - 1;
 
-            if long_count != data.len() {
+            if PackedLayout::Padded.long_count(bits_per_idx, BIOMES_PER_SECTION) != longs.len() {
                 return Err(ParseChunkError::BadBiomeLongCount);
             };
 
-            let mut i: u32 = 0;
-            for &long in data.iter() {
-                let u64 = long as u64;
+            let idxs = data
+                .as_packed_bits(bits_per_idx, PackedLayout::Padded)
+                .expect("data is a long array")
+                .take(BIOMES_PER_SECTION);
 
-                for j in 0..idxs_per_long {
-                    if i >= BIOMES_PER_SECTION as u32 {
-                        break;
-                    }
+            for (i, idx) in (0..).zip(idxs) {
+                let Some(biome) = converted_biome_palette.get(idx as usize).cloned() else {
+                    return Err(ParseChunkError::BadBiomePaletteIndex);
+                };
 
-                    let idx = (u64 >> (bits_per_idx * j)) & mask;
+                let x = i % 4;
+                let z = i / 4 % 4;
+                let y = i / (4 * 4);
 
-                    let Some(biome) = converted_biome_palette.get(idx as usize).cloned() else {
-                        return Err(ParseChunkError::BadBiomePaletteIndex)
-                    };
-
-                    let x = i % 4;
-                    let z = i / 4 % 4;
-                    let y = i / (4 * 4);
-
-                    chunk.set_biome(x, sect_y * 4 + y, z, biome);
-
-                    i += 1;
-                }
+                chunk.set_biome(x, sect_y * 4 + y, z, biome);
             }
         }
     }
@@ -322,6 +300,6 @@ fn ident_path(ident: &str) -> &str {
 }
 
 /// Returns the minimum number of bits needed to represent the integer `n`.
-const fn bit_width(n: usize) -> usize {
-    (usize::BITS - n.leading_zeros()) as _
+const fn bit_width(n: usize) -> u32 {
+    usize::BITS - n.leading_zeros()
 }
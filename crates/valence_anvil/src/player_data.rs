@@ -0,0 +1,540 @@
+//! Reading and writing player data stored in the `playerdata` directory of an
+//! anvil world.
+//!
+//! Each player's data is stored in a gzip compressed NBT file named
+//! `<uuid>.dat`. The commonly used fields are exposed on [`PlayerData`], while
+//! every other field is kept in [`PlayerData::extra`] so that nothing is lost
+//! across a read-modify-write cycle.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::PathBuf;
+
+use flate2::bufread::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use glam::DVec3;
+use thiserror::Error;
+use uuid::Uuid;
+use valence_core::ident;
+use valence_core::ident::Ident;
+use valence_core::item::{ItemKind, ItemNbtError, ItemStack};
+use valence_nbt::{Compound, List, Value};
+
+/// The `playerdata` directory of an anvil world.
+#[derive(Clone, Debug)]
+pub struct PlayerDataFolder {
+    /// Path to the "playerdata" subdirectory in the world root.
+    player_data_root: PathBuf,
+}
+
+impl PlayerDataFolder {
+    pub fn new(world_root: impl Into<PathBuf>) -> Self {
+        let mut player_data_root = world_root.into();
+        player_data_root.push("playerdata");
+
+        Self { player_data_root }
+    }
+
+    /// Reads the data of the player with the given UUID. Returns `Ok(None)` if
+    /// the player has no data file.
+    pub fn read(&self, uuid: Uuid) -> Result<Option<PlayerData>, PlayerDataError> {
+        let path = self.player_data_root.join(format!("{uuid}.dat"));
+
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut buf = vec![];
+        GzDecoder::new(BufReader::new(file)).read_to_end(&mut buf)?;
+
+        let (nbt, _) = Compound::from_binary(&mut buf.as_slice())?;
+
+        PlayerData::from_nbt(nbt).map(Some)
+    }
+
+    /// Writes the data of the player with the given UUID, creating the
+    /// `playerdata` directory if it does not exist.
+    ///
+    /// The data is first written to a temporary file which then replaces the
+    /// old file, so an interrupted write never leaves a truncated file
+    /// behind.
+    pub fn write(&self, uuid: Uuid, data: &PlayerData) -> Result<(), PlayerDataError> {
+        std::fs::create_dir_all(&self.player_data_root)?;
+
+        let path = self.player_data_root.join(format!("{uuid}.dat"));
+        let tmp_path = self.player_data_root.join(format!("{uuid}.dat_tmp"));
+
+        let mut z = GzEncoder::new(
+            BufWriter::new(File::create(&tmp_path)?),
+            Compression::default(),
+        );
+        data.to_nbt().to_binary(&mut z, "")?;
+        z.finish()?.flush()?;
+
+        std::fs::rename(tmp_path, path)?;
+
+        Ok(())
+    }
+}
+
+/// The contents of a player data file.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PlayerData {
+    /// The items in the player's inventory, keyed by their slot in the player
+    /// inventory window. This uses the same slot indices as a player's
+    /// `Inventory` in `valence_inventory`, so armor is in slots 5-8 (head to
+    /// feet), the hotbar is in slots 36-44 and the off hand is slot 45.
+    pub inventory: BTreeMap<u16, ItemStack>,
+    /// The position of the player.
+    pub position: DVec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// The dimension the player is in, such as `minecraft:overworld`.
+    pub dimension: Ident<String>,
+    pub xp_level: i32,
+    /// Progress towards the next level, in the range `0.0..1.0`.
+    pub xp_progress: f32,
+    pub xp_total: i32,
+    pub health: f32,
+    pub food_level: i32,
+    pub food_saturation: f32,
+    /// All the fields of the player data which are not covered by the other
+    /// fields of this struct.
+    pub extra: Compound,
+}
+
+impl Default for PlayerData {
+    fn default() -> Self {
+        Self {
+            inventory: BTreeMap::new(),
+            position: DVec3::ZERO,
+            yaw: 0.0,
+            pitch: 0.0,
+            dimension: ident!("overworld").into(),
+            xp_level: 0,
+            xp_progress: 0.0,
+            xp_total: 0,
+            health: 20.0,
+            food_level: 20,
+            food_saturation: 5.0,
+            extra: Compound::new(),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum PlayerDataError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Nbt(#[from] valence_nbt::binary::Error),
+    #[error("player data field \"{0}\" has an unexpected type")]
+    BadFieldType(&'static str),
+    #[error("invalid dimension name of \"{0}\"")]
+    InvalidDimension(String),
+    #[error("invalid inventory slot of {0}")]
+    InvalidSlot(i8),
+    #[error(transparent)]
+    Item(#[from] ItemNbtError),
+}
+
+impl PlayerData {
+    /// Parses player data from the root compound of a player data file. Fields
+    /// that are absent take their default values.
+    pub fn from_nbt(mut nbt: Compound) -> Result<Self, PlayerDataError> {
+        let mut data = Self::default();
+
+        match nbt.remove("Inventory") {
+            Some(Value::List(List::Compound(items))) => {
+                for item in items {
+                    let (slot, stack) = item_from_nbt(item)?;
+                    data.inventory.insert(slot, stack);
+                }
+            }
+            Some(Value::List(List::End)) | None => {}
+            Some(_) => return Err(PlayerDataError::BadFieldType("Inventory")),
+        }
+
+        match nbt.remove("Pos") {
+            Some(Value::List(List::Double(pos))) if pos.len() == 3 => {
+                data.position = DVec3::new(pos[0], pos[1], pos[2]);
+            }
+            None => {}
+            Some(_) => return Err(PlayerDataError::BadFieldType("Pos")),
+        }
+
+        match nbt.remove("Rotation") {
+            Some(Value::List(List::Float(rot))) if rot.len() == 2 => {
+                data.yaw = rot[0];
+                data.pitch = rot[1];
+            }
+            None => {}
+            Some(_) => return Err(PlayerDataError::BadFieldType("Rotation")),
+        }
+
+        match nbt.remove("Dimension") {
+            Some(Value::String(dim)) => match Ident::new(dim) {
+                Ok(dim) => data.dimension = dim.into(),
+                Err(e) => return Err(PlayerDataError::InvalidDimension(e.0)),
+            },
+            None => {}
+            Some(_) => return Err(PlayerDataError::BadFieldType("Dimension")),
+        }
+
+        take_field(&mut nbt, "XpLevel", Value::into_int, &mut data.xp_level)?;
+        take_field(&mut nbt, "XpP", Value::into_float, &mut data.xp_progress)?;
+        take_field(&mut nbt, "XpTotal", Value::into_int, &mut data.xp_total)?;
+        take_field(&mut nbt, "Health", Value::into_float, &mut data.health)?;
+        take_field(&mut nbt, "foodLevel", Value::into_int, &mut data.food_level)?;
+        take_field(
+            &mut nbt,
+            "foodSaturationLevel",
+            Value::into_float,
+            &mut data.food_saturation,
+        )?;
+
+        data.extra = nbt;
+
+        Ok(data)
+    }
+
+    /// Converts this player data into the root compound of a player data
+    /// file.
+    pub fn to_nbt(&self) -> Compound {
+        let mut nbt = self.extra.clone();
+
+        nbt.insert(
+            "Inventory",
+            List::Compound(
+                self.inventory
+                    .iter()
+                    .filter_map(|(&slot, stack)| item_to_nbt(slot, stack))
+                    .collect(),
+            ),
+        );
+        nbt.insert(
+            "Pos",
+            List::Double(vec![self.position.x, self.position.y, self.position.z]),
+        );
+        nbt.insert("Rotation", List::Float(vec![self.yaw, self.pitch]));
+        nbt.insert("Dimension", self.dimension.as_str());
+        nbt.insert("XpLevel", self.xp_level);
+        nbt.insert("XpP", self.xp_progress);
+        nbt.insert("XpTotal", self.xp_total);
+        nbt.insert("Health", self.health);
+        nbt.insert("foodLevel", self.food_level);
+        nbt.insert("foodSaturationLevel", self.food_saturation);
+
+        nbt
+    }
+}
+
+/// Removes a field from `nbt` and stores it in `dest` if it is present.
+fn take_field<T>(
+    nbt: &mut Compound,
+    key: &'static str,
+    into: fn(Value) -> Option<T>,
+    dest: &mut T,
+) -> Result<(), PlayerDataError> {
+    if let Some(value) = nbt.remove(key) {
+        *dest = into(value).ok_or(PlayerDataError::BadFieldType(key))?;
+    }
+
+    Ok(())
+}
+
+/// Converts a slot number used in player data files to a slot in the player
+/// inventory window.
+fn player_data_slot_to_inventory_slot(slot: i8) -> Option<u16> {
+    match slot {
+        // Hotbar
+        0..=8 => Some(slot as u16 + 36),
+        // Main inventory
+        9..=35 => Some(slot as u16),
+        // Armor, from feet to head.
+        100..=103 => Some(108 - slot as u16),
+        // Off hand
+        -106 => Some(45),
+        _ => None,
+    }
+}
+
+/// The inverse of [`player_data_slot_to_inventory_slot`].
+fn inventory_slot_to_player_data_slot(slot: u16) -> Option<i8> {
+    match slot {
+        5..=8 => Some(108 - slot as i8),
+        9..=35 => Some(slot as i8),
+        36..=44 => Some(slot as i8 - 36),
+        45 => Some(-106),
+        _ => None,
+    }
+}
+
+fn item_from_nbt(nbt: Compound) -> Result<(u16, ItemStack), PlayerDataError> {
+    let slot = match nbt.get("Slot") {
+        Some(&Value::Byte(slot)) => slot,
+        _ => return Err(PlayerDataError::BadFieldType("Slot")),
+    };
+
+    let Some(inv_slot) = player_data_slot_to_inventory_slot(slot) else {
+        return Err(PlayerDataError::InvalidSlot(slot));
+    };
+
+    Ok((inv_slot, ItemStack::from_nbt(&nbt)?))
+}
+
+/// Returns `None` if the stack is air or the slot cannot be represented in a
+/// player data file, such as the crafting grid slots.
+fn item_to_nbt(slot: u16, stack: &ItemStack) -> Option<Compound> {
+    if stack.item == ItemKind::Air {
+        return None;
+    }
+
+    let mut nbt = stack.to_nbt();
+    nbt.insert("Slot", inventory_slot_to_player_data_slot(slot)?);
+
+    Some(nbt)
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_nbt::compound;
+
+    use super::*;
+
+    /// A player data file written by hand to follow the fields that a vanilla
+    /// 1.20.1 server writes, plus a plugin field that valence doesn't know. It
+    /// was not captured from a server.
+    fn hand_written_player_nbt() -> Compound {
+        compound! {
+            "AbsorptionAmount" => 0.0_f32,
+            "Air" => 300_i16,
+            "Attributes" => List::Compound(vec![compound! {
+                "Base" => 0.10000000149011612_f64,
+                "Name" => "minecraft:generic.movement_speed",
+            }]),
+            "Brain" => compound! { "memories" => Compound::new() },
+            "BukkitValues" => compound! {
+                "myplugin:kills" => 7,
+                "myplugin:title" => "Nether Explorer",
+            },
+            "DataVersion" => 3465,
+            "DeathTime" => 0_i16,
+            "Dimension" => "minecraft:the_nether",
+            "EnderItems" => List::End,
+            "FallDistance" => 0.0_f32,
+            "FallFlying" => 0_i8,
+            "Fire" => -20_i16,
+            "Health" => 17.5_f32,
+            "HurtByTimestamp" => 0,
+            "HurtTime" => 0_i16,
+            "Inventory" => List::Compound(vec![
+                compound! {
+                    "Count" => 1_i8,
+                    "Slot" => 0_i8,
+                    "id" => "minecraft:diamond_sword",
+                    "tag" => compound! {
+                        "Damage" => 12,
+                        "Enchantments" => List::Compound(vec![compound! {
+                            "id" => "minecraft:sharpness",
+                            "lvl" => 3_i16,
+                        }]),
+                    },
+                },
+                compound! {
+                    "Count" => 64_i8,
+                    "Slot" => 8_i8,
+                    "id" => "minecraft:cobblestone",
+                },
+                compound! {
+                    "Count" => 12_i8,
+                    "Slot" => 20_i8,
+                    "id" => "minecraft:bread",
+                },
+                compound! {
+                    "Count" => 1_i8,
+                    "Slot" => 100_i8,
+                    "id" => "minecraft:iron_boots",
+                    "tag" => compound! { "Damage" => 3 },
+                },
+                compound! {
+                    "Count" => 1_i8,
+                    "Slot" => 103_i8,
+                    "id" => "minecraft:turtle_helmet",
+                    "tag" => compound! { "Damage" => 0 },
+                },
+                compound! {
+                    "Count" => 1_i8,
+                    "Slot" => -106_i8,
+                    "id" => "minecraft:shield",
+                    "tag" => compound! { "Damage" => 0 },
+                },
+            ]),
+            "Invulnerable" => 0_i8,
+            "LastDeathLocation" => compound! {
+                "dimension" => "minecraft:overworld",
+                "pos" => vec![12, 70, -5],
+            },
+            "Motion" => List::Double(vec![0.0, -0.0784000015258789, 0.0]),
+            "OnGround" => 1_i8,
+            "PortalCooldown" => 0,
+            "Pos" => List::Double(vec![-41.5, 64.0, 127.30000001192093]),
+            "Rotation" => List::Float(vec![-93.75, 12.5]),
+            "Score" => 31,
+            "SelectedItemSlot" => 0,
+            "SleepTimer" => 0_i16,
+            "UUID" => vec![-1017466049, 2008632549, -1780451036, -1543208453],
+            "XpLevel" => 3,
+            "XpP" => 0.4117647_f32,
+            "XpSeed" => -1306925455,
+            "XpTotal" => 31,
+            "abilities" => compound! {
+                "flySpeed" => 0.05_f32,
+                "flying" => 0_i8,
+                "instabuild" => 0_i8,
+                "invulnerable" => 0_i8,
+                "mayBuild" => 1_i8,
+                "mayfly" => 0_i8,
+                "walkSpeed" => 0.1_f32,
+            },
+            "foodExhaustionLevel" => 1.2_f32,
+            "foodLevel" => 18,
+            "foodSaturationLevel" => 0.0_f32,
+            "foodTickTimer" => 0,
+            "playerGameType" => 0,
+            "recipeBook" => compound! {
+                "isFilteringCraftable" => 0_i8,
+                "isGuiOpen" => 0_i8,
+                "recipes" => List::String(vec!["minecraft:crafting_table".into()]),
+                "toBeDisplayed" => List::End,
+            },
+            "seenCredits" => 0_i8,
+        }
+    }
+
+    /// Encodes a single field of a compound, so that fields can be compared
+    /// byte for byte.
+    fn field_bytes(nbt: &Compound, key: &str) -> Vec<u8> {
+        let mut field = Compound::new();
+
+        if let Some(value) = nbt.get(key) {
+            field.insert(key, value.clone());
+        }
+
+        let mut buf = vec![];
+        field.to_binary(&mut buf, "").unwrap();
+        buf
+    }
+
+    /// Sorts the inventory list of a player data compound by slot, since the
+    /// order of the items is not significant.
+    fn sort_inventory(mut nbt: Compound) -> Compound {
+        if let Some(Value::List(List::Compound(items))) = nbt.get_mut("Inventory") {
+            items.sort_by_key(|item| match item.get("Slot") {
+                Some(&Value::Byte(slot)) => slot,
+                _ => 0,
+            });
+        }
+
+        nbt
+    }
+
+    #[test]
+    fn parse_hand_written_player_data() {
+        let data = PlayerData::from_nbt(hand_written_player_nbt()).unwrap();
+
+        assert_eq!(data.position, DVec3::new(-41.5, 64.0, 127.30000001192093));
+        assert_eq!((data.yaw, data.pitch), (-93.75, 12.5));
+        assert_eq!(data.dimension, ident!("the_nether"));
+        assert_eq!(data.xp_level, 3);
+        assert_eq!(data.xp_total, 31);
+        assert_eq!(data.health, 17.5);
+        assert_eq!(data.food_level, 18);
+
+        let slots: Vec<_> = data
+            .inventory
+            .iter()
+            .map(|(&slot, stack)| (slot, stack.item, stack.count()))
+            .collect();
+
+        assert_eq!(
+            slots,
+            [
+                (5, ItemKind::TurtleHelmet, 1),
+                (8, ItemKind::IronBoots, 1),
+                (20, ItemKind::Bread, 12),
+                (36, ItemKind::DiamondSword, 1),
+                (44, ItemKind::Cobblestone, 64),
+                (45, ItemKind::Shield, 1),
+            ]
+        );
+
+        assert!(data.extra.contains_key("abilities"));
+        assert!(data.extra.contains_key("BukkitValues"));
+        assert!(data.extra.contains_key("SelectedItemSlot"));
+        assert!(!data.extra.contains_key("Inventory"));
+    }
+
+    #[test]
+    fn player_data_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let folder = PlayerDataFolder::new(dir.path());
+        let uuid = Uuid::from_u128(0xc35a_0b3f_77b9_4a25_95e0_6f24_a404_0cfb);
+
+        assert_eq!(folder.read(uuid).unwrap(), None);
+
+        let data = PlayerData::from_nbt(hand_written_player_nbt()).unwrap();
+        folder.write(uuid, &data).unwrap();
+
+        // Unmodified data must produce an identical file.
+        let read = folder.read(uuid).unwrap().unwrap();
+        assert_eq!(read, data);
+        assert_eq!(
+            sort_inventory(read.to_nbt()),
+            sort_inventory(hand_written_player_nbt())
+        );
+
+        // Modify some fields and check that everything else is preserved.
+        let mut modified = read;
+        modified.health = 20.0;
+        modified.inventory.remove(&44);
+        modified
+            .inventory
+            .insert(9, ItemStack::new(ItemKind::Apple, 3, None));
+        folder.write(uuid, &modified).unwrap();
+
+        let mut expected = hand_written_player_nbt();
+        expected.insert("Health", 20.0_f32);
+        let Some(Value::List(List::Compound(items))) = expected.get_mut("Inventory") else {
+            unreachable!()
+        };
+        items.retain(|item| item.get("Slot") != Some(&Value::Byte(8)));
+        items.push(compound! {
+            "Count" => 3_i8,
+            "Slot" => 9_i8,
+            "id" => "minecraft:apple",
+        });
+
+        let actual = folder.read(uuid).unwrap().unwrap().to_nbt();
+
+        // The fields that weren't modified, including the ones valence doesn't know,
+        // are written back exactly as they were read.
+        for key in hand_written_player_nbt().keys() {
+            if key != "Health" && key != "Inventory" {
+                assert_eq!(
+                    field_bytes(&actual, key),
+                    field_bytes(&expected, key),
+                    "field `{key}` changed"
+                );
+            }
+        }
+
+        assert_eq!(sort_inventory(actual), sort_inventory(expected));
+    }
+}
@@ -0,0 +1,379 @@
+//! Reading point of interest (POI) data from the `poi` directory of an anvil
+//! world.
+//!
+//! POI files use the same region format as regular chunk data and record
+//! things like villager workstations, beds, bee nests and nether portals.
+//! The data here is read-only and is never synchronized with clients.
+//!
+//! Everything is read lazily. Region files are only opened as
+//! [`PoiFolder::regions`] is advanced, and chunks are only read and parsed as
+//! [`PoiRegion::chunks`] is advanced.
+
+use std::collections::BTreeMap;
+use std::fs::ReadDir;
+use std::io;
+use std::path::PathBuf;
+
+use thiserror::Error;
+use valence_core::block_pos::BlockPos;
+use valence_core::chunk_pos::ChunkPos;
+use valence_core::ident::Ident;
+use valence_nbt::{Compound, List, Value};
+
+use crate::region::{parse_region_file_name, Region};
+use crate::RegionError;
+
+/// The `poi` directory of an anvil world.
+#[derive(Clone, Debug)]
+pub struct PoiFolder {
+    /// Path to the "poi" subdirectory in the world root.
+    poi_root: PathBuf,
+}
+
+impl PoiFolder {
+    pub fn new(world_root: impl Into<PathBuf>) -> Self {
+        let mut poi_root = world_root.into();
+        poi_root.push("poi");
+
+        Self { poi_root }
+    }
+
+    /// Opens the POI region file containing the given region position.
+    /// Returns `Ok(None)` if the region file does not exist.
+    pub fn region(&self, region_x: i32, region_z: i32) -> Result<Option<PoiRegion>, PoiError> {
+        let path = self.poi_root.join(format!("r.{region_x}.{region_z}.mca"));
+
+        Ok(Region::open(path)?.map(|region| PoiRegion::new(region, region_x, region_z)))
+    }
+
+    /// Reads the POI data of a single chunk. Returns `Ok(None)` if there is no
+    /// POI data for the chunk.
+    pub fn chunk(&self, pos: ChunkPos) -> Result<Option<PoiChunk>, PoiError> {
+        match self.region(pos.x.div_euclid(32), pos.z.div_euclid(32))? {
+            Some(mut region) => region.chunk(pos),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns an iterator over all the POI region files in this folder.
+    /// Each region file is opened as the iterator is advanced. Files in the
+    /// directory that are not named like region files are skipped.
+    pub fn regions(&self) -> Result<PoiRegions, PoiError> {
+        Ok(PoiRegions {
+            read_dir: self.poi_root.read_dir()?,
+        })
+    }
+}
+
+/// An iterator over the region files in a [`PoiFolder`]. Returned by
+/// [`PoiFolder::regions`].
+#[derive(Debug)]
+pub struct PoiRegions {
+    read_dir: ReadDir,
+}
+
+impl Iterator for PoiRegions {
+    type Item = Result<PoiRegion, PoiError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let path = match self.read_dir.next()? {
+                Ok(entry) => entry.path(),
+                Err(e) => return Some(Err(e.into())),
+            };
+
+            let Some((region_x, region_z)) = parse_region_file_name(&path) else {
+                continue;
+            };
+
+            match Region::open(&path) {
+                Ok(Some(region)) => return Some(Ok(PoiRegion::new(region, region_x, region_z))),
+                // The file was removed after we listed it.
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+    }
+}
+
+/// A single POI region file covering 32x32 chunks.
+#[derive(Debug)]
+pub struct PoiRegion {
+    region: Region,
+    region_x: i32,
+    region_z: i32,
+    /// Scratch buffer for decompression.
+    decompress_buf: Vec<u8>,
+}
+
+impl PoiRegion {
+    fn new(region: Region, region_x: i32, region_z: i32) -> Self {
+        Self {
+            region,
+            region_x,
+            region_z,
+            decompress_buf: vec![],
+        }
+    }
+
+    /// Returns the X and Z position of this region.
+    pub fn pos(&self) -> (i32, i32) {
+        (self.region_x, self.region_z)
+    }
+
+    /// Reads the POI data of a single chunk in this region. Only the position
+    /// of the chunk within the region is considered. Returns `Ok(None)` if
+    /// there is no POI data for the chunk.
+    pub fn chunk(&mut self, pos: ChunkPos) -> Result<Option<PoiChunk>, PoiError> {
+        let pos = ChunkPos::new(
+            self.region_x * 32 + pos.x.rem_euclid(32),
+            self.region_z * 32 + pos.z.rem_euclid(32),
+        );
+
+        let Some(chunk) = self.region.read_chunk(pos, &mut self.decompress_buf)? else {
+            return Ok(None);
+        };
+
+        parse_poi_chunk(pos, chunk.data, chunk.timestamp).map(Some)
+    }
+
+    /// Returns an iterator over the POI data of every chunk present in this
+    /// region. Chunks are read one at a time as the iterator is advanced.
+    pub fn chunks(&mut self) -> impl Iterator<Item = Result<PoiChunk, PoiError>> + '_ {
+        let (region_x, region_z) = self.pos();
+
+        (0..32 * 32).filter_map(move |idx| {
+            let pos = ChunkPos::new(region_x * 32 + idx % 32, region_z * 32 + idx / 32);
+            self.chunk(pos).transpose()
+        })
+    }
+}
+
+/// The POI data of a single chunk.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PoiChunk {
+    /// The position of the chunk.
+    pub pos: ChunkPos,
+    /// The time this chunk was last modified, measured in seconds since the
+    /// epoch.
+    pub timestamp: u32,
+    /// The sections of the chunk that have POI data, keyed by section Y.
+    pub sections: BTreeMap<i32, PoiSection>,
+}
+
+/// The POI data of a 16x16x16 chunk section.
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct PoiSection {
+    /// If this is `false`, the game will rescan the section for POIs the next
+    /// time it is loaded and the records should not be trusted.
+    pub valid: bool,
+    pub records: Vec<PoiRecord>,
+}
+
+/// A single point of interest.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PoiRecord {
+    /// The position of the block this POI is attached to.
+    pub pos: BlockPos,
+    /// The POI type, such as `minecraft:home` or `minecraft:nether_portal`.
+    pub kind: Ident<String>,
+    /// The number of tickets that can still be claimed on this POI, e.g. the
+    /// number of villagers that can still use a bell as a meeting point.
+    pub free_tickets: i32,
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum PoiError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Region(#[from] RegionError),
+    #[error("missing POI sections")]
+    MissingSections,
+    #[error("invalid POI section Y of \"{0}\"")]
+    InvalidSectionY(String),
+    #[error("POI section is not a compound")]
+    BadSectionType,
+    #[error("missing POI section valid flag")]
+    MissingValid,
+    #[error("missing POI records")]
+    MissingRecords,
+    #[error("missing or invalid POI record position")]
+    BadRecordPos,
+    #[error("missing POI record type")]
+    MissingRecordType,
+    #[error("invalid POI record type of \"{0}\"")]
+    InvalidRecordType(String),
+    #[error("missing POI record free tickets")]
+    MissingFreeTickets,
+}
+
+fn parse_poi_chunk(pos: ChunkPos, mut nbt: Compound, timestamp: u32) -> Result<PoiChunk, PoiError> {
+    let Some(Value::Compound(sections_nbt)) = nbt.remove("Sections") else {
+        return Err(PoiError::MissingSections);
+    };
+
+    let mut sections = BTreeMap::new();
+
+    for (y, section) in sections_nbt {
+        let Ok(sect_y) = y.parse::<i32>() else {
+            return Err(PoiError::InvalidSectionY(y));
+        };
+
+        let Value::Compound(section) = section else {
+            return Err(PoiError::BadSectionType);
+        };
+
+        sections.insert(sect_y, parse_poi_section(section)?);
+    }
+
+    Ok(PoiChunk {
+        pos,
+        timestamp,
+        sections,
+    })
+}
+
+fn parse_poi_section(mut nbt: Compound) -> Result<PoiSection, PoiError> {
+    let Some(Value::Byte(valid)) = nbt.remove("Valid") else {
+        return Err(PoiError::MissingValid);
+    };
+
+    let records = match nbt.remove("Records") {
+        Some(Value::List(List::Compound(records))) => records
+            .into_iter()
+            .map(parse_poi_record)
+            .collect::<Result<_, _>>()?,
+        // Empty lists are written with an element type of `TAG_End`.
+        Some(Value::List(List::End)) => vec![],
+        _ => return Err(PoiError::MissingRecords),
+    };
+
+    Ok(PoiSection {
+        valid: valid != 0,
+        records,
+    })
+}
+
+fn parse_poi_record(mut nbt: Compound) -> Result<PoiRecord, PoiError> {
+    let pos = match nbt.remove("pos") {
+        Some(Value::IntArray(pos)) if pos.len() == 3 => BlockPos::new(pos[0], pos[1], pos[2]),
+        _ => return Err(PoiError::BadRecordPos),
+    };
+
+    let Some(Value::String(kind)) = nbt.remove("type") else {
+        return Err(PoiError::MissingRecordType);
+    };
+
+    let kind = match Ident::new(kind) {
+        Ok(kind) => kind.into(),
+        Err(e) => return Err(PoiError::InvalidRecordType(e.0)),
+    };
+
+    let Some(Value::Int(free_tickets)) = nbt.remove("free_tickets") else {
+        return Err(PoiError::MissingFreeTickets);
+    };
+
+    Ok(PoiRecord {
+        pos,
+        kind,
+        free_tickets,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use valence_nbt::compound;
+
+    use super::*;
+    use crate::region::testing::{write_region, zlib_payload};
+
+    fn poi_chunk_nbt() -> Compound {
+        compound! {
+            "DataVersion" => 3465,
+            "Sections" => compound! {
+                "4" => compound! {
+                    "Valid" => 1_i8,
+                    "Records" => List::Compound(vec![
+                        compound! {
+                            "pos" => vec![-20, 70, 35],
+                            "type" => "minecraft:home",
+                            "free_tickets" => 1,
+                        },
+                        compound! {
+                            "pos" => vec![-18, 71, 36],
+                            "type" => "minecraft:cartographer",
+                            "free_tickets" => 0,
+                        },
+                    ]),
+                },
+                "-1" => compound! {
+                    "Valid" => 0_i8,
+                    "Records" => List::End,
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn read_poi_region() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("poi")).unwrap();
+        fs::write(dir.path().join("poi/not_a_region.txt"), "hello").unwrap();
+
+        write_region(
+            &dir.path().join("poi/r.-1.0.mca"),
+            &[(ChunkPos::new(-2, 2), zlib_payload(&poi_chunk_nbt()))],
+        );
+
+        let folder = PoiFolder::new(dir.path());
+
+        let mut regions = folder
+            .regions()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(regions.len(), 1);
+        assert_eq!(regions[0].pos(), (-1, 0));
+
+        let chunks = regions[0].chunks().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(chunks.len(), 1);
+
+        let chunk = &chunks[0];
+        assert_eq!(chunk.pos, ChunkPos::new(-2, 2));
+        assert_eq!(chunk.timestamp, 1234);
+        assert_eq!(chunk.sections.len(), 2);
+
+        let sect = &chunk.sections[&4];
+        assert!(sect.valid);
+        assert_eq!(
+            sect.records,
+            [
+                PoiRecord {
+                    pos: BlockPos::new(-20, 70, 35),
+                    kind: Ident::new("minecraft:home").unwrap().into(),
+                    free_tickets: 1,
+                },
+                PoiRecord {
+                    pos: BlockPos::new(-18, 71, 36),
+                    kind: Ident::new("minecraft:cartographer").unwrap().into(),
+                    free_tickets: 0,
+                },
+            ]
+        );
+
+        let sect = &chunk.sections[&-1];
+        assert!(!sect.valid);
+        assert!(sect.records.is_empty());
+
+        assert_eq!(
+            folder.chunk(ChunkPos::new(-2, 2)).unwrap().as_ref(),
+            Some(chunk)
+        );
+        assert_eq!(folder.chunk(ChunkPos::new(-3, 2)).unwrap(), None);
+        assert_eq!(folder.chunk(ChunkPos::new(50, 50)).unwrap(), None);
+    }
+}
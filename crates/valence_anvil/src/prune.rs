@@ -0,0 +1,346 @@
+//! Offline trimming of the region files in an anvil world.
+//!
+//! This is meant to be run on a world that is not currently loaded, e.g. to
+//! shrink a lobby map before distributing it.
+
+use std::fs;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use valence_core::chunk_pos::ChunkPos;
+
+use crate::region::{
+    chunk_index, external_chunk_path, parse_region_file_name, EXTERNAL_CHUNK_FLAG, SECTOR_SIZE,
+};
+use crate::RegionError;
+
+/// Information passed to the progress callback of [`prune`] after each region
+/// file is processed.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct PruneProgress {
+    /// The X and Z position of the region that was just processed.
+    pub region: (i32, i32),
+    /// The number of region files processed so far, including this one.
+    pub regions_done: usize,
+    /// The total number of region files to process.
+    pub regions_total: usize,
+    /// The combined size in bytes of the region files processed so far.
+    pub bytes_done: u64,
+    /// The combined size in bytes of all region files to process.
+    pub bytes_total: u64,
+}
+
+/// Statistics about a completed call to [`prune`].
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct PruneStats {
+    pub chunks_kept: usize,
+    pub chunks_removed: usize,
+    /// The number of region files that were deleted because none of their
+    /// chunks were kept.
+    pub regions_deleted: usize,
+}
+
+/// Rewrites every region file in `region_dir`, keeping only the chunks for
+/// which `keep` returns `true`.
+///
+/// The data of kept chunks is copied byte-for-byte, but the sectors are
+/// compacted so that removed chunks no longer take up space. Region files
+/// with no remaining chunks are deleted, as are the external `.mcc` files of
+/// removed oversized chunks. Region files where every chunk is kept are left
+/// untouched.
+///
+/// `progress` is called after each region file is processed.
+///
+/// # Examples
+///
+/// Keep only the chunks within 16 chunks of the origin.
+///
+/// ```no_run
+/// use valence_anvil::prune::prune;
+///
+/// let stats = prune(
+///     "world/region",
+///     |pos| pos.x * pos.x + pos.z * pos.z <= 16 * 16,
+///     |p| println!("{}/{} regions", p.regions_done, p.regions_total),
+/// )
+/// .unwrap();
+///
+/// println!("removed {} chunks", stats.chunks_removed);
+/// ```
+pub fn prune(
+    region_dir: impl AsRef<Path>,
+    keep: impl Fn(ChunkPos) -> bool,
+    mut progress: impl FnMut(PruneProgress),
+) -> Result<PruneStats, RegionError> {
+    let region_dir = region_dir.as_ref();
+
+    let mut regions = vec![];
+    let mut bytes_total = 0;
+
+    for entry in region_dir.read_dir()? {
+        let entry = entry?;
+        let path = entry.path();
+
+        if let Some(region_pos) = parse_region_file_name(&path) {
+            let len = entry.metadata()?.len();
+            bytes_total += len;
+            regions.push((region_pos, path, len));
+        }
+    }
+
+    // Process regions in a consistent order.
+    regions.sort_unstable_by_key(|(pos, _, _)| *pos);
+
+    let mut stats = PruneStats::default();
+    let mut bytes_done = 0;
+
+    for (i, (region_pos, path, len)) in regions.iter().enumerate() {
+        prune_region(region_dir, path, *region_pos, &keep, &mut stats)?;
+
+        bytes_done += len;
+
+        progress(PruneProgress {
+            region: *region_pos,
+            regions_done: i + 1,
+            regions_total: regions.len(),
+            bytes_done,
+            bytes_total,
+        });
+    }
+
+    Ok(stats)
+}
+
+fn prune_region(
+    region_dir: &Path,
+    path: &Path,
+    (region_x, region_z): (i32, i32),
+    keep: &impl Fn(ChunkPos) -> bool,
+    stats: &mut PruneStats,
+) -> Result<(), RegionError> {
+    let mut file = File::open(path)?;
+
+    let mut header = [0; SECTOR_SIZE * 2];
+
+    match file.read_exact(&mut header) {
+        Ok(()) => {}
+        // A region file too short to hold a header has no chunks in it.
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+            drop(file);
+            fs::remove_file(path)?;
+            stats.regions_deleted += 1;
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    let mut new_header = [0; SECTOR_SIZE * 2];
+    let mut body = vec![];
+    let mut removed_any = false;
+
+    for idx in 0..32 * 32 {
+        let location = u32::from_be_bytes(header[idx * 4..idx * 4 + 4].try_into().unwrap());
+
+        if location == 0 {
+            continue;
+        }
+
+        let pos = ChunkPos::new(
+            region_x * 32 + (idx % 32) as i32,
+            region_z * 32 + (idx / 32) as i32,
+        );
+
+        let sector_offset = (location >> 8) as u64;
+        let sector_count = (location & 0xff) as usize;
+
+        if sector_offset < 2 {
+            return Err(RegionError::InvalidChunkSectorOffset);
+        }
+
+        file.seek(SeekFrom::Start(sector_offset * SECTOR_SIZE as u64))?;
+
+        let mut sectors = Vec::with_capacity(sector_count * SECTOR_SIZE);
+        (&mut file)
+            .take((sector_count * SECTOR_SIZE) as u64)
+            .read_to_end(&mut sectors)?;
+        // The last sector in the file might not be padded.
+        sectors.resize(sector_count * SECTOR_SIZE, 0);
+
+        // The compression scheme byte follows the length of the chunk.
+        let external = sectors
+            .get(4)
+            .is_some_and(|&b| b & EXTERNAL_CHUNK_FLAG != 0);
+
+        if keep(pos) {
+            debug_assert_eq!(chunk_index(pos), idx);
+
+            let new_location = ((2 + body.len() / SECTOR_SIZE) as u32) << 8 | sector_count as u32;
+
+            new_header[idx * 4..idx * 4 + 4].copy_from_slice(&new_location.to_be_bytes());
+            // Copy the timestamp.
+            new_header[SECTOR_SIZE + idx * 4..SECTOR_SIZE + idx * 4 + 4]
+                .copy_from_slice(&header[SECTOR_SIZE + idx * 4..SECTOR_SIZE + idx * 4 + 4]);
+
+            body.extend_from_slice(&sectors);
+            stats.chunks_kept += 1;
+        } else {
+            if external {
+                match fs::remove_file(external_chunk_path(region_dir, pos)) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            removed_any = true;
+            stats.chunks_removed += 1;
+        }
+    }
+
+    drop(file);
+
+    if body.is_empty() {
+        fs::remove_file(path)?;
+        stats.regions_deleted += 1;
+    } else if removed_any {
+        // Write to a temporary file first so that the region file is never left
+        // half written.
+        let tmp_path = path.with_extension("mca.tmp");
+
+        let mut tmp = File::create(&tmp_path)?;
+        tmp.write_all(&new_header)?;
+        tmp.write_all(&body)?;
+        tmp.sync_all()?;
+        drop(tmp);
+
+        fs::rename(tmp_path, path)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_nbt::compound;
+
+    use super::*;
+    use crate::region::testing::{write_region, zlib_payload};
+    use crate::region::Region;
+
+    /// Returns the raw sectors of every chunk in a region file along with the
+    /// chunk's timestamp.
+    fn raw_chunks(path: &Path) -> Vec<(usize, u32, Vec<u8>)> {
+        let bytes = fs::read(path).unwrap();
+        let mut chunks = vec![];
+
+        for idx in 0..32 * 32 {
+            let location = u32::from_be_bytes(bytes[idx * 4..idx * 4 + 4].try_into().unwrap());
+            if location == 0 {
+                continue;
+            }
+
+            let timestamp = u32::from_be_bytes(
+                bytes[SECTOR_SIZE + idx * 4..SECTOR_SIZE + idx * 4 + 4]
+                    .try_into()
+                    .unwrap(),
+            );
+
+            let start = (location >> 8) as usize * SECTOR_SIZE;
+            let end = start + (location & 0xff) as usize * SECTOR_SIZE;
+
+            chunks.push((idx, timestamp, bytes[start..end].to_vec()));
+        }
+
+        chunks
+    }
+
+    #[test]
+    fn prune_region_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let region_dir = dir.path();
+
+        let nbt = |n: i32| compound! { "n" => n };
+        // An oversized chunk is marked with the external flag and has no data in the
+        // region file itself.
+        let external_payload = vec![2 | EXTERNAL_CHUNK_FLAG];
+
+        write_region(
+            &region_dir.join("r.0.0.mca"),
+            &[
+                (ChunkPos::new(0, 0), zlib_payload(&nbt(0))),
+                (ChunkPos::new(1, 0), external_payload.clone()),
+                (ChunkPos::new(2, 0), external_payload),
+                (ChunkPos::new(5, 5), zlib_payload(&nbt(1))),
+                (ChunkPos::new(0, 7), zlib_payload(&nbt(2))),
+            ],
+        );
+        fs::write(region_dir.join("c.1.0.mcc"), "removed").unwrap();
+        fs::write(region_dir.join("c.2.0.mcc"), "kept").unwrap();
+
+        write_region(
+            &region_dir.join("r.1.0.mca"),
+            &[(ChunkPos::new(32, 0), zlib_payload(&nbt(3)))],
+        );
+
+        write_region(
+            &region_dir.join("r.0.1.mca"),
+            &[(ChunkPos::new(0, 32), zlib_payload(&nbt(4)))],
+        );
+        let untouched = fs::read(region_dir.join("r.0.1.mca")).unwrap();
+
+        let before = raw_chunks(&region_dir.join("r.0.0.mca"));
+
+        let mut progress_calls = vec![];
+
+        let stats = prune(
+            region_dir,
+            |pos| pos.x == 0 || pos.x == 2,
+            |p| progress_calls.push(p),
+        )
+        .unwrap();
+
+        assert_eq!(
+            stats,
+            PruneStats {
+                chunks_kept: 4,
+                chunks_removed: 3,
+                regions_deleted: 1,
+            }
+        );
+
+        assert_eq!(progress_calls.len(), 3);
+        assert_eq!(progress_calls[2].regions_done, 3);
+        assert_eq!(progress_calls[2].regions_total, 3);
+        assert_eq!(progress_calls[2].bytes_done, progress_calls[2].bytes_total);
+
+        // Kept chunks are byte-for-byte identical.
+        let after = raw_chunks(&region_dir.join("r.0.0.mca"));
+        let expected: Vec<_> = before
+            .into_iter()
+            .filter(|(idx, _, _)| idx % 32 == 0 || idx % 32 == 2)
+            .collect();
+        assert_eq!(after, expected);
+
+        // Sectors are compacted.
+        let len = fs::metadata(region_dir.join("r.0.0.mca")).unwrap().len();
+        assert_eq!(len, (SECTOR_SIZE * (2 + 3)) as u64);
+
+        let mut region = Region::open(region_dir.join("r.0.0.mca")).unwrap().unwrap();
+        let mut buf = vec![];
+        let chunk = region
+            .read_chunk(ChunkPos::new(0, 7), &mut buf)
+            .unwrap()
+            .unwrap();
+        assert_eq!(chunk.data, nbt(2));
+        assert!(region
+            .read_chunk(ChunkPos::new(5, 5), &mut buf)
+            .unwrap()
+            .is_none());
+
+        assert!(!region_dir.join("c.1.0.mcc").exists());
+        assert!(region_dir.join("c.2.0.mcc").exists());
+        assert!(!region_dir.join("r.1.0.mca").exists());
+        assert_eq!(fs::read(region_dir.join("r.0.1.mca")).unwrap(), untouched);
+    }
+}
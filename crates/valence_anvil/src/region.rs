@@ -0,0 +1,640 @@
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fs, io};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use flate2::bufread::{GzDecoder, ZlibDecoder};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use lru::LruCache;
+use num_integer::div_ceil;
+use thiserror::Error;
+use valence_core::chunk_pos::ChunkPos;
+use valence_nbt::Compound;
+
+use crate::scan::{self, ScannedBlockEntity};
+
+/// Errors that can occur when reading or writing chunk data in a region file.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum RegionError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("invalid chunk sector offset")]
+    InvalidChunkSectorOffset,
+    #[error("invalid chunk size")]
+    InvalidChunkSize,
+    #[error("unknown compression scheme number of {0}")]
+    UnknownCompressionScheme(u8),
+    #[error(transparent)]
+    Nbt(#[from] valence_nbt::binary::Error),
+    #[error("not all chunk NBT data was read")]
+    TrailingNbtData,
+    #[error("region file is full")]
+    RegionFull,
+}
+
+/// A chunk read from a region file.
+#[derive(Clone, PartialEq, Debug)]
+pub struct AnvilChunk {
+    /// The chunk's NBT data.
+    pub data: Compound,
+    /// The time this chunk was last modified, measured in seconds since the
+    /// epoch.
+    pub timestamp: u32,
+}
+
+/// A directory of region files, such as the `region` directory of a world.
+///
+/// Region files are opened on demand. An LRU cache is used to limit the
+/// number of open file handles.
+#[derive(Debug)]
+pub struct RegionFolder {
+    regions: LruCache<RegionPos, RegionEntry>,
+    region_root: PathBuf,
+    /// Scratch buffer for compression and decompression.
+    compress_buf: Vec<u8>,
+}
+
+impl RegionFolder {
+    pub fn new(region_root: impl Into<PathBuf>) -> Self {
+        Self {
+            regions: LruCache::new(LRU_CACHE_SIZE),
+            region_root: region_root.into(),
+            compress_buf: vec![],
+        }
+    }
+
+    /// Reads the chunk at `pos`. Returns `Ok(None)` if there is no chunk at
+    /// the position.
+    pub fn get_chunk(&mut self, pos: ChunkPos) -> Result<Option<AnvilChunk>, RegionError> {
+        let Some(region) = open_region(&mut self.regions, &self.region_root, pos, false)? else {
+            return Ok(None);
+        };
+
+        region.read_chunk(pos, &mut self.compress_buf)
+    }
+
+    /// Lists the block entities in the chunk at `pos` without decoding the
+    /// rest of the chunk, which is much faster than [`Self::get_chunk`] when
+    /// searching many chunks. Returns `Ok(None)` if there is no chunk at the
+    /// position.
+    pub fn scan_block_entities(
+        &mut self,
+        pos: ChunkPos,
+    ) -> Result<Option<Vec<ScannedBlockEntity>>, RegionError> {
+        let Some(region) = open_region(&mut self.regions, &self.region_root, pos, false)? else {
+            return Ok(None);
+        };
+
+        region.read_chunk_nbt(pos, &mut self.compress_buf, |nbt, _| {
+            Ok(scan::block_entities(nbt)?)
+        })
+    }
+
+    /// Writes the chunk at `pos`, replacing any chunk that was already there.
+    /// The region file is created if it does not exist.
+    ///
+    /// Like vanilla, chunks too large to fit in the region file are written to
+    /// a separate `c.<x>.<z>.mcc` file next to it.
+    pub fn set_chunk(&mut self, pos: ChunkPos, data: &Compound) -> Result<(), RegionError> {
+        let region = open_region(&mut self.regions, &self.region_root, pos, true)?
+            .expect("region should be created");
+
+        region.write_chunk(pos, data, &mut self.compress_buf)
+    }
+
+    /// Closes the region file containing `pos` if it is open. The region file
+    /// is read from disk again the next time it is accessed, which is
+    /// necessary to observe changes made by other programs.
+    pub fn forget_region(&mut self, pos: ChunkPos) {
+        self.regions
+            .pop(&(pos.x.div_euclid(32), pos.z.div_euclid(32)));
+    }
+}
+
+/// Returns the region containing `pos` from the cache, opening it if needed.
+/// If `create` is true, the region file is created if it does not exist.
+fn open_region<'a>(
+    regions: &'a mut LruCache<RegionPos, RegionEntry>,
+    region_root: &Path,
+    pos: ChunkPos,
+    create: bool,
+) -> Result<Option<&'a mut Region>, RegionError> {
+    let region_x = pos.x.div_euclid(32);
+    let region_z = pos.z.div_euclid(32);
+
+    let needs_open = match regions.get(&(region_x, region_z)) {
+        None => true,
+        Some(RegionEntry::Vacant) => create,
+        Some(RegionEntry::Occupied(_)) => false,
+    };
+
+    if needs_open {
+        let path = region_root.join(format!("r.{region_x}.{region_z}.mca"));
+
+        let entry = if create {
+            RegionEntry::Occupied(Region::create(path)?)
+        } else {
+            match Region::open(path)? {
+                Some(region) => RegionEntry::Occupied(region),
+                None => RegionEntry::Vacant,
+            }
+        };
+
+        regions.put((region_x, region_z), entry);
+    }
+
+    match regions.get_mut(&(region_x, region_z)) {
+        Some(RegionEntry::Occupied(region)) => Ok(Some(region)),
+        _ => Ok(None),
+    }
+}
+
+const LRU_CACHE_SIZE: NonZeroUsize = match NonZeroUsize::new(256) {
+    Some(n) => n,
+    None => unreachable!(),
+};
+
+/// X and Z positions of a region.
+type RegionPos = (i32, i32);
+
+#[allow(clippy::large_enum_variant)] // We're not moving this around.
+#[derive(Debug)]
+enum RegionEntry {
+    /// There is a region file loaded here.
+    Occupied(Region),
+    /// There is no region file at this position. Don't try to read it from the
+    /// filesystem again.
+    Vacant,
+}
+
+#[derive(Debug)]
+pub(crate) struct Region {
+    file: File,
+    /// The directory containing the region file. External chunk files are
+    /// stored here.
+    dir: PathBuf,
+    /// The first 8 KiB in the file.
+    header: [u8; SECTOR_SIZE * 2],
+}
+
+impl Region {
+    /// Opens the region file at `path`. Returns `Ok(None)` if the file does
+    /// not exist.
+    pub(crate) fn open(path: impl AsRef<Path>) -> Result<Option<Self>, RegionError> {
+        let path = path.as_ref();
+
+        let mut file = match File::options().read(true).write(true).open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut header = [0; SECTOR_SIZE * 2];
+
+        file.read_exact(&mut header)?;
+
+        Ok(Some(Self {
+            file,
+            dir: region_dir(path),
+            header,
+        }))
+    }
+
+    /// Opens the region file at `path`, creating an empty one if it does not
+    /// exist.
+    pub(crate) fn create(path: impl AsRef<Path>) -> Result<Self, RegionError> {
+        if let Some(region) = Self::open(&path)? {
+            return Ok(region);
+        }
+
+        let path = path.as_ref();
+        let dir = region_dir(path);
+
+        fs::create_dir_all(&dir)?;
+
+        let header = [0; SECTOR_SIZE * 2];
+
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        file.write_all(&header)?;
+
+        Ok(Self { file, dir, header })
+    }
+
+    /// Returns the timestamp of the chunk at `pos` from the region header, or
+    /// `None` if there is no chunk at the position. Only the position of the
+    /// chunk within its region is considered.
+    pub(crate) fn chunk_timestamp(&self, pos: ChunkPos) -> Option<u32> {
+        let idx = chunk_index(pos);
+        let location = u32::from_be_bytes(self.header[idx * 4..idx * 4 + 4].try_into().unwrap());
+
+        (location != 0).then(|| {
+            u32::from_be_bytes(
+                self.header[SECTOR_SIZE + idx * 4..SECTOR_SIZE + idx * 4 + 4]
+                    .try_into()
+                    .unwrap(),
+            )
+        })
+    }
+
+    /// Reads the chunk at `pos`. Only the position of the chunk within its
+    /// region is considered. Returns `Ok(None)` if there is no chunk at the
+    /// position.
+    ///
+    /// `decompress_buf` is used as scratch space for decompression.
+    pub(crate) fn read_chunk(
+        &mut self,
+        pos: ChunkPos,
+        decompress_buf: &mut Vec<u8>,
+    ) -> Result<Option<AnvilChunk>, RegionError> {
+        self.read_chunk_nbt(pos, decompress_buf, |mut nbt_slice, timestamp| {
+            let (data, _) = Compound::from_binary(&mut nbt_slice)?;
+
+            if !nbt_slice.is_empty() {
+                return Err(RegionError::TrailingNbtData);
+            }
+
+            Ok(AnvilChunk { data, timestamp })
+        })
+    }
+
+    /// Decompresses the chunk at `pos` and passes its binary NBT and timestamp
+    /// to `f`. Only the position of the chunk within its region is
+    /// considered. Returns `Ok(None)` if there is no chunk at the position.
+    ///
+    /// `decompress_buf` is used as scratch space for decompression.
+    pub(crate) fn read_chunk_nbt<T>(
+        &mut self,
+        pos: ChunkPos,
+        decompress_buf: &mut Vec<u8>,
+        f: impl FnOnce(&[u8], u32) -> Result<T, RegionError>,
+    ) -> Result<Option<T>, RegionError> {
+        let chunk_idx = chunk_index(pos);
+
+        let location_bytes = (&self.header[chunk_idx * 4..]).read_u32::<BigEndian>()?;
+        let timestamp = (&self.header[chunk_idx * 4 + SECTOR_SIZE..]).read_u32::<BigEndian>()?;
+
+        if location_bytes == 0 {
+            // No chunk exists at this position.
+            return Ok(None);
+        }
+
+        let sector_offset = (location_bytes >> 8) as u64;
+        let sector_count = (location_bytes & 0xff) as usize;
+
+        // If the sector offset was <2, then the chunk data would be inside the region
+        // header. That doesn't make any sense.
+        if sector_offset < 2 {
+            return Err(RegionError::InvalidChunkSectorOffset);
+        }
+
+        // Seek to the beginning of the chunk's data.
+        self.file
+            .seek(SeekFrom::Start(sector_offset * SECTOR_SIZE as u64))?;
+
+        let exact_chunk_size = self.file.read_u32::<BigEndian>()? as usize;
+
+        // size of this chunk in sectors must always be >= the exact size.
+        if exact_chunk_size == 0 || sector_count * SECTOR_SIZE < exact_chunk_size {
+            return Err(RegionError::InvalidChunkSize);
+        }
+
+        let mut data_buf = vec![0; exact_chunk_size].into_boxed_slice();
+        self.file.read_exact(&mut data_buf)?;
+
+        let mut r = data_buf.as_ref();
+
+        let mut compression = r.read_u8()?;
+
+        // Oversized chunks are stored in a separate file. Whatever is left in the
+        // region file is ignored.
+        let external_buf;
+        if compression & EXTERNAL_CHUNK_FLAG != 0 {
+            compression &= !EXTERNAL_CHUNK_FLAG;
+            external_buf = fs::read(external_chunk_path(&self.dir, pos))?;
+            r = &external_buf;
+        }
+
+        decompress_buf.clear();
+
+        // What compression does the chunk use?
+        let nbt_slice = match compression {
+            // GZip
+            1 => {
+                let mut z = GzDecoder::new(r);
+                z.read_to_end(decompress_buf)?;
+                decompress_buf.as_slice()
+            }
+            // Zlib
+            2 => {
+                let mut z = ZlibDecoder::new(r);
+                z.read_to_end(decompress_buf)?;
+                decompress_buf.as_slice()
+            }
+            // Uncompressed
+            3 => r,
+            // Unknown
+            b => return Err(RegionError::UnknownCompressionScheme(b)),
+        };
+
+        f(nbt_slice, timestamp).map(Some)
+    }
+
+    /// Writes the chunk at `pos` using zlib compression and sets its timestamp
+    /// to the current time. Only the position of the chunk within its region
+    /// is considered.
+    ///
+    /// `compress_buf` is used as scratch space for compression.
+    pub(crate) fn write_chunk(
+        &mut self,
+        pos: ChunkPos,
+        data: &Compound,
+        compress_buf: &mut Vec<u8>,
+    ) -> Result<(), RegionError> {
+        let chunk_idx = chunk_index(pos);
+
+        compress_buf.clear();
+
+        let mut z = ZlibEncoder::new(compress_buf, Compression::default());
+        data.to_binary(&mut z, "")?;
+        let compressed = z.finish()?;
+
+        let external_path = external_chunk_path(&self.dir, pos);
+
+        // The length prefix and compression scheme byte come before the data.
+        let oversized = compressed.len() + 5 > MAX_CHUNK_SECTORS * SECTOR_SIZE;
+
+        let payload: &[u8] = if oversized {
+            fs::write(&external_path, &compressed)?;
+            &[]
+        } else {
+            // Remove the external file left behind if this chunk used to be oversized.
+            match fs::remove_file(&external_path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            compressed.as_slice()
+        };
+
+        let compression = if oversized {
+            2 | EXTERNAL_CHUNK_FLAG
+        } else {
+            2
+        };
+
+        let mut buf = Vec::with_capacity(payload.len() + 5);
+        buf.write_u32::<BigEndian>(payload.len() as u32 + 1)?;
+        buf.write_u8(compression)?;
+        buf.extend_from_slice(payload);
+        buf.resize(div_ceil(buf.len(), SECTOR_SIZE) * SECTOR_SIZE, 0);
+
+        let sector_count = buf.len() / SECTOR_SIZE;
+        let sector_offset = self.allocate_sectors(chunk_idx, sector_count)?;
+
+        self.file
+            .seek(SeekFrom::Start((sector_offset * SECTOR_SIZE) as u64))?;
+        self.file.write_all(&buf)?;
+
+        let location = (sector_offset as u32) << 8 | sector_count as u32;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as u32);
+
+        self.header[chunk_idx * 4..chunk_idx * 4 + 4].copy_from_slice(&location.to_be_bytes());
+        self.header[SECTOR_SIZE + chunk_idx * 4..SECTOR_SIZE + chunk_idx * 4 + 4]
+            .copy_from_slice(&timestamp.to_be_bytes());
+
+        // Update the header only after the data has been written, so a failed write
+        // leaves the old chunk in place.
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&self.header)?;
+
+        Ok(())
+    }
+
+    /// Finds the first run of `count` free sectors, ignoring the sectors
+    /// currently used by the chunk at `chunk_idx`.
+    fn allocate_sectors(&self, chunk_idx: usize, count: usize) -> Result<usize, RegionError> {
+        let mut used = vec![];
+
+        for idx in (0..32 * 32).filter(|&idx| idx != chunk_idx) {
+            let location =
+                u32::from_be_bytes(self.header[idx * 4..idx * 4 + 4].try_into().unwrap());
+
+            if location != 0 {
+                let offset = (location >> 8) as usize;
+                used.push(offset..offset + (location & 0xff) as usize);
+            }
+        }
+
+        used.sort_unstable_by_key(|r| r.start);
+
+        // The first two sectors are the header.
+        let mut offset = 2;
+
+        for range in used {
+            if range.start >= offset + count {
+                break;
+            }
+
+            offset = offset.max(range.end);
+        }
+
+        // The sector offset is stored in 3 bytes.
+        if offset + count > 1 << 24 {
+            return Err(RegionError::RegionFull);
+        }
+
+        Ok(offset)
+    }
+}
+
+/// Returns the directory containing the region file at `path`.
+fn region_dir(path: &Path) -> PathBuf {
+    path.parent().map_or_else(PathBuf::new, Path::to_path_buf)
+}
+
+/// Extracts the region position from a path of the form `r.<x>.<z>.mca`.
+pub(crate) fn parse_region_file_name(path: &Path) -> Option<(i32, i32)> {
+    let name = path.file_name()?.to_str()?;
+    let mut parts = name.strip_prefix("r.")?.strip_suffix(".mca")?.split('.');
+
+    let region_x = parts.next()?.parse().ok()?;
+    let region_z = parts.next()?.parse().ok()?;
+
+    parts.next().is_none().then_some((region_x, region_z))
+}
+
+/// Returns the index of a chunk in the region header. Only the position of
+/// the chunk within its region is considered.
+pub(crate) fn chunk_index(pos: ChunkPos) -> usize {
+    (pos.x.rem_euclid(32) + pos.z.rem_euclid(32) * 32) as usize
+}
+
+/// Returns the path of the file holding the data of an oversized chunk.
+pub(crate) fn external_chunk_path(region_dir: &Path, pos: ChunkPos) -> PathBuf {
+    region_dir.join(format!("c.{}.{}.mcc", pos.x, pos.z))
+}
+
+pub(crate) const SECTOR_SIZE: usize = 4096;
+
+/// The number of sectors a single chunk can occupy in a region file. Chunks
+/// larger than this are stored in an external file.
+const MAX_CHUNK_SECTORS: usize = 255;
+
+/// Set in the compression scheme byte of a chunk when the chunk's data is
+/// stored in a separate `.mcc` file instead of the region file.
+pub(crate) const EXTERNAL_CHUNK_FLAG: u8 = 0x80;
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::io::Write;
+
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+
+    use super::*;
+
+    /// Returns the chunk data of `nbt` compressed with zlib, prefixed with the
+    /// compression scheme byte.
+    pub(crate) fn zlib_payload(nbt: &Compound) -> Vec<u8> {
+        let mut z = ZlibEncoder::new(vec![2], Compression::default());
+        nbt.to_binary(&mut z, "").unwrap();
+        z.finish().unwrap()
+    }
+
+    /// Writes a region file containing the given chunk payloads. Every chunk
+    /// gets a timestamp of 1234.
+    pub(crate) fn write_region(path: &Path, chunks: &[(ChunkPos, Vec<u8>)]) {
+        let mut header = vec![0; SECTOR_SIZE * 2];
+        let mut body = vec![];
+
+        for (pos, payload) in chunks {
+            let mut data = vec![];
+            data.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+            data.extend_from_slice(payload);
+            data.resize(data.len().next_multiple_of(SECTOR_SIZE), 0);
+
+            let sector_offset = 2 + body.len() / SECTOR_SIZE;
+            let sector_count = data.len() / SECTOR_SIZE;
+            let idx = chunk_index(*pos);
+
+            let location = (sector_offset as u32) << 8 | sector_count as u32;
+            header[idx * 4..idx * 4 + 4].copy_from_slice(&location.to_be_bytes());
+            header[SECTOR_SIZE + idx * 4..SECTOR_SIZE + idx * 4 + 4]
+                .copy_from_slice(&1234_u32.to_be_bytes());
+
+            body.extend_from_slice(&data);
+        }
+
+        let mut file = File::create(path).unwrap();
+        file.write_all(&header).unwrap();
+        file.write_all(&body).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_nbt::compound;
+
+    use super::testing::{write_region, zlib_payload};
+    use super::*;
+
+    /// Returns a chunk that compresses to more than the maximum size of a
+    /// chunk in a region file.
+    fn oversized_chunk() -> Compound {
+        // Pseudorandom data doesn't compress well.
+        let mut state = 0x853c_49e6_748f_ea9b_u64;
+        let longs = (0..MAX_CHUNK_SECTORS * SECTOR_SIZE / 8 + 1000)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 1) as i64
+            })
+            .collect::<Vec<_>>();
+
+        compound! {
+            "block_entities" => longs,
+        }
+    }
+
+    #[test]
+    fn read_external_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let pos = ChunkPos::new(-3, 40);
+        let nbt = compound! { "Status" => "minecraft:full" };
+
+        // Vanilla leaves only the compression scheme byte in the region file.
+        write_region(
+            &dir.path().join("r.-1.1.mca"),
+            &[(pos, vec![2 | EXTERNAL_CHUNK_FLAG])],
+        );
+        fs::write(dir.path().join("c.-3.40.mcc"), &zlib_payload(&nbt)[1..]).unwrap();
+
+        let mut folder = RegionFolder::new(dir.path());
+        let chunk = folder.get_chunk(pos).unwrap().unwrap();
+
+        assert_eq!(chunk.data, nbt);
+        assert_eq!(chunk.timestamp, 1234);
+    }
+
+    #[test]
+    fn write_oversized_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut folder = RegionFolder::new(dir.path());
+
+        let pos = ChunkPos::new(5, -7);
+        let mcc_path = dir.path().join("c.5.-7.mcc");
+        let mca_path = dir.path().join("r.0.-1.mca");
+
+        let small = compound! { "Status" => "minecraft:full" };
+        let big = oversized_chunk();
+
+        folder.set_chunk(ChunkPos::new(4, -7), &small).unwrap();
+        folder.set_chunk(pos, &big).unwrap();
+
+        assert!(mcc_path.exists());
+        // Only the header and one sector for each chunk are in the region file.
+        assert_eq!(
+            fs::metadata(&mca_path).unwrap().len(),
+            (SECTOR_SIZE * 4) as u64
+        );
+
+        assert_eq!(folder.get_chunk(pos).unwrap().unwrap().data, big);
+
+        // Read back from disk without any cached state.
+        let mut folder = RegionFolder::new(dir.path());
+        assert_eq!(folder.get_chunk(pos).unwrap().unwrap().data, big);
+        assert_eq!(
+            folder
+                .get_chunk(ChunkPos::new(4, -7))
+                .unwrap()
+                .unwrap()
+                .data,
+            small
+        );
+
+        // Shrinking the chunk moves it back into the region file.
+        folder.set_chunk(pos, &small).unwrap();
+
+        assert!(!mcc_path.exists());
+        assert_eq!(folder.get_chunk(pos).unwrap().unwrap().data, small);
+        assert_eq!(
+            fs::metadata(&mca_path).unwrap().len(),
+            (SECTOR_SIZE * 4) as u64
+        );
+    }
+}
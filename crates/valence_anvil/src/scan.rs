@@ -0,0 +1,159 @@
+use valence_core::block_pos::BlockPos;
+use valence_nbt::binary::{Event, Reader, Result};
+
+/// A block entity found by [`RegionFolder::scan_block_entities`].
+///
+/// [`RegionFolder::scan_block_entities`]: crate::RegionFolder::scan_block_entities
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ScannedBlockEntity {
+    /// The block entity's ID, such as `minecraft:chest`.
+    pub id: String,
+    /// The position of the block entity in the world.
+    pub pos: BlockPos,
+}
+
+/// Reads the ID and position of every block entity in a chunk's binary NBT,
+/// skipping everything else. Block entities without an ID or position are
+/// left out.
+pub(crate) fn block_entities(nbt: &[u8]) -> Result<Vec<ScannedBlockEntity>> {
+    let mut reader = Reader::new(nbt);
+    let mut res = vec![];
+
+    // Skip the root compound event.
+    reader.next().transpose()?;
+
+    while let Some(event) = reader.next() {
+        match event? {
+            Event::ListStart(Some(name), ..) if name == "block_entities" => {
+                read_block_entities(&mut reader, &mut res)?
+            }
+            Event::CompoundStart(_) | Event::ListStart(..) => reader.skip_value()?,
+            _ => {}
+        }
+    }
+
+    Ok(res)
+}
+
+fn read_block_entities(reader: &mut Reader, res: &mut Vec<ScannedBlockEntity>) -> Result<()> {
+    let mut id = None;
+    let [mut x, mut y, mut z] = [None; 3];
+
+    while let Some(event) = reader.next() {
+        match event? {
+            Event::ListEnd => break,
+            Event::CompoundStart(None) => {
+                id = None;
+                [x, y, z] = [None; 3];
+            }
+            Event::CompoundEnd => {
+                if let (Some(id), Some(x), Some(y), Some(z)) = (id.take(), x, y, z) {
+                    res.push(ScannedBlockEntity {
+                        id,
+                        pos: BlockPos::new(x, y, z),
+                    });
+                }
+            }
+            Event::TagString(Some(name), value) if name == "id" => id = Some(value.into_owned()),
+            Event::TagInt(Some(name), value) => match &*name {
+                "x" => x = Some(value),
+                "y" => y = Some(value),
+                "z" => z = Some(value),
+                _ => {}
+            },
+            // Don't look inside the block entities' items and other data.
+            Event::CompoundStart(_) | Event::ListStart(..) => reader.skip_value()?,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_core::chunk_pos::ChunkPos;
+    use valence_nbt::{compound, List};
+
+    use super::*;
+    use crate::RegionFolder;
+
+    #[test]
+    fn scan_block_entities() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut folder = RegionFolder::new(dir.path());
+
+        let pos = ChunkPos::new(3, -2);
+        let chest = compound! {
+            "Items" => List::Compound(vec![compound! {
+                "Count" => 1_i8,
+                "Slot" => 0_i8,
+                "id" => "minecraft:diamond",
+            }]),
+            "id" => "minecraft:chest",
+            "x" => 50,
+            "y" => 64,
+            "z" => -20,
+        };
+
+        folder
+            .set_chunk(
+                pos,
+                &compound! {
+                    "DataVersion" => 3465,
+                    "block_entities" => List::Compound(vec![
+                        chest.clone(),
+                        // Not a complete block entity.
+                        compound! { "id" => "minecraft:sign", "x" => 1 },
+                        compound! {
+                            "id" => "minecraft:sign",
+                            "x" => 48,
+                            "y" => -60,
+                            "z" => -32,
+                            "front_text" => compound! {
+                                "messages" => List::String(vec![r#""""#.into(); 4]),
+                            },
+                        },
+                    ]),
+                    "sections" => List::Compound(vec![compound! {
+                        "Y" => 0_i8,
+                        "block_states" => compound! {
+                            "data" => vec![0_i64; 256],
+                            "palette" => List::Compound(vec![chest]),
+                        },
+                    }]),
+                },
+            )
+            .unwrap();
+
+        folder
+            .set_chunk(ChunkPos::new(4, -2), &compound! { "Status" => "empty" })
+            .unwrap();
+
+        assert_eq!(
+            folder.scan_block_entities(pos).unwrap().unwrap(),
+            [
+                ScannedBlockEntity {
+                    id: "minecraft:chest".into(),
+                    pos: BlockPos::new(50, 64, -20),
+                },
+                ScannedBlockEntity {
+                    id: "minecraft:sign".into(),
+                    pos: BlockPos::new(48, -60, -32),
+                },
+            ]
+        );
+
+        assert_eq!(
+            folder
+                .scan_block_entities(ChunkPos::new(4, -2))
+                .unwrap()
+                .unwrap(),
+            []
+        );
+        assert_eq!(
+            folder.scan_block_entities(ChunkPos::new(5, -2)).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,552 @@
+//! Checking block entity NBT against the fields that clients read.
+//!
+//! Clients don't report block entity data they can't read. A field with the
+//! wrong type is silently replaced with a default, so a sign shows no text or
+//! a spawner shows no mob. The schemas here describe the fields of the most
+//! common block entities so that mistakes are found on the server instead.
+
+use std::fmt;
+
+use valence_nbt::{Compound, List, Tag, Value};
+
+use crate::BlockEntityKind;
+
+impl BlockEntityKind {
+    /// Checks a block entity compound against the fields that clients read
+    /// for this kind of block entity. Returns every mismatch that was found,
+    /// or an empty vec if the compound is valid.
+    ///
+    /// Fields that aren't in the schema are ignored, and kinds without a
+    /// schema accept any compound.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valence_block::BlockEntityKind;
+    /// use valence_nbt::compound;
+    ///
+    /// let nbt = compound! {
+    ///     "front_text" => compound! {
+    ///         "messages" => valence_nbt::List::String(vec![
+    ///             r#""Hello""#.into(),
+    ///             r#""""#.into(),
+    ///             r#""""#.into(),
+    ///         ]),
+    ///     },
+    /// };
+    ///
+    /// let mismatches = BlockEntityKind::Sign.validate_nbt(&nbt);
+    ///
+    /// assert_eq!(
+    ///     mismatches[0].to_string(),
+    ///     "`front_text.messages`: expected list of 4 strings, found list of 3 strings"
+    /// );
+    /// ```
+    pub fn validate_nbt(self, nbt: &Compound) -> Vec<SchemaMismatch> {
+        let mut mismatches = vec![];
+
+        if let Some(fields) = schema(self) {
+            check_compound(fields, nbt, &mut String::new(), &mut mismatches);
+        }
+
+        mismatches
+    }
+}
+
+/// A field of a block entity compound that doesn't match the schema. The
+/// [`Display`](fmt::Display) impl describes the mismatch in a single line.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SchemaMismatch {
+    /// The path to the field, such as `Items[2].Count`.
+    pub path: String,
+    /// A description of the expected value, such as `byte`.
+    pub expected: String,
+    /// A description of the value that was found, or `None` if a required
+    /// field is missing.
+    pub found: Option<String>,
+}
+
+impl fmt::Display for SchemaMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.found {
+            Some(found) => write!(
+                f,
+                "`{}`: expected {}, found {found}",
+                self.path, self.expected
+            ),
+            None => write!(f, "`{}`: missing required {}", self.path, self.expected),
+        }
+    }
+}
+
+struct Field {
+    name: &'static str,
+    ty: Type,
+    required: bool,
+}
+
+enum Type {
+    Scalar(Tag),
+    /// An int array of the given length.
+    IntArray(usize),
+    Compound(&'static [Field]),
+    /// A list with elements of the given type, with an exact length if there
+    /// is one.
+    List(&'static Type, Option<usize>),
+    /// Any one of the types.
+    OneOf(&'static [Type]),
+}
+
+const fn opt(name: &'static str, ty: Type) -> Field {
+    Field {
+        name,
+        ty,
+        required: false,
+    }
+}
+
+const fn req(name: &'static str, ty: Type) -> Field {
+    Field {
+        name,
+        ty,
+        required: true,
+    }
+}
+
+const BYTE: Type = Type::Scalar(Tag::Byte);
+const SHORT: Type = Type::Scalar(Tag::Short);
+const INT: Type = Type::Scalar(Tag::Int);
+const LONG: Type = Type::Scalar(Tag::Long);
+const STRING: Type = Type::Scalar(Tag::String);
+const UUID: Type = Type::IntArray(4);
+
+const BLOCK_POS: Type = Type::Compound(&[req("X", INT), req("Y", INT), req("Z", INT)]);
+
+const ITEM: Type = Type::Compound(&[
+    req("id", STRING),
+    req("Count", BYTE),
+    opt("tag", Type::Compound(&[])),
+]);
+
+const SLOTTED_ITEM: Type = Type::Compound(&[
+    req("Slot", BYTE),
+    req("id", STRING),
+    req("Count", BYTE),
+    opt("tag", Type::Compound(&[])),
+]);
+
+const ITEMS: Field = opt("Items", Type::List(&SLOTTED_ITEM, None));
+const CUSTOM_NAME: Field = opt("CustomName", STRING);
+const LOCK: Field = opt("Lock", STRING);
+const LOOT_TABLE: Field = opt("LootTable", STRING);
+const LOOT_TABLE_SEED: Field = opt("LootTableSeed", LONG);
+
+const CONTAINER: &[Field] = &[ITEMS, CUSTOM_NAME, LOCK, LOOT_TABLE, LOOT_TABLE_SEED];
+
+const FURNACE: &[Field] = &[
+    ITEMS,
+    CUSTOM_NAME,
+    LOCK,
+    opt("BurnTime", SHORT),
+    opt("CookTime", SHORT),
+    opt("CookTimeTotal", SHORT),
+];
+
+const SIGN_TEXT: Type = Type::Compound(&[
+    req("messages", Type::List(&STRING, Some(4))),
+    opt("filtered_messages", Type::List(&STRING, Some(4))),
+    opt("color", STRING),
+    opt("has_glowing_text", BYTE),
+]);
+
+const SIGN: &[Field] = &[
+    opt("front_text", SIGN_TEXT),
+    opt("back_text", SIGN_TEXT),
+    opt("is_waxed", BYTE),
+];
+
+const GAME_PROFILE: Type = Type::Compound(&[
+    opt("Id", UUID),
+    opt("Name", STRING),
+    opt("Properties", Type::Compound(&[])),
+]);
+
+const SKULL: &[Field] = &[
+    opt("SkullOwner", Type::OneOf(&[GAME_PROFILE, STRING])),
+    opt("note_block_sound", STRING),
+];
+
+const BANNER: &[Field] = &[
+    CUSTOM_NAME,
+    opt(
+        "Patterns",
+        Type::List(
+            &Type::Compound(&[req("Pattern", STRING), req("Color", INT)]),
+            None,
+        ),
+    ),
+];
+
+const SPAWN_DATA: Type = Type::Compound(&[
+    req("entity", Type::Compound(&[req("id", STRING)])),
+    opt("custom_spawn_rules", Type::Compound(&[])),
+]);
+
+const MOB_SPAWNER: &[Field] = &[
+    req("SpawnData", SPAWN_DATA),
+    opt(
+        "SpawnPotentials",
+        Type::List(
+            &Type::Compound(&[req("data", SPAWN_DATA), req("weight", INT)]),
+            None,
+        ),
+    ),
+    opt("Delay", SHORT),
+    opt("MinSpawnDelay", SHORT),
+    opt("MaxSpawnDelay", SHORT),
+    opt("SpawnCount", SHORT),
+    opt("MaxNearbyEntities", SHORT),
+    opt("RequiredPlayerRange", SHORT),
+    opt("SpawnRange", SHORT),
+];
+
+const BEACON: &[Field] = &[
+    CUSTOM_NAME,
+    LOCK,
+    opt("Primary", INT),
+    opt("Secondary", INT),
+];
+
+const CAMPFIRE: &[Field] = &[
+    opt("Items", Type::List(&SLOTTED_ITEM, None)),
+    opt("CookingTimes", Type::IntArray(4)),
+    opt("CookingTotalTimes", Type::IntArray(4)),
+];
+
+const LECTERN: &[Field] = &[opt("Book", ITEM), opt("Page", INT)];
+
+const BEEHIVE: &[Field] = &[
+    opt(
+        "Bees",
+        Type::List(
+            &Type::Compound(&[
+                req("EntityData", Type::Compound(&[])),
+                opt("TicksInHive", INT),
+                opt("MinOccupationTicks", INT),
+            ]),
+            None,
+        ),
+    ),
+    opt("FlowerPos", BLOCK_POS),
+];
+
+const DECORATED_POT: &[Field] = &[
+    opt("sherds", Type::List(&STRING, Some(4))),
+    opt("item", ITEM),
+];
+
+const JUKEBOX: &[Field] = &[opt("RecordItem", ITEM), opt("IsPlaying", BYTE)];
+
+const CHISELED_BOOKSHELF: &[Field] = &[ITEMS, opt("last_interacted_slot", INT)];
+
+const END_GATEWAY: &[Field] = &[
+    opt("Age", LONG),
+    opt("ExactTeleport", BYTE),
+    opt("ExitPortal", BLOCK_POS),
+];
+
+/// Returns the fields of a kind of block entity, or `None` if it doesn't have
+/// a schema.
+fn schema(kind: BlockEntityKind) -> Option<&'static [Field]> {
+    Some(match kind {
+        BlockEntityKind::Sign | BlockEntityKind::HangingSign => SIGN,
+        BlockEntityKind::Skull => SKULL,
+        BlockEntityKind::Banner => BANNER,
+        BlockEntityKind::MobSpawner => MOB_SPAWNER,
+        BlockEntityKind::Chest
+        | BlockEntityKind::TrappedChest
+        | BlockEntityKind::Barrel
+        | BlockEntityKind::ShulkerBox
+        | BlockEntityKind::Hopper
+        | BlockEntityKind::Dispenser
+        | BlockEntityKind::Dropper => CONTAINER,
+        BlockEntityKind::Furnace | BlockEntityKind::Smoker | BlockEntityKind::BlastFurnace => {
+            FURNACE
+        }
+        BlockEntityKind::Beacon => BEACON,
+        BlockEntityKind::Campfire => CAMPFIRE,
+        BlockEntityKind::Lectern => LECTERN,
+        BlockEntityKind::Beehive => BEEHIVE,
+        BlockEntityKind::DecoratedPot => DECORATED_POT,
+        BlockEntityKind::Jukebox => JUKEBOX,
+        BlockEntityKind::ChiseledBookshelf => CHISELED_BOOKSHELF,
+        BlockEntityKind::EndGateway => END_GATEWAY,
+        _ => return None,
+    })
+}
+
+fn check_compound(
+    fields: &[Field],
+    nbt: &Compound,
+    path: &mut String,
+    mismatches: &mut Vec<SchemaMismatch>,
+) {
+    for field in fields {
+        let len = path.len();
+
+        if !path.is_empty() {
+            path.push('.');
+        }
+        path.push_str(field.name);
+
+        match nbt.get(field.name) {
+            Some(value) => check_value(&field.ty, value, path, mismatches),
+            None if field.required => mismatches.push(SchemaMismatch {
+                path: path.clone(),
+                expected: describe_type(&field.ty),
+                found: None,
+            }),
+            None => {}
+        }
+
+        path.truncate(len);
+    }
+}
+
+fn check_value(ty: &Type, value: &Value, path: &mut String, mismatches: &mut Vec<SchemaMismatch>) {
+    if !matches_shape(ty, value) {
+        mismatches.push(SchemaMismatch {
+            path: path.clone(),
+            expected: describe_type(ty),
+            found: Some(describe_value(value)),
+        });
+        return;
+    }
+
+    match (ty, value) {
+        (Type::Compound(fields), Value::Compound(c)) => check_compound(fields, c, path, mismatches),
+        (Type::List(elem, _), Value::List(List::Compound(elems))) => {
+            for (i, c) in elems.iter().enumerate() {
+                let len = path.len();
+                path.push_str(&format!("[{i}]"));
+
+                if let Type::Compound(fields) = elem {
+                    check_compound(fields, c, path, mismatches);
+                }
+
+                path.truncate(len);
+            }
+        }
+        (Type::OneOf(types), _) => {
+            if let Some(ty) = types.iter().find(|ty| matches_shape(ty, value)) {
+                check_value(ty, value, path, mismatches);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns whether a value has the right tag for a type, not counting the
+/// contents of compounds.
+fn matches_shape(ty: &Type, value: &Value) -> bool {
+    match (ty, value) {
+        (Type::Scalar(tag), _) => *tag == Tag::element_type(value),
+        (Type::IntArray(len), Value::IntArray(arr)) => arr.len() == *len,
+        (Type::Compound(_), Value::Compound(_)) => true,
+        (Type::List(elem, len), Value::List(list)) => {
+            let elem_matches = match list {
+                List::End => true,
+                List::Compound(_) => matches!(elem, Type::Compound(_)),
+                _ => matches!(elem, Type::Scalar(tag) if *tag == list.element_tag()),
+            };
+
+            elem_matches && len.map_or(true, |len| list.len() == len)
+        }
+        (Type::OneOf(types), _) => types.iter().any(|ty| matches_shape(ty, value)),
+        _ => false,
+    }
+}
+
+fn describe_type(ty: &Type) -> String {
+    match ty {
+        Type::Scalar(tag) => tag.name().into(),
+        Type::IntArray(len) => format!("int array of length {len}"),
+        Type::Compound(_) => "compound".into(),
+        Type::List(elem, None) => format!("list of {}s", describe_type(elem)),
+        Type::List(elem, Some(len)) => format!("list of {len} {}s", describe_type(elem)),
+        Type::OneOf(types) => types
+            .iter()
+            .map(describe_type)
+            .collect::<Vec<_>>()
+            .join(" or "),
+    }
+}
+
+fn describe_value(value: &Value) -> String {
+    match value {
+        Value::IntArray(arr) => format!("int array of length {}", arr.len()),
+        Value::List(List::End) => "empty list".into(),
+        Value::List(list) => format!("list of {} {}s", list.len(), list.element_tag()),
+        _ => Tag::element_type(value).name().into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_nbt::compound;
+
+    use super::*;
+
+    fn messages(n: usize) -> List {
+        List::String(vec![r#""""#.into(); n])
+    }
+
+    fn errors(kind: BlockEntityKind, nbt: Compound) -> Vec<String> {
+        kind.validate_nbt(&nbt)
+            .iter()
+            .map(|m| m.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn valid_block_entities() {
+        let sign = compound! {
+            "front_text" => compound! {
+                "color" => "black",
+                "has_glowing_text" => 0_i8,
+                "messages" => messages(4),
+            },
+            "is_waxed" => 1_i8,
+        };
+
+        assert!(BlockEntityKind::Sign.validate_nbt(&sign).is_empty());
+        assert!(BlockEntityKind::HangingSign.validate_nbt(&sign).is_empty());
+
+        let chest = compound! {
+            "CustomName" => r#""Loot""#,
+            "Items" => List::Compound(vec![
+                compound! { "Count" => 1_i8, "Slot" => 0_i8, "id" => "minecraft:stone" },
+                compound! {
+                    "Count" => 64_i8,
+                    "Slot" => 26_i8,
+                    "id" => "minecraft:diamond",
+                    "tag" => compound! { "Damage" => 0 },
+                },
+            ]),
+        };
+
+        assert!(BlockEntityKind::Chest.validate_nbt(&chest).is_empty());
+
+        let skull = compound! { "SkullOwner" => "Notch" };
+        assert!(BlockEntityKind::Skull.validate_nbt(&skull).is_empty());
+
+        let skull = compound! {
+            "SkullOwner" => compound! { "Id" => vec![1, 2, 3, 4], "Name" => "Notch" },
+        };
+        assert!(BlockEntityKind::Skull.validate_nbt(&skull).is_empty());
+
+        // Empty lists have no element type, and unknown fields are ignored.
+        let banner = compound! { "Patterns" => List::End, "foo" => 5_i8 };
+        assert!(BlockEntityKind::Banner.validate_nbt(&banner).is_empty());
+
+        // Kinds without a schema accept anything.
+        let bed = compound! { "whatever" => 1.5 };
+        assert!(BlockEntityKind::Bed.validate_nbt(&bed).is_empty());
+    }
+
+    #[test]
+    fn wrong_types() {
+        assert_eq!(
+            errors(
+                BlockEntityKind::Sign,
+                compound! {
+                    "back_text" => compound! {
+                        "has_glowing_text" => "true",
+                        "messages" => List::Int(vec![1, 2, 3, 4]),
+                    },
+                    "front_text" => compound! { "messages" => messages(3) },
+                    "is_waxed" => 1,
+                }
+            ),
+            [
+                "`front_text.messages`: expected list of 4 strings, found list of 3 strings",
+                "`back_text.messages`: expected list of 4 strings, found list of 4 ints",
+                "`back_text.has_glowing_text`: expected byte, found string",
+                "`is_waxed`: expected byte, found int",
+            ]
+        );
+
+        assert_eq!(
+            errors(
+                BlockEntityKind::Chest,
+                compound! {
+                    "Items" => List::Compound(vec![
+                        compound! { "Count" => 1_i8, "Slot" => 0_i8, "id" => "minecraft:stone" },
+                        compound! { "Count" => 1, "Slot" => 1_i8, "id" => "minecraft:dirt" },
+                        compound! { "Slot" => 2_i8, "id" => 5_i8 },
+                    ]),
+                    "Lock" => compound! {},
+                }
+            ),
+            [
+                "`Items[1].Count`: expected byte, found int",
+                "`Items[2].id`: expected string, found byte",
+                "`Items[2].Count`: missing required byte",
+                "`Lock`: expected string, found compound",
+            ]
+        );
+
+        assert_eq!(
+            errors(
+                BlockEntityKind::Skull,
+                compound! { "SkullOwner" => compound! { "Id" => vec![1, 2, 3] } }
+            ),
+            ["`SkullOwner.Id`: expected int array of length 4, found int array of length 3"]
+        );
+
+        assert_eq!(
+            errors(BlockEntityKind::Skull, compound! { "SkullOwner" => 7 }),
+            ["`SkullOwner`: expected compound or string, found int"]
+        );
+
+        assert_eq!(
+            errors(
+                BlockEntityKind::Banner,
+                compound! {
+                    "Patterns" => List::Compound(vec![
+                        compound! { "Color" => 14, "Pattern" => "bs" },
+                        compound! { "Color" => "red", "Pattern" => "cr" },
+                    ]),
+                }
+            ),
+            ["`Patterns[1].Color`: expected int, found string"]
+        );
+
+        assert_eq!(
+            errors(
+                BlockEntityKind::Banner,
+                compound! { "Patterns" => messages(1) }
+            ),
+            ["`Patterns`: expected list of compounds, found list of 1 strings"]
+        );
+
+        assert_eq!(
+            errors(
+                BlockEntityKind::MobSpawner,
+                compound! { "Delay" => 20, "MaxSpawnDelay" => 800_i16 }
+            ),
+            [
+                "`SpawnData`: missing required compound",
+                "`Delay`: expected short, found int",
+            ]
+        );
+
+        assert_eq!(
+            errors(
+                BlockEntityKind::MobSpawner,
+                compound! { "SpawnData" => compound! { "entity" => compound! { "id" => 5 } } }
+            ),
+            ["`SpawnData.entity.id`: expected string, found int"]
+        );
+    }
+}
@@ -30,6 +30,11 @@ use valence_core::item::ItemKind;
 use valence_core::protocol::var_int::VarInt;
 use valence_core::protocol::{Decode, Encode};
 
+#[cfg(feature = "validate_block_entities")]
+pub mod block_entity_schema;
+#[cfg(feature = "serde")]
+mod serialize;
+
 include!(concat!(env!("OUT_DIR"), "/block.rs"));
 
 impl fmt::Debug for BlockState {
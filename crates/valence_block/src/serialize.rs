@@ -0,0 +1,309 @@
+//! [`Serialize`] and [`Deserialize`] impls for blocks.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::de::{Error, MapAccess, SeqAccess, Visitor};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{BlockKind, BlockState, PropName, PropValue};
+
+/// Block kinds are serialized by name, such as `"oak_log"`. Names with the
+/// `minecraft` namespace are also accepted.
+impl Serialize for BlockKind {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_str().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BlockKind {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        kind_from_name(&String::deserialize(deserializer)?)
+    }
+}
+
+/// Block states are serialized as `{name: "oak_log", properties: {axis:
+/// "x"}}`. When deserializing from a human readable format, properties that
+/// are left out take their default value and a bare name such as `"oak_log"`
+/// is the default state of the block.
+impl Serialize for BlockState {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        struct Properties(BlockState);
+
+        impl Serialize for Properties {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                serializer.collect_map(self.0.to_kind().props().iter().map(|&name| {
+                    let value = self.0.get(name).expect("block has property");
+                    (name.to_str(), value.to_str())
+                }))
+            }
+        }
+
+        let mut s = serializer.serialize_struct("BlockState", 2)?;
+        s.serialize_field("name", self.to_kind().to_str())?;
+        s.serialize_field("properties", &Properties(*self))?;
+        s.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for BlockState {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(BlockStateVisitor)
+        } else {
+            deserializer.deserialize_struct(
+                "BlockState",
+                &["name", "properties"],
+                BlockStateVisitor,
+            )
+        }
+    }
+}
+
+struct BlockStateVisitor;
+
+impl<'de> Visitor<'de> for BlockStateVisitor {
+    type Value = BlockState;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a block name or a map with a block name and properties")
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(kind_from_name(v)?.to_state())
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let name: String = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+
+        let props: BTreeMap<String, PropString> = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+
+        state_from_parts(&name, props)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut name: Option<String> = None;
+        let mut props = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "name" if name.is_none() => name = Some(map.next_value()?),
+                "properties" if props.is_none() => props = Some(map.next_value()?),
+                "name" | "properties" => {
+                    return Err(A::Error::custom(format!("duplicate field `{key}`")))
+                }
+                _ => return Err(A::Error::unknown_field(&key, &["name", "properties"])),
+            }
+        }
+
+        let name = name.ok_or_else(|| A::Error::missing_field("name"))?;
+
+        state_from_parts(&name, props.unwrap_or_default())
+    }
+}
+
+/// A property value, which may also be written as a boolean or integer in
+/// human readable formats.
+struct PropString(String);
+
+impl<'de> Deserialize<'de> for PropString {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct PropStringVisitor;
+
+        impl<'de> Visitor<'de> for PropStringVisitor {
+            type Value = PropString;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a property value")
+            }
+
+            fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(PropString(v.to_owned()))
+            }
+
+            fn visit_bool<E: Error>(self, v: bool) -> Result<Self::Value, E> {
+                Ok(PropString(v.to_string()))
+            }
+
+            fn visit_i64<E: Error>(self, v: i64) -> Result<Self::Value, E> {
+                Ok(PropString(v.to_string()))
+            }
+
+            fn visit_u64<E: Error>(self, v: u64) -> Result<Self::Value, E> {
+                Ok(PropString(v.to_string()))
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(PropStringVisitor)
+        } else {
+            deserializer.deserialize_str(PropStringVisitor)
+        }
+    }
+}
+
+fn kind_from_name<E: Error>(name: &str) -> Result<BlockKind, E> {
+    let path = name.strip_prefix("minecraft:").unwrap_or(name);
+
+    BlockKind::from_str(path).ok_or_else(|| E::custom(format!("unknown block kind \"{name}\"")))
+}
+
+fn state_from_parts<E: Error>(
+    name: &str,
+    props: BTreeMap<String, PropString>,
+) -> Result<BlockState, E> {
+    let kind = kind_from_name(name)?;
+    let mut state = kind.to_state();
+
+    for (prop_name, PropString(prop_value)) in props {
+        let prop = PropName::from_str(&prop_name)
+            .filter(|prop| kind.props().contains(prop))
+            .ok_or_else(|| {
+                E::custom(format!(
+                    "unknown property \"{prop_name}\" for block \"{name}\""
+                ))
+            })?;
+
+        let value = PropValue::from_str(&prop_value)
+            .filter(|&value| state.set(prop, value).get(prop) == Some(value))
+            .ok_or_else(|| {
+                E::custom(format!(
+                    "invalid value \"{prop_value}\" of property \"{prop_name}\" for block \
+                     \"{name}\""
+                ))
+            })?;
+
+        state = state.set(prop, value);
+    }
+
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_kind_serde() {
+        assert_eq!(
+            serde_json::to_string(&BlockKind::OakLog).unwrap(),
+            r#""oak_log""#
+        );
+        assert_eq!(
+            serde_json::from_str::<BlockKind>(r#""minecraft:oak_log""#).unwrap(),
+            BlockKind::OakLog
+        );
+        assert!(serde_json::from_str::<BlockKind>(r#""oak_logs""#)
+            .unwrap_err()
+            .to_string()
+            .starts_with(r#"unknown block kind "oak_logs""#));
+    }
+
+    #[test]
+    fn block_state_round_trip() {
+        let states = [
+            BlockState::STONE,
+            BlockState::OAK_LOG.set(PropName::Axis, PropValue::X),
+            BlockState::OAK_STAIRS
+                .set(PropName::Facing, PropValue::West)
+                .set(PropName::Half, PropValue::Top)
+                .set(PropName::Shape, PropValue::OuterLeft)
+                .set(PropName::Waterlogged, PropValue::True),
+            BlockState::REDSTONE_WIRE.set(PropName::Power, PropValue::_15),
+        ];
+
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Config {
+            states: Vec<BlockState>,
+            kinds: Vec<BlockKind>,
+        }
+
+        let config = Config {
+            states: states.to_vec(),
+            kinds: states.iter().map(|s| s.to_kind()).collect(),
+        };
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(serde_json::from_str::<Config>(&json).unwrap(), config);
+
+        let toml = toml::to_string(&config).unwrap();
+        assert_eq!(toml::from_str::<Config>(&toml).unwrap(), config);
+
+        let bin = bincode::serialize(&config).unwrap();
+        assert_eq!(bincode::deserialize::<Config>(&bin).unwrap(), config);
+
+        assert_eq!(
+            serde_json::to_value(states[2]).unwrap(),
+            serde_json::json!({
+                "name": "oak_stairs",
+                "properties": {
+                    "facing": "west",
+                    "half": "top",
+                    "shape": "outer_left",
+                    "waterlogged": "true",
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn block_state_leniency() {
+        let read = |json: &str| serde_json::from_str::<BlockState>(json);
+
+        assert_eq!(read(r#""oak_log""#).unwrap(), BlockState::OAK_LOG);
+        assert_eq!(
+            read(r#"{"name": "minecraft:oak_log"}"#).unwrap(),
+            BlockState::OAK_LOG
+        );
+        assert_eq!(
+            read(r#"{"name": "redstone_wire", "properties": {"power": 7, "north": "up"}}"#)
+                .unwrap(),
+            BlockState::REDSTONE_WIRE
+                .set(PropName::Power, PropValue::_7)
+                .set(PropName::North, PropValue::Up)
+        );
+        assert_eq!(
+            read(r#"{"name": "oak_stairs", "properties": {"waterlogged": true}}"#).unwrap(),
+            BlockState::OAK_STAIRS.set(PropName::Waterlogged, PropValue::True)
+        );
+
+        let err = |json: &str| read(json).unwrap_err().to_string();
+
+        assert!(err(r#""not_a_block""#).starts_with(r#"unknown block kind "not_a_block""#));
+        assert!(err(r#"{"name": "stone", "properties": {"axis": "x"}}"#)
+            .starts_with(r#"unknown property "axis" for block "stone""#));
+        assert!(err(r#"{"name": "oak_log", "properties": {"axis": "up"}}"#)
+            .starts_with(r#"invalid value "up" of property "axis" for block "oak_log""#));
+        assert!(err(r#"{"properties": {}}"#).starts_with("missing field `name`"));
+        assert!(err(r#"{"name": "stone", "color": "red"}"#).starts_with("unknown field `color`"));
+    }
+}
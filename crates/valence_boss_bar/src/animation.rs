@@ -0,0 +1,106 @@
+use bevy_ecs::prelude::*;
+
+use crate::components::BossBarHealth;
+
+/// Moves the health of a boss bar toward a target over a number of ticks.
+///
+/// The component removes itself once the target is reached. Inserting or
+/// changing it restarts the animation from the current health of the boss
+/// bar.
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct BossBarAnimation {
+    /// The health that the boss bar ends up with.
+    pub target: f32,
+    /// The number of ticks that the animation lasts.
+    pub ticks: u32,
+    /// How the health moves between its start and the target.
+    pub easing: BossBarEasing,
+    /// The number of ticks between health updates sent to viewers. The final
+    /// health is always sent.
+    pub interval: u32,
+    start: f32,
+    elapsed: u32,
+}
+
+impl BossBarAnimation {
+    /// Creates a linear animation that updates the health every tick.
+    pub fn new(target: f32, ticks: u32) -> Self {
+        Self {
+            target,
+            ticks,
+            easing: BossBarEasing::Linear,
+            interval: 1,
+            start: 0.0,
+            elapsed: 0,
+        }
+    }
+
+    pub fn with_easing(mut self, easing: BossBarEasing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    pub fn with_interval(mut self, interval: u32) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+/// The easing function of a [`BossBarAnimation`].
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum BossBarEasing {
+    #[default]
+    Linear,
+    /// Starts slow and speeds up.
+    EaseIn,
+    /// Starts fast and slows down.
+    EaseOut,
+    /// Starts and ends slow.
+    EaseInOut,
+}
+
+impl BossBarEasing {
+    /// Maps the progress of an animation, from 0 to 1, to how far the health
+    /// has moved toward the target, from 0 to 1.
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            BossBarEasing::Linear => t,
+            BossBarEasing::EaseIn => t * t,
+            BossBarEasing::EaseOut => t * (2.0 - t),
+            BossBarEasing::EaseInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - 2.0 * (1.0 - t) * (1.0 - t)
+                }
+            }
+        }
+    }
+}
+
+/// System that steps the boss bar animations and removes the finished ones.
+pub(crate) fn boss_bar_animation(
+    mut boss_bars: Query<(Entity, &mut BossBarAnimation, &mut BossBarHealth)>,
+    mut commands: Commands,
+) {
+    for (entity, mut animation, mut health) in &mut boss_bars {
+        if animation.is_changed() {
+            let animation = animation.bypass_change_detection();
+            animation.start = health.0;
+            animation.elapsed = 0;
+        }
+
+        // Stepping the animation isn't a change that restarts it.
+        let animation = animation.bypass_change_detection();
+        animation.elapsed += 1;
+
+        if animation.elapsed >= animation.ticks {
+            health.0 = animation.target;
+            commands.entity(entity).remove::<BossBarAnimation>();
+        } else if animation.elapsed % animation.interval.max(1) == 0 {
+            let t = animation.elapsed as f32 / animation.ticks as f32;
+            health.0 =
+                animation.start + (animation.target - animation.start) * animation.easing.apply(t);
+        }
+    }
+}
@@ -77,13 +77,23 @@ pub enum BossBarDivision {
 #[bitfield(u8)]
 #[derive(Component, PartialEq, Eq, Encode, Decode)]
 pub struct BossBarFlags {
+    /// Darkens the sky of viewers, like the wither does.
     pub darken_sky: bool,
+    /// Plays the ender dragon's boss music to viewers.
     pub dragon_bar: bool,
+    /// Adds fog around viewers, like the ender dragon does.
     pub create_fog: bool,
     #[bits(5)]
     _pad: u8,
 }
 
+/// The order of a boss bar among the other boss bars of its viewers. Boss bars
+/// with a higher priority are shown above those with a lower priority, and
+/// boss bars with the same priority are ordered by entity. Boss bars without
+/// this component have a priority of 0.
+#[derive(Component, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Debug)]
+pub struct BossBarPriority(pub i32);
+
 /// The viewers of a boss bar.
 #[derive(Component, Default)]
 pub struct BossBarViewers {
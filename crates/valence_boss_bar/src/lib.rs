@@ -19,6 +19,8 @@
 )]
 
 use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
 
 use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
@@ -28,7 +30,9 @@ use valence_core::despawn::Despawned;
 use valence_core::protocol::encode::WritePacket;
 use valence_core::uuid::UniqueId;
 
+mod animation;
 mod components;
+pub use animation::*;
 pub use components::*;
 
 pub mod packet;
@@ -41,9 +45,10 @@ impl Plugin for BossBarPlugin {
             PostUpdate,
             (
                 boss_bar_title_update,
+                boss_bar_animation.before(boss_bar_health_update),
                 boss_bar_health_update,
                 boss_bar_style_update,
-                boss_bar_flags_update,
+                boss_bar_flags_update.before(boss_bar_viewers_update),
                 boss_bar_viewers_update,
                 boss_bar_despawn,
                 client_disconnection.before(boss_bar_viewers_update),
@@ -109,12 +114,19 @@ fn boss_bar_style_update(
 
 /// System that sends a bossbar update flags packet to all viewers of a boss bar
 /// that has had its flags updated.
+///
+/// The flags can be changed any number of times during a tick, and only their
+/// final value is sent. Viewers that are added in the same tick get the flags
+/// in the add packet instead.
 fn boss_bar_flags_update(
     boss_bars: Query<(&UniqueId, &BossBarFlags, &BossBarViewers), Changed<BossBarFlags>>,
     mut clients: Query<&mut Client>,
 ) {
     for (id, flags, boss_bar_viewers) in boss_bars.iter() {
-        for viewer in boss_bar_viewers.viewers.iter() {
+        for viewer in boss_bar_viewers
+            .viewers
+            .intersection(&boss_bar_viewers.old_viewers)
+        {
             if let Ok(mut client) = clients.get_mut(*viewer) {
                 client.write_packet(&BossBarS2c {
                     id: id.0,
@@ -127,41 +139,86 @@ fn boss_bar_flags_update(
 
 /// System that sends a bossbar add/remove packet to all viewers of a boss bar
 /// that just have been added/removed.
+///
+/// Clients stack boss bars in the order they were added, so boss bars are added
+/// in [`BossBarPriority`] order. When a client gains a boss bar or the priority
+/// of one of its boss bars changes, the boss bars that must come after it are
+/// removed and added again.
 fn boss_bar_viewers_update(
     mut boss_bars: Query<
         (
+            Entity,
             &UniqueId,
             &BossBarTitle,
             &BossBarHealth,
             &BossBarStyle,
             &BossBarFlags,
+            Option<Ref<BossBarPriority>>,
             &mut BossBarViewers,
         ),
-        Changed<BossBarViewers>,
+        Without<Despawned>,
     >,
+    mut removed_priorities: RemovedComponents<BossBarPriority>,
     mut clients: Query<&mut Client>,
 ) {
-    for (id, title, health, style, flags, mut boss_bar_viewers) in boss_bars.iter_mut() {
+    let mut reprioritized: BTreeSet<Entity> = removed_priorities.iter().collect();
+
+    // The boss bars of each client that have to be put in place.
+    let mut misplaced: BTreeMap<Entity, BTreeSet<Entity>> = BTreeMap::new();
+
+    for (entity, id, _, _, _, _, priority, boss_bar_viewers) in &boss_bars {
+        if priority.is_some_and(|p| p.is_changed()) {
+            reprioritized.insert(entity);
+        }
+
         let old_viewers = &boss_bar_viewers.old_viewers;
         let current_viewers = &boss_bar_viewers.viewers;
 
         for &added_viewer in current_viewers.difference(old_viewers) {
-            if let Ok(mut client) = clients.get_mut(added_viewer) {
+            misplaced.entry(added_viewer).or_default().insert(entity);
+        }
+
+        if reprioritized.contains(&entity) {
+            for &viewer in current_viewers.intersection(old_viewers) {
+                misplaced.entry(viewer).or_default().insert(entity);
+            }
+        }
+
+        for &removed_viewer in old_viewers.difference(current_viewers) {
+            if let Ok(mut client) = clients.get_mut(removed_viewer) {
                 client.write_packet(&BossBarS2c {
                     id: id.0,
-                    action: BossBarAction::Add {
-                        title: Cow::Borrowed(&title.0),
-                        health: health.0,
-                        color: style.color,
-                        division: style.division,
-                        flags: *flags,
-                    },
+                    action: BossBarAction::Remove,
                 });
             }
         }
+    }
 
-        for &removed_viewer in old_viewers.difference(current_viewers) {
-            if let Ok(mut client) = clients.get_mut(removed_viewer) {
+    for (viewer, misplaced) in misplaced {
+        let Ok(mut client) = clients.get_mut(viewer) else {
+            continue;
+        };
+
+        let mut order: Vec<_> = boss_bars
+            .iter()
+            .filter(|(.., viewers)| viewers.viewers.contains(&viewer))
+            .map(|(entity, .., priority, _)| (Reverse(priority.map_or(0, |p| p.0)), entity))
+            .collect();
+
+        order.sort_unstable();
+
+        // The boss bars before the first misplaced one are already in order.
+        let first = order
+            .iter()
+            .position(|(_, entity)| misplaced.contains(entity))
+            .unwrap_or(order.len());
+
+        for &(_, entity) in &order[first..] {
+            let Ok((_, id, .., boss_bar_viewers)) = boss_bars.get(entity) else {
+                continue;
+            };
+
+            if boss_bar_viewers.old_viewers.contains(&viewer) {
                 client.write_packet(&BossBarS2c {
                     id: id.0,
                     action: BossBarAction::Remove,
@@ -169,7 +226,28 @@ fn boss_bar_viewers_update(
             }
         }
 
-        boss_bar_viewers.old_viewers = boss_bar_viewers.viewers.clone();
+        for &(_, entity) in &order[first..] {
+            let Ok((_, id, title, health, style, flags, ..)) = boss_bars.get(entity) else {
+                continue;
+            };
+
+            client.write_packet(&BossBarS2c {
+                id: id.0,
+                action: BossBarAction::Add {
+                    title: Cow::Borrowed(&title.0),
+                    health: health.0,
+                    color: style.color,
+                    division: style.division,
+                    flags: *flags,
+                },
+            });
+        }
+    }
+
+    for (.., mut boss_bar_viewers) in &mut boss_bars {
+        if boss_bar_viewers.old_viewers != boss_bar_viewers.viewers {
+            boss_bar_viewers.old_viewers = boss_bar_viewers.viewers.clone();
+        }
     }
 }
 
@@ -0,0 +1,204 @@
+//! Handling of clients that can't keep up with the data sent to them.
+//!
+//! Data sent to a client is buffered until the connection can take it. Clients
+//! with slow connections would otherwise accumulate buffered data without
+//! bound, mostly from chunks entering their view. See
+//! [`BackpressureSettings`] for how such clients are handled.
+
+use std::collections::BTreeSet;
+
+use super::*;
+
+pub(super) fn build(app: &mut App) {
+    app.init_resource::<BackpressureSettings>().add_systems(
+        PostUpdate,
+        (
+            send_pending_chunks.after(remove_entities),
+            disconnect_overloaded_clients.after(send_pending_chunks),
+        )
+            .in_set(UpdateClientsSet),
+    );
+}
+
+/// Limits the amount of outgoing data buffered for each client. See
+/// [`Client::buffered_bytes`].
+#[derive(Resource, Clone, PartialEq, Eq, Debug)]
+pub struct BackpressureSettings {
+    /// The number of buffered bytes at which a client is considered
+    /// overloaded. `None` disables the limit.
+    ///
+    /// This should be lower than the hard limit on outgoing data set by the
+    /// networking layer, if any. Clients over that limit are disconnected
+    /// regardless of the [`policy`](Self::policy).
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    pub max_buffered_bytes: Option<usize>,
+    /// What to do with overloaded clients.
+    ///
+    /// # Default Value
+    ///
+    /// [`BackpressurePolicy::PauseChunks`]
+    pub policy: BackpressurePolicy,
+}
+
+impl Default for BackpressureSettings {
+    fn default() -> Self {
+        Self {
+            max_buffered_bytes: None,
+            policy: BackpressurePolicy::PauseChunks,
+        }
+    }
+}
+
+impl BackpressureSettings {
+    /// Returns whether `client` is at or over the limit.
+    pub fn is_overloaded(&self, client: &Client) -> bool {
+        self.max_buffered_bytes
+            .is_some_and(|max| client.buffered_bytes() >= max)
+    }
+
+    /// Returns whether chunks entering the client's view should be added to
+    /// the pending chunks instead of being sent. Once any chunks are pending,
+    /// new chunks are queued behind them so that the nearest chunks are sent
+    /// first.
+    pub(crate) fn should_defer_chunks(&self, client: &Client, pending: &PendingChunks) -> bool {
+        self.policy == BackpressurePolicy::PauseChunks
+            && (!pending.is_empty() || self.is_overloaded(client))
+    }
+}
+
+/// What to do with clients over [`BackpressureSettings::max_buffered_bytes`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BackpressurePolicy {
+    /// Chunks entering the client's view are not sent until the client is
+    /// below the limit again. The chunks are added to the client's
+    /// [`PendingChunks`] and sent nearest first once the client catches up.
+    /// Entities in pending chunks are not visible to the client.
+    PauseChunks,
+    /// The client is disconnected.
+    Disconnect,
+}
+
+/// The chunks in a client's view that have not been sent to the client
+/// because it was overloaded. See [`BackpressurePolicy::PauseChunks`].
+///
+/// You should not need to modify this directly under normal circumstances.
+#[derive(Component, Clone, Default, Debug)]
+pub struct PendingChunks(BTreeSet<ChunkPos>);
+
+impl PendingChunks {
+    pub fn contains(&self, pos: ChunkPos) -> bool {
+        self.0.contains(&pos)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ChunkPos> + '_ {
+        self.0.iter().copied()
+    }
+
+    pub(crate) fn insert(&mut self, pos: ChunkPos) {
+        self.0.insert(pos);
+    }
+
+    pub(crate) fn remove(&mut self, pos: ChunkPos) -> bool {
+        self.0.remove(&pos)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// Sends pending chunks to clients that are no longer overloaded.
+fn send_pending_chunks(
+    mut clients: Query<(
+        Entity,
+        &mut Client,
+        &mut PendingChunks,
+        &Location,
+        &Position,
+        &ViewDistance,
+    )>,
+    instances: Query<&Instance>,
+    entities: Query<(EntityInitQuery, &Position)>,
+    settings: Res<BackpressureSettings>,
+) {
+    clients.par_iter_mut().for_each_mut(
+        |(self_entity, mut client, mut pending, loc, pos, view_dist)| {
+            if pending.is_empty() || settings.is_overloaded(&client) {
+                return;
+            }
+
+            let Ok(inst) = instances.get(loc.0) else {
+                return;
+            };
+
+            let view = ChunkView::new(ChunkPos::from_dvec3(pos.0), view_dist.0);
+
+            let mut queue: Vec<_> = pending.iter().collect();
+            queue.sort_by_key(|&p| view.pos.distance_squared(p));
+
+            for pos in queue {
+                if settings.is_overloaded(&client) {
+                    break;
+                }
+
+                if !view.contains(pos) {
+                    pending.remove(pos);
+                    continue;
+                }
+
+                let Some(chunk) = inst.chunk(pos) else {
+                    // Stays pending in case the chunk is inserted again.
+                    continue;
+                };
+
+                if chunk.state() == ChunkState::Removed || chunk.state() == ChunkState::AddedRemoved
+                {
+                    continue;
+                }
+
+                chunk.write_init_packets(&mut *client, pos, inst.info());
+
+                for entity in chunk.entities() {
+                    // Skip client's own entity.
+                    if entity != self_entity {
+                        if let Ok((entity, pos)) = entities.get(entity) {
+                            entity.write_init_packets(pos.get(), &mut *client);
+                        }
+                    }
+                }
+
+                pending.remove(pos);
+            }
+        },
+    );
+}
+
+fn disconnect_overloaded_clients(
+    clients: Query<(Entity, &Client)>,
+    settings: Res<BackpressureSettings>,
+    mut commands: Commands,
+) {
+    if settings.policy != BackpressurePolicy::Disconnect || settings.max_buffered_bytes.is_none() {
+        return;
+    }
+
+    for (entity, client) in &clients {
+        if settings.is_overloaded(client) {
+            commands.add(DisconnectClient {
+                client: entity,
+                reason: "Connection overloaded".into(),
+            });
+        }
+    }
+}
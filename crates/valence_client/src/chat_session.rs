@@ -0,0 +1,609 @@
+//! Secure chat: the chat sessions of clients and the signatures on their chat
+//! messages.
+//!
+//! Clients send their chat signing key in a [`PlayerSessionC2s`] packet after
+//! joining, and then sign every chat message with it. The session is stored in
+//! the client's [`ChatSession`] component, and the signature of every chat
+//! message is checked against it. Whether a message carried a valid signature
+//! is available from [`ChatMessageEvent::signature`].
+//!
+//! Signed messages can be shown to other clients with their signatures intact
+//! using [`write_player_chat`], so that vanilla clients can verify them
+//! themselves. See [`ChatSessionSettings::signed_chat`].
+//!
+//! [`ChatMessageEvent::signature`]: crate::message::ChatMessageEvent::signature
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{PaddingScheme, PublicKey, RsaPublicKey};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use valence_core::protocol::packet::chat::{
+    ChatMessageC2s, ChatMessageS2c, MessageFilterType, MessageSignature, PlayerSessionC2s,
+};
+
+use super::*;
+use crate::message::ChatType;
+use crate::packet::ServerMetadataS2c;
+
+pub(super) fn build(app: &mut App) {
+    app.init_resource::<ChatSessionSettings>()
+        .add_systems(PostUpdate, send_server_data.in_set(UpdateClientsSet));
+}
+
+/// Settings for the chat sessions of clients.
+#[derive(Resource, Clone, Debug)]
+pub struct ChatSessionSettings {
+    /// The keys allowed to sign the public keys of chat sessions. A session is
+    /// only accepted if its key was signed by one of these for the client's
+    /// UUID. `None` accepts any key, which is what offline mode servers should
+    /// use since their players have no way to get a signed key.
+    ///
+    /// In [online mode], the network plugin sets this to the player
+    /// certificate keys of the session authenticator, which are Mojang's by
+    /// default. Sessions sent before the keys have been fetched are rejected.
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    ///
+    /// [online mode]: https://docs.rs/valence_network/latest/valence_network/enum.ConnectionMode.html#variant.Online
+    pub trusted_keys: Option<Vec<RsaPublicKey>>,
+    /// How signed messages are shown to other clients.
+    ///
+    /// # Default Value
+    ///
+    /// [`SignedChatMode::Strip`]
+    pub signed_chat: SignedChatMode,
+}
+
+impl Default for ChatSessionSettings {
+    fn default() -> Self {
+        Self {
+            trusted_keys: None,
+            signed_chat: SignedChatMode::Strip,
+        }
+    }
+}
+
+/// How signed chat messages are shown to other clients. See
+/// [`write_player_chat`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SignedChatMode {
+    /// Messages are relayed with their signatures, and the chat sessions of
+    /// clients are sent to others in the player list so that vanilla clients
+    /// can verify the messages.
+    ///
+    /// Vanilla clients expect every message from a player with a session to
+    /// be signed, so all player chat must go through [`write_player_chat`].
+    Preserve,
+    /// Like [`Preserve`](Self::Preserve), but the server also declares that it
+    /// enforces secure chat, in the status response and to clients as they
+    /// join. Clients that send chat without a valid signature or an invalid
+    /// chat session are disconnected, like on vanilla servers with
+    /// `enforce-secure-chat` enabled, and no
+    /// [`ChatMessageEvent`](crate::message::ChatMessageEvent) is sent for
+    /// their messages.
+    Enforce,
+    /// Signatures are removed and messages are shown as unsigned chat.
+    Strip,
+}
+
+impl SignedChatMode {
+    /// Whether signed messages are relayed with their signatures.
+    pub fn preserves_signatures(self) -> bool {
+        self != Self::Strip
+    }
+
+    /// Whether the server declares that it enforces secure chat.
+    pub fn enforces_secure_chat(self) -> bool {
+        self == Self::Enforce
+    }
+}
+
+/// The chat session of a client: the key it signs its chat messages with, if
+/// it has sent a valid one.
+#[derive(Component, Default, Debug)]
+pub struct ChatSession {
+    session: Option<PlayerSession>,
+}
+
+/// The state of the chat messages signed by a client and of the signed
+/// messages sent to it.
+#[derive(Component, Default, Debug)]
+pub struct SignedChatState {
+    /// The index in the message chain of the next signed message from the
+    /// client.
+    next_index: i32,
+    /// The timestamp of the last chat message from the client.
+    last_timestamp: u64,
+    last_seen: LastSeenValidator,
+}
+
+/// A public key a client signs its chat messages with.
+#[derive(Clone, Debug)]
+pub struct PlayerSession {
+    session_id: Uuid,
+    expires_at: i64,
+    public_key: RsaPublicKey,
+    public_key_der: Box<[u8]>,
+    key_signature: Box<[u8]>,
+}
+
+impl PlayerSession {
+    /// The random ID the client chose for the session.
+    pub fn session_id(&self) -> Uuid {
+        self.session_id
+    }
+
+    /// When the public key expires, as a Unix timestamp in milliseconds.
+    pub fn expires_at(&self) -> i64 {
+        self.expires_at
+    }
+
+    pub fn public_key(&self) -> &RsaPublicKey {
+        &self.public_key
+    }
+
+    /// The public key, DER encoded as a `SubjectPublicKeyInfo`.
+    pub fn public_key_der(&self) -> &[u8] {
+        &self.public_key_der
+    }
+
+    /// The signature of the public key by Mojang, or whoever signed it.
+    pub fn key_signature(&self) -> &[u8] {
+        &self.key_signature
+    }
+
+    /// Whether the public key has expired at `now`, a Unix timestamp in
+    /// milliseconds.
+    pub fn has_expired(&self, now: i64) -> bool {
+        self.expires_at < now
+    }
+}
+
+/// The signature on a chat message from a client.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ChatSignature {
+    /// The message was not signed. Clients don't sign messages when they have
+    /// no chat session or when chat signing is disabled in their settings.
+    Unsigned,
+    /// The message was signed, but the signature could not be verified.
+    Invalid(ChatSessionError),
+    /// The message was signed with the client's chat session.
+    Valid(Box<SignedMessage>),
+}
+
+impl ChatSignature {
+    pub fn is_valid(&self) -> bool {
+        matches!(self, Self::Valid(_))
+    }
+}
+
+/// The signed parts of a chat message, other than the message itself.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SignedMessage {
+    /// The UUID of the client that sent the message.
+    pub sender: Uuid,
+    pub session_id: Uuid,
+    /// The index of the message in the chain of messages from the session.
+    pub index: i32,
+    pub salt: u64,
+    /// Unix timestamp in milliseconds.
+    pub timestamp: u64,
+    pub signature: Box<[u8; 256]>,
+    /// The signatures of the messages the client had seen when it sent this
+    /// one, oldest first.
+    pub last_seen: Vec<[u8; 256]>,
+}
+
+/// The reasons a chat session or a signed message can be rejected.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Error)]
+pub enum ChatSessionError {
+    #[error("invalid chat session public key")]
+    InvalidPublicKey,
+    #[error("chat session public key was not signed by a trusted key")]
+    UntrustedPublicKey,
+    #[error("chat session public key has expired")]
+    ExpiredPublicKey,
+    #[error("signed message without a chat session")]
+    MissingSession,
+    #[error("invalid message signature")]
+    InvalidSignature,
+    #[error("chat message received out of order")]
+    OutOfOrder,
+    #[error("invalid message acknowledgements")]
+    InvalidAcknowledgements,
+}
+
+/// The maximum length of the DER encoded public key of a session.
+const MAX_KEY_LEN: usize = 512;
+/// The maximum length of the signature of a session's public key.
+const MAX_KEY_SIGNATURE_LEN: usize = 4096;
+
+impl ChatSession {
+    /// The client's current session, if it has sent a valid one.
+    pub fn session(&self) -> Option<&PlayerSession> {
+        self.session.as_ref()
+    }
+
+    /// Replaces the client's session with the one in `pkt`. The session is
+    /// removed if `pkt` is invalid.
+    pub(crate) fn update_session(
+        &mut self,
+        uuid: Uuid,
+        pkt: &PlayerSessionC2s,
+        settings: &ChatSessionSettings,
+    ) -> Result<(), ChatSessionError> {
+        self.session = None;
+
+        if pkt.public_key_data.len() > MAX_KEY_LEN
+            || pkt.key_signature.len() > MAX_KEY_SIGNATURE_LEN
+        {
+            return Err(ChatSessionError::InvalidPublicKey);
+        }
+
+        if pkt.expires_at < unix_millis() {
+            return Err(ChatSessionError::ExpiredPublicKey);
+        }
+
+        let public_key = RsaPublicKey::from_public_key_der(pkt.public_key_data)
+            .map_err(|_| ChatSessionError::InvalidPublicKey)?;
+
+        if let Some(trusted_keys) = &settings.trusted_keys {
+            let hash = Sha1::new()
+                .chain_update(uuid.as_bytes())
+                .chain_update(pkt.expires_at.to_be_bytes())
+                .chain_update(pkt.public_key_data)
+                .finalize();
+
+            let trusted = trusted_keys.iter().any(|key| {
+                key.verify(
+                    PaddingScheme::new_pkcs1v15_sign::<Sha1>(),
+                    &hash,
+                    pkt.key_signature,
+                )
+                .is_ok()
+            });
+
+            if !trusted {
+                return Err(ChatSessionError::UntrustedPublicKey);
+            }
+        }
+
+        self.session = Some(PlayerSession {
+            session_id: pkt.session_id,
+            expires_at: pkt.expires_at,
+            public_key,
+            public_key_der: pkt.public_key_data.into(),
+            key_signature: pkt.key_signature.into(),
+        });
+
+        Ok(())
+    }
+}
+
+impl SignedChatState {
+    /// Starts a new message chain for a new session.
+    pub(crate) fn reset_chain(&mut self) {
+        self.next_index = 0;
+    }
+
+    /// Handles a `MessageAcknowledgmentC2s` packet.
+    pub(crate) fn acknowledge(&mut self, offset: i32) -> Result<(), ChatSessionError> {
+        if self.last_seen.apply_offset(offset) {
+            Ok(())
+        } else {
+            Err(ChatSessionError::InvalidAcknowledgements)
+        }
+    }
+
+    /// Checks the signature of a chat message from the client with UUID
+    /// `sender` and chat session `session`.
+    pub(crate) fn verify_message(
+        &mut self,
+        sender: Uuid,
+        session: &ChatSession,
+        pkt: &ChatMessageC2s,
+    ) -> ChatSignature {
+        let last_seen = self
+            .last_seen
+            .apply_update(pkt.message_count.0, pkt.acknowledgement);
+
+        let in_order = pkt.timestamp >= self.last_timestamp;
+        self.last_timestamp = self.last_timestamp.max(pkt.timestamp);
+
+        let Some(signature) = pkt.signature else {
+            return ChatSignature::Unsigned;
+        };
+
+        let Some(session) = &session.session else {
+            return ChatSignature::Invalid(ChatSessionError::MissingSession);
+        };
+
+        // The client advances its chain with every signed message, whether or
+        // not we accept it.
+        let index = self.next_index;
+        self.next_index = self.next_index.wrapping_add(1);
+
+        if session.has_expired(unix_millis()) {
+            return ChatSignature::Invalid(ChatSessionError::ExpiredPublicKey);
+        }
+
+        if !in_order {
+            return ChatSignature::Invalid(ChatSessionError::OutOfOrder);
+        }
+
+        let Some(last_seen) = last_seen else {
+            return ChatSignature::Invalid(ChatSessionError::InvalidAcknowledgements);
+        };
+
+        let msg = SignedMessage {
+            sender,
+            session_id: session.session_id,
+            index,
+            salt: pkt.salt,
+            timestamp: pkt.timestamp,
+            signature: Box::new(*signature),
+            last_seen,
+        };
+
+        let hash = message_hash(&msg, pkt.message);
+
+        match session.public_key.verify(
+            PaddingScheme::new_pkcs1v15_sign::<Sha256>(),
+            &hash,
+            signature,
+        ) {
+            Ok(()) => ChatSignature::Valid(Box::new(msg)),
+            Err(_) => ChatSignature::Invalid(ChatSessionError::InvalidSignature),
+        }
+    }
+}
+
+/// Returns the SHA-256 hash of the data a client signs for a chat message.
+pub fn message_hash(msg: &SignedMessage, message: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new()
+        .chain_update(1_i32.to_be_bytes())
+        .chain_update(msg.sender.as_bytes())
+        .chain_update(msg.session_id.as_bytes())
+        .chain_update(msg.index.to_be_bytes())
+        .chain_update(msg.salt.to_be_bytes())
+        .chain_update((msg.timestamp / 1000).to_be_bytes())
+        .chain_update((message.len() as i32).to_be_bytes())
+        .chain_update(message.as_bytes())
+        .chain_update((msg.last_seen.len() as i32).to_be_bytes());
+
+    for sig in &msg.last_seen {
+        hasher.update(sig);
+    }
+
+    hasher.finalize().into()
+}
+
+/// A chat message from a player to be shown to clients with
+/// [`write_player_chat`].
+#[derive(Clone, Debug)]
+pub struct PlayerChat<'a> {
+    /// The UUID of the player that sent the message.
+    pub sender: Uuid,
+    /// The name shown for the player.
+    pub sender_name: Cow<'a, Text>,
+    pub message: &'a str,
+    /// Unix timestamp in milliseconds.
+    pub timestamp: u64,
+    pub chat_type: ChatType,
+    pub signature: &'a ChatSignature,
+}
+
+/// Writes `chat` to `client` as player chat. `state` belongs to the client
+/// the message is written to.
+///
+/// Unless signatures are stripped, messages with a valid signature are
+/// written with the signature and the signatures of the messages the sender
+/// had seen, so that vanilla clients can verify them. The message is then
+/// expected among the acknowledgements of the receiving client. Other
+/// messages are written unsigned.
+pub fn write_player_chat(
+    mut client: impl WritePacket,
+    state: &mut SignedChatState,
+    settings: &ChatSessionSettings,
+    chat: &PlayerChat,
+) {
+    let signed = match chat.signature {
+        ChatSignature::Valid(msg) if settings.signed_chat.preserves_signatures() => Some(msg),
+        _ => None,
+    };
+
+    client.write_packet(&ChatMessageS2c {
+        sender: chat.sender,
+        index: VarInt(signed.map_or(0, |msg| msg.index)),
+        message_signature: signed.map(|msg| &*msg.signature),
+        message: chat.message,
+        time_stamp: chat.timestamp,
+        salt: signed.map_or(0, |msg| msg.salt),
+        previous_messages: signed.map_or(vec![], |msg| {
+            msg.last_seen
+                .iter()
+                .map(|sig| MessageSignature {
+                    message_id: -1,
+                    signature: Some(sig),
+                })
+                .collect()
+        }),
+        unsigned_content: None,
+        filter_type: MessageFilterType::PassThrough,
+        filter_type_bits: None,
+        chat_type: chat.chat_type.0.into(),
+        network_name: Cow::Borrowed(&chat.sender_name),
+        network_target_name: None,
+    });
+
+    if let Some(msg) = signed {
+        state.last_seen.add_pending(&msg.signature);
+    }
+}
+
+/// Tells joining clients that secure chat is enforced. Clients show a warning
+/// when told that it isn't, so nothing is sent in that case.
+fn send_server_data(
+    mut clients: Query<&mut Client, Added<Client>>,
+    settings: Res<ChatSessionSettings>,
+) {
+    if settings.signed_chat.enforces_secure_chat() {
+        for mut client in &mut clients {
+            client.write_packet(&ServerMetadataS2c {
+                // Only used to update the client's server list entry until the
+                // server is pinged again.
+                motd: Cow::Owned(Text::default()),
+                icon: None,
+                enforce_secure_chat: true,
+            });
+        }
+    }
+}
+
+fn unix_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64)
+}
+
+/// The number of messages a client acknowledges in each chat message.
+const LAST_SEEN_COUNT: usize = 20;
+
+/// Tracks the signed messages sent to a client so that the messages it
+/// acknowledges can be recovered, like vanilla's `LastSeenMessagesValidator`.
+#[derive(Debug)]
+struct LastSeenValidator {
+    /// The oldest [`LAST_SEEN_COUNT`] entries are the window the client's
+    /// acknowledgements refer to. Newer entries have been sent to the client
+    /// but not acknowledged yet.
+    tracked: VecDeque<Option<TrackedMessage>>,
+}
+
+#[derive(Debug)]
+struct TrackedMessage {
+    signature: [u8; 256],
+    /// Whether the client has not acknowledged the message yet.
+    pending: bool,
+}
+
+impl Default for LastSeenValidator {
+    fn default() -> Self {
+        Self {
+            tracked: (0..LAST_SEEN_COUNT).map(|_| None).collect(),
+        }
+    }
+}
+
+impl LastSeenValidator {
+    fn add_pending(&mut self, signature: &[u8; 256]) {
+        if let Some(Some(last)) = self.tracked.back() {
+            if last.pending && last.signature == *signature {
+                return;
+            }
+        }
+
+        self.tracked.push_back(Some(TrackedMessage {
+            signature: *signature,
+            pending: true,
+        }));
+    }
+
+    /// Drops the `offset` oldest messages the client no longer needs to
+    /// refer to.
+    fn apply_offset(&mut self, offset: i32) -> bool {
+        let max = self.tracked.len() - LAST_SEEN_COUNT;
+
+        match usize::try_from(offset) {
+            Ok(offset) if offset <= max => {
+                self.tracked.drain(..offset);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the signatures of the acknowledged messages, oldest first.
+    fn apply_update(&mut self, offset: i32, acknowledged: [u8; 3]) -> Option<Vec<[u8; 256]>> {
+        if !self.apply_offset(offset) {
+            return None;
+        }
+
+        // The bitset only has room for `LAST_SEEN_COUNT` messages.
+        if acknowledged[2] >> (LAST_SEEN_COUNT % 8) != 0 {
+            return None;
+        }
+
+        let mut signatures = vec![];
+
+        for (i, entry) in self.tracked.iter_mut().take(LAST_SEEN_COUNT).enumerate() {
+            if acknowledged[i / 8] >> (i % 8) & 1 == 1 {
+                let msg = entry.as_mut()?;
+                msg.pending = false;
+                signatures.push(msg.signature);
+            } else {
+                if entry.as_ref().is_some_and(|msg| !msg.pending) {
+                    return None;
+                }
+
+                *entry = None;
+            }
+        }
+
+        Some(signatures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sig(n: u8) -> [u8; 256] {
+        [n; 256]
+    }
+
+    #[test]
+    fn last_seen_acknowledgements() {
+        let mut validator = LastSeenValidator::default();
+
+        for n in 1..=3 {
+            validator.add_pending(&sig(n));
+        }
+
+        // Duplicates of the latest message are only tracked once.
+        validator.add_pending(&sig(3));
+
+        // The window must be moved to cover the new messages first.
+        assert!(validator.apply_update(0, [0, 0, 0b1000]).is_none());
+
+        // Acknowledge messages 1 and 3.
+        assert_eq!(
+            validator.apply_update(3, [0, 0, 0b1010]),
+            Some(vec![sig(1), sig(3)])
+        );
+
+        // Acknowledged messages can't be unacknowledged.
+        assert!(validator.apply_update(0, [0, 0, 0b1000]).is_none());
+    }
+
+    #[test]
+    fn last_seen_offset_bounds() {
+        let mut validator = LastSeenValidator::default();
+
+        validator.add_pending(&sig(1));
+
+        assert!(!validator.apply_offset(-1));
+        assert!(!validator.apply_offset(2));
+        assert!(validator.apply_offset(1));
+        assert!(!validator.apply_offset(1));
+
+        // Bits past the window are invalid.
+        assert!(validator.apply_update(0, [0, 0, 0b1_0000]).is_none());
+    }
+}
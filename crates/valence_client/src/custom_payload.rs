@@ -1,40 +1,226 @@
+use std::collections::BTreeSet;
+
+use thiserror::Error;
 use valence_core::protocol::raw::RawBytes;
 use valence_core::protocol::{packet_id, Decode, Encode};
 
 use super::*;
 use crate::event_loop::{EventLoopPreUpdate, PacketEvent};
 
+/// The maximum size of the data in a plugin message sent to a client.
+pub const MAX_PLUGIN_MESSAGE_SIZE_S2C: usize = 1048576;
+
+/// The maximum size of the data in a plugin message sent by a client.
+/// Messages larger than this are ignored.
+pub const MAX_PLUGIN_MESSAGE_SIZE_C2S: usize = 32767;
+
+/// The channel used to declare channels that the sender is listening on.
+const REGISTER_CHANNEL: &str = "minecraft:register";
+/// The channel used to declare channels that the sender is no longer listening
+/// on.
+const UNREGISTER_CHANNEL: &str = "minecraft:unregister";
+
 pub(super) fn build(app: &mut App) {
-    app.add_event::<CustomPayloadEvent>()
-        .add_systems(EventLoopPreUpdate, handle_custom_payload);
+    app.add_event::<PluginMessageEvent>()
+        .init_resource::<PluginChannels>()
+        .add_systems(EventLoopPreUpdate, handle_plugin_message)
+        .add_systems(
+            PostUpdate,
+            send_channel_registrations
+                .after(initial_join)
+                .in_set(UpdateClientsSet),
+        );
 }
 
+/// A plugin message (also known as a custom payload) sent by a client.
 #[derive(Event, Clone, Debug)]
-pub struct CustomPayloadEvent {
+pub struct PluginMessageEvent {
     pub client: Entity,
     pub channel: Ident<String>,
-    pub data: Box<[u8]>,
+    pub data: Bytes,
+}
+
+/// The plugin channels the server is listening on. Clients are told about the
+/// channels in this set with a `minecraft:register` message when they join,
+/// and about changes to this set while they are connected.
+///
+/// Registering a channel is not required to send or receive messages on it,
+/// but some client mods will only send messages on channels the server has
+/// registered.
+#[derive(Resource, Default, Debug)]
+pub struct PluginChannels {
+    channels: BTreeSet<Ident<String>>,
+    registered: Vec<Ident<String>>,
+    unregistered: Vec<Ident<String>>,
+}
+
+impl PluginChannels {
+    /// Adds a channel to the set. Returns `true` if the channel was not
+    /// already present.
+    pub fn register(&mut self, channel: Ident<String>) -> bool {
+        if self.channels.insert(channel.clone()) {
+            self.unregistered.retain(|c| *c != channel);
+            self.registered.push(channel);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Removes a channel from the set. Returns `true` if the channel was
+    /// present.
+    pub fn unregister(&mut self, channel: Ident<&str>) -> bool {
+        if self.channels.remove(channel.as_str()) {
+            self.registered.retain(|c| *c != channel);
+            self.unregistered.push(channel.into());
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn contains(&self, channel: Ident<&str>) -> bool {
+        self.channels.contains(channel.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Ident<&str>> + '_ {
+        self.channels.iter().map(|c| c.as_str_ident())
+    }
+}
+
+/// The plugin channels a client has declared it is listening on with
+/// `minecraft:register` messages.
+#[derive(Component, Clone, Default, Debug)]
+pub struct ClientPluginChannels(BTreeSet<Ident<String>>);
+
+impl ClientPluginChannels {
+    pub fn contains(&self, channel: Ident<&str>) -> bool {
+        self.0.contains(channel.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Ident<&str>> + '_ {
+        self.0.iter().map(|c| c.as_str_ident())
+    }
+}
+
+/// The error returned when the data of a plugin message is larger than
+/// [`MAX_PLUGIN_MESSAGE_SIZE_S2C`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Error)]
+#[error("plugin message of {len} bytes exceeds the maximum of {MAX_PLUGIN_MESSAGE_SIZE_S2C} bytes")]
+pub struct PluginMessageTooLarge {
+    pub len: usize,
 }
 
 impl Client {
-    pub fn send_custom_payload(&mut self, channel: Ident<&str>, data: &[u8]) {
+    /// Sends a plugin message to the client on the given channel. Nothing is
+    /// sent if the data is larger than [`MAX_PLUGIN_MESSAGE_SIZE_S2C`].
+    pub fn send_plugin_message(
+        &mut self,
+        channel: Ident<&str>,
+        data: &[u8],
+    ) -> Result<(), PluginMessageTooLarge> {
+        if data.len() > MAX_PLUGIN_MESSAGE_SIZE_S2C {
+            return Err(PluginMessageTooLarge { len: data.len() });
+        }
+
         self.write_packet(&CustomPayloadS2c {
             channel: channel.into(),
             data: data.into(),
         });
+
+        Ok(())
     }
 }
 
-fn handle_custom_payload(
+fn send_channel_registrations(
+    mut channels: ResMut<PluginChannels>,
+    mut clients: Query<&mut Client>,
+) {
+    let new_clients_only = !channels.is_changed();
+
+    if new_clients_only && channels.channels.is_empty() {
+        return;
+    }
+
+    let channels = channels.bypass_change_detection();
+
+    for mut client in &mut clients {
+        if client.is_added() {
+            write_channel_list(&mut client, REGISTER_CHANNEL, &channels.channels);
+        } else if !new_clients_only {
+            write_channel_list(&mut client, REGISTER_CHANNEL, &channels.registered);
+            write_channel_list(&mut client, UNREGISTER_CHANNEL, &channels.unregistered);
+        }
+    }
+
+    channels.registered.clear();
+    channels.unregistered.clear();
+}
+
+/// Writes a registration message with the null-separated list of channels.
+fn write_channel_list<'a>(
+    client: &mut Client,
+    channel: &str,
+    list: impl IntoIterator<Item = &'a Ident<String>>,
+) {
+    let mut data = vec![];
+
+    for c in list {
+        if !data.is_empty() {
+            data.push(0);
+        }
+        data.extend_from_slice(c.as_str().as_bytes());
+    }
+
+    if !data.is_empty() {
+        client.write_packet(&CustomPayloadS2c {
+            channel: Ident::new(channel).unwrap(),
+            data: data.as_slice().into(),
+        });
+    }
+}
+
+fn handle_plugin_message(
     mut packets: EventReader<PacketEvent>,
-    mut events: EventWriter<CustomPayloadEvent>,
+    mut clients: Query<&mut ClientPluginChannels>,
+    mut events: EventWriter<PluginMessageEvent>,
 ) {
     for packet in packets.iter() {
         if let Some(pkt) = packet.decode::<CustomPayloadC2s>() {
-            events.send(CustomPayloadEvent {
+            if pkt.data.0.len() > MAX_PLUGIN_MESSAGE_SIZE_C2S {
+                debug!(
+                    "ignoring plugin message of {} bytes on channel {}",
+                    pkt.data.0.len(),
+                    pkt.channel
+                );
+                continue;
+            }
+
+            let register = pkt.channel.as_str() == REGISTER_CHANNEL;
+
+            if register || pkt.channel.as_str() == UNREGISTER_CHANNEL {
+                if let Ok(mut client_channels) = clients.get_mut(packet.client) {
+                    let list = pkt
+                        .data
+                        .0
+                        .split(|&b| b == 0)
+                        .filter_map(|c| std::str::from_utf8(c).ok())
+                        .filter_map(|c| Ident::<String>::try_from(c).ok());
+
+                    for c in list {
+                        if register {
+                            client_channels.0.insert(c);
+                        } else {
+                            client_channels.0.remove(c.as_str());
+                        }
+                    }
+                }
+            }
+
+            events.send(PluginMessageEvent {
                 client: packet.client,
                 channel: pkt.channel.into(),
-                data: pkt.data.0.into(),
+                data: packet.data.slice_ref(pkt.data.0),
             })
         }
     }
@@ -49,9 +49,10 @@ use valence_core::protocol::encode::{PacketEncoder, WritePacket};
 use valence_core::protocol::global_pos::GlobalPos;
 use valence_core::protocol::packet::sound::{PlaySoundS2c, Sound, SoundCategory};
 use valence_core::protocol::var_int::VarInt;
-use valence_core::protocol::{Encode, Packet};
+use valence_core::protocol::{Encode, Packet, SharedPacket};
 use valence_core::text::Text;
 use valence_core::uuid::UniqueId;
+use valence_core::Server;
 use valence_entity::packet::{
     EntitiesDestroyS2c, EntitySetHeadYawS2c, EntitySpawnS2c, EntityStatusS2c,
     EntityTrackerUpdateS2c, EntityVelocityUpdateS2c, ExperienceOrbSpawnS2c,
@@ -71,6 +72,8 @@ use valence_registry::tags::TagsRegistry;
 use valence_registry::RegistrySet;
 
 pub mod action;
+pub mod backpressure;
+pub mod chat_session;
 pub mod command;
 pub mod custom_payload;
 pub mod event_loop;
@@ -85,6 +88,7 @@ pub mod op_level;
 pub mod packet;
 pub mod resource_pack;
 pub mod settings;
+pub mod stats;
 pub mod status;
 pub mod teleport;
 pub mod title;
@@ -143,6 +147,7 @@ impl Plugin for ClientPlugin {
         );
 
         event_loop::build(app);
+        backpressure::build(app);
         movement::build(app);
         command::build(app);
         keepalive::build(app);
@@ -152,6 +157,7 @@ impl Plugin for ClientPlugin {
         teleport::build(app);
         weather::build(app);
         message::build(app);
+        chat_session::build(app);
         custom_payload::build(app);
         hand_swing::build(app);
         interact_block::build(app);
@@ -159,6 +165,7 @@ impl Plugin for ClientPlugin {
         op_level::build(app);
         resource_pack::build(app);
         status::build(app);
+        stats::build(app);
     }
 }
 
@@ -190,15 +197,25 @@ pub struct ClientBundle {
     pub is_flat: IsFlat,
     pub teleport_state: teleport::TeleportState,
     pub packet_byte_range: PacketByteRange,
+    pub plugin_channels: custom_payload::ClientPluginChannels,
+    pub pending_chunks: backpressure::PendingChunks,
+    pub connection_stats: stats::ConnectionStats,
+    pub chat_session: chat_session::ChatSession,
+    pub signed_chat_state: chat_session::SignedChatState,
     pub player: PlayerEntityBundle,
 }
 
 impl ClientBundle {
     pub fn new(args: ClientBundleArgs) -> Self {
+        let mut enc = args.enc;
+        // Large packets are compressed in parallel when clients are flushed.
+        enc.set_deferred_compression(true);
+
         Self {
             client: Client {
                 conn: args.conn,
-                enc: args.enc,
+                enc,
+                sent: stats::TrafficStats::default(),
             },
             settings: settings::ClientSettings::default(),
             entity_remove_buf: EntityRemoveBuf(vec![]),
@@ -223,6 +240,11 @@ impl ClientBundle {
             reduced_debug_info: ReducedDebugInfo::default(),
             is_debug: IsDebug::default(),
             packet_byte_range: PacketByteRange::default(),
+            plugin_channels: custom_payload::ClientPluginChannels::default(),
+            pending_chunks: backpressure::PendingChunks::default(),
+            connection_stats: stats::ConnectionStats::default(),
+            chat_session: chat_session::ChatSession::default(),
+            signed_chat_state: chat_session::SignedChatState::default(),
             player: PlayerEntityBundle {
                 uuid: UniqueId(args.uuid),
                 ..Default::default()
@@ -252,6 +274,25 @@ pub struct ClientBundleArgs {
 pub struct Client {
     conn: Box<dyn ClientConnection>,
     enc: PacketEncoder,
+    sent: stats::TrafficStats,
+}
+
+/// Writes `packet` to each of `clients`, encoding and compressing it only once.
+/// Every client receives the same data, so only [`SharedPacket`]s can be
+/// broadcast this way.
+pub fn broadcast<P: SharedPacket>(
+    server: &Server,
+    clients: impl IntoIterator<Item = impl WritePacket>,
+    packet: &P,
+) {
+    match server.encode_shared(packet) {
+        Ok(encoded) => {
+            for mut client in clients {
+                client.write_encoded(&encoded);
+            }
+        }
+        Err(e) => warn!("failed to encode shared packet '{}': {e:#}", P::NAME),
+    }
 }
 
 /// Represents the bidirectional packet channel between the server and a client
@@ -266,6 +307,15 @@ pub trait ClientConnection: Send + Sync + 'static {
     /// The number of pending packets waiting to be received via
     /// [`Self::try_recv`].
     fn len(&self) -> usize;
+    /// The number of bytes passed to [`Self::try_send`] that have not been
+    /// sent to the client yet. This grows when the client can't keep up with
+    /// the data sent to it.
+    fn buffered_bytes(&self) -> usize;
+    /// Counters for the packets received from the client so far. Connections
+    /// that don't keep track of this return zeros.
+    fn received_stats(&self) -> stats::TrafficStats {
+        stats::TrafficStats::default()
+    }
 
     fn is_empty(&self) -> bool {
         self.len() == 0
@@ -313,6 +363,34 @@ impl Client {
         self.conn.as_mut()
     }
 
+    /// The number of outgoing bytes waiting to be sent to the client. This
+    /// includes packets written this tick and data flushed to the
+    /// connection that has not been sent yet.
+    ///
+    /// See [`BackpressureSettings`] for limiting this.
+    ///
+    /// [`BackpressureSettings`]: backpressure::BackpressureSettings
+    pub fn buffered_bytes(&self) -> usize {
+        self.enc.len() + self.conn.buffered_bytes()
+    }
+
+    /// The packets flushed to the connection so far. Packets written this tick
+    /// are counted once they are flushed.
+    ///
+    /// The [`ConnectionStats`] component has a copy of this that is updated
+    /// every tick.
+    ///
+    /// [`ConnectionStats`]: stats::ConnectionStats
+    pub fn sent_stats(&self) -> stats::TrafficStats {
+        self.sent
+    }
+
+    /// The packets received from the client so far. See
+    /// [`ClientConnection::received_stats`].
+    pub fn received_stats(&self) -> stats::TrafficStats {
+        self.conn.received_stats()
+    }
+
     /// Flushes the packet queue to the underlying connection.
     ///
     /// This is called automatically at the end of the tick and when the client
@@ -321,6 +399,9 @@ impl Client {
     ///
     /// Returns an error if flushing was unsuccessful.
     pub fn flush_packets(&mut self) -> anyhow::Result<()> {
+        self.enc.compress_deferred();
+        stats::record_sent_frames(&self.enc, &mut self.sent);
+
         let bytes = self.enc.take();
         if !bytes.is_empty() {
             self.conn.try_send(bytes)
@@ -688,9 +769,12 @@ fn initial_join(
 ) {
     for mut q in &mut clients {
         let Ok(instance) = instances.get(q.loc.0) else {
-            warn!("client {:?} joined nonexistent instance {:?}", q.entity, q.loc.0);
+            warn!(
+                "client {:?} joined nonexistent instance {:?}",
+                q.entity, q.loc.0
+            );
             commands.entity(q.entity).remove::<Client>();
-            continue
+            continue;
         };
 
         let dimension_names: Vec<Ident<Cow<str>>> = codec
@@ -766,7 +850,7 @@ fn respawn(
 
         let Ok(instance) = instances.get(loc.0) else {
             warn!("Client respawned in nonexistent instance.");
-            continue
+            continue;
         };
 
         let dimension_name = instance.dimension_type_name();
@@ -823,51 +907,54 @@ struct EntityInitQuery {
 
 impl EntityInitQueryItem<'_> {
     /// Writes the appropriate packets to initialize an entity. This will spawn
-    /// the entity and initialize tracked data.
+    /// the entity and initialize tracked data. The packets are bundled so the
+    /// client never sees the entity without its tracked data.
     fn write_init_packets(&self, pos: DVec3, mut writer: impl WritePacket) {
-        match *self.kind {
-            EntityKind::MARKER => {}
-            EntityKind::EXPERIENCE_ORB => {
-                writer.write_packet(&ExperienceOrbSpawnS2c {
-                    entity_id: self.entity_id.get().into(),
-                    position: pos,
-                    count: self.object_data.0 as i16,
-                });
-            }
-            EntityKind::PLAYER => {
-                writer.write_packet(&PlayerSpawnS2c {
+        writer.bundled(|writer| {
+            match *self.kind {
+                EntityKind::MARKER => {}
+                EntityKind::EXPERIENCE_ORB => {
+                    writer.write_packet(&ExperienceOrbSpawnS2c {
+                        entity_id: self.entity_id.get().into(),
+                        position: pos,
+                        count: self.object_data.0 as i16,
+                    });
+                }
+                EntityKind::PLAYER => {
+                    writer.write_packet(&PlayerSpawnS2c {
+                        entity_id: self.entity_id.get().into(),
+                        player_uuid: self.uuid.0,
+                        position: pos,
+                        yaw: ByteAngle::from_degrees(self.look.yaw),
+                        pitch: ByteAngle::from_degrees(self.look.pitch),
+                    });
+
+                    // Player spawn packet doesn't include head yaw for some reason.
+                    writer.write_packet(&EntitySetHeadYawS2c {
+                        entity_id: self.entity_id.get().into(),
+                        head_yaw: ByteAngle::from_degrees(self.head_yaw.0),
+                    });
+                }
+                _ => writer.write_packet(&EntitySpawnS2c {
                     entity_id: self.entity_id.get().into(),
-                    player_uuid: self.uuid.0,
+                    object_uuid: self.uuid.0,
+                    kind: self.kind.get().into(),
                     position: pos,
-                    yaw: ByteAngle::from_degrees(self.look.yaw),
                     pitch: ByteAngle::from_degrees(self.look.pitch),
-                });
+                    yaw: ByteAngle::from_degrees(self.look.yaw),
+                    head_yaw: ByteAngle::from_degrees(self.head_yaw.0),
+                    data: self.object_data.0.into(),
+                    velocity: self.velocity.to_packet_units(),
+                }),
+            }
 
-                // Player spawn packet doesn't include head yaw for some reason.
-                writer.write_packet(&EntitySetHeadYawS2c {
+            if let Some(init_data) = self.tracked_data.init_data() {
+                writer.write_packet(&EntityTrackerUpdateS2c {
                     entity_id: self.entity_id.get().into(),
-                    head_yaw: ByteAngle::from_degrees(self.head_yaw.0),
+                    metadata: init_data.into(),
                 });
             }
-            _ => writer.write_packet(&EntitySpawnS2c {
-                entity_id: self.entity_id.get().into(),
-                object_uuid: self.uuid.0,
-                kind: self.kind.get().into(),
-                position: pos,
-                pitch: ByteAngle::from_degrees(self.look.pitch),
-                yaw: ByteAngle::from_degrees(self.look.yaw),
-                head_yaw: ByteAngle::from_degrees(self.head_yaw.0),
-                data: self.object_data.0.into(),
-                velocity: self.velocity.to_packet_units(),
-            }),
-        }
-
-        if let Some(init_data) = self.tracked_data.init_data() {
-            writer.write_packet(&EntityTrackerUpdateS2c {
-                entity_id: self.entity_id.get().into(),
-                metadata: init_data.into(),
-            });
-        }
+        });
     }
 }
 
@@ -882,6 +969,7 @@ fn read_data_in_old_view(
         &OldPosition,
         &OldViewDistance,
         &PacketByteRange,
+        &backpressure::PendingChunks,
     )>,
     instances: Query<&Instance>,
     entities: Query<(EntityInitQuery, &OldPosition)>,
@@ -898,6 +986,7 @@ fn read_data_in_old_view(
             old_pos,
             old_view_dist,
             byte_range,
+            pending,
         )| {
             let Ok(inst) = instances.get(old_loc.get()) else {
                 return;
@@ -912,15 +1001,26 @@ fn read_data_in_old_view(
 
             let view = ChunkView::new(old_chunk_pos, old_view_dist.0);
 
+            // Chunks that have not been sent yet are treated as if they were outside the
+            // view.
+            let is_loaded = |p: ChunkPos| view.contains(p) && !pending.contains(p);
+
             // Iterate over all visible chunks from the previous tick.
             for pos in view.iter() {
                 if let Some(chunk) = inst.chunk(pos) {
                     // Mark this chunk as being in view of a client.
                     chunk.set_viewed();
 
+                    if pending.contains(pos) {
+                        // The client doesn't have this chunk yet. Its current state is sent
+                        // when it stops being pending.
+                        continue;
+                    }
+
                     // Send entity spawn packets for entities entering the client's view.
                     for &(entity, src_pos) in chunk.incoming_entities() {
-                        if src_pos.map_or(true, |p| !view.contains(p)) {
+                        // The client's own entity can move in or out of a pending chunk.
+                        if entity != self_entity && src_pos.is_none_or(|p| !is_loaded(p)) {
                             // The incoming entity originated from outside the view distance, so it
                             // must be spawned.
                             if let Ok((entity, old_pos)) = entities.get(entity) {
@@ -935,7 +1035,7 @@ fn read_data_in_old_view(
 
                     // Send entity despawn packets for entities exiting the client's view.
                     for &(entity, dest_pos) in chunk.outgoing_entities() {
-                        if dest_pos.map_or(true, |p| !view.contains(p)) {
+                        if entity != self_entity && dest_pos.is_none_or(|p| !is_loaded(p)) {
                             // The outgoing entity moved outside the view distance, so it must be
                             // despawned.
                             if let Ok(id) = entity_ids.get(entity) {
@@ -1008,12 +1108,14 @@ fn update_view(
             &OldPosition,
             &ViewDistance,
             &OldViewDistance,
+            &mut backpressure::PendingChunks,
         ),
         Or<(Changed<Location>, Changed<Position>, Changed<ViewDistance>)>,
     >,
     instances: Query<&Instance>,
     entities: Query<(EntityInitQuery, &Position)>,
     entity_ids: Query<&EntityId>,
+    backpressure: Res<backpressure::BackpressureSettings>,
 ) {
     clients.par_iter_mut().for_each_mut(
         |(
@@ -1026,6 +1128,7 @@ fn update_view(
             old_pos,
             view_dist,
             old_view_dist,
+            mut pending,
         )| {
             let view = ChunkView::new(ChunkPos::from_dvec3(pos.0), view_dist.0);
             let old_view = ChunkView::new(ChunkPos::from_dvec3(old_pos.get()), old_view_dist.0);
@@ -1047,6 +1150,11 @@ fn update_view(
 
                     // Unload all chunks and entities in the old view.
                     for pos in old_view.iter() {
+                        if pending.contains(pos) {
+                            // The chunk was never sent.
+                            continue;
+                        }
+
                         if let Some(chunk) = old_inst.chunk(pos) {
                             // Unload the chunk if its state is not "removed", since we already
                             // unloaded "removed" chunks earlier.
@@ -1070,6 +1178,8 @@ fn update_view(
                     }
                 }
 
+                pending.clear();
+
                 if let Ok(inst) = instances.get(loc.0) {
                     // Load all chunks and entities in new view.
                     for pos in view.iter() {
@@ -1077,6 +1187,11 @@ fn update_view(
                             // Mark this chunk as being in view of a client.
                             chunk.set_viewed();
 
+                            if backpressure.should_defer_chunks(&client, &pending) {
+                                pending.insert(pos);
+                                continue;
+                            }
+
                             // Load the chunk if it's not already removed.
                             chunk.write_init_packets(&mut *client, pos, inst.info());
 
@@ -1102,6 +1217,11 @@ fn update_view(
                     // the new view. We don't need to do any work where the old and new view
                     // overlap.
                     for pos in old_view.diff(view) {
+                        if pending.remove(pos) {
+                            // The chunk was never sent.
+                            continue;
+                        }
+
                         if let Some(chunk) = inst.chunk(pos) {
                             // Unload the chunk if its state is not "removed", since we already
                             // unloaded "removed" chunks earlier.
@@ -1133,6 +1253,11 @@ fn update_view(
                                 // Mark this chunk as being in view of a client.
                                 chunk.set_viewed();
 
+                                if backpressure.should_defer_chunks(&client, &pending) {
+                                    pending.insert(pos);
+                                    continue;
+                                }
+
                                 // Load the chunk.
                                 chunk.write_init_packets(&mut *client, pos, inst.info());
 
@@ -1208,8 +1333,36 @@ fn update_respawn_position(
 
 fn flush_packets(
     mut clients: Query<(Entity, &mut Client), Changed<Client>>,
+    server: Res<Server>,
+    mut compression_pool: Local<Option<rayon::ThreadPool>>,
     mut commands: Commands,
 ) {
+    use rayon::prelude::*;
+
+    // Compress the large packets of all clients in parallel first. The packets
+    // stay in order, since each client's packets are compressed in place.
+    let mut encoders: Vec<_> = clients
+        .iter_mut()
+        .map(|(_, client)| &mut client.into_inner().enc)
+        .filter(|enc| enc.has_deferred())
+        .collect();
+
+    if !encoders.is_empty() {
+        let pool = compression_pool.get_or_insert_with(|| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(server.compression_threads().map_or(0, |n| n.get()))
+                .thread_name(|i| format!("valence-compression-{i}"))
+                .build()
+                .expect("failed to build compression thread pool")
+        });
+
+        pool.install(|| {
+            encoders
+                .par_iter_mut()
+                .for_each(|enc| enc.compress_deferred())
+        });
+    }
+
     for (entity, mut client) in &mut clients {
         if let Err(e) = client.flush_packets() {
             warn!("Failed to flush packet queue for client {entity:?}: {e:#}.");
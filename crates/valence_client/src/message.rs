@@ -1,12 +1,27 @@
 // TODO: delete this module in favor of valence_chat.
 
+use std::borrow::Cow;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
+use tracing::warn;
+use uuid::Uuid;
 use valence_core::protocol::encode::WritePacket;
-use valence_core::protocol::packet::chat::{ChatMessageC2s, GameMessageS2c};
+use valence_core::protocol::packet::chat::{
+    ChatMessageC2s, ChatMessageS2c, GameMessageS2c, MessageAcknowledgmentC2s, MessageFilterType,
+    MessageSignature, PlayerSessionC2s, RemoveMessageS2c,
+};
+use valence_core::protocol::var_int::VarInt;
 use valence_core::text::Text;
+use valence_core::translation_key;
+use valence_core::uuid::UniqueId;
 
+use crate::chat_session::{
+    ChatSession, ChatSessionError, ChatSessionSettings, ChatSignature, SignedChatState,
+};
 use crate::event_loop::{EventLoopPreUpdate, PacketEvent};
+use crate::DisconnectClient;
 
 pub(super) fn build(app: &mut App) {
     app.add_event::<ChatMessageEvent>()
@@ -18,6 +33,24 @@ pub trait SendMessage {
     fn send_chat_message(&mut self, msg: impl Into<Text>);
     /// Displays a message in the player's action bar (text above the hotbar).
     fn send_action_bar_message(&mut self, msg: impl Into<Text>);
+    /// Sends an unsigned chat message from the player with UUID `sender`,
+    /// decorated according to `chat_type` with `sender_name`.
+    ///
+    /// Unlike system messages, clients can hide player chat from players they
+    /// have blocked. The sender must be in the client's player list. To relay
+    /// signed messages, see
+    /// [`write_player_chat`](crate::chat_session::write_player_chat).
+    fn send_player_chat(
+        &mut self,
+        sender: Uuid,
+        chat_type: ChatType,
+        sender_name: impl Into<Text>,
+        message: &str,
+    );
+    /// Removes the player chat message with the given signature from the
+    /// client's chat. Only signed messages can be removed. See
+    /// [`SignedMessage::signature`](crate::chat_session::SignedMessage::signature).
+    fn delete_chat_message(&mut self, signature: &[u8; 256]);
 }
 
 impl<T: WritePacket> SendMessage for T {
@@ -34,6 +67,66 @@ impl<T: WritePacket> SendMessage for T {
             overlay: true,
         });
     }
+
+    fn send_player_chat(
+        &mut self,
+        sender: Uuid,
+        chat_type: ChatType,
+        sender_name: impl Into<Text>,
+        message: &str,
+    ) {
+        let time_stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+
+        self.write_packet(&ChatMessageS2c {
+            sender,
+            // Unsigned messages are not part of a message chain.
+            index: VarInt(0),
+            message_signature: None,
+            message,
+            time_stamp,
+            salt: 0,
+            previous_messages: vec![],
+            unsigned_content: None,
+            filter_type: MessageFilterType::PassThrough,
+            filter_type_bits: None,
+            chat_type: chat_type.0.into(),
+            network_name: Cow::Owned(sender_name.into()),
+            network_target_name: None,
+        });
+    }
+
+    fn delete_chat_message(&mut self, signature: &[u8; 256]) {
+        self.write_packet(&RemoveMessageS2c {
+            signature: MessageSignature {
+                message_id: -1,
+                signature: Some(signature),
+            },
+        });
+    }
+}
+
+/// An entry of the `minecraft:chat_type` registry, which determines how player
+/// chat is decorated with the name of the sender.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ChatType(pub i32);
+
+impl ChatType {
+    /// `<sender> message`
+    pub const CHAT: Self = Self(0);
+    /// `* sender message`
+    pub const EMOTE_COMMAND: Self = Self(1);
+    /// `sender whispers to you: message`
+    pub const MSG_COMMAND_INCOMING: Self = Self(2);
+    /// `You whisper to target: message`
+    pub const MSG_COMMAND_OUTGOING: Self = Self(3);
+    /// `[sender] message`
+    pub const SAY_COMMAND: Self = Self(4);
+    /// `target <sender> message`
+    pub const TEAM_MSG_COMMAND_INCOMING: Self = Self(5);
+    /// `target -> sender message`
+    pub const TEAM_MSG_COMMAND_OUTGOING: Self = Self(6);
 }
 
 #[derive(Event, Clone, Debug)]
@@ -41,19 +134,95 @@ pub struct ChatMessageEvent {
     pub client: Entity,
     pub message: Box<str>,
     pub timestamp: u64,
+    pub salt: u64,
+    /// Whether the client signed the message, and if the signature is valid.
+    /// The signature of a valid message can be used to delete it later with
+    /// [`SendMessage::delete_chat_message`].
+    pub signature: ChatSignature,
 }
 
+/// Handles chat messages along with the chat session packets, which must be
+/// processed in the order they were received.
 pub fn handle_chat_message(
     mut packets: EventReader<PacketEvent>,
+    mut clients: Query<(&UniqueId, &mut ChatSession, &mut SignedChatState)>,
+    settings: Res<ChatSessionSettings>,
     mut events: EventWriter<ChatMessageEvent>,
+    mut commands: Commands,
 ) {
+    let enforce = settings.signed_chat.enforces_secure_chat();
+
     for packet in packets.iter() {
-        if let Some(pkt) = packet.decode::<ChatMessageC2s>() {
+        let Ok((uuid, mut session, mut state)) = clients.get_mut(packet.client) else {
+            continue;
+        };
+
+        if let Some(pkt) = packet.decode::<PlayerSessionC2s>() {
+            state.reset_chain();
+
+            if let Err(e) = session.update_session(uuid.0, &pkt, &settings) {
+                warn!("rejected chat session of client {:?}: {e}", packet.client);
+
+                if enforce {
+                    let reason = match e {
+                        ChatSessionError::ExpiredPublicKey => {
+                            translation_key::MULTIPLAYER_DISCONNECT_EXPIRED_PUBLIC_KEY
+                        }
+                        _ => translation_key::MULTIPLAYER_DISCONNECT_INVALID_PUBLIC_KEY_SIGNATURE,
+                    };
+
+                    disconnect(&mut commands, packet.client, reason);
+                }
+            }
+        } else if let Some(pkt) = packet.decode::<MessageAcknowledgmentC2s>() {
+            if let Err(e) = state.acknowledge(pkt.message_count.0) {
+                warn!("client {:?}: {e}", packet.client);
+
+                if enforce {
+                    disconnect(
+                        &mut commands,
+                        packet.client,
+                        translation_key::MULTIPLAYER_DISCONNECT_CHAT_VALIDATION_FAILED,
+                    );
+                }
+            }
+        } else if let Some(pkt) = packet.decode::<ChatMessageC2s>() {
+            let signature = state.verify_message(uuid.0, &session, &pkt);
+
+            if enforce {
+                let reason = match &signature {
+                    ChatSignature::Valid(_) => None,
+                    ChatSignature::Unsigned => {
+                        Some(translation_key::MULTIPLAYER_DISCONNECT_UNSIGNED_CHAT)
+                    }
+                    ChatSignature::Invalid(ChatSessionError::OutOfOrder) => {
+                        Some(translation_key::MULTIPLAYER_DISCONNECT_OUT_OF_ORDER_CHAT)
+                    }
+                    ChatSignature::Invalid(_) => {
+                        Some(translation_key::MULTIPLAYER_DISCONNECT_CHAT_VALIDATION_FAILED)
+                    }
+                };
+
+                if let Some(reason) = reason {
+                    disconnect(&mut commands, packet.client, reason);
+                    continue;
+                }
+            }
+
             events.send(ChatMessageEvent {
                 client: packet.client,
                 message: pkt.message.into(),
                 timestamp: pkt.timestamp,
+                salt: pkt.salt,
+                signature,
             });
         }
     }
 }
+
+fn disconnect(commands: &mut Commands, client: Entity, translation_key: &'static str) {
+    commands.add(DisconnectClient {
+        client,
+        reason: Text::translate(translation_key, []),
+    });
+}
@@ -12,6 +12,7 @@ use valence_core::game_mode::GameMode;
 use valence_core::hand::Hand;
 use valence_core::ident::Ident;
 use valence_core::protocol::byte_angle::ByteAngle;
+pub use valence_core::protocol::encode::BundleSplitterS2c;
 use valence_core::protocol::global_pos::GlobalPos;
 use valence_core::protocol::var_int::VarInt;
 use valence_core::protocol::var_long::VarLong;
@@ -19,10 +20,6 @@ use valence_core::protocol::{packet_id, Decode, Encode, Packet};
 use valence_core::text::Text;
 use valence_nbt::Compound;
 
-#[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
-#[packet(id = packet_id::BUNDLE_SPLITTER)]
-pub struct BundleSplitterS2c;
-
 #[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::BOAT_PADDLE_STATE_C2S)]
 pub struct BoatPaddleStateC2s {
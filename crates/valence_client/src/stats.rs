@@ -0,0 +1,107 @@
+//! Counters for the data sent to and received from clients.
+//!
+//! Every client has a [`ConnectionStats`] component, and the totals for all
+//! clients are kept in the [`NetworkStats`] resource. Both are updated once
+//! per tick after packets are flushed.
+
+use valence_core::protocol::encode::FrameSize;
+
+use super::*;
+
+pub(super) fn build(app: &mut App) {
+    app.init_resource::<NetworkStats>()
+        .add_systems(PostUpdate, update_connection_stats.after(FlushPacketsSet));
+}
+
+/// Counters for the packets sent in one direction of a connection.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct TrafficStats {
+    /// The number of packets.
+    pub packets: u64,
+    /// The number of bytes on the wire, after compression.
+    pub bytes: u64,
+    /// The number of bytes in the packet IDs and bodies before compression.
+    pub uncompressed_bytes: u64,
+    /// The size of the largest packet before compression.
+    pub largest_packet: u64,
+}
+
+impl TrafficStats {
+    /// Counts one packet with the given sizes.
+    pub fn record(&mut self, frame_len: usize, data_len: usize) {
+        self.packets += 1;
+        self.bytes += frame_len as u64;
+        self.uncompressed_bytes += data_len as u64;
+        self.largest_packet = self.largest_packet.max(data_len as u64);
+    }
+
+    /// The ratio of uncompressed bytes to bytes on the wire, or `None` if
+    /// nothing has been counted yet. Higher is better. Values below one are
+    /// possible because of packet framing.
+    pub fn compression_ratio(&self) -> Option<f64> {
+        (self.bytes > 0).then(|| self.uncompressed_bytes as f64 / self.bytes as f64)
+    }
+
+    /// Adds the difference between `old` and `new` to `self`.
+    fn add_change(&mut self, old: &Self, new: &Self) {
+        self.packets += new.packets.saturating_sub(old.packets);
+        self.bytes += new.bytes.saturating_sub(old.bytes);
+        self.uncompressed_bytes += new
+            .uncompressed_bytes
+            .saturating_sub(old.uncompressed_bytes);
+        self.largest_packet = self.largest_packet.max(new.largest_packet);
+    }
+}
+
+/// The data sent to and received from a client since it was spawned.
+///
+/// Sent packets are counted as they are encoded by the server. If the network
+/// layer compresses them again with different settings for this connection,
+/// the bytes on the wire will differ. Received packets are counted by the
+/// [`ClientConnection`], including packets that were never passed to the
+/// server because they were dropped or the client disconnected.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct ConnectionStats {
+    pub sent: TrafficStats,
+    pub received: TrafficStats,
+}
+
+/// The data sent to and received from all clients since the server started,
+/// including clients that have disconnected. Data from the last tick of a
+/// disconnecting client may not be counted.
+#[derive(Resource, Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct NetworkStats {
+    pub sent: TrafficStats,
+    pub received: TrafficStats,
+}
+
+/// Counts the frames in `enc` towards `stats`. Must be called before the
+/// frames are taken.
+pub(crate) fn record_sent_frames(enc: &PacketEncoder, stats: &mut TrafficStats) {
+    for FrameSize {
+        frame_len,
+        data_len,
+    } in enc.frame_sizes()
+    {
+        stats.record(frame_len, data_len);
+    }
+}
+
+fn update_connection_stats(
+    mut clients: Query<(&Client, &mut ConnectionStats)>,
+    mut totals: ResMut<NetworkStats>,
+) {
+    for (client, mut stats) in &mut clients {
+        let new = ConnectionStats {
+            sent: client.sent_stats(),
+            received: client.received_stats(),
+        };
+
+        if *stats != new {
+            totals.sent.add_change(&stats.sent, &new.sent);
+            totals.received.add_change(&stats.received, &new.received);
+
+            *stats = new;
+        }
+    }
+}
@@ -1,12 +1,21 @@
 use std::io::Write;
 
 use anyhow::bail;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use crate::direction::Direction;
 use crate::protocol::{Decode, Encode};
 
 /// Represents an absolute block position in world space.
+///
+/// With the `serde` feature, block positions are serialized as `[x, y, z]`.
 #[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(from = "[i32; 3]", into = "[i32; 3]")
+)]
 pub struct BlockPos {
     pub x: i32,
     pub y: i32,
@@ -139,4 +148,15 @@ mod tests {
             }
         }
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn block_pos_serde() {
+        let pos = BlockPos::new(1, -64, 300);
+        let json = serde_json::to_string(&pos).unwrap();
+
+        assert_eq!(json, "[1,-64,300]");
+        assert_eq!(serde_json::from_str::<BlockPos>(&json).unwrap(), pos);
+        assert!(serde_json::from_str::<BlockPos>("[1, 2]").is_err());
+    }
 }
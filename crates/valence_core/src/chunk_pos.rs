@@ -1,10 +1,19 @@
 use glam::DVec3;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use crate::block_pos::BlockPos;
 use crate::protocol::{Decode, Encode};
 
 /// The X and Z position of a chunk.
+///
+/// With the `serde` feature, chunk positions are serialized as `[x, z]`.
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash, Debug, Encode, Decode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(from = "[i32; 2]", into = "[i32; 2]")
+)]
 pub struct ChunkPos {
     /// The X position of the chunk.
     pub x: i32,
@@ -151,4 +160,14 @@ mod tests {
         assert_eq!(ChunkPos::from(<(i32, i32)>::from(p)), p);
         assert_eq!(ChunkPos::from(<[i32; 2]>::from(p)), p);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn chunk_pos_serde() {
+        let pos = ChunkPos::new(-3, 12);
+        let json = serde_json::to_string(&pos).unwrap();
+
+        assert_eq!(json, "[-3,12]");
+        assert_eq!(serde_json::from_str::<ChunkPos>(&json).unwrap(), pos);
+    }
 }
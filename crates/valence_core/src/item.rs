@@ -1,10 +1,19 @@
 use std::io::Write;
 
 use anyhow::{ensure, Context};
-use valence_nbt::Compound;
+use bitfield_struct::bitfield;
+use rand::Rng;
+#[cfg(feature = "serde")]
+use serde::de::Error as _;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+use valence_nbt::{compound, Compound, List, Value};
 
+use crate::ident::Ident;
 use crate::protocol::var_int::VarInt;
 use crate::protocol::{Decode, Encode};
+use crate::text::Text;
 
 include!(concat!(env!("OUT_DIR"), "/item.rs"));
 
@@ -56,6 +65,406 @@ impl ItemStack {
     pub fn set_count(&mut self, count: u8) {
         self.count = count.clamp(Self::STACK_MIN, Self::STACK_MAX);
     }
+
+    /// Reads an item stack in the format vanilla stores items in, such as
+    /// `{id: "minecraft:stone", Count: 3b, tag: {...}}`.
+    ///
+    /// IDs are read case-insensitively and the `minecraft` namespace may be
+    /// left out. The count may be any integer type and is clamped like in
+    /// [`ItemStack::new`], or is 1 if it is missing. The `tag` compound is
+    /// kept as is.
+    pub fn from_nbt(nbt: &Compound) -> Result<Self, ItemNbtError> {
+        let id = match nbt.get("id") {
+            Some(Value::String(id)) => id,
+            Some(_) => return Err(ItemNbtError::BadFieldType("id")),
+            None => return Err(ItemNbtError::MissingId),
+        };
+
+        let ident =
+            Ident::new(id.to_ascii_lowercase()).map_err(|_| ItemNbtError::InvalidId(id.clone()))?;
+
+        let item = if ident.namespace() == "minecraft" {
+            ItemKind::from_str(ident.path())
+        } else {
+            None
+        };
+
+        let Some(item) = item else {
+            return Err(ItemNbtError::UnknownId(ident.into()));
+        };
+
+        let count = match nbt.get("Count") {
+            Some(&Value::Byte(count)) => count.into(),
+            Some(&Value::Short(count)) => count.into(),
+            Some(&Value::Int(count)) => count.into(),
+            Some(&Value::Long(count)) => count,
+            None => 1,
+            Some(_) => return Err(ItemNbtError::BadFieldType("Count")),
+        };
+
+        let tag = match nbt.get("tag") {
+            Some(Value::Compound(tag)) => Some(tag.clone()),
+            None => None,
+            Some(_) => return Err(ItemNbtError::BadFieldType("tag")),
+        };
+
+        Ok(Self {
+            item,
+            count: count.clamp(Self::STACK_MIN.into(), Self::STACK_MAX.into()) as u8,
+            nbt: tag,
+        })
+    }
+
+    /// Converts this item stack into the format read by
+    /// [`ItemStack::from_nbt`].
+    pub fn to_nbt(&self) -> Compound {
+        let mut nbt = compound! {
+            "id" => format!("minecraft:{}", self.item.to_str()),
+            "Count" => self.count as i8,
+        };
+
+        if let Some(tag) = &self.nbt {
+            nbt.insert("tag", tag.clone());
+        }
+
+        nbt
+    }
+
+    /// Sets the custom name of this item, which is stored in
+    /// `display.Name`.
+    #[must_use]
+    pub fn with_name(mut self, name: impl Into<Text>) -> Self {
+        self.display_mut().insert("Name", name.into());
+        self
+    }
+
+    /// Sets the lines of lore shown under the item's name, which are stored in
+    /// `display.Lore`. An empty lore removes the tag.
+    #[must_use]
+    pub fn with_lore(mut self, lore: impl IntoIterator<Item = impl Into<Text>>) -> Self {
+        let lore: Vec<String> = lore
+            .into_iter()
+            .map(|line| text_to_json(line.into()))
+            .collect();
+
+        if lore.is_empty() {
+            self.display_mut().remove("Lore");
+        } else {
+            self.display_mut().insert("Lore", List::String(lore));
+        }
+
+        self
+    }
+
+    /// Adds an enchantment to the item's `Enchantments`, replacing the level of
+    /// the enchantment if the item already has it.
+    #[must_use]
+    pub fn with_enchantment(mut self, ench: impl Into<Ident<String>>, level: i16) -> Self {
+        let ench = ench.into();
+        let tag = self.tag_mut();
+
+        let list = tag
+            .entry("Enchantments")
+            .or_insert_with(|| Value::List(List::Compound(vec![])));
+
+        // Empty lists read from binary NBT have no element type and are replaced too.
+        if !matches!(list, Value::List(List::Compound(_))) {
+            *list = List::Compound(vec![]).into();
+        }
+
+        let Value::List(List::Compound(enchantments)) = list else {
+            unreachable!()
+        };
+
+        let existing = enchantments.iter_mut().find(|e| {
+            matches!(e.get("id"), Some(Value::String(id)) if parse_ident(id).as_ref() == Some(&ench))
+        });
+
+        match existing {
+            Some(e) => {
+                e.insert("lvl", level);
+            }
+            None => enchantments.push(compound! {
+                "id" => ench,
+                "lvl" => level,
+            }),
+        }
+
+        self
+    }
+
+    /// Sets whether the item loses durability, which is stored in
+    /// `Unbreakable`.
+    #[must_use]
+    pub fn with_unbreakable(mut self, unbreakable: bool) -> Self {
+        if unbreakable {
+            self.tag_mut().insert("Unbreakable", 1_i8);
+        } else if let Some(tag) = &mut self.nbt {
+            tag.remove("Unbreakable");
+        }
+
+        self
+    }
+
+    /// Sets the parts of the tooltip that are hidden, which are stored in
+    /// `HideFlags`.
+    #[must_use]
+    pub fn with_hide_flags(mut self, flags: HideFlags) -> Self {
+        self.tag_mut()
+            .insert("HideFlags", i32::from(u8::from(flags)));
+        self
+    }
+
+    /// Sets the `CustomModelData` of the item, which resource packs can use to
+    /// change its model.
+    #[must_use]
+    pub fn with_custom_model_data(mut self, data: i32) -> Self {
+        self.tag_mut().insert("CustomModelData", data);
+        self
+    }
+
+    /// Gets the custom name of this item. Names that aren't valid JSON text
+    /// are read as plain text.
+    pub fn name(&self) -> Option<Text> {
+        match self.display()?.get("Name")? {
+            Value::String(name) => Some(text_from_json(name)),
+            _ => None,
+        }
+    }
+
+    /// Gets the lore of this item. Lines that aren't valid JSON text are read
+    /// as plain text.
+    pub fn lore(&self) -> Vec<Text> {
+        match self.display().and_then(|d| d.get("Lore")) {
+            Some(Value::List(List::String(lore))) => {
+                lore.iter().map(|line| text_from_json(line)).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Gets the enchantments on this item and their levels. Entries with a
+    /// missing or invalid ID are skipped, and levels may be any integer type.
+    pub fn enchantments(&self) -> Vec<(Ident<String>, i16)> {
+        let Some(Value::List(List::Compound(enchantments))) = self.tag_get("Enchantments") else {
+            return vec![];
+        };
+
+        enchantments
+            .iter()
+            .filter_map(|e| {
+                let Some(Value::String(id)) = e.get("id") else {
+                    return None;
+                };
+
+                let level = e.get("lvl").and_then(int_value).unwrap_or(0);
+
+                Some((
+                    parse_ident(id)?,
+                    level.clamp(i16::MIN.into(), i16::MAX.into()) as i16,
+                ))
+            })
+            .collect()
+    }
+
+    /// Returns if this item has a nonzero `Unbreakable` tag of any integer
+    /// type.
+    pub fn is_unbreakable(&self) -> bool {
+        self.tag_get("Unbreakable")
+            .and_then(int_value)
+            .is_some_and(|v| v != 0)
+    }
+
+    /// Gets the damage of this item, which is stored in `Damage`. Items start
+    /// at 0 and break once they reach [`Self::max_damage`].
+    pub fn damage_taken(&self) -> i32 {
+        self.tag_get("Damage")
+            .and_then(int_value)
+            .map_or(0, |v| v.clamp(i32::MIN.into(), i32::MAX.into()) as i32)
+    }
+
+    /// Gets the damage at which this item breaks, or 0 if the item can't be
+    /// damaged.
+    pub fn max_damage(&self) -> i32 {
+        self.item.max_durability().into()
+    }
+
+    /// Returns if this item has durability and isn't unbreakable.
+    pub fn is_damageable(&self) -> bool {
+        self.max_damage() > 0 && !self.is_unbreakable()
+    }
+
+    /// Damages this item by `amount`, like when a tool is used or armor is hit.
+    ///
+    /// Like in vanilla, each point of damage may be prevented by the
+    /// `unbreaking` enchantment, using `rng` for the chance. Once the item
+    /// reaches its [`max_damage`](Self::max_damage), [`DamageResult::Broken`]
+    /// is returned and it's up to the caller to remove the item.
+    pub fn damage(&mut self, amount: i32, rng: &mut impl Rng) -> DamageResult {
+        if !self.is_damageable() {
+            return DamageResult::Unbreakable;
+        }
+
+        let mut amount = amount;
+
+        if amount > 0 {
+            let unbreaking = self
+                .enchantments()
+                .into_iter()
+                .find(|(id, _)| id.as_str() == "minecraft:unbreaking")
+                .map_or(0, |(_, level)| level);
+
+            if unbreaking > 0 {
+                let prevented = (0..amount)
+                    .filter(|_| prevents_damage(self.item, unbreaking, rng))
+                    .count();
+
+                amount -= prevented as i32;
+            }
+
+            if amount == 0 {
+                return DamageResult::Damaged(self.damage_taken());
+            }
+        }
+
+        let damage = self.damage_taken().saturating_add(amount).max(0);
+        self.tag_mut().insert("Damage", damage);
+
+        if damage >= self.max_damage() {
+            DamageResult::Broken
+        } else {
+            DamageResult::Damaged(damage)
+        }
+    }
+
+    /// Gets the parts of the tooltip that are hidden.
+    pub fn hide_flags(&self) -> HideFlags {
+        let flags = self.tag_get("HideFlags").and_then(int_value).unwrap_or(0);
+        HideFlags::from(flags as u8)
+    }
+
+    /// Gets the `CustomModelData` of this item.
+    pub fn custom_model_data(&self) -> Option<i32> {
+        self.tag_get("CustomModelData")
+            .and_then(int_value)
+            .map(|v| v as i32)
+    }
+
+    fn tag_get(&self, key: &str) -> Option<&Value> {
+        self.nbt.as_ref()?.get(key)
+    }
+
+    /// Gets the `tag` compound, creating it if it doesn't exist.
+    fn tag_mut(&mut self) -> &mut Compound {
+        self.nbt.get_or_insert_with(Compound::new)
+    }
+
+    fn display(&self) -> Option<&Compound> {
+        match self.tag_get("display")? {
+            Value::Compound(display) => Some(display),
+            _ => None,
+        }
+    }
+
+    /// Gets the `display` compound, creating it if it doesn't exist or isn't a
+    /// compound.
+    fn display_mut(&mut self) -> &mut Compound {
+        let display = self
+            .tag_mut()
+            .entry("display")
+            .or_insert_with(|| Value::Compound(Compound::new()));
+
+        if !matches!(display, Value::Compound(_)) {
+            *display = Compound::new().into();
+        }
+
+        match display {
+            Value::Compound(display) => display,
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// The result of [`ItemStack::damage`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DamageResult {
+    /// The item has no durability or is unbreakable, so it wasn't changed.
+    Unbreakable,
+    /// The item now has this much damage. This is the old damage if all of it
+    /// was prevented by `unbreaking`.
+    Damaged(i32),
+    /// The item reached its maximum damage and should be removed.
+    Broken,
+}
+
+/// Returns if a point of damage is prevented by `unbreaking` at the given
+/// level. Armor is less likely to keep its durability than other items.
+fn prevents_damage(item: ItemKind, unbreaking: i16, rng: &mut impl Rng) -> bool {
+    let is_armor = ["_helmet", "_chestplate", "_leggings", "_boots"]
+        .iter()
+        .any(|suffix| item.to_str().ends_with(suffix));
+
+    if is_armor && rng.gen::<f32>() < 0.6 {
+        return false;
+    }
+
+    rng.gen_range(0..=unbreaking) > 0
+}
+
+/// The parts of an item's tooltip that can be hidden with
+/// [`ItemStack::with_hide_flags`].
+#[bitfield(u8)]
+#[derive(PartialEq, Eq)]
+pub struct HideFlags {
+    pub enchantments: bool,
+    /// Attribute modifiers, such as attack damage.
+    pub modifiers: bool,
+    pub unbreakable: bool,
+    pub can_destroy: bool,
+    pub can_place_on: bool,
+    /// Other information, such as potion effects and book authors.
+    pub additional: bool,
+    /// The dye color of leather armor.
+    pub dye: bool,
+    /// Armor trims.
+    pub upgrades: bool,
+}
+
+fn text_to_json(text: Text) -> String {
+    serde_json::to_string(&text)
+        .unwrap_or_else(|err| panic!("failed to jsonify text {text:?}\n{err}"))
+}
+
+fn text_from_json(json: &str) -> Text {
+    serde_json::from_str(json).unwrap_or_else(|_| Text::text(json.to_owned()))
+}
+
+/// Parses an ID the way vanilla does, where the namespace may be left out.
+fn parse_ident(id: &str) -> Option<Ident<String>> {
+    Ident::new(id.to_ascii_lowercase()).ok().map(Into::into)
+}
+
+fn int_value(value: &Value) -> Option<i64> {
+    match *value {
+        Value::Byte(v) => Some(v.into()),
+        Value::Short(v) => Some(v.into()),
+        Value::Int(v) => Some(v.into()),
+        Value::Long(v) => Some(v),
+        _ => None,
+    }
+}
+
+/// An error from reading an item stack with [`ItemStack::from_nbt`].
+#[derive(Clone, PartialEq, Eq, Debug, Error)]
+pub enum ItemNbtError {
+    #[error("missing item ID")]
+    MissingId,
+    #[error("invalid item ID of \"{0}\"")]
+    InvalidId(String),
+    #[error("unknown item ID of \"{0}\"")]
+    UnknownId(Ident<String>),
+    #[error("item field \"{0}\" has an unexpected type")]
+    BadFieldType(&'static str),
 }
 
 impl Default for ItemStack {
@@ -115,6 +524,32 @@ impl Decode<'_> for Option<ItemStack> {
     }
 }
 
+/// Item kinds are serialized by name, such as `"diamond_sword"`. Names with
+/// the `minecraft` namespace are also accepted.
+#[cfg(feature = "serde")]
+impl Serialize for ItemKind {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_str().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for ItemKind {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name = String::deserialize(deserializer)?;
+        let path = name.strip_prefix("minecraft:").unwrap_or(&name);
+
+        ItemKind::from_str(path)
+            .ok_or_else(|| D::Error::custom(format!("unknown item kind \"{name}\"")))
+    }
+}
+
 impl Encode for ItemKind {
     fn encode(&self, w: impl Write) -> anyhow::Result<()> {
         VarInt(self.to_raw() as i32).encode(w)
@@ -130,6 +565,311 @@ impl Decode<'_> for ItemKind {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use valence_nbt::snbt::from_snbt_str;
+
+    use super::*;
+    use crate::ident;
+    use crate::text::TextFormat;
+
+    #[test]
+    fn item_nbt_round_trip() {
+        let stacks = [
+            ItemStack::new(ItemKind::Cobblestone, 64, None),
+            ItemStack::new(
+                ItemKind::DiamondSword,
+                1,
+                Some(compound! {
+                    "Damage" => 12,
+                    "Enchantments" => List::Compound(vec![
+                        compound! { "id" => "minecraft:sharpness", "lvl" => 5_i16 },
+                        compound! { "id" => "minecraft:unbreaking", "lvl" => 3_i16 },
+                    ]),
+                }),
+            ),
+            ItemStack::new(
+                ItemKind::Apple,
+                3,
+                Some(compound! {
+                    "display" => compound! {
+                        "Name" => r#"{"text":"Golden","color":"gold","italic":false}"#,
+                        "Lore" => List::String(vec![r#""A very good apple""#.into()]),
+                    },
+                    "custom:data" => vec![1_i64, 2, 3],
+                }),
+            ),
+        ];
+
+        for stack in stacks {
+            let nbt = stack.to_nbt();
+            assert_eq!(ItemStack::from_nbt(&nbt), Ok(stack.clone()));
+            assert_eq!(ItemStack::from_nbt(&nbt).unwrap().to_nbt(), nbt);
+        }
+
+        assert_eq!(
+            ItemStack::new(ItemKind::Stone, 5, None).to_nbt(),
+            compound! { "id" => "minecraft:stone", "Count" => 5_i8 }
+        );
+    }
+
+    #[test]
+    fn item_tag_builders() {
+        // From `/data get entity @s SelectedItem` after giving a sword with these tags.
+        let vanilla = from_snbt_str(
+            r#"{id:"minecraft:diamond_sword",Count:1b,tag:{Damage:0,display:{Name:'{"text":"Excalibur","italic":false}',Lore:['{"text":"Forged in the lake"}','{"text":"Sharp"}']},Enchantments:[{id:"minecraft:sharpness",lvl:5s},{id:"minecraft:unbreaking",lvl:3s}],Unbreakable:1b,HideFlags:5,CustomModelData:1234567}}"#,
+        )
+        .unwrap();
+
+        let Value::Compound(vanilla) = vanilla else {
+            panic!("expected a compound");
+        };
+
+        let name = "Excalibur".not_italic();
+        let lore = [Text::text("Forged in the lake"), Text::text("Sharp")];
+        let flags = HideFlags::new()
+            .with_enchantments(true)
+            .with_unbreakable(true);
+
+        let stack = ItemStack::from_nbt(&vanilla).unwrap();
+
+        assert_eq!(stack.name(), Some(name.clone()));
+        assert_eq!(stack.lore(), lore);
+        assert_eq!(
+            stack.enchantments(),
+            [
+                (ident!("sharpness").to_string_ident(), 5),
+                (ident!("unbreaking").to_string_ident(), 3)
+            ]
+        );
+        assert!(stack.is_unbreakable());
+        assert_eq!(stack.hide_flags(), flags);
+        assert_eq!(stack.custom_model_data(), Some(1234567));
+
+        let built = ItemStack::new(ItemKind::DiamondSword, 1, Some(compound! { "Damage" => 0 }))
+            .with_name(name)
+            .with_lore(lore)
+            .with_enchantment(ident!("sharpness"), 4)
+            .with_enchantment(ident!("unbreaking"), 3)
+            .with_enchantment(ident!("sharpness"), 5)
+            .with_unbreakable(true)
+            .with_hide_flags(flags)
+            .with_custom_model_data(1234567);
+
+        assert_eq!(built.to_nbt(), vanilla);
+
+        // Other tags are kept.
+        let stack = ItemStack::new(
+            ItemKind::LeatherHelmet,
+            1,
+            Some(compound! {
+                "Unbreakable" => 1_i8,
+                "display" => compound! { "color" => 0xff0000 },
+            }),
+        )
+        .with_name("Hat")
+        .with_lore(Vec::<Text>::new())
+        .with_unbreakable(false);
+
+        assert_eq!(
+            stack.nbt,
+            Some(compound! {
+                "display" => compound! {
+                    "Name" => r#"{"text":"Hat"}"#,
+                    "color" => 0xff0000,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn item_tag_getter_leniency() {
+        let stack = ItemStack::new(
+            ItemKind::Stick,
+            1,
+            Some(compound! {
+                "display" => compound! {
+                    "Name" => "not json",
+                    "Lore" => List::String(vec![r#""plain""#.into(), "{".into()]),
+                },
+                "Enchantments" => List::Compound(vec![
+                    compound! { "id" => "Knockback", "lvl" => 100_000 },
+                    compound! { "id" => "not an id", "lvl" => 1_i16 },
+                    compound! { "lvl" => 1_i16 },
+                ]),
+                "Unbreakable" => 2,
+                "HideFlags" => 64_i8,
+                "CustomModelData" => 7_i64,
+            }),
+        );
+
+        assert_eq!(stack.name(), Some(Text::text("not json")));
+        assert_eq!(stack.lore(), [Text::text("plain"), Text::text("{")]);
+        assert_eq!(
+            stack.enchantments(),
+            [(ident!("knockback").to_string_ident(), i16::MAX)]
+        );
+        assert!(stack.is_unbreakable());
+        assert_eq!(stack.hide_flags(), HideFlags::new().with_dye(true));
+        assert_eq!(stack.custom_model_data(), Some(7));
+
+        let stack = ItemStack::new(
+            ItemKind::Stick,
+            1,
+            Some(compound! {
+                "display" => "wrong type",
+                "Enchantments" => List::Int(vec![1]),
+            }),
+        );
+
+        assert_eq!(stack.name(), None);
+        assert!(stack.lore().is_empty());
+        assert!(stack.enchantments().is_empty());
+        assert!(!stack.is_unbreakable());
+        assert_eq!(stack.hide_flags(), HideFlags::new());
+        assert_eq!(stack.custom_model_data(), None);
+
+        // Values of the wrong type are replaced.
+        let stack = stack
+            .with_name("Stick")
+            .with_enchantment(ident!("knockback"), 2);
+
+        assert_eq!(stack.name(), Some(Text::text("Stick")));
+        assert_eq!(
+            stack.enchantments(),
+            [(ident!("knockback").to_string_ident(), 2)]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn item_kind_serde() {
+        assert_eq!(
+            serde_json::to_string(&ItemKind::DiamondSword).unwrap(),
+            r#""diamond_sword""#
+        );
+        assert_eq!(
+            serde_json::from_str::<ItemKind>(r#""minecraft:diamond_sword""#).unwrap(),
+            ItemKind::DiamondSword
+        );
+        assert_eq!(
+            serde_json::from_str::<ItemKind>(r#""diamond_swords""#)
+                .unwrap_err()
+                .to_string(),
+            r#"unknown item kind "diamond_swords""#
+        );
+    }
+
+    #[test]
+    fn item_nbt_leniency() {
+        let read = |nbt: Compound| ItemStack::from_nbt(&nbt);
+
+        assert_eq!(
+            read(compound! { "id" => "Minecraft:Diamond_Sword", "Count" => 1_i8 }),
+            Ok(ItemStack::new(ItemKind::DiamondSword, 1, None))
+        );
+        assert_eq!(
+            read(compound! { "id" => "STONE", "Count" => 300 }),
+            Ok(ItemStack::new(ItemKind::Stone, 127, None))
+        );
+        assert_eq!(
+            read(compound! { "id" => "stone", "Count" => -4_i8 }),
+            Ok(ItemStack::new(ItemKind::Stone, 1, None))
+        );
+        assert_eq!(
+            read(compound! { "id" => "minecraft:stone" }),
+            Ok(ItemStack::new(ItemKind::Stone, 1, None))
+        );
+
+        assert_eq!(read(compound! {}), Err(ItemNbtError::MissingId));
+        assert_eq!(
+            read(compound! { "id" => 1 }),
+            Err(ItemNbtError::BadFieldType("id"))
+        );
+        assert_eq!(
+            read(compound! { "id" => "not an id" }),
+            Err(ItemNbtError::InvalidId("not an id".into()))
+        );
+        assert_eq!(
+            read(compound! { "id" => "minecraft:not_an_item" }),
+            Err(ItemNbtError::UnknownId(
+                ident!("not_an_item").to_string_ident()
+            ))
+        );
+        assert_eq!(
+            read(compound! { "id" => "other:stone" }),
+            Err(ItemNbtError::UnknownId(
+                ident!("other:stone").to_string_ident()
+            ))
+        );
+        assert_eq!(
+            read(compound! { "id" => "stone", "Count" => "1" }),
+            Err(ItemNbtError::BadFieldType("Count"))
+        );
+        assert_eq!(
+            read(compound! { "id" => "stone", "tag" => 1 }),
+            Err(ItemNbtError::BadFieldType("tag"))
+        );
+    }
+
+    #[test]
+    fn item_damage_break_threshold() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut sword = ItemStack::new(ItemKind::DiamondSword, 1, None);
+
+        assert_eq!(sword.max_damage(), 1561);
+        assert_eq!(sword.damage(1559, &mut rng), DamageResult::Damaged(1559));
+        assert_eq!(sword.damage(1, &mut rng), DamageResult::Damaged(1560));
+        assert_eq!(sword.damage_taken(), 1560);
+        assert_eq!(sword.damage(1, &mut rng), DamageResult::Broken);
+        assert_eq!(sword.damage_taken(), 1561);
+
+        // Negative damage repairs the item.
+        assert_eq!(sword.damage(-2000, &mut rng), DamageResult::Damaged(0));
+
+        let mut stick = ItemStack::new(ItemKind::Stick, 1, None);
+        assert_eq!(stick.damage(1, &mut rng), DamageResult::Unbreakable);
+        assert_eq!(stick.nbt, None);
+
+        let mut sword = ItemStack::new(ItemKind::DiamondSword, 1, None).with_unbreakable(true);
+        assert_eq!(sword.damage(5000, &mut rng), DamageResult::Unbreakable);
+        assert_eq!(sword.damage_taken(), 0);
+    }
+
+    #[test]
+    fn item_damage_unbreaking() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        // Without unbreaking, all of the damage is taken.
+        let mut pickaxe = ItemStack::new(ItemKind::NetheritePickaxe, 1, None);
+        assert_eq!(pickaxe.damage(1000, &mut rng), DamageResult::Damaged(1000));
+
+        // Tools keep 1 / (level + 1) of the damage.
+        let mut pickaxe = ItemStack::new(ItemKind::NetheritePickaxe, 1, None)
+            .with_enchantment(ident!("unbreaking"), 3);
+        pickaxe.damage(1000, &mut rng);
+        assert!((150..350).contains(&pickaxe.damage_taken()));
+
+        // Armor keeps 0.6 + 0.4 / (level + 1) of the damage.
+        let mut chestplate = ItemStack::new(ItemKind::NetheriteChestplate, 1, None)
+            .with_enchantment(ident!("unbreaking"), 3);
+        chestplate.damage(500, &mut rng);
+        assert!((280..420).contains(&chestplate.damage_taken()));
+
+        // Damage is never increased.
+        let mut pickaxe = ItemStack::new(ItemKind::NetheritePickaxe, 1, None)
+            .with_enchantment(ident!("unbreaking"), i16::MAX);
+
+        for _ in 0..100 {
+            let before = pickaxe.damage_taken();
+            pickaxe.damage(1, &mut rng);
+            assert!((before..=before + 1).contains(&pickaxe.damage_taken()));
+        }
+    }
+}
+
 /*
 #[cfg(test)]
 mod tests {
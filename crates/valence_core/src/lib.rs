@@ -37,7 +37,7 @@ pub mod text;
 pub mod translation_key;
 pub mod uuid;
 
-use std::num::NonZeroU32;
+use std::num::{NonZeroU32, NonZeroUsize};
 use std::time::Duration;
 
 use bevy_app::prelude::*;
@@ -78,11 +78,15 @@ impl Plugin for CorePlugin {
         let settings = app.world.get_resource_or_insert_with(CoreSettings::default);
 
         let compression_threshold = settings.compression_threshold;
+        let compression_level = settings.compression_level;
+        let compression_threads = settings.compression_threads;
         let tick_rate = settings.tick_rate;
 
         app.insert_resource(Server {
             current_tick: 0,
             compression_threshold,
+            compression_level,
+            compression_threads,
         });
 
         let tick_period = Duration::from_secs_f64((tick_rate.get() as f64).recip());
@@ -122,11 +126,33 @@ pub struct CoreSettings {
     /// If the server is used behind a proxy on the same machine, you will
     /// likely want to disable compression.
     ///
+    /// The network plugin can override this for individual connections.
+    ///
     /// # Default Value
     ///
     /// Compression is enabled with an unspecified value. This value may
     /// change in future versions.
     pub compression_threshold: Option<u32>,
+    /// The zlib compression level of packets over the compression threshold,
+    /// from 0 (no compression) to 9 (best compression). Higher levels use
+    /// less bandwidth at the cost of more CPU time.
+    ///
+    /// # Default Value
+    ///
+    /// [`DEFAULT_COMPRESSION_LEVEL`]
+    ///
+    /// [`DEFAULT_COMPRESSION_LEVEL`]: protocol::encode::DEFAULT_COMPRESSION_LEVEL
+    pub compression_level: u32,
+    /// The maximum number of threads used to compress packets sent to
+    /// clients. Packets over the compression threshold are compressed on
+    /// these threads when clients are flushed at the end of the tick, so that
+    /// packets for different clients are compressed in parallel. `None` uses
+    /// one thread per CPU core.
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    pub compression_threads: Option<NonZeroUsize>,
 }
 
 impl Default for CoreSettings {
@@ -134,16 +160,31 @@ impl Default for CoreSettings {
         Self {
             tick_rate: DEFAULT_TPS,
             compression_threshold: Some(256),
+            compression_level: protocol::encode::DEFAULT_COMPRESSION_LEVEL,
+            compression_threads: None,
         }
     }
 }
 
 /// Contains global server state accessible as a [`Resource`].
-#[derive(Resource, Default)]
+#[derive(Resource)]
 pub struct Server {
     /// Incremented on every tick.
     current_tick: i64,
     compression_threshold: Option<u32>,
+    compression_level: u32,
+    compression_threads: Option<NonZeroUsize>,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self {
+            current_tick: 0,
+            compression_threshold: None,
+            compression_level: protocol::encode::DEFAULT_COMPRESSION_LEVEL,
+            compression_threads: None,
+        }
+    }
 }
 
 impl Server {
@@ -156,4 +197,30 @@ impl Server {
     pub fn compression_threshold(&self) -> Option<u32> {
         self.compression_threshold
     }
+
+    /// Returns the server's zlib compression level.
+    pub fn compression_level(&self) -> u32 {
+        self.compression_level
+    }
+
+    /// Encodes `packet` once with the server's compression settings, so that
+    /// it can be written to many clients with [`WritePacket::write_encoded`].
+    ///
+    /// [`WritePacket::write_encoded`]: protocol::encode::WritePacket::write_encoded
+    pub fn encode_shared<P: protocol::SharedPacket>(
+        &self,
+        packet: &P,
+    ) -> anyhow::Result<protocol::encode::EncodedPacket> {
+        protocol::encode::EncodedPacket::new(
+            packet,
+            self.compression_threshold,
+            self.compression_level,
+        )
+    }
+
+    /// Returns the maximum number of threads used to compress packets, or
+    /// `None` for one thread per CPU core.
+    pub fn compression_threads(&self) -> Option<NonZeroUsize> {
+        self.compression_threads
+    }
 }
@@ -7,7 +7,7 @@ use glam::{DVec3, Vec3};
 use crate::block_pos::BlockPos;
 use crate::item::ItemStack;
 use crate::protocol::var_int::VarInt;
-use crate::protocol::{packet_id, Decode, Encode, Packet};
+use crate::protocol::{packet_id, Decode, Encode, Packet, SharedPacket};
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum Particle {
@@ -367,6 +367,8 @@ pub struct ParticleS2c<'a> {
     pub count: i32,
 }
 
+impl SharedPacket for ParticleS2c<'_> {}
+
 impl Encode for ParticleS2c<'_> {
     fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
         VarInt(self.particle.id()).encode(&mut w)?;
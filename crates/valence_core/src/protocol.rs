@@ -15,11 +15,19 @@ use std::io::Write;
 
 use anyhow::Context;
 pub use valence_core_macros::{Decode, Encode, Packet};
+use valence_nbt::binary::DecodeLimits;
 use var_int::VarInt;
 
 /// The maximum number of bytes in a single Minecraft packet.
 pub const MAX_PACKET_SIZE: i32 = 2097152;
 
+/// The limits on NBT decoded from packets. Like vanilla, decoded NBT may take
+/// up to 2 MiB.
+pub const NBT_DECODE_LIMITS: DecodeLimits = DecodeLimits {
+    max_depth: 512,
+    max_size: 2097152,
+};
+
 /// The `Encode` trait allows objects to be written to the Minecraft protocol.
 /// It is the inverse of [`Decode`].
 ///
@@ -189,8 +197,20 @@ pub trait Packet: std::fmt::Debug {
     }
 }
 
+/// Packets that are the same for every client they are sent to. These can be
+/// encoded once with [`EncodedPacket::new`] and written to many clients,
+/// instead of being encoded and compressed again for each client.
+///
+/// Packets that contain data specific to one client must not implement this.
+/// This includes packets that refer to the client's own entity, which always
+/// has the ID zero, and packets with IDs the client must respond with, such as
+/// teleport and keepalive IDs.
+///
+/// [`EncodedPacket::new`]: encode::EncodedPacket::new
+pub trait SharedPacket: Packet + Encode {}
+
 /// The side a packet is intended for
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum PacketSide {
     /// Server -> Client
     Clientbound,
@@ -199,7 +219,7 @@ pub enum PacketSide {
 }
 
 /// The state which a packet is used
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum PacketState {
     Handshaking,
     Status,
@@ -274,6 +294,8 @@ mod tests {
         assert_eq!(StructWithGenerics::<()>::NAME, "StructWithGenerics");
     }
 
+    use valence_nbt::Compound;
+
     use crate::block_pos::BlockPos;
     use crate::hand::Hand;
     use crate::ident::Ident;
@@ -370,4 +392,293 @@ mod tests {
         check_test_packet(&mut dec, "fourth");
         check_test_packet(&mut dec, "third");
     }
+
+    #[test]
+    fn frame_sizes() {
+        use crate::protocol::encode::FrameSize;
+
+        let mut enc = PacketEncoder::new();
+        enc.append_packet(&BytesPacket(crate::protocol::raw::RawBytes(&[0; 200])))
+            .unwrap();
+
+        // Two bytes of length prefix, one byte of packet ID.
+        let expected = FrameSize {
+            frame_len: 203,
+            data_len: 201,
+        };
+        assert_eq!(enc.frame_sizes().collect::<Vec<_>>(), [expected]);
+
+        #[cfg(feature = "compression")]
+        {
+            enc.clear();
+            enc.set_compression(Some(100));
+            enc.append_packet(&BytesPacket(crate::protocol::raw::RawBytes(&[0; 200])))
+                .unwrap();
+            enc.append_packet(&BytesPacket(crate::protocol::raw::RawBytes(&[0; 10])))
+                .unwrap();
+
+            let sizes: Vec<_> = enc.frame_sizes().collect();
+            assert_eq!(sizes.len(), 2);
+
+            // The compressed packet is smaller on the wire.
+            assert_eq!(sizes[0].data_len, 201);
+            assert!(sizes[0].frame_len < 50);
+
+            // Packets under the threshold have an extra byte for the data length.
+            assert_eq!(
+                sizes[1],
+                FrameSize {
+                    frame_len: 13,
+                    data_len: 11,
+                }
+            );
+
+            assert_eq!(sizes.iter().map(|s| s.frame_len).sum::<usize>(), enc.len());
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn deferred_compression_preserves_order() {
+        let write = |enc: &mut PacketEncoder| {
+            let mut append = |byte, len| {
+                enc.append_packet(&BytesPacket(crate::protocol::raw::RawBytes(
+                    &[byte; 300][..len],
+                )))
+                .unwrap()
+            };
+
+            append(1, 10);
+            append(2, 200);
+            append(3, 20);
+            append(4, 300);
+
+            enc.prepend_packet(&BytesPacket(crate::protocol::raw::RawBytes(&[5; 100])))
+                .unwrap();
+            enc.append_packet(&BytesPacket(crate::protocol::raw::RawBytes(&[6; 30])))
+                .unwrap();
+        };
+
+        let mut enc = PacketEncoder::new();
+        enc.set_compression(Some(64));
+        write(&mut enc);
+        let expected = enc.take();
+
+        let mut deferred = PacketEncoder::new();
+        deferred.set_compression(Some(64));
+        deferred.set_deferred_compression(true);
+        write(&mut deferred);
+
+        assert!(deferred.has_deferred());
+        deferred.compress_deferred();
+        assert!(!deferred.has_deferred());
+
+        assert_eq!(deferred.take(), expected);
+
+        // Deferred packets are also compressed when taken.
+        write(&mut deferred);
+        assert_eq!(deferred.take(), expected);
+    }
+
+    #[derive(PartialEq, Debug, Encode, Decode, Packet)]
+    #[packet(id = 43, side = PacketSide::Clientbound)]
+    struct BytesPacket<'a>(crate::protocol::raw::RawBytes<'a>);
+
+    impl SharedPacket for BytesPacket<'_> {}
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn encoded_packets_match_encoder() {
+        use crate::protocol::encode::{EncodedPacket, WritePacket, DEFAULT_COMPRESSION_LEVEL};
+
+        for len in [10, 300] {
+            let pkt = BytesPacket(crate::protocol::raw::RawBytes(&[7; 300][..len]));
+            let encoded = EncodedPacket::new(&pkt, Some(64), DEFAULT_COMPRESSION_LEVEL).unwrap();
+
+            let mut enc = PacketEncoder::new();
+            enc.set_compression(Some(64));
+            enc.append_packet(&pkt).unwrap();
+            let expected = enc.take();
+
+            // The same data is written to every encoder.
+            for _ in 0..2 {
+                let mut enc = PacketEncoder::new();
+                enc.set_compression(Some(64));
+                enc.write_encoded(&encoded);
+                assert_eq!(enc.take(), expected);
+            }
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn compression_threshold_boundaries() {
+        use crate::protocol::encode::{PacketWriter, WritePacket, DEFAULT_COMPRESSION_LEVEL};
+
+        const THRESHOLD: u32 = 64;
+
+        let body = [0xab; THRESHOLD as usize];
+
+        for level in [0, DEFAULT_COMPRESSION_LEVEL, 9] {
+            // The packet ID is one byte, so the packet lengths are one less than,
+            // equal to, and one more than the threshold.
+            for body_len in [
+                THRESHOLD as usize - 2,
+                THRESHOLD as usize - 1,
+                THRESHOLD as usize,
+            ] {
+                let pkt = BytesPacket(crate::protocol::raw::RawBytes(&body[..body_len]));
+                let data_len = body_len + 1;
+
+                let mut enc = PacketEncoder::new();
+                enc.set_compression(Some(THRESHOLD));
+                enc.set_compression_level(level);
+                enc.append_packet(&pkt).unwrap();
+                let bytes = enc.take();
+
+                let mut writer_bytes = vec![];
+                PacketWriter::new(&mut writer_bytes, Some(THRESHOLD))
+                    .with_level(level)
+                    .write_packet(&pkt);
+                assert_eq!(bytes[..], writer_bytes[..]);
+
+                // Packets at least as long as the threshold are compressed, and are
+                // prefixed with their uncompressed length. Others are prefixed with
+                // zero.
+                let mut r = &bytes[..];
+                VarInt::decode(&mut r).unwrap();
+                let expected_data_len = if data_len >= THRESHOLD as usize {
+                    data_len as i32
+                } else {
+                    0
+                };
+                assert_eq!(VarInt::decode(&mut r).unwrap().0, expected_data_len);
+
+                let mut dec = PacketDecoder::new();
+                dec.set_compression(Some(THRESHOLD));
+                dec.queue_bytes(bytes);
+
+                let frame = dec.try_next_packet().unwrap().unwrap();
+                assert_eq!(frame.decode::<BytesPacket>().unwrap(), pkt);
+            }
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn decode_rejects_wrong_side_of_threshold() {
+        let pkt = BytesPacket(crate::protocol::raw::RawBytes(&[0xab; 63]));
+
+        // Uncompressed packet of length 64 with a threshold of 64.
+        let mut enc = PacketEncoder::new();
+        enc.set_compression(Some(65));
+        enc.append_packet(&pkt).unwrap();
+
+        let mut dec = PacketDecoder::new();
+        dec.set_compression(Some(64));
+        dec.queue_bytes(enc.take());
+        assert!(dec.try_next_packet().is_err());
+
+        // Compressed packet of length 64 with a threshold of 65.
+        let mut enc = PacketEncoder::new();
+        enc.set_compression(Some(64));
+        enc.append_packet(&pkt).unwrap();
+
+        let mut dec = PacketDecoder::new();
+        dec.set_compression(Some(65));
+        dec.queue_bytes(enc.take());
+        assert!(dec.try_next_packet().is_err());
+    }
+
+    #[test]
+    fn strings_are_borrowed_from_input() {
+        let mut buf = vec![];
+        "minecraft:brand".encode(&mut buf).unwrap();
+
+        let mut r = buf.as_slice();
+        let ident = Ident::<Cow<str>>::decode(&mut r).unwrap();
+        assert!(r.is_empty());
+        assert_eq!(ident.as_str(), "minecraft:brand");
+        assert!(matches!(ident.into_inner(), Cow::Borrowed(_)));
+
+        // Invalid UTF-8 is rejected before anything is borrowed.
+        let mut buf = vec![];
+        [0xc3_u8, 0x28].as_slice().encode(&mut buf).unwrap();
+
+        assert!(Cow::<str>::decode(&mut buf.as_slice()).is_err());
+        assert!(<&str>::decode(&mut buf.as_slice()).is_err());
+    }
+
+    #[test]
+    fn bundles_are_flattened_and_split() {
+        use crate::protocol::encode::{
+            BundleSplitterS2c, PacketWriter, WritePacket, MAX_BUNDLE_PACKETS,
+        };
+
+        let mut buf = vec![];
+        let mut writer = PacketWriter::new(&mut buf, None);
+
+        // Empty bundles are not written.
+        writer.bundled(|_| {});
+
+        writer.bundled(|w| {
+            w.write_packet(&UnitStruct);
+            w.bundled(|w| w.write_packet(&UnitStruct));
+
+            let mut raw = vec![];
+            let mut raw_writer = PacketWriter::new(&mut raw, None);
+
+            for _ in 0..MAX_BUNDLE_PACKETS - 1 {
+                raw_writer.write_packet(&UnitStruct);
+            }
+
+            // The bundle is split after these packets.
+            w.write_packet_bytes(&raw);
+        });
+
+        let mut dec = PacketDecoder::new();
+        dec.queue_slice(&buf);
+
+        let mut ids = vec![];
+        while let Some(frame) = dec.try_next_packet().unwrap() {
+            ids.push(frame.id);
+        }
+
+        let delimiters: Vec<_> = ids
+            .iter()
+            .enumerate()
+            .filter(|(_, &id)| id == BundleSplitterS2c::ID)
+            .map(|(i, _)| i)
+            .collect();
+
+        assert_eq!(ids.len(), MAX_BUNDLE_PACKETS + 1 + 4);
+        assert_eq!(
+            delimiters,
+            [
+                0,
+                MAX_BUNDLE_PACKETS + 1,
+                MAX_BUNDLE_PACKETS + 2,
+                MAX_BUNDLE_PACKETS + 4
+            ]
+        );
+    }
+
+    #[test]
+    fn nbt_is_decoded_with_limits() {
+        let codec = include_bytes!("../../../extracted/registry_codec_1.20.dat");
+        assert!(Compound::decode(&mut codec.as_slice()).is_ok());
+
+        // A list nested 10,000 deep.
+        let mut buf = vec![10, 0, 0, 9, 0, 0];
+        for _ in 0..10_000 {
+            buf.extend([9, 0, 0, 0, 1]);
+        }
+        assert!(Compound::decode(&mut buf.as_slice()).is_err());
+
+        // 300,000 empty compounds in 300 KB.
+        let mut buf = vec![10, 0, 0, 9, 0, 0, 10];
+        buf.extend(300_000_i32.to_be_bytes());
+        buf.extend([0; 300_001]);
+        assert!(Compound::decode(&mut buf.as_slice()).is_err());
+    }
 }
@@ -72,8 +72,8 @@ impl PacketDecoder {
             // Is this packet compressed?
             if data_len > 0 {
                 ensure!(
-                    data_len as u32 > threshold,
-                    "decompressed packet length of {data_len} is <= the compression threshold of \
+                    data_len as u32 >= threshold,
+                    "decompressed packet length of {data_len} is < the compression threshold of \
                      {threshold}"
                 );
 
@@ -100,8 +100,8 @@ impl PacketDecoder {
                 debug_assert_eq!(data_len, 0);
 
                 ensure!(
-                    r.len() <= threshold as usize,
-                    "uncompressed packet length of {} exceeds compression threshold of {}",
+                    r.len() < threshold as usize,
+                    "uncompressed packet length of {} is >= the compression threshold of {}",
                     r.len(),
                     threshold
                 );
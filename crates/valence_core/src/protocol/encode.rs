@@ -1,4 +1,7 @@
 use std::io::Write;
+#[cfg(feature = "compression")]
+use std::mem;
+use std::ops::Range;
 
 #[cfg(feature = "encryption")]
 use aes::cipher::generic_array::GenericArray;
@@ -6,28 +9,70 @@ use aes::cipher::generic_array::GenericArray;
 use aes::cipher::{BlockEncryptMut, BlockSizeUser, KeyIvInit};
 use anyhow::ensure;
 use bevy_ecs::world::Mut;
-use bytes::{BufMut, BytesMut};
+use bytes::{BufMut, Bytes, BytesMut};
 use tracing::warn;
 
 use crate::protocol::var_int::VarInt;
-use crate::protocol::{Encode, Packet, MAX_PACKET_SIZE};
+use crate::protocol::{packet_id, Decode, Encode, Packet, SharedPacket, MAX_PACKET_SIZE};
 
 /// The AES block cipher with a 128 bit key, using the CFB-8 mode of
 /// operation.
 #[cfg(feature = "encryption")]
 type Cipher = cfb8::Encryptor<aes::Aes128>;
 
-#[derive(Default)]
+/// The zlib compression level used to compress packets unless another level is
+/// set. Levels range from 0 (no compression) to 9 (best compression).
+pub const DEFAULT_COMPRESSION_LEVEL: u32 = 4;
+
+/// The highest zlib compression level. Higher levels are clamped to this.
+const MAX_COMPRESSION_LEVEL: u32 = 9;
+
+/// The size of a packet frame written to a [`PacketEncoder`]. See
+/// [`PacketEncoder::frame_sizes`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct FrameSize {
+    /// The size of the whole frame, including the length prefix. This is the
+    /// number of bytes the packet takes up on the wire.
+    pub frame_len: usize,
+    /// The size of the packet ID and body before compression.
+    pub data_len: usize,
+}
+
 pub struct PacketEncoder {
     buf: BytesMut,
     #[cfg(feature = "compression")]
     compress_buf: Vec<u8>,
     #[cfg(feature = "compression")]
     compression_threshold: Option<u32>,
+    #[cfg(feature = "compression")]
+    compression_level: u32,
+    #[cfg(feature = "compression")]
+    defer_compression: bool,
+    /// The unframed packets in `buf` waiting to be compressed, in order.
+    deferred: Vec<Range<usize>>,
     #[cfg(feature = "encryption")]
     cipher: Option<Cipher>,
 }
 
+impl Default for PacketEncoder {
+    fn default() -> Self {
+        Self {
+            buf: BytesMut::new(),
+            #[cfg(feature = "compression")]
+            compress_buf: vec![],
+            #[cfg(feature = "compression")]
+            compression_threshold: None,
+            #[cfg(feature = "compression")]
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            #[cfg(feature = "compression")]
+            defer_compression: false,
+            deferred: vec![],
+            #[cfg(feature = "encryption")]
+            cipher: None,
+        }
+    }
+}
+
 impl PacketEncoder {
     pub fn new() -> Self {
         Self::default()
@@ -43,11 +88,25 @@ impl PacketEncoder {
         P: Packet + Encode,
     {
         let start_len = self.buf.len();
-        self.append_packet(pkt)?;
+
+        // The deferred packets are kept in order, so the prepended packet is
+        // compressed right away.
+        #[cfg(feature = "compression")]
+        let defer_compression = mem::replace(&mut self.defer_compression, false);
+        let res = self.append_packet(pkt);
+        #[cfg(feature = "compression")]
+        {
+            self.defer_compression = defer_compression;
+        }
+        res?;
 
         let end_len = self.buf.len();
         let total_packet_len = end_len - start_len;
 
+        for range in &mut self.deferred {
+            *range = range.start + total_packet_len..range.end + total_packet_len;
+        }
+
         // 1) Move everything back by the length of the packet.
         // 2) Move the packet to the new space at the front.
         // 3) Truncate the old packet away.
@@ -67,38 +126,33 @@ impl PacketEncoder {
 
         pkt.encode_with_id((&mut self.buf).writer())?;
 
-        let data_len = self.buf.len() - start_len;
-
-        #[cfg(feature = "compression")]
-        if let Some(threshold) = self.compression_threshold {
-            use std::io::Read;
-
-            use flate2::bufread::ZlibEncoder;
-            use flate2::Compression;
-
-            if data_len > threshold as usize {
-                let mut z = ZlibEncoder::new(&self.buf[start_len..], Compression::new(4));
-
-                self.compress_buf.clear();
-
-                let data_len_size = VarInt(data_len as i32).written_size();
-
-                let packet_len = data_len_size + z.read_to_end(&mut self.compress_buf)?;
+        self.frame_packet(start_len)
+    }
 
-                ensure!(
-                    packet_len <= MAX_PACKET_SIZE as usize,
-                    "packet exceeds maximum length"
-                );
+    /// Appends a packet that has already been encoded. `data` is the packet's
+    /// VarInt ID followed by the packet's body. The packet is framed and
+    /// compressed the same way as [`append_packet`](Self::append_packet).
+    pub fn append_packet_bytes(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        let start_len = self.buf.len();
 
-                drop(z);
+        self.buf.extend_from_slice(data);
 
-                self.buf.truncate(start_len);
+        self.frame_packet(start_len)
+    }
 
-                let mut writer = (&mut self.buf).writer();
+    /// Adds the length prefix (and compresses if enabled) to the unframed
+    /// packet data at the end of the buffer starting at `start_len`.
+    fn frame_packet(&mut self, start_len: usize) -> anyhow::Result<()> {
+        let data_len = self.buf.len() - start_len;
 
-                VarInt(packet_len as i32).encode(&mut writer)?;
-                VarInt(data_len as i32).encode(&mut writer)?;
-                self.buf.extend_from_slice(&self.compress_buf);
+        #[cfg(feature = "compression")]
+        if let Some(threshold) = self.compression_threshold {
+            if data_len >= threshold as usize {
+                if self.defer_compression {
+                    self.deferred.push(start_len..self.buf.len());
+                } else {
+                    self.compress_packet(start_len)?;
+                }
             } else {
                 let data_len_size = 1;
                 let packet_len = data_len_size + data_len;
@@ -145,9 +199,103 @@ impl PacketEncoder {
         Ok(())
     }
 
-    /// Takes all the packets written so far and encrypts them if encryption is
-    /// enabled.
+    /// Compresses and frames the unframed packet data at the end of the buffer
+    /// starting at `start_len`.
+    #[cfg(feature = "compression")]
+    fn compress_packet(&mut self, start_len: usize) -> anyhow::Result<()> {
+        use std::io::Read;
+
+        use flate2::bufread::ZlibEncoder;
+        use flate2::Compression;
+
+        let data_len = self.buf.len() - start_len;
+
+        let mut z = ZlibEncoder::new(
+            &self.buf[start_len..],
+            Compression::new(self.compression_level),
+        );
+
+        self.compress_buf.clear();
+
+        let data_len_size = VarInt(data_len as i32).written_size();
+
+        let packet_len = data_len_size + z.read_to_end(&mut self.compress_buf)?;
+
+        ensure!(
+            packet_len <= MAX_PACKET_SIZE as usize,
+            "packet exceeds maximum length"
+        );
+
+        drop(z);
+
+        self.buf.truncate(start_len);
+
+        let mut writer = (&mut self.buf).writer();
+
+        VarInt(packet_len as i32).encode(&mut writer)?;
+        VarInt(data_len as i32).encode(&mut writer)?;
+        self.buf.extend_from_slice(&self.compress_buf);
+
+        Ok(())
+    }
+
+    /// Sets whether packets over the compression threshold are compressed
+    /// later by [`Self::compress_deferred`] instead of as they are written.
+    /// This allows large packets to be compressed on another thread. Smaller
+    /// packets are always framed as they are written.
+    ///
+    /// Errors from compressing deferred packets are logged and the packets are
+    /// discarded, rather than being returned from the `append` functions.
+    pub fn set_deferred_compression(&mut self, defer: bool) {
+        #[cfg(feature = "compression")]
+        {
+            self.defer_compression = defer;
+        }
+        #[cfg(not(feature = "compression"))]
+        let _ = defer;
+    }
+
+    /// Whether any packets are waiting to be compressed by
+    /// [`Self::compress_deferred`].
+    pub fn has_deferred(&self) -> bool {
+        !self.deferred.is_empty()
+    }
+
+    /// Compresses the packets deferred by
+    /// [`set_deferred_compression`](Self::set_deferred_compression). This is
+    /// done by [`Self::take`] if it hasn't been done already.
+    pub fn compress_deferred(&mut self) {
+        #[cfg(feature = "compression")]
+        if !self.deferred.is_empty() {
+            let deferred = mem::take(&mut self.deferred);
+            let capacity = self.buf.len();
+            let old = mem::replace(&mut self.buf, BytesMut::with_capacity(capacity));
+
+            let mut pos = 0;
+
+            for range in deferred {
+                self.buf.extend_from_slice(&old[pos..range.start]);
+
+                let start_len = self.buf.len();
+                self.buf.extend_from_slice(&old[range.clone()]);
+
+                if let Err(e) = self.compress_packet(start_len) {
+                    warn!("failed to compress deferred packet: {e:#}");
+                    self.buf.truncate(start_len);
+                }
+
+                pos = range.end;
+            }
+
+            self.buf.extend_from_slice(&old[pos..]);
+        }
+    }
+
+    /// Takes all the packets written so far, compressing any deferred packets
+    /// and encrypting them if encryption is enabled.
     pub fn take(&mut self) -> BytesMut {
+        self.compress_deferred();
+
         #[cfg(feature = "encryption")]
         if let Some(cipher) = &mut self.cipher {
             for chunk in self.buf.chunks_mut(Cipher::block_size()) {
@@ -161,13 +309,81 @@ impl PacketEncoder {
 
     pub fn clear(&mut self) {
         self.buf.clear();
+        self.deferred.clear();
+    }
+
+    /// The number of bytes written so far that have not been taken.
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Returns the sizes of the packet frames written so far that have not
+    /// been taken. Deferred packets must be
+    /// [compressed](Self::compress_deferred) first.
+    pub fn frame_sizes(&self) -> impl Iterator<Item = FrameSize> + '_ {
+        debug_assert!(!self.has_deferred(), "packets are waiting to be compressed");
+
+        let mut r = &self.buf[..];
+
+        #[cfg(feature = "compression")]
+        let compressed = self.compression_threshold.is_some();
+        #[cfg(not(feature = "compression"))]
+        let compressed = false;
+
+        std::iter::from_fn(move || {
+            let start_len = r.len();
+            let packet_len = VarInt::decode_partial(&mut r).ok()? as usize;
+            let frame_len = start_len - r.len() + packet_len;
+
+            let mut body = r.get(..packet_len)?;
+            r = &r[packet_len..];
+
+            let data_len = if compressed {
+                match VarInt::decode_partial(&mut body).ok()? {
+                    // Zero for no compression on this packet.
+                    0 => body.len(),
+                    data_len => data_len as usize,
+                }
+            } else {
+                packet_len
+            };
+
+            Some(FrameSize {
+                frame_len,
+                data_len,
+            })
+        })
     }
 
+    /// Sets the compression threshold. Packets with an uncompressed length of
+    /// at least `threshold` bytes are compressed. `None` disables compression.
     #[cfg(feature = "compression")]
     pub fn set_compression(&mut self, threshold: Option<u32>) {
         self.compression_threshold = threshold;
     }
 
+    #[cfg(feature = "compression")]
+    pub fn compression(&self) -> Option<u32> {
+        self.compression_threshold
+    }
+
+    /// Sets the zlib compression level used for packets over the compression
+    /// threshold, from 0 (no compression) to 9 (best compression). Higher
+    /// levels are clamped to 9.
+    #[cfg(feature = "compression")]
+    pub fn set_compression_level(&mut self, level: u32) {
+        self.compression_level = level.min(MAX_COMPRESSION_LEVEL);
+    }
+
+    #[cfg(feature = "compression")]
+    pub fn compression_level(&self) -> u32 {
+        self.compression_level
+    }
+
     /// Encrypts all future packets **and any packets that have
     /// not been [taken] yet.**
     ///
@@ -179,6 +395,15 @@ impl PacketEncoder {
     }
 }
 
+/// The maximum number of packets the client accepts in a single bundle.
+pub const MAX_BUNDLE_PACKETS: usize = 4096;
+
+/// Marks the start and end of a bundle of packets. See
+/// [`WritePacket::bundled`].
+#[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
+#[packet(id = packet_id::BUNDLE_SPLITTER)]
+pub struct BundleSplitterS2c;
+
 /// Types that can have packets written to them.
 pub trait WritePacket {
     /// Writes a packet to this object. Encoding errors are typically logged and
@@ -201,9 +426,44 @@ pub trait WritePacket {
     /// Copies raw packet data directly into this object. Don't use this unless
     /// you know what you're doing.
     fn write_packet_bytes(&mut self, bytes: &[u8]);
+
+    /// Writes a packet that was encoded ahead of time. See [`EncodedPacket`].
+    fn write_encoded(&mut self, packet: &EncodedPacket) {
+        self.write_packet_bytes(packet.as_bytes())
+    }
+
+    /// Writes the packets written by `f` as a bundle. The client handles all
+    /// packets in a bundle at once, so an entity spawned together with its
+    /// metadata never appears with default metadata for a frame.
+    ///
+    /// The bundle is surrounded with [`BundleSplitterS2c`] packets, and
+    /// nothing is written for an empty bundle. Bundles written inside a bundle
+    /// are merged into it. Bundles with more than [`MAX_BUNDLE_PACKETS`]
+    /// packets are split into several.
+    fn bundled<R>(&mut self, f: impl FnOnce(&mut BundleWriter<Self>) -> R) -> R {
+        let mut writer = BundleWriter {
+            nested: self.is_bundling(),
+            inner: self,
+            len: 0,
+        };
+
+        let res = f(&mut writer);
+
+        if writer.len > 0 {
+            writer.inner.write_packet(&BundleSplitterS2c);
+        }
+
+        res
+    }
+
+    /// Whether packets written to this object are already part of a bundle.
+    /// See [`bundled`](Self::bundled).
+    fn is_bundling(&self) -> bool {
+        false
+    }
 }
 
-impl<W: WritePacket> WritePacket for &mut W {
+impl<W: WritePacket + ?Sized> WritePacket for &mut W {
     fn write_packet_fallible<P>(&mut self, packet: &P) -> anyhow::Result<()>
     where
         P: Packet + Encode,
@@ -214,6 +474,10 @@ impl<W: WritePacket> WritePacket for &mut W {
     fn write_packet_bytes(&mut self, bytes: &[u8]) {
         (*self).write_packet_bytes(bytes)
     }
+
+    fn is_bundling(&self) -> bool {
+        (**self).is_bundling()
+    }
 }
 
 impl<T: WritePacket> WritePacket for Mut<'_, T> {
@@ -227,17 +491,107 @@ impl<T: WritePacket> WritePacket for Mut<'_, T> {
     fn write_packet_bytes(&mut self, bytes: &[u8]) {
         self.as_mut().write_packet_bytes(bytes)
     }
+
+    fn is_bundling(&self) -> bool {
+        (**self).is_bundling()
+    }
+}
+
+/// Writes packets in a bundle. See [`WritePacket::bundled`].
+pub struct BundleWriter<'a, W: ?Sized> {
+    inner: &'a mut W,
+    /// The number of packets in the current bundle. Zero if the bundle has not
+    /// been started.
+    len: usize,
+    /// Whether this bundle is merged into an enclosing bundle.
+    nested: bool,
+}
+
+impl<W: WritePacket + ?Sized> BundleWriter<'_, W> {
+    /// Starts the bundle if needed and makes room for another packet.
+    fn start_packet(&mut self) -> anyhow::Result<()> {
+        if self.len == MAX_BUNDLE_PACKETS {
+            // End the full bundle and start another.
+            self.inner.write_packet_fallible(&BundleSplitterS2c)?;
+            self.len = 0;
+        }
+
+        if self.len == 0 {
+            self.inner.write_packet_fallible(&BundleSplitterS2c)?;
+        }
+
+        self.len += 1;
+
+        Ok(())
+    }
+}
+
+impl<W: WritePacket + ?Sized> WritePacket for BundleWriter<'_, W> {
+    fn write_packet_fallible<P>(&mut self, packet: &P) -> anyhow::Result<()>
+    where
+        P: Packet + Encode,
+    {
+        if !self.nested {
+            self.start_packet()?;
+        }
+
+        self.inner.write_packet_fallible(packet)
+    }
+
+    fn write_packet_bytes(&mut self, mut bytes: &[u8]) {
+        if self.nested {
+            return self.inner.write_packet_bytes(bytes);
+        }
+
+        // Count the packets in the data by their length prefixes.
+        while !bytes.is_empty() {
+            let mut r = bytes;
+
+            let frame_len = match VarInt::decode_partial(&mut r) {
+                Ok(len) => (bytes.len() - r.len()).saturating_add(len.max(0) as usize),
+                Err(_) => bytes.len(),
+            }
+            .min(bytes.len());
+
+            if let Err(e) = self.start_packet() {
+                warn!("failed to write bundle delimiter: {e:#}");
+            }
+
+            self.inner.write_packet_bytes(&bytes[..frame_len]);
+
+            bytes = &bytes[frame_len..];
+        }
+    }
+
+    fn is_bundling(&self) -> bool {
+        true
+    }
 }
 
 /// An implementor of [`WritePacket`] backed by a `Vec` reference.
 pub struct PacketWriter<'a> {
     pub buf: &'a mut Vec<u8>,
     pub threshold: Option<u32>,
+    /// The zlib compression level. See
+    /// [`PacketEncoder::set_compression_level`].
+    pub level: u32,
 }
 
 impl<'a> PacketWriter<'a> {
+    /// Creates a writer that compresses with the
+    /// [default level](DEFAULT_COMPRESSION_LEVEL).
     pub fn new(buf: &'a mut Vec<u8>, threshold: Option<u32>) -> Self {
-        Self { buf, threshold }
+        Self {
+            buf,
+            threshold,
+            level: DEFAULT_COMPRESSION_LEVEL,
+        }
+    }
+
+    /// Sets the zlib compression level. Levels above 9 are clamped to 9.
+    pub fn with_level(mut self, level: u32) -> Self {
+        self.level = level.min(MAX_COMPRESSION_LEVEL);
+        self
     }
 }
 
@@ -248,7 +602,7 @@ impl WritePacket for PacketWriter<'_> {
     {
         #[cfg(feature = "compression")]
         if let Some(threshold) = self.threshold {
-            encode_packet_compressed(self.buf, pkt, threshold)
+            encode_packet_compressed(self.buf, pkt, threshold, self.level)
         } else {
             encode_packet(self.buf, pkt)
         }
@@ -264,6 +618,41 @@ impl WritePacket for PacketWriter<'_> {
     }
 }
 
+/// A [`SharedPacket`] encoded once so that it can be written to many clients
+/// with [`WritePacket::write_encoded`]. The encoded data is shared, so cloning
+/// is cheap.
+#[derive(Clone, Debug)]
+pub struct EncodedPacket {
+    bytes: Bytes,
+}
+
+impl EncodedPacket {
+    /// Encodes `packet`, compressing it with `level` if it's at least
+    /// `threshold` bytes long. The threshold must be the same as the
+    /// threshold of the encoders the packet is written to. The server's
+    /// settings can be used with [`Server::encode_shared`].
+    ///
+    /// [`Server::encode_shared`]: crate::Server::encode_shared
+    pub fn new<P: SharedPacket>(
+        packet: &P,
+        threshold: Option<u32>,
+        level: u32,
+    ) -> anyhow::Result<Self> {
+        let mut buf = vec![];
+
+        PacketWriter::new(&mut buf, threshold)
+            .with_level(level)
+            .write_packet_fallible(packet)?;
+
+        Ok(Self { bytes: buf.into() })
+    }
+
+    /// The framed (and possibly compressed) packet data.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
 impl WritePacket for PacketEncoder {
     fn write_packet_fallible<P>(&mut self, packet: &P) -> anyhow::Result<()>
     where
@@ -307,7 +696,12 @@ where
 }
 
 #[cfg(feature = "compression")]
-fn encode_packet_compressed<P>(buf: &mut Vec<u8>, pkt: &P, threshold: u32) -> anyhow::Result<()>
+fn encode_packet_compressed<P>(
+    buf: &mut Vec<u8>,
+    pkt: &P,
+    threshold: u32,
+    level: u32,
+) -> anyhow::Result<()>
 where
     P: Packet + Encode,
 {
@@ -322,8 +716,8 @@ where
 
     let data_len = buf.len() - start_len;
 
-    if data_len > threshold as usize {
-        let mut z = ZlibEncoder::new(&buf[start_len..], Compression::new(4));
+    if data_len >= threshold as usize {
+        let mut z = ZlibEncoder::new(&buf[start_len..], Compression::new(level));
 
         let mut scratch = vec![];
 
@@ -17,7 +17,7 @@ use uuid::Uuid;
 use valence_nbt::Compound;
 
 use super::var_int::VarInt;
-use super::{Decode, Encode, MAX_PACKET_SIZE};
+use super::{Decode, Encode, MAX_PACKET_SIZE, NBT_DECODE_LIMITS};
 
 // ==== Primitive ==== //
 
@@ -744,13 +744,30 @@ where
     }
 }
 
-impl<'a, 'b, B> Decode<'a> for Cow<'b, B>
+impl<'a, 'b, T> Decode<'a> for Cow<'b, T>
 where
-    B: ToOwned + ?Sized,
-    B::Owned: Decode<'a>,
+    T: Clone + Decode<'a>,
 {
     fn decode(r: &mut &'a [u8]) -> Result<Self> {
-        B::Owned::decode(r).map(Cow::Owned)
+        T::decode(r).map(Cow::Owned)
+    }
+}
+
+impl<'a, 'b, T> Decode<'a> for Cow<'b, [T]>
+where
+    T: Clone,
+    Vec<T>: Decode<'a>,
+{
+    fn decode(r: &mut &'a [u8]) -> Result<Self> {
+        Vec::decode(r).map(Cow::Owned)
+    }
+}
+
+/// Borrows the string from the input without allocating. The string is
+/// validated as UTF-8 first.
+impl<'a> Decode<'a> for Cow<'a, str> {
+    fn decode(r: &mut &'a [u8]) -> Result<Self> {
+        <&str>::decode(r).map(Cow::Borrowed)
     }
 }
 
@@ -774,6 +791,6 @@ impl Encode for Compound {
 
 impl Decode<'_> for Compound {
     fn decode(r: &mut &[u8]) -> Result<Self> {
-        Ok(Self::from_binary(r)?.0)
+        Ok(Self::from_binary_with_limits(r, NBT_DECODE_LIMITS)?.0)
     }
 }
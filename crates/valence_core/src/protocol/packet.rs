@@ -15,7 +15,7 @@ use uuid::Uuid;
 use crate::ident;
 use crate::ident::Ident;
 use crate::protocol::var_int::VarInt;
-use crate::protocol::{packet_id, Decode, Encode, Packet};
+use crate::protocol::{packet_id, Decode, Encode, Packet, SharedPacket};
 use crate::text::Text;
 
 // TODO: move module contents to valence_chat.
@@ -102,7 +102,6 @@ pub mod chat {
 
     #[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
     #[packet(id = packet_id::MESSAGE_ACKNOWLEDGMENT_C2S)]
-
     pub struct MessageAcknowledgmentC2s {
         pub message_count: VarInt,
     }
@@ -675,6 +674,8 @@ pub mod sound {
         pub seed: i64,
     }
 
+    impl SharedPacket for PlaySoundS2c<'_> {}
+
     #[derive(Clone, PartialEq, Debug)]
     pub enum SoundId<'a> {
         Direct {
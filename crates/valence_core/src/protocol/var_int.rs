@@ -38,21 +38,19 @@ impl VarInt {
 
         Err(VarIntDecodeError::TooLarge)
     }
-}
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Error)]
-pub enum VarIntDecodeError {
-    #[error("incomplete VarInt decode")]
-    Incomplete,
-    #[error("VarInt is too large")]
-    TooLarge,
-}
+    /// Encodes this VarInt into the first [`Self::written_size`] bytes of the
+    /// returned array. The rest of the array is unspecified.
+    #[inline]
+    fn to_bytes(self) -> ([u8; 8], usize) {
+        let x = self.0 as u32 as u64;
 
-impl Encode for VarInt {
-    // Adapted from VarInt-Simd encode
-    // https://github.com/as-com/varint-simd/blob/0f468783da8e181929b01b9c6e9f741c1fe09825/src/encode/mod.rs#L71
-    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
-        let x = self.0 as u64;
+        if x < 0b10000000 {
+            return ([x as u8, 0, 0, 0, 0, 0, 0, 0], 1);
+        }
+
+        // Adapted from VarInt-Simd encode
+        // https://github.com/as-com/varint-simd/blob/0f468783da8e181929b01b9c6e9f741c1fe09825/src/encode/mod.rs#L71
         let stage1 = (x & 0x000000000000007f)
             | ((x & 0x0000000000003f80) << 1)
             | ((x & 0x00000000001fc000) << 2)
@@ -69,9 +67,50 @@ impl Encode for VarInt {
         let msbmask = 0xffffffffffffffff >> (((8 - bytes_needed + 1) << 3) - 1);
 
         let merged = stage1 | (msbs & msbmask);
-        let bytes = merged.to_le_bytes();
 
-        w.write_all(unsafe { bytes.get_unchecked(..bytes_needed as usize) })?;
+        (merged.to_le_bytes(), bytes_needed as usize)
+    }
+}
+
+/// The size of the stack buffer used by `encode_slice` for VarInts and
+/// VarLongs.
+pub(crate) const BATCH_BUF_SIZE: usize = 512;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Error)]
+pub enum VarIntDecodeError {
+    #[error("incomplete VarInt decode")]
+    Incomplete,
+    #[error("VarInt is too large")]
+    TooLarge,
+}
+
+impl Encode for VarInt {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        let (bytes, len) = self.to_bytes();
+        w.write_all(&bytes[..len])?;
+
+        Ok(())
+    }
+
+    fn encode_slice(slice: &[Self], mut w: impl Write) -> anyhow::Result<()> {
+        // Encode into a buffer on the stack so that the writer is called once
+        // per chunk instead of once per VarInt.
+        let mut buf = [0; BATCH_BUF_SIZE];
+        let mut len = 0;
+
+        for n in slice {
+            if len + 8 > buf.len() {
+                w.write_all(&buf[..len])?;
+                len = 0;
+            }
+
+            let (bytes, n_len) = n.to_bytes();
+            // Copying all eight bytes is faster than copying a variable number.
+            buf[len..len + 8].copy_from_slice(&bytes);
+            len += n_len;
+        }
+
+        w.write_all(&buf[..len])?;
 
         Ok(())
     }
@@ -79,6 +118,31 @@ impl Encode for VarInt {
 
 impl Decode<'_> for VarInt {
     fn decode(r: &mut &[u8]) -> anyhow::Result<Self> {
+        // Most VarInts are a single byte.
+        if let Some(&byte) = r.first() {
+            if byte & 0b10000000 == 0 {
+                *r = &r[1..];
+                return Ok(VarInt(byte as i32));
+            }
+        }
+
+        // If the longest VarInt fits in the input, the bytes can be read
+        // without checking the length of the input for each one.
+        if let Some(bytes) = r.get(..Self::MAX_SIZE) {
+            let bytes: &[u8; Self::MAX_SIZE] = bytes.try_into().unwrap();
+
+            let mut val = 0;
+            for (i, &byte) in bytes.iter().enumerate() {
+                val |= (byte as i32 & 0b01111111) << (i * 7);
+                if byte & 0b10000000 == 0 {
+                    *r = &r[i + 1..];
+                    return Ok(VarInt(val));
+                }
+            }
+
+            bail!("VarInt is too large")
+        }
+
         let mut val = 0;
         for i in 0..Self::MAX_SIZE {
             let byte = r.read_u8()?;
@@ -145,4 +209,81 @@ mod tests {
             buf.clear();
         }
     }
+
+    /// Encodes one byte at a time, for comparison with the fast paths.
+    fn encode_slow(n: i32, buf: &mut Vec<u8>) {
+        let mut val = n as u32;
+        loop {
+            if val & !0b01111111 == 0 {
+                buf.push(val as u8);
+                return;
+            }
+            buf.push(val as u8 & 0b01111111 | 0b10000000);
+            val >>= 7;
+        }
+    }
+
+    /// Returns a random number with a random encoded size.
+    fn gen_sized(rng: &mut impl Rng) -> i32 {
+        rng.gen::<i32>() >> rng.gen_range(0..32)
+    }
+
+    #[test]
+    fn encode_matches_slow_path() {
+        let mut rng = thread_rng();
+        let mut expected = vec![];
+        let mut buf = vec![];
+
+        for _ in 0..1000 {
+            let nums: Vec<_> = (0..rng.gen_range(0..500))
+                .map(|_| gen_sized(&mut rng))
+                .chain([0, -1, 127, 128, i32::MIN, i32::MAX])
+                .collect();
+
+            expected.clear();
+            buf.clear();
+
+            for &n in &nums {
+                encode_slow(n, &mut expected);
+            }
+
+            for &n in &nums {
+                VarInt(n).encode(&mut buf).unwrap();
+            }
+
+            assert_eq!(buf, expected);
+
+            buf.clear();
+            let nums: Vec<_> = nums.into_iter().map(VarInt).collect();
+            VarInt::encode_slice(&nums, &mut buf).unwrap();
+
+            assert_eq!(buf, expected);
+        }
+    }
+
+    #[test]
+    fn decode_matches_slow_path() {
+        let mut rng = thread_rng();
+
+        for _ in 0..1_000_000 {
+            // Random bytes with mostly set continuation bits, with some slack
+            // after the VarInt some of the time.
+            let len = rng.gen_range(0..=VarInt::MAX_SIZE + 3);
+            let bytes: Vec<u8> = (0..len)
+                .map(|_| rng.gen::<u8>() | if rng.gen_bool(0.8) { 0x80 } else { 0 })
+                .collect();
+
+            let mut fast = bytes.as_slice();
+            let mut slow = bytes.as_slice();
+
+            match (VarInt::decode(&mut fast), VarInt::decode_partial(&mut slow)) {
+                (Ok(a), Ok(b)) => {
+                    assert_eq!(a.0, b, "{bytes:?}");
+                    assert_eq!(fast.len(), slow.len(), "{bytes:?}");
+                }
+                (Err(_), Err(_)) => {}
+                (a, b) => panic!("{bytes:?}: {a:?} != {b:?}"),
+            }
+        }
+    }
 }
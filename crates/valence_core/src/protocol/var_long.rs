@@ -3,6 +3,7 @@ use std::io::Write;
 use anyhow::bail;
 use byteorder::ReadBytesExt;
 
+use crate::protocol::var_int::BATCH_BUF_SIZE;
 use crate::protocol::{Decode, Encode};
 
 /// An `i64` encoded with variable length.
@@ -25,14 +26,51 @@ impl VarLong {
     }
 }
 
-impl Encode for VarLong {
+impl VarLong {
+    /// Encodes this VarLong into the first [`Self::written_size`] bytes of the
+    /// returned array. The rest of the array is unspecified.
+    #[inline]
+    fn to_bytes(self) -> ([u8; 16], usize) {
+        let x = self.0 as u64;
+
+        if x < 0b10000000 {
+            let mut bytes = [0; 16];
+            bytes[0] = x as u8;
+            return (bytes, 1);
+        }
+
+        // Numbers of up to eight bytes are spread out within a `u64`, like
+        // `VarInt`s are. This covers all but the largest and negative numbers.
+        if x < 1 << 56 {
+            let stage1 = (x & 0x000000000000007f)
+                | ((x & 0x0000000000003f80) << 1)
+                | ((x & 0x00000000001fc000) << 2)
+                | ((x & 0x000000000fe00000) << 3)
+                | ((x & 0x00000007f0000000) << 4)
+                | ((x & 0x000003f800000000) << 5)
+                | ((x & 0x0001fc0000000000) << 6)
+                | ((x & 0x00fe000000000000) << 7);
+
+            let bytes_needed = 8 - (stage1.leading_zeros() - 1) / 8;
+
+            // Set the MSBs of all but the last byte.
+            let msbmask = 0x0080808080808080 >> ((8 - bytes_needed) * 8);
+
+            let mut bytes = [0; 16];
+            bytes[..8].copy_from_slice(&(stage1 | msbmask).to_le_bytes());
+            return (bytes, bytes_needed as usize);
+        }
+
+        self.to_bytes_multi()
+    }
+
     // Adapted from VarInt-Simd encode
     // https://github.com/as-com/varint-simd/blob/0f468783da8e181929b01b9c6e9f741c1fe09825/src/encode/mod.rs#L71
     #[cfg(all(
         any(target_arch = "x86", target_arch = "x86_64"),
         not(target_os = "macos")
     ))]
-    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+    fn to_bytes_multi(self) -> ([u8; 16], usize) {
         #[cfg(target_arch = "x86")]
         use std::arch::x86::*;
         #[cfg(target_arch = "x86_64")]
@@ -72,32 +110,85 @@ impl Encode for VarLong {
         let merged = unsafe { _mm_or_si128(stage1, msbmask) };
         let bytes = unsafe { std::mem::transmute::<__m128i, [u8; 16]>(merged) };
 
-        w.write_all(unsafe { bytes.get_unchecked(..bytes_needed as usize) })?;
-
-        Ok(())
+        (bytes, bytes_needed as usize)
     }
 
     #[cfg(any(
         not(any(target_arch = "x86", target_arch = "x86_64")),
         target_os = "macos"
     ))]
-    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
-        use byteorder::WriteBytesExt;
-
+    fn to_bytes_multi(self) -> ([u8; 16], usize) {
+        let mut bytes = [0; 16];
         let mut val = self.0 as u64;
-        loop {
+
+        for (i, byte) in bytes.iter_mut().enumerate() {
             if val & 0b1111111111111111111111111111111111111111111111111111111110000000 == 0 {
-                w.write_u8(val as u8)?;
-                return Ok(());
+                *byte = val as u8;
+                return (bytes, i + 1);
             }
-            w.write_u8(val as u8 & 0b01111111 | 0b10000000)?;
+            *byte = val as u8 & 0b01111111 | 0b10000000;
             val >>= 7;
         }
+
+        unreachable!("VarLong is never longer than {} bytes", Self::MAX_SIZE)
+    }
+}
+
+impl Encode for VarLong {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        let (bytes, len) = self.to_bytes();
+        w.write_all(&bytes[..len])?;
+
+        Ok(())
+    }
+
+    fn encode_slice(slice: &[Self], mut w: impl Write) -> anyhow::Result<()> {
+        // See `VarInt::encode_slice`.
+        let mut buf = [0; BATCH_BUF_SIZE];
+        let mut len = 0;
+
+        for n in slice {
+            if len + 16 > buf.len() {
+                w.write_all(&buf[..len])?;
+                len = 0;
+            }
+
+            let (bytes, n_len) = n.to_bytes();
+            buf[len..len + 16].copy_from_slice(&bytes);
+            len += n_len;
+        }
+
+        w.write_all(&buf[..len])?;
+
+        Ok(())
     }
 }
 
 impl Decode<'_> for VarLong {
     fn decode(r: &mut &[u8]) -> anyhow::Result<Self> {
+        // See `VarInt::decode`.
+        if let Some(&byte) = r.first() {
+            if byte & 0b10000000 == 0 {
+                *r = &r[1..];
+                return Ok(VarLong(byte as i64));
+            }
+        }
+
+        if let Some(bytes) = r.get(..Self::MAX_SIZE) {
+            let bytes: &[u8; Self::MAX_SIZE] = bytes.try_into().unwrap();
+
+            let mut val = 0;
+            for (i, &byte) in bytes.iter().enumerate() {
+                val |= (byte as i64 & 0b01111111) << (i * 7);
+                if byte & 0b10000000 == 0 {
+                    *r = &r[i + 1..];
+                    return Ok(VarLong(val));
+                }
+            }
+
+            bail!("VarLong is too large")
+        }
+
         let mut val = 0;
         for i in 0..Self::MAX_SIZE {
             let byte = r.read_u8()?;
@@ -106,7 +197,7 @@ impl Decode<'_> for VarLong {
                 return Ok(VarLong(val));
             }
         }
-        bail!("VarInt is too large")
+        bail!("VarLong is too large")
     }
 }
 
@@ -135,4 +226,88 @@ mod tests {
             buf.clear();
         }
     }
+
+    /// Encodes one byte at a time, for comparison with the fast paths.
+    fn encode_slow(n: i64, buf: &mut Vec<u8>) {
+        let mut val = n as u64;
+        loop {
+            if val & !0b01111111 == 0 {
+                buf.push(val as u8);
+                return;
+            }
+            buf.push(val as u8 & 0b01111111 | 0b10000000);
+            val >>= 7;
+        }
+    }
+
+    /// Decodes one byte at a time, for comparison with the fast paths.
+    fn decode_slow(r: &mut &[u8]) -> Option<i64> {
+        let mut val = 0;
+        for i in 0..VarLong::MAX_SIZE {
+            let (&byte, rest) = r.split_first()?;
+            *r = rest;
+            val |= (byte as i64 & 0b01111111) << (i * 7);
+            if byte & 0b10000000 == 0 {
+                return Some(val);
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn encode_matches_slow_path() {
+        let mut rng = thread_rng();
+        let mut expected = vec![];
+        let mut buf = vec![];
+
+        for _ in 0..1000 {
+            let nums: Vec<_> = (0..rng.gen_range(0..500))
+                .map(|_| rng.gen::<i64>() >> rng.gen_range(0..64))
+                .chain([0, -1, 127, 128, i64::MIN, i64::MAX])
+                .collect();
+
+            expected.clear();
+            buf.clear();
+
+            for &n in &nums {
+                encode_slow(n, &mut expected);
+            }
+
+            for &n in &nums {
+                VarLong(n).encode(&mut buf).unwrap();
+            }
+
+            assert_eq!(buf, expected);
+
+            buf.clear();
+            let nums: Vec<_> = nums.into_iter().map(VarLong).collect();
+            VarLong::encode_slice(&nums, &mut buf).unwrap();
+
+            assert_eq!(buf, expected);
+        }
+    }
+
+    #[test]
+    fn decode_matches_slow_path() {
+        let mut rng = thread_rng();
+
+        for _ in 0..1_000_000 {
+            let len = rng.gen_range(0..=VarLong::MAX_SIZE + 3);
+            let bytes: Vec<u8> = (0..len)
+                .map(|_| rng.gen::<u8>() | if rng.gen_bool(0.9) { 0x80 } else { 0 })
+                .collect();
+
+            let mut fast = bytes.as_slice();
+            let mut slow = bytes.as_slice();
+
+            match (VarLong::decode(&mut fast), decode_slow(&mut slow)) {
+                (Ok(a), Some(b)) => {
+                    assert_eq!(a.0, b, "{bytes:?}");
+                    assert_eq!(fast.len(), slow.len(), "{bytes:?}");
+                }
+                (Err(_), None) => {}
+                (a, b) => panic!("{bytes:?}: {a:?} != {b:?}"),
+            }
+        }
+    }
 }
@@ -1,8 +1,9 @@
 //! Formatted text.
 
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::io::Write;
-use std::{fmt, ops};
+use std::{fmt, mem, ops};
 
 use anyhow::Context;
 use serde::de::Visitor;
@@ -11,8 +12,25 @@ use uuid::Uuid;
 use valence_nbt::Value;
 
 use crate::ident::Ident;
+use crate::item::ItemStack;
 use crate::protocol::{Decode, Encode};
 
+mod click_event;
+mod deserialize;
+mod gradient;
+mod hover_event;
+pub mod keybind;
+mod legacy_json;
+mod macros;
+mod markup;
+mod plain;
+mod resolve;
+
+pub use click_event::ClickEvent;
+pub use hover_event::HoverEvent;
+pub use markup::{MarkupError, MarkupErrorKind};
+pub use resolve::{NbtSource, ResolveContext};
+
 /// Represents formatted text in Minecraft's JSON text format.
 ///
 /// Text is used in various places such as chat, window titles,
@@ -84,7 +102,7 @@ impl<'de> Deserialize<'de> for Text {
 
             fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
                 let Some(mut res) = seq.next_element()? else {
-                    return Ok(Text::default())
+                    return Ok(Text::default());
                 };
 
                 while let Some(child) = seq.next_element::<Text>()? {
@@ -97,9 +115,9 @@ impl<'de> Deserialize<'de> for Text {
             fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
                 use de::value::MapAccessDeserializer;
 
-                Ok(Text(Box::new(TextInner::deserialize(
-                    MapAccessDeserializer::new(map),
-                )?)))
+                let map = serde_json::Map::deserialize(MapAccessDeserializer::new(map))?;
+
+                deserialize::text_from_map(map).map_err(de::Error::custom)
             }
         }
 
@@ -107,7 +125,7 @@ impl<'de> Deserialize<'de> for Text {
     }
 }
 
-#[derive(Clone, PartialEq, Default, Debug, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Default, Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct TextInner {
     #[serde(flatten)]
@@ -117,7 +135,7 @@ struct TextInner {
     color: Option<Color>,
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    font: Option<Cow<'static, str>>,
+    font: Option<Ident<String>>,
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     bold: Option<bool>,
@@ -145,9 +163,14 @@ struct TextInner {
 
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     extra: Vec<Text>,
+
+    /// Keys that were read but aren't known or couldn't be used, which are
+    /// written back as they were.
+    #[serde(flatten)]
+    other: BTreeMap<String, serde_json::Value>,
 }
 
-#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Debug, Serialize)]
 #[serde(untagged)]
 enum TextContent {
     Text {
@@ -164,6 +187,11 @@ enum TextContent {
         /// translation text. Ignored if `translate` is not present.
         #[serde(default, skip_serializing_if = "Vec::is_empty")]
         with: Vec<Text>,
+        /// Translation text used in place of the translation of `translate`
+        /// if the client doesn't have one, such as for keys added by resource
+        /// packs. Added in 1.19.4.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fallback: Option<Cow<'static, str>>,
     },
     /// Displays a score holder's current score in an objective.
     ScoreboardValue {
@@ -246,36 +274,6 @@ pub struct Color {
     pub b: u8,
 }
 
-#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
-#[serde(tag = "action", content = "value", rename_all = "snake_case")]
-enum ClickEvent {
-    OpenUrl(Cow<'static, str>),
-    /// Only usable by internal servers for security reasons.
-    OpenFile(Cow<'static, str>),
-    RunCommand(Cow<'static, str>),
-    SuggestCommand(Cow<'static, str>),
-    ChangePage(i32),
-    CopyToClipboard(Cow<'static, str>),
-}
-
-#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
-#[serde(tag = "action", content = "contents", rename_all = "snake_case")]
-#[allow(clippy::enum_variant_names)]
-enum HoverEvent {
-    ShowText(Text),
-    ShowItem {
-        id: Ident<String>,
-        count: Option<i32>,
-        // TODO: tag
-    },
-    ShowEntity {
-        name: Text,
-        #[serde(rename = "type")]
-        kind: Ident<String>,
-        id: Uuid,
-    },
-}
-
 #[allow(clippy::self_named_constructors)]
 impl Text {
     /// Constructs a new plain text object.
@@ -293,6 +291,25 @@ impl Text {
             content: TextContent::Translate {
                 translate: key.into(),
                 with: with.into(),
+                fallback: None,
+            },
+            ..Default::default()
+        }))
+    }
+
+    /// Like [`Text::translate`], but with translation text which the client
+    /// uses if it has no translation for the key. The fallback may have slots
+    /// for the `with` components just like a translation.
+    pub fn translate_with_fallback(
+        key: impl Into<Cow<'static, str>>,
+        fallback: impl Into<Cow<'static, str>>,
+        with: impl Into<Vec<Text>>,
+    ) -> Self {
+        Self(Box::new(TextInner {
+            content: TextContent::Translate {
+                translate: key.into(),
+                with: with.into(),
+                fallback: Some(fallback.into()),
             },
             ..Default::default()
         }))
@@ -329,9 +346,20 @@ impl Text {
     }
 
     /// Creates a text component for a keybind. The keybind should be a valid
-    /// [`keybind identifier`].
+    /// [`keybind identifier`], such as those in the [`keybind`] module. The
+    /// client shows the name of the key the player has bound to it.
     ///
     /// [`keybind identifier`]: https://minecraft.fandom.com/wiki/Controls#Configurable_controls
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valence_core::text::{keybind, Color, Text, TextFormat};
+    ///
+    /// let txt = "Press ".into_text() + Text::keybind(keybind::JUMP).color(Color::GOLD) + " to fly";
+    ///
+    /// assert_eq!(txt.to_string(), "Press keybind[key.jump] to fly");
+    /// ```
     pub fn keybind(keybind: impl Into<Cow<'static, str>>) -> Self {
         Self(Box::new(TextInner {
             content: TextContent::Keybind {
@@ -398,135 +426,145 @@ impl Text {
     /// Writes the string representation of this text object to the provided
     /// writer.
     pub fn write_string(&self, mut w: impl fmt::Write) -> fmt::Result {
-        fn write_string_inner(this: &Text, w: &mut impl fmt::Write) -> fmt::Result {
-            match &this.0.content {
-                TextContent::Text { text } => w.write_str(text.as_ref())?,
-                TextContent::Translate { translate, with } => {
-                    w.write_str(translate.as_ref())?;
-
-                    if !with.is_empty() {
-                        w.write_char('[')?;
-                        for (i, slot) in with.iter().enumerate() {
-                            if i > 0 {
-                                w.write_str(", ")?;
-                            }
-                            w.write_char(char::from_digit((i + 1) as u32, 10).unwrap_or('?'))?;
-                            w.write_char('=')?;
-                            write_string_inner(slot, w)?;
-                        }
-                        w.write_char(']')?;
-                    }
-                }
-                TextContent::ScoreboardValue { score } => {
-                    let ScoreboardValueContent {
-                        name,
-                        objective,
-                        value,
-                    } = score;
-
-                    write!(w, "scoreboard_value[name={name}, objective={objective}")?;
-
-                    if let Some(value) = value {
-                        if !value.is_empty() {
-                            w.write_str(", value=")?;
-                            w.write_str(value)?;
-                        }
-                    }
+        self.write_string_inner(&mut w)
+    }
 
-                    w.write_char(']')?;
-                }
-                TextContent::EntityNames {
-                    selector,
-                    separator,
-                } => {
-                    write!(w, "entity_names[selector={selector}")?;
-
-                    if let Some(separator) = separator {
-                        if !separator.is_empty() {
-                            w.write_str(", separator={separator}")?;
+    fn write_string_inner(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        self.write_content(w)?;
+
+        for child in &self.0.extra {
+            child.write_string_inner(w)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the string representation of the content of this text object,
+    /// without its children.
+    fn write_content(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        match &self.0.content {
+            TextContent::Text { text } => w.write_str(text.as_ref())?,
+            TextContent::Translate {
+                translate, with, ..
+            } => {
+                w.write_str(translate.as_ref())?;
+
+                if !with.is_empty() {
+                    w.write_char('[')?;
+                    for (i, slot) in with.iter().enumerate() {
+                        if i > 0 {
+                            w.write_str(", ")?;
                         }
+                        w.write_char(char::from_digit((i + 1) as u32, 10).unwrap_or('?'))?;
+                        w.write_char('=')?;
+                        slot.write_string_inner(w)?;
                     }
-
                     w.write_char(']')?;
                 }
-                TextContent::Keybind { keybind } => write!(w, "keybind[{keybind}]")?,
-                TextContent::BlockNbt {
-                    block,
-                    nbt,
-                    interpret,
-                    separator,
-                } => {
-                    write!(w, "block_nbt[nbt={nbt}")?;
-
-                    if let Some(interpret) = interpret {
-                        write!(w, ", interpret={interpret}")?;
+            }
+            TextContent::ScoreboardValue { score } => {
+                let ScoreboardValueContent {
+                    name,
+                    objective,
+                    value,
+                } = score;
+
+                write!(w, "scoreboard_value[name={name}, objective={objective}")?;
+
+                if let Some(value) = value {
+                    if !value.is_empty() {
+                        w.write_str(", value=")?;
+                        w.write_str(value)?;
                     }
+                }
 
-                    if let Some(separator) = separator {
-                        if !separator.is_empty() {
-                            write!(w, "separator={separator}")?;
-                        }
+                w.write_char(']')?;
+            }
+            TextContent::EntityNames {
+                selector,
+                separator,
+            } => {
+                write!(w, "entity_names[selector={selector}")?;
+
+                if let Some(separator) = separator {
+                    if !separator.is_empty() {
+                        write!(w, ", separator={separator}")?;
                     }
+                }
 
-                    write!(w, "block={block}")?;
+                w.write_char(']')?;
+            }
+            TextContent::Keybind { keybind } => write!(w, "keybind[{keybind}]")?,
+            TextContent::BlockNbt {
+                block,
+                nbt,
+                interpret,
+                separator,
+            } => {
+                write!(w, "block_nbt[nbt={nbt}")?;
 
-                    w.write_char(']')?;
+                if let Some(interpret) = interpret {
+                    write!(w, ", interpret={interpret}")?;
                 }
-                TextContent::EntityNbt {
-                    entity,
-                    nbt,
-                    interpret,
-                    separator,
-                } => {
-                    write!(w, "entity_nbt[nbt={nbt}")?;
-
-                    if let Some(interpret) = interpret {
-                        write!(w, ", interpret={interpret}")?;
-                    }
 
-                    if let Some(separator) = separator {
-                        if !separator.is_empty() {
-                            write!(w, "separator={separator}")?;
-                        }
+                if let Some(separator) = separator {
+                    if !separator.is_empty() {
+                        write!(w, ", separator={separator}")?;
                     }
+                }
 
-                    write!(w, ", entity={entity}")?;
+                write!(w, ", block={block}")?;
 
-                    w.write_char(']')?;
+                w.write_char(']')?;
+            }
+            TextContent::EntityNbt {
+                entity,
+                nbt,
+                interpret,
+                separator,
+            } => {
+                write!(w, "entity_nbt[nbt={nbt}")?;
+
+                if let Some(interpret) = interpret {
+                    write!(w, ", interpret={interpret}")?;
                 }
-                TextContent::StorageNbt {
-                    storage,
-                    nbt,
-                    interpret,
-                    separator,
-                } => {
-                    write!(w, "storage_nbt[nbt={nbt}")?;
-
-                    if let Some(interpret) = interpret {
-                        write!(w, ", interpret={interpret}")?;
-                    }
 
-                    if let Some(separator) = separator {
-                        if !separator.is_empty() {
-                            write!(w, "separator=")?;
-                            write_string_inner(separator, w)?;
-                        }
+                if let Some(separator) = separator {
+                    if !separator.is_empty() {
+                        write!(w, ", separator={separator}")?;
                     }
+                }
 
-                    write!(w, ", storage={storage}")?;
+                write!(w, ", entity={entity}")?;
 
-                    w.write_char(']')?;
-                }
+                w.write_char(']')?;
             }
+            TextContent::StorageNbt {
+                storage,
+                nbt,
+                interpret,
+                separator,
+            } => {
+                write!(w, "storage_nbt[nbt={nbt}")?;
 
-            for child in &this.0.extra {
-                write_string_inner(child, w)?;
-            }
+                if let Some(interpret) = interpret {
+                    write!(w, ", interpret={interpret}")?;
+                }
+
+                if let Some(separator) = separator {
+                    if !separator.is_empty() {
+                        write!(w, ", separator=")?;
+                        separator.write_string_inner(w)?;
+                    }
+                }
+
+                write!(w, ", storage={storage}")?;
 
-            Ok(())
+                w.write_char(']')?;
+            }
         }
 
-        write_string_inner(self, &mut w)
+        Ok(())
     }
 
     /// Returns `true` if the text contains no characters. Returns `false`
@@ -673,6 +711,384 @@ impl Text {
 
         result
     }
+
+    /// Parses a string with [legacy formatting codes](https://wiki.vg/Chat#Old_system)
+    /// into a [`Text`] object.
+    ///
+    /// Codes start with `§`, or with `alt_char` if provided (such as `&`).
+    /// Like in vanilla, color codes and `§r` reset the formatting, and format
+    /// codes add to it. Hex colors are written as `§x§r§r§g§g§b§b`. Unknown
+    /// codes starting with `§` are removed, and `alt_char` followed by
+    /// anything other than a code is kept as it is.
+    ///
+    /// The result has a child for each run of text with the same formatting,
+    /// or is the only run itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valence_core::text::{Color, Text, TextFormat};
+    ///
+    /// let txt = Text::from_legacy("&cRed &lbold&r & plain", Some('&'));
+    ///
+    /// assert_eq!(
+    ///     txt,
+    ///     Text::default() + "Red ".color(Color::RED) + "bold".color(Color::RED).bold() + " & plain"
+    /// );
+    /// ```
+    pub fn from_legacy(s: &str, alt_char: Option<char>) -> Self {
+        let chars: Vec<char> = s.chars().collect();
+
+        let mut runs = vec![];
+        let mut run = String::new();
+        let mut style = LegacyStyle::default();
+
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+
+            if c != '§' && Some(c) != alt_char {
+                run.push(c);
+                i += 1;
+                continue;
+            }
+
+            let (new_style, len) = match chars.get(i + 1).map(|c| c.to_ascii_lowercase()) {
+                Some('k') => (
+                    Some(LegacyStyle {
+                        obfuscated: true,
+                        ..style
+                    }),
+                    2,
+                ),
+                Some('l') => (
+                    Some(LegacyStyle {
+                        bold: true,
+                        ..style
+                    }),
+                    2,
+                ),
+                Some('m') => (
+                    Some(LegacyStyle {
+                        strikethrough: true,
+                        ..style
+                    }),
+                    2,
+                ),
+                Some('n') => (
+                    Some(LegacyStyle {
+                        underlined: true,
+                        ..style
+                    }),
+                    2,
+                ),
+                Some('o') => (
+                    Some(LegacyStyle {
+                        italic: true,
+                        ..style
+                    }),
+                    2,
+                ),
+                Some('r') => (Some(LegacyStyle::default()), 2),
+                Some('x') => (
+                    parse_legacy_hex(&chars[i + 2..], c).map(LegacyStyle::color),
+                    14,
+                ),
+                Some(code) => (Color::from_legacy_code(code).map(LegacyStyle::color), 2),
+                None => (None, 1),
+            };
+
+            match new_style {
+                Some(new_style) => {
+                    if new_style != style && !run.is_empty() {
+                        runs.push(style.apply(mem::take(&mut run)));
+                    }
+
+                    style = new_style;
+                    i += len;
+                }
+                // Vanilla doesn't show unknown codes.
+                None if c == '§' => i += 2,
+                None => {
+                    run.push(c);
+                    i += 1;
+                }
+            }
+        }
+
+        if !run.is_empty() {
+            runs.push(style.apply(run));
+        }
+
+        if runs.len() == 1 {
+            return runs.pop().unwrap();
+        }
+
+        let mut txt = Text::default();
+        txt.0.extra = runs;
+        txt
+    }
+
+    /// Converts the [`Text`] object to a string with [legacy formatting codes](https://wiki.vg/Chat#Old_system),
+    /// the reverse of [`Text::from_legacy`].
+    ///
+    /// Unlike [`Text::to_legacy_lossy`], colors that aren't on the legacy
+    /// color list are written as hex colors (`§x§r§r§g§g§b§b`), and children
+    /// only inherit the formatting of their parents, not of their previous
+    /// siblings. Codes are only written where the formatting of the text
+    /// changes, using `§r` or a color code to remove formatting.
+    ///
+    /// Only text content is written. `§` in the text itself is not escaped,
+    /// since legacy formatting has no way to do so.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valence_core::text::{Color, Text, TextFormat};
+    ///
+    /// let txt = "Hello ".color(Color::RED) + "world".bold() + "!";
+    ///
+    /// assert_eq!(txt.to_legacy_string(), "§cHello §lworld§c!");
+    /// assert_eq!(
+    ///     Text::from_legacy(&txt.to_legacy_string(), None).to_legacy_string(),
+    ///     "§cHello §lworld§c!"
+    /// );
+    /// ```
+    pub fn to_legacy_string(&self) -> String {
+        fn to_legacy_inner(
+            this: &Text,
+            parent: LegacyStyle,
+            written: &mut LegacyStyle,
+            result: &mut String,
+        ) {
+            let style = LegacyStyle::of(this, parent);
+
+            if let TextContent::Text { text } = &this.0.content {
+                if !text.is_empty() {
+                    style.write_codes(*written, result);
+                    *written = style;
+                    result.push_str(text);
+                }
+            }
+
+            for child in &this.0.extra {
+                to_legacy_inner(child, style, written, result);
+            }
+        }
+
+        let mut result = String::new();
+        to_legacy_inner(
+            self,
+            LegacyStyle::default(),
+            &mut LegacyStyle::default(),
+            &mut result,
+        );
+
+        result
+    }
+
+    /// Converts the [`Text`] object to a string with [ANSI escape codes](https://en.wikipedia.org/wiki/ANSI_escape_code#SGR)
+    /// for printing to a terminal, such as in server logs.
+    ///
+    /// Colors on the legacy color list are written as the closest of the 16
+    /// standard terminal colors, and other colors as 24-bit colors. Bold,
+    /// italic, underlined and strikethrough text is written with the matching
+    /// codes. Obfuscated text is written as it is, and the arguments of
+    /// translations are written in the style of the translation.
+    ///
+    /// If `colors` is `false`, the text is written without any escape codes,
+    /// like its [`Display`](fmt::Display) implementation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valence_core::text::{Color, Text, TextFormat};
+    ///
+    /// let txt = "Hello ".color(Color::RED) + "world".bold() + "!";
+    ///
+    /// assert_eq!(
+    ///     txt.to_ansi(true),
+    ///     "\x1b[0;91mHello \x1b[0;1;91mworld\x1b[0;91m!\x1b[0m"
+    /// );
+    /// assert_eq!(txt.to_ansi(false), "Hello world!");
+    /// ```
+    pub fn to_ansi(&self, colors: bool) -> String {
+        fn to_ansi_inner(
+            this: &Text,
+            parent: LegacyStyle,
+            written: &mut LegacyStyle,
+            result: &mut String,
+        ) {
+            let style = LegacyStyle::of(this, parent);
+            let start = result.len();
+
+            this.write_content(result).unwrap();
+
+            // Codes are written before the content once we know it isn't empty, so
+            // that the style of a parent is restored after its children.
+            if result.len() > start && style != *written {
+                result.insert_str(start, &style.ansi_codes());
+                *written = style;
+            }
+
+            for child in &this.0.extra {
+                to_ansi_inner(child, style, written, result);
+            }
+        }
+
+        if !colors {
+            return self.to_string();
+        }
+
+        let mut result = String::new();
+        let mut written = LegacyStyle::default();
+        to_ansi_inner(self, LegacyStyle::default(), &mut written, &mut result);
+
+        if written != LegacyStyle::default() {
+            result.push_str("\x1b[0m");
+        }
+
+        result
+    }
+}
+
+/// The formatting that can be represented with legacy formatting codes.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+struct LegacyStyle {
+    color: Option<Color>,
+    obfuscated: bool,
+    bold: bool,
+    strikethrough: bool,
+    underlined: bool,
+    italic: bool,
+}
+
+impl LegacyStyle {
+    fn color(color: Color) -> Self {
+        Self {
+            color: Some(color),
+            ..Default::default()
+        }
+    }
+
+    /// The formatting of `txt` with the formatting it inherits from its
+    /// parent.
+    fn of(txt: &Text, parent: Self) -> Self {
+        Self {
+            color: txt.0.color.or(parent.color),
+            obfuscated: txt.0.obfuscated.unwrap_or(parent.obfuscated),
+            bold: txt.0.bold.unwrap_or(parent.bold),
+            strikethrough: txt.0.strikethrough.unwrap_or(parent.strikethrough),
+            underlined: txt.0.underlined.unwrap_or(parent.underlined),
+            italic: txt.0.italic.unwrap_or(parent.italic),
+        }
+    }
+
+    /// The format codes of the formatting.
+    fn flags(self) -> [(bool, char); 5] {
+        [
+            (self.obfuscated, 'k'),
+            (self.bold, 'l'),
+            (self.strikethrough, 'm'),
+            (self.underlined, 'n'),
+            (self.italic, 'o'),
+        ]
+    }
+
+    fn apply(self, text: String) -> Text {
+        let mut txt = Text::text(text);
+
+        txt.0.color = self.color;
+
+        for (enabled, flag) in [
+            (self.obfuscated, &mut txt.0.obfuscated),
+            (self.bold, &mut txt.0.bold),
+            (self.strikethrough, &mut txt.0.strikethrough),
+            (self.underlined, &mut txt.0.underlined),
+            (self.italic, &mut txt.0.italic),
+        ] {
+            if enabled {
+                *flag = Some(true);
+            }
+        }
+
+        txt
+    }
+
+    /// The ANSI escape code that resets the terminal style and sets it to this.
+    fn ansi_codes(self) -> String {
+        let mut codes = String::from("\x1b[0");
+
+        for (enabled, code) in [
+            (self.bold, "1"),
+            (self.italic, "3"),
+            (self.underlined, "4"),
+            (self.strikethrough, "9"),
+        ] {
+            if enabled {
+                codes.push(';');
+                codes.push_str(code);
+            }
+        }
+
+        if let Some(color) = self.color {
+            codes.push(';');
+            color.write_ansi(&mut codes);
+        }
+
+        codes.push('m');
+        codes
+    }
+
+    /// Writes the codes needed to change the formatting from `prev` to this.
+    fn write_codes(self, prev: Self, result: &mut String) {
+        if self == prev {
+            return;
+        }
+
+        let only_adds_flags = self.color == prev.color
+            && self
+                .flags()
+                .iter()
+                .zip(prev.flags())
+                .all(|(&(enabled, _), (was_enabled, _))| enabled || !was_enabled);
+
+        let prev_flags = if only_adds_flags {
+            prev.flags()
+        } else {
+            // Color codes and resets remove all formatting.
+            match self.color {
+                Some(color) => color.write_legacy(result),
+                None => result.push_str("§r"),
+            }
+
+            LegacyStyle::default().flags()
+        };
+
+        for ((enabled, code), (was_enabled, _)) in self.flags().into_iter().zip(prev_flags) {
+            if enabled && !was_enabled {
+                result.push('§');
+                result.push(code);
+            }
+        }
+    }
+}
+
+/// Parses the hex digits of a `§x§r§r§g§g§b§b` hex color after the `§x`. Each
+/// digit must be preceded by `§` or `code_char`.
+fn parse_legacy_hex(chars: &[char], code_char: char) -> Option<Color> {
+    let mut rgb = 0;
+
+    for pair in chars.get(..12)?.chunks(2) {
+        if pair[0] != '§' && pair[0] != code_char {
+            return None;
+        }
+
+        rgb = rgb << 4 | pair[1].to_digit(16)?;
+    }
+
+    Some(Color::new((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
 }
 
 /// Provides the methods necessary for working with [`Text`] objects.
@@ -698,7 +1114,9 @@ pub trait TextFormat: Into<Text> {
         t
     }
 
-    fn font(self, font: impl Into<Cow<'static, str>>) -> Text {
+    /// Sets the font of the text, such as `minecraft:uniform` or a font from a
+    /// resource pack. Children use the same font unless they set their own.
+    fn font(self, font: impl Into<Ident<String>>) -> Text {
         let mut t = self.into();
         t.0.font = Some(font.into());
         t
@@ -812,6 +1230,12 @@ pub trait TextFormat: Into<Text> {
         t
     }
 
+    fn on_click(self, event: ClickEvent) -> Text {
+        let mut t = self.into();
+        t.0.click_event = Some(event);
+        t
+    }
+
     fn on_click_open_url(self, url: impl Into<Cow<'static, str>>) -> Text {
         let mut t = self.into();
         t.0.click_event = Some(ClickEvent::OpenUrl(url.into()));
@@ -848,18 +1272,70 @@ pub trait TextFormat: Into<Text> {
         t
     }
 
+    fn on_hover(self, event: HoverEvent) -> Text {
+        let mut t = self.into();
+        t.0.hover_event = Some(event);
+        t
+    }
+
     fn on_hover_show_text(self, text: impl Into<Text>) -> Text {
         let mut t = self.into();
         t.0.hover_event = Some(HoverEvent::ShowText(text.into()));
         t
     }
 
+    fn on_hover_show_item(self, stack: &ItemStack) -> Text {
+        let mut t = self.into();
+        t.0.hover_event = Some(HoverEvent::show_item(stack));
+        t
+    }
+
+    fn on_hover_show_entity(self, kind: Ident<String>, id: Uuid, name: Option<Text>) -> Text {
+        let mut t = self.into();
+        t.0.hover_event = Some(HoverEvent::show_entity(kind, id, name));
+        t
+    }
+
     fn clear_hover_event(self) -> Text {
         let mut t = self.into();
         t.0.hover_event = None;
         t
     }
 
+    /// Colors the characters of the text with a gradient through `colors`,
+    /// keeping the rest of its formatting. Each character becomes a child
+    /// with its own color, except in children that have a color already.
+    /// The text is left as it is if `colors` is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valence_core::text::{Color, TextFormat};
+    ///
+    /// let title = "Valence".bold().gradient(&[Color::GOLD, Color::RED]);
+    ///
+    /// assert_eq!(title.to_plain(None), "Valence");
+    /// ```
+    fn gradient(self, colors: &[Color]) -> Text {
+        if colors.is_empty() {
+            return self.into();
+        }
+
+        gradient::paint(self.into(), |idx, len| {
+            gradient::gradient_color(colors, idx, len)
+        })
+    }
+
+    /// Colors the characters of the text with the colors of the rainbow, like
+    /// [`TextFormat::gradient`]. The hue goes around once over the text,
+    /// starting at `offset` (a fraction of the way around). `saturation` and
+    /// `value` are between 0 and 1, where 1 gives the brightest colors.
+    fn rainbow(self, saturation: f32, value: f32, offset: f32) -> Text {
+        gradient::paint(self.into(), |idx, len| {
+            gradient::rainbow_color(idx, len, saturation, value, offset)
+        })
+    }
+
     fn add_child(self, text: impl Into<Text>) -> Text {
         let mut t = self.into();
         t.0.extra.push(text.into());
@@ -1016,7 +1492,71 @@ impl Color {
         Self { r, g, b }
     }
 
-    // Returns the closest legacy color
+    /// The colors of the legacy color codes `0` to `f`.
+    const LEGACY: [Self; 16] = [
+        Self::BLACK,
+        Self::DARK_BLUE,
+        Self::DARK_GREEN,
+        Self::DARK_AQUA,
+        Self::DARK_RED,
+        Self::DARK_PURPLE,
+        Self::GOLD,
+        Self::GRAY,
+        Self::DARK_GRAY,
+        Self::BLUE,
+        Self::GREEN,
+        Self::AQUA,
+        Self::RED,
+        Self::LIGHT_PURPLE,
+        Self::YELLOW,
+        Self::WHITE,
+    ];
+
+    /// Returns the color of a legacy color code, ignoring case.
+    fn from_legacy_code(code: char) -> Option<Self> {
+        Some(Self::LEGACY[code.to_digit(16)? as usize])
+    }
+
+    /// Writes the legacy color code of this color, or its hex color if it
+    /// isn't on the legacy color list.
+    fn write_legacy(self, result: &mut String) {
+        match Self::LEGACY.iter().position(|&c| c == self) {
+            Some(code) => {
+                result.push('§');
+                result.push(char::from_digit(code as u32, 16).unwrap());
+            }
+            None => {
+                result.push_str("§x");
+
+                for digit in format!("{:02x}{:02x}{:02x}", self.r, self.g, self.b).chars() {
+                    result.push('§');
+                    result.push(digit);
+                }
+            }
+        }
+    }
+
+    /// Writes the ANSI foreground color code of this color, using the standard
+    /// terminal colors for the legacy colors.
+    fn write_ansi(self, result: &mut String) {
+        // The standard terminal colors in the order of the legacy color codes.
+        const ANSI: [u8; 16] = [
+            30, 34, 32, 36, 31, 35, 33, 37, 90, 94, 92, 96, 91, 95, 93, 97,
+        ];
+
+        match Self::LEGACY.iter().position(|&c| c == self) {
+            Some(code) => result.push_str(&ANSI[code].to_string()),
+            None => result.push_str(&format!("38;2;{};{};{}", self.r, self.g, self.b)),
+        }
+    }
+
+    /// Returns the closest of the 16 legacy colors, which are the colors with
+    /// names.
+    ///
+    /// The distance between two colors is the squared Euclidean distance
+    /// between their red, green and blue components, which is
+    /// `(r1 - r2)² + (g1 - g2)² + (b1 - b2)²`. If two legacy colors are equally
+    /// close, the one whose name comes first alphabetically is returned.
     pub fn to_legacy(self) -> Self {
         [
             Self::AQUA,
@@ -1171,6 +1711,29 @@ mod tests {
         assert_eq!(txt, deserialized);
     }
 
+    #[test]
+    fn translate_with_fallback() {
+        let txt = Text::translate_with_fallback(
+            "myserver.welcome",
+            "Welcome to %s, %s!",
+            ["the server".into(), "Steve".bold()],
+        );
+        let serialized = serde_json::to_string(&txt).unwrap();
+        let deserialized: Text = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(
+            serialized,
+            r#"{"translate":"myserver.welcome","with":[{"text":"the server"},{"text":"Steve","bold":true}],"fallback":"Welcome to %s, %s!"}"#
+        );
+        assert_eq!(txt, deserialized);
+        assert_eq!(txt.to_plain(None), "Welcome to the server, Steve!");
+
+        // The fallback is only written if there is one.
+        assert_eq!(
+            serde_json::to_string(&Text::translate("key", [])).unwrap(),
+            r#"{"translate":"key"}"#
+        );
+    }
+
     #[test]
     fn score() {
         let txt = Text::score("foo", "bar", Some(Cow::from("baz")));
@@ -1261,4 +1824,265 @@ mod tests {
              text\n§r§1Not formatted blue text"
         );
     }
+
+    #[test]
+    fn text_legacy_round_trip() {
+        for (i, &color) in Color::LEGACY.iter().enumerate() {
+            let code = char::from_digit(i as u32, 16).unwrap();
+            let legacy = format!("§{code}text");
+
+            assert_eq!(Text::from_legacy(&legacy, None), "text".color(color));
+            assert_eq!(Text::from_legacy(&legacy, None).to_legacy_string(), legacy);
+            assert_eq!(
+                Text::from_legacy(&legacy.to_uppercase(), None),
+                "TEXT".color(color)
+            );
+        }
+
+        let formats = [
+            ('k', "text".obfuscated()),
+            ('l', "text".bold()),
+            ('m', "text".strikethrough()),
+            ('n', "text".underlined()),
+            ('o', "text".italic()),
+        ];
+
+        for (code, text) in formats {
+            let legacy = format!("§{code}text");
+
+            assert_eq!(Text::from_legacy(&legacy, None), text);
+            assert_eq!(text.to_legacy_string(), legacy);
+        }
+
+        let texts = [
+            "Hello ".color(Color::RED) + "world".bold() + "!",
+            "a".color(Color::new(0x12, 0xab, 0xef)).italic() + "b".not_italic() + "c",
+            "plain".into_text() + "bold".bold().underlined() + "less".underlined(),
+            "a & b".into_text(),
+            Text::default(),
+        ];
+
+        for text in texts {
+            let legacy = text.to_legacy_string();
+            let parsed = Text::from_legacy(&legacy, None);
+
+            assert_eq!(parsed.to_legacy_string(), legacy);
+            assert_eq!(Text::from_legacy(&parsed.to_legacy_string(), None), parsed);
+        }
+    }
+
+    #[test]
+    fn text_from_legacy() {
+        assert_eq!(
+            Text::from_legacy("§cHello §lworld§r!", None),
+            Text::default() + "Hello ".color(Color::RED) + "world".color(Color::RED).bold() + "!"
+        );
+
+        // Color codes reset the formatting.
+        assert_eq!(
+            Text::from_legacy("§l§obold§9blue", None),
+            Text::default() + "bold".bold().italic() + "blue".color(Color::BLUE)
+        );
+
+        // Unknown codes are removed, but not with the alternate code character.
+        assert_eq!(Text::from_legacy("§zab§", None), "ab".into_text());
+        assert_eq!(
+            Text::from_legacy("&aA & B&z&", Some('&')),
+            "A & B&z&".color(Color::GREEN)
+        );
+
+        assert_eq!(
+            Text::from_legacy("§x§1§2§A§b§C§dhex", None),
+            "hex".color(Color::new(0x12, 0xab, 0xcd))
+        );
+        assert_eq!(
+            Text::from_legacy("&x&f&f&5&5&5&5red", Some('&')),
+            "red".color(Color::RED)
+        );
+        // Incomplete hex colors are unknown codes.
+        assert_eq!(
+            Text::from_legacy("§x§1§2hex", None),
+            "hex".color(Color::DARK_GREEN)
+        );
+    }
+
+    #[test]
+    fn text_to_legacy_string() {
+        let text = ("Hello ".color(Color::RED) + "world".bold()) + "!";
+        assert_eq!(text.to_legacy_string(), "§cHello §lworld§c!");
+
+        // Siblings don't inherit formatting from each other.
+        let text = "Hello ".into_text() + "world".color(Color::RED).bold() + "!";
+        assert_eq!(text.to_legacy_string(), "Hello §c§lworld§r!");
+
+        assert_eq!(
+            "hex".color(Color::new(0x12, 0xab, 0xcd)).to_legacy_string(),
+            "§x§1§2§a§b§c§dhex"
+        );
+        assert_eq!(
+            "red".color(Color::new(0xff, 0x55, 0x55)).to_legacy_string(),
+            "§cred"
+        );
+        assert_eq!(
+            ("bold".bold() + "both".italic() + "plain".not_bold()).to_legacy_string(),
+            "§lbold§oboth§rplain"
+        );
+        assert_eq!(
+            Text::translate(translation_key::CHAT_TYPE_TEXT, []).to_legacy_string(),
+            ""
+        );
+    }
+
+    #[test]
+    fn text_to_ansi() {
+        // A red parent with a green child resets back to red.
+        let text = "red ".color(Color::RED) + "green ".color(Color::GREEN) + "red";
+        assert_eq!(
+            text.to_ansi(true),
+            "\x1b[0;91mred \x1b[0;92mgreen \x1b[0;91mred\x1b[0m"
+        );
+
+        let text = "plain ".into_text()
+            + ("styled ".bold().underlined().italic()
+                + "hex".color(Color::new(0x12, 0xab, 0xcd)).not_underlined())
+            + " strike".strikethrough().obfuscated();
+        assert_eq!(
+            text.to_ansi(true),
+            "plain \x1b[0;1;3;4mstyled \x1b[0;1;3;38;2;18;171;205mhex\x1b[0;9m strike\x1b[0m"
+        );
+
+        let text = Text::translate(
+            translation_key::CHAT_TYPE_TEXT,
+            ["Notch".color(Color::GOLD), "hi".into()],
+        )
+        .color(Color::GRAY);
+        assert_eq!(
+            text.to_ansi(true),
+            "\x1b[0;37mchat.type.text[1=Notch, 2=hi]\x1b[0m"
+        );
+
+        // Empty children don't change the style.
+        let text = "a".into_text() + "".color(Color::RED) + "b";
+        assert_eq!(text.to_ansi(true), "ab");
+        assert_eq!(text.to_ansi(false), "ab");
+    }
+
+    #[test]
+    fn tellraw_components() {
+        let parse = |json: &str| serde_json::from_str::<Text>(json).unwrap();
+
+        assert_eq!(
+            parse(
+                r#"["",{"text":"Kills: "},{"score":{"name":"@p","objective":"kills"},"color":"gold"}]"#
+            ),
+            Text::text("") + "Kills: " + Text::score("@p", "kills", None).color(Color::GOLD)
+        );
+        assert_eq!(
+            parse(r#"{"score":{"name":"*","objective":"deaths","value":"5"}}"#),
+            Text::score("*", "deaths", Some("5".into()))
+        );
+        assert_eq!(
+            parse(r#"{"selector":"@e[type=pig]","separator":{"text":" | ","color":"gray"}}"#),
+            Text::selector("@e[type=pig]", Some(" | ".color(Color::GRAY)))
+        );
+        assert_eq!(
+            parse(r#"{"selector":"@a","extra":[{"text":" won!"}]}"#),
+            Text::selector("@a", None) + " won!"
+        );
+        assert_eq!(
+            parse(r#"{"nbt":"Items[0].id","block":"~ ~-1 ~"}"#),
+            Text::block_nbt("~ ~-1 ~", "Items[0].id", None, None)
+        );
+        assert_eq!(
+            parse(
+                r#"{"nbt":"CustomName","entity":"@e[type=armor_stand,limit=1]","interpret":true}"#
+            ),
+            Text::entity_nbt(
+                "@e[type=armor_stand,limit=1]",
+                "CustomName",
+                Some(true),
+                None
+            )
+        );
+        assert_eq!(
+            parse(r#"{"nbt":"messages[]","storage":"example:chat","separator":"\n"}"#),
+            Text::storage_nbt(
+                ident!("example:chat"),
+                "messages[]",
+                None,
+                Some("\n".into())
+            )
+        );
+
+        for json in [
+            r#"{"score":{"name":"@p","objective":"kills"},"bold":true}"#,
+            r#"{"selector":"@e[type=pig]","separator":{"text":" | "}}"#,
+            r#"{"nbt":"Items[0].id","block":"~ ~-1 ~","interpret":false}"#,
+            r#"{"nbt":"Pos","entity":"@s","extra":["!"]}"#,
+            r#"{"nbt":"messages[]","storage":"example:chat","separator":"\n"}"#,
+        ] {
+            let txt = parse(json);
+            assert_eq!(parse(&serde_json::to_string(&txt).unwrap()), txt, "{json}");
+        }
+    }
+
+    #[test]
+    fn keybind_components() {
+        let txt = "Press ".into_text() + Text::keybind(keybind::JUMP).bold() + " to fly";
+
+        // Written by a vanilla server for the same component.
+        let vanilla =
+            r#"{"extra":[{"bold":true,"keybind":"key.jump"},{"text":" to fly"}],"text":"Press "}"#;
+
+        assert_eq!(serde_json::from_str::<Text>(vanilla).unwrap(), txt);
+        assert_eq!(
+            serde_json::to_value(&txt).unwrap(),
+            serde_json::from_str::<serde_json::Value>(vanilla).unwrap()
+        );
+
+        assert_eq!(
+            serde_json::to_string(&Text::keybind(keybind::HOTBAR_1)).unwrap(),
+            r#"{"keybind":"key.hotbar.1"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Text>(
+                r#"{"keybind":"key.sneak","color":"gold","italic":false}"#
+            )
+            .unwrap(),
+            Text::keybind(keybind::SNEAK)
+                .color(Color::GOLD)
+                .not_italic()
+        );
+    }
+
+    #[test]
+    fn text_font() {
+        let txt = "Rank ".font(ident!("uniform"))
+            + "✦".font(ident!("mypack:badges")).color(Color::GOLD)
+            + " Notch";
+
+        let json = serde_json::to_string(&txt).unwrap();
+
+        assert_eq!(
+            json,
+            r##"{"text":"Rank ","font":"minecraft:uniform","extra":[{"text":"✦","color":"#ffaa00","font":"mypack:badges"},{"text":" Notch"}]}"##
+        );
+        assert_eq!(serde_json::from_str::<Text>(&json).unwrap(), txt);
+
+        // Fonts are part of equality, and vanilla accepts fonts without a namespace.
+        assert_ne!(txt, txt.clone().clear_font());
+        assert_eq!(
+            serde_json::from_str::<Text>(r#"{"text":"a","font":"uniform"}"#).unwrap(),
+            "a".font(ident!("minecraft:uniform"))
+        );
+        // Invalid fonts are ignored, but written again.
+        let json = r#"{"text":"a","font":"Not Valid"}"#;
+        let invalid = serde_json::from_str::<Text>(json).unwrap();
+
+        assert_eq!(invalid.0.font, None);
+        assert_eq!(serde_json::to_string(&invalid).unwrap(), json);
+
+        // Legacy formatting has no fonts.
+        assert_eq!(txt.to_legacy_string(), "Rank §6✦§r Notch");
+    }
 }
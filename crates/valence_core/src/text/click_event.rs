@@ -0,0 +1,165 @@
+use std::borrow::Cow;
+
+use serde::de::Error as _;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// What happens when clicking on a [`Text`](super::Text) object.
+///
+/// Click events are written as an `action` and a string `value`, like
+/// vanilla does, including the page number of
+/// [`ChangePage`](Self::ChangePage).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ClickEvent {
+    /// Opens a URL in the browser after asking the player.
+    OpenUrl(Cow<'static, str>),
+    /// Opens a file on the computer of the player. Only used by the client
+    /// itself (such as for screenshots) and ignored when sent by servers.
+    OpenFile(Cow<'static, str>),
+    /// Sends a message or command as the player.
+    RunCommand(Cow<'static, str>),
+    /// Replaces the contents of the chat box of the player.
+    SuggestCommand(Cow<'static, str>),
+    /// Turns to a page of the open book. Pages start at 1, and pages outside
+    /// of the book are clamped to its first or last page by the client.
+    ChangePage(i32),
+    /// Copies the text to the clipboard of the player.
+    CopyToClipboard(Cow<'static, str>),
+    /// An action that isn't known to Valence, with its value. Kept so that
+    /// text from other sources can be read and written again.
+    Other(String, String),
+}
+
+impl ClickEvent {
+    /// The `action` of the click event.
+    pub fn action(&self) -> &str {
+        match self {
+            ClickEvent::OpenUrl(_) => "open_url",
+            ClickEvent::OpenFile(_) => "open_file",
+            ClickEvent::RunCommand(_) => "run_command",
+            ClickEvent::SuggestCommand(_) => "suggest_command",
+            ClickEvent::ChangePage(_) => "change_page",
+            ClickEvent::CopyToClipboard(_) => "copy_to_clipboard",
+            ClickEvent::Other(action, _) => action,
+        }
+    }
+}
+
+impl Serialize for ClickEvent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+
+        map.serialize_entry("action", self.action())?;
+
+        match self {
+            ClickEvent::OpenUrl(value)
+            | ClickEvent::OpenFile(value)
+            | ClickEvent::RunCommand(value)
+            | ClickEvent::SuggestCommand(value)
+            | ClickEvent::CopyToClipboard(value) => map.serialize_entry("value", value)?,
+            ClickEvent::ChangePage(page) => map.serialize_entry("value", &page.to_string())?,
+            ClickEvent::Other(_, value) => map.serialize_entry("value", value)?,
+        }
+
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for ClickEvent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct RawClickEvent {
+            action: String,
+            value: serde_json::Value,
+        }
+
+        let RawClickEvent { action, value } = RawClickEvent::deserialize(deserializer)?;
+
+        // Some third-party JSON writes numbers and booleans without quotes.
+        let value = match value {
+            serde_json::Value::String(value) => value,
+            serde_json::Value::Number(n) => n.to_string(),
+            serde_json::Value::Bool(b) => b.to_string(),
+            _ => return Err(D::Error::custom("click event value must be a string")),
+        };
+
+        Ok(match action.as_str() {
+            "open_url" => ClickEvent::OpenUrl(value.into()),
+            "open_file" => ClickEvent::OpenFile(value.into()),
+            "run_command" => ClickEvent::RunCommand(value.into()),
+            "suggest_command" => ClickEvent::SuggestCommand(value.into()),
+            "copy_to_clipboard" => ClickEvent::CopyToClipboard(value.into()),
+            // Like vanilla, pages that aren't numbers do nothing, so they are kept as
+            // they are.
+            "change_page" => match value.parse() {
+                Ok(page) => ClickEvent::ChangePage(page),
+                Err(_) => ClickEvent::Other(action, value),
+            },
+            _ => ClickEvent::Other(action, value),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn click_event_round_trip() {
+        for (event, json) in [
+            (
+                ClickEvent::OpenUrl("https://valencemc.rs".into()),
+                json!({ "action": "open_url", "value": "https://valencemc.rs" }),
+            ),
+            (
+                ClickEvent::OpenFile("screenshots/a.png".into()),
+                json!({ "action": "open_file", "value": "screenshots/a.png" }),
+            ),
+            (
+                ClickEvent::RunCommand("/spawn".into()),
+                json!({ "action": "run_command", "value": "/spawn" }),
+            ),
+            (
+                ClickEvent::SuggestCommand("/msg ".into()),
+                json!({ "action": "suggest_command", "value": "/msg " }),
+            ),
+            (
+                ClickEvent::ChangePage(3),
+                json!({ "action": "change_page", "value": "3" }),
+            ),
+            (
+                ClickEvent::CopyToClipboard("copied".into()),
+                json!({ "action": "copy_to_clipboard", "value": "copied" }),
+            ),
+            (
+                ClickEvent::Other("open_dialog".into(), "dialog:1".into()),
+                json!({ "action": "open_dialog", "value": "dialog:1" }),
+            ),
+        ] {
+            assert_eq!(serde_json::to_value(&event).unwrap(), json);
+            assert_eq!(ClickEvent::deserialize(json).unwrap(), event);
+        }
+    }
+
+    #[test]
+    fn click_event_change_page_bounds() {
+        let page =
+            |value| ClickEvent::deserialize(json!({ "action": "change_page", "value": value }));
+
+        assert_eq!(page(json!(7)).unwrap(), ClickEvent::ChangePage(7));
+        assert_eq!(page(json!("-1")).unwrap(), ClickEvent::ChangePage(-1));
+
+        // Pages that don't fit are kept as they are.
+        for value in ["99999999999", "next", " 2", ""] {
+            assert_eq!(
+                page(json!(value)).unwrap(),
+                ClickEvent::Other("change_page".into(), value.into())
+            );
+        }
+
+        assert!(page(json!([1])).is_err());
+        assert!(ClickEvent::deserialize(json!({ "action": "run_command" })).is_err());
+    }
+}
@@ -0,0 +1,292 @@
+//! Reading text objects leniently, like the vanilla JSON reader.
+
+use std::borrow::Cow;
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use super::{
+    color_from_str, ClickEvent, HoverEvent, ScoreboardValueContent, Text, TextContent, TextInner,
+};
+use crate::ident::Ident;
+
+/// Reads a text object from a JSON object.
+///
+/// Unlike a derived implementation, this accepts anything the vanilla reader
+/// does and some things it doesn't but other software writes. Objects without
+/// any content are empty text, and keys that are unknown or have values that
+/// can't be used are kept in `other` so that they are written
+/// again.
+pub(super) fn text_from_map(mut map: Map<String, Value>) -> Result<Text, serde_json::Error> {
+    let content = take_content(&mut map)?;
+
+    let mut inner = TextInner {
+        content,
+        ..Default::default()
+    };
+
+    inner.color = take(&mut map, "color", |v| v.as_str().and_then(color_from_str));
+    inner.font = take(&mut map, "font", as_ident);
+    inner.bold = take(&mut map, "bold", as_bool);
+    inner.italic = take(&mut map, "italic", as_bool);
+    inner.underlined = take(&mut map, "underlined", as_bool);
+    inner.strikethrough = take(&mut map, "strikethrough", as_bool);
+    inner.obfuscated = take(&mut map, "obfuscated", as_bool);
+    inner.insertion = take(&mut map, "insertion", as_string);
+    inner.click_event = take(&mut map, "clickEvent", |v| {
+        ClickEvent::deserialize(v.clone()).ok()
+    });
+    inner.hover_event = take(&mut map, "hoverEvent", |v| {
+        HoverEvent::deserialize(v.clone()).ok()
+    });
+
+    if let Some(Value::Array(extra)) = map.get("extra") {
+        inner.extra = extra
+            .iter()
+            .map(Text::deserialize)
+            .collect::<Result<_, _>>()?;
+
+        map.remove("extra");
+    }
+
+    inner.other = map.into_iter().collect();
+
+    Ok(Text(Box::new(inner)))
+}
+
+/// Removes the content keys from `map` and returns the content they describe,
+/// in the same order of precedence as vanilla.
+fn take_content(map: &mut Map<String, Value>) -> Result<TextContent, serde_json::Error> {
+    if let Some(text) = take(map, "text", as_string) {
+        return Ok(TextContent::Text { text });
+    }
+
+    if let Some(translate) = map.get("translate").and_then(as_string) {
+        let with = match map.get("with") {
+            Some(Value::Array(with)) => {
+                let with = with
+                    .iter()
+                    .map(Text::deserialize)
+                    .collect::<Result<_, _>>()?;
+
+                map.remove("with");
+                with
+            }
+            _ => vec![],
+        };
+
+        let fallback = take(map, "fallback", as_string);
+
+        map.remove("translate");
+        return Ok(TextContent::Translate {
+            translate,
+            with,
+            fallback,
+        });
+    }
+
+    if let Some(score) = take(map, "score", |v| {
+        ScoreboardValueContent::deserialize(v.clone()).ok()
+    }) {
+        return Ok(TextContent::ScoreboardValue { score });
+    }
+
+    if let Some(selector) = take(map, "selector", as_string) {
+        return Ok(TextContent::EntityNames {
+            selector,
+            separator: take_separator(map)?,
+        });
+    }
+
+    if let Some(keybind) = take(map, "keybind", as_string) {
+        return Ok(TextContent::Keybind { keybind });
+    }
+
+    if let Some(nbt) = map.get("nbt").and_then(as_string) {
+        let content = if let Some(block) = take(map, "block", as_string) {
+            Some(TextContent::BlockNbt {
+                block,
+                nbt,
+                interpret: take(map, "interpret", as_bool),
+                separator: take_separator(map)?,
+            })
+        } else if let Some(entity) = take(map, "entity", as_string) {
+            Some(TextContent::EntityNbt {
+                entity,
+                nbt,
+                interpret: take(map, "interpret", as_bool),
+                separator: take_separator(map)?,
+            })
+        } else if let Some(storage) = take(map, "storage", as_ident) {
+            Some(TextContent::StorageNbt {
+                storage,
+                nbt,
+                interpret: take(map, "interpret", as_bool),
+                separator: take_separator(map)?,
+            })
+        } else {
+            None
+        };
+
+        if let Some(content) = content {
+            map.remove("nbt");
+            return Ok(content);
+        }
+    }
+
+    Ok(TextContent::Text {
+        text: Cow::Borrowed(""),
+    })
+}
+
+fn take_separator(map: &mut Map<String, Value>) -> Result<Option<Text>, serde_json::Error> {
+    map.remove("separator").map(Text::deserialize).transpose()
+}
+
+/// Removes `key` from `map` if `f` can read its value.
+fn take<T>(
+    map: &mut Map<String, Value>,
+    key: &str,
+    f: impl FnOnce(&Value) -> Option<T>,
+) -> Option<T> {
+    let value = f(map.get(key)?)?;
+    map.remove(key);
+    Some(value)
+}
+
+/// Reads a string, number or boolean as a string.
+fn as_string(value: &Value) -> Option<Cow<'static, str>> {
+    match value {
+        Value::String(s) => Some(s.clone().into()),
+        Value::Number(n) => Some(n.to_string().into()),
+        Value::Bool(b) => Some(b.to_string().into()),
+        _ => None,
+    }
+}
+
+fn as_ident(value: &Value) -> Option<Ident<String>> {
+    Ident::new(value.as_str()?).ok().map(Into::into)
+}
+
+/// Reads a boolean, or a string containing one.
+fn as_bool(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(b) => Some(*b),
+        Value::String(s) if s.eq_ignore_ascii_case("true") => Some(true),
+        Value::String(s) if s.eq_ignore_ascii_case("false") => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::text::{Color, TextFormat};
+
+    /// Components written by Paper, Adventure, Bungee and plugins, which must
+    /// all be readable.
+    const CORPUS: &[&str] = &[
+        r#""plain string""#,
+        r#"42"#,
+        r#"-1.5"#,
+        r#"true"#,
+        r#"["", "a", {"text": "b", "color": "red"}, 3]"#,
+        r#"[]"#,
+        r#"{"text": ""}"#,
+        r#"{"extra": ["Hello ", {"text": "world", "bold": true}], "text": ""}"#,
+        r#"{"extra": [{"text": "only extra"}]}"#,
+        r#"{"text": "", "extra": [[{"text": "nested"}, " array"]]}"#,
+        r#"{"text": 7, "italic": "false"}"#,
+        r##"{"text": "x", "bold": "TRUE", "color": "#FfAa00"}"##,
+        r#"{"text": "x", "color": "not_a_color", "font": "Not An Ident"}"#,
+        r#"{"text": "x", "color": "reset"}"#,
+        r#"{"translate": "chat.type.text", "with": [{"text": "Notch"}, "hi", 5, true]}"#,
+        r#"{"translate": "multiplayer.player.joined", "with": [{"text": "Steve", "insertion": "Steve", "clickEvent": {"action": "suggest_command", "value": "/tell Steve "}, "hoverEvent": {"action": "show_entity", "contents": {"type": "minecraft:player", "id": "069a79f4-44e9-4726-a5be-fca90e38aaf5", "name": {"text": "Steve"}}}}]}"#,
+        r#"{"translate": "key", "fallback": "Fallback text"}"#,
+        r#"{"translate": "key", "with": ["a", {"text": "b"}], "fallback": "%s and %s"}"#,
+        r#"{"translate": "key", "fallback": ["not", "a", "string"]}"#,
+        r#"{"text": "hover", "hoverEvent": {"action": "show_text", "value": [{"text": "legacy "}, {"text": "value", "color": "gold"}]}}"#,
+        r#"{"text": "hover", "hoverEvent": {"action": "show_text", "value": "plain"}}"#,
+        r#"{"text": "item", "hoverEvent": {"action": "show_item", "value": "{id:\"minecraft:stone\",Count:1b}"}}"#,
+        r#"{"text": "x", "hoverEvent": {"action": "show_achievement", "value": "achievement.openInventory"}}"#,
+        r#"{"text": "x", "clickEvent": {"action": "change_page", "value": 2}}"#,
+        r#"{"text": "x", "clickEvent": {"action": "open_url"}}"#,
+        r#"{"score": {"name": "@p", "objective": "kills"}, "color": "aqua"}"#,
+        r#"{"score": {"name": "*", "objective": "deaths", "value": "3"}}"#,
+        r#"{"selector": "@a[limit=3]", "separator": {"text": " | ", "color": "dark_gray"}}"#,
+        r#"{"keybind": "key.jump", "type": "keybind"}"#,
+        r#"{"nbt": "Items[0].id", "block": "~ ~-1 ~", "interpret": false}"#,
+        r#"{"nbt": "CustomName", "entity": "@s", "interpret": "true"}"#,
+        r#"{"nbt": "motd", "storage": "example:chat", "separator": ", "}"#,
+        r#"{"nbt": "no_source"}"#,
+        r#"{"text": "unknown", "shadow_color": -16777216, "paper:extra": {"a": [1, 2]}}"#,
+        r#"{"text": "§cLegacy §lcodes"}"#,
+    ];
+
+    #[test]
+    fn lenient_corpus() {
+        for json in CORPUS {
+            let txt = serde_json::from_str::<Text>(json)
+                .unwrap_or_else(|e| panic!("failed to read {json}: {e}"));
+
+            // Writing and reading the text again gives the same text.
+            let written = serde_json::to_string(&txt).unwrap();
+            assert_eq!(
+                serde_json::from_str::<Text>(&written).unwrap(),
+                txt,
+                "{json}"
+            );
+        }
+    }
+
+    #[test]
+    fn lenient_values() {
+        let read = |json: Value| Text::deserialize(json).unwrap();
+
+        assert_eq!(
+            read(json!({ "extra": ["a", { "text": "b", "bold": "true" }] })),
+            Text::text("") + "a" + "b".bold()
+        );
+        assert_eq!(
+            read(json!({ "text": 5, "italic": "False" })),
+            "5".not_italic()
+        );
+        assert_eq!(
+            read(json!({ "text": "a", "translate": "b" })).to_string(),
+            "a"
+        );
+        assert_eq!(
+            read(json!({ "selector": "@a", "separator": "; " })),
+            Text::selector("@a", Some("; ".into()))
+        );
+        assert_eq!(
+            read(json!({ "translate": "a", "with": ["b", 1], "fallback": "%s: %s" })),
+            Text::translate_with_fallback("a", "%s: %s", ["b".into(), "1".into()])
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_kept() {
+        let json = json!({
+            "text": "a",
+            "color": "not_a_color",
+            "bold": 1,
+            "shadow_color": -1,
+            "with": ["ignored"],
+            "extra": [{ "keybind": "key.jump", "type": "keybind" }],
+        });
+
+        let txt = Text::deserialize(&json).unwrap();
+
+        assert_eq!(txt.0.color, None);
+        assert_eq!(txt.0.bold, None);
+        assert_eq!(txt.0.other.len(), 4);
+        assert_eq!(serde_json::to_value(&txt).unwrap(), json);
+
+        // Known keys are still used.
+        let txt = Text::deserialize(json!({ "text": "a", "color": "gold", "foo": 1 })).unwrap();
+        assert_eq!(txt.0.color, Some(Color::GOLD));
+    }
+}
@@ -0,0 +1,239 @@
+//! Coloring text with gradients and rainbows.
+
+use std::borrow::Cow;
+use std::mem;
+
+use super::{Color, Text, TextContent, TextFormat};
+
+/// Returns the color of character `idx` out of `len` in a gradient through
+/// `colors`, which must not be empty. Colors are mixed in linear RGB so that
+/// the middle of a gradient isn't darker than its ends.
+pub(super) fn gradient_color(colors: &[Color], idx: usize, len: usize) -> Color {
+    if colors.len() == 1 {
+        return colors[0];
+    }
+
+    let pos = if len > 1 {
+        idx as f32 / (len - 1) as f32 * (colors.len() - 1) as f32
+    } else {
+        0.0
+    };
+
+    let seg = (pos as usize).min(colors.len() - 2);
+    let t = pos - seg as f32;
+    let (from, to) = (colors[seg], colors[seg + 1]);
+
+    let mix = |a: u8, b: u8| {
+        let (a, b) = (to_linear(a), to_linear(b));
+        from_linear(a + (b - a) * t)
+    };
+
+    Color::new(mix(from.r, to.r), mix(from.g, to.g), mix(from.b, to.b))
+}
+
+/// Returns the color of character `idx` out of `len` in a rainbow, which goes
+/// around the hue circle once starting at `offset` (between 0 and 1).
+pub(super) fn rainbow_color(
+    idx: usize,
+    len: usize,
+    saturation: f32,
+    value: f32,
+    offset: f32,
+) -> Color {
+    let hue = (idx as f32 / len as f32 + offset).rem_euclid(1.0);
+
+    hsv_to_rgb(hue, saturation.clamp(0.0, 1.0), value.clamp(0.0, 1.0))
+}
+
+/// Gives each character of `txt` and its children without their own color a
+/// color from `color_at`, which is called with the index of the character and
+/// the number of characters. The formatting of the text is kept, and
+/// characters next to each other with the same color share a component.
+pub(super) fn paint(mut txt: Text, mut color_at: impl FnMut(usize, usize) -> Color) -> Text {
+    txt.0.color = None;
+
+    let len = painted_len(&txt);
+
+    if len == 0 {
+        return txt;
+    }
+
+    paint_in_place(&mut txt, &mut 0, &mut |idx| color_at(idx, len));
+    txt
+}
+
+fn painted_len(txt: &Text) -> usize {
+    let len = match &txt.0.content {
+        TextContent::Text { text } => text.chars().count(),
+        _ => 0,
+    };
+
+    len + txt
+        .0
+        .extra
+        .iter()
+        .filter(|child| child.0.color.is_none())
+        .map(painted_len)
+        .sum::<usize>()
+}
+
+fn paint_in_place(txt: &mut Text, idx: &mut usize, color_at: &mut impl FnMut(usize) -> Color) {
+    let mut painted = vec![];
+
+    if let TextContent::Text { text } = &mut txt.0.content {
+        let text = mem::replace(text, Cow::Borrowed(""));
+        let mut run = String::new();
+        let mut run_color = None;
+
+        for c in text.chars() {
+            let color = color_at(*idx);
+            *idx += 1;
+
+            if run_color != Some(color) && !run.is_empty() {
+                painted.push(Text::text(mem::take(&mut run)).color(run_color.unwrap()));
+            }
+
+            run_color = Some(color);
+            run.push(c);
+        }
+
+        if let Some(color) = run_color {
+            painted.push(Text::text(run).color(color));
+        }
+    }
+
+    for child in &mut txt.0.extra {
+        if child.0.color.is_none() {
+            paint_in_place(child, idx, color_at);
+        }
+    }
+
+    if !painted.is_empty() {
+        painted.append(&mut txt.0.extra);
+        txt.0.extra = painted;
+    }
+}
+
+/// Converts a hue, saturation and value between 0 and 1 to a color.
+fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> Color {
+    let h = hue * 6.0;
+    let c = value * saturation;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let m = value - c;
+
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+
+    let to_u8 = |c: f32| ((c + m) * 255.0).round() as u8;
+
+    Color::new(to_u8(r), to_u8(g), to_u8(b))
+}
+
+/// Converts an sRGB channel to linear light.
+fn to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a channel in linear light to sRGB.
+fn from_linear(c: f32) -> u8 {
+    let c = if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+
+    (c * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gradient_tree() {
+        let red = Color::new(0xff, 0, 0);
+        let blue = Color::new(0, 0, 0xff);
+        let txt = "abc".bold().gradient(&[red, blue]);
+
+        assert_eq!(
+            txt,
+            Text::text("").bold()
+                + "a".color(red)
+                + "b".color(Color::new(0xbc, 0, 0xbc))
+                + "c".color(blue)
+        );
+
+        // Children with their own color keep it, and the others are painted
+        // after the parent.
+        let txt = ("ab".into_text() + "c".color(Color::GOLD) + "de".italic())
+            .gradient(&[Color::BLACK, Color::WHITE]);
+
+        assert_eq!(
+            txt,
+            Text::text("")
+                + "a".color(Color::BLACK)
+                + "b".color(Color::new(0x9c, 0x9c, 0x9c))
+                + "c".color(Color::GOLD)
+                + (Text::text("").italic()
+                    + "d".color(Color::new(0xd5, 0xd5, 0xd5))
+                    + "e".color(Color::WHITE))
+        );
+
+        // Equal colors share a component.
+        assert_eq!(
+            "aaa".gradient(&[Color::GREEN]),
+            Text::text("") + "aaa".color(Color::GREEN)
+        );
+    }
+
+    #[test]
+    fn gradient_edge_cases() {
+        assert_eq!(
+            "".color(Color::RED).gradient(&[Color::RED, Color::BLUE]),
+            "".into_text()
+        );
+        assert_eq!(
+            "a".gradient(&[Color::RED, Color::BLUE]),
+            Text::text("") + "a".color(Color::RED)
+        );
+        assert_eq!("ab".gradient(&[]), "ab".into_text());
+        assert_eq!(
+            Text::translate("a", []).gradient(&[Color::RED, Color::BLUE]),
+            Text::translate("a", [])
+        );
+    }
+
+    #[test]
+    fn rainbow_tree() {
+        assert_eq!(
+            "abc".underlined().rainbow(1.0, 1.0, 0.0),
+            Text::text("").underlined()
+                + "a".color(Color::new(0xff, 0, 0))
+                + "b".color(Color::new(0, 0xff, 0))
+                + "c".color(Color::new(0, 0, 0xff))
+        );
+        assert_eq!(
+            "ab".rainbow(0.5, 0.5, 0.5),
+            Text::text("")
+                + "a".color(Color::new(0x40, 0x80, 0x80))
+                + "b".color(Color::new(0x80, 0x40, 0x40))
+        );
+        assert_eq!(
+            "a".rainbow(1.0, 1.0, -0.25),
+            Text::text("") + "a".color(Color::new(0x80, 0, 0xff))
+        );
+        assert_eq!("".rainbow(1.0, 1.0, 0.0), "".into_text());
+    }
+}
@@ -0,0 +1,411 @@
+use std::borrow::Cow;
+
+use serde::de::Error as _;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::json;
+use uuid::Uuid;
+use valence_nbt::snbt::{from_snbt_str, to_snbt_string};
+use valence_nbt::{Compound, Value};
+
+use super::Text;
+use crate::ident::Ident;
+use crate::item::ItemStack;
+
+/// What is shown when hovering over a [`Text`] object.
+///
+/// Hover events are written in the `contents` form used since 1.16, and can
+/// be read from both it and the older `value` form. Use
+/// [`HoverEvent::to_legacy_value`] to get the `value` form for older clients.
+#[derive(Clone, PartialEq, Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum HoverEvent {
+    /// Shows a text tooltip.
+    ShowText(Text),
+    /// Shows the tooltip of an item.
+    ShowItem {
+        id: Ident<String>,
+        count: i32,
+        /// The NBT of the item, such as its display name and enchantments.
+        tag: Option<Compound>,
+    },
+    /// Shows the name, type and UUID of an entity.
+    ShowEntity {
+        name: Option<Text>,
+        kind: Ident<String>,
+        id: Uuid,
+    },
+}
+
+impl HoverEvent {
+    /// Shows `text` as a tooltip.
+    pub fn show_text(text: impl Into<Text>) -> Self {
+        Self::ShowText(text.into())
+    }
+
+    /// Shows the tooltip of an item stack, including its count and NBT.
+    pub fn show_item(stack: &ItemStack) -> Self {
+        Self::ShowItem {
+            id: Ident::new(stack.item.to_str()).unwrap().into(),
+            count: stack.count().into(),
+            tag: stack.nbt.clone(),
+        }
+    }
+
+    /// Shows an entity with the given type, UUID and optional custom name.
+    pub fn show_entity(kind: Ident<String>, id: Uuid, name: Option<Text>) -> Self {
+        Self::ShowEntity { name, kind, id }
+    }
+
+    /// Returns the `value` of the hover event in the format used before 1.16.
+    /// Items and entities are written as SNBT.
+    pub fn to_legacy_value(&self) -> Text {
+        match self {
+            HoverEvent::ShowText(text) => text.clone(),
+            HoverEvent::ShowItem { id, count, tag } => {
+                let mut item = Compound::new();
+                item.insert("id", id.as_str());
+                item.insert("Count", *count as i8);
+
+                if let Some(tag) = tag {
+                    item.insert("tag", tag.clone());
+                }
+
+                to_snbt_string(&Value::Compound(item)).into()
+            }
+            HoverEvent::ShowEntity { name, kind, id } => {
+                let mut entity = Compound::new();
+                entity.insert("type", kind.as_str());
+                entity.insert("id", id.to_string());
+
+                if let Some(name) = name {
+                    entity.insert("name", serde_json::to_string(name).unwrap());
+                }
+
+                to_snbt_string(&Value::Compound(entity)).into()
+            }
+        }
+    }
+
+    fn action(&self) -> &'static str {
+        match self {
+            HoverEvent::ShowText(_) => "show_text",
+            HoverEvent::ShowItem { .. } => "show_item",
+            HoverEvent::ShowEntity { .. } => "show_entity",
+        }
+    }
+}
+
+impl Serialize for HoverEvent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+
+        map.serialize_entry("action", self.action())?;
+
+        match self {
+            HoverEvent::ShowText(text) => map.serialize_entry("contents", text)?,
+            HoverEvent::ShowItem { id, count, tag } => {
+                let mut contents = json!({ "id": id });
+
+                // Vanilla leaves out the count of single items.
+                if *count != 1 {
+                    contents["count"] = json!(count);
+                }
+
+                if let Some(tag) = tag {
+                    contents["tag"] = json!(to_snbt_string(&Value::Compound(tag.clone())));
+                }
+
+                map.serialize_entry("contents", &contents)?;
+            }
+            HoverEvent::ShowEntity { name, kind, id } => {
+                let mut contents = json!({ "type": kind, "id": id });
+
+                if let Some(name) = name {
+                    contents["name"] = json!(name);
+                }
+
+                map.serialize_entry("contents", &contents)?;
+            }
+        }
+
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for HoverEvent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct RawHoverEvent {
+            action: Cow<'static, str>,
+            contents: Option<serde_json::Value>,
+            value: Option<Text>,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum ItemContents {
+            Id(Ident<String>),
+            Item {
+                id: Ident<String>,
+                #[serde(default = "one")]
+                count: i32,
+                tag: Option<String>,
+            },
+        }
+
+        #[derive(Deserialize)]
+        struct EntityContents {
+            #[serde(rename = "type")]
+            kind: Ident<String>,
+            id: Uuid,
+            name: Option<Text>,
+        }
+
+        fn one() -> i32 {
+            1
+        }
+
+        fn parse_snbt<E: serde::de::Error>(snbt: &str) -> Result<Compound, E> {
+            match from_snbt_str(snbt) {
+                Ok(Value::Compound(compound)) => Ok(compound),
+                Ok(_) => Err(E::custom("expected an SNBT compound")),
+                Err(e) => Err(E::custom(format!("invalid SNBT: {e}"))),
+            }
+        }
+
+        let raw = RawHoverEvent::deserialize(deserializer)?;
+
+        match (raw.contents, raw.value) {
+            (Some(contents), _) => match &*raw.action {
+                "show_text" => Text::deserialize(contents)
+                    .map(HoverEvent::ShowText)
+                    .map_err(D::Error::custom),
+                "show_item" => match ItemContents::deserialize(contents) {
+                    Ok(ItemContents::Id(id)) => Ok(HoverEvent::ShowItem {
+                        id,
+                        count: 1,
+                        tag: None,
+                    }),
+                    Ok(ItemContents::Item { id, count, tag }) => Ok(HoverEvent::ShowItem {
+                        id,
+                        count,
+                        tag: tag.as_deref().map(parse_snbt).transpose()?,
+                    }),
+                    Err(e) => Err(D::Error::custom(e)),
+                },
+                "show_entity" => EntityContents::deserialize(contents)
+                    .map(|entity| HoverEvent::ShowEntity {
+                        name: entity.name,
+                        kind: entity.kind,
+                        id: entity.id,
+                    })
+                    .map_err(D::Error::custom),
+                action => Err(D::Error::custom(format!(
+                    "unknown hover event action \"{action}\""
+                ))),
+            },
+            (None, Some(value)) => match &*raw.action {
+                "show_text" => Ok(HoverEvent::ShowText(value)),
+                "show_item" => {
+                    let mut item = parse_snbt::<D::Error>(&value.to_string())?;
+
+                    let id = match item.remove("id") {
+                        Some(Value::String(id)) => Ident::new(id).map_err(D::Error::custom)?.into(),
+                        _ => return Err(D::Error::missing_field("id")),
+                    };
+
+                    let count = match item.remove("Count") {
+                        Some(Value::Byte(count)) => count.into(),
+                        Some(Value::Int(count)) => count,
+                        _ => 1,
+                    };
+
+                    let tag = match item.remove("tag") {
+                        Some(Value::Compound(tag)) => Some(tag),
+                        _ => None,
+                    };
+
+                    Ok(HoverEvent::ShowItem { id, count, tag })
+                }
+                "show_entity" => {
+                    let mut entity = parse_snbt::<D::Error>(&value.to_string())?;
+
+                    let kind = match entity.remove("type") {
+                        Some(Value::String(kind)) => {
+                            Ident::new(kind).map_err(D::Error::custom)?.into()
+                        }
+                        _ => return Err(D::Error::missing_field("type")),
+                    };
+
+                    let id = match entity.remove("id") {
+                        Some(Value::String(id)) => id.parse().map_err(D::Error::custom)?,
+                        _ => return Err(D::Error::missing_field("id")),
+                    };
+
+                    let name = match entity.remove("name") {
+                        Some(Value::String(name)) => {
+                            Some(serde_json::from_str(&name).map_err(D::Error::custom)?)
+                        }
+                        _ => None,
+                    };
+
+                    Ok(HoverEvent::ShowEntity { name, kind, id })
+                }
+                action => Err(D::Error::custom(format!(
+                    "unknown hover event action \"{action}\""
+                ))),
+            },
+            (None, None) => Err(D::Error::missing_field("contents")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ident;
+    use crate::item::ItemKind;
+    use crate::text::{Color, TextFormat};
+
+    fn hover_event(json: &str) -> HoverEvent {
+        let txt: Text = serde_json::from_str(json).unwrap();
+        txt.0.hover_event.unwrap()
+    }
+
+    /// Checks that the hover event survives being written and read again.
+    fn assert_round_trip(event: &HoverEvent) {
+        let json = serde_json::to_string(&"x".on_hover(event.clone())).unwrap();
+        assert_eq!(&hover_event(&json), event, "{json}");
+    }
+
+    #[test]
+    fn vanilla_show_item() {
+        // Written by a vanilla 1.20.1 server for `/tellraw` with an item.
+        let event = hover_event(
+            r#"{"text":"[Diamond Sword]","hoverEvent":{"action":"show_item","contents":{"id":"minecraft:diamond_sword","tag":"{Damage:0,display:{Name:'{\"text\":\"Excalibur\"}'}}"}}}"#,
+        );
+
+        let HoverEvent::ShowItem { id, count, tag } = &event else {
+            panic!("expected show_item, got {event:?}");
+        };
+
+        assert_eq!(id.as_str(), "minecraft:diamond_sword");
+        assert_eq!(*count, 1);
+
+        let tag = tag.as_ref().unwrap();
+        assert_eq!(tag.get("Damage"), Some(&Value::Int(0)));
+
+        let Some(Value::Compound(display)) = tag.get("display") else {
+            panic!("missing display in {tag:?}");
+        };
+        assert_eq!(
+            display.get("Name"),
+            Some(&Value::String(r#"{"text":"Excalibur"}"#.into()))
+        );
+
+        assert_round_trip(&event);
+
+        let event = hover_event(
+            r#"{"text":"","hoverEvent":{"action":"show_item","contents":{"id":"minecraft:stone","count":64}}}"#,
+        );
+
+        assert_eq!(
+            event,
+            HoverEvent::show_item(&ItemStack::new(ItemKind::Stone, 64, None))
+        );
+        assert_round_trip(&event);
+
+        let event = hover_event(
+            r#"{"text":"","hoverEvent":{"action":"show_item","contents":"minecraft:apple"}}"#,
+        );
+
+        assert_eq!(
+            event,
+            HoverEvent::show_item(&ItemStack::new(ItemKind::Apple, 1, None))
+        );
+    }
+
+    #[test]
+    fn vanilla_show_entity() {
+        let event = hover_event(
+            r#"{"text":"Pig","hoverEvent":{"action":"show_entity","contents":{"type":"minecraft:pig","id":"3f3a7e8a-7f4e-4c56-9f3c-0b0d3b1d2a10","name":{"text":"Pig"}}}}"#,
+        );
+
+        assert_eq!(
+            event,
+            HoverEvent::show_entity(
+                ident!("pig").into(),
+                "3f3a7e8a-7f4e-4c56-9f3c-0b0d3b1d2a10".parse().unwrap(),
+                Some("Pig".into())
+            )
+        );
+        assert_round_trip(&event);
+
+        let nameless = HoverEvent::show_entity(ident!("zombie").into(), Uuid::from_u128(7), None);
+        assert_round_trip(&nameless);
+    }
+
+    #[test]
+    fn legacy_hover_values() {
+        // The `value` form written by 1.12 clients.
+        let event = hover_event(
+            r#"{"text":"","hoverEvent":{"action":"show_item","value":"{id:\"minecraft:golden_apple\",Count:3b,tag:{display:{Lore:[\"Shiny\"]}}}"}}"#,
+        );
+
+        let HoverEvent::ShowItem { id, count, tag } = &event else {
+            panic!("expected show_item, got {event:?}");
+        };
+
+        assert_eq!(id.as_str(), "minecraft:golden_apple");
+        assert_eq!(*count, 3);
+        assert!(tag.is_some());
+
+        // The legacy value is read back to the same event.
+        let legacy = format!(
+            r#"{{"text":"","hoverEvent":{{"action":"show_item","value":{}}}}}"#,
+            serde_json::to_string(&event.to_legacy_value()).unwrap()
+        );
+        assert_eq!(hover_event(&legacy), event);
+
+        let entity = HoverEvent::show_entity(
+            ident!("minecraft:creeper").into(),
+            Uuid::from_u128(0xc4ee9e4),
+            Some("Boom".color(Color::GREEN)),
+        );
+
+        let legacy = format!(
+            r#"{{"text":"","hoverEvent":{{"action":"show_entity","value":{}}}}}"#,
+            serde_json::to_string(&entity.to_legacy_value()).unwrap()
+        );
+        assert_eq!(hover_event(&legacy), entity);
+
+        let text = hover_event(
+            r#"{"text":"","hoverEvent":{"action":"show_text","value":{"text":"hi","bold":true}}}"#,
+        );
+        assert_eq!(text, HoverEvent::show_text("hi".bold()));
+    }
+
+    #[test]
+    fn item_nbt_is_snbt_encoded() {
+        let mut nbt = Compound::new();
+        nbt.insert("Unbreakable", 1_i8);
+        nbt.insert("note", "quote \" and \\ backslash");
+
+        let event = HoverEvent::show_item(&ItemStack::new(ItemKind::Stick, 2, Some(nbt)));
+        let json = serde_json::to_value(&event).unwrap();
+
+        assert_eq!(
+            json,
+            json!({
+                "action": "show_item",
+                "contents": {
+                    "id": "minecraft:stick",
+                    "count": 2,
+                    "tag": r#"{Unbreakable:1b,note:"quote \" and \\ backslash"}"#,
+                },
+            })
+        );
+        assert_round_trip(&event);
+    }
+}
@@ -0,0 +1,72 @@
+//! The identifiers of the vanilla keybinds, for use with [`Text::keybind`].
+//!
+//! [`Text::keybind`]: super::Text::keybind
+
+/// "Attack/Destroy"
+pub const ATTACK: &str = "key.attack";
+/// "Use Item/Place Block"
+pub const USE: &str = "key.use";
+/// "Walk Forwards"
+pub const FORWARD: &str = "key.forward";
+/// "Strafe Left"
+pub const LEFT: &str = "key.left";
+/// "Walk Backwards"
+pub const BACK: &str = "key.back";
+/// "Strafe Right"
+pub const RIGHT: &str = "key.right";
+/// "Jump"
+pub const JUMP: &str = "key.jump";
+/// "Sneak"
+pub const SNEAK: &str = "key.sneak";
+/// "Sprint"
+pub const SPRINT: &str = "key.sprint";
+/// "Drop Selected Item"
+pub const DROP: &str = "key.drop";
+/// "Open/Close Inventory"
+pub const INVENTORY: &str = "key.inventory";
+/// "Open Chat"
+pub const CHAT: &str = "key.chat";
+/// "List Players"
+pub const PLAYERLIST: &str = "key.playerlist";
+/// "Pick Block"
+pub const PICK_ITEM: &str = "key.pickItem";
+/// "Open Command"
+pub const COMMAND: &str = "key.command";
+/// "Social Interactions Screen"
+pub const SOCIAL_INTERACTIONS: &str = "key.socialInteractions";
+/// "Take Screenshot"
+pub const SCREENSHOT: &str = "key.screenshot";
+/// "Toggle Perspective"
+pub const TOGGLE_PERSPECTIVE: &str = "key.togglePerspective";
+/// "Toggle Cinematic Camera"
+pub const SMOOTH_CAMERA: &str = "key.smoothCamera";
+/// "Toggle Fullscreen"
+pub const FULLSCREEN: &str = "key.fullscreen";
+/// "Highlight Players (Spectators)"
+pub const SPECTATOR_OUTLINES: &str = "key.spectatorOutlines";
+/// "Swap Item With Offhand"
+pub const SWAP_OFFHAND: &str = "key.swapOffhand";
+/// "Save Hotbar Activator"
+pub const SAVE_TOOLBAR_ACTIVATOR: &str = "key.saveToolbarActivator";
+/// "Load Hotbar Activator"
+pub const LOAD_TOOLBAR_ACTIVATOR: &str = "key.loadToolbarActivator";
+/// "Advancements"
+pub const ADVANCEMENTS: &str = "key.advancements";
+/// "Hotbar Slot 1"
+pub const HOTBAR_1: &str = "key.hotbar.1";
+/// "Hotbar Slot 2"
+pub const HOTBAR_2: &str = "key.hotbar.2";
+/// "Hotbar Slot 3"
+pub const HOTBAR_3: &str = "key.hotbar.3";
+/// "Hotbar Slot 4"
+pub const HOTBAR_4: &str = "key.hotbar.4";
+/// "Hotbar Slot 5"
+pub const HOTBAR_5: &str = "key.hotbar.5";
+/// "Hotbar Slot 6"
+pub const HOTBAR_6: &str = "key.hotbar.6";
+/// "Hotbar Slot 7"
+pub const HOTBAR_7: &str = "key.hotbar.7";
+/// "Hotbar Slot 8"
+pub const HOTBAR_8: &str = "key.hotbar.8";
+/// "Hotbar Slot 9"
+pub const HOTBAR_9: &str = "key.hotbar.9";
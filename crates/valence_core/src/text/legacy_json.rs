@@ -0,0 +1,281 @@
+//! Text JSON in the format used before 1.16, which has no hex colors, fonts or
+//! hover event `contents`.
+
+use serde::de::Error as _;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use super::{color_from_str, Color, HoverEvent, Text};
+
+/// The names of the legacy colors in the order of [`Color::LEGACY`].
+const LEGACY_NAMES: [&str; 16] = [
+    "black",
+    "dark_blue",
+    "dark_green",
+    "dark_aqua",
+    "dark_red",
+    "dark_purple",
+    "gold",
+    "gray",
+    "dark_gray",
+    "blue",
+    "green",
+    "aqua",
+    "red",
+    "light_purple",
+    "yellow",
+    "white",
+];
+
+impl Text {
+    /// Writes this text as JSON that clients before 1.16 and other software
+    /// using the old format can read.
+    ///
+    /// Colors are replaced with the closest of the 16 named colors according
+    /// to [`Color::to_legacy`] and written by name, fonts are left out, and
+    /// hover events are written with a `value` instead of `contents`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valence_core::text::{Color, Text, TextFormat};
+    ///
+    /// let txt = "Welcome".color(Color::new(0xff, 0x88, 0x00)).bold();
+    ///
+    /// let json: serde_json::Value = serde_json::from_str(&txt.to_json_legacy_colors()).unwrap();
+    ///
+    /// assert_eq!(
+    ///     json,
+    ///     serde_json::json!({ "text": "Welcome", "color": "gold", "bold": true })
+    /// );
+    /// ```
+    pub fn to_json_legacy_colors(&self) -> String {
+        let mut value = serde_json::to_value(self).expect("text is valid JSON");
+        downgrade(&mut value);
+        value.to_string()
+    }
+
+    /// Reads text from JSON like the [`Deserialize`] impl, but returns an
+    /// error if it has a hex color, which clients before 1.16 can't read. This
+    /// is useful for validating text that is meant for older software.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valence_core::text::{Color, Text, TextFormat};
+    ///
+    /// assert_eq!(
+    ///     Text::from_json_legacy_colors(r#"{"text":"hi","color":"red"}"#).unwrap(),
+    ///     "hi".color(Color::RED)
+    /// );
+    /// assert!(Text::from_json_legacy_colors(r##"{"text":"hi","color":"#ff5555"}"##).is_err());
+    /// ```
+    pub fn from_json_legacy_colors(json: &str) -> Result<Self, serde_json::Error> {
+        let value: Value = serde_json::from_str(json)?;
+        check_colors(&value)?;
+        Text::deserialize(value)
+    }
+}
+
+/// Returns the name of a legacy color.
+fn legacy_name(color: Color) -> Option<&'static str> {
+    Color::LEGACY
+        .iter()
+        .position(|&c| c == color)
+        .map(|i| LEGACY_NAMES[i])
+}
+
+/// Converts written text to the old format in place.
+fn downgrade(value: &mut Value) {
+    match value {
+        Value::Array(elems) => elems.iter_mut().for_each(downgrade),
+        Value::Object(map) => {
+            if let Some(color) = map.get_mut("color") {
+                if let Some(c) = color.as_str().and_then(color_from_str) {
+                    *color = legacy_name(c.to_legacy()).unwrap().into();
+                }
+            }
+
+            map.remove("font");
+
+            for key in ["extra", "with", "separator"] {
+                if let Some(v) = map.get_mut(key) {
+                    downgrade(v);
+                }
+            }
+
+            if let Some(hover) = map.get_mut("hoverEvent") {
+                if let Ok(event) = HoverEvent::deserialize(&*hover) {
+                    let mut value =
+                        serde_json::to_value(event.to_legacy_value()).expect("text is valid JSON");
+                    downgrade(&mut value);
+
+                    *hover = json!({ "action": hover["action"], "value": value });
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns an error if text JSON has a hex color anywhere.
+fn check_colors(value: &Value) -> Result<(), serde_json::Error> {
+    match value {
+        Value::Array(elems) => elems.iter().try_for_each(check_colors),
+        Value::Object(map) => {
+            if let Some(color) = map.get("color").and_then(Value::as_str) {
+                if color.starts_with('#') {
+                    return Err(serde_json::Error::custom(format!(
+                        "hex color \"{color}\" is not supported before 1.16"
+                    )));
+                }
+            }
+
+            for key in ["extra", "with", "separator"] {
+                if let Some(v) = map.get(key) {
+                    check_colors(v)?;
+                }
+            }
+
+            if let Some(Value::Object(hover)) = map.get("hoverEvent") {
+                check_hover_colors(hover)?;
+            }
+
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_hover_colors(hover: &Map<String, Value>) -> Result<(), serde_json::Error> {
+    match hover.get("action").and_then(Value::as_str) {
+        Some("show_text") => {
+            for key in ["contents", "value"] {
+                if let Some(v) = hover.get(key) {
+                    check_colors(v)?;
+                }
+            }
+        }
+        Some("show_entity") => {
+            if let Some(name) = hover.get("contents").and_then(|c| c.get("name")) {
+                check_colors(name)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ident;
+    use crate::text::TextFormat;
+
+    fn distance(a: Color, b: Color) -> i32 {
+        (a.r as i32 - b.r as i32).pow(2)
+            + (a.g as i32 - b.g as i32).pow(2)
+            + (a.b as i32 - b.b as i32).pow(2)
+    }
+
+    #[test]
+    fn nearest_color_sweep() {
+        for r in (0..=255).step_by(15) {
+            for g in (0..=255).step_by(15) {
+                for b in (0..=255).step_by(15) {
+                    let color = Color::new(r, g, b);
+                    let legacy = color.to_legacy();
+
+                    assert!(legacy_name(legacy).is_some());
+
+                    for other in Color::LEGACY {
+                        assert!(
+                            distance(color, legacy) <= distance(color, other),
+                            "{color:?} -> {legacy:?}, but {other:?} is closer"
+                        );
+                    }
+
+                    let json: Value =
+                        serde_json::from_str(&"x".color(color).to_json_legacy_colors()).unwrap();
+                    assert_eq!(json["color"], legacy_name(legacy).unwrap());
+                }
+            }
+        }
+
+        for (color, name) in Color::LEGACY.into_iter().zip(LEGACY_NAMES) {
+            assert_eq!(color.to_legacy(), color);
+            assert_eq!(color_from_str(name), Some(color));
+        }
+
+        let nearest = |hex: &str| legacy_name(color_from_str(hex).unwrap().to_legacy()).unwrap();
+
+        assert_eq!(nearest("#ff0000"), "dark_red");
+        assert_eq!(nearest("#00ff00"), "dark_green");
+        assert_eq!(nearest("#ff8800"), "gold");
+        assert_eq!(nearest("#123456"), "dark_gray");
+        assert_eq!(nearest("#808080"), "gray");
+        assert_eq!(nearest("#ffc0cb"), "white");
+        assert_eq!(nearest("#2a2a2a"), "black");
+    }
+
+    #[test]
+    fn write_legacy_json() {
+        let txt = "a"
+            .color(Color::new(0xc0, 0xc0, 0xc0))
+            .font(ident!("uniform"))
+            + Text::translate_with_fallback("key", "%s", ["b".color(Color::new(0xff, 0, 0))])
+                .on_hover_show_text("c".color(Color::new(0x12, 0x34, 0x56)));
+
+        let json: Value = serde_json::from_str(&txt.to_json_legacy_colors()).unwrap();
+
+        assert_eq!(
+            json,
+            json!({
+                "text": "a",
+                "color": "gray",
+                "extra": [{
+                    "translate": "key",
+                    "with": [{ "text": "b", "color": "dark_red" }],
+                    "fallback": "%s",
+                    "hoverEvent": {
+                        "action": "show_text",
+                        "value": { "text": "c", "color": "dark_gray" },
+                    },
+                }],
+            })
+        );
+
+        // The old format can be read again.
+        assert_eq!(
+            Text::from_json_legacy_colors(&json.to_string()).unwrap(),
+            "a".color(Color::GRAY)
+                + Text::translate_with_fallback("key", "%s", ["b".color(Color::DARK_RED)])
+                    .on_hover_show_text("c".color(Color::DARK_GRAY))
+        );
+    }
+
+    #[test]
+    fn strict_colors() {
+        let read = Text::from_json_legacy_colors;
+
+        assert!(read(r#"{"text": "a", "color": "dark_red"}"#).is_ok());
+        assert!(read(r#"["a", {"text": "b", "color": "gold"}]"#).is_ok());
+
+        for json in [
+            r##"{"text": "a", "color": "#aa0000"}"##,
+            r##"["a", {"text": "b", "color": "#ffaa00"}]"##,
+            r##"{"text": "a", "extra": [{"text": "b", "color": "#000000"}]}"##,
+            r##"{"translate": "a", "with": [{"text": "b", "color": "#000000"}]}"##,
+            r##"{"selector": "@a", "separator": {"text": ",", "color": "#000000"}}"##,
+            r##"{"text": "a", "hoverEvent": {"action": "show_text", "value": {"text": "b", "color": "#000000"}}}"##,
+            r##"{"text": "a", "hoverEvent": {"action": "show_text", "contents": {"text": "b", "color": "#000000"}}}"##,
+        ] {
+            let err = read(json).unwrap_err().to_string();
+            assert!(
+                err.contains("is not supported before 1.16"),
+                "{json}: {err}"
+            );
+        }
+    }
+}
@@ -0,0 +1,122 @@
+/// Builds a [`Text`](crate::text::Text) out of a comma-separated list of
+/// segments, which become the children of an empty text.
+///
+/// A segment is one of the following:
+///
+/// - A string literal, which is a format string like in [`format!`]. Variables
+///   can be used in it with `{name}`, and braces are escaped with `{{` and
+///   `}}`.
+/// - A block such as `{username}` or `{score + 1}`, whose value is converted
+///   with `Into<Text>`.
+/// - A group of segments in square brackets, which is a text of its own.
+/// - A segment prefixed with styles in parentheses and a colon, such as `(bold,
+///   color = GOLD): "text"`.
+///
+/// Styles are applied with the methods of
+/// [`TextFormat`](crate::text::TextFormat), so each style is the name of one of
+/// its methods, with the arguments in parentheses if it takes any. `color =
+/// NAME` is short for `color(Color::NAME)`. Misspelled styles and colors are
+/// compile errors.
+///
+/// # Examples
+///
+/// ```
+/// use valence_core::text;
+/// use valence_core::text::{Color, Text, TextFormat};
+///
+/// let username = "Steve";
+///
+/// let txt = text!["Welcome ", (bold, color = GOLD): {username}, "!"];
+///
+/// assert_eq!(
+///     txt,
+///     Text::default() + "Welcome " + "Steve".bold().color(Color::GOLD) + "!"
+/// );
+/// assert_eq!(txt.to_string(), "Welcome Steve!");
+/// ```
+///
+/// Groups are styled as a whole, and events are attached like other styles:
+///
+/// ```
+/// use valence_core::text;
+/// use valence_core::text::{Color, Text, TextFormat};
+///
+/// let players = 3;
+///
+/// let txt = text![
+///     (color = GRAY): ["There are ", (color = AQUA): "{players}", " players online. "],
+///     (
+///         underlined,
+///         on_click_run_command("/list"),
+///         on_hover_show_text(text!["Run ", (italic): "/list"]),
+///     ): "[List]",
+/// ];
+///
+/// assert_eq!(
+///     txt,
+///     Text::default()
+///         + (Text::default()
+///             + "There are "
+///             + "3".color(Color::AQUA)
+///             + " players online. ")
+///             .color(Color::GRAY)
+///         + "[List]"
+///             .underlined()
+///             .on_click_run_command("/list")
+///             .on_hover_show_text(Text::default() + "Run " + "/list".italic())
+/// );
+/// ```
+#[macro_export]
+macro_rules! text {
+    (@segments $txt:ident) => {};
+    (@segments $txt:ident ( $($style:tt)* ) : $segment:tt $(, $($rest:tt)*)?) => {
+        $txt += $crate::text!(@style ($crate::text!(@segment $segment)) $($style)*);
+        $crate::text!(@segments $txt $($($rest)*)?);
+    };
+    (@segments $txt:ident $segment:tt $(, $($rest:tt)*)?) => {
+        $txt += $crate::text!(@segment $segment);
+        $crate::text!(@segments $txt $($($rest)*)?);
+    };
+
+    (@segment $fmt:literal) => {
+        $crate::text::Text::from(::std::format!($fmt))
+    };
+    (@segment { $($value:tt)* }) => {
+        ::std::convert::Into::<$crate::text::Text>::into({ $($value)* })
+    };
+    (@segment [ $($segments:tt)* ]) => {
+        $crate::text![$($segments)*]
+    };
+    (@segment $($other:tt)*) => {
+        ::std::compile_error!("expected a string literal, block or group of segments")
+    };
+
+    (@style ($txt:expr) $(,)?) => {
+        $txt
+    };
+    (@style ($txt:expr) color = $color:ident $(, $($rest:tt)*)?) => {
+        $crate::text!(@style
+            ($crate::text::TextFormat::color($txt, $crate::text::Color::$color))
+            $($($rest)*)?
+        )
+    };
+    (@style ($txt:expr) $method:ident ( $($args:tt)* ) $(, $($rest:tt)*)?) => {
+        $crate::text!(@style
+            ($crate::text::TextFormat::$method($txt, $($args)*))
+            $($($rest)*)?
+        )
+    };
+    (@style ($txt:expr) $method:ident $(, $($rest:tt)*)?) => {
+        $crate::text!(@style ($crate::text::TextFormat::$method($txt)) $($($rest)*)?)
+    };
+    (@style ($txt:expr) $($other:tt)*) => {
+        ::std::compile_error!("expected a style such as `bold` or `color = RED`")
+    };
+
+    ($($segments:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut txt = $crate::text::Text::default();
+        $crate::text!(@segments txt $($segments)*);
+        txt
+    }};
+}
@@ -0,0 +1,1167 @@
+//! Parsing and writing [MiniMessage](https://docs.advntr.dev/minimessage/format.html)-style
+//! markup.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::ops::Range;
+
+use thiserror::Error;
+
+use super::{
+    color_from_str, gradient, ClickEvent, Color, HoverEvent, Text, TextContent, TextFormat,
+    TextInner,
+};
+use crate::ident::Ident;
+
+/// An error returned by [`Text::from_markup`].
+#[derive(Clone, PartialEq, Eq, Debug, Error)]
+#[error("{kind} at bytes {}..{}", span.start, span.end)]
+pub struct MarkupError {
+    /// The byte range of the tag in the markup.
+    pub span: Range<usize>,
+    pub kind: MarkupErrorKind,
+}
+
+/// The kind of a [`MarkupError`].
+#[derive(Clone, PartialEq, Eq, Debug, Error)]
+pub enum MarkupErrorKind {
+    #[error("unterminated tag")]
+    UnterminatedTag,
+    #[error("unterminated quoted argument")]
+    UnterminatedQuote,
+    #[error("unknown tag `{0}`")]
+    UnknownTag(String),
+    #[error("closing tag `{0}` does not match any open tag")]
+    UnmatchedClosingTag(String),
+    #[error("invalid arguments for tag `{tag}`: {reason}")]
+    InvalidArguments { tag: String, reason: String },
+}
+
+impl Text {
+    /// Parses [MiniMessage](https://docs.advntr.dev/minimessage/format.html)-style
+    /// markup into a [`Text`] object.
+    ///
+    /// The following tags are supported:
+    ///
+    /// - Colors: `<red>`, `<#ff5555>`, `<color:red>` (or `colour` and `c`).
+    /// - Decorations: `<bold>` (`b`), `<italic>` (`i`, `em`), `<underlined>`
+    ///   (`u`), `<strikethrough>` (`st`) and `<obfuscated>` (`obf`).
+    ///   Decorations are turned off with a `!`, such as `<!italic>`.
+    /// - `<click:action:value>` with the actions `open_url`, `open_file`,
+    ///   `run_command`, `suggest_command`, `change_page` and
+    ///   `copy_to_clipboard`.
+    /// - `<hover:show_text:'markup'>`.
+    /// - `<insert:text>` and `<font:name>`.
+    /// - `<gradient:color:color...>`, which defaults to white and black, and
+    ///   `<rainbow>`, which is reversed with `<rainbow:!>` and shifted with
+    ///   `<rainbow:phase>`. Text with its own color inside is skipped.
+    /// - `<lang:key:'arg'...>` (`tr`, `translate`),
+    ///   `<lang_or:key:fallback:'arg'...>` (`tr_or`, `translate_or`),
+    ///   `<key:name>` (`keybind`) and `<newline>` (`br`).
+    /// - `<reset>`, which closes all open tags.
+    /// - `<name>` for each entry of `placeholders`, which is replaced with its
+    ///   text.
+    ///
+    /// Tags are closed with `</name>`, which also closes the tags opened after
+    /// it. Tags that are still open at the end are closed automatically.
+    /// Arguments can be quoted with `'` or `"` to contain `:` or `>`. `\<`
+    /// writes a literal `<`, and a `<` that can't start a tag (such as in
+    /// `a < b`) is kept as it is.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error with the byte range of the tag if a tag is unknown,
+    /// unterminated, has invalid arguments, or closes a tag that isn't open.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// use valence_core::text::{Color, Text, TextFormat};
+    ///
+    /// let placeholders = HashMap::from([("player", "Notch".color(Color::GOLD))]);
+    ///
+    /// let txt = Text::from_markup("<gray>Welcome, <player><bold>!", &placeholders).unwrap();
+    ///
+    /// assert_eq!(
+    ///     txt,
+    ///     "Welcome, ".color(Color::GRAY) + "Notch".color(Color::GOLD) + "!".bold()
+    /// );
+    ///
+    /// assert!(Text::from_markup("<red>Hello</bold>", &HashMap::new()).is_err());
+    /// ```
+    pub fn from_markup(
+        markup: &str,
+        placeholders: &HashMap<&str, Text>,
+    ) -> Result<Self, MarkupError> {
+        let nodes = Parser {
+            markup,
+            placeholders,
+            pos: 0,
+        }
+        .parse()?;
+
+        let mut texts = build(nodes, None);
+
+        Ok(match texts.len() {
+            0 => Text::default(),
+            1 => texts.pop().unwrap(),
+            _ if is_plain(&texts[0]) => {
+                let mut txt = texts.remove(0);
+                txt.0.extra = texts;
+                txt
+            }
+            _ => {
+                let mut txt = Text::default();
+                txt.0.extra = texts;
+                txt
+            }
+        })
+    }
+
+    /// Writes the [`Text`] object as markup that can be parsed with
+    /// [`Text::from_markup`].
+    ///
+    /// Scoreboard values, entity names and NBT values can't be written as
+    /// markup and are left out, as are the `show_item` and `show_entity` hover
+    /// events.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valence_core::text::{Color, Text, TextFormat};
+    ///
+    /// let txt = "Hello ".color(Color::RED) + "<world>".bold().on_click_run_command("/spawn");
+    ///
+    /// assert_eq!(
+    ///     txt.to_markup(),
+    ///     "<red>Hello <bold><click:run_command:/spawn>\\<world></click></bold></red>"
+    /// );
+    /// ```
+    pub fn to_markup(&self) -> String {
+        let mut result = String::new();
+        write_markup(self, &mut result);
+        result
+    }
+}
+
+/// Returns if the text has no formatting or children, so that other
+/// formatting can be added to it without changing its meaning.
+fn is_plain(txt: &Text) -> bool {
+    *txt.0
+        == TextInner {
+            content: txt.0.content.clone(),
+            ..Default::default()
+        }
+}
+
+enum Node {
+    Text(String),
+    /// Text that isn't changed by gradients.
+    Leaf(Text),
+    Tag {
+        tag: Tag,
+        children: Vec<Node>,
+    },
+}
+
+#[derive(Clone, Debug)]
+enum Tag {
+    Color(Color),
+    Bold(bool),
+    Italic(bool),
+    Underlined(bool),
+    Strikethrough(bool),
+    Obfuscated(bool),
+    Click(ClickEvent),
+    Hover(Text),
+    Insert(String),
+    Font(Ident<String>),
+    Gradient(Vec<Color>),
+    Rainbow { reversed: bool, phase: i32 },
+}
+
+impl Tag {
+    /// Adds the formatting of the tag to `txt`. Returns `false` if the
+    /// formatting is already set.
+    fn apply(&self, txt: &mut Text) -> bool {
+        fn set<T>(field: &mut Option<T>, value: T) -> bool {
+            if field.is_some() {
+                return false;
+            }
+
+            *field = Some(value);
+            true
+        }
+
+        let inner = &mut txt.0;
+
+        match self {
+            Tag::Color(color) => set(&mut inner.color, *color),
+            Tag::Bold(b) => set(&mut inner.bold, *b),
+            Tag::Italic(b) => set(&mut inner.italic, *b),
+            Tag::Underlined(b) => set(&mut inner.underlined, *b),
+            Tag::Strikethrough(b) => set(&mut inner.strikethrough, *b),
+            Tag::Obfuscated(b) => set(&mut inner.obfuscated, *b),
+            Tag::Click(event) => set(&mut inner.click_event, event.clone()),
+            Tag::Hover(text) => set(&mut inner.hover_event, HoverEvent::ShowText(text.clone())),
+            Tag::Insert(insertion) => set(&mut inner.insertion, insertion.clone().into()),
+            Tag::Font(font) => set(&mut inner.font, font.clone()),
+            Tag::Gradient(_) | Tag::Rainbow { .. } => true,
+        }
+    }
+}
+
+/// An open tag while parsing.
+struct OpenTag {
+    /// The name that closes the tag.
+    name: &'static str,
+    tag: Tag,
+    children: Vec<Node>,
+}
+
+/// What an opening tag becomes.
+enum Parsed {
+    Open(&'static str, Tag),
+    Leaf(Text),
+    Reset,
+}
+
+struct Parser<'a> {
+    markup: &'a str,
+    placeholders: &'a HashMap<&'a str, Text>,
+    pos: usize,
+}
+
+/// A tag as it is written.
+struct RawTag {
+    closing: bool,
+    name: String,
+    args: Vec<String>,
+    span: Range<usize>,
+}
+
+impl Parser<'_> {
+    fn parse(mut self) -> Result<Vec<Node>, MarkupError> {
+        let mut root = vec![];
+        let mut stack: Vec<OpenTag> = vec![];
+        let mut text = String::new();
+
+        while let Some(c) = self.markup[self.pos..].chars().next() {
+            let rest = &self.markup[self.pos + c.len_utf8()..];
+
+            match c {
+                '\\' if rest.starts_with(['<', '\\']) => {
+                    text.push(rest.as_bytes()[0] as char);
+                    self.pos += 2;
+                }
+                '<' if rest.starts_with(|c: char| {
+                    c.is_ascii_alphabetic() || matches!(c, '/' | '!' | '#' | '_')
+                }) =>
+                {
+                    let raw = self.lex_tag()?;
+
+                    let children = match stack.last_mut() {
+                        Some(open) => &mut open.children,
+                        None => &mut root,
+                    };
+
+                    if !text.is_empty() {
+                        children.push(Node::Text(std::mem::take(&mut text)));
+                    }
+
+                    if raw.closing {
+                        let name = closing_name(&raw.name);
+
+                        let Some(idx) = stack.iter().rposition(|open| Some(open.name) == name)
+                        else {
+                            return Err(MarkupError {
+                                span: raw.span,
+                                kind: MarkupErrorKind::UnmatchedClosingTag(raw.name),
+                            });
+                        };
+
+                        close_tags(&mut stack, &mut root, idx);
+                        continue;
+                    }
+
+                    match self.resolve(raw)? {
+                        Parsed::Open(name, tag) => stack.push(OpenTag {
+                            name,
+                            tag,
+                            children: vec![],
+                        }),
+                        Parsed::Leaf(txt) => children.push(Node::Leaf(txt)),
+                        Parsed::Reset => close_tags(&mut stack, &mut root, 0),
+                    }
+                }
+                _ => {
+                    text.push(c);
+                    self.pos += c.len_utf8();
+                }
+            }
+        }
+
+        let children = match stack.last_mut() {
+            Some(open) => &mut open.children,
+            None => &mut root,
+        };
+
+        if !text.is_empty() {
+            children.push(Node::Text(text));
+        }
+
+        close_tags(&mut stack, &mut root, 0);
+
+        Ok(root)
+    }
+
+    /// Reads the tag at the current position, which must be a `<`.
+    fn lex_tag(&mut self) -> Result<RawTag, MarkupError> {
+        let start = self.pos;
+        let mut chars = self.markup[start + 1..].char_indices();
+
+        let closing = self.markup[start + 1..].starts_with('/');
+        if closing {
+            chars.next();
+        }
+
+        let mut parts = vec![];
+        let mut part = String::new();
+        let mut quote = None;
+
+        loop {
+            let Some((i, c)) = chars.next() else {
+                return Err(MarkupError {
+                    span: start..self.markup.len(),
+                    kind: if quote.is_some() {
+                        MarkupErrorKind::UnterminatedQuote
+                    } else {
+                        MarkupErrorKind::UnterminatedTag
+                    },
+                });
+            };
+
+            match (quote, c) {
+                (Some(q), '\\') if chars.as_str().starts_with([q, '\\']) => {
+                    part.push(chars.next().unwrap().1);
+                }
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), c) => part.push(c),
+                (None, '\'' | '"') if part.is_empty() => quote = Some(c),
+                (None, ':') => parts.push(std::mem::take(&mut part)),
+                (None, '>') => {
+                    parts.push(part);
+                    self.pos = start + 1 + i + 1;
+                    break;
+                }
+                (None, c) => part.push(c),
+            }
+        }
+
+        let mut parts = parts.into_iter();
+
+        Ok(RawTag {
+            closing,
+            name: parts.next().unwrap(),
+            args: parts.collect(),
+            span: start..self.pos,
+        })
+    }
+
+    fn resolve(&self, raw: RawTag) -> Result<Parsed, MarkupError> {
+        let RawTag {
+            name, args, span, ..
+        } = raw;
+
+        let invalid = |reason: &str| MarkupError {
+            span: span.clone(),
+            kind: MarkupErrorKind::InvalidArguments {
+                tag: name.clone(),
+                reason: reason.into(),
+            },
+        };
+
+        let no_args = |parsed: Parsed| {
+            if args.is_empty() {
+                Ok(parsed)
+            } else {
+                Err(invalid("expected no arguments"))
+            }
+        };
+
+        if let Some(txt) = self.placeholders.get(name.as_str()) {
+            return no_args(Parsed::Leaf(txt.clone()));
+        }
+
+        let lower = name.to_ascii_lowercase();
+        let (negated, decoration) = match lower.strip_prefix('!') {
+            Some(decoration) => (true, decoration),
+            None => (false, lower.as_str()),
+        };
+
+        if let Some(name) = decoration_name(decoration) {
+            let tag = match name {
+                "bold" => Tag::Bold(!negated),
+                "italic" => Tag::Italic(!negated),
+                "underlined" => Tag::Underlined(!negated),
+                "strikethrough" => Tag::Strikethrough(!negated),
+                _ => Tag::Obfuscated(!negated),
+            };
+
+            return no_args(Parsed::Open(name, tag));
+        }
+
+        if let Some(color) = parse_color(&lower) {
+            return no_args(Parsed::Open("color", Tag::Color(color)));
+        }
+
+        match lower.as_str() {
+            "color" | "colour" | "c" => match args.as_slice() {
+                [color] => match parse_color(&color.to_ascii_lowercase()) {
+                    Some(color) => Ok(Parsed::Open("color", Tag::Color(color))),
+                    None => Err(invalid("expected a color name or hex color")),
+                },
+                _ => Err(invalid("expected a color")),
+            },
+            "click" => {
+                let [action, value @ ..] = args.as_slice() else {
+                    return Err(invalid("expected an action and a value"));
+                };
+
+                if value.is_empty() {
+                    return Err(invalid("expected an action and a value"));
+                }
+
+                let value = value.join(":");
+
+                let event = match action.as_str() {
+                    "open_url" => ClickEvent::OpenUrl(value.into()),
+                    "open_file" => ClickEvent::OpenFile(value.into()),
+                    "run_command" => ClickEvent::RunCommand(value.into()),
+                    "suggest_command" => ClickEvent::SuggestCommand(value.into()),
+                    "change_page" => match value.parse() {
+                        Ok(page) => ClickEvent::ChangePage(page),
+                        Err(_) => return Err(invalid("expected a page number")),
+                    },
+                    "copy_to_clipboard" => ClickEvent::CopyToClipboard(value.into()),
+                    _ => return Err(invalid("unknown click action")),
+                };
+
+                Ok(Parsed::Open("click", Tag::Click(event)))
+            }
+            "hover" => match args.as_slice() {
+                [action, value @ ..] if action == "show_text" && !value.is_empty() => {
+                    match Text::from_markup(&value.join(":"), self.placeholders) {
+                        Ok(txt) => Ok(Parsed::Open("hover", Tag::Hover(txt))),
+                        Err(e) => Err(invalid(&format!("invalid hover text: {e}"))),
+                    }
+                }
+                [action, ..] if action != "show_text" => Err(invalid("unsupported hover action")),
+                _ => Err(invalid("expected `show_text` and the text")),
+            },
+            "insert" | "insertion" if !args.is_empty() => {
+                Ok(Parsed::Open("insert", Tag::Insert(args.join(":"))))
+            }
+            "font" if !args.is_empty() => match Ident::new(args.join(":")) {
+                Ok(font) => Ok(Parsed::Open("font", Tag::Font(font.into()))),
+                Err(_) => Err(invalid("expected a resource identifier")),
+            },
+            "insert" | "insertion" | "font" => Err(invalid("expected a value")),
+            "gradient" => {
+                let colors = args
+                    .iter()
+                    .map(|arg| parse_color(&arg.to_ascii_lowercase()))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| invalid("expected color names or hex colors"))?;
+
+                match colors.len() {
+                    0 => Ok(Parsed::Open(
+                        "gradient",
+                        Tag::Gradient(vec![Color::WHITE, Color::BLACK]),
+                    )),
+                    1 => Err(invalid("expected at least two colors")),
+                    _ => Ok(Parsed::Open("gradient", Tag::Gradient(colors))),
+                }
+            }
+            "rainbow" => {
+                let arg = match args.as_slice() {
+                    [] => "",
+                    [arg] => arg.as_str(),
+                    _ => return Err(invalid("expected at most one argument")),
+                };
+
+                let (reversed, phase) = match arg.strip_prefix('!') {
+                    Some(phase) => (true, phase),
+                    None => (false, arg),
+                };
+
+                let phase = match phase {
+                    "" => 0,
+                    phase => phase
+                        .parse()
+                        .map_err(|_| invalid("expected `!` and/or a phase"))?,
+                };
+
+                Ok(Parsed::Open("rainbow", Tag::Rainbow { reversed, phase }))
+            }
+            "lang" | "tr" | "translate" => {
+                let [key, slots @ ..] = args.as_slice() else {
+                    return Err(invalid("expected a translation key"));
+                };
+
+                let with = slots
+                    .iter()
+                    .map(|slot| Text::from_markup(slot, self.placeholders))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| invalid(&format!("invalid translation argument: {e}")))?;
+
+                Ok(Parsed::Leaf(Text::translate(key.clone(), with)))
+            }
+            "lang_or" | "tr_or" | "translate_or" => {
+                let [key, fallback, slots @ ..] = args.as_slice() else {
+                    return Err(invalid("expected a translation key and fallback"));
+                };
+
+                let with = slots
+                    .iter()
+                    .map(|slot| Text::from_markup(slot, self.placeholders))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| invalid(&format!("invalid translation argument: {e}")))?;
+
+                Ok(Parsed::Leaf(Text::translate_with_fallback(
+                    key.clone(),
+                    fallback.clone(),
+                    with,
+                )))
+            }
+            "key" | "keybind" => match args.as_slice() {
+                [keybind] => Ok(Parsed::Leaf(Text::keybind(keybind.clone()))),
+                _ => Err(invalid("expected a keybind")),
+            },
+            "newline" | "br" => no_args(Parsed::Leaf(Text::text("\n"))),
+            "reset" => no_args(Parsed::Reset),
+            _ => Err(MarkupError {
+                span,
+                kind: MarkupErrorKind::UnknownTag(name),
+            }),
+        }
+    }
+}
+
+/// Closes the open tags from `idx` onwards.
+fn close_tags(stack: &mut Vec<OpenTag>, root: &mut Vec<Node>, idx: usize) {
+    while stack.len() > idx {
+        let open = stack.pop().unwrap();
+
+        let children = match stack.last_mut() {
+            Some(parent) => &mut parent.children,
+            None => &mut *root,
+        };
+
+        children.push(Node::Tag {
+            tag: open.tag,
+            children: open.children,
+        });
+    }
+}
+
+fn decoration_name(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "bold" | "b" => "bold",
+        "italic" | "i" | "em" => "italic",
+        "underlined" | "u" => "underlined",
+        "strikethrough" | "st" => "strikethrough",
+        "obfuscated" | "obf" => "obfuscated",
+        _ => return None,
+    })
+}
+
+/// The name of the open tag that a closing tag closes.
+fn closing_name(name: &str) -> Option<&'static str> {
+    let name = name.to_ascii_lowercase();
+    let name = name.strip_prefix('!').unwrap_or(&name);
+
+    if let Some(name) = decoration_name(name) {
+        return Some(name);
+    }
+
+    if parse_color(name).is_some() {
+        return Some("color");
+    }
+
+    Some(match name {
+        "color" | "colour" | "c" => "color",
+        "insert" | "insertion" => "insert",
+        "click" => "click",
+        "hover" => "hover",
+        "font" => "font",
+        "gradient" => "gradient",
+        "rainbow" => "rainbow",
+        _ => return None,
+    })
+}
+
+fn parse_color(name: &str) -> Option<Color> {
+    match name {
+        "grey" => Some(Color::GRAY),
+        "dark_grey" => Some(Color::DARK_GRAY),
+        name => color_from_str(name),
+    }
+}
+
+fn color_name(color: Color) -> Option<&'static str> {
+    Some(match color {
+        Color::BLACK => "black",
+        Color::DARK_BLUE => "dark_blue",
+        Color::DARK_GREEN => "dark_green",
+        Color::DARK_AQUA => "dark_aqua",
+        Color::DARK_RED => "dark_red",
+        Color::DARK_PURPLE => "dark_purple",
+        Color::GOLD => "gold",
+        Color::GRAY => "gray",
+        Color::DARK_GRAY => "dark_gray",
+        Color::BLUE => "blue",
+        Color::GREEN => "green",
+        Color::AQUA => "aqua",
+        Color::RED => "red",
+        Color::LIGHT_PURPLE => "light_purple",
+        Color::YELLOW => "yellow",
+        Color::WHITE => "white",
+        _ => return None,
+    })
+}
+
+/// Colors the characters inside a gradient or rainbow.
+struct Paint {
+    tag: Tag,
+    len: usize,
+    idx: usize,
+}
+
+impl Paint {
+    fn next_color(&mut self) -> Color {
+        let idx = self.idx;
+        self.idx += 1;
+
+        match &self.tag {
+            Tag::Gradient(colors) => gradient::gradient_color(colors, idx, self.len),
+            Tag::Rainbow { reversed, phase } => {
+                let idx = if *reversed { self.len - 1 - idx } else { idx };
+
+                gradient::rainbow_color(idx, self.len, 1.0, 1.0, *phase as f32 / self.len as f32)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Adds the characters of `text` to `texts` in their colors.
+    fn paint(&mut self, text: &str, texts: &mut Vec<Text>) {
+        let mut run = String::new();
+        let mut run_color = Color::WHITE;
+
+        for c in text.chars() {
+            let color = self.next_color();
+
+            if color != run_color && !run.is_empty() {
+                texts.push(Text::text(std::mem::take(&mut run)).color(run_color));
+            }
+
+            run_color = color;
+            run.push(c);
+        }
+
+        if !run.is_empty() {
+            texts.push(Text::text(run).color(run_color));
+        }
+    }
+}
+
+/// The number of characters colored by a gradient or rainbow.
+fn painted_len(nodes: &[Node]) -> usize {
+    nodes
+        .iter()
+        .map(|node| match node {
+            Node::Text(text) => text.chars().count(),
+            Node::Leaf(_) => 0,
+            Node::Tag {
+                tag: Tag::Color(_) | Tag::Gradient(_) | Tag::Rainbow { .. },
+                ..
+            } => 0,
+            Node::Tag { children, .. } => painted_len(children),
+        })
+        .sum()
+}
+
+fn build(nodes: Vec<Node>, mut paint: Option<&mut Paint>) -> Vec<Text> {
+    let mut texts = vec![];
+
+    for node in nodes {
+        match node {
+            Node::Text(text) => match paint.as_deref_mut() {
+                Some(paint) => paint.paint(&text, &mut texts),
+                None => texts.push(Text::text(text)),
+            },
+            Node::Leaf(txt) => texts.push(txt),
+            Node::Tag { tag, children } => {
+                let mut children = match &tag {
+                    Tag::Color(_) => build(children, None),
+                    Tag::Gradient(_) | Tag::Rainbow { .. } => {
+                        let mut paint = Paint {
+                            tag: tag.clone(),
+                            len: painted_len(&children),
+                            idx: 0,
+                        };
+
+                        build(children, Some(&mut paint))
+                    }
+                    _ => build(children, paint.as_deref_mut()),
+                };
+
+                if let Tag::Gradient(_) | Tag::Rainbow { .. } = tag {
+                    texts.extend(children);
+                    continue;
+                }
+
+                // Add the formatting to the first child if that doesn't change the
+                // formatting of the others.
+                if children.len() == 1 || children.first().is_some_and(is_plain) {
+                    let mut first = children.remove(0);
+
+                    if tag.apply(&mut first) {
+                        first.0.extra.extend(children);
+                        texts.push(first);
+                        continue;
+                    }
+
+                    children.insert(0, first);
+                }
+
+                let mut txt = Text::default();
+                tag.apply(&mut txt);
+                txt.0.extra = children;
+                texts.push(txt);
+            }
+        }
+    }
+
+    texts
+}
+
+fn write_markup(txt: &Text, result: &mut String) {
+    let inner = &txt.0;
+    let mut closing = vec![];
+
+    let mut open = |result: &mut String, tag: String, close: &str| {
+        result.push('<');
+        result.push_str(&tag);
+        result.push('>');
+        closing.push(close.to_owned());
+    };
+
+    if let Some(color) = inner.color {
+        let name = match color_name(color) {
+            Some(name) => name.to_owned(),
+            None => format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b),
+        };
+
+        open(result, name.clone(), &name);
+    }
+
+    if let Some(font) = &inner.font {
+        open(result, format!("font:{}", quote(font.as_str())), "font");
+    }
+
+    for (value, name) in [
+        (inner.bold, "bold"),
+        (inner.italic, "italic"),
+        (inner.underlined, "underlined"),
+        (inner.strikethrough, "strikethrough"),
+        (inner.obfuscated, "obfuscated"),
+    ] {
+        match value {
+            Some(true) => open(result, name.into(), name),
+            Some(false) => open(result, format!("!{name}"), &format!("!{name}")),
+            None => {}
+        }
+    }
+
+    if let Some(insertion) = &inner.insertion {
+        open(result, format!("insert:{}", quote(insertion)), "insert");
+    }
+
+    if let Some(event) = &inner.click_event {
+        let value = match event {
+            ClickEvent::OpenUrl(value)
+            | ClickEvent::OpenFile(value)
+            | ClickEvent::RunCommand(value)
+            | ClickEvent::SuggestCommand(value)
+            | ClickEvent::CopyToClipboard(value) => Some(value.clone()),
+            ClickEvent::ChangePage(page) => Some(page.to_string().into()),
+            // Unknown actions can't be parsed.
+            ClickEvent::Other(..) => None,
+        };
+
+        if let Some(value) = value {
+            let action = event.action();
+            open(result, format!("click:{action}:{}", quote(&value)), "click");
+        }
+    }
+
+    if let Some(HoverEvent::ShowText(text)) = &inner.hover_event {
+        open(
+            result,
+            format!("hover:show_text:{}", quote(&text.to_markup())),
+            "hover",
+        );
+    }
+
+    match &inner.content {
+        TextContent::Text { text } => {
+            for c in text.chars() {
+                if matches!(c, '<' | '\\') {
+                    result.push('\\');
+                }
+
+                result.push(c);
+            }
+        }
+        TextContent::Translate {
+            translate,
+            with,
+            fallback,
+        } => {
+            match fallback {
+                Some(fallback) => {
+                    write!(result, "<lang_or:{}:{}", quote(translate), quote(fallback)).unwrap()
+                }
+                None => write!(result, "<lang:{}", quote(translate)).unwrap(),
+            }
+
+            for slot in with {
+                write!(result, ":{}", quote(&slot.to_markup())).unwrap();
+            }
+
+            result.push('>');
+        }
+        TextContent::Keybind { keybind } => {
+            write!(result, "<key:{}>", quote(keybind)).unwrap();
+        }
+        // Not representable as markup.
+        TextContent::ScoreboardValue { .. }
+        | TextContent::EntityNames { .. }
+        | TextContent::BlockNbt { .. }
+        | TextContent::EntityNbt { .. }
+        | TextContent::StorageNbt { .. } => {}
+    }
+
+    for child in &inner.extra {
+        write_markup(child, result);
+    }
+
+    for name in closing.iter().rev() {
+        write!(result, "</{name}>").unwrap();
+    }
+}
+
+/// Quotes a tag argument.
+fn quote(arg: &str) -> Cow<'_, str> {
+    if arg.is_empty() || arg.contains(['\'', '"', ':', '>', '\\']) || arg != arg.trim() {
+        let mut quoted = String::from("'");
+
+        for c in arg.chars() {
+            if matches!(c, '\'' | '\\') {
+                quoted.push('\\');
+            }
+
+            quoted.push(c);
+        }
+
+        quoted.push('\'');
+        Cow::Owned(quoted)
+    } else {
+        Cow::Borrowed(arg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ident, translation_key};
+
+    fn parse(markup: &str) -> Result<Text, MarkupError> {
+        Text::from_markup(markup, &HashMap::new())
+    }
+
+    fn error_kind(markup: &str) -> (Range<usize>, MarkupErrorKind) {
+        let e = parse(markup).unwrap_err();
+        (e.span, e.kind)
+    }
+
+    #[test]
+    fn markup_colors_and_decorations() {
+        assert_eq!(parse("plain").unwrap(), "plain".into_text());
+        assert_eq!(parse("").unwrap(), Text::default());
+
+        assert_eq!(parse("<red>red").unwrap(), "red".color(Color::RED));
+        assert_eq!(parse("<grey>grey").unwrap(), "grey".color(Color::GRAY));
+        assert_eq!(
+            parse("<#12ABcd>hex</#12abcd>").unwrap(),
+            "hex".color(Color::new(0x12, 0xab, 0xcd))
+        );
+        assert_eq!(
+            parse("<color:gold>a</color><c:#000000>b</c><colour:blue>c").unwrap(),
+            Text::default()
+                + "a".color(Color::GOLD)
+                + "b".color(Color::BLACK)
+                + "c".color(Color::BLUE)
+        );
+
+        assert_eq!(
+            parse("<b>b</b><i>i</i><em>em</em><u>u</u><st>st</st><obf>obf").unwrap(),
+            Text::default()
+                + "b".bold()
+                + "i".italic()
+                + "em".italic()
+                + "u".underlined()
+                + "st".strikethrough()
+                + "obf".obfuscated()
+        );
+
+        assert_eq!(
+            parse("<italic>a<!italic>b</!italic>c</italic>d").unwrap(),
+            Text::default() + ("a".italic() + "b".not_italic() + "c") + "d"
+        );
+
+        assert_eq!(
+            parse("<red><bold>both</bold></red>").unwrap(),
+            "both".bold().color(Color::RED)
+        );
+
+        // Inner colors override outer colors.
+        assert_eq!(
+            parse("<red>a<blue>b</blue>c").unwrap(),
+            ("a".into_text() + "b".color(Color::BLUE) + "c").color(Color::RED)
+        );
+        assert_eq!(
+            parse("<red><blue>b</blue></red>").unwrap(),
+            Text::default().color(Color::RED) + "b".color(Color::BLUE)
+        );
+    }
+
+    #[test]
+    fn markup_escapes() {
+        assert_eq!(parse("a < b").unwrap(), "a < b".into_text());
+        assert_eq!(parse("1<2").unwrap(), "1<2".into_text());
+        assert_eq!(parse(r"\<red>").unwrap(), "<red>".into_text());
+        assert_eq!(
+            parse(r"\\<red>x").unwrap(),
+            "\\".into_text() + "x".color(Color::RED)
+        );
+        assert_eq!(parse(r"a\b").unwrap(), r"a\b".into_text());
+        assert_eq!(parse("<red>héllo ✓").unwrap(), "héllo ✓".color(Color::RED));
+    }
+
+    #[test]
+    fn markup_events() {
+        assert_eq!(
+            parse("<click:open_url:https://valencemc.rs>link").unwrap(),
+            "link".on_click_open_url("https://valencemc.rs")
+        );
+        assert_eq!(
+            parse("<click:run_command:'/say <hi>'>run</click>").unwrap(),
+            "run".on_click_run_command("/say <hi>")
+        );
+        assert_eq!(
+            parse("<click:change_page:3>page").unwrap(),
+            "page".on_click_change_page(3)
+        );
+        assert_eq!(
+            parse(r#"<hover:show_text:"<red>It\"s red">hover"#).unwrap(),
+            "hover".on_hover_show_text("It\"s red".color(Color::RED))
+        );
+        assert_eq!(
+            parse("<insert:'a:b'><font:minecraft:uniform>x").unwrap(),
+            "x".font(ident!("minecraft:uniform")).insertion("a:b")
+        );
+    }
+
+    #[test]
+    fn markup_leaves_and_placeholders() {
+        let placeholders = HashMap::from([("player", "Notch".color(Color::GOLD))]);
+
+        assert_eq!(
+            Text::from_markup("<red>Hi <player>!", &placeholders).unwrap(),
+            ("Hi ".into_text() + "Notch".color(Color::GOLD) + "!").color(Color::RED)
+        );
+        assert_eq!(
+            Text::from_markup("<lang:chat.type.text:'<player>':hi>", &placeholders).unwrap(),
+            Text::translate(
+                translation_key::CHAT_TYPE_TEXT,
+                ["Notch".color(Color::GOLD), "hi".into()]
+            )
+        );
+        assert_eq!(
+            Text::from_markup("<lang_or:my.greeting:'Hi %s':'<player>'>", &placeholders).unwrap(),
+            Text::translate_with_fallback("my.greeting", "Hi %s", ["Notch".color(Color::GOLD)])
+        );
+        assert_eq!(
+            parse("a<br>b<key:key.jump>").unwrap(),
+            "a".into_text() + "\n" + "b" + Text::keybind("key.jump")
+        );
+
+        // Placeholders aren't tags without them.
+        assert_eq!(
+            error_kind("<player>"),
+            (0..8, MarkupErrorKind::UnknownTag("player".into()))
+        );
+    }
+
+    #[test]
+    fn markup_unclosed_and_reset() {
+        assert_eq!(
+            parse("<red><bold>both").unwrap(),
+            "both".bold().color(Color::RED)
+        );
+        // Closing a tag closes the tags opened after it.
+        assert_eq!(
+            parse("<red>a<bold>b</red>c").unwrap(),
+            Text::default() + ("a".into_text() + "b".bold()).color(Color::RED) + "c"
+        );
+        assert_eq!(
+            parse("<red>a<bold>b<reset>c").unwrap(),
+            Text::default() + ("a".into_text() + "b".bold()).color(Color::RED) + "c"
+        );
+        assert_eq!(
+            parse("<red></red>").unwrap(),
+            Text::default().color(Color::RED)
+        );
+    }
+
+    #[test]
+    fn markup_gradients() {
+        let txt = parse("<gradient:#000000:#ff0000:#ffff00>abcde").unwrap();
+        let colors: Vec<_> = txt.0.extra.iter().map(|t| t.0.color.unwrap()).collect();
+
+        assert_eq!(txt.0.color, None);
+        assert_eq!(
+            colors,
+            [
+                Color::new(0, 0, 0),
+                Color::new(0xbc, 0, 0),
+                Color::new(0xff, 0, 0),
+                Color::new(0xff, 0xbc, 0),
+                Color::new(0xff, 0xff, 0),
+            ]
+        );
+
+        // Text with its own color is skipped, and styled text is colored.
+        assert_eq!(
+            parse("<gradient:black:white>a<red>b</red><bold>c</bold>").unwrap(),
+            Text::default()
+                + "a".color(Color::BLACK)
+                + "b".color(Color::RED)
+                + "c".color(Color::WHITE).bold()
+        );
+
+        let rainbow = parse("<rainbow>abc").unwrap();
+        let reversed = parse("<rainbow:!>cba").unwrap();
+
+        assert_eq!(rainbow.0.extra[0], "a".color(Color::new(0xff, 0, 0)));
+        assert_eq!(rainbow.0.extra[1], "b".color(Color::new(0, 0xff, 0)));
+        assert_eq!(rainbow.0.extra[2], "c".color(Color::new(0, 0, 0xff)));
+        assert_eq!(reversed.0.extra[2].0.color, Some(Color::new(0xff, 0, 0)));
+        assert_eq!(
+            parse("<rainbow:1>abc").unwrap().0.extra[0].0.color,
+            Some(Color::new(0, 0xff, 0))
+        );
+    }
+
+    #[test]
+    fn markup_errors() {
+        assert_eq!(
+            error_kind("ab<red"),
+            (2..6, MarkupErrorKind::UnterminatedTag)
+        );
+        assert_eq!(
+            error_kind("<click:run_command:'/say>"),
+            (0..25, MarkupErrorKind::UnterminatedQuote)
+        );
+        assert_eq!(
+            error_kind("x<foo:bar>"),
+            (1..10, MarkupErrorKind::UnknownTag("foo".into()))
+        );
+        assert_eq!(
+            error_kind("<red>x</bold>"),
+            (6..13, MarkupErrorKind::UnmatchedClosingTag("bold".into()))
+        );
+        assert_eq!(
+            error_kind("</bold>"),
+            (0..7, MarkupErrorKind::UnmatchedClosingTag("bold".into()))
+        );
+
+        for (markup, span) in [
+            ("<bold:true>", 0..11),
+            ("<color:nope>", 0..12),
+            ("<click:explode:now>", 0..19),
+            ("<click:change_page:x>", 0..21),
+            ("<hover:show_item:stone>", 0..23),
+            ("<hover:show_text:'<nope>'>", 0..26),
+            ("<gradient:red>", 0..14),
+            ("<rainbow:abc>", 0..13),
+            ("<key>", 0..5),
+            ("a<insert>", 1..9),
+            ("<font:Bad Font>", 0..15),
+        ] {
+            let (err_span, kind) = error_kind(markup);
+
+            assert_eq!(err_span, span, "{markup}");
+            assert!(
+                matches!(kind, MarkupErrorKind::InvalidArguments { .. }),
+                "{markup}: {kind:?}"
+            );
+        }
+
+        assert_eq!(
+            parse("<gradient:red>").unwrap_err().to_string(),
+            "invalid arguments for tag `gradient`: expected at least two colors at bytes 0..14"
+        );
+    }
+
+    #[test]
+    fn markup_round_trip() {
+        let texts = [
+            "plain".into_text(),
+            "Hello ".color(Color::RED) + "world".bold() + "!",
+            Text::default() + "a".color(Color::new(0x12, 0xab, 0xcd)) + "b".not_italic(),
+            "x".underlined()
+                .strikethrough()
+                .obfuscated()
+                .font(ident!("uniform")),
+            "click"
+                .on_click_copy_to_clipboard("it's: <here>")
+                .insertion(""),
+            "page".on_click_change_page(7),
+            "hover".on_hover_show_text("<red>".color(Color::RED).italic()),
+            Text::translate(translation_key::CHAT_TYPE_TEXT, ["a".bold(), "b\\".into()])
+                .color(Color::GRAY),
+            Text::translate_with_fallback("my.key", "Fallback: %s", ["x".italic()]),
+            Text::keybind("key.jump").bold(),
+        ];
+
+        for txt in texts {
+            let markup = txt.to_markup();
+            assert_eq!(parse(&markup).unwrap(), txt, "{markup}");
+        }
+
+        // Not everything parses back to the same structure, but the markup is the
+        // same.
+        let markup = "<gradient:red:blue>ab<bold>cd</bold></gradient> <rainbow>ef";
+        let written = parse(markup).unwrap().to_markup();
+
+        assert_eq!(parse(&written).unwrap().to_markup(), written);
+        assert_eq!(Text::score("a", "b", None).to_markup(), "");
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+
+use super::{Text, TextContent};
+
+/// The width of a space in the default font, in pixels.
+const SPACE_WIDTH: u32 = 4;
+
+impl Text {
+    /// Returns the text that is shown to players, without any formatting.
+    ///
+    /// Translations are looked up in `translations` (such as the contents of
+    /// `en_us.json`), and are shown as their fallback or key if they aren't
+    /// found, like clients do. Keybinds are shown as their identifier. Scores,
+    /// entity names and NBT values are empty unless they were
+    /// [resolved](Text::resolve).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// use valence_core::text::{Color, Text, TextFormat};
+    ///
+    /// let txt = "Hello ".color(Color::RED) + Text::translate("my.greeting", ["Notch".bold()]) + "!";
+    ///
+    /// assert_eq!(txt.to_plain(None), "Hello my.greeting!");
+    ///
+    /// let translations = HashMap::from([("my.greeting".into(), "dear %s".into())]);
+    /// assert_eq!(txt.to_plain(Some(&translations)), "Hello dear Notch!");
+    /// ```
+    pub fn to_plain(&self, translations: Option<&HashMap<String, String>>) -> String {
+        let mut result = String::new();
+        self.visit_plain(translations, false, &mut |s, _| result.push_str(s));
+        result
+    }
+
+    /// Returns an estimate of the width of the text in pixels at the default
+    /// GUI scale, using the widths of the characters in the default font.
+    /// Bold characters are one pixel wider. Obfuscated text has the width of
+    /// the characters it hides.
+    ///
+    /// Characters outside of ASCII are assumed to be as wide as most letters,
+    /// and custom fonts are ignored. See [`Text::to_plain`] for how
+    /// `translations` is used.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valence_core::text::{Text, TextFormat};
+    ///
+    /// assert_eq!("Hello".into_text().width_pixels(None), 24);
+    /// assert_eq!("Hello".bold().width_pixels(None), 29);
+    /// ```
+    pub fn width_pixels(&self, translations: Option<&HashMap<String, String>>) -> u32 {
+        let mut width = 0;
+
+        self.visit_plain(translations, false, &mut |s, bold| {
+            width += s.chars().map(|c| char_width(c) + bold as u32).sum::<u32>();
+        });
+
+        width
+    }
+
+    /// Adds spaces in front of the text so that it is centered in a line
+    /// `total_width` pixels wide, such as the 270 pixel wide server list MOTD
+    /// or the 320 pixel wide chat. The text is returned as it is if it doesn't
+    /// fit. See [`Text::to_plain`] for how `translations` is used.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valence_core::text::{Text, TextFormat};
+    ///
+    /// let txt = "Hello".into_text().pad_center(40, None);
+    ///
+    /// assert_eq!(txt.to_plain(None), "  Hello");
+    /// ```
+    pub fn pad_center(
+        self,
+        total_width: u32,
+        translations: Option<&HashMap<String, String>>,
+    ) -> Text {
+        let width = self.width_pixels(translations);
+        let spaces = total_width.saturating_sub(width) / 2 / SPACE_WIDTH;
+
+        if spaces == 0 {
+            return self;
+        }
+
+        Text::text(" ".repeat(spaces as usize)) + self
+    }
+
+    /// Calls `f` with each piece of visible text and whether it is bold.
+    fn visit_plain(
+        &self,
+        translations: Option<&HashMap<String, String>>,
+        parent_bold: bool,
+        f: &mut impl FnMut(&str, bool),
+    ) {
+        let bold = self.0.bold.unwrap_or(parent_bold);
+
+        match &self.0.content {
+            TextContent::Text { text } => f(text, bold),
+            TextContent::Translate {
+                translate,
+                with,
+                fallback,
+            } => {
+                let format = translations
+                    .and_then(|t| t.get(translate.as_ref()))
+                    .map(|format| format.as_str())
+                    .or(fallback.as_deref());
+
+                match format {
+                    Some(format) => visit_translation(format, with, translations, bold, f),
+                    None => f(translate, bold),
+                }
+            }
+            TextContent::ScoreboardValue { score } => {
+                if let Some(value) = &score.value {
+                    f(value, bold);
+                }
+            }
+            TextContent::Keybind { keybind } => f(keybind, bold),
+            TextContent::EntityNames { .. }
+            | TextContent::BlockNbt { .. }
+            | TextContent::EntityNbt { .. }
+            | TextContent::StorageNbt { .. } => {}
+        }
+
+        for child in &self.0.extra {
+            child.visit_plain(translations, bold, f);
+        }
+    }
+}
+
+/// Fills in the `%s` and `%1$s` slots of a translation, like vanilla does.
+fn visit_translation(
+    format: &str,
+    with: &[Text],
+    translations: Option<&HashMap<String, String>>,
+    bold: bool,
+    f: &mut impl FnMut(&str, bool),
+) {
+    let mut next_arg = 0;
+    let mut rest = format;
+
+    while let Some(idx) = rest.find('%') {
+        f(&rest[..idx], bold);
+        rest = &rest[idx + 1..];
+
+        if let Some(after) = rest.strip_prefix('%') {
+            f("%", bold);
+            rest = after;
+            continue;
+        }
+
+        let arg = if let Some(after) = rest.strip_prefix('s') {
+            rest = after;
+            next_arg += 1;
+            Some(next_arg - 1)
+        } else if let Some((num, after)) = rest.split_once("$s") {
+            match num.parse::<usize>() {
+                Ok(num) if num > 0 => {
+                    rest = after;
+                    Some(num - 1)
+                }
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        match arg {
+            Some(arg) => {
+                if let Some(arg) = with.get(arg) {
+                    arg.visit_plain(translations, bold, f);
+                }
+            }
+            // Not a slot.
+            None => f("%", bold),
+        }
+    }
+
+    f(rest, bold);
+}
+
+/// The advance of a character in the default font, in pixels, including the
+/// pixel between characters.
+fn char_width(c: char) -> u32 {
+    match c {
+        ' ' => SPACE_WIDTH,
+        '!' | '\'' | ',' | '.' | ':' | ';' | 'i' | '|' => 2,
+        '`' | 'l' => 3,
+        '"' | '(' | ')' | '*' | 'I' | '[' | ']' | 't' | '{' | '}' => 4,
+        '<' | '>' | 'f' | 'k' => 5,
+        '@' | '~' => 7,
+        '\n' => 0,
+        _ => 6,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::{Color, TextFormat};
+
+    #[test]
+    fn plain_text() {
+        let txt = "a".color(Color::RED)
+            + ("b".into_text() + Text::keybind("key.jump"))
+            + Text::score("@p", "kills", None)
+            + Text::score("@p", "kills", Some("3".into()))
+            + Text::selector("@a", None);
+
+        assert_eq!(txt.to_plain(None), "abkey.jump3");
+
+        let translations = HashMap::from([
+            ("a".into(), "%2$s, %1$s and %s%% %x".into()),
+            ("b".into(), "[%s]".into()),
+        ]);
+
+        let txt = Text::translate("a", [Text::translate("b", ["x".into()]), "y".into()]);
+
+        assert_eq!(txt.to_plain(None), "a");
+        assert_eq!(txt.to_plain(Some(&translations)), "y, [x] and [x]% %x");
+    }
+
+    #[test]
+    fn text_widths() {
+        // Widths from the glyphs of the vanilla default font.
+        for (text, width) in [
+            ("", 0),
+            ("Hello", 24),
+            ("Hello, World!", 59),
+            ("illicit", 22),
+            ("[VIP] Notch", 56),
+            ("0123456789", 60),
+        ] {
+            assert_eq!(text.into_text().width_pixels(None), width, "{text}");
+        }
+
+        // Bold is inherited by children, and obfuscated text keeps its width.
+        let txt = "ab".bold() + "c" + "d".not_bold() + "ef".obfuscated();
+        assert_eq!(txt.width_pixels(None), 7 + 7 + 7 + 6 + 7 + 6);
+    }
+
+    #[test]
+    fn pad_center() {
+        assert_eq!(
+            "Hello".into_text().pad_center(64, None).to_plain(None),
+            "     Hello"
+        );
+        // Odd leftover pixels round down.
+        assert_eq!(
+            "Hello".into_text().pad_center(35, None).to_plain(None),
+            " Hello"
+        );
+        assert_eq!(
+            "Hello".into_text().pad_center(10, None).to_plain(None),
+            "Hello"
+        );
+
+        let padded = "Hi".bold().pad_center(30, None);
+        assert_eq!(padded, Text::text("  ") + "Hi".bold());
+    }
+}
@@ -0,0 +1,263 @@
+use std::borrow::Cow;
+
+use super::{Color, Text, TextContent, TextFormat};
+use crate::ident::Ident;
+
+/// Provides the values of score, entity name and NBT components to
+/// [`Text::resolve`]. Components are left as they are when a method returns
+/// `None`, which is what the default implementations do.
+#[allow(unused_variables)]
+pub trait ResolveContext {
+    /// Returns the score of `name` in `objective`.
+    fn score(&self, name: &str, objective: &str) -> Option<String> {
+        None
+    }
+
+    /// Returns the names of the entities found by `selector`.
+    fn entity_names(&self, selector: &str) -> Option<Vec<Text>> {
+        None
+    }
+
+    /// Returns the NBT values at `path` in `source`. If `interpret` is `true`,
+    /// the values are text components in JSON and should be returned as the
+    /// text they contain. Otherwise, they should be returned as SNBT.
+    fn nbt(&self, source: NbtSource, path: &str, interpret: bool) -> Option<Vec<Text>> {
+        None
+    }
+}
+
+/// Where the values of an NBT component come from.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum NbtSource<'a> {
+    /// The block entity at the given coordinates.
+    Block(&'a str),
+    /// The entities found by the given selector.
+    Entity(&'a str),
+    /// The command storage with the given identifier.
+    Storage(&'a Ident<String>),
+}
+
+impl Text {
+    /// Replaces the score, entity name and NBT components of this text object
+    /// and its children with their values from `ctx`, like vanilla does before
+    /// sending text from commands. Clients can't resolve these components
+    /// themselves.
+    ///
+    /// Scores become plain text. Entity names and NBT values are joined with
+    /// the separator of the component, which is `", "` in gray for entity
+    /// names and `", "` for NBT values by default. The formatting and children
+    /// of the components are kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valence_core::text::{ResolveContext, Text};
+    ///
+    /// struct Scores;
+    ///
+    /// impl ResolveContext for Scores {
+    ///     fn score(&self, name: &str, objective: &str) -> Option<String> {
+    ///         (objective == "kills").then(|| format!("{}", name.len()))
+    ///     }
+    /// }
+    ///
+    /// let txt = Text::text("Kills: ") + Text::score("Notch", "kills", None);
+    ///
+    /// assert_eq!(txt.resolve(&Scores).to_string(), "Kills: 5");
+    /// ```
+    pub fn resolve(&self, ctx: &impl ResolveContext) -> Text {
+        let mut txt = self.clone();
+        resolve_in_place(&mut txt, ctx);
+        txt
+    }
+}
+
+fn resolve_in_place(txt: &mut Text, ctx: &impl ResolveContext) {
+    for child in &mut txt.0.extra {
+        resolve_in_place(child, ctx);
+    }
+
+    let values = match &mut txt.0.content {
+        TextContent::Translate { with, .. } => {
+            for arg in with {
+                resolve_in_place(arg, ctx);
+            }
+
+            return;
+        }
+        TextContent::ScoreboardValue { score } => {
+            let value = match &score.value {
+                Some(value) => Some(value.to_string()),
+                None => ctx.score(&score.name, &score.objective),
+            };
+
+            if let Some(value) = value {
+                txt.0.content = TextContent::Text { text: value.into() };
+            }
+
+            return;
+        }
+        TextContent::EntityNames {
+            selector,
+            separator,
+        } => ctx.entity_names(selector).map(|names| {
+            let separator = separator.clone().unwrap_or_else(|| ", ".color(Color::GRAY));
+
+            (names, separator)
+        }),
+        TextContent::BlockNbt {
+            block,
+            nbt,
+            interpret,
+            separator,
+        } => ctx
+            .nbt(NbtSource::Block(block), nbt, interpret.unwrap_or(false))
+            .map(|values| (values, nbt_separator(separator))),
+        TextContent::EntityNbt {
+            entity,
+            nbt,
+            interpret,
+            separator,
+        } => ctx
+            .nbt(NbtSource::Entity(entity), nbt, interpret.unwrap_or(false))
+            .map(|values| (values, nbt_separator(separator))),
+        TextContent::StorageNbt {
+            storage,
+            nbt,
+            interpret,
+            separator,
+        } => ctx
+            .nbt(NbtSource::Storage(storage), nbt, interpret.unwrap_or(false))
+            .map(|values| (values, nbt_separator(separator))),
+        TextContent::Text { .. } | TextContent::Keybind { .. } => None,
+    };
+
+    if let Some((values, separator)) = values {
+        let mut joined = Vec::with_capacity(values.len() * 2 + txt.0.extra.len());
+
+        for (i, value) in values.into_iter().enumerate() {
+            if i > 0 {
+                joined.push(separator.clone());
+            }
+
+            joined.push(value);
+        }
+
+        joined.append(&mut txt.0.extra);
+
+        txt.0.content = TextContent::Text {
+            text: Cow::Borrowed(""),
+        };
+        txt.0.extra = joined;
+    }
+}
+
+fn nbt_separator(separator: &Option<Text>) -> Text {
+    separator.clone().unwrap_or_else(|| ", ".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ident;
+
+    struct TestContext;
+
+    impl ResolveContext for TestContext {
+        fn score(&self, name: &str, objective: &str) -> Option<String> {
+            (name == "@p" && objective == "kills").then(|| "12".into())
+        }
+
+        fn entity_names(&self, selector: &str) -> Option<Vec<Text>> {
+            (selector == "@a").then(|| vec!["Notch".into(), "jeb_".into()])
+        }
+
+        fn nbt(&self, source: NbtSource, path: &str, interpret: bool) -> Option<Vec<Text>> {
+            match (source, path) {
+                (NbtSource::Block("~ ~-1 ~"), "Items[].id") => Some(vec![
+                    "\"minecraft:stone\"".into(),
+                    "\"minecraft:dirt\"".into(),
+                ]),
+                (NbtSource::Entity("@s"), "CustomName") if interpret => {
+                    Some(vec!["Steve".color(Color::AQUA)])
+                }
+                (NbtSource::Storage(id), "motd") if id.as_str() == "example:chat" => {
+                    Some(vec!["Hi".into()])
+                }
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn resolve_scores_and_names() {
+        let txt = "Kills: ".into_text() + Text::score("@p", "kills", None).bold();
+
+        assert_eq!(
+            txt.resolve(&TestContext),
+            "Kills: ".into_text() + "12".bold()
+        );
+
+        // Unknown scores are left unresolved.
+        let unknown = Text::score("@p", "deaths", None);
+        assert_eq!(unknown.resolve(&TestContext), unknown);
+
+        // Scores with a value use it.
+        assert_eq!(
+            Text::score("x", "y", Some("3".into())).resolve(&TestContext),
+            "3".into_text()
+        );
+
+        let names = Text::selector("@a", None).color(Color::GOLD) + " won!";
+
+        assert_eq!(
+            names.resolve(&TestContext),
+            Text::text("").color(Color::GOLD)
+                + "Notch"
+                + ", ".color(Color::GRAY)
+                + "jeb_"
+                + " won!"
+        );
+        assert_eq!(
+            Text::selector("@a", Some(" & ".into()))
+                .resolve(&TestContext)
+                .to_string(),
+            "Notch & jeb_"
+        );
+    }
+
+    #[test]
+    fn resolve_nbt_and_nested() {
+        assert_eq!(
+            Text::block_nbt("~ ~-1 ~", "Items[].id", None, None)
+                .resolve(&TestContext)
+                .to_string(),
+            "\"minecraft:stone\", \"minecraft:dirt\""
+        );
+        assert_eq!(
+            Text::entity_nbt("@s", "CustomName", Some(true), None).resolve(&TestContext),
+            Text::text("") + "Steve".color(Color::AQUA)
+        );
+        assert_eq!(
+            Text::storage_nbt(ident!("example:chat"), "motd", None, None)
+                .resolve(&TestContext)
+                .to_string(),
+            "Hi"
+        );
+
+        // Translation arguments and children are resolved too.
+        let txt = Text::translate(
+            "commands.scoreboard.players.get.success",
+            [
+                Text::selector("@a", None),
+                Text::score("@p", "kills", None),
+                "kills".into(),
+            ],
+        ) + Text::score("@p", "kills", None);
+
+        assert_eq!(
+            txt.resolve(&TestContext).to_string(),
+            "commands.scoreboard.players.get.success[1=Notch, jeb_, 2=12, 3=kills]12"
+        );
+    }
+}
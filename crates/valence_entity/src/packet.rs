@@ -9,7 +9,7 @@ use valence_core::item::ItemStack;
 use valence_core::protocol::byte_angle::ByteAngle;
 use valence_core::protocol::raw::RawBytes;
 use valence_core::protocol::var_int::VarInt;
-use valence_core::protocol::{packet_id, Decode, Encode, Packet};
+use valence_core::protocol::{packet_id, Decode, Encode, Packet, SharedPacket};
 use valence_nbt::Compound;
 
 #[derive(Clone, PartialEq, Debug, Encode, Decode, Packet)]
@@ -18,6 +18,8 @@ pub struct EntitiesDestroyS2c<'a> {
     pub entity_ids: Cow<'a, [VarInt]>,
 }
 
+impl SharedPacket for EntitiesDestroyS2c<'_> {}
+
 #[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::ENTITY_ANIMATION_S2C)]
 pub struct EntityAnimationS2c {
@@ -25,6 +27,8 @@ pub struct EntityAnimationS2c {
     pub animation: u8,
 }
 
+impl SharedPacket for EntityAnimationS2c {}
+
 #[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::ENTITY_ATTACH_S2C)]
 pub struct EntityAttachS2c {
@@ -82,6 +86,8 @@ pub struct EntityEquipmentUpdateS2c {
     pub equipment: Vec<EquipmentEntry>,
 }
 
+impl SharedPacket for EntityEquipmentUpdateS2c {}
+
 #[derive(Clone, PartialEq, Debug, Encode, Decode)]
 pub struct EquipmentEntry {
     pub slot: i8,
@@ -139,6 +145,8 @@ pub struct MoveRelativeS2c {
     pub on_ground: bool,
 }
 
+impl SharedPacket for MoveRelativeS2c {}
+
 #[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::ROTATE_AND_MOVE_RELATIVE)]
 pub struct RotateAndMoveRelativeS2c {
@@ -149,6 +157,8 @@ pub struct RotateAndMoveRelativeS2c {
     pub on_ground: bool,
 }
 
+impl SharedPacket for RotateAndMoveRelativeS2c {}
+
 #[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::ROTATE)]
 pub struct RotateS2c {
@@ -158,6 +168,8 @@ pub struct RotateS2c {
     pub on_ground: bool,
 }
 
+impl SharedPacket for RotateS2c {}
+
 #[derive(Clone, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::ENTITY_PASSENGERS_SET_S2C)]
 pub struct EntityPassengersSetS2c {
@@ -176,6 +188,8 @@ pub struct EntityPositionS2c {
     pub on_ground: bool,
 }
 
+impl SharedPacket for EntityPositionS2c {}
+
 #[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::ENTITY_SET_HEAD_YAW_S2C)]
 pub struct EntitySetHeadYawS2c {
@@ -183,6 +197,8 @@ pub struct EntitySetHeadYawS2c {
     pub head_yaw: ByteAngle,
 }
 
+impl SharedPacket for EntitySetHeadYawS2c {}
+
 #[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::ENTITY_SPAWN_S2C)]
 pub struct EntitySpawnS2c {
@@ -197,6 +213,8 @@ pub struct EntitySpawnS2c {
     pub velocity: [i16; 3],
 }
 
+impl SharedPacket for EntitySpawnS2c {}
+
 #[derive(Clone, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::ENTITY_STATUS_EFFECT_S2C)]
 pub struct EntityStatusEffectS2c {
@@ -239,6 +257,8 @@ pub struct EntityVelocityUpdateS2c {
     pub velocity: [i16; 3],
 }
 
+impl SharedPacket for EntityVelocityUpdateS2c {}
+
 #[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::EXPERIENCE_ORB_SPAWN_S2C)]
 pub struct ExperienceOrbSpawnS2c {
@@ -247,6 +267,8 @@ pub struct ExperienceOrbSpawnS2c {
     pub count: i16,
 }
 
+impl SharedPacket for ExperienceOrbSpawnS2c {}
+
 #[derive(Clone, PartialEq, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::REMOVE_ENTITY_STATUS_EFFECT_S2C)]
 pub struct RemoveEntityStatusEffectS2c {
@@ -262,6 +284,8 @@ pub struct ItemPickupAnimationS2c {
     pub pickup_item_count: VarInt,
 }
 
+impl SharedPacket for ItemPickupAnimationS2c {}
+
 /// Instructs a client to face an entity.
 #[derive(Copy, Clone, PartialEq, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::LOOK_AT_S2C)]
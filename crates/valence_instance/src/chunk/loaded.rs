@@ -7,6 +7,8 @@ use bevy_ecs::entity::Entity;
 use bevy_ecs::prelude::*;
 use parking_lot::Mutex; // Using nonstandard mutex to avoid poisoning API.
 use valence_biome::BiomeId;
+#[cfg(feature = "validate_block_entities")]
+use valence_block::BlockEntityKind;
 use valence_block::BlockState;
 use valence_core::block_pos::BlockPos;
 use valence_core::chunk_pos::ChunkPos;
@@ -50,6 +52,8 @@ pub struct LoadedChunk {
     changed_biomes: bool,
     /// The global compression threshold.
     compression_threshold: Option<u32>,
+    /// The global compression level.
+    compression_level: u32,
     /// A buffer of packets to send to all clients currently in view of this
     /// chunk at the end of the tick. Clients entering the view of this
     /// chunk this tick should _not_ receive this data.
@@ -131,7 +135,11 @@ impl Section {
 }
 
 impl LoadedChunk {
-    pub(crate) fn new(height: u32, compression_threshold: Option<u32>) -> Self {
+    pub(crate) fn new(
+        height: u32,
+        compression_threshold: Option<u32>,
+        compression_level: u32,
+    ) -> Self {
         Self {
             state: ChunkState::Added,
             is_viewed: AtomicBool::new(false),
@@ -140,6 +148,7 @@ impl LoadedChunk {
             changed_block_entities: BTreeSet::new(),
             changed_biomes: false,
             compression_threshold,
+            compression_level,
             packet_buf: vec![],
             cached_init_packets: Mutex::new(vec![]),
             entities: BTreeSet::new(),
@@ -304,7 +313,8 @@ impl LoadedChunk {
             "other chunk states should be unviewed"
         );
 
-        let mut writer = PacketWriter::new(&mut self.packet_buf, info.compression_threshold);
+        let mut writer = PacketWriter::new(&mut self.packet_buf, info.compression_threshold)
+            .with_level(info.compression_level);
 
         // Block states
         for (sect_y, sect) in self.sections.iter_mut().enumerate() {
@@ -362,9 +372,13 @@ impl LoadedChunk {
             let global_x = pos.x * 16 + x as i32;
             let global_y = info.min_y + y as i32;
             let global_z = pos.z * 16 + z as i32;
+            let position = BlockPos::new(global_x, global_y, global_z);
+
+            #[cfg(feature = "validate_block_entities")]
+            validate_block_entity(kind, nbt, position);
 
             writer.write_packet(&BlockEntityUpdateS2c {
-                position: BlockPos::new(global_x, global_y, global_z),
+                position,
                 kind: VarInt(kind as i32),
                 data: Cow::Borrowed(nbt),
             });
@@ -407,7 +421,8 @@ impl LoadedChunk {
 
             let start = self.packet_buf.len();
 
-            let writer = PacketWriter::new(&mut self.packet_buf, self.compression_threshold);
+            let writer = PacketWriter::new(&mut self.packet_buf, self.compression_threshold)
+                .with_level(self.compression_level);
             entity.write_update_packets(writer);
 
             let end = self.packet_buf.len();
@@ -499,6 +514,19 @@ impl LoadedChunk {
                         .get(idx as usize % SECTION_BLOCK_COUNT)
                         .block_entity_kind();
 
+                    #[cfg(feature = "validate_block_entities")]
+                    if let Some(kind) = kind {
+                        let global_x = pos.x * 16 + x as i32;
+                        let global_z = pos.z * 16 + z as i32;
+                        let global_y = info.min_y + y as i32;
+
+                        validate_block_entity(
+                            kind,
+                            nbt,
+                            BlockPos::new(global_x, global_y, global_z),
+                        );
+                    }
+
                     kind.map(|kind| ChunkDataBlockEntity {
                         packed_xz: ((x << 4) | z) as i8,
                         y: y as i16 + info.min_y as i16,
@@ -508,8 +536,9 @@ impl LoadedChunk {
                 })
                 .collect();
 
-            PacketWriter::new(&mut init_packets, info.compression_threshold).write_packet(
-                &ChunkDataS2c {
+            PacketWriter::new(&mut init_packets, info.compression_threshold)
+                .with_level(info.compression_level)
+                .write_packet(&ChunkDataS2c {
                     pos,
                     heightmaps: Cow::Owned(heightmaps),
                     blocks_and_biomes: &blocks_and_biomes,
@@ -520,8 +549,7 @@ impl LoadedChunk {
                     empty_block_light_mask: Cow::Borrowed(&[]),
                     sky_light_arrays: Cow::Borrowed(&info.sky_light_arrays),
                     block_light_arrays: Cow::Borrowed(&[]),
-                },
-            )
+                })
         }
 
         writer.write_packet_bytes(&init_packets);
@@ -760,6 +788,7 @@ impl WritePacket for LoadedChunk {
     {
         if *self.is_viewed.get_mut() {
             PacketWriter::new(&mut self.packet_buf, self.compression_threshold)
+                .with_level(self.compression_level)
                 .write_packet_fallible(packet)?;
         }
 
@@ -773,9 +802,23 @@ impl WritePacket for LoadedChunk {
     }
 }
 
+/// Logs a warning for each field of a block entity that clients can't read.
+#[cfg(feature = "validate_block_entities")]
+fn validate_block_entity(kind: BlockEntityKind, nbt: &Compound, pos: BlockPos) {
+    for mismatch in kind.validate_nbt(nbt) {
+        tracing::warn!(
+            "invalid {kind:?} block entity at ({}, {}, {}): {mismatch}",
+            pos.x,
+            pos.y,
+            pos.z
+        );
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use valence_core::ident;
+    use valence_core::protocol::encode::DEFAULT_COMPRESSION_LEVEL;
 
     use super::*;
 
@@ -783,7 +826,7 @@ mod tests {
 
     #[test]
     fn loaded_chunk_unviewed_no_changes() {
-        let mut chunk = LoadedChunk::new(512, THRESHOLD);
+        let mut chunk = LoadedChunk::new(512, THRESHOLD, DEFAULT_COMPRESSION_LEVEL);
 
         chunk.set_block(0, 10, 0, BlockState::MAGMA_BLOCK);
         chunk.assert_no_changes();
@@ -808,6 +851,7 @@ mod tests {
                 min_y: -16,
                 biome_registry_len: 200,
                 compression_threshold: THRESHOLD,
+                compression_level: DEFAULT_COMPRESSION_LEVEL,
                 sky_light_mask: vec![].into(),
                 sky_light_arrays: vec![].into(),
             };
@@ -830,7 +874,7 @@ mod tests {
             assert!(!chunk.cached_init_packets.get_mut().is_empty());
         }
 
-        let mut chunk = LoadedChunk::new(512, THRESHOLD);
+        let mut chunk = LoadedChunk::new(512, THRESHOLD, DEFAULT_COMPRESSION_LEVEL);
 
         check(&mut chunk, |c| {
             c.set_block_state(0, 4, 0, BlockState::ACACIA_WOOD)
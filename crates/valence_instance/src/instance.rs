@@ -41,6 +41,7 @@ pub struct InstanceInfo {
     pub(super) min_y: i32,
     pub(super) biome_registry_len: usize,
     pub(super) compression_threshold: Option<u32>,
+    pub(super) compression_level: u32,
     // We don't have a proper lighting engine yet, so we just fill chunks with full brightness.
     pub(super) sky_light_mask: Box<[u64]>,
     pub(super) sky_light_arrays: Box<[LengthPrefixedArray<u8, 2048>]>,
@@ -80,6 +81,7 @@ impl Instance {
                 min_y: dim.min_y,
                 biome_registry_len: biomes.iter().len(),
                 compression_threshold: server.compression_threshold(),
+                compression_level: server.compression_level(),
                 sky_light_mask: sky_light_mask.into(),
                 sky_light_arrays: vec![LengthPrefixedArray([0xff; 2048]); light_section_count]
                     .into(),
@@ -158,6 +160,7 @@ impl Instance {
             Entry::Vacant(ve) => ChunkEntry::Vacant(VacantChunkEntry {
                 height: self.info.height,
                 compression_threshold: self.info.compression_threshold,
+                compression_level: self.info.compression_level,
                 entry: ve,
             }),
         }
@@ -202,7 +205,11 @@ impl Instance {
 
     #[inline]
     fn chunk_and_offsets(&self, pos: BlockPos) -> Option<(&LoadedChunk, u32, u32, u32)> {
-        let Some(y) = pos.y.checked_sub(self.info.min_y).and_then(|y| y.try_into().ok()) else {
+        let Some(y) = pos
+            .y
+            .checked_sub(self.info.min_y)
+            .and_then(|y| y.try_into().ok())
+        else {
             return None;
         };
 
@@ -225,7 +232,11 @@ impl Instance {
         &mut self,
         pos: BlockPos,
     ) -> Option<(&mut LoadedChunk, u32, u32, u32)> {
-        let Some(y) = pos.y.checked_sub(self.info.min_y).and_then(|y| y.try_into().ok()) else {
+        let Some(y) = pos
+            .y
+            .checked_sub(self.info.min_y)
+            .and_then(|y| y.try_into().ok())
+        else {
             return None;
         };
 
@@ -348,6 +359,7 @@ impl WritePacket for Instance {
         P: Packet + Encode,
     {
         PacketWriter::new(&mut self.packet_buf, self.info.compression_threshold)
+            .with_level(self.info.compression_level)
             .write_packet_fallible(packet)
     }
 
@@ -413,12 +425,17 @@ impl<'a> OccupiedChunkEntry<'a> {
 pub struct VacantChunkEntry<'a> {
     height: u32,
     compression_threshold: Option<u32>,
+    compression_level: u32,
     entry: VacantEntry<'a, ChunkPos, LoadedChunk>,
 }
 
 impl<'a> VacantChunkEntry<'a> {
     pub fn insert(self, chunk: UnloadedChunk) -> &'a mut LoadedChunk {
-        let mut loaded = LoadedChunk::new(self.height, self.compression_threshold);
+        let mut loaded = LoadedChunk::new(
+            self.height,
+            self.compression_threshold,
+            self.compression_level,
+        );
         loaded.insert(chunk);
 
         self.entry.insert(loaded)
@@ -6,7 +6,7 @@ use valence_core::ident::Ident;
 use valence_core::protocol::array::LengthPrefixedArray;
 use valence_core::protocol::var_int::VarInt;
 use valence_core::protocol::var_long::VarLong;
-use valence_core::protocol::{packet_id, Decode, Encode, Packet};
+use valence_core::protocol::{packet_id, Decode, Encode, Packet, SharedPacket};
 use valence_nbt::Compound;
 
 #[derive(Clone, Debug, Encode, Decode, Packet)]
@@ -18,6 +18,8 @@ pub struct WorldEventS2c {
     pub disable_relative_volume: bool,
 }
 
+impl SharedPacket for WorldEventS2c {}
+
 #[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::WORLD_TIME_UPDATE_S2C)]
 pub struct WorldTimeUpdateS2c {
@@ -29,6 +31,8 @@ pub struct WorldTimeUpdateS2c {
     pub time_of_day: i64,
 }
 
+impl SharedPacket for WorldTimeUpdateS2c {}
+
 #[derive(Clone, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::CHUNK_BIOME_DATA_S2C)]
 pub struct ChunkBiomeDataS2c<'a> {
@@ -72,6 +76,8 @@ pub struct ChunkDeltaUpdateS2c<'a> {
     pub blocks: Cow<'a, [VarLong]>,
 }
 
+impl SharedPacket for ChunkDeltaUpdateS2c<'_> {}
+
 #[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::CHUNK_LOAD_DISTANCE_S2C)]
 pub struct ChunkLoadDistanceS2c {
@@ -112,6 +118,8 @@ pub struct BlockBreakingProgressS2c {
     pub destroy_stage: u8,
 }
 
+impl SharedPacket for BlockBreakingProgressS2c {}
+
 #[derive(Clone, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::BLOCK_ENTITY_UPDATE_S2C)]
 pub struct BlockEntityUpdateS2c<'a> {
@@ -120,6 +128,8 @@ pub struct BlockEntityUpdateS2c<'a> {
     pub data: Cow<'a, Compound>,
 }
 
+impl SharedPacket for BlockEntityUpdateS2c<'_> {}
+
 #[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::BLOCK_EVENT_S2C)]
 pub struct BlockEventS2c {
@@ -129,6 +139,8 @@ pub struct BlockEventS2c {
     pub block_type: VarInt,
 }
 
+impl SharedPacket for BlockEventS2c {}
+
 #[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::BLOCK_UPDATE_S2C)]
 pub struct BlockUpdateS2c {
@@ -136,6 +148,8 @@ pub struct BlockUpdateS2c {
     pub block_id: VarInt,
 }
 
+impl SharedPacket for BlockUpdateS2c {}
+
 #[derive(Clone, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::EXPLOSION_S2C)]
 pub struct ExplosionS2c<'a> {
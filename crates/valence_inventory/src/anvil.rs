@@ -0,0 +1,211 @@
+//! Anvil windows.
+//!
+//! The server is in charge of what an anvil makes. When the client types a
+//! name, an [`AnvilRenameEvent`] is sent, after which the server should put
+//! the result in the output slot and set the level cost with
+//! [`ContainerProperties::set_anvil_repair_cost`]. Taking the result is
+//! handled through [`PendingAnvilTakes`].
+
+use bevy_ecs::prelude::*;
+use tracing::debug;
+use valence_client::event_loop::PacketEvent;
+use valence_client::Client;
+use valence_core::item::ItemStack;
+
+use crate::output::{self, TakeClick, TakeTarget};
+use crate::packet::RenameItemC2s;
+use crate::{
+    ClientInventoryState, ContainerProperties, CursorItem, Inventory, InventoryKind, OpenInventory,
+    SlotChangeCause,
+};
+
+pub const FIRST_INPUT_SLOT: u16 = 0;
+pub const SECOND_INPUT_SLOT: u16 = 1;
+pub const OUTPUT_SLOT: u16 = 2;
+
+/// The maximum length of a name typed into an anvil, in UTF-16 code units.
+/// Longer names are ignored.
+pub const MAX_NAME_LENGTH: usize = 50;
+
+/// Sent when a client types a name into an anvil.
+#[derive(Event, Clone, PartialEq, Eq, Debug)]
+pub struct AnvilRenameEvent {
+    pub client: Entity,
+    /// The entity with the anvil's [`Inventory`].
+    pub inventory: Entity,
+    /// The typed name with invalid characters removed, or `None` if it is
+    /// blank. Like in vanilla, a blank name removes the custom name of the
+    /// item.
+    pub name: Option<String>,
+}
+
+/// A client taking the result out of an anvil, which has yet to be applied.
+///
+/// When applied, the result is moved to the client, the first input is used
+/// up, and the output slot and level cost are cleared.
+#[derive(Clone, Debug)]
+pub struct AnvilTakeResultEvent {
+    pub client: Entity,
+    /// The entity with the anvil's [`Inventory`].
+    pub inventory: Entity,
+    pub result: ItemStack,
+    /// The level cost of the anvil when the result was taken. The client
+    /// expects to lose this many levels unless it is in creative mode, so
+    /// systems should take them away or cancel the event.
+    pub level_cost: i16,
+    /// The number of items used up from the second input. If this is 0, the
+    /// whole stack is used up.
+    pub repair_item_usage: u8,
+    target: TakeTarget,
+    cancelled: bool,
+}
+
+impl AnvilTakeResultEvent {
+    /// Keeps the client from taking the result. The client is sent the actual
+    /// contents of the anvil.
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// The [`AnvilTakeResultEvent`]s from the current run of the event loop.
+///
+/// They are applied in [`EventLoopPostUpdate`], so systems in
+/// [`EventLoopUpdate`] can take the levels or cancel them first.
+///
+/// [`EventLoopPostUpdate`]: valence_client::event_loop::EventLoopPostUpdate
+/// [`EventLoopUpdate`]: valence_client::event_loop::EventLoopUpdate
+#[derive(Resource, Default, Debug)]
+pub struct PendingAnvilTakes(pub Vec<AnvilTakeResultEvent>);
+
+pub(super) fn handle_rename_item(
+    mut packets: EventReader<PacketEvent>,
+    clients: Query<&OpenInventory>,
+    inventories: Query<&Inventory>,
+    mut events: EventWriter<AnvilRenameEvent>,
+) {
+    for packet in packets.iter() {
+        if let Some(pkt) = packet.decode::<RenameItemC2s>() {
+            let Ok(open_inventory) = clients.get(packet.client) else {
+                continue;
+            };
+
+            if !inventories
+                .get(open_inventory.entity)
+                .is_ok_and(|inv| inv.kind == InventoryKind::Anvil)
+            {
+                // The client is not looking at an anvil, ignore.
+                continue;
+            }
+
+            let name: String = pkt
+                .item_name
+                .chars()
+                .filter(|&c| c != '§' && c >= ' ' && c != '\x7f')
+                .collect();
+
+            if name.encode_utf16().count() > MAX_NAME_LENGTH {
+                debug!(
+                    "client {:?} sent an anvil name that is too long",
+                    packet.client
+                );
+                continue;
+            }
+
+            events.send(AnvilRenameEvent {
+                client: packet.client,
+                inventory: open_inventory.entity,
+                name: (!name.trim().is_empty()).then_some(name),
+            });
+        }
+    }
+}
+
+/// Works out the result of a click that takes the result out of an anvil.
+/// Returns `None` if the result can't be taken.
+pub(super) fn take_result(click: &TakeClick, level_cost: i16) -> Option<AnvilTakeResultEvent> {
+    let result = click.result()?;
+
+    // Like the client, don't allow taking results that are free.
+    if level_cost <= 0 {
+        return None;
+    }
+
+    Some(AnvilTakeResultEvent {
+        client: click.client,
+        inventory: click.inventory,
+        result: result.clone(),
+        level_cost,
+        repair_item_usage: 0,
+        target: output::take_target(click, result)?,
+        cancelled: false,
+    })
+}
+
+pub(super) fn apply_anvil_takes(
+    mut pending: ResMut<PendingAnvilTakes>,
+    mut clients: Query<(
+        &mut Client,
+        &mut Inventory,
+        &mut ClientInventoryState,
+        &mut CursorItem,
+        &OpenInventory,
+    )>,
+    mut anvils: Query<(&mut Inventory, Option<&mut ContainerProperties>), Without<Client>>,
+) {
+    for event in pending.0.drain(..) {
+        let Ok((mut client, mut player_inventory, mut inv_state, mut cursor_item, open_inventory)) =
+            clients.get_mut(event.client)
+        else {
+            continue;
+        };
+
+        if open_inventory.entity != event.inventory {
+            // The client closed the anvil in the meantime.
+            continue;
+        }
+
+        let Ok((mut anvil, properties)) = anvils.get_mut(event.inventory) else {
+            continue;
+        };
+
+        if event.cancelled
+            || !output::can_still_take(&event.target, &anvil, &cursor_item, &event.result)
+        {
+            output::resync(
+                &mut client,
+                &inv_state,
+                &mut anvil,
+                &player_inventory,
+                event.target.player_slot_changes(),
+            );
+            continue;
+        }
+
+        output::give_result(
+            &mut client,
+            event.target,
+            &mut anvil,
+            &mut player_inventory,
+            &mut inv_state,
+            &mut cursor_item,
+            SlotChangeCause::Click(event.client),
+        );
+
+        anvil.set_slot(FIRST_INPUT_SLOT, None);
+
+        let second_input = anvil
+            .slot(SECOND_INPUT_SLOT)
+            .filter(|s| event.repair_item_usage != 0 && s.count() > event.repair_item_usage)
+            .map(|s| s.clone().with_count(s.count() - event.repair_item_usage));
+        anvil.set_slot(SECOND_INPUT_SLOT, second_input);
+
+        if let Some(mut properties) = properties {
+            properties.set_anvil_repair_cost(0);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! Putting on armor by using it.
+//!
+//! Using a piece of armor or an elytra swaps it with the item in its armor
+//! slot, like in vanilla. The client does this on its own before telling the
+//! server, so a cancelled [`ArmorEquipEvent`] sends the real slots back to the
+//! client.
+
+use bevy_ecs::prelude::*;
+use valence_client::event_loop::PacketEvent;
+use valence_client::interact_item::PlayerInteractItemC2s;
+use valence_core::hand::Hand;
+use valence_core::item::{ItemKind, ItemStack};
+
+use crate::equipment::EquipmentSlot;
+use crate::{HeldItem, Inventory, SlotChangeCause, OFFHAND_SLOT};
+
+/// A client putting on armor by using it, which has yet to be applied.
+///
+/// When applied, the armor is swapped with the item in its armor slot, which
+/// ends up in the hand the armor was used with.
+///
+/// Equips are kept in [`PendingArmorEquips`] for systems in
+/// [`EventLoopUpdate`] to cancel, and the equips that weren't cancelled are
+/// then applied and sent as events.
+///
+/// [`EventLoopUpdate`]: valence_client::event_loop::EventLoopUpdate
+#[derive(Event, Clone, PartialEq, Debug)]
+pub struct ArmorEquipEvent {
+    pub client: Entity,
+    /// The hand that the armor was used with.
+    pub hand: Hand,
+    /// The equipment slot that the armor is put on in.
+    pub slot: EquipmentSlot,
+    /// The armor that is put on.
+    pub item: ItemStack,
+    /// The armor that was worn before, which ends up in the hand.
+    pub replaced: Option<ItemStack>,
+    cancelled: bool,
+}
+
+impl ArmorEquipEvent {
+    /// Keeps the armor from being put on. The client is sent the items that are
+    /// actually in its hand and armor slot.
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// The [`ArmorEquipEvent`]s from the current run of the event loop.
+///
+/// They are applied in [`EventLoopPostUpdate`], so systems in
+/// [`EventLoopUpdate`] can cancel them first.
+///
+/// [`EventLoopPostUpdate`]: valence_client::event_loop::EventLoopPostUpdate
+/// [`EventLoopUpdate`]: valence_client::event_loop::EventLoopUpdate
+#[derive(Resource, Default, Debug)]
+pub struct PendingArmorEquips(pub Vec<ArmorEquipEvent>);
+
+/// Returns whether using an item puts it on. Heads and carved pumpkins are
+/// worn too, but using them doesn't put them on.
+fn equips_on_use(item: ItemKind) -> bool {
+    let name = item.to_str();
+
+    name.ends_with("_helmet")
+        || name.ends_with("_chestplate")
+        || name.ends_with("_leggings")
+        || name.ends_with("_boots")
+        || item == ItemKind::Elytra
+}
+
+fn hand_slot(hand: Hand, held_item: &HeldItem) -> u16 {
+    match hand {
+        Hand::Main => held_item.slot(),
+        Hand::Off => OFFHAND_SLOT,
+    }
+}
+
+fn has_binding_curse(stack: &ItemStack) -> bool {
+    stack
+        .enchantments()
+        .iter()
+        .any(|(ench, _)| ench.as_str() == "minecraft:binding_curse")
+}
+
+pub(super) fn handle_interact_item(
+    mut packets: EventReader<PacketEvent>,
+    clients: Query<(&Inventory, &HeldItem)>,
+    mut pending: ResMut<PendingArmorEquips>,
+) {
+    for packet in packets.iter() {
+        let Some(pkt) = packet.decode::<PlayerInteractItemC2s>() else {
+            continue;
+        };
+
+        let Ok((inventory, held_item)) = clients.get(packet.client) else {
+            continue;
+        };
+
+        let Some(item) = inventory.slot(hand_slot(pkt.hand, held_item)) else {
+            continue;
+        };
+
+        if !equips_on_use(item.item) {
+            continue;
+        }
+
+        let slot = EquipmentSlot::for_item(item.item);
+        let Some(armor_slot) = slot.armor_slot() else {
+            continue;
+        };
+
+        let replaced = inventory.slot(armor_slot);
+
+        // Armor with the curse of binding can't be taken off, and nothing happens
+        // when the same armor is already worn.
+        if replaced.is_some_and(|r| has_binding_curse(r) || r == item) {
+            continue;
+        }
+
+        pending.0.push(ArmorEquipEvent {
+            client: packet.client,
+            hand: pkt.hand,
+            slot,
+            item: item.clone(),
+            replaced: replaced.cloned(),
+            cancelled: false,
+        });
+    }
+}
+
+pub(super) fn apply_armor_equips(
+    mut pending: ResMut<PendingArmorEquips>,
+    mut clients: Query<(&mut Inventory, &HeldItem)>,
+    mut events: EventWriter<ArmorEquipEvent>,
+) {
+    for event in pending.0.drain(..) {
+        let Ok((mut inventory, held_item)) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        let hand_slot = hand_slot(event.hand, held_item);
+        let Some(armor_slot) = event.slot.armor_slot() else {
+            continue;
+        };
+
+        if event.cancelled
+            || inventory.slot(hand_slot) != Some(&event.item)
+            || inventory.slot(armor_slot) != event.replaced.as_ref()
+        {
+            // Make sure that the client agrees with the server.
+            inventory.changed |= 1 << hand_slot | 1 << armor_slot;
+            continue;
+        }
+
+        // The client already swapped the items.
+        let cause = SlotChangeCause::Equip(event.client);
+        inventory.set_slot_unchanged(armor_slot, Some(event.item.clone()), cause);
+        inventory.set_slot_unchanged(hand_slot, event.replaced.clone(), cause);
+
+        events.send(event);
+    }
+}
@@ -0,0 +1,157 @@
+//! Beacon windows.
+//!
+//! Spawn a [`BeaconBundle`] and open it for a client with [`OpenInventory`].
+//! When the client confirms its choice of effects, one item is taken from the
+//! payment slot and a [`BeaconSelectEvent`] is sent. Applying the effects is up
+//! to the server.
+
+use bevy_ecs::prelude::*;
+use tracing::debug;
+use valence_client::event_loop::PacketEvent;
+use valence_core::ident;
+use valence_core::ident::Ident;
+use valence_core::item::ItemKind;
+use valence_core::protocol::var_int::VarInt;
+
+use crate::packet::UpdateBeaconC2s;
+use crate::{ContainerProperties, Inventory, InventoryKind, OpenInventory};
+
+pub const PAYMENT_SLOT: u16 = 0;
+
+/// The effects that can be chosen in a beacon, with their protocol IDs.
+const BEACON_EFFECTS: [(Ident<&str>, i32); 6] = [
+    (ident!("speed"), 1),
+    (ident!("haste"), 3),
+    (ident!("strength"), 5),
+    (ident!("jump_boost"), 8),
+    (ident!("regeneration"), 10),
+    (ident!("resistance"), 11),
+];
+
+/// The items that can be put in the payment slot.
+const PAYMENT_ITEMS: [ItemKind; 5] = [
+    ItemKind::NetheriteIngot,
+    ItemKind::Emerald,
+    ItemKind::Diamond,
+    ItemKind::GoldIngot,
+    ItemKind::IronIngot,
+];
+
+/// The components of a beacon window.
+#[derive(Bundle)]
+pub struct BeaconBundle {
+    pub inventory: Inventory,
+    pub properties: ContainerProperties,
+}
+
+impl BeaconBundle {
+    /// Creates a beacon window with the given number of pyramid levels, from 0
+    /// to 4, which decides the effects that the client can choose.
+    pub fn new(power_level: i16) -> Self {
+        let mut properties = ContainerProperties::new(InventoryKind::Beacon);
+        properties.set_beacon_power_level(power_level);
+        properties.set_beacon_effects(None, None);
+
+        Self {
+            inventory: Inventory::new(InventoryKind::Beacon),
+            properties,
+        }
+    }
+}
+
+/// Sent when a client confirms the effects chosen in a beacon. The item in the
+/// payment slot has already been used up.
+#[derive(Event, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct BeaconSelectEvent {
+    pub client: Entity,
+    /// The entity with the beacon's [`Inventory`].
+    pub inventory: Entity,
+    pub primary: Option<Ident<&'static str>>,
+    pub secondary: Option<Ident<&'static str>>,
+}
+
+/// Returns the protocol ID of an effect that can be chosen in a beacon.
+pub fn effect_id(effect: Ident<&str>) -> Option<i32> {
+    BEACON_EFFECTS
+        .iter()
+        .find(|(name, _)| *name == effect)
+        .map(|&(_, id)| id)
+}
+
+/// Returns the name of an effect that can be chosen in a beacon from its
+/// protocol ID.
+pub fn effect_from_id(id: i32) -> Option<Ident<&'static str>> {
+    BEACON_EFFECTS
+        .iter()
+        .find(|&&(_, effect_id)| effect_id == id)
+        .map(|&(name, _)| name)
+}
+
+pub(super) fn handle_update_beacon(
+    mut packets: EventReader<PacketEvent>,
+    clients: Query<&OpenInventory>,
+    mut beacons: Query<(&mut Inventory, Option<&mut ContainerProperties>)>,
+    mut events: EventWriter<BeaconSelectEvent>,
+) {
+    for packet in packets.iter() {
+        if let Some(pkt) = packet.decode::<UpdateBeaconC2s>() {
+            let Ok(open_inventory) = clients.get(packet.client) else {
+                continue;
+            };
+
+            let Ok((mut beacon, properties)) = beacons.get_mut(open_inventory.entity) else {
+                continue;
+            };
+
+            if beacon.kind != InventoryKind::Beacon {
+                // The client is not looking at a beacon, ignore.
+                continue;
+            }
+
+            let to_effect = |id: Option<VarInt>| match id {
+                Some(id) => effect_from_id(id.0).ok_or(id.0).map(Some),
+                None => Ok(None),
+            };
+
+            let (primary, secondary) = match (
+                to_effect(pkt.primary_effect),
+                to_effect(pkt.secondary_effect),
+            ) {
+                (Ok(primary), Ok(secondary)) => (primary, secondary),
+                (Err(id), _) | (_, Err(id)) => {
+                    debug!(
+                        "client {:?} chose invalid beacon effect {id}",
+                        packet.client
+                    );
+                    continue;
+                }
+            };
+
+            let Some(count) = beacon
+                .slot(PAYMENT_SLOT)
+                .filter(|s| PAYMENT_ITEMS.contains(&s.item))
+                .map(|s| s.count())
+            else {
+                // Effects can't be chosen without paying.
+                continue;
+            };
+
+            if count > 1 {
+                beacon.set_slot_amount(PAYMENT_SLOT, count - 1);
+            } else {
+                beacon.set_slot(PAYMENT_SLOT, None);
+            }
+
+            if let Some(mut properties) = properties {
+                properties.set_beacon_effects(primary, secondary);
+            }
+
+            events.send(BeaconSelectEvent {
+                client: packet.client,
+                inventory: open_inventory.entity,
+                primary,
+                secondary,
+            });
+        }
+    }
+}
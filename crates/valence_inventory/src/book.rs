@@ -0,0 +1,131 @@
+//! Written books.
+//!
+//! Build the item stack of a book with [`WrittenBook`] and show it to a client
+//! with [`BookReaderItem::open_book`]. Books can also be put on lecterns, see
+//! [`lectern`](crate::lectern).
+
+use std::borrow::Cow;
+
+use bevy_ecs::query::WorldQuery;
+use valence_client::packet::OpenWrittenBookS2c;
+use valence_client::Client;
+use valence_core::hand::Hand;
+use valence_core::item::{ItemKind, ItemStack};
+use valence_core::protocol::encode::WritePacket;
+use valence_core::protocol::var_int::VarInt;
+use valence_core::text::Text;
+use valence_nbt::{compound, List, Value};
+
+use crate::packet::ScreenHandlerSlotUpdateS2c;
+use crate::{ClientInventoryState, HeldItem, Inventory, OFFHAND_SLOT};
+
+/// A written book, which can be turned into an [`ItemStack`].
+///
+/// ```
+/// # use valence_core::item::{ItemKind, ItemStack};
+/// # use valence_inventory::book::WrittenBook;
+/// let book = WrittenBook::new(
+///     "Quest Log",
+///     "Guide",
+///     vec!["Page one".into(), "Page two".into()],
+/// );
+///
+/// let stack = ItemStack::from(book);
+/// assert_eq!(stack.item, ItemKind::WrittenBook);
+/// ```
+#[derive(Clone, PartialEq, Debug)]
+pub struct WrittenBook {
+    pub title: String,
+    pub author: String,
+    pub pages: Vec<Text>,
+    /// How many times the book has been copied, from 0 for the original to 3
+    /// for a tattered book.
+    pub generation: i32,
+}
+
+impl WrittenBook {
+    pub fn new(title: impl Into<String>, author: impl Into<String>, pages: Vec<Text>) -> Self {
+        Self {
+            title: title.into(),
+            author: author.into(),
+            pages,
+            generation: 0,
+        }
+    }
+}
+
+impl From<WrittenBook> for ItemStack {
+    fn from(book: WrittenBook) -> Self {
+        let pages = book
+            .pages
+            .into_iter()
+            .map(|page| {
+                serde_json::to_string(&page)
+                    .unwrap_or_else(|err| panic!("failed to jsonify text {page:?}\n{err}"))
+            })
+            .collect();
+
+        let nbt = compound! {
+            "title" => book.title,
+            "author" => book.author,
+            "pages" => List::String(pages),
+            "generation" => book.generation,
+            // The text is sent as is, without the client trying to resolve it.
+            "resolved" => true,
+        };
+
+        ItemStack::new(ItemKind::WrittenBook, 1, Some(nbt))
+    }
+}
+
+/// The components needed to open a book for a client.
+#[derive(WorldQuery)]
+#[world_query(mutable)]
+pub struct BookReader {
+    pub client: &'static mut Client,
+    pub inventory: &'static Inventory,
+    pub held_item: &'static HeldItem,
+    inv_state: &'static ClientInventoryState,
+}
+
+impl BookReaderItem<'_> {
+    /// Opens the book screen for a written book.
+    ///
+    /// The client only reads books from its hands, so the book is put in the
+    /// held slot or the offhand first. The client copies the pages when the
+    /// screen opens, so the slot is restored right after and the player's
+    /// [`Inventory`] is never changed.
+    pub fn open_book(&mut self, book: &ItemStack, offhand: bool) {
+        let (slot, hand) = if offhand {
+            (OFFHAND_SLOT, Hand::Off)
+        } else {
+            (self.held_item.slot(), Hand::Main)
+        };
+
+        let state_id = VarInt(self.inv_state.state_id().0);
+
+        self.client.write_packet(&ScreenHandlerSlotUpdateS2c {
+            window_id: 0,
+            state_id,
+            slot_idx: slot as i16,
+            slot_data: Cow::Owned(Some(book.clone())),
+        });
+
+        self.client.write_packet(&OpenWrittenBookS2c { hand });
+
+        self.client.write_packet(&ScreenHandlerSlotUpdateS2c {
+            window_id: 0,
+            state_id,
+            slot_idx: slot as i16,
+            slot_data: Cow::Borrowed(&self.inventory.slots[slot as usize]),
+        });
+    }
+}
+
+/// Returns the number of pages of a written or writable book.
+pub fn page_count(book: &ItemStack) -> usize {
+    match book.nbt.as_ref().and_then(|nbt| nbt.get("pages")) {
+        Some(Value::List(pages)) => pages.len(),
+        _ => 0,
+    }
+}
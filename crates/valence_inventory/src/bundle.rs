@@ -0,0 +1,425 @@
+//! Bundles, which hold a mix of items up to a total weight of
+//! [`Bundle::MAX_WEIGHT`].
+//!
+//! The items are stored in the `Items` tag of the bundle, with the most
+//! recently inserted item first. Right clicking a slot with a bundle, or right
+//! clicking a bundle in a slot, moves items in and out of the bundle. The
+//! result of these clicks is worked out by the server like in vanilla instead
+//! of following the client's prediction.
+
+use thiserror::Error;
+use valence_core::item::{ItemKind, ItemStack};
+use valence_nbt::{Compound, List, Value};
+
+use crate::packet::{ClickMode, ClickSlotC2s, SlotChange};
+use crate::{click, CursorItem, InventoryWindow};
+
+/// Reads and changes the contents of bundles.
+///
+/// ```
+/// # use valence_core::item::{ItemKind, ItemStack};
+/// # use valence_inventory::bundle::{Bundle, BundleInsertError};
+/// let mut bundle = ItemStack::new(ItemKind::Bundle, 1, None);
+///
+/// // Ender pearls stack up to 16, so each one weighs 4.
+/// let pearls = ItemStack::new(ItemKind::EnderPearl, 20, None);
+/// assert_eq!(Bundle::try_insert(&mut bundle, &pearls), Ok(16));
+/// assert_eq!(Bundle::weight(&bundle), 64);
+///
+/// let dirt = ItemStack::new(ItemKind::Dirt, 1, None);
+/// assert_eq!(
+///     Bundle::try_insert(&mut bundle, &dirt),
+///     Err(BundleInsertError::Full)
+/// );
+///
+/// assert_eq!(
+///     Bundle::remove_last(&mut bundle),
+///     Some(pearls.with_count(16))
+/// );
+/// assert!(Bundle::contents(&bundle).is_empty());
+/// ```
+pub struct Bundle;
+
+impl Bundle {
+    /// The total weight of the items that fit in a bundle.
+    pub const MAX_WEIGHT: u32 = 64;
+
+    /// Returns the items in a bundle, starting with the most recently inserted
+    /// one. Items that can't be read are skipped.
+    pub fn contents(bundle: &ItemStack) -> Vec<ItemStack> {
+        items(bundle)
+            .iter()
+            .filter_map(|nbt| ItemStack::from_nbt(nbt).ok())
+            .collect()
+    }
+
+    /// Returns the total weight of the items in a bundle.
+    pub fn weight(bundle: &ItemStack) -> u32 {
+        Self::contents(bundle)
+            .iter()
+            .map(|stack| Self::item_weight(stack) * u32::from(stack.count()))
+            .sum()
+    }
+
+    /// Returns the weight of a single item of the stack when put in a bundle.
+    ///
+    /// Items weigh [`Self::MAX_WEIGHT`] divided by their maximum stack size,
+    /// so a bundle fits one stack of them. Beehives and bee nests with bees
+    /// inside fill a whole bundle, and bundles weigh 4 plus their contents.
+    pub fn item_weight(stack: &ItemStack) -> u32 {
+        match stack.item {
+            ItemKind::Bundle => 4 + Self::weight(stack),
+            ItemKind::Beehive | ItemKind::BeeNest if has_bees(stack) => Self::MAX_WEIGHT,
+            item => Self::MAX_WEIGHT / u32::from(item.max_stack().max(1)),
+        }
+    }
+
+    /// Inserts as many items of `stack` into `bundle` as fit, and returns how
+    /// many were inserted. `bundle` is left unchanged if none of the items
+    /// fit.
+    ///
+    /// The items are merged with the same items already in the bundle, and
+    /// become the last inserted item.
+    pub fn try_insert(bundle: &mut ItemStack, stack: &ItemStack) -> Result<u8, BundleInsertError> {
+        if bundle.item != ItemKind::Bundle {
+            return Err(BundleInsertError::NotABundle);
+        }
+
+        if !can_be_nested(stack.item) {
+            return Err(BundleInsertError::CannotNest);
+        }
+
+        let free = Self::MAX_WEIGHT.saturating_sub(Self::weight(bundle));
+        let count = (free / Self::item_weight(stack)).min(stack.count().into()) as u8;
+
+        if count == 0 {
+            return Err(BundleInsertError::Full);
+        }
+
+        let items = items_mut(bundle);
+
+        // Bundles are never merged, since each one has its own contents.
+        let existing = (stack.item != ItemKind::Bundle)
+            .then(|| {
+                items.iter().position(|nbt| {
+                    ItemStack::from_nbt(nbt)
+                        .is_ok_and(|s| s.item == stack.item && s.nbt == stack.nbt)
+                })
+            })
+            .flatten();
+
+        let inserted = match existing {
+            Some(idx) => {
+                let mut merged = ItemStack::from_nbt(&items.remove(idx)).unwrap();
+                merged.set_count(merged.count() + count);
+                merged
+            }
+            None => stack.clone().with_count(count),
+        };
+
+        items.insert(0, inserted.to_nbt());
+
+        Ok(count)
+    }
+
+    /// Removes the last inserted item from a bundle.
+    pub fn remove_last(bundle: &mut ItemStack) -> Option<ItemStack> {
+        if bundle.item != ItemKind::Bundle {
+            return None;
+        }
+
+        // Vanilla always creates the tag, even if the bundle is empty.
+        let tag = bundle.nbt.get_or_insert_with(Compound::new);
+
+        let Some(Value::List(List::Compound(items))) = tag.get_mut("Items") else {
+            return None;
+        };
+
+        if items.is_empty() {
+            return None;
+        }
+
+        let removed = items.remove(0);
+
+        if items.is_empty() {
+            tag.remove("Items");
+        }
+
+        ItemStack::from_nbt(&removed).ok()
+    }
+}
+
+/// An error from [`Bundle::try_insert`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Error)]
+pub enum BundleInsertError {
+    #[error("the item is not a bundle")]
+    NotABundle,
+    #[error("the item can't be put in a bundle")]
+    CannotNest,
+    #[error("the bundle is too full to fit any of the items")]
+    Full,
+}
+
+fn items(bundle: &ItemStack) -> &[Compound] {
+    match bundle.nbt.as_ref().and_then(|nbt| nbt.get("Items")) {
+        Some(Value::List(List::Compound(items))) => items,
+        _ => &[],
+    }
+}
+
+/// Gets the `Items` list of a bundle, creating it if it doesn't exist or isn't
+/// a list of compounds.
+fn items_mut(bundle: &mut ItemStack) -> &mut Vec<Compound> {
+    let items = bundle
+        .nbt
+        .get_or_insert_with(Compound::new)
+        .entry("Items")
+        .or_insert_with(|| Value::List(List::Compound(vec![])));
+
+    if !matches!(items, Value::List(List::Compound(_))) {
+        *items = List::Compound(vec![]).into();
+    }
+
+    match items {
+        Value::List(List::Compound(items)) => items,
+        _ => unreachable!(),
+    }
+}
+
+fn has_bees(stack: &ItemStack) -> bool {
+    let Some(Value::Compound(block_entity)) =
+        stack.nbt.as_ref().and_then(|nbt| nbt.get("BlockEntityTag"))
+    else {
+        return false;
+    };
+
+    matches!(block_entity.get("Bees"), Some(Value::List(bees)) if !bees.is_empty())
+}
+
+/// Returns if the item can be put in a bundle. Shulker boxes can't.
+fn can_be_nested(item: ItemKind) -> bool {
+    item != ItemKind::Air && !item.to_str().ends_with("shulker_box")
+}
+
+/// Returns if a bundle in the slot of a window can be used to move items.
+/// Results can only be taken as a whole.
+fn is_usable(window: &InventoryWindow, idx: u16) -> bool {
+    !click::is_result_slot(window, idx)
+}
+
+/// Returns whether a click moves items in or out of a bundle.
+pub(super) fn is_bundle_click(
+    pkt: &ClickSlotC2s,
+    window: &InventoryWindow,
+    cursor_item: &CursorItem,
+) -> bool {
+    if pkt.mode != ClickMode::Click || pkt.button != 1 || pkt.slot_idx < 0 {
+        return false;
+    }
+
+    let idx = pkt.slot_idx as u16;
+
+    if idx >= window.slot_count() {
+        return false;
+    }
+
+    let is_bundle = |stack: Option<&ItemStack>| stack.is_some_and(|s| s.item == ItemKind::Bundle);
+
+    is_bundle(cursor_item.0.as_ref()) || (is_bundle(window.slot(idx)) && is_usable(window, idx))
+}
+
+/// Works out the slot changes and cursor item that result from a click moving
+/// items in or out of a bundle. Returns `None` if the click isn't a bundle
+/// click.
+pub(super) fn click(
+    pkt: &ClickSlotC2s,
+    window: &InventoryWindow,
+    cursor_item: &CursorItem,
+) -> Option<(Vec<SlotChange>, Option<ItemStack>)> {
+    if !is_bundle_click(pkt, window, cursor_item) {
+        return None;
+    }
+
+    let idx = pkt.slot_idx as u16;
+    let slot = window.slot(idx);
+
+    let (new_slot, new_cursor) = match &cursor_item.0 {
+        Some(cursor) if cursor.item == ItemKind::Bundle => {
+            // Right clicking a slot with a bundle.
+            let mut bundle = cursor.clone();
+
+            let new_slot = match slot {
+                None => Bundle::remove_last(&mut bundle).and_then(|removed| {
+                    let count = removed.count().min(click::max_count(window, idx, &removed));
+
+                    // What doesn't fit in the slot goes back in the bundle.
+                    if count < removed.count() {
+                        let rest = removed.clone().with_count(removed.count() - count);
+                        let _ = Bundle::try_insert(&mut bundle, &rest);
+                    }
+
+                    (count > 0).then(|| removed.with_count(count))
+                }),
+                Some(stack) if is_usable(window, idx) => {
+                    match Bundle::try_insert(&mut bundle, stack) {
+                        Ok(count) if count < stack.count() => {
+                            Some(stack.clone().with_count(stack.count() - count))
+                        }
+                        Ok(_) => None,
+                        Err(_) => Some(stack.clone()),
+                    }
+                }
+                Some(stack) => Some(stack.clone()),
+            };
+
+            (new_slot, Some(bundle))
+        }
+        cursor => {
+            // Right clicking a bundle in a slot.
+            let mut bundle = slot?.clone();
+
+            let new_cursor = match cursor {
+                None => Bundle::remove_last(&mut bundle),
+                Some(stack) => match Bundle::try_insert(&mut bundle, stack) {
+                    Ok(count) if count < stack.count() => {
+                        Some(stack.clone().with_count(stack.count() - count))
+                    }
+                    Ok(_) => None,
+                    Err(_) => Some(stack.clone()),
+                },
+            };
+
+            (Some(bundle), new_cursor)
+        }
+    };
+
+    let slot_changes = if new_slot.as_ref() != slot {
+        vec![SlotChange {
+            idx: pkt.slot_idx,
+            item: new_slot,
+        }]
+    } else {
+        vec![]
+    };
+
+    Some((slot_changes, new_cursor))
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_nbt::compound;
+
+    use super::*;
+
+    fn bundle_with(stacks: &[ItemStack]) -> ItemStack {
+        let mut bundle = ItemStack::new(ItemKind::Bundle, 1, None);
+
+        for stack in stacks.iter().rev() {
+            assert_eq!(Bundle::try_insert(&mut bundle, stack), Ok(stack.count()));
+        }
+
+        bundle
+    }
+
+    #[test]
+    fn item_weights() {
+        let weight = |item, nbt| Bundle::item_weight(&ItemStack::new(item, 1, nbt));
+
+        assert_eq!(weight(ItemKind::Dirt, None), 1);
+        assert_eq!(weight(ItemKind::EnderPearl, None), 4);
+        assert_eq!(weight(ItemKind::DiamondSword, None), 64);
+        assert_eq!(weight(ItemKind::Beehive, None), 1);
+        assert_eq!(weight(ItemKind::BeeNest, Some(compound! {})), 1);
+        assert_eq!(
+            weight(
+                ItemKind::BeeNest,
+                Some(compound! {
+                    "BlockEntityTag" => compound! {
+                        "Bees" => List::Compound(vec![compound! {}]),
+                    },
+                })
+            ),
+            64
+        );
+
+        // Nested bundles weigh 4 plus their contents.
+        assert_eq!(weight(ItemKind::Bundle, None), 4);
+
+        let inner = bundle_with(&[
+            ItemStack::new(ItemKind::Dirt, 10, None),
+            ItemStack::new(ItemKind::EnderPearl, 2, None),
+        ]);
+
+        assert_eq!(Bundle::weight(&inner), 18);
+        assert_eq!(Bundle::item_weight(&inner), 22);
+
+        let outer = bundle_with(&[inner.clone(), ItemStack::new(ItemKind::Dirt, 42, None)]);
+        assert_eq!(Bundle::weight(&outer), 64);
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        let dirt = ItemStack::new(ItemKind::Dirt, 20, None);
+        let named_dirt = dirt.clone().with_name("Dirt?");
+        let stone = ItemStack::new(ItemKind::Stone, 10, None);
+
+        let mut bundle = bundle_with(&[stone.clone(), named_dirt.clone(), dirt.clone()]);
+
+        // Only 14 more items fit.
+        assert_eq!(
+            Bundle::try_insert(&mut bundle, &dirt.clone().with_count(30)),
+            Ok(14)
+        );
+        assert_eq!(Bundle::weight(&bundle), 64);
+
+        // Inserted items are merged and moved to the front.
+        assert_eq!(
+            Bundle::contents(&bundle),
+            [
+                dirt.clone().with_count(34),
+                stone.clone(),
+                named_dirt.clone()
+            ]
+        );
+
+        let before = bundle.clone();
+        assert_eq!(
+            Bundle::try_insert(&mut bundle, &stone),
+            Err(BundleInsertError::Full)
+        );
+        assert_eq!(bundle, before);
+
+        assert_eq!(Bundle::remove_last(&mut bundle), Some(dirt.with_count(34)));
+        assert_eq!(Bundle::remove_last(&mut bundle), Some(stone));
+        assert_eq!(Bundle::remove_last(&mut bundle), Some(named_dirt));
+        assert_eq!(Bundle::remove_last(&mut bundle), None);
+        assert_eq!(bundle.nbt, Some(compound! {}));
+    }
+
+    #[test]
+    fn insert_errors() {
+        let mut bundle = ItemStack::new(ItemKind::Bundle, 1, None);
+
+        assert_eq!(
+            Bundle::try_insert(
+                &mut bundle,
+                &ItemStack::new(ItemKind::RedShulkerBox, 1, None)
+            ),
+            Err(BundleInsertError::CannotNest)
+        );
+
+        let mut chest = ItemStack::new(ItemKind::Chest, 1, None);
+
+        assert_eq!(
+            Bundle::try_insert(&mut chest, &ItemStack::new(ItemKind::Dirt, 1, None)),
+            Err(BundleInsertError::NotABundle)
+        );
+
+        // A bundle can't fit inside itself once it's more than 60 full.
+        let full = bundle_with(&[ItemStack::new(ItemKind::Dirt, 61, None)]);
+        assert_eq!(
+            Bundle::try_insert(&mut full.clone(), &full),
+            Err(BundleInsertError::Full)
+        );
+    }
+}
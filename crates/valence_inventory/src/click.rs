@@ -0,0 +1,389 @@
+//! Clicks that pick up, put down, and swap items.
+//!
+//! Like drags, the server works out the result of these clicks on its own
+//! instead of trusting the slot changes that the client predicted.
+
+use valence_core::item::ItemStack;
+
+use crate::equipment::EquipmentSlot;
+use crate::packet::{ClickMode, ClickSlotC2s, SlotChange};
+use crate::quick_move::QuickMove;
+use crate::{
+    quick_move, CursorItem, InventoryKind, InventoryWindow, OFFHAND_SLOT,
+    PLAYER_INVENTORY_MAIN_SLOTS_COUNT,
+};
+
+/// Returns whether a slot of a window is where the window puts its result.
+/// Items can't be put in result slots.
+pub(super) fn is_result_slot(window: &InventoryWindow, idx: u16) -> bool {
+    let Some(open_inventory) = window.open_inventory else {
+        // The crafting result of the player's inventory.
+        return idx == 0;
+    };
+
+    let result_slot = match open_inventory.kind() {
+        InventoryKind::Crafting => 0,
+        InventoryKind::Stonecutter => 1,
+        InventoryKind::Anvil
+        | InventoryKind::BlastFurnace
+        | InventoryKind::Furnace
+        | InventoryKind::Smoker
+        | InventoryKind::Grindstone
+        | InventoryKind::Cartography
+        | InventoryKind::Merchant => 2,
+        InventoryKind::Loom | InventoryKind::Smithing => 3,
+        _ => return false,
+    };
+
+    idx == result_slot
+}
+
+/// Returns how many items of a stack fit in a slot of a window, which is 0 if
+/// the stack can't be put in the slot.
+pub(super) fn max_count(window: &InventoryWindow, idx: u16, stack: &ItemStack) -> u8 {
+    if is_result_slot(window, idx) {
+        return 0;
+    }
+
+    match window.open_inventory {
+        None if (5..=8).contains(&idx) => {
+            return (EquipmentSlot::for_item(stack.item).armor_slot() == Some(idx)).into();
+        }
+        Some(open_inventory)
+            if open_inventory.kind() == InventoryKind::ShulkerBox
+                && idx < open_inventory.slot_count()
+                && stack.item.to_str().ends_with("shulker_box") =>
+        {
+            // Shulker boxes can't be put inside each other.
+            return 0;
+        }
+        _ => {}
+    }
+
+    stack.item.max_stack()
+}
+
+pub(super) fn can_combine(a: &ItemStack, b: &ItemStack) -> bool {
+    a.item == b.item && a.nbt == b.nbt
+}
+
+/// Removes `count` items from a stack, leaving `None` if it's used up.
+pub(super) fn shrink(stack: &ItemStack, count: u8) -> Option<ItemStack> {
+    (stack.count() > count).then(|| stack.clone().with_count(stack.count() - count))
+}
+
+/// Works out the slot changes and cursor item that result from a click.
+/// Returns `None` if the result of the click isn't worked out by the server.
+pub(super) fn click(
+    pkt: &ClickSlotC2s,
+    window: &InventoryWindow,
+    cursor_item: &CursorItem,
+    quick_move_rules: Option<&dyn QuickMove>,
+) -> Option<(Vec<SlotChange>, Option<ItemStack>)> {
+    if pkt.slot_idx < 0 || pkt.slot_idx as u16 >= window.slot_count() {
+        return None;
+    }
+
+    let idx = pkt.slot_idx as u16;
+
+    let mut slots: Vec<_> = (0..window.slot_count())
+        .map(|i| window.slot(i).cloned())
+        .collect();
+
+    let cursor = match pkt.mode {
+        ClickMode::Click => pick_up(&mut slots, idx, pkt.button == 0, window, cursor_item),
+        ClickMode::Hotbar => {
+            // The hotbar is the last row of the player's inventory in the window. The
+            // offhand is only part of the player's own window.
+            let other_idx = match (pkt.button, window.open_inventory) {
+                (0..=8, Some(open_inventory)) => {
+                    open_inventory.slot_count() + 27 + pkt.button as u16
+                }
+                (0..=8, None) => PLAYER_INVENTORY_MAIN_SLOTS_COUNT + pkt.button as u16,
+                (40, None) => OFFHAND_SLOT,
+                _ => return None,
+            };
+
+            swap(&mut slots, idx, other_idx, window);
+            cursor_item.0.clone()
+        }
+        ClickMode::ShiftClick => {
+            quick_move::quick_move(&mut slots, idx, window, quick_move_rules)?;
+            cursor_item.0.clone()
+        }
+        ClickMode::DoubleClick => pick_up_all(&mut slots, idx, window, cursor_item),
+        _ => return None,
+    };
+
+    Some((slot_changes(slots, window), cursor))
+}
+
+/// Returns the changes to the slots of a window that turn it into `slots`.
+pub(super) fn slot_changes(
+    slots: Vec<Option<ItemStack>>,
+    window: &InventoryWindow,
+) -> Vec<SlotChange> {
+    slots
+        .into_iter()
+        .enumerate()
+        .filter(|(i, item)| item.as_ref() != window.slot(*i as u16))
+        .map(|(i, item)| SlotChange {
+            idx: i as i16,
+            item,
+        })
+        .collect()
+}
+
+/// A left or right click on a slot, which moves items between the slot and
+/// the cursor. Returns the new cursor item.
+fn pick_up(
+    slots: &mut [Option<ItemStack>],
+    idx: u16,
+    left: bool,
+    window: &InventoryWindow,
+    cursor_item: &CursorItem,
+) -> Option<ItemStack> {
+    let slot = &mut slots[idx as usize];
+    let cursor = cursor_item.0.clone();
+
+    match (slot.clone(), cursor) {
+        (None, None) => None,
+        (Some(stack), None) => {
+            // Left clicks take the whole stack, and right clicks take half of it.
+            let count = if left {
+                stack.count()
+            } else {
+                stack.count() / 2 + stack.count() % 2
+            };
+
+            *slot = shrink(&stack, count);
+            Some(stack.with_count(count))
+        }
+        (None, Some(cursor)) => {
+            let count = if left { cursor.count() } else { 1 }.min(max_count(window, idx, &cursor));
+
+            if count == 0 {
+                return Some(cursor);
+            }
+
+            *slot = Some(cursor.clone().with_count(count));
+            shrink(&cursor, count)
+        }
+        (Some(stack), Some(cursor)) => {
+            let max = max_count(window, idx, &cursor);
+
+            if can_combine(&stack, &cursor) {
+                if max == 0 {
+                    // Result slots can only be taken from.
+                    let count = stack
+                        .count()
+                        .min(cursor.item.max_stack().saturating_sub(cursor.count()));
+
+                    if count == 0 {
+                        return Some(cursor);
+                    }
+
+                    *slot = shrink(&stack, count);
+                    return Some(cursor.clone().with_count(cursor.count() + count));
+                }
+
+                let count =
+                    if left { cursor.count() } else { 1 }.min(max.saturating_sub(stack.count()));
+
+                if count == 0 {
+                    return Some(cursor);
+                }
+
+                *slot = Some(stack.clone().with_count(stack.count() + count));
+                shrink(&cursor, count)
+            } else if cursor.count() <= max {
+                *slot = Some(cursor);
+                Some(stack)
+            } else {
+                Some(cursor)
+            }
+        }
+    }
+}
+
+/// Swaps a slot with a slot of the hotbar or the offhand.
+fn swap(slots: &mut [Option<ItemStack>], idx: u16, other_idx: u16, window: &InventoryWindow) {
+    if idx == other_idx || is_result_slot(window, idx) {
+        // Results are only taken by clicking them.
+        return;
+    }
+
+    let can_put = |stack: &Option<ItemStack>, slot: u16| match stack {
+        Some(stack) => stack.count() <= max_count(window, slot, stack),
+        None => true,
+    };
+
+    if can_put(&slots[other_idx as usize], idx) && can_put(&slots[idx as usize], other_idx) {
+        slots.swap(idx as usize, other_idx as usize);
+    }
+}
+
+/// A double click, which gathers items of the same kind as the cursor item
+/// from the window onto the cursor. Returns the new cursor item.
+fn pick_up_all(
+    slots: &mut [Option<ItemStack>],
+    idx: u16,
+    window: &InventoryWindow,
+    cursor_item: &CursorItem,
+) -> Option<ItemStack> {
+    let Some(mut cursor) = cursor_item.0.clone() else {
+        return None;
+    };
+
+    if slots[idx as usize].is_some() {
+        return Some(cursor);
+    }
+
+    let max_stack = cursor.item.max_stack();
+
+    // Stacks that aren't full are gathered first.
+    for gather_full in [false, true] {
+        for (i, slot) in slots.iter_mut().enumerate() {
+            if cursor.count() >= max_stack {
+                return Some(cursor);
+            }
+
+            let Some(stack) = slot.as_ref() else {
+                continue;
+            };
+
+            if !can_combine(stack, &cursor)
+                || is_result_slot(window, i as u16)
+                || (!gather_full && stack.count() >= stack.item.max_stack())
+            {
+                continue;
+            }
+
+            let count = stack.count().min(max_stack - cursor.count());
+
+            *slot = shrink(stack, count);
+            cursor.set_count(cursor.count() + count);
+        }
+    }
+
+    Some(cursor)
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_core::item::ItemKind;
+    use valence_core::protocol::var_int::VarInt;
+
+    use super::*;
+    use crate::Inventory;
+
+    fn click_packet(slot_idx: i16, button: i8, mode: ClickMode) -> ClickSlotC2s {
+        ClickSlotC2s {
+            window_id: 0,
+            state_id: VarInt(0),
+            slot_idx,
+            button,
+            mode,
+            slot_changes: vec![],
+            carried_item: None,
+        }
+    }
+
+    #[test]
+    fn pick_up_and_put_down() {
+        let mut inventory = Inventory::new(InventoryKind::Player);
+        inventory.set_slot(9, ItemStack::new(ItemKind::Diamond, 5, None));
+        inventory.set_slot(10, ItemStack::new(ItemKind::Diamond, 62, None));
+
+        let window = InventoryWindow::new(&inventory, None);
+
+        // Right clicks take half of the stack, rounded up.
+        let (changes, cursor) = click(
+            &click_packet(9, 1, ClickMode::Click),
+            &window,
+            &CursorItem(None),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            changes,
+            [SlotChange {
+                idx: 9,
+                item: Some(ItemStack::new(ItemKind::Diamond, 2, None)),
+            }]
+        );
+        assert_eq!(cursor, Some(ItemStack::new(ItemKind::Diamond, 3, None)));
+
+        // Stacks are only filled up to the max stack size.
+        let (changes, cursor) = click(
+            &click_packet(10, 0, ClickMode::Click),
+            &window,
+            &CursorItem(Some(ItemStack::new(ItemKind::Diamond, 5, None))),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            changes,
+            [SlotChange {
+                idx: 10,
+                item: Some(ItemStack::new(ItemKind::Diamond, 64, None)),
+            }]
+        );
+        assert_eq!(cursor, Some(ItemStack::new(ItemKind::Diamond, 3, None)));
+
+        // Only armor can be put in armor slots.
+        let dirt = ItemStack::new(ItemKind::Dirt, 1, None);
+
+        let (changes, cursor) = click(
+            &click_packet(5, 0, ClickMode::Click),
+            &window,
+            &CursorItem(Some(dirt.clone())),
+            None,
+        )
+        .unwrap();
+
+        assert!(changes.is_empty());
+        assert_eq!(cursor, Some(dirt));
+    }
+
+    #[test]
+    fn gather_on_double_click() {
+        let mut inventory = Inventory::new(InventoryKind::Player);
+        inventory.set_slot(9, ItemStack::new(ItemKind::Diamond, 64, None));
+        inventory.set_slot(10, ItemStack::new(ItemKind::Diamond, 20, None));
+        inventory.set_slot(11, ItemStack::new(ItemKind::Dirt, 20, None));
+        inventory.set_slot(12, ItemStack::new(ItemKind::Diamond, 20, None));
+
+        let window = InventoryWindow::new(&inventory, None);
+
+        let (changes, cursor) = click(
+            &click_packet(13, 0, ClickMode::DoubleClick),
+            &window,
+            &CursorItem(Some(ItemStack::new(ItemKind::Diamond, 10, None))),
+            None,
+        )
+        .unwrap();
+
+        // The full stack is only taken from after the others are used up.
+        assert_eq!(
+            changes,
+            [
+                SlotChange {
+                    idx: 9,
+                    item: Some(ItemStack::new(ItemKind::Diamond, 50, None)),
+                },
+                SlotChange {
+                    idx: 10,
+                    item: None
+                },
+                SlotChange {
+                    idx: 12,
+                    item: None
+                },
+            ]
+        );
+        assert_eq!(cursor, Some(ItemStack::new(ItemKind::Diamond, 64, None)));
+    }
+}
@@ -0,0 +1,744 @@
+//! Crafting in the player's inventory and in crafting tables.
+//!
+//! Whenever the items in a crafting grid change, the [`CraftingRules`]
+//! resource works out what they make and puts it in the result slot. The
+//! client doesn't work out results on its own, so it is sent the result slot
+//! like any other slot change.
+//!
+//! Taking the result uses up one item from each slot of the grid, and shift
+//! clicking it crafts as many times as the grid and the player's inventory
+//! allow. Clicking a recipe in the recipe book moves its ingredients from the
+//! player's inventory into the grid.
+
+use std::ops::Range;
+
+use bevy_ecs::prelude::*;
+use tracing::debug;
+use valence_client::event_loop::PacketEvent;
+use valence_client::Client;
+use valence_core::item::{ItemKind, ItemStack};
+use valence_core::protocol::encode::WritePacket;
+
+use crate::click::{can_combine, shrink, slot_changes};
+use crate::packet::{ClickMode, ClickSlotC2s, CraftFailedResponseS2c, CraftRequestC2s, SlotChange};
+use crate::quick_move::{move_to, result_target, QuickMove};
+use crate::recipe::{
+    Ingredient, RecipeData, RecipeRegistry, ShapedRecipe, ShapelessRecipe, UnlockedRecipes,
+};
+use crate::{
+    ClientInventoryState, CursorItem, Inventory, InventoryKind, InventoryWindow,
+    InventoryWindowMut, OpenInventory,
+};
+
+/// The slot that the result of a crafting grid is put in, in both the
+/// player's inventory and crafting tables.
+pub const RESULT_SLOT: u16 = 0;
+
+/// The items in a crafting grid, row by row.
+#[derive(Copy, Clone, Debug)]
+pub struct CraftingGrid<'a> {
+    /// The number of slots in each row, which is 2 in the player's inventory
+    /// and 3 in crafting tables.
+    pub width: u8,
+    pub slots: &'a [Option<ItemStack>],
+}
+
+impl CraftingGrid<'_> {
+    pub fn height(&self) -> u8 {
+        (self.slots.len() / self.width as usize) as u8
+    }
+}
+
+/// Works out what the items in a crafting grid make.
+pub trait CraftingResolver: Send + Sync + 'static {
+    /// Returns the stack that the items in a grid make, or `None` if they
+    /// don't make anything.
+    fn result(&self, grid: CraftingGrid, recipes: &RecipeRegistry) -> Option<ItemStack>;
+
+    /// Returns what is left behind when an ingredient is used up, such as the
+    /// empty bucket of a milk bucket.
+    fn remainder(&self, ingredient: &ItemStack) -> Option<ItemStack> {
+        remainder(ingredient.item).map(|item| ItemStack::new(item, 1, None))
+    }
+}
+
+/// The [`CraftingResolver`] that fills the result slots of crafting grids. By
+/// default, this is [`RecipeResolver`].
+///
+/// ```
+/// # use valence_core::item::{ItemKind, ItemStack};
+/// # use valence_inventory::crafting::{CraftingGrid, CraftingResolver, CraftingRules};
+/// # use valence_inventory::recipe::RecipeRegistry;
+/// /// Any single item turns into a diamond.
+/// struct Alchemy;
+///
+/// impl CraftingResolver for Alchemy {
+///     fn result(&self, grid: CraftingGrid, _recipes: &RecipeRegistry) -> Option<ItemStack> {
+///         let mut items = grid.slots.iter().flatten();
+///
+///         match (items.next(), items.next()) {
+///             (Some(_), None) => Some(ItemStack::new(ItemKind::Diamond, 1, None)),
+///             _ => None,
+///         }
+///     }
+/// }
+///
+/// let rules = CraftingRules::new(Alchemy);
+/// ```
+#[derive(Resource)]
+pub struct CraftingRules(pub Box<dyn CraftingResolver>);
+
+impl CraftingRules {
+    pub fn new(resolver: impl CraftingResolver) -> Self {
+        Self(Box::new(resolver))
+    }
+}
+
+impl Default for CraftingRules {
+    fn default() -> Self {
+        Self::new(RecipeResolver)
+    }
+}
+
+/// Matches crafting grids against the shaped and shapeless recipes in the
+/// [`RecipeRegistry`]. When several recipes match, the one that was added
+/// first is used.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct RecipeResolver;
+
+impl CraftingResolver for RecipeResolver {
+    fn result(&self, grid: CraftingGrid, recipes: &RecipeRegistry) -> Option<ItemStack> {
+        recipes
+            .iter()
+            .find(|(_, recipe)| matches_recipe(recipe, grid))
+            .and_then(|(_, recipe)| match recipe {
+                RecipeData::CraftingShaped(recipe) => Some(recipe.result.clone()),
+                RecipeData::CraftingShapeless(recipe) => Some(recipe.result.clone()),
+                _ => None,
+            })
+    }
+}
+
+/// Returns whether the items in a grid match a shaped or shapeless recipe.
+pub fn matches_recipe(recipe: &RecipeData, grid: CraftingGrid) -> bool {
+    match recipe {
+        RecipeData::CraftingShaped(recipe) => matches_shaped(recipe, grid),
+        RecipeData::CraftingShapeless(recipe) => matches_shapeless(recipe, grid),
+        _ => false,
+    }
+}
+
+fn ingredient_matches(ingredient: &Ingredient, slot: Option<&ItemStack>) -> bool {
+    match slot {
+        Some(stack) => ingredient.0.contains(&stack.item),
+        None => ingredient.0.is_empty(),
+    }
+}
+
+/// The pattern of a shaped recipe can be anywhere in the grid, and it can be
+/// mirrored from left to right.
+fn matches_shaped(recipe: &ShapedRecipe, grid: CraftingGrid) -> bool {
+    let (width, height) = (recipe.width as usize, recipe.height as usize);
+    let (grid_width, grid_height) = (grid.width as usize, grid.height() as usize);
+
+    if width > grid_width || height > grid_height || recipe.ingredients.len() != width * height {
+        return false;
+    }
+
+    let matches_at = |dx: usize, dy: usize, mirrored: bool| {
+        grid.slots.iter().enumerate().all(|(i, slot)| {
+            let (x, y) = (i % grid_width, i / grid_width);
+
+            if !(dx..dx + width).contains(&x) || !(dy..dy + height).contains(&y) {
+                return slot.is_none();
+            }
+
+            let x = if mirrored { dx + width - 1 - x } else { x - dx };
+
+            ingredient_matches(&recipe.ingredients[(y - dy) * width + x], slot.as_ref())
+        })
+    };
+
+    (0..=grid_width - width).any(|dx| {
+        (0..=grid_height - height).any(|dy| matches_at(dx, dy, false) || matches_at(dx, dy, true))
+    })
+}
+
+fn matches_shapeless(recipe: &ShapelessRecipe, grid: CraftingGrid) -> bool {
+    /// Tries to give each item its own ingredient.
+    fn assign(items: &[ItemKind], ingredients: &[Ingredient], used: &mut [bool]) -> bool {
+        let Some((item, rest)) = items.split_first() else {
+            return true;
+        };
+
+        for (i, ingredient) in ingredients.iter().enumerate() {
+            if !used[i] && ingredient.0.contains(item) {
+                used[i] = true;
+
+                if assign(rest, ingredients, used) {
+                    return true;
+                }
+
+                used[i] = false;
+            }
+        }
+
+        false
+    }
+
+    let items: Vec<_> = grid.slots.iter().flatten().map(|s| s.item).collect();
+
+    items.len() == recipe.ingredients.len()
+        && assign(
+            &items,
+            &recipe.ingredients,
+            &mut vec![false; recipe.ingredients.len()],
+        )
+}
+
+/// The item that vanilla leaves behind when an item is used up in crafting.
+fn remainder(item: ItemKind) -> Option<ItemKind> {
+    match item {
+        ItemKind::WaterBucket
+        | ItemKind::LavaBucket
+        | ItemKind::MilkBucket
+        | ItemKind::PowderSnowBucket => Some(ItemKind::Bucket),
+        ItemKind::HoneyBottle | ItemKind::DragonBreath => Some(ItemKind::GlassBottle),
+        _ => None,
+    }
+}
+
+/// Returns the window slots of an inventory's crafting grid and the width of
+/// the grid.
+fn grid_slots(kind: InventoryKind) -> Option<(Range<u16>, u8)> {
+    match kind {
+        InventoryKind::Player => Some((1..5, 2)),
+        InventoryKind::Crafting => Some((1..10, 3)),
+        _ => None,
+    }
+}
+
+fn window_grid_slots(window: &InventoryWindow) -> Option<(Range<u16>, u8)> {
+    grid_slots(
+        window
+            .open_inventory
+            .map_or(InventoryKind::Player, |inv| inv.kind()),
+    )
+}
+
+/// Returns the first window slot of the player's main inventory, which is
+/// followed by the hotbar.
+fn player_slots_start(window: &InventoryWindow) -> u16 {
+    window.open_inventory.map_or(9, |inv| inv.slot_count())
+}
+
+fn result_of(
+    slots: &[Option<ItemStack>],
+    (grid, width): (Range<u16>, u8),
+    rules: &CraftingRules,
+    recipes: &RecipeRegistry,
+) -> Option<ItemStack> {
+    let grid = CraftingGrid {
+        width,
+        slots: &slots[grid.start as usize..grid.end as usize],
+    };
+
+    rules.0.result(grid, recipes)
+}
+
+/// Puts a stack in the player's inventory like vanilla does. Stacks of the
+/// same item are filled up first, and then the rest goes in the first empty
+/// slot, starting with the hotbar. Returns what doesn't fit.
+fn give_to_player(
+    slots: &mut [Option<ItemStack>],
+    mut stack: ItemStack,
+    window: &InventoryWindow,
+) -> Option<ItemStack> {
+    let start = player_slots_start(window);
+    let order: Vec<u16> = (start + 27..start + 36).chain(start..start + 27).collect();
+    let max_stack = stack.item.max_stack();
+
+    for &i in &order {
+        let Some(slot) = &mut slots[i as usize] else {
+            continue;
+        };
+
+        if can_combine(slot, &stack) && slot.count() < max_stack {
+            let count = stack.count().min(max_stack - slot.count());
+
+            slot.set_count(slot.count() + count);
+            stack = shrink(&stack, count)?;
+        }
+    }
+
+    match order.iter().find(|&&i| slots[i as usize].is_none()) {
+        Some(&i) => {
+            slots[i as usize] = Some(stack);
+            None
+        }
+        None => Some(stack),
+    }
+}
+
+/// Uses up one item from each slot of the grid and puts the new result in the
+/// result slot. The remainders of ingredients that are used up are left in
+/// the grid, and the other remainders go to the player's inventory. Remainders
+/// that don't fit anywhere are lost.
+fn craft(
+    slots: &mut [Option<ItemStack>],
+    window: &InventoryWindow,
+    rules: &CraftingRules,
+    recipes: &RecipeRegistry,
+) {
+    let Some(grid) = window_grid_slots(window) else {
+        return;
+    };
+
+    for idx in grid.0.clone() {
+        let Some(stack) = slots[idx as usize].clone() else {
+            continue;
+        };
+
+        let remainder = rules.0.remainder(&stack);
+
+        slots[idx as usize] = shrink(&stack, 1);
+
+        if let Some(remainder) = remainder {
+            if slots[idx as usize].is_none() {
+                slots[idx as usize] = Some(remainder);
+            } else {
+                give_to_player(slots, remainder, window);
+            }
+        }
+    }
+
+    slots[RESULT_SLOT as usize] = result_of(slots, grid, rules, recipes);
+}
+
+/// Returns whether a slot of a window is the result slot of a crafting grid.
+pub(super) fn is_result_slot(window: &InventoryWindow, idx: i16) -> bool {
+    window_grid_slots(window).is_some() && idx == RESULT_SLOT as i16
+}
+
+/// Returns whether a click takes the result out of a crafting grid, which is
+/// worked out by the server.
+pub(super) fn is_take_result(pkt: &ClickSlotC2s, window: &InventoryWindow) -> bool {
+    is_result_slot(window, pkt.slot_idx)
+        && matches!(pkt.mode, ClickMode::Click | ClickMode::ShiftClick)
+}
+
+/// Works out the slot changes and cursor item of a click that takes the result
+/// out of a crafting grid. Returns `None` if the click doesn't take a result.
+pub(super) fn click(
+    pkt: &ClickSlotC2s,
+    window: &InventoryWindow,
+    cursor_item: &CursorItem,
+    rules: &CraftingRules,
+    recipes: &RecipeRegistry,
+    quick_move_rules: Option<&dyn QuickMove>,
+) -> Option<(Vec<SlotChange>, Option<ItemStack>)> {
+    if !is_take_result(pkt, window) {
+        return None;
+    }
+
+    let mut slots: Vec<_> = (0..window.slot_count())
+        .map(|i| window.slot(i).cloned())
+        .collect();
+
+    let mut cursor = cursor_item.0.clone();
+
+    if let Some(result) = slots[RESULT_SLOT as usize].clone() {
+        if pkt.mode == ClickMode::Click {
+            // Both buttons take the whole result, which has to fit on the cursor.
+            let taken = match &cursor {
+                None => Some(result),
+                Some(cursor) if can_combine(cursor, &result) => {
+                    let count = cursor.count() as u16 + result.count() as u16;
+
+                    (count <= result.item.max_stack() as u16)
+                        .then(|| result.with_count(count as u8))
+                }
+                Some(_) => None,
+            };
+
+            if taken.is_some() {
+                cursor = taken;
+                craft(&mut slots, window, rules, recipes);
+            }
+        } else {
+            // Shift clicks craft again until the result changes or doesn't fit in the
+            // target slots anymore.
+            while slots[RESULT_SLOT as usize].as_ref() == Some(&result) {
+                let Some(target) =
+                    result_target(window, &slots, RESULT_SLOT, &result, quick_move_rules)
+                else {
+                    break;
+                };
+
+                let mut moved = slots.clone();
+
+                if move_to(&mut moved, result.clone(), target, window).is_some() {
+                    break;
+                }
+
+                slots = moved;
+                craft(&mut slots, window, rules, recipes);
+            }
+        }
+    }
+
+    Some((slot_changes(slots, window), cursor))
+}
+
+/// Returns whether a stack is a plain stack of an item. Only plain stacks are
+/// moved by the recipe book, so that named or enchanted items aren't used up.
+fn is_plain(stack: &ItemStack, kind: ItemKind) -> bool {
+    *stack == ItemStack::new(kind, stack.count(), None)
+}
+
+/// Works out the slots of a window after the ingredients of a recipe are moved
+/// into its crafting grid. Returns `None` if the player doesn't have the
+/// ingredients.
+fn place_recipe(
+    window: &InventoryWindow,
+    recipe: &RecipeData,
+    make_all: bool,
+) -> Option<Vec<Option<ItemStack>>> {
+    let (grid, width) = window_grid_slots(window)?;
+    let height = grid.len() / width as usize;
+
+    // The ingredient that goes in each slot of the grid. Shaped recipes go in the
+    // top left corner.
+    let layout: Vec<Option<&Ingredient>> = match recipe {
+        RecipeData::CraftingShaped(recipe) => {
+            if recipe.width > width || recipe.height as usize > height {
+                return None;
+            }
+
+            (0..grid.len())
+                .map(|i| {
+                    let (x, y) = (i % width as usize, i / width as usize);
+
+                    (x < recipe.width as usize && y < recipe.height as usize)
+                        .then(|| &recipe.ingredients[y * recipe.width as usize + x])
+                })
+                .collect()
+        }
+        RecipeData::CraftingShapeless(recipe) => {
+            if recipe.ingredients.len() > grid.len() {
+                return None;
+            }
+
+            (0..grid.len()).map(|i| recipe.ingredients.get(i)).collect()
+        }
+        _ => return None,
+    };
+
+    let mut slots: Vec<_> = (0..window.slot_count())
+        .map(|i| window.slot(i).cloned())
+        .collect();
+
+    // Clicking the same recipe again adds one more of each ingredient.
+    let layout_matches = layout
+        .iter()
+        .zip(&slots[grid.start as usize..grid.end as usize])
+        .all(|(ingredient, slot)| match ingredient {
+            Some(ingredient) => ingredient_matches(ingredient, slot.as_ref()),
+            None => slot.is_none(),
+        });
+
+    let placed = if layout_matches {
+        slots[grid.start as usize..grid.end as usize]
+            .iter()
+            .flatten()
+            .map(|s| s.count())
+            .min()
+            .unwrap_or(0)
+    } else {
+        0
+    };
+
+    // The items in the grid are put back before the ingredients are placed.
+    for idx in grid.clone() {
+        if let Some(stack) = slots[idx as usize].take() {
+            if give_to_player(&mut slots, stack, window).is_some() {
+                return None;
+            }
+        }
+    }
+
+    let start = player_slots_start(window);
+    let player_slots = start as usize..start as usize + 36;
+
+    let place = |count: u8| {
+        let mut slots = slots.clone();
+
+        for (i, ingredient) in layout.iter().copied().enumerate() {
+            let Some(ingredient) = ingredient.filter(|i| !i.0.is_empty()) else {
+                continue;
+            };
+
+            let &kind = ingredient.0.iter().find(|&&kind| {
+                let available: u32 = slots[player_slots.clone()]
+                    .iter()
+                    .flatten()
+                    .filter(|s| is_plain(s, kind))
+                    .map(|s| s.count() as u32)
+                    .sum();
+
+                count <= kind.max_stack() && available >= count as u32
+            })?;
+
+            let mut needed = count;
+
+            for slot in &mut slots[player_slots.clone()] {
+                let Some(stack) = slot.as_ref().filter(|s| is_plain(s, kind)) else {
+                    continue;
+                };
+
+                let taken = needed.min(stack.count());
+                *slot = shrink(stack, taken);
+                needed -= taken;
+
+                if needed == 0 {
+                    break;
+                }
+            }
+
+            slots[grid.start as usize + i] = Some(ItemStack::new(kind, count, None));
+        }
+
+        Some(slots)
+    };
+
+    if make_all {
+        (1..=ItemStack::STACK_MAX).rev().find_map(place)
+    } else {
+        (1..=placed + 1).rev().find_map(place)
+    }
+}
+
+pub(super) fn handle_craft_request(
+    mut packets: EventReader<PacketEvent>,
+    mut clients: Query<(
+        &mut Client,
+        &mut Inventory,
+        &ClientInventoryState,
+        Option<&OpenInventory>,
+        &UnlockedRecipes,
+    )>,
+    mut windows: Query<&mut Inventory, Without<Client>>,
+    recipes: Res<RecipeRegistry>,
+) {
+    for packet in packets.iter() {
+        let Some(pkt) = packet.decode::<CraftRequestC2s>() else {
+            continue;
+        };
+
+        let Ok((mut client, mut player_inventory, inv_state, open_inventory, unlocked)) =
+            clients.get_mut(packet.client)
+        else {
+            continue;
+        };
+
+        let window_id = if open_inventory.is_some() {
+            inv_state.window_id
+        } else {
+            0
+        };
+
+        if pkt.window_id as u8 != window_id {
+            continue;
+        }
+
+        let recipe_id = pkt.recipe.as_str_ident();
+
+        let Some(recipe) = recipes
+            .get(recipe_id)
+            .filter(|_| unlocked.contains(recipe_id))
+        else {
+            debug!(
+                "client {:?} requested recipe {recipe_id} which it doesn't have",
+                packet.client
+            );
+            continue;
+        };
+
+        let mut open_window = match open_inventory {
+            Some(open) => match windows.get_mut(open.entity) {
+                Ok(inventory) => Some(inventory),
+                Err(_) => continue,
+            },
+            None => None,
+        };
+
+        let window = InventoryWindow::new(&player_inventory, open_window.as_deref());
+
+        if window_grid_slots(&window).is_none() {
+            // Not a crafting window.
+            continue;
+        }
+
+        let Some(slots) = place_recipe(&window, recipe, pkt.make_all) else {
+            // The client shows the recipe in the grid instead.
+            client.write_packet(&CraftFailedResponseS2c {
+                window_id,
+                recipe: pkt.recipe,
+            });
+            continue;
+        };
+
+        let mut window = InventoryWindowMut::new(&mut player_inventory, open_window.as_deref_mut());
+
+        for (idx, item) in slots.into_iter().enumerate() {
+            if window.slot(idx as u16) != item.as_ref() {
+                window.set_slot(idx as u16, item);
+            }
+        }
+    }
+}
+
+/// Puts the results of crafting grids whose items changed in their result
+/// slots.
+pub(super) fn update_crafting_results(
+    mut inventories: Query<&mut Inventory>,
+    rules: Res<CraftingRules>,
+    recipes: Res<RecipeRegistry>,
+) {
+    for mut inventory in &mut inventories {
+        if !(inventory.is_changed() || rules.is_changed() || recipes.is_changed()) {
+            continue;
+        }
+
+        let Some(grid) = grid_slots(inventory.kind()) else {
+            continue;
+        };
+
+        let result = result_of(&inventory.slots, grid, &rules, &recipes);
+
+        if inventory.slot(RESULT_SLOT) != result.as_ref() {
+            inventory.set_slot(RESULT_SLOT, result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recipe::CraftingCategory;
+
+    /// Turns rows of letters into items, where `.` is an empty slot.
+    fn items(rows: &str) -> Vec<Option<ItemKind>> {
+        rows.chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| match c {
+                'C' => Some(ItemKind::Cobblestone),
+                'S' => Some(ItemKind::Stick),
+                'E' => Some(ItemKind::Egg),
+                'U' => Some(ItemKind::Sugar),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn grid(rows: &str) -> Vec<Option<ItemStack>> {
+        items(rows)
+            .into_iter()
+            .map(|i| i.map(|kind| ItemStack::new(kind, 1, None)))
+            .collect()
+    }
+
+    #[test]
+    fn shaped_recipes_can_move_and_mirror() {
+        let axe = RecipeData::CraftingShaped(ShapedRecipe {
+            group: String::new(),
+            category: CraftingCategory::Equipment,
+            width: 2,
+            height: 3,
+            ingredients: items("CC CS .S")
+                .into_iter()
+                .map(|i| Ingredient(i.into_iter().collect()))
+                .collect(),
+            result: ItemStack::new(ItemKind::StoneAxe, 1, None),
+            show_notification: true,
+        });
+
+        let matches_grid = |width, rows| {
+            matches_recipe(
+                &axe,
+                CraftingGrid {
+                    width,
+                    slots: &grid(rows),
+                },
+            )
+        };
+
+        assert!(matches_grid(3, "CC. CS. .S."));
+        assert!(matches_grid(3, ".CC .SC .S."));
+
+        // Anything outside of the pattern keeps it from matching.
+        assert!(!matches_grid(3, "CCS CS. .S."));
+
+        // The pattern doesn't fit in the player's inventory.
+        assert!(!matches_grid(2, "CC CS"));
+    }
+
+    #[test]
+    fn shapeless_ingredients_in_any_order() {
+        let recipe = RecipeData::CraftingShapeless(ShapelessRecipe {
+            group: String::new(),
+            category: CraftingCategory::Misc,
+            ingredients: vec![
+                Ingredient(vec![ItemKind::Sugar, ItemKind::Egg]),
+                Ingredient(vec![ItemKind::Sugar]),
+            ],
+            result: ItemStack::new(ItemKind::Cake, 1, None),
+        });
+
+        let matches_grid = |rows| {
+            matches_recipe(
+                &recipe,
+                CraftingGrid {
+                    width: 2,
+                    slots: &grid(rows),
+                },
+            )
+        };
+
+        // The egg has to be used for the first ingredient.
+        assert!(matches_grid(".U E."));
+        assert!(!matches_grid("EE .."));
+        assert!(!matches_grid("UU U."));
+    }
+
+    #[test]
+    fn buckets_are_left_behind() {
+        let mut inventory = Inventory::new(InventoryKind::Player);
+        inventory.set_slot(1, ItemStack::new(ItemKind::MilkBucket, 1, None));
+        inventory.set_slot(2, ItemStack::new(ItemKind::HoneyBottle, 2, None));
+
+        let window = InventoryWindow::new(&inventory, None);
+
+        let mut slots: Vec<_> = (0..window.slot_count())
+            .map(|i| window.slot(i).cloned())
+            .collect();
+
+        craft(
+            &mut slots,
+            &window,
+            &CraftingRules::default(),
+            &RecipeRegistry::default(),
+        );
+
+        // The bottle of the stack that isn't used up goes to the hotbar.
+        assert_eq!(slots[1], Some(ItemStack::new(ItemKind::Bucket, 1, None)));
+        assert_eq!(
+            slots[2],
+            Some(ItemStack::new(ItemKind::HoneyBottle, 1, None))
+        );
+        assert_eq!(
+            slots[36],
+            Some(ItemStack::new(ItemKind::GlassBottle, 1, None))
+        );
+    }
+}
@@ -0,0 +1,326 @@
+//! Dragging items across slots, which the client sends as a sequence of
+//! [`ClickMode::Drag`] packets: one to start the drag, one for each slot
+//! added, and one to end it.
+//!
+//! The server keeps track of the drag and works out the result on its own
+//! instead of trusting the slot changes in the final packet.
+
+use valence_core::item::ItemStack;
+
+use crate::packet::{ClickMode, ClickSlotC2s, SlotChange};
+use crate::{CursorItem, InventoryWindow};
+
+/// A drag in progress.
+#[derive(Clone, Debug)]
+pub(super) struct DragState {
+    window_id: u8,
+    kind: DragKind,
+    /// The slots the drag has passed over, in order.
+    slots: Vec<u16>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum DragKind {
+    /// Left mouse button. The cursor stack is split evenly between the slots.
+    Split,
+    /// Right mouse button. One item is put in each slot.
+    One,
+    /// Middle mouse button, only in creative mode. Each slot is filled with a
+    /// full stack, and the cursor stack is kept.
+    Clone,
+}
+
+/// Updates the drag state of a client with a [`ClickMode::Drag`] packet.
+///
+/// Returns the slot changes and cursor item that result from the packet. They
+/// are empty and unchanged unless this packet ends a drag. Packets that don't
+/// continue the current drag cancel it.
+pub(super) fn update_drag(
+    drag: &mut Option<DragState>,
+    packet: &ClickSlotC2s,
+    window: &InventoryWindow,
+    cursor_item: &CursorItem,
+    creative: bool,
+) -> (Vec<SlotChange>, Option<ItemStack>) {
+    debug_assert_eq!(packet.mode, ClickMode::Drag);
+
+    let unchanged = (vec![], cursor_item.0.clone());
+
+    let Some(cursor) = &cursor_item.0 else {
+        *drag = None;
+        return unchanged;
+    };
+
+    let kind = match packet.button >> 2 {
+        0 => DragKind::Split,
+        1 => DragKind::One,
+        2 if creative => DragKind::Clone,
+        _ => {
+            *drag = None;
+            return unchanged;
+        }
+    };
+
+    match packet.button & 3 {
+        // Start a drag.
+        0 => {
+            *drag = Some(DragState {
+                window_id: packet.window_id,
+                kind,
+                slots: vec![],
+            });
+
+            unchanged
+        }
+        // Add a slot.
+        1 => {
+            let Some(state) = drag
+                .as_mut()
+                .filter(|state| state.window_id == packet.window_id && state.kind == kind)
+            else {
+                *drag = None;
+                return unchanged;
+            };
+
+            let idx = packet.slot_idx as u16;
+
+            // Like vanilla, slots that can't take part in the drag are ignored.
+            if packet.slot_idx >= 0
+                && !state.slots.contains(&idx)
+                && can_drag_to(window.slot(idx), cursor)
+                && (kind == DragKind::Clone || cursor.count() as usize > state.slots.len())
+            {
+                state.slots.push(idx);
+            }
+
+            unchanged
+        }
+        // End the drag.
+        2 => {
+            let Some(state) = drag
+                .take()
+                .filter(|state| state.window_id == packet.window_id && state.kind == kind)
+            else {
+                return unchanged;
+            };
+
+            distribute(&state, window, cursor)
+        }
+        _ => {
+            *drag = None;
+            unchanged
+        }
+    }
+}
+
+/// Returns if the cursor stack can be dragged onto a slot.
+fn can_drag_to(slot: Option<&ItemStack>, cursor: &ItemStack) -> bool {
+    match slot {
+        Some(stack) => {
+            stack.item == cursor.item
+                && stack.nbt == cursor.nbt
+                && stack.count() <= cursor.item.max_stack()
+        }
+        None => true,
+    }
+}
+
+/// Spreads the cursor stack over the slots of a finished drag.
+fn distribute(
+    state: &DragState,
+    window: &InventoryWindow,
+    cursor: &ItemStack,
+) -> (Vec<SlotChange>, Option<ItemStack>) {
+    let max_stack = cursor.item.max_stack();
+    let slot_count = state.slots.len();
+
+    let per_slot = match state.kind {
+        DragKind::Split => cursor.count() as usize / slot_count.max(1),
+        DragKind::One => 1,
+        DragKind::Clone => max_stack as usize,
+    };
+
+    let mut remaining = cursor.count();
+    let mut slot_changes = vec![];
+
+    for &idx in &state.slots {
+        let slot = window.slot(idx);
+
+        // The slot could have changed since it was added to the drag.
+        if !can_drag_to(slot, cursor)
+            || (state.kind != DragKind::Clone && (cursor.count() as usize) < slot_count)
+        {
+            continue;
+        }
+
+        let old_count = slot.map_or(0, |stack| stack.count());
+        let new_count = (old_count as usize + per_slot).min(max_stack as usize) as u8;
+
+        if state.kind != DragKind::Clone {
+            remaining -= new_count - old_count;
+        }
+
+        slot_changes.push(SlotChange {
+            idx: idx as i16,
+            item: Some(cursor.clone().with_count(new_count)),
+        });
+    }
+
+    let carried_item = (remaining > 0).then(|| cursor.clone().with_count(remaining));
+
+    (slot_changes, carried_item)
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_core::item::ItemKind;
+    use valence_core::protocol::var_int::VarInt;
+
+    use super::*;
+    use crate::{Inventory, InventoryKind};
+
+    fn drag_packet(button: i8, slot_idx: i16) -> ClickSlotC2s {
+        ClickSlotC2s {
+            window_id: 0,
+            state_id: VarInt(0),
+            slot_idx,
+            button,
+            mode: ClickMode::Drag,
+            slot_changes: vec![],
+            carried_item: None,
+        }
+    }
+
+    /// Sends a whole drag over `slots` and returns the result of the last
+    /// packet.
+    fn drag(
+        inv: &Inventory,
+        cursor: &CursorItem,
+        button: i8,
+        slots: &[i16],
+        creative: bool,
+    ) -> (Vec<SlotChange>, Option<ItemStack>) {
+        let window = InventoryWindow::new(inv, None);
+        let mut state = None;
+
+        let start = drag_packet(button << 2, -999);
+        assert!(update_drag(&mut state, &start, &window, cursor, creative)
+            .0
+            .is_empty());
+
+        for &slot in slots {
+            let add = drag_packet(button << 2 | 1, slot);
+            assert!(update_drag(&mut state, &add, &window, cursor, creative)
+                .0
+                .is_empty());
+        }
+
+        let end = drag_packet(button << 2 | 2, -999);
+        let res = update_drag(&mut state, &end, &window, cursor, creative);
+
+        assert!(state.is_none());
+
+        res
+    }
+
+    fn changes(res: &[SlotChange]) -> Vec<(i16, u8)> {
+        res.iter()
+            .map(|c| (c.idx, c.item.as_ref().map_or(0, |s| s.count())))
+            .collect()
+    }
+
+    #[test]
+    fn split_drag() {
+        let mut inv = Inventory::new(InventoryKind::Player);
+        inv.set_slot(10, ItemStack::new(ItemKind::Diamond, 60, None));
+        inv.set_slot(11, ItemStack::new(ItemKind::GoldIngot, 1, None));
+
+        let cursor = CursorItem(Some(ItemStack::new(ItemKind::Diamond, 17, None)));
+
+        // Slot 11 has another item, and slot 9 is added twice.
+        let (slot_changes, carried) = drag(&inv, &cursor, 0, &[9, 10, 11, 9, 12], false);
+
+        // 17 items over 3 slots is 5 each, but slot 10 only has room for 4.
+        assert_eq!(changes(&slot_changes), [(9, 5), (10, 64), (12, 5)]);
+        assert_eq!(carried, Some(ItemStack::new(ItemKind::Diamond, 3, None)));
+    }
+
+    #[test]
+    fn one_per_slot_drag() {
+        let inv = Inventory::new(InventoryKind::Player);
+        let cursor = CursorItem(Some(ItemStack::new(ItemKind::Diamond, 3, None)));
+
+        let (slot_changes, carried) = drag(&inv, &cursor, 1, &[20, 21, 22], false);
+
+        assert_eq!(changes(&slot_changes), [(20, 1), (21, 1), (22, 1)]);
+        assert_eq!(carried, None);
+
+        // A slot can't be added when there are no more items for it.
+        let (slot_changes, carried) = drag(&inv, &cursor, 1, &[20, 21, 22, 23], false);
+
+        assert_eq!(changes(&slot_changes), [(20, 1), (21, 1), (22, 1)]);
+        assert_eq!(carried, None);
+    }
+
+    #[test]
+    fn clone_drag() {
+        let inv = Inventory::new(InventoryKind::Player);
+        let cursor = CursorItem(Some(ItemStack::new(ItemKind::EnderPearl, 2, None)));
+
+        let (slot_changes, carried) = drag(&inv, &cursor, 2, &[30, 31], true);
+
+        assert_eq!(changes(&slot_changes), [(30, 16), (31, 16)]);
+        assert_eq!(carried, cursor.0);
+
+        // Only allowed in creative mode.
+        let (slot_changes, carried) = drag(&inv, &cursor, 2, &[30, 31], false);
+
+        assert!(slot_changes.is_empty());
+        assert_eq!(carried, cursor.0);
+    }
+
+    #[test]
+    fn out_of_order_drag() {
+        let inv = Inventory::new(InventoryKind::Player);
+        let window = InventoryWindow::new(&inv, None);
+        let cursor = CursorItem(Some(ItemStack::new(ItemKind::Diamond, 10, None)));
+        let mut state = None;
+
+        // Adding a slot or ending without starting does nothing.
+        update_drag(&mut state, &drag_packet(1, 9), &window, &cursor, false);
+        assert!(state.is_none());
+
+        let (slot_changes, carried) =
+            update_drag(&mut state, &drag_packet(2, -999), &window, &cursor, false);
+        assert!(slot_changes.is_empty());
+        assert_eq!(carried, cursor.0);
+
+        // A packet for a different button cancels the drag.
+        update_drag(&mut state, &drag_packet(0, -999), &window, &cursor, false);
+        update_drag(&mut state, &drag_packet(1, 9), &window, &cursor, false);
+        update_drag(&mut state, &drag_packet(5, 10), &window, &cursor, false);
+        assert!(state.is_none());
+
+        let (slot_changes, _) =
+            update_drag(&mut state, &drag_packet(2, -999), &window, &cursor, false);
+        assert!(slot_changes.is_empty());
+
+        // Starting again restarts the drag.
+        update_drag(&mut state, &drag_packet(0, -999), &window, &cursor, false);
+        update_drag(&mut state, &drag_packet(1, 9), &window, &cursor, false);
+        update_drag(&mut state, &drag_packet(0, -999), &window, &cursor, false);
+        update_drag(&mut state, &drag_packet(1, 10), &window, &cursor, false);
+
+        let (slot_changes, carried) =
+            update_drag(&mut state, &drag_packet(2, -999), &window, &cursor, false);
+        assert_eq!(changes(&slot_changes), [(10, 10)]);
+        assert_eq!(carried, None);
+
+        // Packets for another window cancel the drag.
+        update_drag(&mut state, &drag_packet(0, -999), &window, &cursor, false);
+        let mut add = drag_packet(1, 9);
+        add.window_id = 3;
+        update_drag(&mut state, &add, &window, &cursor, false);
+        assert!(state.is_none());
+    }
+}
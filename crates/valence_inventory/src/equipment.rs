@@ -0,0 +1,200 @@
+//! Shows the items that players hold and wear to the other clients that can
+//! see them.
+//!
+//! The equipment is mirrored from the player's [`Inventory`] for every client
+//! when [`InventorySettings::mirror_equipment`] is enabled, or only for the
+//! clients with a [`MirrorEquipment`] component otherwise. An
+//! [`EquipmentOverride`] changes what the other clients see without touching
+//! the inventory.
+//!
+//! [`InventorySettings::mirror_equipment`]: crate::InventorySettings::mirror_equipment
+
+use bevy_ecs::prelude::*;
+use valence_client::{Client, View};
+use valence_core::item::{ItemKind, ItemStack};
+use valence_core::protocol::encode::WritePacket;
+use valence_core::protocol::var_int::VarInt;
+use valence_entity::packet::{EntityEquipmentUpdateS2c, EquipmentEntry};
+use valence_entity::{EntityId, Location, Position};
+
+use crate::{HeldItem, Inventory, InventorySettings, OFFHAND_SLOT};
+
+/// The equipment slots of an entity, as sent in [`EntityEquipmentUpdateS2c`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum EquipmentSlot {
+    MainHand,
+    OffHand,
+    Feet,
+    Legs,
+    Chest,
+    Head,
+}
+
+impl EquipmentSlot {
+    pub const ALL: [Self; 6] = [
+        Self::MainHand,
+        Self::OffHand,
+        Self::Feet,
+        Self::Legs,
+        Self::Chest,
+        Self::Head,
+    ];
+
+    /// Returns the slot in the player's inventory that this equipment slot is
+    /// mirrored from.
+    pub fn player_slot(self, held_item: &HeldItem) -> u16 {
+        match self {
+            Self::MainHand => held_item.slot(),
+            Self::OffHand => OFFHAND_SLOT,
+            _ => self.armor_slot().unwrap(),
+        }
+    }
+
+    /// Returns the armor slot of the player's inventory for this equipment
+    /// slot, or `None` for the hands.
+    pub fn armor_slot(self) -> Option<u16> {
+        match self {
+            Self::MainHand | Self::OffHand => None,
+            Self::Feet => Some(8),
+            Self::Legs => Some(7),
+            Self::Chest => Some(6),
+            Self::Head => Some(5),
+        }
+    }
+
+    pub fn is_armor(self) -> bool {
+        self.armor_slot().is_some()
+    }
+
+    /// Returns the equipment slot that an item goes in, like vanilla does when
+    /// an entity picks it up. Armor, heads, and elytras are worn, shields go in
+    /// the offhand, and everything else goes in the main hand.
+    pub fn for_item(item: ItemKind) -> Self {
+        let name = item.to_str();
+
+        if name.ends_with("_helmet")
+            || name.ends_with("_head")
+            || name.ends_with("_skull")
+            || item == ItemKind::CarvedPumpkin
+        {
+            Self::Head
+        } else if name.ends_with("_chestplate") || item == ItemKind::Elytra {
+            Self::Chest
+        } else if name.ends_with("_leggings") {
+            Self::Legs
+        } else if name.ends_with("_boots") {
+            Self::Feet
+        } else if item == ItemKind::Shield {
+            Self::OffHand
+        } else {
+            Self::MainHand
+        }
+    }
+}
+
+/// Mirrors the equipment of this client to the other clients, even when
+/// [`InventorySettings::mirror_equipment`] is disabled.
+#[derive(Component, Copy, Clone, Default, Debug)]
+pub struct MirrorEquipment;
+
+/// Replaces the equipment that the other clients see in some slots, for
+/// example to disguise a player. The slots without an override keep mirroring
+/// the player's inventory.
+#[derive(Component, Clone, Default, Debug)]
+pub struct EquipmentOverride([Option<Option<ItemStack>>; 6]);
+
+impl EquipmentOverride {
+    /// Shows `item` in the slot instead of the item in the inventory. `None`
+    /// shows the slot as empty.
+    pub fn set(&mut self, slot: EquipmentSlot, item: impl Into<Option<ItemStack>>) {
+        self.0[slot as usize] = Some(item.into());
+    }
+
+    /// Removes the override of the slot, so the item in the inventory is shown
+    /// again.
+    pub fn remove(&mut self, slot: EquipmentSlot) {
+        self.0[slot as usize] = None;
+    }
+
+    /// Returns the override of the slot, if there is one.
+    pub fn get(&self, slot: EquipmentSlot) -> Option<Option<&ItemStack>> {
+        self.0[slot as usize].as_ref().map(Option::as_ref)
+    }
+}
+
+/// The equipment of a player that was last sent to the other clients, in the
+/// order of [`EquipmentSlot`].
+#[derive(Component, Default, Debug)]
+pub(super) struct SentEquipment([Option<ItemStack>; 6]);
+
+pub(super) fn broadcast_equipment(
+    mut players: Query<(
+        Entity,
+        &EntityId,
+        &Position,
+        &Location,
+        Ref<Inventory>,
+        Ref<HeldItem>,
+        Option<Ref<EquipmentOverride>>,
+        Option<Ref<MirrorEquipment>>,
+        &mut SentEquipment,
+    )>,
+    mut viewers: Query<(Entity, &mut Client, &Location, View)>,
+    mut removed_overrides: RemovedComponents<EquipmentOverride>,
+    settings: Res<InventorySettings>,
+) {
+    let removed_overrides: Vec<_> = removed_overrides.iter().collect();
+
+    for (entity, id, pos, loc, inventory, held_item, overrides, mirror, mut sent) in &mut players {
+        if !settings.mirror_equipment && mirror.is_none() {
+            continue;
+        }
+
+        let changed = inventory.is_changed()
+            || held_item.is_changed()
+            || overrides.as_ref().is_some_and(|o| o.is_changed())
+            || mirror.is_some_and(|m| m.is_added())
+            || settings.is_changed()
+            || removed_overrides.contains(&entity);
+
+        if !changed {
+            continue;
+        }
+
+        let mut equipment = vec![];
+
+        for slot in EquipmentSlot::ALL {
+            let item = overrides
+                .as_ref()
+                .and_then(|o| o.get(slot))
+                .unwrap_or_else(|| inventory.slot(slot.player_slot(&held_item)));
+
+            let sent_item = &mut sent.0[slot as usize];
+
+            if sent_item.as_ref() != item {
+                *sent_item = item.cloned();
+
+                equipment.push(EquipmentEntry {
+                    slot: slot as i8,
+                    item: item.cloned(),
+                });
+            }
+        }
+
+        if equipment.is_empty() {
+            continue;
+        }
+
+        let pkt = EntityEquipmentUpdateS2c {
+            entity_id: VarInt(id.get()),
+            equipment,
+        };
+
+        for (viewer, mut client, viewer_loc, view) in &mut viewers {
+            // The client already knows its own equipment.
+            if viewer != entity && viewer_loc == loc && view.get().contains(pos.chunk_pos()) {
+                client.write_packet(&pkt);
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! Lectern windows.
+//!
+//! Put a book in the [`BOOK_SLOT`] of a lectern's [`Inventory`] and open it for
+//! a client with [`OpenInventory`]. Turning pages is done by the server, which
+//! updates the page in [`ContainerProperties`] and sends a
+//! [`LecternPageEvent`]. When the client presses the "Take Book" button, a
+//! [`LecternTakeBookEvent`] is sent and moving the book is up to the server.
+
+use bevy_ecs::prelude::*;
+use tracing::debug;
+use valence_client::event_loop::PacketEvent;
+
+use crate::book::page_count;
+use crate::packet::ButtonClickC2s;
+use crate::{ClientInventoryState, ContainerProperties, Inventory, InventoryKind, OpenInventory};
+
+pub const BOOK_SLOT: u16 = 0;
+
+const PREVIOUS_PAGE_BUTTON: i8 = 1;
+const NEXT_PAGE_BUTTON: i8 = 2;
+const TAKE_BOOK_BUTTON: i8 = 3;
+/// Buttons from this ID onwards jump to the page `button_id - JUMP_TO_PAGE`.
+const JUMP_TO_PAGE: i8 = 100;
+
+/// Sent when a client turns the page of the book in a lectern. The page in
+/// the lectern's [`ContainerProperties`] has already been updated.
+#[derive(Event, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct LecternPageEvent {
+    pub client: Entity,
+    /// The entity with the lectern's [`Inventory`].
+    pub inventory: Entity,
+    /// The new page, starting from 0.
+    pub page: i16,
+}
+
+/// Sent when a client presses the "Take Book" button of a lectern.
+#[derive(Event, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct LecternTakeBookEvent {
+    pub client: Entity,
+    /// The entity with the lectern's [`Inventory`].
+    pub inventory: Entity,
+}
+
+pub(super) fn handle_button_click(
+    mut packets: EventReader<PacketEvent>,
+    clients: Query<(&OpenInventory, &ClientInventoryState)>,
+    mut windows: Query<(&Inventory, Option<&mut ContainerProperties>)>,
+    mut page_events: EventWriter<LecternPageEvent>,
+    mut take_events: EventWriter<LecternTakeBookEvent>,
+) {
+    for packet in packets.iter() {
+        if let Some(pkt) = packet.decode::<ButtonClickC2s>() {
+            let Ok((open_inventory, inv_state)) = clients.get(packet.client) else {
+                continue;
+            };
+
+            if pkt.window_id as u8 != inv_state.window_id {
+                continue;
+            }
+
+            let Ok((lectern, properties)) = windows.get_mut(open_inventory.entity) else {
+                continue;
+            };
+
+            if lectern.kind != InventoryKind::Lectern {
+                // Buttons in other windows are not handled here.
+                continue;
+            }
+
+            if pkt.button_id == TAKE_BOOK_BUTTON {
+                take_events.send(LecternTakeBookEvent {
+                    client: packet.client,
+                    inventory: open_inventory.entity,
+                });
+                continue;
+            }
+
+            let Some(book) = lectern.slot(BOOK_SLOT) else {
+                continue;
+            };
+
+            let current_page = properties.as_ref().and_then(|p| p.get(0)).unwrap_or(0);
+            let last_page = page_count(book).saturating_sub(1) as i16;
+
+            let page = match pkt.button_id {
+                PREVIOUS_PAGE_BUTTON => current_page - 1,
+                NEXT_PAGE_BUTTON => current_page + 1,
+                id if id >= JUMP_TO_PAGE => (id - JUMP_TO_PAGE).into(),
+                id => {
+                    debug!(
+                        "client {:?} pressed invalid lectern button {id}",
+                        packet.client
+                    );
+                    continue;
+                }
+            };
+
+            let page = page.clamp(0, last_page);
+
+            if page == current_page {
+                continue;
+            }
+
+            if let Some(mut properties) = properties {
+                properties.set_lectern_page(page);
+            }
+
+            page_events.send(LecternPageEvent {
+                client: packet.client,
+                inventory: open_inventory.entity,
+                page,
+            });
+        }
+    }
+}
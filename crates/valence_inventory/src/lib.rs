@@ -19,28 +19,61 @@
 #![allow(clippy::type_complexity)]
 
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::iter::FusedIterator;
 use std::num::Wrapping;
 use std::ops::Range;
 
+use anvil::{AnvilRenameEvent, PendingAnvilTakes};
+use armor::{ArmorEquipEvent, PendingArmorEquips};
+use beacon::BeaconSelectEvent;
 use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
+use crafting::CraftingRules;
+use equipment::EquipmentSlot;
+use glam::{DVec3, Vec3};
+use lectern::{LecternPageEvent, LecternTakeBookEvent};
+use output::PendingResultTakes;
 use packet::{
     ClickMode, ClickSlotC2s, CloseHandledScreenC2s, CloseScreenS2c, CreativeInventoryActionC2s,
-    InventoryS2c, OpenScreenS2c, ScreenHandlerSlotUpdateS2c, SlotChange, UpdateSelectedSlotC2s,
-    WindowType,
+    InventoryS2c, OpenScreenS2c, ScreenHandlerPropertyUpdateS2c, ScreenHandlerSlotUpdateS2c,
+    SlotChange, UpdateSelectedSlotC2s, UpdateSelectedSlotS2c, WindowType,
 };
+use quick_move::QuickMoveRules;
+use rand::Rng;
+use recipe::{RecipeRegistry, UnlockedRecipes};
+use stonecutter::StonecutterSelectEvent;
+use thiserror::Error;
 use tracing::{debug, warn};
-use valence_client::event_loop::{EventLoopPreUpdate, PacketEvent};
+use valence_client::event_loop::{EventLoopPostUpdate, EventLoopPreUpdate, PacketEvent};
 use valence_client::packet::{PlayerAction, PlayerActionC2s};
 use valence_client::{Client, FlushPacketsSet, SpawnClientsSet};
 use valence_core::game_mode::GameMode;
-use valence_core::item::ItemStack;
+use valence_core::ident::Ident;
+use valence_core::item::{DamageResult, ItemStack};
 use valence_core::protocol::encode::WritePacket;
 use valence_core::protocol::var_int::VarInt;
 use valence_core::text::Text;
-
+use valence_core::DEFAULT_TPS;
+use valence_entity::item::{ItemEntityBundle, Stack};
+use valence_entity::{Location, Look, Position, Velocity};
+
+pub mod anvil;
+pub mod armor;
+pub mod beacon;
+pub mod book;
+pub mod bundle;
+mod click;
+pub mod crafting;
+mod drag;
+pub mod equipment;
+pub mod lectern;
+pub mod output;
 pub mod packet;
+pub mod quick_move;
+pub mod recipe;
+pub mod smithing;
+pub mod stonecutter;
 mod validate;
 
 pub struct InventoryPlugin;
@@ -55,8 +88,17 @@ impl Plugin for InventoryPlugin {
             PostUpdate,
             (
                 update_client_on_close_inventory.before(update_open_inventories),
+                crafting::update_crafting_results
+                    .before(update_open_inventories)
+                    .before(update_player_inventories),
                 update_open_inventories,
+                clear_container_property_changes.after(update_open_inventories),
                 update_player_inventories,
+                equipment::broadcast_equipment,
+                send_item_break_events,
+                send_slot_change_events,
+                update_held_items,
+                (recipe::send_recipes, recipe::update_unlocked_recipes).chain(),
             )
                 .before(FlushPacketsSet),
         )
@@ -68,13 +110,50 @@ impl Plugin for InventoryPlugin {
                 handle_creative_inventory_action,
                 handle_close_handled_screen,
                 handle_player_actions,
+                armor::handle_interact_item,
+                recipe::handle_recipe_category_options,
+                crafting::handle_craft_request,
+                anvil::handle_rename_item,
+                beacon::handle_update_beacon,
+                stonecutter::handle_button_click,
+                lectern::handle_button_click,
+            ),
+        )
+        .add_systems(
+            EventLoopPostUpdate,
+            (
+                apply_creative_set_slots,
+                apply_item_drops,
+                apply_hand_swaps,
+                armor::apply_armor_equips,
+                anvil::apply_anvil_takes,
+                output::apply_result_takes,
             ),
         )
         .init_resource::<InventorySettings>()
+        .init_resource::<PendingCreativeSetSlots>()
+        .init_resource::<PendingItemDrops>()
+        .init_resource::<PendingHandSwaps>()
+        .init_resource::<PendingArmorEquips>()
+        .init_resource::<PendingAnvilTakes>()
+        .init_resource::<PendingResultTakes>()
+        .init_resource::<RecipeRegistry>()
+        .init_resource::<CraftingRules>()
         .add_event::<ClickSlotEvent>()
-        .add_event::<DropItemStackEvent>()
+        .add_event::<ClickRejectedEvent>()
+        .add_event::<DropItemEvent>()
+        .add_event::<SwapHandsEvent>()
+        .add_event::<ArmorEquipEvent>()
+        .add_event::<ItemBreakEvent>()
+        .add_event::<SlotChangeEvent>()
         .add_event::<CreativeInventoryActionEvent>()
-        .add_event::<UpdateSelectedSlotEvent>();
+        .add_event::<UpdateSelectedSlotEvent>()
+        .add_event::<HeldItemChangeEvent>()
+        .add_event::<AnvilRenameEvent>()
+        .add_event::<BeaconSelectEvent>()
+        .add_event::<StonecutterSelectEvent>()
+        .add_event::<LecternPageEvent>()
+        .add_event::<LecternTakeBookEvent>();
     }
 }
 
@@ -90,6 +169,11 @@ pub struct Inventory {
     /// Contains a set bit for each modified slot in `slots`.
     #[doc(hidden)]
     pub changed: u64,
+    /// Items that broke in [`Inventory::damage_slot`] and have yet to be sent
+    /// as [`ItemBreakEvent`]s.
+    broken_items: Vec<(u16, ItemStack)>,
+    /// Changes to `slots` that have yet to be sent as [`SlotChangeEvent`]s.
+    slot_changes: Vec<PendingSlotChange>,
 }
 
 impl Inventory {
@@ -104,9 +188,98 @@ impl Inventory {
             kind,
             slots: vec![None; kind.slot_count()].into(),
             changed: 0,
+            broken_items: vec![],
+            slot_changes: vec![],
         }
     }
 
+    /// Creates a chest-like inventory with 9 columns and the given number of
+    /// rows. It is titled like a double chest if it has 6 rows, and like a
+    /// chest otherwise.
+    ///
+    /// ```
+    /// # use valence_inventory::*;
+    /// let inv = Inventory::generic_9x(6);
+    /// assert_eq!(inv.kind(), InventoryKind::Generic9x6);
+    /// assert_eq!(inv.slot_count(), 54);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rows` is not in the range 1-6 inclusive.
+    #[track_caller]
+    pub fn generic_9x(rows: u8) -> Self {
+        let kind = match rows {
+            1 => InventoryKind::Generic9x1,
+            2 => InventoryKind::Generic9x2,
+            3 => InventoryKind::Generic9x3,
+            4 => InventoryKind::Generic9x4,
+            5 => InventoryKind::Generic9x5,
+            6 => InventoryKind::Generic9x6,
+            _ => panic!("chest-like inventories have 1-6 rows, got {rows}"),
+        };
+
+        let title = if rows == 6 {
+            "container.chestDouble"
+        } else {
+            "container.chest"
+        };
+
+        Self::with_title(kind, Text::translate(title, []))
+    }
+
+    /// Creates a chest inventory with 3 rows.
+    pub fn chest() -> Self {
+        Self::generic_9x(3)
+    }
+
+    /// Creates a double chest inventory with 6 rows.
+    pub fn double_chest() -> Self {
+        Self::generic_9x(6)
+    }
+
+    pub fn barrel() -> Self {
+        Self::with_title(
+            InventoryKind::Generic9x3,
+            Text::translate("container.barrel", []),
+        )
+    }
+
+    pub fn ender_chest() -> Self {
+        Self::with_title(
+            InventoryKind::Generic9x3,
+            Text::translate("container.enderchest", []),
+        )
+    }
+
+    pub fn shulker_box() -> Self {
+        Self::with_title(
+            InventoryKind::ShulkerBox,
+            Text::translate("container.shulkerBox", []),
+        )
+    }
+
+    pub fn hopper() -> Self {
+        Self::with_title(
+            InventoryKind::Hopper,
+            Text::translate("container.hopper", []),
+        )
+    }
+
+    pub fn dispenser() -> Self {
+        Self::with_title(
+            InventoryKind::Generic3x3,
+            Text::translate("container.dispenser", []),
+        )
+    }
+
+    pub fn dropper() -> Self {
+        Self::with_title(
+            InventoryKind::Generic3x3,
+            Text::translate("container.dropper", []),
+        )
+    }
+
     #[track_caller]
     pub fn slot(&self, idx: u16) -> Option<&ItemStack> {
         self.slots
@@ -151,6 +324,18 @@ impl Inventory {
         &mut self,
         idx: u16,
         item: impl Into<Option<ItemStack>>,
+    ) -> Option<ItemStack> {
+        self.replace_slot_by(idx, item, SlotChangeCause::Server)
+    }
+
+    /// Like [`Inventory::replace_slot`], but the [`SlotChangeEvent`] is sent
+    /// with the given cause.
+    #[track_caller]
+    pub(crate) fn replace_slot_by(
+        &mut self,
+        idx: u16,
+        item: impl Into<Option<ItemStack>>,
+        cause: SlotChangeCause,
     ) -> Option<ItemStack> {
         assert!(idx < self.slot_count(), "slot index of {idx} out of bounds");
 
@@ -159,11 +344,49 @@ impl Inventory {
 
         if new != *old {
             self.changed |= 1 << idx;
+            self.slot_changes.push(PendingSlotChange::Slot {
+                slot: idx,
+                old: old.clone(),
+                new: new.clone(),
+                cause,
+            });
         }
 
         std::mem::replace(old, new)
     }
 
+    /// Like [`Inventory::set_slot`], but the [`SlotChangeEvent`] is sent with
+    /// the given cause.
+    #[track_caller]
+    pub(crate) fn set_slot_by(
+        &mut self,
+        idx: u16,
+        item: impl Into<Option<ItemStack>>,
+        cause: SlotChangeCause,
+    ) {
+        let _ = self.replace_slot_by(idx, item, cause);
+    }
+
+    /// Sets a slot without marking it as changed, for when the client already
+    /// knows about the new stack. The [`SlotChangeEvent`] is still sent.
+    pub(crate) fn set_slot_unchanged(
+        &mut self,
+        idx: u16,
+        item: Option<ItemStack>,
+        cause: SlotChangeCause,
+    ) {
+        let old = std::mem::replace(&mut self.slots[idx as usize], item);
+
+        if old != self.slots[idx as usize] {
+            self.slot_changes.push(PendingSlotChange::Slot {
+                slot: idx,
+                old,
+                new: self.slots[idx as usize].clone(),
+                cause,
+            });
+        }
+    }
+
     /// Swap the contents of two slots. If the slots are the same, nothing
     /// happens.
     ///
@@ -196,6 +419,15 @@ impl Inventory {
         self.changed |= 1 << idx_b;
 
         self.slots.swap(idx_a as usize, idx_b as usize);
+
+        for (idx, other) in [(idx_a, idx_b), (idx_b, idx_a)] {
+            self.slot_changes.push(PendingSlotChange::Slot {
+                slot: idx,
+                old: self.slots[other as usize].clone(),
+                new: self.slots[idx as usize].clone(),
+                cause: SlotChangeCause::Server,
+            });
+        }
     }
 
     /// Set the amount of items in the given slot without replacing the slot
@@ -212,14 +444,113 @@ impl Inventory {
     /// ```
     #[track_caller]
     pub fn set_slot_amount(&mut self, idx: u16, amount: u8) {
+        self.set_slot_amount_by(idx, amount, SlotChangeCause::Server);
+    }
+
+    /// Like [`Inventory::set_slot_amount`], but the [`SlotChangeEvent`] is
+    /// sent with the given cause.
+    #[track_caller]
+    pub(crate) fn set_slot_amount_by(&mut self, idx: u16, amount: u8, cause: SlotChangeCause) {
         assert!(idx < self.slot_count(), "slot index out of range");
 
         if let Some(item) = self.slots[idx as usize].as_mut() {
             if item.count() == amount {
                 return;
             }
+            let old = item.clone();
             item.set_count(amount);
             self.changed |= 1 << idx;
+            self.slot_changes.push(PendingSlotChange::Slot {
+                slot: idx,
+                old: Some(old),
+                new: Some(item.clone()),
+                cause,
+            });
+        }
+    }
+
+    /// Damages the item in the given slot with [`ItemStack::damage`]. If the
+    /// item breaks, the slot is cleared and an [`ItemBreakEvent`] is sent. If
+    /// the slot is empty, nothing happens.
+    ///
+    /// ```
+    /// # use valence_inventory::*;
+    /// # use valence_core::item::{DamageResult, ItemStack, ItemKind};
+    /// let mut inv = Inventory::new(InventoryKind::Generic9x1);
+    /// inv.set_slot(0, ItemStack::new(ItemKind::WoodenSword, 1, None));
+    ///
+    /// let mut rng = rand::thread_rng();
+    /// assert_eq!(
+    ///     inv.damage_slot(0, 10, &mut rng),
+    ///     Some(DamageResult::Damaged(10))
+    /// );
+    /// assert_eq!(
+    ///     inv.damage_slot(0, 100, &mut rng),
+    ///     Some(DamageResult::Broken)
+    /// );
+    /// assert_eq!(inv.slot(0), None);
+    /// ```
+    #[track_caller]
+    pub fn damage_slot(
+        &mut self,
+        idx: u16,
+        amount: i32,
+        rng: &mut impl Rng,
+    ) -> Option<DamageResult> {
+        assert!(idx < self.slot_count(), "slot index out of range");
+
+        let item = self.slots[idx as usize].as_mut()?;
+        let old = item.clone();
+        let result = item.damage(amount, rng);
+
+        match result {
+            DamageResult::Unbreakable => {}
+            DamageResult::Damaged(_) => {
+                self.changed |= 1 << idx;
+                self.slot_changes.push(PendingSlotChange::Slot {
+                    slot: idx,
+                    old: Some(old),
+                    new: Some(item.clone()),
+                    cause: SlotChangeCause::Server,
+                });
+            }
+            DamageResult::Broken => {
+                if let Some(item) = self.replace_slot(idx, None) {
+                    self.broken_items.push((idx, item));
+                }
+            }
+        }
+
+        Some(result)
+    }
+
+    /// Empties every slot of the inventory. Only a single
+    /// [`SlotChangeEvent::BulkChange`] is sent instead of an event for every
+    /// slot.
+    ///
+    /// ```
+    /// # use valence_inventory::*;
+    /// # use valence_core::item::{ItemStack, ItemKind};
+    /// let mut inv = Inventory::new(InventoryKind::Generic9x1);
+    /// inv.set_slot(0, ItemStack::new(ItemKind::Diamond, 1, None));
+    /// inv.clear();
+    /// assert_eq!(inv.first_empty_slot(), Some(0));
+    /// assert_eq!(inv.slots().flatten().count(), 0);
+    /// ```
+    pub fn clear(&mut self) {
+        let mut changed = 0;
+
+        for (idx, slot) in self.slots.iter_mut().enumerate() {
+            if slot.take().is_some() {
+                changed |= 1 << idx;
+            }
+        }
+
+        if changed != 0 {
+            self.changed |= changed;
+            self.slot_changes.push(PendingSlotChange::Bulk {
+                cause: SlotChangeCause::Server,
+            });
         }
     }
 
@@ -241,6 +572,36 @@ impl Inventory {
         self.kind
     }
 
+    /// Returns the window type that this inventory is shown to clients with,
+    /// or an error if it can't be shown in a window.
+    ///
+    /// ```
+    /// # use valence_inventory::*;
+    /// # use valence_inventory::packet::WindowType;
+    /// assert_eq!(Inventory::hopper().window_type(), Ok(WindowType::Hopper));
+    ///
+    /// let player_inventory = Inventory::new(InventoryKind::Player);
+    /// assert_eq!(
+    ///     player_inventory.window_type(),
+    ///     Err(WindowError::NotAWindow(InventoryKind::Player))
+    /// );
+    /// ```
+    pub fn window_type(&self) -> Result<WindowType, WindowError> {
+        if self.kind == InventoryKind::Player {
+            return Err(WindowError::NotAWindow(self.kind));
+        }
+
+        if self.slots.len() != self.kind.slot_count() {
+            return Err(WindowError::SlotCount {
+                kind: self.kind,
+                expected: self.kind.slot_count(),
+                actual: self.slots.len(),
+            });
+        }
+
+        Ok(self.kind.into())
+    }
+
     /// The text displayed on the inventory's title bar.
     ///
     /// ```
@@ -321,6 +682,19 @@ impl Inventory {
     }
 }
 
+/// An error from [`Inventory::window_type`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Error)]
+pub enum WindowError {
+    #[error("{0:?} inventories can't be opened in a window")]
+    NotAWindow(InventoryKind),
+    #[error("{kind:?} windows have {expected} slots, but the inventory has {actual}")]
+    SlotCount {
+        kind: InventoryKind,
+        expected: usize,
+        actual: usize,
+    },
+}
+
 /// Miscellaneous inventory data.
 #[derive(Component, Debug)]
 pub struct ClientInventoryState {
@@ -334,6 +708,10 @@ pub struct ClientInventoryState {
     /// on the `CursorItem` component to make maintaining accurate change
     /// detection for end users easier.
     client_updated_cursor_item: bool,
+    /// The slot of the [`HeldItem`] that the client knows about.
+    client_held_item_slot: u16,
+    /// The drag that the client is in the middle of, if any.
+    drag: Option<drag::DragState>,
 }
 
 impl ClientInventoryState {
@@ -348,6 +726,43 @@ impl ClientInventoryState {
     }
 }
 
+/// The slot of the offhand in the player's inventory.
+pub(crate) const OFFHAND_SLOT: u16 = 45;
+
+/// What a slot of the player's inventory is for.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SlotCategory {
+    /// The result of the crafting grid.
+    CraftingResult,
+    /// The 2x2 crafting grid.
+    CraftingGrid,
+    /// An armor slot, which only holds the armor worn in its equipment slot.
+    Armor(EquipmentSlot),
+    /// The main inventory above the hotbar.
+    Storage,
+    Hotbar,
+    Offhand,
+}
+
+impl SlotCategory {
+    /// Returns the category of a slot of the player's inventory, or `None` if
+    /// the slot is out of range.
+    pub fn of_player_slot(idx: u16) -> Option<Self> {
+        Some(match idx {
+            0 => Self::CraftingResult,
+            1..=4 => Self::CraftingGrid,
+            5 => Self::Armor(EquipmentSlot::Head),
+            6 => Self::Armor(EquipmentSlot::Chest),
+            7 => Self::Armor(EquipmentSlot::Legs),
+            8 => Self::Armor(EquipmentSlot::Feet),
+            9..=35 => Self::Storage,
+            36..=44 => Self::Hotbar,
+            OFFHAND_SLOT => Self::Offhand,
+            _ => return None,
+        })
+    }
+}
+
 /// Indicates which hotbar slot the player is currently holding.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
 pub struct HeldItem {
@@ -360,10 +775,34 @@ impl HeldItem {
     pub fn slot(&self) -> u16 {
         self.held_item_slot
     }
+
+    /// The hotbar slot that is currently held, in the range 0-8 inclusive.
+    pub fn hotbar_slot(&self) -> u8 {
+        (self.held_item_slot - PLAYER_INVENTORY_MAIN_SLOTS_COUNT) as u8
+    }
+
+    /// Switches the client to another hotbar slot, in the range 0-8 inclusive.
+    /// The client is sent the new slot at the end of the tick.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slot is out of range.
+    #[track_caller]
+    pub fn set_held_slot(&mut self, hotbar_slot: u8) {
+        assert!(hotbar_slot <= 8, "hotbar slot {hotbar_slot} out of range");
+
+        self.held_item_slot = convert_hotbar_slot_id(hotbar_slot as u16);
+    }
 }
 
-/// The item stack that the client thinks it's holding under the mouse
-/// cursor.
+/// The item stack that the client is holding under the mouse cursor.
+///
+/// This is kept up to date as the client clicks in inventories. Changing it
+/// sends the new cursor item to the client, so it can also be used to take
+/// the item away, for example.
+///
+/// When the client closes a window, the cursor item is emptied according to
+/// [`InventorySettings::cursor_item_on_close`].
 #[derive(Component, Clone, PartialEq, Default, Debug)]
 pub struct CursorItem(pub Option<ItemStack>);
 
@@ -375,6 +814,11 @@ pub struct OpenInventory {
     /// viewing.
     pub entity: Entity,
     client_changed: u64,
+    /// Properties from [`OpenInventory::set_property`] that have yet to be
+    /// sent.
+    pending_properties: Vec<(u16, i16)>,
+    /// The value of each window property that was last sent to the client.
+    sent_properties: BTreeMap<u16, i16>,
 }
 
 impl OpenInventory {
@@ -382,8 +826,172 @@ impl OpenInventory {
         OpenInventory {
             entity,
             client_changed: 0,
+            pending_properties: vec![],
+            sent_properties: BTreeMap::new(),
+        }
+    }
+
+    /// Sets a property of the window for this client only, such as to show
+    /// something in a custom UI. Properties are numbered as in the
+    /// [`ScreenHandlerPropertyUpdateS2c`] packet, and aren't checked against
+    /// the window type. See [`ContainerProperties`] for properties shared by
+    /// every client viewing the inventory.
+    ///
+    /// The property is sent with the other window packets at the end of the
+    /// tick, after the window is opened if it hasn't been yet. Nothing is sent
+    /// if the client already has the value.
+    pub fn set_property(&mut self, index: u16, value: i16) {
+        self.pending_properties.retain(|&(i, _)| i != index);
+        self.pending_properties.push((index, value));
+    }
+
+    /// Returns the value of a window property that was last sent to the
+    /// client, either from [`OpenInventory::set_property`] or from the
+    /// inventory's [`ContainerProperties`].
+    pub fn sent_property(&self, index: u16) -> Option<i16> {
+        self.sent_properties.get(&index).copied()
+    }
+}
+
+/// The properties of an inventory's window, such as the progress arrow of a
+/// furnace. Add this next to an [`Inventory`] to show the properties to the
+/// clients viewing it.
+///
+/// Changed properties are sent to the viewing clients, and all properties are
+/// sent when a client opens the inventory.
+///
+/// ```
+/// # use valence_inventory::*;
+/// let mut properties = ContainerProperties::new(InventoryKind::Furnace);
+/// properties.set_furnace_progress(100, 200);
+///
+/// assert_eq!(properties.get(2), Some(100));
+/// ```
+#[derive(Component, Clone, PartialEq, Eq, Debug)]
+pub struct ContainerProperties {
+    values: Box<[i16]>,
+    /// Contains a set bit for each modified property in `values`.
+    changed: u16,
+}
+
+impl ContainerProperties {
+    /// Creates properties for an inventory of the given kind, which are all
+    /// zero.
+    pub fn new(kind: InventoryKind) -> Self {
+        Self {
+            values: vec![0; kind.property_count()].into(),
+            changed: 0,
+        }
+    }
+
+    /// Returns the value of a property, or `None` if this window doesn't have
+    /// it.
+    pub fn get(&self, property: u8) -> Option<i16> {
+        self.values.get(property as usize).copied()
+    }
+
+    /// Sets the value of a property. Properties are numbered as in the
+    /// [`ScreenHandlerPropertyUpdateS2c`] packet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this window doesn't have the property.
+    #[track_caller]
+    pub fn set(&mut self, property: u8, value: i16) {
+        let Some(slot) = self.values.get_mut(property as usize) else {
+            panic!(
+                "property index of {property} is out of bounds (this window has {} properties)",
+                self.values.len()
+            );
+        };
+
+        if *slot != value {
+            *slot = value;
+            self.changed |= 1 << property;
         }
     }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = i16> + '_ {
+        self.values.iter().copied()
+    }
+
+    /// Sets the fuel indicator of a furnace, blast furnace, or smoker. The
+    /// flame is full when `fuel_left` is `max_fuel`.
+    pub fn set_furnace_fuel(&mut self, fuel_left: i16, max_fuel: i16) {
+        self.set(0, fuel_left);
+        self.set(1, max_fuel);
+    }
+
+    /// Sets the progress arrow of a furnace, blast furnace, or smoker. The
+    /// arrow is full when `progress` is `max_progress`.
+    pub fn set_furnace_progress(&mut self, progress: i16, max_progress: i16) {
+        self.set(2, progress);
+        self.set(3, max_progress);
+    }
+
+    /// Sets the level needed for one of the three enchantment options of an
+    /// enchanting table, or 0 to disable the option.
+    pub fn set_enchantment_level_requirement(&mut self, option: u8, level: i16) {
+        assert!(option < 3, "enchantment option {option} is out of bounds");
+        self.set(option, level);
+    }
+
+    /// Sets the seed of the text shown on the enchanting table's options.
+    pub fn set_enchantment_seed(&mut self, seed: i16) {
+        self.set(3, seed);
+    }
+
+    /// Sets the enchantment shown when hovering over one of the three options
+    /// of an enchanting table. The enchantment is given by its protocol ID,
+    /// or `-1` for none.
+    pub fn set_enchantment_hint(&mut self, option: u8, enchantment: i16, level: i16) {
+        assert!(option < 3, "enchantment option {option} is out of bounds");
+        self.set(4 + option, enchantment);
+        self.set(7 + option, level);
+    }
+
+    /// Sets the number of pyramid levels of a beacon, from 0 to 4.
+    pub fn set_beacon_power_level(&mut self, level: i16) {
+        self.set(0, level);
+    }
+
+    /// Sets the selected effects of a beacon. Effects that can't be chosen in
+    /// a beacon are shown as none.
+    pub fn set_beacon_effects(
+        &mut self,
+        primary: Option<Ident<&str>>,
+        secondary: Option<Ident<&str>>,
+    ) {
+        let id = |effect: Option<Ident<&str>>| {
+            effect
+                .and_then(beacon::effect_id)
+                .map_or(-1, |id| id as i16)
+        };
+
+        self.set(1, id(primary));
+        self.set(2, id(secondary));
+    }
+
+    /// Sets the level cost shown in an anvil.
+    pub fn set_anvil_repair_cost(&mut self, cost: i16) {
+        self.set(0, cost);
+    }
+
+    /// Sets the brewing progress of a brewing stand. This counts down from 400
+    /// while brewing, and is 0 when nothing is brewing.
+    pub fn set_brewing_time(&mut self, time: i16) {
+        self.set(0, time);
+    }
+
+    /// Sets the blaze powder indicator of a brewing stand, from 0 to 20.
+    pub fn set_brewing_fuel(&mut self, fuel: i16) {
+        self.set(1, fuel);
+    }
+
+    /// Sets the page shown in a lectern, starting from 0.
+    pub fn set_lectern_page(&mut self, page: i16) {
+        self.set(0, page);
+    }
 }
 
 /// A helper to represent the inventory window that the player is currently
@@ -538,11 +1146,15 @@ fn init_new_client_inventories(clients: Query<Entity, Added<Client>>, mut comman
                 state_id: Wrapping(0),
                 slots_changed: 0,
                 client_updated_cursor_item: false,
+                client_held_item_slot: 36,
+                drag: None,
             },
             HeldItem {
                 // First slot of the hotbar.
                 held_item_slot: 36,
             },
+            UnlockedRecipes::default(),
+            equipment::SentEquipment::default(),
         ));
     }
 }
@@ -631,10 +1243,10 @@ fn update_open_inventories(
         Entity,
         &mut Client,
         &mut ClientInventoryState,
-        &CursorItem,
+        Ref<CursorItem>,
         &mut OpenInventory,
     )>,
-    mut inventories: Query<&mut Inventory>,
+    mut inventories: Query<(&mut Inventory, Option<&ContainerProperties>)>,
     mut commands: Commands,
 ) {
     // These operations need to happen in this order.
@@ -643,7 +1255,7 @@ fn update_open_inventories(
     for (client_entity, mut client, mut inv_state, cursor_item, mut open_inventory) in &mut clients
     {
         // Validate that the inventory exists.
-        let Ok(mut inventory) = inventories.get_mut(open_inventory.entity) else {
+        let Ok((mut inventory, properties)) = inventories.get_mut(open_inventory.entity) else {
             // The inventory no longer exists, so close the inventory.
             commands.entity(client_entity).remove::<OpenInventory>();
 
@@ -656,12 +1268,23 @@ fn update_open_inventories(
 
         if open_inventory.is_added() {
             // Send the inventory to the client if the client just opened the inventory.
+
+            let window_type = match inventory.window_type() {
+                Ok(window_type) => window_type,
+                Err(e) => {
+                    // Opening the window would crash the client.
+                    warn!("client {client_entity:?} can't open inventory: {e}");
+                    commands.entity(client_entity).remove::<OpenInventory>();
+                    continue;
+                }
+            };
+
             inv_state.window_id = inv_state.window_id % 100 + 1;
             open_inventory.client_changed = 0;
 
             client.write_packet(&OpenScreenS2c {
                 window_id: VarInt(inv_state.window_id.into()),
-                window_type: WindowType::from(inventory.kind),
+                window_type,
                 window_title: Cow::Borrowed(&inventory.title),
             });
 
@@ -671,6 +1294,18 @@ fn update_open_inventories(
                 slots: Cow::Borrowed(inventory.slot_slice()),
                 carried_item: Cow::Borrowed(&cursor_item.0),
             });
+
+            if let Some(properties) = properties {
+                for (property, value) in properties.iter().enumerate() {
+                    send_property(
+                        &mut client,
+                        inv_state.window_id,
+                        &mut open_inventory.sent_properties,
+                        property as u16,
+                        value,
+                    );
+                }
+            }
         } else {
             // The client is already viewing the inventory.
 
@@ -705,9 +1340,47 @@ fn update_open_inventories(
                         }
                     }
                 }
+
+                if cursor_item.is_changed() && !inv_state.client_updated_cursor_item {
+                    // See the comment in `update_player_inventories` about the state ID.
+                    client.write_packet(&ScreenHandlerSlotUpdateS2c {
+                        window_id: -1,
+                        state_id: VarInt(inv_state.state_id.0),
+                        slot_idx: -1,
+                        slot_data: Cow::Borrowed(&cursor_item.0),
+                    });
+                }
+            }
+
+            if let Some(properties) = properties.filter(|p| p.changed != 0) {
+                for (property, value) in properties.iter().enumerate() {
+                    if (properties.changed >> property) & 1 == 1 {
+                        send_property(
+                            &mut client,
+                            inv_state.window_id,
+                            &mut open_inventory.sent_properties,
+                            property as u16,
+                            value,
+                        );
+                    }
+                }
             }
         }
 
+        // The properties for this client go after the ones of the inventory, so they
+        // win if both set the same property.
+        let open_inventory = &mut *open_inventory;
+
+        for (property, value) in open_inventory.pending_properties.drain(..) {
+            send_property(
+                &mut client,
+                inv_state.window_id,
+                &mut open_inventory.sent_properties,
+                property,
+                value,
+            );
+        }
+
         open_inventory.client_changed = 0;
         inv_state.slots_changed = 0;
         inv_state.client_updated_cursor_item = false;
@@ -715,10 +1388,56 @@ fn update_open_inventories(
     }
 }
 
+/// Sends a window property to a client, unless the client already has the
+/// value.
+fn send_property(
+    client: &mut Client,
+    window_id: u8,
+    sent_properties: &mut BTreeMap<u16, i16>,
+    property: u16,
+    value: i16,
+) {
+    if sent_properties.insert(property, value) != Some(value) {
+        client.write_packet(&ScreenHandlerPropertyUpdateS2c {
+            window_id,
+            property: property as i16,
+            value,
+        });
+    }
+}
+
+/// Clears the changed properties after they were sent to every client viewing
+/// them.
+fn clear_container_property_changes(
+    mut properties: Query<&mut ContainerProperties, Changed<ContainerProperties>>,
+) {
+    for mut properties in &mut properties {
+        properties.bypass_change_detection().changed = 0;
+    }
+}
+
 /// Handles clients telling the server that they are closing an inventory.
-fn handle_close_handled_screen(mut packets: EventReader<PacketEvent>, mut commands: Commands) {
+fn handle_close_handled_screen(
+    mut packets: EventReader<PacketEvent>,
+    mut clients: Query<(&mut Inventory, &mut CursorItem), With<Client>>,
+    settings: Res<InventorySettings>,
+    mut pending_drops: ResMut<PendingItemDrops>,
+    mut commands: Commands,
+) {
     for packet in packets.iter() {
         if packet.decode::<CloseHandledScreenC2s>().is_some() {
+            // This is also sent when the client closes its own inventory, which has no
+            // `OpenInventory`.
+            if let Ok((mut inventory, mut cursor_item)) = clients.get_mut(packet.client) {
+                empty_cursor_item(
+                    packet.client,
+                    &mut inventory,
+                    &mut cursor_item,
+                    &settings,
+                    &mut pending_drops,
+                );
+            }
+
             if let Some(mut entity) = commands.get_entity(packet.client) {
                 entity.remove::<OpenInventory>();
             }
@@ -730,37 +1449,377 @@ fn handle_close_handled_screen(mut packets: EventReader<PacketEvent>, mut comman
 /// indicates that the client is no longer viewing an inventory.
 fn update_client_on_close_inventory(
     mut removals: RemovedComponents<OpenInventory>,
-    mut clients: Query<(&mut Client, &ClientInventoryState)>,
+    mut clients: Query<(
+        &mut Client,
+        &ClientInventoryState,
+        &mut Inventory,
+        &mut CursorItem,
+    )>,
+    settings: Res<InventorySettings>,
+    mut pending_drops: ResMut<PendingItemDrops>,
 ) {
     for entity in &mut removals {
-        if let Ok((mut client, inv_state)) = clients.get_mut(entity) {
+        if let Ok((mut client, inv_state, mut inventory, mut cursor_item)) = clients.get_mut(entity)
+        {
             client.write_packet(&CloseScreenS2c {
                 window_id: inv_state.window_id,
-            })
+            });
+
+            // The client lets go of the cursor item when the window is closed.
+            empty_cursor_item(
+                entity,
+                &mut inventory,
+                &mut cursor_item,
+                &settings,
+                &mut pending_drops,
+            );
         }
     }
 }
 
-// TODO: make this event user friendly.
-#[derive(Event, Clone, Debug)]
-pub struct ClickSlotEvent {
-    pub client: Entity,
-    pub window_id: u8,
-    pub state_id: i32,
-    pub slot_id: i16,
-    pub button: i8,
-    pub mode: ClickMode,
-    pub slot_changes: Vec<SlotChange>,
-    pub carried_item: Option<ItemStack>,
-}
+/// Empties the cursor item of a client that closed a window.
+fn empty_cursor_item(
+    client: Entity,
+    player_inventory: &mut Inventory,
+    cursor_item: &mut Mut<CursorItem>,
+    settings: &InventorySettings,
+    pending_drops: &mut PendingItemDrops,
+) {
+    let Some(mut stack) = cursor_item.0.take() else {
+        return;
+    };
 
-#[derive(Event, Clone, Debug)]
-pub struct DropItemStackEvent {
-    pub client: Entity,
-    pub from_slot: Option<u16>,
-    pub stack: ItemStack,
+    if settings.cursor_item_on_close == CursorItemOnClose::ReturnToInventory {
+        let Some(rest) = insert_into_player_inventory(player_inventory, stack) else {
+            return;
+        };
+
+        stack = rest;
+    }
+
+    pending_drops.0.push(DropItemEvent::new(
+        client,
+        stack,
+        true,
+        DropSource::ClosedWindow,
+    ));
 }
 
+/// Puts an item stack in a player inventory the way vanilla does, by first
+/// adding to matching stacks and then using empty slots, starting with the
+/// hotbar. Returns the part of the stack that didn't fit.
+fn insert_into_player_inventory(inventory: &mut Inventory, stack: ItemStack) -> Option<ItemStack> {
+    const HOTBAR: Range<u16> = 36..45;
+    const MAIN: Range<u16> = 9..36;
+
+    let max_stack = stack.item.max_stack();
+    let mut remaining = stack.count();
+
+    for idx in HOTBAR.chain(MAIN).chain([OFFHAND_SLOT]) {
+        let count = match inventory.slot(idx) {
+            Some(slot) if slot.item == stack.item && slot.nbt == stack.nbt => slot.count(),
+            _ => continue,
+        };
+
+        if count < max_stack {
+            let added = remaining.min(max_stack - count);
+            inventory.set_slot_amount(idx, count + added);
+            remaining -= added;
+
+            if remaining == 0 {
+                return None;
+            }
+        }
+    }
+
+    for idx in HOTBAR.chain(MAIN) {
+        if inventory.slot(idx).is_none() {
+            let added = remaining.min(max_stack);
+            inventory.set_slot(idx, stack.clone().with_count(added));
+            remaining -= added;
+
+            if remaining == 0 {
+                return None;
+            }
+        }
+    }
+
+    Some(stack.with_count(remaining))
+}
+
+// TODO: make this event user friendly.
+#[derive(Event, Clone, Debug)]
+pub struct ClickSlotEvent {
+    pub client: Entity,
+    pub window_id: u8,
+    pub state_id: i32,
+    pub slot_id: i16,
+    pub button: i8,
+    pub mode: ClickMode,
+    pub slot_changes: Vec<SlotChange>,
+    pub carried_item: Option<ItemStack>,
+}
+
+/// Sent when the server rejects a click of a client, so anti-cheat plugins can
+/// keep track of clients sending invalid clicks. The client has already been
+/// sent the actual contents of the window.
+#[derive(Event, Clone, PartialEq, Eq, Debug)]
+pub struct ClickRejectedEvent {
+    pub client: Entity,
+    pub window_id: u8,
+    pub state_id: i32,
+    pub reason: ClickRejection,
+}
+
+/// Why a click was rejected.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ClickRejection {
+    /// The click was for a window that isn't open anymore, so it was ignored.
+    WrongWindow,
+    /// The client's state ID was out of date, so the click was ignored.
+    StaleStateId,
+    /// The click was invalid, such as clicking a slot that doesn't exist or
+    /// creating items out of nothing, so it was ignored.
+    Invalid(String),
+    /// The client predicted a different result for the click than the server
+    /// worked out. Vanilla clients may also do this when the server changes
+    /// the window at the same time.
+    Mispredicted,
+}
+
+/// A client dropping an item with the drop key, by clicking outside of a
+/// window, by closing a window with an item under the cursor, or from the
+/// creative inventory menu.
+///
+/// The stack has already been taken out of the slot or cursor it was dropped
+/// from. Drops are kept in [`PendingItemDrops`] for systems in
+/// [`EventLoopUpdate`] to cancel, and the drops that weren't cancelled are
+/// then sent as events. Spawning the dropped item is up to the server, for
+/// example with [`DropItemEvent::item_entity`].
+///
+/// [`EventLoopUpdate`]: valence_client::event_loop::EventLoopUpdate
+#[derive(Event, Clone, PartialEq, Debug)]
+pub struct DropItemEvent {
+    pub client: Entity,
+    pub stack: ItemStack,
+    /// The slot the stack was dropped from, or `None` if it was dropped from
+    /// the cursor or the creative inventory menu. This is a slot of the open
+    /// window if [`DropItemEvent::window`] is `Some`, and a slot of the
+    /// player's inventory otherwise.
+    pub slot: Option<u16>,
+    /// Whether the whole stack was dropped, as opposed to a single item.
+    pub whole_stack: bool,
+    source: DropSource,
+    cancelled: bool,
+}
+
+/// Where a dropped stack was taken from.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum DropSource {
+    PlayerInventory(u16),
+    Window(Entity, u16),
+    Cursor,
+    /// The cursor item of a window that was closed.
+    ClosedWindow,
+    CreativeMenu,
+}
+
+impl DropItemEvent {
+    fn new(client: Entity, stack: ItemStack, whole_stack: bool, source: DropSource) -> Self {
+        let slot = match source {
+            DropSource::PlayerInventory(slot) | DropSource::Window(_, slot) => Some(slot),
+            DropSource::Cursor | DropSource::ClosedWindow | DropSource::CreativeMenu => None,
+        };
+
+        Self {
+            client,
+            stack,
+            slot,
+            whole_stack,
+            source,
+            cancelled: false,
+        }
+    }
+
+    /// The entity with the [`Inventory`] of the open window that the stack was
+    /// dropped from, if any.
+    pub fn window(&self) -> Option<Entity> {
+        match self.source {
+            DropSource::Window(window, _) => Some(window),
+            _ => None,
+        }
+    }
+
+    /// Keeps the client from dropping the stack. It is put back where it came
+    /// from, or in the player's inventory if that is no longer possible, and
+    /// the client is sent the result. Items that don't fit anywhere are lost.
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    /// Returns an item entity for the dropped stack, thrown forward from the
+    /// eyes of a player like in vanilla.
+    pub fn item_entity(
+        &self,
+        location: Location,
+        position: Position,
+        look: Look,
+    ) -> ItemEntityBundle {
+        let (pitch_sin, pitch_cos) = look.pitch.to_radians().sin_cos();
+        let (yaw_sin, yaw_cos) = look.yaw.to_radians().sin_cos();
+
+        // A small random spread, in blocks per tick.
+        let angle = rand::random::<f32>() * std::f32::consts::TAU;
+        let spread = rand::random::<f32>() * 0.02;
+        let lift = (rand::random::<f32>() - rand::random::<f32>()) * 0.1;
+
+        let velocity = Vec3::new(
+            -yaw_sin * pitch_cos * 0.3 + angle.cos() * spread,
+            -pitch_sin * 0.3 + 0.1 + lift,
+            yaw_cos * pitch_cos * 0.3 + angle.sin() * spread,
+        );
+
+        ItemEntityBundle {
+            item_stack: Stack(self.stack.clone()),
+            location,
+            // Just below the eyes of a standing player.
+            position: Position(position.0 + DVec3::new(0.0, 1.32, 0.0)),
+            velocity: Velocity(velocity * DEFAULT_TPS.get() as f32),
+            ..Default::default()
+        }
+    }
+}
+
+/// The [`DropItemEvent`]s from the current run of the event loop.
+///
+/// They are applied in [`EventLoopPostUpdate`], so systems in
+/// [`EventLoopUpdate`] can cancel them first.
+///
+/// [`EventLoopUpdate`]: valence_client::event_loop::EventLoopUpdate
+#[derive(Resource, Default, Debug)]
+pub struct PendingItemDrops(pub Vec<DropItemEvent>);
+
+/// Takes the dropped part out of a slot, which is either a single item or the
+/// whole stack.
+fn take_dropped(
+    inventory: &mut Inventory,
+    idx: u16,
+    whole_stack: bool,
+    client: Entity,
+) -> Option<ItemStack> {
+    let stack = inventory.slot(idx)?;
+    let cause = SlotChangeCause::Click(client);
+
+    if whole_stack || stack.count() == 1 {
+        inventory.replace_slot_by(idx, None, cause)
+    } else {
+        let dropped = stack.clone().with_count(1);
+        inventory.set_slot_amount_by(idx, stack.count() - 1, cause);
+        Some(dropped)
+    }
+}
+
+/// Puts a stack back in a slot if it is empty or holds the same item. Returns
+/// the stack if it doesn't fit.
+fn put_back(inventory: &mut Inventory, idx: u16, stack: ItemStack) -> Option<ItemStack> {
+    match inventory.slot(idx) {
+        None => {
+            inventory.set_slot(idx, stack);
+            None
+        }
+        Some(slot)
+            if slot.item == stack.item
+                && slot.nbt == stack.nbt
+                && slot.count() as u16 + stack.count() as u16 <= stack.item.max_stack() as u16 =>
+        {
+            inventory.set_slot_amount(idx, slot.count() + stack.count());
+            None
+        }
+        Some(_) => Some(stack),
+    }
+}
+
+fn apply_item_drops(
+    mut pending: ResMut<PendingItemDrops>,
+    mut clients: Query<
+        (
+            &mut Inventory,
+            &mut ClientInventoryState,
+            &mut CursorItem,
+            Option<&mut OpenInventory>,
+        ),
+        With<Client>,
+    >,
+    mut windows: Query<&mut Inventory, Without<Client>>,
+    mut events: EventWriter<DropItemEvent>,
+) {
+    for event in pending.0.drain(..) {
+        if !event.cancelled {
+            events.send(event);
+            continue;
+        }
+
+        let Ok((mut inventory, mut inv_state, mut cursor_item, open_inventory)) =
+            clients.get_mut(event.client)
+        else {
+            continue;
+        };
+
+        // The client thinks the stack was dropped, so the places it is put back in
+        // are sent to the client.
+        let rest = match event.source {
+            DropSource::PlayerInventory(slot) => {
+                inv_state.slots_changed &= !(1 << slot);
+                put_back(&mut inventory, slot, event.stack)
+            }
+            DropSource::Window(window_ent, idx) => {
+                match (open_inventory, windows.get_mut(window_ent)) {
+                    (Some(mut open_inventory), Ok(mut window))
+                        if open_inventory.entity == window_ent =>
+                    {
+                        open_inventory.client_changed &= !(1 << idx);
+                        put_back(&mut window, idx, event.stack)
+                    }
+                    _ => Some(event.stack),
+                }
+            }
+            DropSource::Cursor => {
+                inv_state.client_updated_cursor_item = false;
+
+                match &mut cursor_item.0 {
+                    None => {
+                        cursor_item.0 = Some(event.stack);
+                        None
+                    }
+                    Some(cursor)
+                        if cursor.item == event.stack.item
+                            && cursor.nbt == event.stack.nbt
+                            && cursor.count() as u16 + event.stack.count() as u16
+                                <= cursor.item.max_stack() as u16 =>
+                    {
+                        cursor.set_count(cursor.count() + event.stack.count());
+                        None
+                    }
+                    Some(_) => Some(event.stack),
+                }
+            }
+            DropSource::ClosedWindow => Some(event.stack),
+            // The item came out of the creative inventory menu, so there is nothing to
+            // put back.
+            DropSource::CreativeMenu => None,
+        };
+
+        if let Some(rest) = rest {
+            insert_into_player_inventory(&mut inventory, rest);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn handle_click_slot(
     mut packets: EventReader<PacketEvent>,
     mut clients: Query<(
@@ -769,15 +1828,23 @@ fn handle_click_slot(
         &mut ClientInventoryState,
         Option<&mut OpenInventory>,
         &mut CursorItem,
+        &GameMode,
     )>,
     mut inventories: Query<&mut Inventory, Without<Client>>,
-    mut drop_item_stack_events: EventWriter<DropItemStackEvent>,
+    properties: Query<&ContainerProperties>,
+    quick_move_rules: Query<&QuickMoveRules>,
+    crafting_rules: Res<CraftingRules>,
+    recipes: Res<RecipeRegistry>,
+    mut pending_anvil_takes: ResMut<PendingAnvilTakes>,
+    mut pending_result_takes: ResMut<PendingResultTakes>,
+    mut pending_drops: ResMut<PendingItemDrops>,
     mut click_slot_events: EventWriter<ClickSlotEvent>,
+    mut rejected_events: EventWriter<ClickRejectedEvent>,
 ) {
     for packet in packets.iter() {
-        let Some(pkt) = packet.decode::<ClickSlotC2s>() else {
+        let Some(mut pkt) = packet.decode::<ClickSlotC2s>() else {
             // Not the packet we're looking for.
-            continue
+            continue;
         };
 
         let Ok((
@@ -785,22 +1852,123 @@ fn handle_click_slot(
             mut client_inv,
             mut inv_state,
             open_inventory,
-            mut cursor_item
-        )) = clients.get_mut(packet.client) else {
+            mut cursor_item,
+            game_mode,
+        )) = clients.get_mut(packet.client)
+        else {
             // The client does not exist, ignore.
             continue;
         };
 
+        let (clicked_window_id, clicked_state_id) = (pkt.window_id, pkt.state_id.0);
+
+        let mut reject = |reason| {
+            rejected_events.send(ClickRejectedEvent {
+                client: packet.client,
+                window_id: clicked_window_id,
+                state_id: clicked_state_id,
+                reason,
+            })
+        };
+
+        let window_id = if open_inventory.is_some() {
+            inv_state.window_id
+        } else {
+            0
+        };
+
+        if pkt.window_id != window_id {
+            // The click is for a window that isn't open anymore.
+            debug!(
+                "client {:?} clicked in window {} while window {window_id} is open",
+                packet.client, pkt.window_id
+            );
+
+            reject(ClickRejection::WrongWindow);
+            continue;
+        }
+
+        if inv_state.state_id.0 != pkt.state_id.0 {
+            // Client is out of sync. Resync and ignore the click.
+
+            debug!("Client state id mismatch, resyncing");
+
+            inv_state.state_id += 1;
+
+            let open_inv = open_inventory
+                .as_ref()
+                .and_then(|open| inventories.get(open.entity).ok());
+
+            client.write_packet(&InventoryS2c {
+                window_id,
+                state_id: VarInt(inv_state.state_id.0),
+                slots: Cow::Borrowed(open_inv.unwrap_or(&client_inv).slot_slice()),
+                carried_item: Cow::Borrowed(&cursor_item.0),
+            });
+
+            reject(ClickRejection::StaleStateId);
+            continue;
+        }
+
+        if let Some((open, mut window)) = open_inventory
+            .as_ref()
+            .and_then(|open| Some((open, inventories.get_mut(open.entity).ok()?)))
+            .filter(|(_, inv)| output::is_take_result(inv.kind, &pkt))
+        {
+            let click = output::TakeClick {
+                client: packet.client,
+                inventory: open.entity,
+                pkt: &pkt,
+                window: &window,
+                player_inventory: &client_inv,
+                cursor_item: &cursor_item,
+                rules: quick_move_rules.get(open.entity).ok().map(|r| &*r.0),
+            };
+
+            let taken = if window.kind == InventoryKind::Anvil {
+                let level_cost = properties
+                    .get(open.entity)
+                    .ok()
+                    .and_then(|p| p.get(0))
+                    .unwrap_or(0);
+
+                anvil::take_result(&click, level_cost)
+                    .map(|event| pending_anvil_takes.0.push(event))
+            } else {
+                output::take_result(&click).map(|event| pending_result_takes.0.push(event))
+            };
+
+            if taken.is_none() {
+                output::resync(
+                    &mut client,
+                    &inv_state,
+                    &mut window,
+                    &client_inv,
+                    &pkt.slot_changes,
+                );
+            }
+
+            continue;
+        }
+
         let open_inv = open_inventory
             .as_ref()
             .and_then(|open| inventories.get_mut(open.entity).ok());
 
-        if let Err(e) = validate::validate_click_slot_packet(
-            &pkt,
-            &client_inv,
-            open_inv.as_deref(),
-            &cursor_item,
-        ) {
+        let validation =
+            if pkt.mode == ClickMode::CreativeMiddleClick && *game_mode != GameMode::Creative {
+                // Only players in creative mode can clone stacks.
+                Err(anyhow::anyhow!("middle click outside of creative mode"))
+            } else {
+                validate::validate_click_slot_packet(
+                    &pkt,
+                    &client_inv,
+                    open_inv.as_deref(),
+                    &cursor_item,
+                )
+            };
+
+        if let Err(e) = validation {
             debug!(
                 "failed to validate click slot packet for client {:#?}: \"{e:#}\" {pkt:#?}",
                 packet.client
@@ -809,33 +1977,42 @@ fn handle_click_slot(
             // Resync the inventory.
 
             client.write_packet(&InventoryS2c {
-                window_id: if open_inv.is_some() {
-                    inv_state.window_id
-                } else {
-                    0
-                },
+                window_id,
                 state_id: VarInt(inv_state.state_id.0),
                 slots: Cow::Borrowed(open_inv.unwrap_or(client_inv).slot_slice()),
                 carried_item: Cow::Borrowed(&cursor_item.0),
             });
 
+            reject(ClickRejection::Invalid(format!("{e:#}")));
             continue;
         }
 
         if pkt.slot_idx < 0 && pkt.mode == ClickMode::Click {
-            // The client is dropping the cursor item by clicking outside the window.
+            // The client is dropping the cursor item by clicking outside the window. A
+            // right click only drops a single item.
 
-            if let Some(stack) = cursor_item.0.take() {
-                drop_item_stack_events.send(DropItemStackEvent {
-                    client: packet.client,
-                    from_slot: None,
+            let whole_stack = pkt.button == 0;
+
+            let dropped = match cursor_item.0.take() {
+                Some(stack) if !whole_stack && stack.count() > 1 => {
+                    cursor_item.0 = Some(stack.clone().with_count(stack.count() - 1));
+                    Some(stack.with_count(1))
+                }
+                stack => stack,
+            };
+
+            if let Some(stack) = dropped {
+                pending_drops.0.push(DropItemEvent::new(
+                    packet.client,
                     stack,
-                });
+                    whole_stack,
+                    DropSource::Cursor,
+                ));
             }
         } else if pkt.mode == ClickMode::DropKey {
             // The client is dropping an item by pressing the drop key.
 
-            let entire_stack = pkt.button == 1;
+            let whole_stack = pkt.button == 1;
 
             // Needs to open the inventory for if the player is dropping an item while
             // having an inventory open.
@@ -847,110 +2024,61 @@ fn handle_click_slot(
                     continue;
                 };
 
-                if inv_state.state_id.0 != pkt.state_id.0 {
-                    // Client is out of sync. Resync and ignore click.
-
-                    debug!("Client state id mismatch, resyncing");
-
-                    inv_state.state_id += 1;
-
-                    client.write_packet(&InventoryS2c {
-                        window_id: inv_state.window_id,
-                        state_id: VarInt(inv_state.state_id.0),
-                        slots: Cow::Borrowed(target_inventory.slot_slice()),
-                        carried_item: Cow::Borrowed(&cursor_item.0),
-                    });
-
-                    continue;
-                }
-
                 if (0i16..target_inventory.slot_count() as i16).contains(&pkt.slot_idx) {
                     // The player is dropping an item from another inventory.
 
-                    if let Some(stack) = target_inventory.slot(pkt.slot_idx as u16) {
-                        let dropped = if entire_stack || stack.count() == 1 {
-                            target_inventory.replace_slot(pkt.slot_idx as u16, None)
-                        } else {
-                            let mut stack = stack.clone();
-                            stack.set_count(stack.count() - 1);
-                            let mut old_slot =
-                                target_inventory.replace_slot(pkt.slot_idx as u16, Some(stack));
-                            // we already checked that the slot was not empty and that the
-                            // stack count is > 1
-                            old_slot.as_mut().unwrap().set_count(1);
-                            old_slot
-                        }
-                        .expect("dropped item should exist"); // we already checked that the slot was not empty
-
-                        drop_item_stack_events.send(DropItemStackEvent {
-                            client: packet.client,
-                            from_slot: Some(pkt.slot_idx as u16),
-                            stack: dropped,
-                        });
+                    let idx = pkt.slot_idx as u16;
+
+                    if let Some(stack) =
+                        take_dropped(&mut target_inventory, idx, whole_stack, packet.client)
+                    {
+                        pending_drops.0.push(DropItemEvent::new(
+                            packet.client,
+                            stack,
+                            whole_stack,
+                            DropSource::Window(open_inventory.entity, idx),
+                        ));
                     }
                 } else {
                     // The player is dropping an item from their inventory.
                     let slot_id =
                         convert_to_player_slot_id(target_inventory.kind, pkt.slot_idx as u16);
-                    if let Some(stack) = client_inv.slot(slot_id) {
-                        let dropped = if entire_stack || stack.count() == 1 {
-                            client_inv.replace_slot(slot_id, None)
-                        } else {
-                            let mut stack = stack.clone();
-                            stack.set_count(stack.count() - 1);
-                            let mut old_slot = client_inv.replace_slot(slot_id, Some(stack));
-                            // we already checked that the slot was not empty and that the
-                            // stack count is > 1
-                            old_slot.as_mut().unwrap().set_count(1);
-                            old_slot
-                        }
-                        .expect("dropped item should exist"); // we already checked that the slot was not empty
 
-                        drop_item_stack_events.send(DropItemStackEvent {
-                            client: packet.client,
-                            from_slot: Some(slot_id),
-                            stack: dropped,
-                        });
+                    if let Some(stack) =
+                        take_dropped(&mut client_inv, slot_id, whole_stack, packet.client)
+                    {
+                        pending_drops.0.push(DropItemEvent::new(
+                            packet.client,
+                            stack,
+                            whole_stack,
+                            DropSource::PlayerInventory(slot_id),
+                        ));
                     }
                 }
             } else {
                 // The player has no inventory open and is dropping an item from their
                 // inventory.
-                if let Some(stack) = client_inv.slot(pkt.slot_idx as u16) {
-                    let dropped = if entire_stack || stack.count() == 1 {
-                        client_inv.replace_slot(pkt.slot_idx as u16, None)
-                    } else {
-                        let mut stack = stack.clone();
-                        stack.set_count(stack.count() - 1);
-                        let mut old_slot =
-                            client_inv.replace_slot(pkt.slot_idx as u16, Some(stack));
-                        // we already checked that the slot was not empty and that the
-                        // stack count is > 1
-                        old_slot.as_mut().unwrap().set_count(1);
-                        old_slot
-                    }
-                    .expect("dropped item should exist"); // we already checked that the slot was not empty
+                let slot_id = pkt.slot_idx as u16;
 
-                    drop_item_stack_events.send(DropItemStackEvent {
-                        client: packet.client,
-                        from_slot: Some(pkt.slot_idx as u16),
-                        stack: dropped,
-                    });
+                if let Some(stack) =
+                    take_dropped(&mut client_inv, slot_id, whole_stack, packet.client)
+                {
+                    pending_drops.0.push(DropItemEvent::new(
+                        packet.client,
+                        stack,
+                        whole_stack,
+                        DropSource::PlayerInventory(slot_id),
+                    ));
                 }
             }
         } else {
             // The player is clicking a slot in an inventory.
 
-            // Validate the window id.
-            if (pkt.window_id == 0) != open_inventory.is_none() {
-                warn!(
-                    "Client sent a click with an invalid window id for current state: window_id = \
-                     {}, open_inventory present = {}",
-                    pkt.window_id,
-                    open_inventory.is_some()
-                );
-                continue;
-            }
+            let cause = if pkt.mode == ClickMode::Drag {
+                SlotChangeCause::Drag(packet.client)
+            } else {
+                SlotChangeCause::Click(packet.client)
+            };
 
             if let Some(mut open_inventory) = open_inventory {
                 // The player is interacting with an inventory that is open.
@@ -960,64 +2088,126 @@ fn handle_click_slot(
                     continue;
                 };
 
-                if inv_state.state_id.0 != pkt.state_id.0 {
-                    // Client is out of sync. Resync and ignore click.
+                let window = InventoryWindow::new(&client_inv, Some(&target_inventory));
+                let rules = quick_move_rules
+                    .get(open_inventory.entity)
+                    .ok()
+                    .map(|r| &*r.0);
+
+                let mispredicted = if pkt.mode == ClickMode::Drag {
+                    resolve_drag(
+                        &mut pkt,
+                        &mut inv_state,
+                        &window,
+                        &cursor_item,
+                        *game_mode == GameMode::Creative,
+                    )
+                } else if let Some((slot_changes, carried_item)) = crafting::click(
+                    &pkt,
+                    &window,
+                    &cursor_item,
+                    &crafting_rules,
+                    &recipes,
+                    rules,
+                )
+                .or_else(|| bundle::click(&pkt, &window, &cursor_item))
+                .or_else(|| click::click(&pkt, &window, &cursor_item, rules))
+                {
+                    replace_prediction(&mut pkt, &window, slot_changes, carried_item)
+                } else {
+                    Mispredicted::default()
+                };
 
-                    debug!("Client state id mismatch, resyncing");
+                if mispredicted.cursor_item || !mispredicted.slots.is_empty() {
+                    reject(ClickRejection::Mispredicted);
+                }
 
-                    inv_state.state_id += 1;
+                // Swapping with the offhand only changes the clicked slot in the window, so the
+                // offhand is updated here.
+                let offhand_swap = (pkt.mode == ClickMode::Hotbar && pkt.button == 40).then(|| {
+                    InventoryWindow::new(&client_inv, Some(&target_inventory))
+                        .slot(pkt.slot_idx as u16)
+                        .cloned()
+                });
 
-                    client.write_packet(&InventoryS2c {
-                        window_id: inv_state.window_id,
-                        state_id: VarInt(inv_state.state_id.0),
-                        slots: Cow::Borrowed(target_inventory.slot_slice()),
-                        carried_item: Cow::Borrowed(&cursor_item.0),
-                    });
+                cursor_item.set_if_neq(CursorItem(pkt.carried_item.clone()));
+                inv_state.client_updated_cursor_item = !mispredicted.cursor_item;
 
-                    continue;
+                if let Some(item) = offhand_swap {
+                    // The client already swapped the items, so the offhand isn't marked as
+                    // changed.
+                    client_inv.set_slot_unchanged(OFFHAND_SLOT, item, cause);
                 }
 
-                cursor_item.set_if_neq(CursorItem(pkt.carried_item.clone()));
-
                 for slot in pkt.slot_changes.clone() {
                     if (0i16..target_inventory.slot_count() as i16).contains(&slot.idx) {
                         // The client is interacting with a slot in the target inventory.
-                        target_inventory.set_slot(slot.idx as u16, slot.item);
+                        target_inventory.set_slot_by(slot.idx as u16, slot.item, cause);
                         open_inventory.client_changed |= 1 << slot.idx;
                     } else {
                         // The client is interacting with a slot in their own inventory.
                         let slot_id =
                             convert_to_player_slot_id(target_inventory.kind, slot.idx as u16);
-                        client_inv.set_slot(slot_id, slot.item);
+                        client_inv.set_slot_by(slot_id, slot.item, cause);
                         inv_state.slots_changed |= 1 << slot_id;
                     }
                 }
-            } else {
-                // The client is interacting with their own inventory.
-
-                if inv_state.state_id.0 != pkt.state_id.0 {
-                    // Client is out of sync. Resync and ignore the click.
 
-                    debug!("Client state id mismatch, resyncing");
+                // Send the slots that the client got wrong.
+                for idx in mispredicted.slots {
+                    if idx < target_inventory.slot_count() {
+                        target_inventory.changed |= 1 << idx;
+                        open_inventory.client_changed &= !(1 << idx);
+                    } else {
+                        let slot_id = convert_to_player_slot_id(target_inventory.kind, idx);
+                        client_inv.changed |= 1 << slot_id;
+                        inv_state.slots_changed &= !(1 << slot_id);
+                    }
+                }
 
-                    inv_state.state_id += 1;
+                if mispredicted.cursor_item {
+                    cursor_item.set_changed();
+                }
+            } else {
+                // The client is interacting with their own inventory.
 
-                    client.write_packet(&InventoryS2c {
-                        window_id: inv_state.window_id,
-                        state_id: VarInt(inv_state.state_id.0),
-                        slots: Cow::Borrowed(client_inv.slot_slice()),
-                        carried_item: Cow::Borrowed(&cursor_item.0),
-                    });
+                let window = InventoryWindow::new(&client_inv, None);
+                let rules = quick_move_rules.get(packet.client).ok().map(|r| &*r.0);
+
+                let mispredicted = if pkt.mode == ClickMode::Drag {
+                    resolve_drag(
+                        &mut pkt,
+                        &mut inv_state,
+                        &window,
+                        &cursor_item,
+                        *game_mode == GameMode::Creative,
+                    )
+                } else if let Some((slot_changes, carried_item)) = crafting::click(
+                    &pkt,
+                    &window,
+                    &cursor_item,
+                    &crafting_rules,
+                    &recipes,
+                    rules,
+                )
+                .or_else(|| bundle::click(&pkt, &window, &cursor_item))
+                .or_else(|| click::click(&pkt, &window, &cursor_item, rules))
+                {
+                    replace_prediction(&mut pkt, &window, slot_changes, carried_item)
+                } else {
+                    Mispredicted::default()
+                };
 
-                    continue;
+                if mispredicted.cursor_item || !mispredicted.slots.is_empty() {
+                    reject(ClickRejection::Mispredicted);
                 }
 
                 cursor_item.set_if_neq(CursorItem(pkt.carried_item.clone()));
-                inv_state.client_updated_cursor_item = true;
+                inv_state.client_updated_cursor_item = !mispredicted.cursor_item;
 
                 for slot in pkt.slot_changes.clone() {
                     if (0i16..client_inv.slot_count() as i16).contains(&slot.idx) {
-                        client_inv.set_slot(slot.idx as u16, slot.item);
+                        client_inv.set_slot_by(slot.idx as u16, slot.item, cause);
                         inv_state.slots_changed |= 1 << slot.idx;
                     } else {
                         // The client is trying to interact with a slot that does not exist,
@@ -1028,6 +2218,16 @@ fn handle_click_slot(
                         );
                     }
                 }
+
+                // Send the slots that the client got wrong.
+                for idx in mispredicted.slots {
+                    client_inv.changed |= 1 << idx;
+                    inv_state.slots_changed &= !(1 << idx);
+                }
+
+                if mispredicted.cursor_item {
+                    cursor_item.set_changed();
+                }
             }
 
             click_slot_events.send(ClickSlotEvent {
@@ -1044,51 +2244,95 @@ fn handle_click_slot(
     }
 }
 
+/// The parts of a click that the client predicted differently than the
+/// server.
+#[derive(Default)]
+struct Mispredicted {
+    slots: Vec<u16>,
+    cursor_item: bool,
+}
+
+/// Replaces the slot changes and carried item of a drag packet with the result
+/// that the server works out from the client's drag state.
+fn resolve_drag(
+    pkt: &mut ClickSlotC2s,
+    inv_state: &mut ClientInventoryState,
+    window: &InventoryWindow,
+    cursor_item: &CursorItem,
+    creative: bool,
+) -> Mispredicted {
+    let (slot_changes, carried_item) =
+        drag::update_drag(&mut inv_state.drag, pkt, window, cursor_item, creative);
+
+    replace_prediction(pkt, window, slot_changes, carried_item)
+}
+
+/// Replaces the slot changes and carried item that the client predicted for a
+/// click with the result that the server worked out.
+fn replace_prediction(
+    pkt: &mut ClickSlotC2s,
+    window: &InventoryWindow,
+    slot_changes: Vec<SlotChange>,
+    carried_item: Option<ItemStack>,
+) -> Mispredicted {
+    let predicted = std::mem::replace(&mut pkt.slot_changes, slot_changes);
+
+    let differs =
+        |a: &SlotChange, b: &[SlotChange]| !b.iter().any(|b| a.idx == b.idx && a.item == b.item);
+
+    let mut slots: Vec<u16> = predicted
+        .iter()
+        .filter(|p| differs(p, &pkt.slot_changes))
+        .chain(pkt.slot_changes.iter().filter(|c| differs(c, &predicted)))
+        .map(|c| c.idx as u16)
+        .filter(|&idx| idx < window.slot_count())
+        .collect();
+
+    slots.sort_unstable();
+    slots.dedup();
+
+    let cursor_item = pkt.carried_item != carried_item;
+    pkt.carried_item = carried_item;
+
+    Mispredicted { slots, cursor_item }
+}
+
 fn handle_player_actions(
     mut packets: EventReader<PacketEvent>,
     mut clients: Query<(&mut Inventory, &mut ClientInventoryState, &HeldItem)>,
-    mut drop_item_stack_events: EventWriter<DropItemStackEvent>,
+    mut pending_drops: ResMut<PendingItemDrops>,
+    mut pending_swaps: ResMut<PendingHandSwaps>,
 ) {
     for packet in packets.iter() {
         if let Some(pkt) = packet.decode::<PlayerActionC2s>() {
             match pkt.action {
-                PlayerAction::DropAllItems => {
-                    if let Ok((mut inv, mut inv_state, &held)) = clients.get_mut(packet.client) {
-                        if let Some(stack) = inv.replace_slot(held.slot(), None) {
-                            inv_state.slots_changed |= 1 << held.slot();
+                PlayerAction::DropAllItems | PlayerAction::DropItem => {
+                    let whole_stack = pkt.action == PlayerAction::DropAllItems;
 
-                            drop_item_stack_events.send(DropItemStackEvent {
-                                client: packet.client,
-                                from_slot: Some(held.slot()),
-                                stack,
-                            });
-                        }
-                    }
-                }
-                PlayerAction::DropItem => {
                     if let Ok((mut inv, mut inv_state, held)) = clients.get_mut(packet.client) {
-                        if let Some(mut stack) = inv.replace_slot(held.slot(), None) {
-                            if stack.count() > 1 {
-                                inv.set_slot(
-                                    held.slot(),
-                                    stack.clone().with_count(stack.count() - 1),
-                                );
-
-                                stack.set_count(1);
-                            }
-
+                        if let Some(stack) =
+                            take_dropped(&mut inv, held.slot(), whole_stack, packet.client)
+                        {
                             inv_state.slots_changed |= 1 << held.slot();
 
-                            drop_item_stack_events.send(DropItemStackEvent {
-                                client: packet.client,
-                                from_slot: Some(held.slot()),
+                            pending_drops.0.push(DropItemEvent::new(
+                                packet.client,
                                 stack,
-                            })
+                                whole_stack,
+                                DropSource::PlayerInventory(held.slot()),
+                            ));
                         }
                     }
                 }
                 PlayerAction::SwapItemWithOffhand => {
-                    // TODO
+                    if let Ok((inv, _, held)) = clients.get(packet.client) {
+                        pending_swaps.0.push(SwapHandsEvent {
+                            client: packet.client,
+                            main_hand: inv.slot(OFFHAND_SLOT).cloned(),
+                            off_hand: inv.slot(held.slot()).cloned(),
+                            cancelled: false,
+                        });
+                    }
                 }
                 _ => {}
             }
@@ -1096,6 +2340,75 @@ fn handle_player_actions(
     }
 }
 
+/// A client swapping the items in its hands with the swap key, which has yet
+/// to be applied.
+///
+/// The client waits for the server to swap the items. Pressing the swap key
+/// while hovering over a slot in a window swaps that slot with the offhand
+/// instead, which is a click and sends a [`ClickSlotEvent`].
+///
+/// Swaps are kept in [`PendingHandSwaps`] for systems in [`EventLoopUpdate`]
+/// to cancel, and the swaps that weren't cancelled are then applied and sent
+/// as events.
+///
+/// [`EventLoopUpdate`]: valence_client::event_loop::EventLoopUpdate
+#[derive(Event, Clone, PartialEq, Debug)]
+pub struct SwapHandsEvent {
+    pub client: Entity,
+    /// The item that ends up in the main hand, which was in the offhand.
+    pub main_hand: Option<ItemStack>,
+    /// The item that ends up in the offhand, which was in the main hand.
+    pub off_hand: Option<ItemStack>,
+    cancelled: bool,
+}
+
+impl SwapHandsEvent {
+    /// Keeps the items from being swapped. The client is sent the items that
+    /// are actually in its hands.
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// The [`SwapHandsEvent`]s from the current run of the event loop.
+///
+/// They are applied in [`EventLoopPostUpdate`], so systems in
+/// [`EventLoopUpdate`] can cancel them first.
+///
+/// [`EventLoopUpdate`]: valence_client::event_loop::EventLoopUpdate
+#[derive(Resource, Default, Debug)]
+pub struct PendingHandSwaps(pub Vec<SwapHandsEvent>);
+
+fn apply_hand_swaps(
+    mut pending: ResMut<PendingHandSwaps>,
+    mut clients: Query<(&mut Inventory, &HeldItem)>,
+    mut events: EventWriter<SwapHandsEvent>,
+) {
+    for mut event in pending.0.drain(..) {
+        let Ok((mut inventory, held)) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        if event.cancelled {
+            // Make sure that the client agrees with the server.
+            inventory.changed |= 1 << held.slot() | 1 << OFFHAND_SLOT;
+            continue;
+        }
+
+        let off_hand = inventory.replace_slot(held.slot(), None);
+        let main_hand = inventory.replace_slot(OFFHAND_SLOT, off_hand.clone());
+        inventory.set_slot(held.slot(), main_hand.clone());
+
+        event.main_hand = main_hand;
+        event.off_hand = off_hand;
+        events.send(event);
+    }
+}
+
 // TODO: make this event user friendly.
 #[derive(Event, Clone, Debug)]
 pub struct CreativeInventoryActionEvent {
@@ -1104,21 +2417,58 @@ pub struct CreativeInventoryActionEvent {
     pub clicked_item: Option<ItemStack>,
 }
 
+/// A change to a slot in the inventory of a client in creative mode, which
+/// has yet to be applied.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CreativeSetSlotEvent {
+    pub client: Entity,
+    pub slot: u16,
+    /// The item to put in the slot. This has already been validated according
+    /// to the [`InventorySettings`].
+    pub proposed: Option<ItemStack>,
+    cancelled: bool,
+}
+
+impl CreativeSetSlotEvent {
+    /// Keeps the slot from changing. The client is sent the item that is
+    /// actually in the slot.
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// The [`CreativeSetSlotEvent`]s from the current run of the event loop.
+///
+/// They are applied in [`EventLoopPostUpdate`], so systems in
+/// [`EventLoopUpdate`] can change or cancel them first.
+///
+/// [`EventLoopUpdate`]: valence_client::event_loop::EventLoopUpdate
+#[derive(Resource, Default, Debug)]
+pub struct PendingCreativeSetSlots(pub Vec<CreativeSetSlotEvent>);
+
 fn handle_creative_inventory_action(
     mut packets: EventReader<PacketEvent>,
     mut clients: Query<(
         &mut Client,
-        &mut Inventory,
+        &Inventory,
         &mut ClientInventoryState,
         &GameMode,
     )>,
+    settings: Res<InventorySettings>,
+    mut pending: ResMut<PendingCreativeSetSlots>,
     mut inv_action_events: EventWriter<CreativeInventoryActionEvent>,
-    mut drop_item_stack_events: EventWriter<DropItemStackEvent>,
+    mut pending_drops: ResMut<PendingItemDrops>,
 ) {
     for packet in packets.iter() {
         if let Some(pkt) = packet.decode::<CreativeInventoryActionC2s>() {
-            let Ok((mut client, mut inventory, mut inv_state, game_mode)) = clients.get_mut(packet.client) else {
-                continue
+            let Ok((mut client, inventory, mut inv_state, game_mode)) =
+                clients.get_mut(packet.client)
+            else {
+                continue;
             };
 
             if *game_mode != GameMode::Creative {
@@ -1126,42 +2476,233 @@ fn handle_creative_inventory_action(
                 continue;
             }
 
-            if pkt.slot == -1 {
-                if let Some(stack) = pkt.clicked_item.clone() {
-                    drop_item_stack_events.send(DropItemStackEvent {
-                        client: packet.client,
-                        from_slot: None,
-                        stack,
-                    });
-                }
-                continue;
-            }
+            let in_bounds = pkt.slot >= 0 && pkt.slot < inventory.slot_count() as i16;
 
-            if pkt.slot < 0 || pkt.slot >= inventory.slot_count() as i16 {
+            if pkt.slot != -1 && !in_bounds {
                 // The client is trying to interact with a slot that does not exist, ignore.
                 continue;
             }
 
-            // Set the slot without marking it as changed.
-            inventory.slots[pkt.slot as usize] = pkt.clicked_item.clone();
+            let clicked_item = match validate::validate_creative_item(pkt.clicked_item, &settings) {
+                Ok(item) => item,
+                Err(e) => {
+                    debug!(
+                        "failed to validate creative inventory action for client {:#?}: {e:#}",
+                        packet.client
+                    );
 
-            inv_state.state_id += 1;
+                    if in_bounds {
+                        // Undo the change on the client's side.
+                        inv_state.state_id += 1;
 
-            // HACK: notchian clients rely on the server to send the slot update when in
-            // creative mode. Simply marking the slot as changed is not enough. This was
-            // discovered because shift-clicking the destroy item slot in creative mode does
-            // not work without this hack.
-            client.write_packet(&ScreenHandlerSlotUpdateS2c {
-                window_id: 0,
-                state_id: VarInt(inv_state.state_id.0),
-                slot_idx: pkt.slot,
-                slot_data: Cow::Borrowed(&pkt.clicked_item),
-            });
+                        client.write_packet(&ScreenHandlerSlotUpdateS2c {
+                            window_id: 0,
+                            state_id: VarInt(inv_state.state_id.0),
+                            slot_idx: pkt.slot,
+                            slot_data: Cow::Borrowed(&inventory.slots[pkt.slot as usize]),
+                        });
+                    }
+
+                    continue;
+                }
+            };
+
+            if pkt.slot == -1 {
+                if let Some(stack) = clicked_item.clone() {
+                    pending_drops.0.push(DropItemEvent::new(
+                        packet.client,
+                        stack,
+                        true,
+                        DropSource::CreativeMenu,
+                    ));
+                }
+            } else {
+                pending.0.push(CreativeSetSlotEvent {
+                    client: packet.client,
+                    slot: pkt.slot as u16,
+                    proposed: clicked_item.clone(),
+                    cancelled: false,
+                });
+            }
 
             inv_action_events.send(CreativeInventoryActionEvent {
                 client: packet.client,
                 slot: pkt.slot,
-                clicked_item: pkt.clicked_item,
+                clicked_item,
+            });
+        }
+    }
+}
+
+fn apply_creative_set_slots(
+    mut pending: ResMut<PendingCreativeSetSlots>,
+    mut clients: Query<(&mut Client, &mut Inventory, &mut ClientInventoryState)>,
+) {
+    for event in pending.0.drain(..) {
+        let Ok((mut client, mut inventory, mut inv_state)) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        if event.slot >= inventory.slot_count() {
+            continue;
+        }
+
+        if !event.cancelled {
+            // Set the slot without marking it as changed.
+            inventory.set_slot_unchanged(
+                event.slot,
+                event.proposed,
+                SlotChangeCause::Creative(event.client),
+            );
+        }
+
+        inv_state.state_id += 1;
+
+        // HACK: notchian clients rely on the server to send the slot update when in
+        // creative mode. Simply marking the slot as changed is not enough. This was
+        // discovered because shift-clicking the destroy item slot in creative mode does
+        // not work without this hack.
+        //
+        // This also tells the client about the item that was actually put in the slot,
+        // in case it was changed or cancelled.
+        client.write_packet(&ScreenHandlerSlotUpdateS2c {
+            window_id: 0,
+            state_id: VarInt(inv_state.state_id.0),
+            slot_idx: event.slot as i16,
+            slot_data: Cow::Borrowed(&inventory.slots[event.slot as usize]),
+        });
+    }
+}
+
+/// Sent when an item in an [`Inventory`] breaks in
+/// [`Inventory::damage_slot`], such as to play the break sound and particles.
+#[derive(Event, Clone, PartialEq, Debug)]
+pub struct ItemBreakEvent {
+    /// The entity with the [`Inventory`].
+    pub inventory: Entity,
+    pub slot: u16,
+    /// The item that broke, which has been removed from the slot.
+    pub item: ItemStack,
+}
+
+fn send_item_break_events(
+    mut inventories: Query<(Entity, &mut Inventory)>,
+    mut events: EventWriter<ItemBreakEvent>,
+) {
+    for (entity, mut inventory) in &mut inventories {
+        // Avoid triggering change detection on every inventory.
+        if inventory.broken_items.is_empty() {
+            continue;
+        }
+
+        for (slot, item) in inventory.broken_items.drain(..) {
+            events.send(ItemBreakEvent {
+                inventory: entity,
+                slot,
+                item,
+            });
+        }
+    }
+}
+
+/// Sent for every change to the slots of an [`Inventory`], so systems can
+/// react to items being obtained or lost without comparing inventories
+/// themselves.
+#[derive(Event, Clone, PartialEq, Debug)]
+pub enum SlotChangeEvent {
+    /// A single slot changed.
+    Slot {
+        /// The entity with the [`Inventory`].
+        inventory: Entity,
+        slot: u16,
+        old: Option<ItemStack>,
+        new: Option<ItemStack>,
+        cause: SlotChangeCause,
+    },
+    /// Many slots changed at once, such as with [`Inventory::clear`]. The
+    /// slots aren't sent one by one to avoid sending lots of events.
+    BulkChange {
+        /// The entity with the [`Inventory`].
+        inventory: Entity,
+        cause: SlotChangeCause,
+    },
+}
+
+impl SlotChangeEvent {
+    /// The entity with the [`Inventory`] that changed.
+    pub fn inventory(&self) -> Entity {
+        match self {
+            SlotChangeEvent::Slot { inventory, .. }
+            | SlotChangeEvent::BulkChange { inventory, .. } => *inventory,
+        }
+    }
+
+    pub fn cause(&self) -> SlotChangeCause {
+        match self {
+            SlotChangeEvent::Slot { cause, .. } | SlotChangeEvent::BulkChange { cause, .. } => {
+                *cause
+            }
+        }
+    }
+}
+
+/// What changed the slots of an [`Inventory`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SlotChangeCause {
+    /// The server changed the slot, such as with [`Inventory::set_slot`].
+    Server,
+    /// The client clicked in a window.
+    Click(Entity),
+    /// The client spread items over slots by dragging them.
+    Drag(Entity),
+    /// The client set the slot from the creative inventory menu.
+    Creative(Entity),
+    /// The client put on armor by using it. See [`ArmorEquipEvent`].
+    Equip(Entity),
+}
+
+/// A [`SlotChangeEvent`] that hasn't been sent yet.
+#[derive(Clone, Debug)]
+enum PendingSlotChange {
+    Slot {
+        slot: u16,
+        old: Option<ItemStack>,
+        new: Option<ItemStack>,
+        cause: SlotChangeCause,
+    },
+    Bulk {
+        cause: SlotChangeCause,
+    },
+}
+
+fn send_slot_change_events(
+    mut inventories: Query<(Entity, &mut Inventory)>,
+    mut events: EventWriter<SlotChangeEvent>,
+) {
+    for (entity, mut inventory) in &mut inventories {
+        // Avoid triggering change detection on every inventory.
+        if inventory.slot_changes.is_empty() {
+            continue;
+        }
+
+        for change in inventory.slot_changes.drain(..) {
+            events.send(match change {
+                PendingSlotChange::Slot {
+                    slot,
+                    old,
+                    new,
+                    cause,
+                } => SlotChangeEvent::Slot {
+                    inventory: entity,
+                    slot,
+                    old,
+                    new,
+                    cause,
+                },
+                PendingSlotChange::Bulk { cause } => SlotChangeEvent::BulkChange {
+                    inventory: entity,
+                    cause,
+                },
             });
         }
     }
@@ -1173,19 +2714,45 @@ pub struct UpdateSelectedSlotEvent {
     pub slot: i16,
 }
 
+/// Sent when the [`HeldItem`] of a client changes, either because the client
+/// switched to another hotbar slot or because the server moved it to one.
+///
+/// The slots are slot IDs of the player's inventory, in the range 36-44
+/// inclusive.
+#[derive(Event, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct HeldItemChangeEvent {
+    pub client: Entity,
+    pub old_slot: u16,
+    pub new_slot: u16,
+}
+
 fn handle_update_selected_slot(
     mut packets: EventReader<PacketEvent>,
-    mut clients: Query<&mut HeldItem>,
+    mut clients: Query<(&mut HeldItem, &mut ClientInventoryState)>,
     mut events: EventWriter<UpdateSelectedSlotEvent>,
+    mut change_events: EventWriter<HeldItemChangeEvent>,
 ) {
     for packet in packets.iter() {
         if let Some(pkt) = packet.decode::<UpdateSelectedSlotC2s>() {
-            if let Ok(mut held) = clients.get_mut(packet.client) {
+            if let Ok((mut held, mut inv_state)) = clients.get_mut(packet.client) {
                 if pkt.slot < 0 || pkt.slot > 8 {
                     // The client is trying to interact with a slot that does not exist, ignore.
                     continue;
                 }
-                held.held_item_slot = convert_hotbar_slot_id(pkt.slot as u16);
+
+                let old_slot = held.held_item_slot;
+                let new_slot = convert_hotbar_slot_id(pkt.slot as u16);
+
+                held.held_item_slot = new_slot;
+                inv_state.client_held_item_slot = new_slot;
+
+                if old_slot != new_slot {
+                    change_events.send(HeldItemChangeEvent {
+                        client: packet.client,
+                        old_slot,
+                        new_slot,
+                    });
+                }
 
                 events.send(UpdateSelectedSlotEvent {
                     client: packet.client,
@@ -1196,6 +2763,35 @@ fn handle_update_selected_slot(
     }
 }
 
+/// Sends the [`HeldItem`] to clients when it was changed by the server.
+fn update_held_items(
+    mut clients: Query<
+        (Entity, &mut Client, &HeldItem, &mut ClientInventoryState),
+        Changed<HeldItem>,
+    >,
+    mut events: EventWriter<HeldItemChangeEvent>,
+) {
+    for (entity, mut client, held, mut inv_state) in &mut clients {
+        let old_slot = inv_state.client_held_item_slot;
+
+        if held.held_item_slot == old_slot {
+            continue;
+        }
+
+        client.write_packet(&UpdateSelectedSlotS2c {
+            slot: held.hotbar_slot(),
+        });
+
+        inv_state.client_held_item_slot = held.held_item_slot;
+
+        events.send(HeldItemChangeEvent {
+            client: entity,
+            old_slot,
+            new_slot: held.held_item_slot,
+        });
+    }
+}
+
 /// Convert a slot that is outside a target inventory's range to a slot that is
 /// inside the player's inventory.
 #[doc(hidden)]
@@ -1250,7 +2846,7 @@ impl InventoryKind {
             InventoryKind::Generic9x5 => 9 * 5,
             InventoryKind::Generic9x6 => 9 * 6,
             InventoryKind::Generic3x3 => 3 * 3,
-            InventoryKind::Anvil => 4,
+            InventoryKind::Anvil => 3,
             InventoryKind::Beacon => 1,
             InventoryKind::BlastFurnace => 3,
             InventoryKind::BrewingStand => 5,
@@ -1263,13 +2859,29 @@ impl InventoryKind {
             InventoryKind::Loom => 4,
             InventoryKind::Merchant => 3,
             InventoryKind::ShulkerBox => 27,
-            InventoryKind::Smithing => 3,
+            InventoryKind::Smithing => 4,
             InventoryKind::Smoker => 3,
             InventoryKind::Cartography => 3,
             InventoryKind::Stonecutter => 2,
             InventoryKind::Player => 46,
         }
     }
+
+    /// The number of window properties that inventories of this kind have. See
+    /// [`ContainerProperties`].
+    pub const fn property_count(self) -> usize {
+        match self {
+            InventoryKind::Furnace | InventoryKind::BlastFurnace | InventoryKind::Smoker => 4,
+            InventoryKind::Enchantment => 10,
+            InventoryKind::Beacon => 3,
+            InventoryKind::BrewingStand => 2,
+            InventoryKind::Anvil
+            | InventoryKind::Lectern
+            | InventoryKind::Loom
+            | InventoryKind::Stonecutter => 1,
+            _ => 0,
+        }
+    }
 }
 
 impl From<InventoryKind> for WindowType {
@@ -1340,12 +2952,39 @@ impl From<WindowType> for InventoryKind {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Resource)]
 pub struct InventorySettings {
     pub validate_actions: bool,
+    /// The largest item NBT, in bytes, that clients in creative mode may put
+    /// in their inventory.
+    pub max_creative_nbt_size: usize,
+    /// Whether item NBT over [`Self::max_creative_nbt_size`] is removed from
+    /// the item. If `false`, the item is rejected instead.
+    pub strip_oversized_nbt: bool,
+    /// What happens to a client's [`CursorItem`] when it closes a window.
+    pub cursor_item_on_close: CursorItemOnClose,
+    /// Whether the held items and armor of every client are shown to the other
+    /// clients. Mirroring can also be enabled for single clients with
+    /// [`MirrorEquipment`](equipment::MirrorEquipment).
+    pub mirror_equipment: bool,
+}
+
+/// What happens to the item on a client's cursor when it closes a window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CursorItemOnClose {
+    /// The item is put back in the player's inventory, like in vanilla. What
+    /// doesn't fit is dropped with a [`DropItemEvent`].
+    #[default]
+    ReturnToInventory,
+    /// The whole item is dropped with a [`DropItemEvent`].
+    Drop,
 }
 
 impl Default for InventorySettings {
     fn default() -> Self {
         Self {
             validate_actions: true,
+            max_creative_nbt_size: 65536,
+            strip_oversized_nbt: false,
+            cursor_item_on_close: CursorItemOnClose::ReturnToInventory,
+            mirror_equipment: false,
         }
     }
 }
@@ -0,0 +1,378 @@
+//! Taking results out of the output slots of anvils, stonecutters, and smithing
+//! tables.
+//!
+//! The client predicts the inputs being used up when it takes a result, so
+//! these clicks are worked out by the server instead of following the client's
+//! prediction.
+
+use std::borrow::Cow;
+
+use bevy_ecs::prelude::*;
+use valence_client::Client;
+use valence_core::item::ItemStack;
+use valence_core::protocol::encode::WritePacket;
+use valence_core::protocol::var_int::VarInt;
+
+use crate::click::slot_changes;
+use crate::packet::{ClickMode, ClickSlotC2s, ScreenHandlerSlotUpdateS2c, SlotChange};
+use crate::quick_move::{move_to, result_target, QuickMove, QuickMoveTarget};
+use crate::{
+    anvil, convert_to_player_slot_id, smithing, stonecutter, ClientInventoryState, CursorItem,
+    Inventory, InventoryKind, InventoryWindow, OpenInventory, SlotChangeCause,
+};
+
+/// A client taking the result out of a stonecutter or smithing table, which
+/// has yet to be applied.
+///
+/// When applied, the result is moved to the client, one item is used up from
+/// each input, and the output slot is cleared. Anvils use
+/// [`AnvilTakeResultEvent`] instead.
+///
+/// [`AnvilTakeResultEvent`]: crate::anvil::AnvilTakeResultEvent
+#[derive(Clone, Debug)]
+pub struct TakeResultEvent {
+    pub client: Entity,
+    /// The entity with the window's [`Inventory`].
+    pub inventory: Entity,
+    pub result: ItemStack,
+    target: TakeTarget,
+    cancelled: bool,
+}
+
+impl TakeResultEvent {
+    /// Keeps the client from taking the result. The client is sent the actual
+    /// contents of the window.
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// The [`TakeResultEvent`]s from the current run of the event loop.
+///
+/// They are applied in [`EventLoopPostUpdate`], so systems in
+/// [`EventLoopUpdate`] can cancel them first.
+///
+/// [`EventLoopPostUpdate`]: valence_client::event_loop::EventLoopPostUpdate
+/// [`EventLoopUpdate`]: valence_client::event_loop::EventLoopUpdate
+#[derive(Resource, Default, Debug)]
+pub struct PendingResultTakes(pub Vec<TakeResultEvent>);
+
+/// Where a taken result goes.
+#[derive(Clone, Debug)]
+pub(super) enum TakeTarget {
+    Cursor,
+    /// The result is shift clicked into the player's inventory.
+    PlayerInventory {
+        /// The changes worked out by the server.
+        changes: Vec<SlotChange>,
+        /// The changes predicted by the client.
+        predicted: Vec<SlotChange>,
+    },
+}
+
+impl TakeTarget {
+    /// Returns the changes to the player's inventory that the client
+    /// predicted.
+    pub(super) fn player_slot_changes(&self) -> &[SlotChange] {
+        match self {
+            TakeTarget::Cursor => &[],
+            TakeTarget::PlayerInventory { predicted, .. } => predicted,
+        }
+    }
+}
+
+/// A click that takes the result out of a window's output slot.
+pub(super) struct TakeClick<'a> {
+    pub client: Entity,
+    /// The entity with the window's [`Inventory`].
+    pub inventory: Entity,
+    pub pkt: &'a ClickSlotC2s,
+    pub window: &'a Inventory,
+    pub player_inventory: &'a Inventory,
+    pub cursor_item: &'a CursorItem,
+    /// The [`QuickMoveRules`](crate::quick_move::QuickMoveRules) of the
+    /// window.
+    pub rules: Option<&'a dyn QuickMove>,
+}
+
+impl TakeClick<'_> {
+    /// Returns the result in the output slot of the window.
+    pub(super) fn result(&self) -> Option<&ItemStack> {
+        self.window.slot(output_slot(self.window.kind)?)
+    }
+}
+
+fn output_slot(kind: InventoryKind) -> Option<u16> {
+    match kind {
+        InventoryKind::Anvil => Some(anvil::OUTPUT_SLOT),
+        InventoryKind::Stonecutter => Some(stonecutter::OUTPUT_SLOT),
+        InventoryKind::Smithing => Some(smithing::OUTPUT_SLOT),
+        _ => None,
+    }
+}
+
+/// Returns whether a slot of a window is the output slot of an anvil,
+/// stonecutter, or smithing table.
+pub(super) fn is_output_slot(window: &InventoryWindow, idx: i16) -> bool {
+    window
+        .open_inventory
+        .and_then(|inv| output_slot(inv.kind))
+        .is_some_and(|slot| idx == slot as i16)
+}
+
+/// Returns whether a click takes the result out of a window's output slot.
+pub(super) fn is_take_result(kind: InventoryKind, pkt: &ClickSlotC2s) -> bool {
+    output_slot(kind).is_some_and(|slot| pkt.slot_idx == slot as i16)
+        && matches!(pkt.mode, ClickMode::Click | ClickMode::ShiftClick)
+}
+
+/// Works out where a click puts the result of a window. Returns `None` if the
+/// result can't be taken.
+///
+/// Shift clicks move the result like [`quick_move`](crate::quick_move), so a
+/// [`QuickMoveRules`](crate::quick_move::QuickMoveRules) component on the
+/// window changes where it goes. The whole result has to fit in the player's
+/// inventory.
+pub(super) fn take_target(click: &TakeClick, result: &ItemStack) -> Option<TakeTarget> {
+    let TakeClick {
+        pkt,
+        window,
+        player_inventory,
+        cursor_item,
+        rules,
+        ..
+    } = *click;
+
+    if pkt.mode != ClickMode::ShiftClick {
+        return fits_in_cursor(result, cursor_item).then_some(TakeTarget::Cursor);
+    }
+
+    let full_window = InventoryWindow::new(player_inventory, Some(window));
+
+    let mut slots: Vec<_> = (0..full_window.slot_count())
+        .map(|i| full_window.slot(i).cloned())
+        .collect();
+
+    let target = result_target(
+        &full_window,
+        &slots,
+        output_slot(window.kind)?,
+        result,
+        rules,
+    )?;
+
+    // Results only go to the player's inventory, since the window's slots are used
+    // up when the result is taken.
+    let target = QuickMoveTarget {
+        slots: target.slots.start.max(window.slot_count())..target.slots.end,
+        ..target
+    };
+
+    if move_to(&mut slots, result.clone(), target, &full_window).is_some() {
+        return None;
+    }
+
+    let player_slots = |changes: Vec<SlotChange>| -> Vec<SlotChange> {
+        changes
+            .into_iter()
+            .filter(|change| change.idx >= window.slot_count() as i16)
+            .collect()
+    };
+
+    Some(TakeTarget::PlayerInventory {
+        changes: player_slots(slot_changes(slots, &full_window)),
+        predicted: player_slots(pkt.slot_changes.clone()),
+    })
+}
+
+fn fits_in_cursor(result: &ItemStack, cursor_item: &CursorItem) -> bool {
+    match &cursor_item.0 {
+        Some(cursor) => {
+            cursor.item == result.item
+                && cursor.nbt == result.nbt
+                && cursor.count() as u16 + result.count() as u16 <= result.item.max_stack() as u16
+        }
+        None => true,
+    }
+}
+
+/// Returns whether a result taken with [`take_target`] is still in the window
+/// and can still be given to the client.
+pub(super) fn can_still_take(
+    target: &TakeTarget,
+    window: &Inventory,
+    cursor_item: &CursorItem,
+    result: &ItemStack,
+) -> bool {
+    let in_window = output_slot(window.kind).and_then(|slot| window.slot(slot)) == Some(result);
+
+    in_window
+        && match target {
+            TakeTarget::Cursor => fits_in_cursor(result, cursor_item),
+            TakeTarget::PlayerInventory { .. } => true,
+        }
+}
+
+/// Moves a taken result to the client and clears the output slot.
+pub(super) fn give_result(
+    client: &mut Client,
+    target: TakeTarget,
+    window: &mut Inventory,
+    player_inventory: &mut Inventory,
+    inv_state: &mut ClientInventoryState,
+    cursor_item: &mut CursorItem,
+    cause: SlotChangeCause,
+) {
+    let Some(result) =
+        output_slot(window.kind).and_then(|slot| window.replace_slot_by(slot, None, cause))
+    else {
+        return;
+    };
+
+    match target {
+        TakeTarget::Cursor => {
+            let count = cursor_item.0.as_ref().map_or(0, |s| s.count()) + result.count();
+            cursor_item.0 = Some(result.with_count(count));
+        }
+        TakeTarget::PlayerInventory { changes, predicted } => {
+            for change in &changes {
+                let slot_id = convert_to_player_slot_id(window.kind, change.idx as u16);
+                player_inventory.set_slot_by(slot_id, change.item.clone(), cause);
+                inv_state.slots_changed |= 1 << slot_id;
+            }
+
+            // The client is sent the slots that it predicted wrongly, such as when the
+            // window has custom quick move rules.
+            let mut mispredicted: Vec<i16> = changes
+                .iter()
+                .filter(|change| !predicted.contains(change))
+                .chain(predicted.iter().filter(|p| !changes.contains(p)))
+                .map(|change| change.idx)
+                .collect();
+
+            mispredicted.sort_unstable();
+            mispredicted.dedup();
+
+            for idx in mispredicted {
+                send_player_slot(client, inv_state, window, player_inventory, idx);
+            }
+        }
+    }
+}
+
+/// Undoes a click that the client predicted would take the result out of a
+/// window.
+pub(super) fn resync(
+    client: &mut Client,
+    inv_state: &ClientInventoryState,
+    window: &mut Inventory,
+    player_inventory: &Inventory,
+    pkt_slot_changes: &[SlotChange],
+) {
+    // This also sends the cursor item.
+    window.changed = u64::MAX;
+
+    for change in pkt_slot_changes {
+        if change.idx >= window.slot_count() as i16 {
+            send_player_slot(client, inv_state, window, player_inventory, change.idx);
+        }
+    }
+}
+
+/// Sends the contents of a slot of the player's inventory, numbered as in the
+/// window.
+fn send_player_slot(
+    client: &mut Client,
+    inv_state: &ClientInventoryState,
+    window: &Inventory,
+    player_inventory: &Inventory,
+    idx: i16,
+) {
+    let slot_id = convert_to_player_slot_id(window.kind, idx as u16);
+
+    client.write_packet(&ScreenHandlerSlotUpdateS2c {
+        window_id: inv_state.window_id as i8,
+        state_id: VarInt(inv_state.state_id.0),
+        slot_idx: idx,
+        slot_data: Cow::Borrowed(&player_inventory.slots[slot_id as usize]),
+    });
+}
+
+/// Works out the result of a click that takes the result out of a stonecutter
+/// or smithing table. Returns `None` if the result can't be taken.
+pub(super) fn take_result(click: &TakeClick) -> Option<TakeResultEvent> {
+    let result = click.result()?;
+
+    Some(TakeResultEvent {
+        client: click.client,
+        inventory: click.inventory,
+        result: result.clone(),
+        target: take_target(click, result)?,
+        cancelled: false,
+    })
+}
+
+pub(super) fn apply_result_takes(
+    mut pending: ResMut<PendingResultTakes>,
+    mut clients: Query<(
+        &mut Client,
+        &mut Inventory,
+        &mut ClientInventoryState,
+        &mut CursorItem,
+        &OpenInventory,
+    )>,
+    mut windows: Query<&mut Inventory, Without<Client>>,
+) {
+    for event in pending.0.drain(..) {
+        let Ok((mut client, mut player_inventory, mut inv_state, mut cursor_item, open_inventory)) =
+            clients.get_mut(event.client)
+        else {
+            continue;
+        };
+
+        if open_inventory.entity != event.inventory {
+            // The client closed the window in the meantime.
+            continue;
+        }
+
+        let Ok(mut window) = windows.get_mut(event.inventory) else {
+            continue;
+        };
+
+        if event.cancelled || !can_still_take(&event.target, &window, &cursor_item, &event.result) {
+            resync(
+                &mut client,
+                &inv_state,
+                &mut window,
+                &player_inventory,
+                event.target.player_slot_changes(),
+            );
+            continue;
+        }
+
+        give_result(
+            &mut client,
+            event.target,
+            &mut window,
+            &mut player_inventory,
+            &mut inv_state,
+            &mut cursor_item,
+            SlotChangeCause::Click(event.client),
+        );
+
+        // Use up one item from each input.
+        for idx in 0..window.slot_count() {
+            if let Some(count) = window.slot(idx).map(|s| s.count()) {
+                if count > 1 {
+                    window.set_slot_amount(idx, count - 1);
+                } else {
+                    window.set_slot(idx, None);
+                }
+            }
+        }
+    }
+}
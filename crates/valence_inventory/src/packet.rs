@@ -7,9 +7,11 @@ use anyhow::bail;
 use valence_core::ident::Ident;
 use valence_core::item::ItemStack;
 use valence_core::protocol::var_int::VarInt;
-use valence_core::protocol::{packet_id, Decode, Encode, Packet};
+use valence_core::protocol::{packet_id, Decode, Encode, Packet, SharedPacket};
 use valence_core::text::Text;
 
+use crate::recipe::Recipe;
+
 #[derive(Clone, Debug, Encode, Decode, Packet)]
 #[packet(id = packet_id::CLICK_SLOT_C2S)]
 pub struct ClickSlotC2s {
@@ -35,7 +37,7 @@ pub enum ClickMode {
     DoubleClick,
 }
 
-#[derive(Clone, Debug, Encode, Decode)]
+#[derive(Clone, PartialEq, Debug, Encode, Decode)]
 pub struct SlotChange {
     pub idx: i16,
     pub item: Option<ItemStack>,
@@ -231,6 +233,14 @@ pub struct UpdateBeaconC2s {
     pub secondary_effect: Option<VarInt>,
 }
 
+#[derive(Clone, PartialEq, Debug, Encode, Decode, Packet)]
+#[packet(id = packet_id::SYNCHRONIZE_RECIPES_S2C)]
+pub struct SynchronizeRecipesS2c<'a> {
+    pub recipes: Cow<'a, [Recipe]>,
+}
+
+impl SharedPacket for SynchronizeRecipesS2c<'_> {}
+
 #[derive(Clone, PartialEq, Eq, Debug, Packet)]
 #[packet(id = packet_id::UNLOCK_RECIPES_S2C)]
 pub struct UnlockRecipesS2c<'a> {
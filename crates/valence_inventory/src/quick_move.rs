@@ -0,0 +1,401 @@
+//! Shift clicks, which quickly move a stack to another part of a window.
+//!
+//! The slots that a stack is moved to follow vanilla, so the result matches
+//! what the client predicted. Windows of custom UIs can move stacks elsewhere
+//! with a [`QuickMoveRules`] component.
+
+use std::ops::Range;
+
+use bevy_ecs::prelude::*;
+use valence_core::item::ItemStack;
+
+use crate::click::{can_combine, max_count, shrink};
+use crate::equipment::EquipmentSlot;
+use crate::{
+    anvil, crafting, smithing, stonecutter, InventoryKind, InventoryWindow, SlotCategory,
+    OFFHAND_SLOT,
+};
+
+/// The slots of a window that a shift click moves a stack to.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct QuickMoveTarget {
+    /// The window slots that the stack is moved to.
+    pub slots: Range<u16>,
+    /// Whether the slots are filled starting from the last one.
+    pub reverse: bool,
+}
+
+impl QuickMoveTarget {
+    pub fn new(slots: Range<u16>) -> Self {
+        Self {
+            slots,
+            reverse: false,
+        }
+    }
+
+    pub fn reversed(slots: Range<u16>) -> Self {
+        Self {
+            slots,
+            reverse: true,
+        }
+    }
+}
+
+/// Decides where shift clicks move stacks in a window.
+///
+/// Stacks are moved like in vanilla: stacks of the same item in the target
+/// slots are filled up first, and then the rest goes in the first empty slot
+/// that it can be put in. The client predicts vanilla's targets, so it is sent
+/// the actual slots when the targets differ.
+pub trait QuickMove: Send + Sync + 'static {
+    /// Returns where a shift click on slot `idx` of a window moves `stack` to,
+    /// or `None` if the stack stays in its slot. `slots` holds the current
+    /// contents of the window, which change as the stack is moved.
+    fn target(
+        &self,
+        window: &InventoryWindow,
+        slots: &[Option<ItemStack>],
+        idx: u16,
+        stack: &ItemStack,
+    ) -> Option<QuickMoveTarget>;
+}
+
+/// Replaces where shift clicks move stacks in the window of the inventory on
+/// this entity. Put it on a client to change shift clicks in its own
+/// inventory.
+///
+/// ```
+/// # use valence_core::item::ItemStack;
+/// # use valence_inventory::quick_move::{QuickMove, QuickMoveRules, QuickMoveTarget};
+/// # use valence_inventory::InventoryWindow;
+/// /// Shift clicks don't move anything.
+/// struct Locked;
+///
+/// impl QuickMove for Locked {
+///     fn target(
+///         &self,
+///         _window: &InventoryWindow,
+///         _slots: &[Option<ItemStack>],
+///         _idx: u16,
+///         _stack: &ItemStack,
+///     ) -> Option<QuickMoveTarget> {
+///         None
+///     }
+/// }
+///
+/// let rules = QuickMoveRules::new(Locked);
+/// ```
+#[derive(Component)]
+pub struct QuickMoveRules(pub Box<dyn QuickMove>);
+
+impl QuickMoveRules {
+    pub fn new(rules: impl QuickMove) -> Self {
+        Self(Box::new(rules))
+    }
+}
+
+/// Returns the slots of a window that vanilla quickly moves a stack to from a
+/// slot. Returns `None` if the server doesn't work out quick moves for the
+/// window.
+fn vanilla_target(
+    window: &InventoryWindow,
+    slots: &[Option<ItemStack>],
+    idx: u16,
+    stack: &ItemStack,
+) -> Option<QuickMoveTarget> {
+    let Some(open_inventory) = window.open_inventory else {
+        let equipment = EquipmentSlot::for_item(stack.item);
+        let is_empty = |slot: u16| slots[slot as usize].is_none();
+
+        return Some(QuickMoveTarget::new(
+            match SlotCategory::of_player_slot(idx)? {
+                // Crafting results go to the main inventory and hotbar, starting with the end of
+                // the hotbar.
+                SlotCategory::CraftingResult => return Some(QuickMoveTarget::reversed(9..45)),
+                SlotCategory::CraftingGrid | SlotCategory::Armor(_) => 9..45,
+                _ if equipment.armor_slot().is_some_and(is_empty) => {
+                    let slot = equipment.armor_slot().unwrap();
+                    slot..slot + 1
+                }
+                _ if equipment == EquipmentSlot::OffHand && is_empty(OFFHAND_SLOT) => {
+                    OFFHAND_SLOT..OFFHAND_SLOT + 1
+                }
+                SlotCategory::Storage => 36..45,
+                SlotCategory::Hotbar => 9..36,
+                SlotCategory::Offhand => 9..45,
+            },
+        ));
+    };
+
+    match open_inventory.kind() {
+        InventoryKind::Generic9x1
+        | InventoryKind::Generic9x2
+        | InventoryKind::Generic9x3
+        | InventoryKind::Generic9x4
+        | InventoryKind::Generic9x5
+        | InventoryKind::Generic9x6
+        | InventoryKind::Generic3x3
+        | InventoryKind::Hopper
+        | InventoryKind::ShulkerBox => {
+            // Stacks go from the container to the player's inventory starting with the
+            // end of the hotbar, and from the player's inventory to the container.
+            let container_slots = open_inventory.slot_count();
+
+            Some(if idx < container_slots {
+                QuickMoveTarget::reversed(container_slots..window.slot_count())
+            } else {
+                QuickMoveTarget::new(0..container_slots)
+            })
+        }
+        InventoryKind::Crafting
+        | InventoryKind::Anvil
+        | InventoryKind::Stonecutter
+        | InventoryKind::Smithing
+            if idx == result_slot(open_inventory.kind()) =>
+        {
+            // Results go to the player's inventory starting with the end of the hotbar.
+            Some(QuickMoveTarget::reversed(
+                open_inventory.slot_count()..window.slot_count(),
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn result_slot(kind: InventoryKind) -> u16 {
+    match kind {
+        InventoryKind::Anvil => anvil::OUTPUT_SLOT,
+        InventoryKind::Stonecutter => stonecutter::OUTPUT_SLOT,
+        InventoryKind::Smithing => smithing::OUTPUT_SLOT,
+        _ => crafting::RESULT_SLOT,
+    }
+}
+
+/// Returns where a shift click on the result slot `idx` of a window moves the
+/// result to, or `None` if the result can't be shift clicked out. Results are
+/// taken by the server instead of [`quick_move`], but go to the same slots.
+pub(super) fn result_target(
+    window: &InventoryWindow,
+    slots: &[Option<ItemStack>],
+    idx: u16,
+    result: &ItemStack,
+    rules: Option<&dyn QuickMove>,
+) -> Option<QuickMoveTarget> {
+    match rules {
+        Some(rules) => rules.target(window, slots, idx, result),
+        None => vanilla_target(window, slots, idx, result),
+    }
+}
+
+/// Moves as much of a stack as possible to some slots of a window. Stacks of
+/// the same item are filled up first, and then the rest goes in the first
+/// empty slot. Returns what's left of the stack.
+pub(super) fn move_to(
+    slots: &mut [Option<ItemStack>],
+    mut stack: ItemStack,
+    target: QuickMoveTarget,
+    window: &InventoryWindow,
+) -> Option<ItemStack> {
+    let targets = target.slots.start..target.slots.end.min(window.slot_count());
+
+    let order: Vec<u16> = if target.reverse {
+        targets.rev().collect()
+    } else {
+        targets.collect()
+    };
+
+    if stack.item.max_stack() > 1 {
+        for &i in &order {
+            let Some(slot) = &mut slots[i as usize] else {
+                continue;
+            };
+
+            if !can_combine(slot, &stack) {
+                continue;
+            }
+
+            let max = max_count(window, i, &stack);
+            let count = stack.count().min(max.saturating_sub(slot.count()));
+
+            if count == 0 {
+                continue;
+            }
+
+            slot.set_count(slot.count() + count);
+            stack = shrink(&stack, count)?;
+        }
+    }
+
+    for &i in &order {
+        if slots[i as usize].is_some() {
+            continue;
+        }
+
+        let count = stack.count().min(max_count(window, i, &stack));
+
+        if count > 0 {
+            slots[i as usize] = Some(stack.clone().with_count(count));
+            return shrink(&stack, count);
+        }
+    }
+
+    Some(stack)
+}
+
+/// Quickly moves the stack in a slot of a window. Returns `None` if the server
+/// doesn't work out quick moves for the window.
+pub(super) fn quick_move(
+    slots: &mut [Option<ItemStack>],
+    idx: u16,
+    window: &InventoryWindow,
+    rules: Option<&dyn QuickMove>,
+) -> Option<()> {
+    // Like vanilla, the stack is moved again until nothing more can be moved,
+    // since the targets can depend on the slots.
+    while let Some(stack) = slots[idx as usize].clone() {
+        let target = match rules {
+            Some(rules) => match rules.target(window, slots, idx, &stack) {
+                Some(target) => target,
+                None => break,
+            },
+            None => vanilla_target(window, slots, idx, &stack)?,
+        };
+
+        let rest = move_to(slots, stack.clone(), target, window);
+        let moved = rest.as_ref().map_or(true, |r| r.count() < stack.count());
+
+        slots[idx as usize] = rest;
+
+        if !moved {
+            break;
+        }
+    }
+
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_core::item::ItemKind;
+
+    use super::*;
+    use crate::Inventory;
+
+    fn quick_move_vanilla(
+        slots: &mut [Option<ItemStack>],
+        idx: u16,
+        window: &InventoryWindow,
+    ) -> Option<()> {
+        quick_move(slots, idx, window, None)
+    }
+
+    fn slots(window: &InventoryWindow) -> Vec<Option<ItemStack>> {
+        (0..window.slot_count())
+            .map(|i| window.slot(i).cloned())
+            .collect()
+    }
+
+    #[test]
+    fn armor_goes_to_armor_slots() {
+        let mut inventory = Inventory::new(InventoryKind::Player);
+        inventory.set_slot(20, ItemStack::new(ItemKind::IronHelmet, 1, None));
+        inventory.set_slot(21, ItemStack::new(ItemKind::DiamondBoots, 1, None));
+        inventory.set_slot(8, ItemStack::new(ItemKind::LeatherBoots, 1, None));
+        inventory.set_slot(22, ItemStack::new(ItemKind::Shield, 1, None));
+
+        let window = InventoryWindow::new(&inventory, None);
+        let mut result = slots(&window);
+
+        quick_move_vanilla(&mut result, 20, &window).unwrap();
+        quick_move_vanilla(&mut result, 22, &window).unwrap();
+
+        assert_eq!(result[20], None);
+        assert_eq!(
+            result[5],
+            Some(ItemStack::new(ItemKind::IronHelmet, 1, None))
+        );
+        assert_eq!(result[22], None);
+        assert_eq!(
+            result[OFFHAND_SLOT as usize],
+            Some(ItemStack::new(ItemKind::Shield, 1, None))
+        );
+
+        // The boots slot is taken, so the boots go to the hotbar instead.
+        quick_move_vanilla(&mut result, 21, &window).unwrap();
+
+        assert_eq!(result[21], None);
+        assert_eq!(
+            result[36],
+            Some(ItemStack::new(ItemKind::DiamondBoots, 1, None))
+        );
+
+        // Armor is taken off into the main inventory.
+        quick_move_vanilla(&mut result, 8, &window).unwrap();
+
+        assert_eq!(result[8], None);
+        assert_eq!(
+            result[9],
+            Some(ItemStack::new(ItemKind::LeatherBoots, 1, None))
+        );
+    }
+
+    #[test]
+    fn fill_stacks_before_empty_slots() {
+        let mut inventory = Inventory::new(InventoryKind::Player);
+        inventory.set_slot(9, ItemStack::new(ItemKind::Diamond, 40, None));
+        inventory.set_slot(37, ItemStack::new(ItemKind::Diamond, 60, None));
+        inventory.set_slot(40, ItemStack::new(ItemKind::Diamond, 50, None));
+
+        let window = InventoryWindow::new(&inventory, None);
+        let mut result = slots(&window);
+
+        quick_move_vanilla(&mut result, 9, &window).unwrap();
+
+        assert_eq!(result[9], None);
+        assert_eq!(
+            result[36],
+            Some(ItemStack::new(ItemKind::Diamond, 22, None))
+        );
+        assert_eq!(
+            result[37],
+            Some(ItemStack::new(ItemKind::Diamond, 64, None))
+        );
+        assert_eq!(
+            result[40],
+            Some(ItemStack::new(ItemKind::Diamond, 64, None))
+        );
+    }
+
+    #[test]
+    fn move_between_chest_and_player_inventory() {
+        let mut inventory = Inventory::new(InventoryKind::Player);
+        inventory.set_slot(9, ItemStack::new(ItemKind::IronHelmet, 1, None));
+
+        let mut chest = Inventory::new(InventoryKind::Generic9x3);
+        chest.set_slot(0, ItemStack::new(ItemKind::Stone, 64, None));
+        chest.set_slot(1, ItemStack::new(ItemKind::Stone, 10, None));
+
+        let window = InventoryWindow::new(&inventory, Some(&chest));
+        let mut result = slots(&window);
+
+        // Stacks from the chest start at the end of the hotbar.
+        quick_move_vanilla(&mut result, 0, &window).unwrap();
+
+        assert_eq!(result[0], None);
+        assert_eq!(result[62], Some(ItemStack::new(ItemKind::Stone, 64, None)));
+
+        quick_move_vanilla(&mut result, 1, &window).unwrap();
+
+        assert_eq!(result[1], None);
+        assert_eq!(result[61], Some(ItemStack::new(ItemKind::Stone, 10, None)));
+
+        // Armor from the player's inventory goes into the chest.
+        quick_move_vanilla(&mut result, 27, &window).unwrap();
+
+        assert_eq!(result[27], None);
+        assert_eq!(
+            result[0],
+            Some(ItemStack::new(ItemKind::IronHelmet, 1, None))
+        );
+    }
+}
@@ -0,0 +1,722 @@
+//! Recipes and the recipe book.
+//!
+//! The [`RecipeRegistry`] holds the recipes that clients are told about, and
+//! the [`UnlockedRecipes`] component on each client controls which of them
+//! are shown in its recipe book.
+
+use std::collections::BTreeSet;
+use std::io::Write;
+
+use anyhow::{bail, ensure, Context};
+use bevy_ecs::prelude::*;
+use indexmap::IndexMap;
+use serde::{Deserialize, Deserializer};
+use tracing::warn;
+use valence_client::event_loop::PacketEvent;
+use valence_client::Client;
+use valence_core::ident::Ident;
+use valence_core::item::{ItemKind, ItemStack};
+use valence_core::protocol::encode::{EncodedPacket, WritePacket};
+use valence_core::protocol::var_int::VarInt;
+use valence_core::protocol::{Decode, Encode};
+use valence_core::{ident, Server};
+
+use crate::packet::{
+    RecipeBookId, RecipeCategoryOptionsC2s, SynchronizeRecipesS2c, UnlockRecipesS2c,
+    UpdateRecipeBookAction,
+};
+
+/// The recipes that are sent to clients when they join, and again whenever
+/// the registry is changed.
+///
+/// Recipes are only shown in a client's recipe book once they are added to its
+/// [`UnlockedRecipes`].
+#[derive(Resource, Default, Debug)]
+pub struct RecipeRegistry {
+    recipes: IndexMap<Ident<String>, RecipeData>,
+    cached_packet: Option<EncodedPacket>,
+}
+
+impl RecipeRegistry {
+    /// Adds a recipe, returning the recipe it replaced if there was one with
+    /// the same ID.
+    pub fn insert(&mut self, id: impl Into<Ident<String>>, data: RecipeData) -> Option<RecipeData> {
+        self.recipes.insert(id.into(), data)
+    }
+
+    pub fn remove(&mut self, id: Ident<&str>) -> Option<RecipeData> {
+        self.recipes.shift_remove(id.as_str())
+    }
+
+    pub fn get(&self, id: Ident<&str>) -> Option<&RecipeData> {
+        self.recipes.get(id.as_str())
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (Ident<&str>, &RecipeData)> + '_ {
+        self.recipes
+            .iter()
+            .map(|(id, data)| (id.as_str_ident(), data))
+    }
+
+    /// Adds the recipes from the `recipes.json` file written by the extractor,
+    /// which has the vanilla recipes with their item tags resolved.
+    pub fn load_extracted(&mut self, json: &str) -> anyhow::Result<()> {
+        #[derive(Deserialize)]
+        struct ExtractedRecipe {
+            id: Ident<String>,
+            #[serde(flatten)]
+            data: RecipeData,
+        }
+
+        let recipes: Vec<ExtractedRecipe> =
+            serde_json::from_str(json).context("invalid extracted recipes")?;
+
+        for recipe in recipes {
+            self.insert(recipe.id, recipe.data);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the recipes in the stonecutter's list for an input item, in the
+    /// order that the client shows them.
+    pub fn stonecutter_recipes(&self, input: ItemKind) -> Vec<(Ident<&str>, &StonecuttingRecipe)> {
+        let mut recipes: Vec<_> = self
+            .iter()
+            .filter_map(|(id, data)| match data {
+                RecipeData::Stonecutting(recipe) if recipe.ingredient.0.contains(&input) => {
+                    Some((id, recipe))
+                }
+                _ => None,
+            })
+            .collect();
+
+        // This is how the client sorts them.
+        recipes.sort_by_key(|(_, recipe)| recipe.result.item.translation_key());
+
+        recipes
+    }
+
+    fn build_packet(&self) -> SynchronizeRecipesS2c<'static> {
+        SynchronizeRecipesS2c {
+            recipes: self
+                .recipes
+                .iter()
+                .map(|(id, data)| Recipe {
+                    id: id.clone(),
+                    data: data.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A recipe with its ID, as sent to clients.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Recipe {
+    pub id: Ident<String>,
+    pub data: RecipeData,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RecipeData {
+    CraftingShaped(ShapedRecipe),
+    CraftingShapeless(ShapelessRecipe),
+    Smelting(CookingRecipe),
+    Blasting(CookingRecipe),
+    Smoking(CookingRecipe),
+    CampfireCooking(CookingRecipe),
+    Stonecutting(StonecuttingRecipe),
+    SmithingTransform(SmithingTransformRecipe),
+    SmithingTrim(SmithingTrimRecipe),
+}
+
+impl RecipeData {
+    /// The ID of the recipe's serializer, which identifies the type of recipe
+    /// to the client.
+    pub fn type_id(&self) -> Ident<&'static str> {
+        match self {
+            RecipeData::CraftingShaped(_) => ident!("crafting_shaped"),
+            RecipeData::CraftingShapeless(_) => ident!("crafting_shapeless"),
+            RecipeData::Smelting(_) => ident!("smelting"),
+            RecipeData::Blasting(_) => ident!("blasting"),
+            RecipeData::Smoking(_) => ident!("smoking"),
+            RecipeData::CampfireCooking(_) => ident!("campfire_cooking"),
+            RecipeData::Stonecutting(_) => ident!("stonecutting"),
+            RecipeData::SmithingTransform(_) => ident!("smithing_transform"),
+            RecipeData::SmithingTrim(_) => ident!("smithing_trim"),
+        }
+    }
+}
+
+/// A crafting recipe with its ingredients in a fixed pattern.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+pub struct ShapedRecipe {
+    /// Recipes with the same group are shown together in the recipe book.
+    #[serde(default)]
+    pub group: String,
+    pub category: CraftingCategory,
+    pub width: u8,
+    pub height: u8,
+    /// The ingredients in the pattern, row by row. There must be `width *
+    /// height` of them, where empty ingredients are empty spaces.
+    pub ingredients: Vec<Ingredient>,
+    #[serde(deserialize_with = "deserialize_result")]
+    pub result: ItemStack,
+    /// Whether a toast is shown when the recipe is unlocked.
+    #[serde(default = "default_show_notification")]
+    pub show_notification: bool,
+}
+
+/// A crafting recipe with its ingredients in any arrangement.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+pub struct ShapelessRecipe {
+    #[serde(default)]
+    pub group: String,
+    pub category: CraftingCategory,
+    pub ingredients: Vec<Ingredient>,
+    #[serde(deserialize_with = "deserialize_result")]
+    pub result: ItemStack,
+}
+
+/// A furnace, blast furnace, smoker, or campfire recipe.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+pub struct CookingRecipe {
+    #[serde(default)]
+    pub group: String,
+    pub category: CookingCategory,
+    pub ingredient: Ingredient,
+    #[serde(deserialize_with = "deserialize_result")]
+    pub result: ItemStack,
+    pub experience: f32,
+    /// The number of ticks it takes to cook the ingredient.
+    pub cooking_time: i32,
+}
+
+/// A recipe shown in the stonecutter's list when the ingredient is put in it.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+pub struct StonecuttingRecipe {
+    #[serde(default)]
+    pub group: String,
+    pub ingredient: Ingredient,
+    #[serde(deserialize_with = "deserialize_result")]
+    pub result: ItemStack,
+}
+
+/// A smithing table recipe that turns the base item into the result, such as
+/// netherite upgrades.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+pub struct SmithingTransformRecipe {
+    pub template: Ingredient,
+    pub base: Ingredient,
+    pub addition: Ingredient,
+    #[serde(deserialize_with = "deserialize_result")]
+    pub result: ItemStack,
+}
+
+/// A smithing table recipe that adds an armor trim to the base item.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+pub struct SmithingTrimRecipe {
+    pub template: Ingredient,
+    pub base: Ingredient,
+    pub addition: Ingredient,
+}
+
+/// The tab of the crafting recipe book that a recipe is shown in.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Encode, Decode, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CraftingCategory {
+    Building,
+    Redstone,
+    Equipment,
+    Misc,
+}
+
+/// The tab of a cooking recipe book that a recipe is shown in.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Encode, Decode, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CookingCategory {
+    Food,
+    Blocks,
+    Misc,
+}
+
+/// The items that can be used for one ingredient of a recipe. An empty
+/// ingredient matches empty slots.
+#[derive(Clone, PartialEq, Eq, Default, Debug, Deserialize)]
+#[serde(transparent)]
+pub struct Ingredient(pub Vec<ItemKind>);
+
+fn default_show_notification() -> bool {
+    true
+}
+
+fn deserialize_result<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ItemStack, D::Error> {
+    #[derive(Deserialize)]
+    struct RecipeResult {
+        item: ItemKind,
+        #[serde(default = "default_count")]
+        count: u8,
+    }
+
+    fn default_count() -> u8 {
+        1
+    }
+
+    let result = RecipeResult::deserialize(deserializer)?;
+
+    Ok(ItemStack::new(result.item, result.count, None))
+}
+
+impl Encode for Ingredient {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        VarInt(self.0.len() as i32).encode(&mut w)?;
+
+        for &item in &self.0 {
+            Some(&ItemStack::new(item, 1, None)).encode(&mut w)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Decode<'_> for Ingredient {
+    fn decode(r: &mut &[u8]) -> anyhow::Result<Self> {
+        let len = VarInt::decode(r)?.0;
+        ensure!(
+            len >= 0,
+            "attempt to decode ingredient with negative length"
+        );
+
+        let mut items = vec![];
+
+        for _ in 0..len {
+            if let Some(stack) = Option::<ItemStack>::decode(r)? {
+                items.push(stack.item);
+            }
+        }
+
+        Ok(Self(items))
+    }
+}
+
+impl Encode for Recipe {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        self.data.type_id().encode(&mut w)?;
+        self.id.encode(&mut w)?;
+
+        match &self.data {
+            RecipeData::CraftingShaped(recipe) => {
+                ensure!(
+                    recipe.ingredients.len() == recipe.width as usize * recipe.height as usize,
+                    "shaped recipe has {} ingredients, but its size is {}x{}",
+                    recipe.ingredients.len(),
+                    recipe.width,
+                    recipe.height
+                );
+
+                VarInt(recipe.width.into()).encode(&mut w)?;
+                VarInt(recipe.height.into()).encode(&mut w)?;
+                recipe.group.encode(&mut w)?;
+                recipe.category.encode(&mut w)?;
+
+                for ingredient in &recipe.ingredients {
+                    ingredient.encode(&mut w)?;
+                }
+
+                Some(&recipe.result).encode(&mut w)?;
+                recipe.show_notification.encode(w)
+            }
+            RecipeData::CraftingShapeless(recipe) => {
+                recipe.group.encode(&mut w)?;
+                recipe.category.encode(&mut w)?;
+                recipe.ingredients.encode(&mut w)?;
+                Some(&recipe.result).encode(w)
+            }
+            RecipeData::Smelting(recipe)
+            | RecipeData::Blasting(recipe)
+            | RecipeData::Smoking(recipe)
+            | RecipeData::CampfireCooking(recipe) => {
+                recipe.group.encode(&mut w)?;
+                recipe.category.encode(&mut w)?;
+                recipe.ingredient.encode(&mut w)?;
+                Some(&recipe.result).encode(&mut w)?;
+                recipe.experience.encode(&mut w)?;
+                VarInt(recipe.cooking_time).encode(w)
+            }
+            RecipeData::Stonecutting(recipe) => {
+                recipe.group.encode(&mut w)?;
+                recipe.ingredient.encode(&mut w)?;
+                Some(&recipe.result).encode(w)
+            }
+            RecipeData::SmithingTransform(recipe) => {
+                recipe.template.encode(&mut w)?;
+                recipe.base.encode(&mut w)?;
+                recipe.addition.encode(&mut w)?;
+                Some(&recipe.result).encode(w)
+            }
+            RecipeData::SmithingTrim(recipe) => {
+                recipe.template.encode(&mut w)?;
+                recipe.base.encode(&mut w)?;
+                recipe.addition.encode(w)
+            }
+        }
+    }
+}
+
+impl Decode<'_> for Recipe {
+    fn decode(r: &mut &[u8]) -> anyhow::Result<Self> {
+        let type_id = Ident::<String>::decode(r)?;
+        let id = Ident::<String>::decode(r)?;
+
+        let decode_result =
+            |r: &mut &[u8]| Option::<ItemStack>::decode(r)?.context("missing recipe result");
+
+        let decode_cooking = |r: &mut &[u8]| -> anyhow::Result<CookingRecipe> {
+            Ok(CookingRecipe {
+                group: String::decode(r)?,
+                category: CookingCategory::decode(r)?,
+                ingredient: Ingredient::decode(r)?,
+                result: decode_result(r)?,
+                experience: f32::decode(r)?,
+                cooking_time: VarInt::decode(r)?.0,
+            })
+        };
+
+        let data = match type_id.path() {
+            "crafting_shaped" => {
+                let width = VarInt::decode(r)?.0.try_into()?;
+                let height = VarInt::decode(r)?.0.try_into()?;
+                let group = String::decode(r)?;
+                let category = CraftingCategory::decode(r)?;
+
+                let mut ingredients = vec![];
+                for _ in 0..width as usize * height as usize {
+                    ingredients.push(Ingredient::decode(r)?);
+                }
+
+                RecipeData::CraftingShaped(ShapedRecipe {
+                    group,
+                    category,
+                    width,
+                    height,
+                    ingredients,
+                    result: decode_result(r)?,
+                    show_notification: bool::decode(r)?,
+                })
+            }
+            "crafting_shapeless" => RecipeData::CraftingShapeless(ShapelessRecipe {
+                group: String::decode(r)?,
+                category: CraftingCategory::decode(r)?,
+                ingredients: Vec::decode(r)?,
+                result: decode_result(r)?,
+            }),
+            "smelting" => RecipeData::Smelting(decode_cooking(r)?),
+            "blasting" => RecipeData::Blasting(decode_cooking(r)?),
+            "smoking" => RecipeData::Smoking(decode_cooking(r)?),
+            "campfire_cooking" => RecipeData::CampfireCooking(decode_cooking(r)?),
+            "stonecutting" => RecipeData::Stonecutting(StonecuttingRecipe {
+                group: String::decode(r)?,
+                ingredient: Ingredient::decode(r)?,
+                result: decode_result(r)?,
+            }),
+            "smithing_transform" => RecipeData::SmithingTransform(SmithingTransformRecipe {
+                template: Ingredient::decode(r)?,
+                base: Ingredient::decode(r)?,
+                addition: Ingredient::decode(r)?,
+                result: decode_result(r)?,
+            }),
+            "smithing_trim" => RecipeData::SmithingTrim(SmithingTrimRecipe {
+                template: Ingredient::decode(r)?,
+                base: Ingredient::decode(r)?,
+                addition: Ingredient::decode(r)?,
+            }),
+            _ => bail!("unsupported recipe type of \"{type_id}\""),
+        };
+
+        ensure!(
+            type_id.namespace() == "minecraft",
+            "unsupported recipe type of \"{type_id}\""
+        );
+
+        Ok(Self { id, data })
+    }
+}
+
+/// The recipes in a client's recipe book.
+///
+/// Changes to this component are sent to the client. Recipes that aren't in the
+/// [`RecipeRegistry`] are ignored by the client.
+#[derive(Component, Clone, Default, Debug)]
+pub struct UnlockedRecipes {
+    recipes: BTreeSet<Ident<String>>,
+    /// The recipes that the client was last told about, or `None` if the
+    /// recipe book hasn't been sent yet.
+    sent: Option<BTreeSet<Ident<String>>>,
+    book_options: [RecipeBookOptions; 4],
+}
+
+/// Whether a recipe book is open and whether it only shows the recipes that
+/// can be made with the items at hand.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct RecipeBookOptions {
+    pub open: bool,
+    pub filter_active: bool,
+}
+
+impl UnlockedRecipes {
+    /// Adds a recipe to the recipe book. Returns `false` if it was already
+    /// there.
+    pub fn unlock(&mut self, id: impl Into<Ident<String>>) -> bool {
+        self.recipes.insert(id.into())
+    }
+
+    /// Removes a recipe from the recipe book. Returns `false` if it wasn't
+    /// there.
+    pub fn lock(&mut self, id: Ident<&str>) -> bool {
+        self.recipes.remove(id.as_str())
+    }
+
+    pub fn contains(&self, id: Ident<&str>) -> bool {
+        self.recipes.contains(id.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Ident<&str>> + '_ {
+        self.recipes.iter().map(|id| id.as_str_ident())
+    }
+
+    /// The options of one of the recipe books. These are kept up to date with
+    /// the client.
+    pub fn book_options(&self, book: RecipeBookId) -> RecipeBookOptions {
+        self.book_options[book as usize]
+    }
+
+    pub fn set_book_options(&mut self, book: RecipeBookId, options: RecipeBookOptions) {
+        self.book_options[book as usize] = options;
+    }
+
+    fn packet(
+        &self,
+        action: UpdateRecipeBookAction<'static>,
+        recipe_ids: Vec<Ident<String>>,
+    ) -> UnlockRecipesS2c<'static> {
+        let [crafting, smelting, blast_furnace, smoker] = self.book_options;
+
+        UnlockRecipesS2c {
+            action,
+            crafting_recipe_book_open: crafting.open,
+            crafting_recipe_book_filter_active: crafting.filter_active,
+            smelting_recipe_book_open: smelting.open,
+            smelting_recipe_book_filter_active: smelting.filter_active,
+            blast_furnace_recipe_book_open: blast_furnace.open,
+            blast_furnace_recipe_book_filter_active: blast_furnace.filter_active,
+            smoker_recipe_book_open: smoker.open,
+            smoker_recipe_book_filter_active: smoker.filter_active,
+            recipe_ids: recipe_ids.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// Sends the recipes to new clients, or to all clients if the registry
+/// changed.
+pub(super) fn send_recipes(
+    server: Res<Server>,
+    mut registry: ResMut<RecipeRegistry>,
+    mut clients: Query<&mut Client>,
+) {
+    let changed = registry.is_changed();
+
+    if changed {
+        let registry = registry.bypass_change_detection();
+
+        registry.cached_packet = match server.encode_shared(&registry.build_packet()) {
+            Ok(packet) => Some(packet),
+            Err(e) => {
+                warn!("failed to encode recipes: {e:#}");
+                None
+            }
+        };
+    }
+
+    let Some(packet) = &registry.cached_packet else {
+        return;
+    };
+
+    for mut client in &mut clients {
+        if changed || client.is_added() {
+            client.write_encoded(packet);
+        }
+    }
+}
+
+pub(super) fn update_unlocked_recipes(
+    mut clients: Query<(&mut Client, &mut UnlockedRecipes), Changed<UnlockedRecipes>>,
+) {
+    for (mut client, mut unlocked) in &mut clients {
+        let unlocked = unlocked.bypass_change_detection();
+
+        let Some(sent) = &unlocked.sent else {
+            let recipes: Vec<_> = unlocked.recipes.iter().cloned().collect();
+
+            client.write_packet(
+                &unlocked.packet(UpdateRecipeBookAction::Init { recipe_ids: vec![] }, recipes),
+            );
+
+            unlocked.sent = Some(unlocked.recipes.clone());
+            continue;
+        };
+
+        let added: Vec<_> = unlocked.recipes.difference(sent).cloned().collect();
+        let removed: Vec<_> = sent.difference(&unlocked.recipes).cloned().collect();
+
+        if !removed.is_empty() {
+            client.write_packet(&unlocked.packet(UpdateRecipeBookAction::Remove, removed));
+        }
+
+        // This is also sent when nothing was added so that the book options are
+        // updated.
+        if !added.is_empty() || sent == &unlocked.recipes {
+            client.write_packet(&unlocked.packet(UpdateRecipeBookAction::Add, added));
+        }
+
+        unlocked.sent = Some(unlocked.recipes.clone());
+    }
+}
+
+/// Keeps track of the recipe book options that the client changes.
+pub(super) fn handle_recipe_category_options(
+    mut packets: EventReader<PacketEvent>,
+    mut clients: Query<&mut UnlockedRecipes>,
+) {
+    for packet in packets.iter() {
+        if let Some(pkt) = packet.decode::<RecipeCategoryOptionsC2s>() {
+            if let Ok(mut unlocked) = clients.get_mut(packet.client) {
+                // The client already knows about this change.
+                unlocked.bypass_change_detection().set_book_options(
+                    pkt.book_id,
+                    RecipeBookOptions {
+                        open: pkt.book_open,
+                        filter_active: pkt.filter_active,
+                    },
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recipe_round_trip() {
+        let recipes = [
+            Recipe {
+                id: ident!("oak_planks").into(),
+                data: RecipeData::CraftingShapeless(ShapelessRecipe {
+                    group: "planks".into(),
+                    category: CraftingCategory::Building,
+                    ingredients: vec![Ingredient(vec![ItemKind::OakLog, ItemKind::OakWood])],
+                    result: ItemStack::new(ItemKind::OakPlanks, 4, None),
+                }),
+            },
+            Recipe {
+                id: ident!("my_game:magic_stick").into(),
+                data: RecipeData::CraftingShaped(ShapedRecipe {
+                    group: String::new(),
+                    category: CraftingCategory::Equipment,
+                    width: 1,
+                    height: 3,
+                    ingredients: vec![
+                        Ingredient(vec![ItemKind::Diamond]),
+                        Ingredient::default(),
+                        Ingredient(vec![ItemKind::Stick]),
+                    ],
+                    result: ItemStack::new(ItemKind::BlazeRod, 1, None),
+                    show_notification: false,
+                }),
+            },
+            Recipe {
+                id: ident!("iron_ingot_from_blasting_raw_iron").into(),
+                data: RecipeData::Blasting(CookingRecipe {
+                    group: "iron_ingot".into(),
+                    category: CookingCategory::Misc,
+                    ingredient: Ingredient(vec![ItemKind::RawIron]),
+                    result: ItemStack::new(ItemKind::IronIngot, 1, None),
+                    experience: 0.7,
+                    cooking_time: 100,
+                }),
+            },
+        ];
+
+        for recipe in recipes {
+            let mut buf = vec![];
+            recipe.encode(&mut buf).unwrap();
+
+            let mut r = buf.as_slice();
+            assert_eq!(Recipe::decode(&mut r).unwrap(), recipe);
+            assert!(r.is_empty());
+        }
+    }
+
+    #[test]
+    fn load_extracted_recipes() {
+        let json = r#"[
+            {
+                "id": "minecraft:oak_planks",
+                "type": "crafting_shapeless",
+                "group": "planks",
+                "category": "building",
+                "ingredients": [["oak_log", "oak_wood"]],
+                "result": { "item": "oak_planks", "count": 4 }
+            },
+            {
+                "id": "minecraft:stick",
+                "type": "crafting_shaped",
+                "group": "sticks",
+                "category": "misc",
+                "width": 1,
+                "height": 2,
+                "ingredients": [["oak_planks"], ["oak_planks"]],
+                "result": { "item": "stick", "count": 4 },
+                "show_notification": true
+            },
+            {
+                "id": "minecraft:cooked_beef",
+                "type": "smelting",
+                "category": "food",
+                "ingredient": ["beef"],
+                "result": { "item": "cooked_beef" },
+                "experience": 0.35,
+                "cooking_time": 200
+            }
+        ]"#;
+
+        let mut registry = RecipeRegistry::default();
+        registry.load_extracted(json).unwrap();
+
+        assert_eq!(registry.iter().len(), 3);
+        assert_eq!(
+            registry.get(ident!("cooked_beef")),
+            Some(&RecipeData::Smelting(CookingRecipe {
+                group: String::new(),
+                category: CookingCategory::Food,
+                ingredient: Ingredient(vec![ItemKind::Beef]),
+                result: ItemStack::new(ItemKind::CookedBeef, 1, None),
+                experience: 0.35,
+                cooking_time: 200,
+            }))
+        );
+
+        let Some(RecipeData::CraftingShaped(stick)) = registry.get(ident!("stick")) else {
+            panic!("expected a shaped recipe");
+        };
+        assert_eq!(stick.ingredients.len(), 2);
+        assert_eq!(stick.result, ItemStack::new(ItemKind::Stick, 4, None));
+
+        assert!(registry
+            .load_extracted(r#"[{ "id": "a", "type": "smithing" }]"#)
+            .is_err());
+    }
+}
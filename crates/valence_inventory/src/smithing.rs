@@ -0,0 +1,43 @@
+//! Smithing table windows.
+//!
+//! The client only lets items that are used in the synced smithing recipes be
+//! put in the template, base, and addition slots. The server should put the
+//! result in the output slot, for instance with [`transform_result`]. Taking
+//! the result is handled through [`PendingResultTakes`].
+//!
+//! [`PendingResultTakes`]: crate::output::PendingResultTakes
+
+use valence_core::item::ItemStack;
+
+use crate::recipe::{RecipeData, RecipeRegistry};
+use crate::Inventory;
+
+pub const TEMPLATE_SLOT: u16 = 0;
+pub const BASE_SLOT: u16 = 1;
+pub const ADDITION_SLOT: u16 = 2;
+pub const OUTPUT_SLOT: u16 = 3;
+
+/// Returns the result of the smithing transform recipe that matches the items
+/// in a smithing table. Like in vanilla, the result keeps the NBT of the base
+/// item.
+pub fn transform_result(
+    registry: &RecipeRegistry,
+    smithing_table: &Inventory,
+) -> Option<ItemStack> {
+    let template = smithing_table.slot(TEMPLATE_SLOT)?;
+    let base = smithing_table.slot(BASE_SLOT)?;
+    let addition = smithing_table.slot(ADDITION_SLOT)?;
+
+    registry.iter().find_map(|(_, data)| match data {
+        RecipeData::SmithingTransform(recipe)
+            if recipe.template.0.contains(&template.item)
+                && recipe.base.0.contains(&base.item)
+                && recipe.addition.0.contains(&addition.item) =>
+        {
+            let mut result = recipe.result.clone();
+            result.nbt = base.nbt.clone();
+            Some(result)
+        }
+        _ => None,
+    })
+}
@@ -0,0 +1,94 @@
+//! Stonecutter windows.
+//!
+//! The client lists the [`StonecuttingRecipe`]s in the [`RecipeRegistry`] that
+//! match the input item. When one is selected, a [`StonecutterSelectEvent`] is
+//! sent, after which the server should put the result in the output slot.
+//! Taking the result is handled through [`PendingResultTakes`].
+//!
+//! [`StonecuttingRecipe`]: crate::recipe::StonecuttingRecipe
+//! [`PendingResultTakes`]: crate::output::PendingResultTakes
+
+use bevy_ecs::prelude::*;
+use tracing::debug;
+use valence_client::event_loop::PacketEvent;
+use valence_core::ident::Ident;
+use valence_core::item::ItemStack;
+
+use crate::packet::ButtonClickC2s;
+use crate::recipe::RecipeRegistry;
+use crate::{ClientInventoryState, ContainerProperties, Inventory, InventoryKind, OpenInventory};
+
+pub const INPUT_SLOT: u16 = 0;
+pub const OUTPUT_SLOT: u16 = 1;
+
+/// Sent when a client selects a recipe in a stonecutter.
+#[derive(Event, Clone, PartialEq, Debug)]
+pub struct StonecutterSelectEvent {
+    pub client: Entity,
+    /// The entity with the stonecutter's [`Inventory`].
+    pub inventory: Entity,
+    /// The position of the recipe in the list.
+    pub index: u8,
+    pub recipe: Ident<String>,
+    pub result: ItemStack,
+}
+
+pub(super) fn handle_button_click(
+    mut packets: EventReader<PacketEvent>,
+    clients: Query<(&OpenInventory, &ClientInventoryState)>,
+    mut windows: Query<(&Inventory, Option<&mut ContainerProperties>)>,
+    registry: Res<RecipeRegistry>,
+    mut events: EventWriter<StonecutterSelectEvent>,
+) {
+    for packet in packets.iter() {
+        if let Some(pkt) = packet.decode::<ButtonClickC2s>() {
+            let Ok((open_inventory, inv_state)) = clients.get(packet.client) else {
+                continue;
+            };
+
+            if pkt.window_id as u8 != inv_state.window_id {
+                continue;
+            }
+
+            let Ok((stonecutter, properties)) = windows.get_mut(open_inventory.entity) else {
+                continue;
+            };
+
+            if stonecutter.kind != InventoryKind::Stonecutter {
+                // Buttons in other windows are not handled here.
+                continue;
+            }
+
+            let Some(input) = stonecutter.slot(INPUT_SLOT) else {
+                continue;
+            };
+
+            let recipes = registry.stonecutter_recipes(input.item);
+
+            let Some((id, recipe)) = usize::try_from(pkt.button_id)
+                .ok()
+                .and_then(|idx| recipes.get(idx))
+            else {
+                debug!(
+                    "client {:?} selected stonecutter recipe {} out of {}",
+                    packet.client,
+                    pkt.button_id,
+                    recipes.len()
+                );
+                continue;
+            };
+
+            if let Some(mut properties) = properties {
+                properties.set(0, pkt.button_id.into());
+            }
+
+            events.send(StonecutterSelectEvent {
+                client: packet.client,
+                inventory: open_inventory.entity,
+                index: pkt.button_id as u8,
+                recipe: id.to_string_ident(),
+                result: recipe.result.clone(),
+            });
+        }
+    }
+}
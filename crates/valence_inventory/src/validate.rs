@@ -1,8 +1,12 @@
 use anyhow::{bail, ensure};
 use valence_core::item::ItemStack;
 
-use super::{CursorItem, Inventory, InventoryWindow, PLAYER_INVENTORY_MAIN_SLOTS_COUNT};
+use super::{
+    CursorItem, Inventory, InventorySettings, InventoryWindow, OFFHAND_SLOT,
+    PLAYER_INVENTORY_MAIN_SLOTS_COUNT,
+};
 use crate::packet::{ClickMode, ClickSlotC2s};
+use crate::{bundle, crafting, output};
 
 /// Validates a click slot packet enforcing that all fields are valid.
 pub(super) fn validate_click_slot_packet(
@@ -23,46 +27,61 @@ pub(super) fn validate_click_slot_packet(
         None => player_inventory.slot_count(),
     };
 
-    // check all slot ids and item counts are valid
+    // check all slot ids are valid
     ensure!(
-        packet.slot_changes.iter().all(|s| {
-            if !(0..=max_slot).contains(&(s.idx as u16)) {
-                return false;
-            }
-            if let Some(slot) = s.item.as_ref() {
-                let max_stack_size = slot
-                    .item
-                    .max_stack()
-                    .max(slot.count())
-                    .min(ItemStack::STACK_MAX);
-                if !(1..=max_stack_size).contains(&slot.count()) {
-                    return false;
-                }
-            }
-
-            true
-        }),
-        "invalid slot ids or item counts"
+        packet
+            .slot_changes
+            .iter()
+            .all(|s| (0..max_slot).contains(&(s.idx as u16))),
+        "invalid slot ids"
     );
 
-    // check carried item count is valid
-    if let Some(carried_item) = &packet.carried_item {
-        let max_stack_size = carried_item
+    let window = InventoryWindow {
+        player_inventory,
+        open_inventory,
+    };
+
+    // Stacks can only be over their max stack size if one of the stacks that
+    // the click touches already was.
+    let overfull_count = packet
+        .slot_changes
+        .iter()
+        .filter_map(|s| window.slot(s.idx as u16))
+        .chain(cursor_item.0.as_ref())
+        .map(|s| s.count())
+        .max()
+        .unwrap_or(0);
+
+    let is_valid_count = |stack: &ItemStack| {
+        let max_stack_size = stack
             .item
             .max_stack()
-            .max(carried_item.count())
+            .max(overfull_count)
             .min(ItemStack::STACK_MAX);
-        ensure!(
-            (1..=max_stack_size).contains(&carried_item.count()),
-            "invalid carried item count"
-        );
+
+        (1..=max_stack_size).contains(&stack.count())
+    };
+
+    // check item counts are valid
+    ensure!(
+        packet
+            .slot_changes
+            .iter()
+            .filter_map(|s| s.item.as_ref())
+            .all(is_valid_count),
+        "invalid item counts"
+    );
+
+    // check carried item count is valid
+    if let Some(carried_item) = &packet.carried_item {
+        ensure!(is_valid_count(carried_item), "invalid carried item count");
     }
 
     match packet.mode {
         ClickMode::Click => {
             ensure!((0..=1).contains(&packet.button), "invalid button");
             ensure!(
-                (0..=max_slot).contains(&(packet.slot_idx as u16)) || packet.slot_idx == -999,
+                (0..max_slot).contains(&(packet.slot_idx as u16)) || packet.slot_idx == -999,
                 "invalid slot index"
             )
         }
@@ -73,7 +92,7 @@ pub(super) fn validate_click_slot_packet(
                 "carried item must be empty for a hotbar swap"
             );
             ensure!(
-                (0..=max_slot).contains(&(packet.slot_idx as u16)),
+                (0..max_slot).contains(&(packet.slot_idx as u16)),
                 "invalid slot index"
             )
         }
@@ -87,7 +106,11 @@ pub(super) fn validate_click_slot_packet(
         ClickMode::CreativeMiddleClick => {
             ensure!(packet.button == 2, "invalid button");
             ensure!(
-                (0..=max_slot).contains(&(packet.slot_idx as u16)),
+                packet.slot_changes.is_empty(),
+                "slot modifications must be empty for a middle click"
+            );
+            ensure!(
+                (0..max_slot).contains(&(packet.slot_idx as u16)),
                 "invalid slot index"
             )
         }
@@ -98,7 +121,7 @@ pub(super) fn validate_click_slot_packet(
                 "carried item must be empty for an item drop"
             );
             ensure!(
-                (0..=max_slot).contains(&(packet.slot_idx as u16)),
+                (0..max_slot).contains(&(packet.slot_idx as u16)),
                 "invalid slot index"
             )
         }
@@ -108,7 +131,7 @@ pub(super) fn validate_click_slot_packet(
                 "invalid button"
             );
             ensure!(
-                (0..=max_slot).contains(&(packet.slot_idx as u16)) || packet.slot_idx == -999,
+                (0..max_slot).contains(&(packet.slot_idx as u16)) || packet.slot_idx == -999,
                 "invalid slot index"
             )
         }
@@ -117,11 +140,6 @@ pub(super) fn validate_click_slot_packet(
 
     // Check that items aren't being duplicated, i.e. conservation of mass.
 
-    let window = InventoryWindow {
-        player_inventory,
-        open_inventory,
-    };
-
     match packet.mode {
         ClickMode::Click => {
             if packet.slot_idx == -999 {
@@ -148,12 +166,22 @@ pub(super) fn validate_click_slot_packet(
                     expected_delta,
                     count_deltas
                 );
+            } else if bundle::is_bundle_click(packet, &window, cursor_item) {
+                // The result of moving items in or out of a bundle is worked
+                // out by the server, so the changes don't need
+                // to be checked.
+            } else if crafting::is_take_result(packet, &window) {
+                // Crafting results are also worked out by the server.
             } else {
                 ensure!(
                     packet.slot_changes.len() == 1,
                     "click must modify one slot, got {}",
                     packet.slot_changes.len()
                 );
+                ensure!(
+                    packet.slot_changes[0].idx == packet.slot_idx,
+                    "click must modify the clicked slot"
+                );
 
                 let old_slot = window.slot(packet.slot_changes[0].idx as u16);
                 // TODO: make sure NBT is the same.
@@ -187,13 +215,15 @@ pub(super) fn validate_click_slot_packet(
                 }
             }
         }
+        ClickMode::ShiftClick if crafting::is_take_result(packet, &window) => {
+            // Shift clicking a crafting result crafts as many times as possible, which
+            // is worked out by the server.
+        }
+        ClickMode::ShiftClick if packet.slot_changes.is_empty() => {
+            // Nothing moves when there is nowhere to move the stack to.
+        }
         ClickMode::ShiftClick => {
-            ensure!(
-                (2..=3).contains(&packet.slot_changes.len()),
-                "shift click must modify 2 or 3 slots, got {}",
-                packet.slot_changes.len()
-            );
-
+            // A stack can be spread over any number of slots that it fills up.
             let count_deltas = calculate_net_item_delta(packet, &window, cursor_item);
             ensure!(
                 count_deltas == 0,
@@ -206,9 +236,10 @@ pub(super) fn validate_click_slot_packet(
                 .iter()
                 .filter_map(|s| s.item.as_ref())
                 .next()
-                .map(|s| s.item) else {
-                    bail!("shift click must move an item");
-                };
+                .map(|s| s.item)
+            else {
+                bail!("shift click must move an item");
+            };
 
             let Some(old_slot_kind) = window.slot(packet.slot_idx as u16).map(|s| s.item) else {
                 bail!("shift click must move an item");
@@ -227,8 +258,41 @@ pub(super) fn validate_click_slot_packet(
                     .all(|s| s.item == item_kind),
                 "shift click must move the same item kind"
             );
+
+            // assert that items are only moved into empty slots or onto the same kind
+            ensure!(
+                packet
+                    .slot_changes
+                    .iter()
+                    .filter_map(|s| window.slot(s.idx as u16))
+                    .all(|s| s.item == item_kind),
+                "shift click must not replace other items"
+            );
         }
 
+        ClickMode::Hotbar if packet.button == 40 && open_inventory.is_some() => {
+            // The offhand is not part of the open window, so only the clicked slot
+            // changes, to the item that was in the offhand.
+            ensure!(
+                (0..max_slot).contains(&(packet.slot_idx as u16)),
+                "invalid slot index"
+            );
+
+            let offhand = player_inventory.slot(OFFHAND_SLOT);
+
+            match packet.slot_changes.as_slice() {
+                // Nothing changes if both slots hold the same item.
+                [] => ensure!(
+                    window.slot(packet.slot_idx as u16) == offhand,
+                    "swapped items must match"
+                ),
+                [change] => ensure!(
+                    change.idx == packet.slot_idx && change.item.as_ref() == offhand,
+                    "swapped items must match"
+                ),
+                changes => bail!("offhand swap must modify one slot, got {}", changes.len()),
+            }
+        }
         ClickMode::Hotbar => {
             ensure!(
                 packet.slot_changes.len() == 2,
@@ -260,6 +324,14 @@ pub(super) fn validate_click_slot_packet(
         }
         ClickMode::CreativeMiddleClick => {}
         ClickMode::DropKey => {
+            ensure!(
+                !crafting::is_result_slot(&window, packet.slot_idx),
+                "crafting results can't be dropped"
+            );
+            ensure!(
+                !output::is_output_slot(&window, packet.slot_idx),
+                "results can't be dropped from output slots"
+            );
             ensure!(
                 packet.slot_changes.len() == 1,
                 "drop key must modify exactly one slot"
@@ -296,14 +368,9 @@ pub(super) fn validate_click_slot_packet(
             );
         }
         ClickMode::Drag => {
-            if matches!(packet.button, 2 | 6 | 10) {
-                let count_deltas = calculate_net_item_delta(packet, &window, cursor_item);
-                ensure!(
-                    count_deltas == 0,
-                    "invalid item delta: expected 0, got {}",
-                    count_deltas
-                );
-            } else {
+            // The result of a drag is worked out by the server when it ends, so the changes
+            // in the last packet don't need to be checked.
+            if !matches!(packet.button, 2 | 6 | 10) {
                 ensure!(packet.slot_changes.is_empty() && packet.carried_item == cursor_item.0);
             }
         }
@@ -314,6 +381,22 @@ pub(super) fn validate_click_slot_packet(
                 "invalid item delta: expected 0, got {}",
                 count_deltas
             );
+
+            // assert that only items of the cursor's kind are gathered
+            let item_kind = packet.carried_item.as_ref().map(|s| s.item);
+            ensure!(
+                cursor_item
+                    .0
+                    .as_ref()
+                    .map_or(true, |s| Some(s.item) == item_kind)
+                    && packet
+                        .slot_changes
+                        .iter()
+                        .flat_map(|s| [window.slot(s.idx as u16), s.item.as_ref()])
+                        .flatten()
+                        .all(|s| Some(s.item) == item_kind),
+                "double click must gather the same item kind"
+            );
         }
     }
 
@@ -354,6 +437,40 @@ fn calculate_net_item_delta(
     net_item_delta
 }
 
+/// Validates the item that a client in creative mode wants to put in a slot.
+///
+/// Stacks over the item's max stack size are shrunk to fit. Item NBT over the
+/// size limit in the settings is either stripped or makes the item invalid.
+pub(super) fn validate_creative_item(
+    item: Option<ItemStack>,
+    settings: &InventorySettings,
+) -> anyhow::Result<Option<ItemStack>> {
+    let Some(mut stack) = item else {
+        return Ok(None);
+    };
+
+    let max_stack = stack.item.max_stack();
+    if stack.count() > max_stack {
+        stack.set_count(max_stack);
+    }
+
+    if let Some(nbt) = &stack.nbt {
+        let size = nbt.written_size("");
+
+        if size > settings.max_creative_nbt_size {
+            ensure!(
+                settings.strip_oversized_nbt,
+                "item NBT is too large: {size} bytes, but the limit is {} bytes",
+                settings.max_creative_nbt_size
+            );
+
+            stack.nbt = None;
+        }
+    }
+
+    Ok(Some(stack))
+}
+
 #[cfg(test)]
 mod tests {
     use valence_core::item::{ItemKind, ItemStack};
@@ -745,4 +862,79 @@ mod tests {
         validate_click_slot_packet(&packet, &player_inventory, None, &cursor_item)
             .expect("packet should be valid");
     }
+
+    #[test]
+    fn disallow_slot_past_end_of_window() {
+        let player_inventory = Inventory::new(InventoryKind::Player);
+        let inventory = Inventory::new(InventoryKind::Generic9x1);
+        let cursor_item = CursorItem::default();
+
+        let max_slot = inventory.slot_count() + PLAYER_INVENTORY_MAIN_SLOTS_COUNT;
+
+        let packet = ClickSlotC2s {
+            window_id: 1,
+            state_id: VarInt(0),
+            slot_idx: max_slot as i16,
+            button: 0,
+            mode: ClickMode::Click,
+            slot_changes: vec![SlotChange {
+                idx: max_slot as i16,
+                item: None,
+            }],
+            carried_item: None,
+        };
+
+        validate_click_slot_packet(&packet, &player_inventory, Some(&inventory), &cursor_item)
+            .expect_err("slot past the end of the window should be invalid");
+    }
+
+    #[test]
+    fn disallow_shift_click_replacing_items() {
+        let mut player_inventory = Inventory::new(InventoryKind::Player);
+        player_inventory.set_slot(9, ItemStack::new(ItemKind::Diamond, 2, None));
+        player_inventory.set_slot(36, ItemStack::new(ItemKind::Dirt, 2, None));
+        let cursor_item = CursorItem::default();
+
+        let packet = ClickSlotC2s {
+            window_id: 0,
+            state_id: VarInt(0),
+            slot_idx: 9,
+            button: 0,
+            mode: ClickMode::ShiftClick,
+            slot_changes: vec![
+                SlotChange { idx: 9, item: None },
+                SlotChange {
+                    idx: 36,
+                    item: Some(ItemStack::new(ItemKind::Diamond, 4, None)),
+                },
+            ],
+            carried_item: None,
+        };
+
+        validate_click_slot_packet(&packet, &player_inventory, None, &cursor_item)
+            .expect_err("shift click should not replace the dirt");
+    }
+
+    #[test]
+    fn disallow_growing_stacks_past_max() {
+        let mut player_inventory = Inventory::new(InventoryKind::Player);
+        player_inventory.set_slot(9, ItemStack::new(ItemKind::EnderPearl, 16, None));
+        let cursor_item = CursorItem(Some(ItemStack::new(ItemKind::EnderPearl, 16, None)));
+
+        let packet = ClickSlotC2s {
+            window_id: 0,
+            state_id: VarInt(0),
+            slot_idx: 9,
+            button: 0,
+            mode: ClickMode::Click,
+            slot_changes: vec![SlotChange {
+                idx: 9,
+                item: Some(ItemStack::new(ItemKind::EnderPearl, 32, None)),
+            }],
+            carried_item: None,
+        };
+
+        validate_click_slot_packet(&packet, &player_inventory, None, &cursor_item)
+            .expect_err("ender pearls should not stack past 16");
+    }
 }
@@ -42,7 +42,10 @@ mod decode;
 mod encode;
 mod error;
 mod modified_utf8;
+mod stream;
 #[cfg(test)]
 mod tests;
 
+pub use encode::DecodeLimits;
 pub use error::*;
+pub use stream::{ArrayElement, ArrayRef, Event, Reader};
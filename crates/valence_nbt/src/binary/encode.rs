@@ -12,8 +12,26 @@ impl Compound {
     ///
     /// The string returned in the tuple is the name of the root compound
     /// (typically the empty string).
+    ///
+    /// This uses the [default limits](DecodeLimits::default). Use
+    /// [`Compound::from_binary_with_limits`] for input that shouldn't be able
+    /// to use lots of memory.
     pub fn from_binary(slice: &mut &[u8]) -> Result<(Self, String)> {
-        let mut state = DecodeState { slice, depth: 0 };
+        Self::from_binary_with_limits(slice, DecodeLimits::default())
+    }
+
+    /// Like [`Compound::from_binary`], but returns an error if the data
+    /// exceeds `limits`.
+    pub fn from_binary_with_limits(
+        slice: &mut &[u8],
+        limits: DecodeLimits,
+    ) -> Result<(Self, String)> {
+        let mut state = DecodeState {
+            slice,
+            depth: 0,
+            max_depth: limits.max_depth,
+            remaining_size: limits.max_size,
+        };
 
         let root_tag = state.read_tag()?;
 
@@ -38,19 +56,43 @@ impl Compound {
     }
 }
 
-/// Maximum recursion depth to prevent overflowing the call stack.
-const MAX_DEPTH: usize = 512;
+/// Limits on the binary NBT that is decoded, so that untrusted input can't
+/// overflow the stack or use up all memory.
+///
+/// Regardless of these limits, lists and arrays are never allocated with more
+/// elements than the rest of the input could contain.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DecodeLimits {
+    /// The maximum nesting depth of lists and compounds. Defaults to 512, like
+    /// vanilla.
+    pub max_depth: usize,
+    /// The maximum number of bytes that the decoded values are estimated to
+    /// take up in memory. Defaults to no limit.
+    pub max_size: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 512,
+            max_size: usize::MAX,
+        }
+    }
+}
 
 struct DecodeState<'a, 'b> {
     slice: &'a mut &'b [u8],
     /// Current recursion depth.
     depth: usize,
+    max_depth: usize,
+    /// Bytes left of [`DecodeLimits::max_size`].
+    remaining_size: usize,
 }
 
 impl DecodeState<'_, '_> {
     #[inline]
     fn check_depth<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
-        if self.depth >= MAX_DEPTH {
+        if self.depth >= self.max_depth {
             return Err(Error::new_static("reached maximum recursion depth"));
         }
 
@@ -60,6 +102,18 @@ impl DecodeState<'_, '_> {
         res
     }
 
+    /// Accounts for `size` more bytes of decoded values.
+    #[inline]
+    fn add_size(&mut self, size: usize) -> Result<()> {
+        match self.remaining_size.checked_sub(size) {
+            Some(remaining) => {
+                self.remaining_size = remaining;
+                Ok(())
+            }
+            None => Err(Error::new_static("reached maximum decoded size")),
+        }
+    }
+
     fn read_tag(&mut self) -> Result<Tag> {
         match self.slice.read_u8()? {
             0 => Ok(Tag::End),
@@ -136,6 +190,8 @@ impl DecodeState<'_, '_> {
             )));
         }
 
+        self.add_size(len as usize)?;
+
         let (left, right) = self.slice.split_at(len as usize);
 
         let array = left.iter().map(|b| *b as i8).collect();
@@ -153,6 +209,8 @@ impl DecodeState<'_, '_> {
             )));
         }
 
+        self.add_size(len)?;
+
         let (left, right) = self.slice.split_at(len);
 
         match cesu8::from_java_cesu8(left) {
@@ -233,6 +291,8 @@ impl DecodeState<'_, '_> {
             )));
         }
 
+        self.add_size((len as usize).saturating_mul(mem::size_of::<T>()))?;
+
         let mut list = Vec::with_capacity(len as usize);
         for _ in 0..len {
             list.push(read_elem(self)?);
@@ -250,6 +310,8 @@ impl DecodeState<'_, '_> {
                 return Ok(compound);
             }
 
+            self.add_size(mem::size_of::<(String, Value)>())?;
+
             compound.insert(self.read_string()?, self.read_value(tag)?);
         }
     }
@@ -269,6 +331,8 @@ impl DecodeState<'_, '_> {
             )));
         }
 
+        self.add_size(len as usize * mem::size_of::<i32>())?;
+
         let mut array = Vec::with_capacity(len as usize);
         for _ in 0..len {
             array.push(self.read_int()?);
@@ -292,6 +356,8 @@ impl DecodeState<'_, '_> {
             )));
         }
 
+        self.add_size(len as usize * mem::size_of::<i64>())?;
+
         let mut array = Vec::with_capacity(len as usize);
         for _ in 0..len {
             array.push(self.read_long()?);
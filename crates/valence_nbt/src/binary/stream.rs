@@ -0,0 +1,562 @@
+use std::borrow::Cow;
+use std::marker::PhantomData;
+use std::{fmt, mem};
+
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt};
+use cesu8::Cesu8DecodingError;
+
+use super::{Error, Result};
+use crate::tag::Tag;
+
+/// A pull-style reader over uncompressed binary NBT.
+///
+/// Instead of building a [`Compound`](crate::Compound), the reader returns the
+/// data as a sequence of [`Event`]s. Strings and arrays are borrowed from the
+/// input, so reading doesn't allocate except for strings that aren't valid
+/// UTF-8 as is. Subtrees that aren't interesting can be passed over with
+/// [`Reader::skip_value`], which uses the lengths in the data to avoid
+/// looking at most of it.
+///
+/// After an error, the reader returns `None`.
+///
+/// # Examples
+///
+/// ```
+/// use valence_nbt::binary::{Event, Reader};
+/// use valence_nbt::{compound, List};
+///
+/// let nbt = compound! {
+///     "DataVersion" => 3465,
+///     "sections" => List::Compound(vec![compound! { "Y" => 0_i8 }; 24]),
+///     "xPos" => 5,
+/// };
+///
+/// let mut buf = vec![];
+/// nbt.to_binary(&mut buf, "").unwrap();
+///
+/// let mut reader = Reader::new(&buf);
+/// let mut ints = vec![];
+///
+/// while let Some(event) = reader.next() {
+///     match event.unwrap() {
+///         Event::TagInt(Some(name), v) => ints.push((name, v)),
+///         Event::ListStart(..) => reader.skip_value().unwrap(),
+///         _ => {}
+///     }
+/// }
+///
+/// assert_eq!(ints, [("DataVersion".into(), 3465), ("xPos".into(), 5)]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Reader<'a> {
+    input: &'a [u8],
+    stack: Vec<Frame>,
+    state: State,
+    /// If the last event started a compound or list.
+    in_new_value: bool,
+}
+
+/// An event from a [`Reader`].
+///
+/// Values in compounds have a name, while list elements have `None`. The root
+/// compound is the first event, and its name is usually empty.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Event<'a> {
+    /// The start of a compound. Its fields follow, then a
+    /// [`CompoundEnd`](Event::CompoundEnd).
+    CompoundStart(Option<Cow<'a, str>>),
+    CompoundEnd,
+    /// The start of a list with its element type and length. Its elements
+    /// follow, then a [`ListEnd`](Event::ListEnd).
+    ListStart(Option<Cow<'a, str>>, Tag, usize),
+    ListEnd,
+    TagByte(Option<Cow<'a, str>>, i8),
+    TagShort(Option<Cow<'a, str>>, i16),
+    TagInt(Option<Cow<'a, str>>, i32),
+    TagLong(Option<Cow<'a, str>>, i64),
+    TagFloat(Option<Cow<'a, str>>, f32),
+    TagDouble(Option<Cow<'a, str>>, f64),
+    TagByteArray(Option<Cow<'a, str>>, ArrayRef<'a, i8>),
+    TagString(Option<Cow<'a, str>>, Cow<'a, str>),
+    TagIntArray(Option<Cow<'a, str>>, ArrayRef<'a, i32>),
+    TagLongArray(Option<Cow<'a, str>>, ArrayRef<'a, i64>),
+}
+
+/// An array borrowed from binary NBT, with its elements still in big-endian
+/// byte order.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ArrayRef<'a, T> {
+    bytes: &'a [u8],
+    _marker: PhantomData<T>,
+}
+
+/// The element types of [`ArrayRef`].
+pub trait ArrayElement: Copy + 'static + private::Sealed {
+    #[doc(hidden)]
+    fn from_be_bytes(bytes: &[u8]) -> Self;
+}
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for i8 {}
+    impl Sealed for i32 {}
+    impl Sealed for i64 {}
+}
+
+impl ArrayElement for i8 {
+    fn from_be_bytes(bytes: &[u8]) -> Self {
+        bytes[0] as i8
+    }
+}
+
+impl ArrayElement for i32 {
+    fn from_be_bytes(bytes: &[u8]) -> Self {
+        BigEndian::read_i32(bytes)
+    }
+}
+
+impl ArrayElement for i64 {
+    fn from_be_bytes(bytes: &[u8]) -> Self {
+        BigEndian::read_i64(bytes)
+    }
+}
+
+impl<'a, T: ArrayElement> ArrayRef<'a, T> {
+    /// Returns the number of elements in the array.
+    pub fn len(&self) -> usize {
+        self.bytes.len() / mem::size_of::<T>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Returns the encoded bytes of the array.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Returns the element at `idx`, or `None` if it is out of bounds.
+    pub fn get(&self, idx: usize) -> Option<T> {
+        let size = mem::size_of::<T>();
+
+        self.bytes
+            .get(idx * size..idx * size + size)
+            .map(T::from_be_bytes)
+    }
+
+    /// Returns an iterator over the elements of the array.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = T> + 'a {
+        self.bytes
+            .chunks_exact(mem::size_of::<T>())
+            .map(T::from_be_bytes)
+    }
+
+    /// Copies the array into a vec.
+    pub fn to_vec(&self) -> Vec<T> {
+        self.iter().collect()
+    }
+}
+
+impl<T: ArrayElement + fmt::Debug> fmt::Debug for ArrayRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Frame {
+    Compound,
+    List {
+        elem: Tag,
+        remaining: usize,
+    },
+    /// A root compound given as a lone `TAG_End`, which has no fields.
+    EmptyRoot,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum State {
+    Start,
+    Reading,
+    Done,
+}
+
+/// The maximum number of nested lists and compounds, like vanilla.
+const MAX_DEPTH: usize = 512;
+
+impl<'a> Reader<'a> {
+    /// Creates a reader over uncompressed binary NBT, which begins with the
+    /// root compound.
+    pub fn new(input: &'a [u8]) -> Self {
+        Self {
+            input,
+            stack: vec![],
+            state: State::Start,
+            in_new_value: false,
+        }
+    }
+
+    /// Returns the part of the input that hasn't been read yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.input
+    }
+
+    /// Returns the number of compounds and lists that contain the next event,
+    /// including the root compound.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Skips the rest of the compound or list that was started by the last
+    /// event, including its end event. Does nothing if the last event didn't
+    /// start a compound or list.
+    ///
+    /// Only the tags and lengths in the skipped data are read, so it is much
+    /// faster than reading every event. Strings in the skipped data are not
+    /// checked for validity.
+    pub fn skip_value(&mut self) -> Result<()> {
+        if !mem::take(&mut self.in_new_value) {
+            return Ok(());
+        }
+
+        let res = self.skip_frames(self.stack.len());
+
+        if res.is_err() {
+            self.state = State::Done;
+        }
+
+        res
+    }
+
+    fn skip_frames(&mut self, depth: usize) -> Result<()> {
+        while self.stack.len() >= depth {
+            match self.stack.last_mut().expect("stack is not empty") {
+                Frame::Compound => {
+                    let tag = self.read_tag()?;
+
+                    if tag == Tag::End {
+                        self.stack.pop();
+                    } else {
+                        let len = self.read_u16()?;
+                        self.advance(len.into())?;
+                        self.skip_payload(tag)?;
+                    }
+                }
+                Frame::List { elem, remaining } => {
+                    let elem = *elem;
+
+                    if *remaining == 0 {
+                        self.stack.pop();
+                    } else if let Some(size) = fixed_size(elem) {
+                        let len = *remaining * size;
+                        *remaining = 0;
+                        self.advance(len)?;
+                    } else {
+                        *remaining -= 1;
+                        self.skip_payload(elem)?;
+                    }
+                }
+                Frame::EmptyRoot => {
+                    self.stack.pop();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Skips a value, pushing a frame if it's a compound or list.
+    fn skip_payload(&mut self, tag: Tag) -> Result<()> {
+        if let Some(size) = fixed_size(tag) {
+            return self.advance(size);
+        }
+
+        match tag {
+            Tag::ByteArray => {
+                let len = self.read_len("byte array")?;
+                self.advance(len)
+            }
+            Tag::String => {
+                let len = self.read_u16()?;
+                self.advance(len.into())
+            }
+            Tag::IntArray => {
+                let len = self.read_len("int array")?;
+                self.advance(len.saturating_mul(4))
+            }
+            Tag::LongArray => {
+                let len = self.read_len("long array")?;
+                self.advance(len.saturating_mul(8))
+            }
+            Tag::Compound => self.push(Frame::Compound),
+            Tag::List => {
+                let (elem, len) = self.read_list_header()?;
+                self.push(Frame::List {
+                    elem,
+                    remaining: len,
+                })
+            }
+            _ => unreachable!("fixed size tags are handled above"),
+        }
+    }
+
+    fn next_event(&mut self) -> Result<Option<Event<'a>>> {
+        self.in_new_value = false;
+
+        if self.state == State::Start {
+            self.state = State::Reading;
+
+            let root_tag = self.read_tag()?;
+
+            // For cases such as Block Entity Data in the chunk packet.
+            // https://wiki.vg/Protocol#Chunk_Data_and_Update_Light
+            if root_tag == Tag::End {
+                self.stack.push(Frame::EmptyRoot);
+                self.in_new_value = true;
+                return Ok(Some(Event::CompoundStart(Some("".into()))));
+            }
+
+            if root_tag != Tag::Compound {
+                return Err(Error::new_owned(format!(
+                    "expected root tag for compound (got {root_tag})",
+                )));
+            }
+
+            let name = self.read_string()?;
+            self.stack.push(Frame::Compound);
+            self.in_new_value = true;
+
+            return Ok(Some(Event::CompoundStart(Some(name))));
+        }
+
+        let Some(frame) = self.stack.last_mut() else {
+            return Ok(None);
+        };
+
+        match frame {
+            Frame::Compound => {
+                let tag = self.read_tag()?;
+
+                if tag == Tag::End {
+                    self.stack.pop();
+                    return Ok(Some(Event::CompoundEnd));
+                }
+
+                let name = self.read_string()?;
+                self.read_value(tag, Some(name)).map(Some)
+            }
+            Frame::List { elem, remaining } => {
+                if *remaining == 0 {
+                    self.stack.pop();
+                    return Ok(Some(Event::ListEnd));
+                }
+
+                *remaining -= 1;
+                let elem = *elem;
+
+                self.read_value(elem, None).map(Some)
+            }
+            Frame::EmptyRoot => {
+                self.stack.pop();
+                Ok(Some(Event::CompoundEnd))
+            }
+        }
+    }
+
+    fn read_value(&mut self, tag: Tag, name: Option<Cow<'a, str>>) -> Result<Event<'a>> {
+        Ok(match tag {
+            Tag::End => unreachable!("illegal TAG_End argument"),
+            Tag::Byte => Event::TagByte(name, self.input.read_i8()?),
+            Tag::Short => Event::TagShort(name, self.input.read_i16::<BigEndian>()?),
+            Tag::Int => Event::TagInt(name, self.input.read_i32::<BigEndian>()?),
+            Tag::Long => Event::TagLong(name, self.input.read_i64::<BigEndian>()?),
+            Tag::Float => Event::TagFloat(name, self.input.read_f32::<BigEndian>()?),
+            Tag::Double => Event::TagDouble(name, self.input.read_f64::<BigEndian>()?),
+            Tag::ByteArray => Event::TagByteArray(name, self.read_array("byte array")?),
+            Tag::String => Event::TagString(name, self.read_string()?),
+            Tag::List => {
+                let (elem, len) = self.read_list_header()?;
+                self.push(Frame::List {
+                    elem,
+                    remaining: len,
+                })?;
+                self.in_new_value = true;
+
+                Event::ListStart(name, elem, len)
+            }
+            Tag::Compound => {
+                self.push(Frame::Compound)?;
+                self.in_new_value = true;
+
+                Event::CompoundStart(name)
+            }
+            Tag::IntArray => Event::TagIntArray(name, self.read_array("int array")?),
+            Tag::LongArray => Event::TagLongArray(name, self.read_array("long array")?),
+        })
+    }
+
+    fn push(&mut self, frame: Frame) -> Result<()> {
+        if self.stack.len() > MAX_DEPTH {
+            return Err(Error::new_static("reached maximum recursion depth"));
+        }
+
+        self.stack.push(frame);
+        Ok(())
+    }
+
+    fn advance(&mut self, len: usize) -> Result<()> {
+        if len > self.input.len() {
+            return Err(Error::new_static("unexpected end of input"));
+        }
+
+        self.input = &self.input[len..];
+        Ok(())
+    }
+
+    fn read_tag(&mut self) -> Result<Tag> {
+        match self.input.read_u8()? {
+            0 => Ok(Tag::End),
+            1 => Ok(Tag::Byte),
+            2 => Ok(Tag::Short),
+            3 => Ok(Tag::Int),
+            4 => Ok(Tag::Long),
+            5 => Ok(Tag::Float),
+            6 => Ok(Tag::Double),
+            7 => Ok(Tag::ByteArray),
+            8 => Ok(Tag::String),
+            9 => Ok(Tag::List),
+            10 => Ok(Tag::Compound),
+            11 => Ok(Tag::IntArray),
+            12 => Ok(Tag::LongArray),
+            byte => Err(Error::new_owned(format!("invalid tag byte of {byte:#x}"))),
+        }
+    }
+
+    fn read_u16(&mut self) -> Result<u16> {
+        Ok(self.input.read_u16::<BigEndian>()?)
+    }
+
+    /// Reads the length of an array.
+    fn read_len(&mut self, what: &str) -> Result<usize> {
+        let len = self.input.read_i32::<BigEndian>()?;
+
+        if len.is_negative() {
+            return Err(Error::new_owned(format!("negative {what} length of {len}")));
+        }
+
+        Ok(len as usize)
+    }
+
+    fn read_array<T: ArrayElement>(&mut self, what: &str) -> Result<ArrayRef<'a, T>> {
+        let len = self.read_len(what)?;
+
+        if len as u64 * mem::size_of::<T>() as u64 > self.input.len() as u64 {
+            return Err(Error::new_owned(format!(
+                "{what} of length {len} exceeds remainder of input"
+            )));
+        }
+
+        let (bytes, rest) = self.input.split_at(len * mem::size_of::<T>());
+        self.input = rest;
+
+        Ok(ArrayRef {
+            bytes,
+            _marker: PhantomData,
+        })
+    }
+
+    fn read_string(&mut self) -> Result<Cow<'a, str>> {
+        let len = self.read_u16()?.into();
+
+        if len > self.input.len() {
+            return Err(Error::new_owned(format!(
+                "string of length {len} exceeds remainder of input"
+            )));
+        }
+
+        let (bytes, rest) = self.input.split_at(len);
+
+        match cesu8::from_java_cesu8(bytes) {
+            Ok(s) => {
+                self.input = rest;
+                Ok(s)
+            }
+            Err(Cesu8DecodingError) => {
+                Err(Error::new_static("could not convert CESU-8 data to UTF-8"))
+            }
+        }
+    }
+
+    /// Reads the element type and length of a list.
+    fn read_list_header(&mut self) -> Result<(Tag, usize)> {
+        let elem = self.read_tag()?;
+        let len = self.input.read_i32::<BigEndian>()?;
+
+        if elem == Tag::End && len != 0 {
+            return Err(Error::new_owned(format!(
+                "TAG_End list with nonzero length of {len}"
+            )));
+        }
+
+        if len.is_negative() {
+            return Err(Error::new_owned(format!(
+                "negative {elem} list length of {len}",
+            )));
+        }
+
+        if len as u64 * min_size(elem) as u64 > self.input.len() as u64 {
+            return Err(Error::new_owned(format!(
+                "{elem} list of length {len} exceeds remainder of input"
+            )));
+        }
+
+        Ok((elem, len as usize))
+    }
+}
+
+impl<'a> Iterator for Reader<'a> {
+    type Item = Result<Event<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.state == State::Done {
+            return None;
+        }
+
+        match self.next_event() {
+            Ok(Some(event)) => Some(Ok(event)),
+            Ok(None) => {
+                self.state = State::Done;
+                None
+            }
+            Err(e) => {
+                self.state = State::Done;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Returns the encoded size of values with the tag if it's always the same.
+fn fixed_size(tag: Tag) -> Option<usize> {
+    match tag {
+        Tag::Byte => Some(1),
+        Tag::Short => Some(2),
+        Tag::Int | Tag::Float => Some(4),
+        Tag::Long | Tag::Double => Some(8),
+        _ => None,
+    }
+}
+
+/// Returns the minimum encoded size of values with the tag.
+fn min_size(tag: Tag) -> usize {
+    match tag {
+        Tag::End => 0,
+        Tag::ByteArray | Tag::IntArray | Tag::LongArray => 4,
+        Tag::String => 2,
+        Tag::List => 5,
+        Tag::Compound => 1,
+        _ => fixed_size(tag).expect("tag has a fixed size"),
+    }
+}
@@ -1,3 +1,4 @@
+use super::{DecodeLimits, Event, Reader, Result};
 use crate::tag::Tag;
 use crate::{compound, Compound, List, Value};
 
@@ -65,7 +66,7 @@ fn deeply_nested_compound_decode() {
     buf.push(Tag::End as u8); // End root compound
 
     // Should not overflow the stack
-    let _ = Compound::from_binary(&mut buf.as_slice());
+    assert!(Compound::from_binary(&mut buf.as_slice()).is_err());
 }
 
 #[test]
@@ -84,7 +85,112 @@ fn deeply_nested_list_decode() {
     buf.push(Tag::End as u8); // End root compound
 
     // Should not overflow the stack
-    let _ = Compound::from_binary(&mut buf.as_slice());
+    assert!(Compound::from_binary(&mut buf.as_slice()).is_err());
+}
+
+#[test]
+fn depth_limit() {
+    fn nested(depth: usize) -> Vec<u8> {
+        let mut list = List::End;
+        for _ in 1..depth {
+            list = List::List(vec![list]);
+        }
+
+        let mut buf = vec![];
+        compound!("" => list).to_binary(&mut buf, "").unwrap();
+        buf
+    }
+
+    let limits = DecodeLimits {
+        max_depth: 5,
+        ..Default::default()
+    };
+
+    assert!(Compound::from_binary_with_limits(&mut nested(5).as_slice(), limits).is_ok());
+    assert!(Compound::from_binary_with_limits(&mut nested(6).as_slice(), limits).is_err());
+    assert!(Compound::from_binary(&mut nested(512).as_slice()).is_ok());
+    assert!(Compound::from_binary(&mut nested(513).as_slice()).is_err());
+}
+
+#[test]
+fn size_limit() {
+    let mut buf = vec![];
+    compound!("bytes" => vec![0_i8; 1000])
+        .to_binary(&mut buf, "")
+        .unwrap();
+
+    let limits = |max_size| DecodeLimits {
+        max_size,
+        ..Default::default()
+    };
+
+    assert!(Compound::from_binary_with_limits(&mut buf.as_slice(), limits(1000)).is_err());
+    assert!(Compound::from_binary_with_limits(&mut buf.as_slice(), limits(2000)).is_ok());
+
+    // Empty compounds are small to encode, but not to decode.
+    let mut buf = vec![];
+    compound!("" => List::Compound(vec![Compound::new(); 10_000]))
+        .to_binary(&mut buf, "")
+        .unwrap();
+
+    assert!(buf.len() < 11_000);
+    assert!(Compound::from_binary_with_limits(&mut buf.as_slice(), limits(100_000)).is_err());
+
+    // Lengths that exceed the input are rejected before anything is allocated.
+    for tag in [Tag::ByteArray, Tag::IntArray, Tag::LongArray] {
+        let mut buf = vec![Tag::Compound as u8, 0, 0, tag as u8, 0, 0];
+        buf.extend(i32::MAX.to_be_bytes());
+        assert!(Compound::from_binary(&mut buf.as_slice()).is_err());
+    }
+
+    let mut buf = vec![
+        Tag::Compound as u8,
+        0,
+        0,
+        Tag::List as u8,
+        0,
+        0,
+        Tag::Long as u8,
+    ];
+    buf.extend(i32::MAX.to_be_bytes());
+    assert!(Compound::from_binary(&mut buf.as_slice()).is_err());
+}
+
+#[test]
+fn malformed_input() {
+    let mut buf = vec![];
+    example_compound().to_binary(&mut buf, ROOT_NAME).unwrap();
+
+    let limits = DecodeLimits {
+        max_depth: 16,
+        max_size: 4096,
+    };
+
+    // Every truncation is an error.
+    for len in 0..buf.len() {
+        assert!(Compound::from_binary_with_limits(&mut &buf[..len], limits).is_err());
+    }
+
+    // Random corruptions never panic.
+    let mut rng = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = || {
+        rng ^= rng << 13;
+        rng ^= rng >> 7;
+        rng ^= rng << 17;
+        rng
+    };
+
+    for _ in 0..20_000 {
+        let mut input = buf.clone();
+
+        for _ in 0..next() % 4 + 1 {
+            let idx = next() as usize % input.len();
+            input[idx] = next() as u8;
+        }
+
+        let _ = Compound::from_binary(&mut input.as_slice());
+        let _ = Compound::from_binary_with_limits(&mut input.as_slice(), limits);
+    }
 }
 
 #[test]
@@ -97,6 +203,340 @@ fn correct_length() {
     assert_eq!(c.written_size("abc"), buf.len());
 }
 
+/// Builds a compound from the events of a [`Reader`].
+fn read_events(reader: &mut Reader) -> Result<(Compound, String)> {
+    fn read_compound(reader: &mut Reader) -> Result<Compound> {
+        let mut compound = Compound::new();
+
+        loop {
+            match reader.next().unwrap()? {
+                Event::CompoundEnd => return Ok(compound),
+                event => {
+                    let (name, value) = read_value(reader, event)?;
+                    compound.insert(name.unwrap().into_owned(), value);
+                }
+            }
+        }
+    }
+
+    fn read_value<'a>(
+        reader: &mut Reader<'a>,
+        event: Event<'a>,
+    ) -> Result<(Option<std::borrow::Cow<'a, str>>, Value)> {
+        Ok(match event {
+            Event::CompoundStart(name) => (name, read_compound(reader)?.into()),
+            Event::ListStart(name, elem, len) => {
+                let mut values = vec![];
+
+                loop {
+                    match reader.next().unwrap()? {
+                        Event::ListEnd => break,
+                        event => values.push(read_value(reader, event)?.1),
+                    }
+                }
+
+                assert_eq!(values.len(), len);
+
+                macro_rules! collect {
+                    ($variant:ident) => {
+                        List::$variant(
+                            values
+                                .into_iter()
+                                .map(|v| match v {
+                                    Value::$variant(v) => v,
+                                    v => panic!("{v:?} in {elem} list"),
+                                })
+                                .collect(),
+                        )
+                    };
+                }
+
+                let list = match elem {
+                    Tag::End => List::End,
+                    Tag::Byte => collect!(Byte),
+                    Tag::Short => collect!(Short),
+                    Tag::Int => collect!(Int),
+                    Tag::Long => collect!(Long),
+                    Tag::Float => collect!(Float),
+                    Tag::Double => collect!(Double),
+                    Tag::ByteArray => collect!(ByteArray),
+                    Tag::String => collect!(String),
+                    Tag::List => collect!(List),
+                    Tag::Compound => collect!(Compound),
+                    Tag::IntArray => collect!(IntArray),
+                    Tag::LongArray => collect!(LongArray),
+                };
+
+                (name, list.into())
+            }
+            Event::TagByte(name, v) => (name, v.into()),
+            Event::TagShort(name, v) => (name, v.into()),
+            Event::TagInt(name, v) => (name, v.into()),
+            Event::TagLong(name, v) => (name, v.into()),
+            Event::TagFloat(name, v) => (name, v.into()),
+            Event::TagDouble(name, v) => (name, v.into()),
+            Event::TagByteArray(name, v) => (name, v.to_vec().into()),
+            Event::TagString(name, v) => (name, v.into_owned().into()),
+            Event::TagIntArray(name, v) => (name, v.to_vec().into()),
+            Event::TagLongArray(name, v) => (name, v.to_vec().into()),
+            Event::CompoundEnd | Event::ListEnd => panic!("unexpected end event"),
+        })
+    }
+
+    let Event::CompoundStart(Some(root_name)) = reader.next().unwrap()? else {
+        panic!("expected root compound");
+    };
+    let root_name = root_name.into_owned();
+
+    let root = read_compound(reader)?;
+
+    assert!(reader.next().is_none());
+
+    Ok((root, root_name))
+}
+
+/// Returns compounds shaped like the chunks in region files.
+fn chunk_fixtures() -> Vec<Compound> {
+    let mut rng = 0x9e37_79b9_7f4a_7c15_u64;
+    let mut next = move || {
+        rng ^= rng << 13;
+        rng ^= rng >> 7;
+        rng ^= rng << 17;
+        rng
+    };
+
+    (0..8)
+        .map(|i| {
+            let sections = (-4..20)
+                .map(|y| {
+                    let palette_len = next() % 12 + 1;
+                    let palette = (0..palette_len)
+                        .map(|p| {
+                            if p % 3 == 0 {
+                                compound! { "Name" => format!("minecraft:block_{p}") }
+                            } else {
+                                compound! {
+                                    "Name" => "minecraft:oak_log",
+                                    "Properties" => compound! { "axis" => "y" },
+                                }
+                            }
+                        })
+                        .collect();
+
+                    let mut section = compound! {
+                        "Y" => y as i8,
+                        "biomes" => compound! {
+                            "palette" => List::String(vec!["minecraft:plains".into()]),
+                        },
+                        "block_states" => compound! {
+                            "palette" => List::Compound(palette),
+                        },
+                    };
+
+                    if palette_len > 1 {
+                        let data = (0..256).map(|_| next() as i64).collect::<Vec<_>>();
+                        if let Some(Value::Compound(states)) = section.get_mut("block_states") {
+                            states.insert("data", data);
+                        }
+                    }
+
+                    if y % 2 == 0 {
+                        section.insert("BlockLight", vec![(next() % 16) as i8; 2048]);
+                        section.insert("SkyLight", (0..2048).map(|_| next() as i8).collect::<Vec<_>>());
+                    }
+
+                    section
+                })
+                .collect();
+
+            let block_entities = (0..next() % 6)
+                .map(|j| {
+                    compound! {
+                        "id" => if j % 2 == 0 { "minecraft:chest" } else { "minecraft:sign" },
+                        "x" => i * 16 + j as i32,
+                        "y" => 64,
+                        "z" => -i * 16,
+                        "Items" => List::Compound(vec![compound! {
+                            "Count" => 1_i8,
+                            "Slot" => 0_i8,
+                            "id" => "minecraft:diamond",
+                            "tag" => compound! { "display" => compound! { "Name" => "{\"text\":\"日本\"}" } },
+                        }]),
+                    }
+                })
+                .collect();
+
+            compound! {
+                "DataVersion" => 3465,
+                "Heightmaps" => compound! {
+                    "MOTION_BLOCKING" => (0..37).map(|_| next() as i64).collect::<Vec<_>>(),
+                    "WORLD_SURFACE" => (0..37).map(|_| next() as i64).collect::<Vec<_>>(),
+                },
+                "InhabitedTime" => next() as i64,
+                "LastUpdate" => 12345_i64,
+                "PostProcessing" => List::List(vec![List::Short(vec![1, 2, 3]), List::End, List::Short(vec![])]),
+                "Status" => "minecraft:full",
+                "block_entities" => List::Compound(block_entities),
+                "block_ticks" => List::End,
+                "fluid_ticks" => List::End,
+                "isLightOn" => 1_i8,
+                "sections" => List::Compound(sections),
+                "structures" => compound! {
+                    "References" => compound! { "village" => vec![next() as i64, 7] },
+                    "starts" => compound! {},
+                },
+                "xPos" => i,
+                "yPos" => -4,
+                "zPos" => -i,
+                "weights" => List::Double(vec![0.5, -1.25, f64::MAX]),
+                "floats" => List::Float(vec![1.5]),
+                "bytes" => List::Byte(vec![1, -2, 3]),
+                "ints" => List::Int(vec![i32::MIN]),
+                "longs" => List::Long(vec![i64::MIN]),
+                "byte_arrays" => List::ByteArray(vec![vec![1, 2], vec![]]),
+                "int_arrays" => List::IntArray(vec![vec![1, 2], vec![]]),
+                "long_arrays" => List::LongArray(vec![vec![1, 2], vec![]]),
+            }
+        })
+        .chain([example_compound(), Compound::new()])
+        .collect()
+}
+
+#[test]
+fn stream_matches_tree() {
+    for (i, chunk) in chunk_fixtures().into_iter().enumerate() {
+        let mut buf = vec![];
+        chunk.to_binary(&mut buf, ROOT_NAME).unwrap();
+
+        let mut reader = Reader::new(&buf);
+        let (streamed, root_name) = read_events(&mut reader).unwrap();
+
+        assert_eq!(root_name, ROOT_NAME);
+        assert_eq!(streamed, chunk, "fixture {i}");
+        assert!(reader.remaining().is_empty());
+    }
+
+    // A lone `TAG_End` is an empty root compound.
+    assert_eq!(
+        read_events(&mut Reader::new(&[Tag::End as u8])).unwrap(),
+        (Compound::new(), String::new())
+    );
+}
+
+#[test]
+fn stream_skip_value() {
+    for chunk in chunk_fixtures() {
+        let mut buf = vec![];
+        chunk.to_binary(&mut buf, "").unwrap();
+
+        // Skipping everything except the top level fields leaves the scalars.
+        let mut reader = Reader::new(&buf);
+        let mut scalars = Compound::new();
+
+        assert!(matches!(reader.next(), Some(Ok(Event::CompoundStart(_)))));
+
+        while let Some(event) = reader.next() {
+            match event.unwrap() {
+                Event::CompoundStart(_) | Event::ListStart(..) => {
+                    reader.skip_value().unwrap();
+                    assert_eq!(reader.depth(), 1);
+                }
+                Event::CompoundEnd => assert_eq!(reader.depth(), 0),
+                Event::TagInt(Some(name), v) => {
+                    scalars.insert(name, v);
+                }
+                Event::TagLong(Some(name), v) => {
+                    scalars.insert(name, v);
+                }
+                Event::TagByte(Some(name), v) => {
+                    scalars.insert(name, v);
+                }
+                Event::TagString(Some(name), v) => {
+                    scalars.insert(name, v.into_owned());
+                }
+                _ => {}
+            }
+        }
+
+        let mut expected = chunk.clone();
+        expected.retain(|_, v| {
+            matches!(
+                v,
+                Value::Int(_) | Value::Long(_) | Value::Byte(_) | Value::String(_)
+            )
+        });
+
+        assert_eq!(scalars, expected);
+        assert!(reader.remaining().is_empty());
+
+        // Skipping the root skips everything.
+        let mut reader = Reader::new(&buf);
+        reader.next().unwrap().unwrap();
+        reader.skip_value().unwrap();
+
+        assert!(reader.remaining().is_empty());
+        assert!(reader.next().is_none());
+    }
+}
+
+#[test]
+fn stream_malformed_input() {
+    let mut chunk = chunk_fixtures().swap_remove(0);
+
+    // Keep the input small so that this doesn't take too long.
+    if let Some(Value::List(List::Compound(sections))) = chunk.get_mut("sections") {
+        sections.truncate(2);
+    }
+
+    let mut buf = vec![];
+    chunk.to_binary(&mut buf, ROOT_NAME).unwrap();
+
+    // Every truncation is an error, with or without skipping.
+    for len in 0..buf.len() {
+        assert!(read_events(&mut Reader::new(&buf[..len])).is_err());
+
+        let mut reader = Reader::new(&buf[..len]);
+        let mut failed = false;
+
+        while let Some(event) = reader.next() {
+            match event {
+                Ok(Event::ListStart(..)) => failed |= reader.skip_value().is_err(),
+                Ok(_) => {}
+                Err(_) => failed = true,
+            }
+        }
+
+        assert!(failed, "truncated to {len} bytes");
+    }
+
+    // The stream and tree parsers agree on corrupted input.
+    let mut rng = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = || {
+        rng ^= rng << 13;
+        rng ^= rng >> 7;
+        rng ^= rng << 17;
+        rng
+    };
+
+    for _ in 0..5_000 {
+        let mut input = buf.clone();
+
+        for _ in 0..next() % 4 + 1 {
+            let idx = next() as usize % input.len();
+            input[idx] = next() as u8;
+        }
+
+        let tree = Compound::from_binary(&mut input.as_slice());
+        let streamed = read_events(&mut Reader::new(&input));
+
+        match (tree, streamed) {
+            (Ok(tree), Ok(streamed)) => assert_eq!(tree, streamed),
+            (Err(_), Err(_)) => {}
+            (tree, streamed) => panic!("tree: {tree:?}, stream: {streamed:?}"),
+        }
+    }
+}
+
 fn example_compound() -> Compound {
     fn inner() -> Compound {
         compound! {
@@ -4,7 +4,7 @@ use std::hash::Hash;
 use std::iter::FusedIterator;
 use std::ops::{Index, IndexMut};
 
-use crate::Value;
+use crate::{List, Value};
 
 /// A map type with [`String`] keys and [`Value`] values.
 #[derive(Clone, PartialEq, Default)]
@@ -178,12 +178,14 @@ impl Compound {
         self.map.retain(f)
     }
 
-    /// Inserts all items from `other` into `self` recursively.
+    /// Inserts all items from `other` into `self`, combining values with the
+    /// same key according to `strategy`.
     ///
     /// # Example
     ///
     /// ```
     /// use valence_nbt::compound;
+    /// use valence_nbt::compound::MergeStrategy;
     ///
     /// let mut this = compound! {
     ///     "foo" => 10,
@@ -199,7 +201,12 @@ impl Compound {
     ///     }
     /// };
     ///
-    /// this.merge(other);
+    /// this.merge(
+    ///     other,
+    ///     MergeStrategy::Deep {
+    ///         concat_lists: false,
+    ///     },
+    /// );
     ///
     /// assert_eq!(
     ///     this,
@@ -212,24 +219,203 @@ impl Compound {
     ///     }
     /// );
     /// ```
-    pub fn merge(&mut self, other: Compound) {
+    pub fn merge(&mut self, other: Compound, strategy: MergeStrategy) {
         for (k, v) in other {
-            match (self.entry(k), v) {
-                (Entry::Occupied(mut oe), Value::Compound(other)) => {
-                    if let Value::Compound(this) = oe.get_mut() {
-                        // Insert compound recursively.
-                        this.merge(other);
+            let mut oe = match self.entry(k) {
+                Entry::Occupied(oe) => oe,
+                Entry::Vacant(ve) => {
+                    ve.insert(v);
+                    continue;
+                }
+            };
+
+            match (strategy, oe.get_mut(), v) {
+                (MergeStrategy::KeepExisting, _, _) => {}
+                (MergeStrategy::Deep { .. }, Value::Compound(this), Value::Compound(other)) => {
+                    this.merge(other, strategy);
+                }
+                (
+                    MergeStrategy::Deep { concat_lists: true },
+                    Value::List(this),
+                    Value::List(other),
+                ) => {
+                    if let Err(other) = append_list(this, other) {
+                        *this = other;
                     }
                 }
-                (Entry::Occupied(mut oe), value) => {
-                    oe.insert(value);
+                (_, this, value) => *this = value,
+            }
+        }
+    }
+
+    /// Returns a patch that turns `self` into `other` when passed to
+    /// [`Compound::apply_patch`]. The patch of equal compounds is empty.
+    ///
+    /// A patch is a compound with up to three entries, which are left out when
+    /// they would be empty:
+    ///
+    /// - `remove`: A list of strings with the keys to remove.
+    /// - `set`: A compound with the values to add or replace.
+    /// - `patch`: A compound with a patch for each key whose value is a
+    ///   compound in both `self` and `other`, and is different.
+    ///
+    /// Lists are compared as a whole. A list which is different in any way,
+    /// including only the order of its elements, is replaced by the whole new
+    /// list in `set`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use valence_nbt::{compound, List};
+    ///
+    /// let old = compound! {
+    ///     "id" => "minecraft:chest",
+    ///     "Lock" => "key",
+    ///     "display" => compound! { "Name" => "\"Chest\"", "color" => 5 },
+    /// };
+    ///
+    /// let new = compound! {
+    ///     "id" => "minecraft:chest",
+    ///     "Items" => List::End,
+    ///     "display" => compound! { "Name" => "\"Loot\"", "color" => 5 },
+    /// };
+    ///
+    /// let patch = old.diff(&new);
+    ///
+    /// assert_eq!(
+    ///     patch,
+    ///     compound! {
+    ///         "remove" => List::String(vec!["Lock".into()]),
+    ///         "set" => compound! { "Items" => List::End },
+    ///         "patch" => compound! {
+    ///             "display" => compound! {
+    ///                 "set" => compound! { "Name" => "\"Loot\"" },
+    ///             },
+    ///         },
+    ///     }
+    /// );
+    ///
+    /// let mut patched = old.clone();
+    /// patched.apply_patch(patch);
+    /// assert_eq!(patched, new);
+    /// ```
+    pub fn diff(&self, other: &Compound) -> Compound {
+        let mut remove = vec![];
+        let mut set = Compound::new();
+        let mut patch = Compound::new();
+
+        for k in self.keys() {
+            if !other.contains_key(k) {
+                remove.push(k.clone());
+            }
+        }
+
+        for (k, v) in other {
+            match (self.get(k), v) {
+                (Some(old), new) if old == new => {}
+                (Some(Value::Compound(old)), Value::Compound(new)) => {
+                    patch.insert(k.clone(), old.diff(new));
                 }
-                (Entry::Vacant(ve), value) => {
-                    ve.insert(value);
+                _ => {
+                    set.insert(k.clone(), v.clone());
                 }
             }
         }
+
+        let mut res = Compound::new();
+
+        if !remove.is_empty() {
+            res.insert("remove", List::String(remove));
+        }
+
+        if !set.is_empty() {
+            res.insert("set", set);
+        }
+
+        if !patch.is_empty() {
+            res.insert("patch", patch);
+        }
+
+        res
     }
+
+    /// Applies a patch created by [`Compound::diff`]. Keys are removed first,
+    /// then values are set, and then nested compounds are patched. A nested
+    /// patch for a value which is missing or not a compound is applied to an
+    /// empty compound which replaces the value.
+    ///
+    /// Entries of the patch which don't have the types described in
+    /// [`Compound::diff`] are ignored.
+    pub fn apply_patch(&mut self, mut patch: Compound) {
+        if let Some(Value::List(List::String(keys))) = patch.remove("remove") {
+            for k in keys {
+                self.remove(&k);
+            }
+        }
+
+        if let Some(Value::Compound(set)) = patch.remove("set") {
+            self.extend(set);
+        }
+
+        if let Some(Value::Compound(patches)) = patch.remove("patch") {
+            for (k, v) in patches {
+                let Value::Compound(patch) = v else {
+                    continue;
+                };
+
+                let value = self.entry(k).or_insert_with(Compound::new);
+
+                if !value.is_compound() {
+                    *value = Compound::new().into();
+                }
+
+                if let Value::Compound(c) = value {
+                    c.apply_patch(patch);
+                }
+            }
+        }
+    }
+}
+
+/// How [`Compound::merge`] combines values that have the same key.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MergeStrategy {
+    /// Values from the other compound replace the existing values.
+    Replace,
+    /// The existing values are kept, so only keys which are missing are added.
+    KeepExisting,
+    /// Like [`MergeStrategy::Replace`], except that compounds which are in
+    /// both are merged recursively with this strategy.
+    ///
+    /// If `concat_lists` is `true`, the elements of a list in the other
+    /// compound are appended to a list in the existing compound if both have
+    /// the same element type. Empty lists can be appended to and appended to
+    /// any list. Otherwise, lists are replaced like other values.
+    Deep { concat_lists: bool },
+}
+
+/// Appends the elements of `other` to `list`, or returns `other` if the
+/// element types are different.
+fn append_list(list: &mut List, other: List) -> Result<(), List> {
+    match (list, other) {
+        (_, List::End) => {}
+        (list @ List::End, other) => *list = other,
+        (List::Byte(l), List::Byte(o)) => l.extend(o),
+        (List::Short(l), List::Short(o)) => l.extend(o),
+        (List::Int(l), List::Int(o)) => l.extend(o),
+        (List::Long(l), List::Long(o)) => l.extend(o),
+        (List::Float(l), List::Float(o)) => l.extend(o),
+        (List::Double(l), List::Double(o)) => l.extend(o),
+        (List::ByteArray(l), List::ByteArray(o)) => l.extend(o),
+        (List::String(l), List::String(o)) => l.extend(o),
+        (List::List(l), List::List(o)) => l.extend(o),
+        (List::Compound(l), List::Compound(o)) => l.extend(o),
+        (List::IntArray(l), List::IntArray(o)) => l.extend(o),
+        (List::LongArray(l), List::LongArray(o)) => l.extend(o),
+        (_, other) => return Err(other),
+    }
+
+    Ok(())
 }
 
 impl Extend<(String, Value)> for Compound {
@@ -494,11 +680,12 @@ impl_iterator_traits!((ValuesMut<'a>) => &'a mut Value);
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::compound;
+
     #[cfg(feature = "preserve_order")]
     #[test]
     fn compound_preserves_order() {
-        use super::*;
-
         let letters = ["g", "b", "d", "e", "h", "z", "m", "a", "q"];
 
         let mut c = Compound::new();
@@ -510,4 +697,181 @@ mod tests {
             assert_eq!(k, l);
         }
     }
+
+    #[test]
+    fn merge_strategies() {
+        let this = compound! {
+            "a" => 1,
+            "list" => List::Int(vec![1, 2]),
+            "strings" => List::String(vec!["x".into()]),
+            "empty" => List::End,
+            "nested" => compound! { "b" => 2, "c" => 3 },
+            "replaced" => compound! { "d" => 4 },
+        };
+
+        let other = compound! {
+            "a" => 10,
+            "list" => List::Int(vec![3]),
+            "strings" => List::Int(vec![5]),
+            "empty" => List::Byte(vec![6]),
+            "nested" => compound! { "c" => 30, "e" => 50 },
+            "replaced" => 7,
+            "new" => "new",
+        };
+
+        let merged = |strategy| {
+            let mut this = this.clone();
+            this.merge(other.clone(), strategy);
+            this
+        };
+
+        assert_eq!(merged(MergeStrategy::Replace), {
+            let mut expected = this.clone();
+            expected.extend(other.clone());
+            expected
+        });
+
+        assert_eq!(merged(MergeStrategy::KeepExisting), {
+            let mut expected = this.clone();
+            expected.insert("new", "new");
+            expected
+        });
+
+        let deep = compound! {
+            "a" => 10,
+            "list" => List::Int(vec![3]),
+            "strings" => List::Int(vec![5]),
+            "empty" => List::Byte(vec![6]),
+            "nested" => compound! { "b" => 2, "c" => 30, "e" => 50 },
+            "replaced" => 7,
+            "new" => "new",
+        };
+
+        assert_eq!(
+            merged(MergeStrategy::Deep {
+                concat_lists: false
+            }),
+            deep
+        );
+
+        let mut deep_concat = deep;
+        deep_concat.insert("list", List::Int(vec![1, 2, 3]));
+
+        assert_eq!(
+            merged(MergeStrategy::Deep { concat_lists: true }),
+            deep_concat
+        );
+    }
+
+    /// A xorshift generator of random compounds with few distinct keys, so
+    /// that random compounds have keys in common.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn key(&mut self) -> String {
+            ["a", "b", "c", "d", "e"][self.next() as usize % 5].into()
+        }
+
+        fn value(&mut self, depth: usize) -> Value {
+            let kinds = if depth > 3 { 8 } else { 10 };
+
+            match self.next() % kinds {
+                0 => Value::Byte(self.next() as i8 % 3),
+                1 => Value::Short(self.next() as i16 % 3),
+                2 => Value::Int(self.next() as i32 % 3),
+                3 => Value::Long(self.next() as i64 % 3),
+                4 => Value::Float((self.next() % 3) as f32),
+                5 => Value::String(self.key()),
+                6 => Value::IntArray((0..self.next() % 3).map(|i| i as i32).collect()),
+                7 => Value::List(List::Int((0..self.next() % 3).map(|i| i as i32).collect())),
+                8 => Value::List(List::Compound(
+                    (0..self.next() % 3)
+                        .map(|_| self.compound(depth + 1))
+                        .collect(),
+                )),
+                _ => Value::Compound(self.compound(depth + 1)),
+            }
+        }
+
+        fn compound(&mut self, depth: usize) -> Compound {
+            (0..self.next() % 5)
+                .map(|_| (self.key(), self.value(depth)))
+                .collect()
+        }
+
+        /// Returns a copy of `c` with some values removed, replaced or
+        /// changed.
+        fn mutate(&mut self, c: &Compound, depth: usize) -> Compound {
+            let mut c = c.clone();
+
+            for _ in 0..self.next() % 4 {
+                let key = self.key();
+
+                match (self.next() % 3, c.get_mut(&key)) {
+                    (0, _) => {
+                        c.remove(&key);
+                    }
+                    (1, Some(Value::Compound(nested))) => {
+                        *nested = self.mutate(nested, depth + 1);
+                    }
+                    _ => {
+                        c.insert(key, self.value(depth));
+                    }
+                }
+            }
+
+            c
+        }
+    }
+
+    #[test]
+    fn apply_diff() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+        for i in 0..5000 {
+            let a = rng.compound(0);
+            let b = if i % 4 == 0 {
+                rng.compound(0)
+            } else {
+                rng.mutate(&a, 0)
+            };
+
+            let mut patched = a.clone();
+            patched.apply_patch(a.diff(&b));
+            assert_eq!(patched, b, "{a:?} -> {b:?}");
+
+            assert!(a.diff(&a).is_empty());
+        }
+    }
+
+    #[test]
+    fn apply_patch_to_other_types() {
+        let mut c = compound! { "a" => 1, "b" => compound! { "c" => 2 } };
+
+        c.apply_patch(compound! {
+            "remove" => List::String(vec!["missing".into()]),
+            "patch" => compound! {
+                "a" => compound! { "set" => compound! { "x" => 1 } },
+                "new" => compound! { "set" => compound! { "y" => 2 } },
+                "b" => 5,
+            },
+            "unknown" => 1,
+        });
+
+        assert_eq!(
+            c,
+            compound! {
+                "a" => compound! { "x" => 1 },
+                "b" => compound! { "c" => 2 },
+                "new" => compound! { "y" => 2 },
+            }
+        );
+    }
 }
@@ -19,11 +19,15 @@
 
 pub use compound::Compound;
 pub use tag::Tag;
-pub use value::{List, Value};
+pub use value::{List, Value, ValueRef};
 
 #[cfg(feature = "binary")]
 pub mod binary;
 pub mod compound;
+pub mod packed;
+#[cfg(feature = "snbt")]
+pub mod path;
+pub mod pretty;
 #[cfg(feature = "serde")]
 pub mod serde;
 #[cfg(feature = "snbt")]
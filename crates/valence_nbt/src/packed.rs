@@ -0,0 +1,393 @@
+//! Reading and writing arrays of integers which are packed together into the
+//! elements of an array, such as block states, heightmaps and light.
+
+use std::iter::FusedIterator;
+
+use crate::Value;
+
+/// How entries are packed into the elements of an array.
+///
+/// Entries are stored starting from the least significant bit of the first
+/// element in both layouts.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PackedLayout {
+    /// Entries never span two elements, so the remaining high bits of each
+    /// element are unused if the number of bits per element isn't a multiple
+    /// of the number of bits per entry. This is the layout of Minecraft 1.16
+    /// and later.
+    Padded,
+    /// Entries are packed without any space between them, so an entry may
+    /// begin in one element and end in the next. This is the layout before
+    /// Minecraft 1.16.
+    Unpadded,
+}
+
+impl PackedLayout {
+    /// Returns the number of `u64`s needed to store `len` entries of
+    /// `bits_per_entry` bits each.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits_per_entry` is not in `1..=64`.
+    pub fn long_count(self, bits_per_entry: u32, len: usize) -> usize {
+        assert_bits(bits_per_entry, u64::BITS);
+
+        match self {
+            PackedLayout::Padded => {
+                let per_long = (u64::BITS / bits_per_entry) as usize;
+                len.div_ceil(per_long)
+            }
+            PackedLayout::Unpadded => (len * bits_per_entry as usize).div_ceil(64),
+        }
+    }
+}
+
+impl Value {
+    /// Returns an iterator over the entries packed into this byte, int or long
+    /// array, or `None` if this is not one of those.
+    ///
+    /// The iterator yields every entry that fits in the array, so it includes
+    /// the unused entries at the end of the last element. Use
+    /// [`Iterator::take`] to read only the actual number of entries.
+    ///
+    /// The nibbles of light arrays are read with 4 bits per entry from the
+    /// byte array.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits_per_entry` is zero or greater than the number of bits in
+    /// an element of the array.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valence_nbt::packed::PackedLayout;
+    /// use valence_nbt::Value;
+    ///
+    /// let heightmap = Value::LongArray(vec![0x0020_8631_4841_8841]);
+    ///
+    /// let entries: Vec<_> = heightmap
+    ///     .as_packed_bits(5, PackedLayout::Padded)
+    ///     .unwrap()
+    ///     .collect();
+    ///
+    /// assert_eq!(entries, [1, 2, 2, 3, 4, 4, 5, 6, 6, 4, 8, 0]);
+    /// ```
+    pub fn as_packed_bits(
+        &self,
+        bits_per_entry: u32,
+        layout: PackedLayout,
+    ) -> Option<PackedBits<'_>> {
+        let words = match self {
+            Value::ByteArray(a) => Words::Byte(a),
+            Value::IntArray(a) => Words::Int(a),
+            Value::LongArray(a) => Words::Long(a),
+            _ => return None,
+        };
+
+        Some(PackedBits::new(words, bits_per_entry, layout))
+    }
+
+    /// Packs `entries` of `bits_per_entry` bits each into a new long array.
+    ///
+    /// Only the low `bits_per_entry` bits of each entry are stored. The unused
+    /// bits of the last long are zero.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits_per_entry` is not in `1..=64`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valence_nbt::packed::PackedLayout;
+    /// use valence_nbt::Value;
+    ///
+    /// let entries = [1, 2, 2, 3, 4, 4, 5, 6, 6, 4, 8, 0, 7];
+    /// let padded = Value::from_packed_bits(5, PackedLayout::Padded, entries);
+    /// let unpadded = Value::from_packed_bits(5, PackedLayout::Unpadded, entries);
+    ///
+    /// assert_eq!(padded, Value::LongArray(vec![0x0020_8631_4841_8841, 7]));
+    /// assert_eq!(unpadded, Value::LongArray(vec![0x7020_8631_4841_8841, 0]));
+    /// ```
+    pub fn from_packed_bits(
+        bits_per_entry: u32,
+        layout: PackedLayout,
+        entries: impl IntoIterator<Item = u64>,
+    ) -> Self {
+        assert_bits(bits_per_entry, u64::BITS);
+
+        let mask = mask(bits_per_entry);
+        let mut longs = vec![];
+        // The long being written and the number of bits used in it.
+        let mut long = 0_u64;
+        let mut used = 0;
+
+        for entry in entries {
+            let entry = entry & mask;
+
+            if used == u64::BITS
+                || (layout == PackedLayout::Padded && used + bits_per_entry > u64::BITS)
+            {
+                longs.push(long as i64);
+                long = 0;
+                used = 0;
+            }
+
+            long |= entry << used;
+
+            if used + bits_per_entry > u64::BITS {
+                // The entry continues in the next long.
+                longs.push(long as i64);
+                long = entry >> (u64::BITS - used);
+                used = used + bits_per_entry - u64::BITS;
+            } else {
+                used += bits_per_entry;
+            }
+        }
+
+        if used > 0 {
+            longs.push(long as i64);
+        }
+
+        Value::LongArray(longs)
+    }
+}
+
+/// An iterator over entries packed into an array.
+///
+/// This is returned by [`Value::as_packed_bits`].
+#[derive(Clone, Debug)]
+pub struct PackedBits<'a> {
+    words: Words<'a>,
+    bits_per_entry: u32,
+    layout: PackedLayout,
+    /// The index of the next entry.
+    idx: usize,
+    len: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Words<'a> {
+    Byte(&'a [i8]),
+    Int(&'a [i32]),
+    Long(&'a [i64]),
+}
+
+impl Words<'_> {
+    fn len(self) -> usize {
+        match self {
+            Words::Byte(w) => w.len(),
+            Words::Int(w) => w.len(),
+            Words::Long(w) => w.len(),
+        }
+    }
+
+    fn bits(self) -> u32 {
+        match self {
+            Words::Byte(_) => u8::BITS,
+            Words::Int(_) => u32::BITS,
+            Words::Long(_) => u64::BITS,
+        }
+    }
+
+    fn get(self, idx: usize) -> u64 {
+        match self {
+            Words::Byte(w) => w[idx] as u8 as u64,
+            Words::Int(w) => w[idx] as u32 as u64,
+            Words::Long(w) => w[idx] as u64,
+        }
+    }
+}
+
+impl<'a> PackedBits<'a> {
+    fn new(words: Words<'a>, bits_per_entry: u32, layout: PackedLayout) -> Self {
+        let word_bits = words.bits();
+
+        assert_bits(bits_per_entry, word_bits);
+
+        let len = match layout {
+            PackedLayout::Padded => words.len() * (word_bits / bits_per_entry) as usize,
+            PackedLayout::Unpadded => words.len() * word_bits as usize / bits_per_entry as usize,
+        };
+
+        Self {
+            words,
+            bits_per_entry,
+            layout,
+            idx: 0,
+            len,
+        }
+    }
+
+    fn entry(&self, idx: usize) -> u64 {
+        let word_bits = self.words.bits();
+        let bits = self.bits_per_entry;
+
+        let (word, offset) = match self.layout {
+            PackedLayout::Padded => {
+                let per_word = (word_bits / bits) as usize;
+                (idx / per_word, (idx % per_word) as u32 * bits)
+            }
+            PackedLayout::Unpadded => {
+                let bit = idx * bits as usize;
+                (bit / word_bits as usize, (bit % word_bits as usize) as u32)
+            }
+        };
+
+        let mut entry = self.words.get(word) >> offset;
+
+        if offset + bits > word_bits {
+            entry |= self.words.get(word + 1) << (word_bits - offset);
+        }
+
+        entry & mask(bits)
+    }
+}
+
+impl Iterator for PackedBits<'_> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx == self.len {
+            return None;
+        }
+
+        let entry = self.entry(self.idx);
+        self.idx += 1;
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.len - self.idx;
+        (remaining, Some(remaining))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.idx = self.idx.saturating_add(n).min(self.len);
+        self.next()
+    }
+}
+
+impl ExactSizeIterator for PackedBits<'_> {}
+
+impl FusedIterator for PackedBits<'_> {}
+
+fn assert_bits(bits_per_entry: u32, word_bits: u32) {
+    assert!(
+        (1..=word_bits).contains(&bits_per_entry),
+        "{bits_per_entry} bits per entry is not in 1..={word_bits}"
+    );
+}
+
+fn mask(bits: u32) -> u64 {
+    u64::MAX >> (u64::BITS - bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The example section data from <https://wiki.vg/Chunk_Format>, which
+    /// has 5 bits per entry.
+    const PADDED: [i64; 1] = [0x0020_8631_4841_8841];
+    const UNPADDED: [i64; 2] = [0x7020_8631_4841_8841, 0x8b10_18a7_260f_68c8_u64 as i64];
+    const ENTRIES: [u64; 24] = [
+        1, 2, 2, 3, 4, 4, 5, 6, 6, 4, 8, 0, 7, 4, 3, 13, 15, 16, 9, 14, 10, 12, 0, 2,
+    ];
+
+    fn unpack(value: &Value, bits: u32, layout: PackedLayout) -> Vec<u64> {
+        value.as_packed_bits(bits, layout).unwrap().collect()
+    }
+
+    #[test]
+    fn wiki_examples() {
+        let padded = Value::LongArray(PADDED.to_vec());
+        assert_eq!(unpack(&padded, 5, PackedLayout::Padded), ENTRIES[..12]);
+        assert_eq!(
+            Value::from_packed_bits(5, PackedLayout::Padded, ENTRIES[..12].iter().copied()),
+            padded
+        );
+
+        let unpadded = Value::LongArray(UNPADDED.to_vec());
+        // The last 3 bits are the beginning of a 25th entry.
+        assert_eq!(unpack(&unpadded, 5, PackedLayout::Unpadded)[..24], ENTRIES);
+        assert_eq!(
+            Value::from_packed_bits(5, PackedLayout::Unpadded, ENTRIES.iter().copied()),
+            Value::LongArray(vec![UNPADDED[0], UNPADDED[1] & 0xff_ffff_ffff_ffff])
+        );
+    }
+
+    #[test]
+    fn heightmap_round_trip() {
+        // Heightmaps of a 384 block tall world have 9 bits per entry.
+        let heights: Vec<u64> = (0..256).map(|i| (i * 37 + i / 16) % 385).collect();
+
+        for layout in [PackedLayout::Padded, PackedLayout::Unpadded] {
+            let packed = Value::from_packed_bits(9, layout, heights.iter().copied());
+
+            assert_eq!(
+                packed.as_long_array().unwrap().len(),
+                layout.long_count(9, heights.len())
+            );
+
+            let unpacked: Vec<_> = packed
+                .as_packed_bits(9, layout)
+                .unwrap()
+                .take(heights.len())
+                .collect();
+
+            assert_eq!(unpacked, heights);
+        }
+
+        assert_eq!(PackedLayout::Padded.long_count(9, 256), 37);
+        assert_eq!(PackedLayout::Unpadded.long_count(9, 256), 36);
+    }
+
+    #[test]
+    fn all_widths_round_trip() {
+        for bits in 1..=64 {
+            let entries: Vec<u64> = (0..100_u64)
+                .map(|i| i.wrapping_mul(0x9e37_79b9_7f4a_7c15) & mask(bits))
+                .collect();
+
+            for layout in [PackedLayout::Padded, PackedLayout::Unpadded] {
+                let packed = Value::from_packed_bits(bits, layout, entries.iter().copied());
+                let mut iter = packed.as_packed_bits(bits, layout).unwrap();
+
+                assert!(iter.len() >= entries.len(), "{bits} {layout:?}");
+                assert_eq!(iter.by_ref().take(100).collect::<Vec<_>>(), entries);
+                assert!(iter.all(|e| e == 0), "{bits} {layout:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn light_nibbles() {
+        let light = Value::ByteArray(vec![0x21, 0xf3_u8 as i8, 0x0e]);
+
+        assert_eq!(
+            unpack(&light, 4, PackedLayout::Padded),
+            [1, 2, 3, 15, 14, 0]
+        );
+
+        assert_eq!(
+            unpack(&Value::IntArray(vec![-1]), 3, PackedLayout::Padded),
+            [7; 10]
+        );
+        assert_eq!(
+            unpack(&Value::IntArray(vec![-1, 0]), 3, PackedLayout::Unpadded),
+            [7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+
+        assert!(Value::Long(5)
+            .as_packed_bits(4, PackedLayout::Padded)
+            .is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_many_bits_for_bytes() {
+        let _ = Value::ByteArray(vec![]).as_packed_bits(9, PackedLayout::Padded);
+    }
+}
@@ -0,0 +1,839 @@
+//! Looking up and changing values with NBT paths, like the ones used by
+//! vanilla's `/data` command.
+//!
+//! A path is a list of nodes separated by `.`. Each node is one of:
+//!
+//! - `key` or `"quoted key"`: the value of a key in a compound.
+//! - `key{...}`: the value of a key, if it is a compound matching the SNBT
+//!   compound in braces.
+//! - `{...}`: the root compound, if it matches. Only allowed at the start.
+//! - `[N]`: the element at index `N` of a list or array. Negative indices count
+//!   from the end.
+//! - `[]`: every element of a list or array.
+//! - `[{...}]`: every compound in a list that matches.
+//!
+//! A compound matches another if it has every key of it with a matching value.
+//! Lists in matchers match lists containing a matching element for each of
+//! their elements.
+//!
+//! # Examples
+//!
+//! ```
+//! use valence_nbt::snbt::from_snbt_str;
+//! use valence_nbt::ValueRef;
+//!
+//! let item = from_snbt_str(r#"{tag: {display: {Lore: ['"First"', '"Second"']}}}"#)
+//!     .unwrap()
+//!     .into_compound()
+//!     .unwrap();
+//!
+//! assert_eq!(
+//!     item.get_path("tag.display.Lore[-1]"),
+//!     Some(ValueRef::String(r#""Second""#))
+//! );
+//! ```
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::snbt::{SnbtErrorKind, SnbtReader};
+use crate::{Compound, List, Value, ValueRef};
+
+impl Compound {
+    /// Returns the first value that `path` leads to, or `None` if there isn't
+    /// one or the path is invalid.
+    ///
+    /// Use [`NbtPath`] to parse the path only once.
+    pub fn get_path(&self, path: &str) -> Option<ValueRef<'_>> {
+        path.parse::<NbtPath>().ok()?.get(self)
+    }
+
+    /// Sets every value that `path` leads to to `value`, creating missing
+    /// compounds and lists along the way. Returns the number of values that
+    /// were changed.
+    ///
+    /// See [`NbtPath::set`] for details.
+    pub fn set_path(&mut self, path: &str, value: impl Into<Value>) -> Result<usize, NbtPathError> {
+        Ok(path.parse::<NbtPath>()?.set(self, value))
+    }
+}
+
+/// A parsed NBT path. See the [module documentation](self) for the syntax.
+#[derive(Clone, PartialEq, Debug)]
+pub struct NbtPath {
+    nodes: Vec<Node>,
+    original: String,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Node {
+    /// `{...}` at the start of a path.
+    MatchRoot(Compound),
+    /// `key`
+    Key(String),
+    /// `key{...}`
+    MatchKey(String, Compound),
+    /// `[N]`
+    Index(i32),
+    /// `[]`
+    AllElements,
+    /// `[{...}]`
+    MatchElement(Compound),
+}
+
+impl NbtPath {
+    /// Returns the first value that this path leads to in `root`.
+    pub fn get<'a>(&self, root: &'a Compound) -> Option<ValueRef<'a>> {
+        self.get_all(root).into_iter().next()
+    }
+
+    /// Returns every value that this path leads to in `root`.
+    pub fn get_all<'a>(&self, root: &'a Compound) -> Vec<ValueRef<'a>> {
+        let mut values = vec![ValueRef::Compound(root)];
+
+        for node in &self.nodes {
+            let mut next = vec![];
+
+            for value in values {
+                node.get(value, &mut next);
+            }
+
+            values = next;
+
+            if values.is_empty() {
+                break;
+            }
+        }
+
+        values
+    }
+
+    /// Sets every value that this path leads to in `root` to `value`. Returns
+    /// the number of values that were changed.
+    ///
+    /// Like in vanilla, keys that are missing along the way are created with an
+    /// empty compound or list, depending on the node after them. Lists that a
+    /// `[]` or `[{...}]` node finds nothing in get a new element. Values of a
+    /// different type than the elements of the list they would be put in are
+    /// not set.
+    pub fn set(&self, root: &mut Compound, value: impl Into<Value>) -> usize {
+        let value = value.into();
+        let (last, parents) = self.nodes.split_last().expect("path is not empty");
+
+        let mut targets = vec![Target::Compound(root)];
+
+        for (i, node) in parents.iter().enumerate() {
+            let mut next = vec![];
+
+            for target in targets {
+                node.get_or_create(target, &self.nodes[i + 1], &mut next);
+            }
+
+            targets = next;
+        }
+
+        targets
+            .into_iter()
+            .map(|target| last.set(target, &value))
+            .sum()
+    }
+}
+
+impl Node {
+    fn get<'a>(&self, value: ValueRef<'a>, out: &mut Vec<ValueRef<'a>>) {
+        match (self, value) {
+            (Node::MatchRoot(pattern), ValueRef::Compound(c)) if compound_matches(pattern, c) => {
+                out.push(value);
+            }
+            (Node::Key(key), ValueRef::Compound(c)) => {
+                out.extend(c.get(key.as_str()).map(ValueRef::from));
+            }
+            (Node::MatchKey(key, pattern), ValueRef::Compound(c)) => {
+                if let Some(Value::Compound(child)) = c.get(key.as_str()) {
+                    if compound_matches(pattern, child) {
+                        out.push(ValueRef::Compound(child));
+                    }
+                }
+            }
+            (Node::Index(idx), _) => {
+                if let Some(idx) = resolve_index(*idx, collection_len(value)) {
+                    out.extend(element(value, idx));
+                }
+            }
+            (Node::AllElements, _) => {
+                out.extend((0..collection_len(value)).filter_map(|idx| element(value, idx)));
+            }
+            (Node::MatchElement(pattern), ValueRef::List(List::Compound(list))) => {
+                out.extend(
+                    list.iter()
+                        .filter(|c| compound_matches(pattern, c))
+                        .map(ValueRef::Compound),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    fn get_or_create<'a>(&self, target: Target<'a>, next: &Node, out: &mut Vec<Target<'a>>) {
+        match (self, target) {
+            (Node::MatchRoot(pattern), Target::Compound(c)) if compound_matches(pattern, c) => {
+                out.push(Target::Compound(c));
+            }
+            (Node::Key(key), Target::Compound(c)) => {
+                let child = c.entry(key.as_str()).or_insert_with(|| next.new_parent());
+                out.extend(Target::from_value(child));
+            }
+            (Node::MatchKey(key, pattern), Target::Compound(c)) => {
+                let child = c.entry(key.as_str()).or_insert_with(|| pattern.clone());
+
+                if let Value::Compound(child) = child {
+                    if compound_matches(pattern, child) {
+                        out.push(Target::Compound(child));
+                    }
+                }
+            }
+            (Node::Index(idx), target) => {
+                if let Some(idx) = resolve_index(*idx, target.len()) {
+                    out.extend(target.into_child(idx));
+                }
+            }
+            (Node::AllElements, Target::List(list)) => {
+                if list.is_empty() {
+                    push(list, next.new_parent());
+                }
+
+                out.extend(Target::List(list).into_children());
+            }
+            (Node::MatchElement(pattern), Target::List(list)) => {
+                let found = match list {
+                    List::End => false,
+                    List::Compound(l) => l.iter().any(|c| compound_matches(pattern, c)),
+                    _ => return,
+                };
+
+                if !found {
+                    push(list, pattern.clone().into());
+                }
+
+                if let List::Compound(l) = list {
+                    out.extend(
+                        l.iter_mut()
+                            .filter(|c| compound_matches(pattern, c))
+                            .map(Target::Compound),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn set(&self, target: Target, value: &Value) -> usize {
+        match (self, target) {
+            (Node::Key(key), Target::Compound(c)) => {
+                (c.insert(key.as_str(), value.clone()).as_ref() != Some(value)) as usize
+            }
+            (Node::MatchKey(key, pattern), Target::Compound(c)) => match c.get(key.as_str()) {
+                Some(Value::Compound(child)) if compound_matches(pattern, child) => {
+                    let changed = !matches!(value, Value::Compound(v) if v == child);
+                    c.insert(key.as_str(), value.clone());
+                    changed as usize
+                }
+                _ => 0,
+            },
+            (Node::Index(idx), mut target) => match resolve_index(*idx, target.len()) {
+                Some(idx) => target.set_element(idx, value) as usize,
+                None => 0,
+            },
+            (Node::AllElements, Target::List(list)) if list.is_empty() => {
+                push(list, value.clone()) as usize
+            }
+            (Node::AllElements, mut target) => (0..target.len())
+                .filter(|&idx| target.set_element(idx, value))
+                .count(),
+            (Node::MatchElement(pattern), Target::List(List::Compound(list))) => {
+                let Value::Compound(value) = value else {
+                    return 0;
+                };
+
+                let mut changed = 0;
+
+                for c in list.iter_mut().filter(|c| compound_matches(pattern, c)) {
+                    if c != value {
+                        *c = value.clone();
+                        changed += 1;
+                    }
+                }
+
+                changed
+            }
+            _ => 0,
+        }
+    }
+
+    /// Returns an empty value that this node can find something in.
+    fn new_parent(&self) -> Value {
+        match self {
+            Node::Index(_) | Node::AllElements | Node::MatchElement(_) => List::End.into(),
+            _ => Compound::new().into(),
+        }
+    }
+}
+
+/// A value that a path can continue into while setting.
+enum Target<'a> {
+    Compound(&'a mut Compound),
+    List(&'a mut List),
+    ByteArray(&'a mut Vec<i8>),
+    IntArray(&'a mut Vec<i32>),
+    LongArray(&'a mut Vec<i64>),
+}
+
+impl<'a> Target<'a> {
+    fn from_value(value: &'a mut Value) -> Option<Self> {
+        match value {
+            Value::Compound(c) => Some(Target::Compound(c)),
+            Value::List(l) => Some(Target::List(l)),
+            Value::ByteArray(a) => Some(Target::ByteArray(a)),
+            Value::IntArray(a) => Some(Target::IntArray(a)),
+            Value::LongArray(a) => Some(Target::LongArray(a)),
+            _ => None,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Target::Compound(_) => 0,
+            Target::List(l) => l.len(),
+            Target::ByteArray(a) => a.len(),
+            Target::IntArray(a) => a.len(),
+            Target::LongArray(a) => a.len(),
+        }
+    }
+
+    fn into_child(self, idx: usize) -> Option<Target<'a>> {
+        match self {
+            Target::List(List::List(l)) => l.get_mut(idx).map(Target::List),
+            Target::List(List::Compound(l)) => l.get_mut(idx).map(Target::Compound),
+            Target::List(List::ByteArray(l)) => l.get_mut(idx).map(Target::ByteArray),
+            Target::List(List::IntArray(l)) => l.get_mut(idx).map(Target::IntArray),
+            Target::List(List::LongArray(l)) => l.get_mut(idx).map(Target::LongArray),
+            _ => None,
+        }
+    }
+
+    fn into_children(self) -> Vec<Target<'a>> {
+        match self {
+            Target::List(List::List(l)) => l.iter_mut().map(Target::List).collect(),
+            Target::List(List::Compound(l)) => l.iter_mut().map(Target::Compound).collect(),
+            Target::List(List::ByteArray(l)) => l.iter_mut().map(Target::ByteArray).collect(),
+            Target::List(List::IntArray(l)) => l.iter_mut().map(Target::IntArray).collect(),
+            Target::List(List::LongArray(l)) => l.iter_mut().map(Target::LongArray).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Replaces the element at `idx`, which must be in bounds. Returns whether
+    /// it was changed.
+    fn set_element(&mut self, idx: usize, value: &Value) -> bool {
+        fn replace<T: Clone + PartialEq>(slot: &mut T, value: &T) -> bool {
+            let changed = slot != value;
+            *slot = value.clone();
+            changed
+        }
+
+        match (self, value) {
+            (Target::List(List::Byte(l)), Value::Byte(v)) => replace(&mut l[idx], v),
+            (Target::List(List::Short(l)), Value::Short(v)) => replace(&mut l[idx], v),
+            (Target::List(List::Int(l)), Value::Int(v)) => replace(&mut l[idx], v),
+            (Target::List(List::Long(l)), Value::Long(v)) => replace(&mut l[idx], v),
+            (Target::List(List::Float(l)), Value::Float(v)) => replace(&mut l[idx], v),
+            (Target::List(List::Double(l)), Value::Double(v)) => replace(&mut l[idx], v),
+            (Target::List(List::ByteArray(l)), Value::ByteArray(v)) => replace(&mut l[idx], v),
+            (Target::List(List::String(l)), Value::String(v)) => replace(&mut l[idx], v),
+            (Target::List(List::List(l)), Value::List(v)) => replace(&mut l[idx], v),
+            (Target::List(List::Compound(l)), Value::Compound(v)) => replace(&mut l[idx], v),
+            (Target::List(List::IntArray(l)), Value::IntArray(v)) => replace(&mut l[idx], v),
+            (Target::List(List::LongArray(l)), Value::LongArray(v)) => replace(&mut l[idx], v),
+            (Target::ByteArray(a), Value::Byte(v)) => replace(&mut a[idx], v),
+            (Target::IntArray(a), Value::Int(v)) => replace(&mut a[idx], v),
+            (Target::LongArray(a), Value::Long(v)) => replace(&mut a[idx], v),
+            _ => false,
+        }
+    }
+}
+
+/// Appends `value` to `list` if it has the same type as the elements.
+fn push(list: &mut List, value: Value) -> bool {
+    if let List::End = list {
+        *list = match value {
+            Value::Byte(v) => List::Byte(vec![v]),
+            Value::Short(v) => List::Short(vec![v]),
+            Value::Int(v) => List::Int(vec![v]),
+            Value::Long(v) => List::Long(vec![v]),
+            Value::Float(v) => List::Float(vec![v]),
+            Value::Double(v) => List::Double(vec![v]),
+            Value::ByteArray(v) => List::ByteArray(vec![v]),
+            Value::String(v) => List::String(vec![v]),
+            Value::List(v) => List::List(vec![v]),
+            Value::Compound(v) => List::Compound(vec![v]),
+            Value::IntArray(v) => List::IntArray(vec![v]),
+            Value::LongArray(v) => List::LongArray(vec![v]),
+        };
+
+        return true;
+    }
+
+    match (list, value) {
+        (List::Byte(l), Value::Byte(v)) => l.push(v),
+        (List::Short(l), Value::Short(v)) => l.push(v),
+        (List::Int(l), Value::Int(v)) => l.push(v),
+        (List::Long(l), Value::Long(v)) => l.push(v),
+        (List::Float(l), Value::Float(v)) => l.push(v),
+        (List::Double(l), Value::Double(v)) => l.push(v),
+        (List::ByteArray(l), Value::ByteArray(v)) => l.push(v),
+        (List::String(l), Value::String(v)) => l.push(v),
+        (List::List(l), Value::List(v)) => l.push(v),
+        (List::Compound(l), Value::Compound(v)) => l.push(v),
+        (List::IntArray(l), Value::IntArray(v)) => l.push(v),
+        (List::LongArray(l), Value::LongArray(v)) => l.push(v),
+        _ => return false,
+    }
+
+    true
+}
+
+fn collection_len(value: ValueRef) -> usize {
+    match value {
+        ValueRef::List(l) => l.len(),
+        ValueRef::ByteArray(a) => a.len(),
+        ValueRef::IntArray(a) => a.len(),
+        ValueRef::LongArray(a) => a.len(),
+        _ => 0,
+    }
+}
+
+fn element(value: ValueRef<'_>, idx: usize) -> Option<ValueRef<'_>> {
+    match value {
+        ValueRef::List(l) => l.get(idx),
+        ValueRef::ByteArray(a) => a.get(idx).map(|v| ValueRef::Byte(*v)),
+        ValueRef::IntArray(a) => a.get(idx).map(|v| ValueRef::Int(*v)),
+        ValueRef::LongArray(a) => a.get(idx).map(|v| ValueRef::Long(*v)),
+        _ => None,
+    }
+}
+
+/// Turns a possibly negative index into an index into a collection of length
+/// `len`.
+fn resolve_index(idx: i32, len: usize) -> Option<usize> {
+    let idx = if idx < 0 {
+        len as i64 + idx as i64
+    } else {
+        idx as i64
+    };
+
+    (0..len as i64).contains(&idx).then_some(idx as usize)
+}
+
+fn compound_matches(pattern: &Compound, target: &Compound) -> bool {
+    pattern.iter().all(|(key, pattern)| {
+        target
+            .get(key.as_str())
+            .is_some_and(|target| matches(pattern.into(), target.into()))
+    })
+}
+
+/// Returns whether `target` matches `pattern`, where compounds only need to
+/// have the keys of the pattern and lists only need an element matching each
+/// element of the pattern.
+fn matches(pattern: ValueRef, target: ValueRef) -> bool {
+    match (pattern, target) {
+        (ValueRef::Compound(pattern), ValueRef::Compound(target)) => {
+            compound_matches(pattern, target)
+        }
+        (ValueRef::List(pattern), ValueRef::List(target)) => {
+            if pattern.is_empty() {
+                target.is_empty()
+            } else {
+                pattern.iter().all(|p| target.iter().any(|t| matches(p, t)))
+            }
+        }
+        (ValueRef::Compound(_) | ValueRef::List(_), _) => false,
+        (pattern, target) => pattern == target,
+    }
+}
+
+impl FromStr for NbtPath {
+    type Err = NbtPathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { input: s, pos: 0 };
+        let mut nodes = vec![];
+
+        loop {
+            nodes.push(parser.parse_node(nodes.is_empty())?);
+
+            match parser.peek() {
+                None => break,
+                Some('[' | '{') => {}
+                Some('.') => parser.pos += 1,
+                Some(_) => return Err(parser.error(NbtPathErrorKind::ExpectDot)),
+            }
+        }
+
+        Ok(Self {
+            nodes,
+            original: s.to_owned(),
+        })
+    }
+}
+
+impl Display for NbtPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.original)
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn error(&self, error_type: NbtPathErrorKind) -> NbtPathError {
+        NbtPathError {
+            error_type,
+            position: self.pos,
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), NbtPathError> {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            Ok(())
+        } else {
+            Err(self.error(NbtPathErrorKind::ExpectChar(c)))
+        }
+    }
+
+    fn parse_node(&mut self, first: bool) -> Result<Node, NbtPathError> {
+        match self.peek() {
+            Some('{') if first => Ok(Node::MatchRoot(self.parse_compound()?)),
+            Some('[') => {
+                self.pos += 1;
+
+                let node = match self.peek() {
+                    Some('{') => Node::MatchElement(self.parse_compound()?),
+                    Some(']') => Node::AllElements,
+                    _ => Node::Index(self.parse_index()?),
+                };
+
+                self.expect(']')?;
+                Ok(node)
+            }
+            Some(quote @ ('"' | '\'')) => {
+                let key = self.parse_quoted(quote)?;
+                self.parse_key_node(key)
+            }
+            Some(c) if is_unquoted(c) => {
+                let len = self.input[self.pos..]
+                    .find(|c| !is_unquoted(c))
+                    .unwrap_or(self.input.len() - self.pos);
+
+                let key = self.input[self.pos..self.pos + len].to_owned();
+                self.pos += len;
+
+                self.parse_key_node(key)
+            }
+            _ => Err(self.error(NbtPathErrorKind::InvalidNode)),
+        }
+    }
+
+    fn parse_key_node(&mut self, key: String) -> Result<Node, NbtPathError> {
+        if self.peek() == Some('{') {
+            Ok(Node::MatchKey(key, self.parse_compound()?))
+        } else {
+            Ok(Node::Key(key))
+        }
+    }
+
+    fn parse_compound(&mut self) -> Result<Compound, NbtPathError> {
+        let mut reader = SnbtReader::new(&self.input[self.pos..]);
+
+        match reader.parse_element() {
+            Ok(Value::Compound(c)) => {
+                self.pos += reader.bytes_read();
+                Ok(c)
+            }
+            Ok(_) => Err(self.error(NbtPathErrorKind::InvalidNode)),
+            Err(e) => Err(self.error(NbtPathErrorKind::Snbt(e.error_type))),
+        }
+    }
+
+    fn parse_index(&mut self) -> Result<i32, NbtPathError> {
+        let len = self.input[self.pos..]
+            .find(|c: char| !(c.is_ascii_digit() || c == '-'))
+            .unwrap_or(self.input.len() - self.pos);
+
+        let idx = self.input[self.pos..self.pos + len]
+            .parse()
+            .map_err(|_| self.error(NbtPathErrorKind::InvalidIndex))?;
+
+        self.pos += len;
+        Ok(idx)
+    }
+
+    fn parse_quoted(&mut self, quote: char) -> Result<String, NbtPathError> {
+        self.pos += 1;
+
+        let mut key = String::new();
+        let mut chars = self.input[self.pos..].chars();
+
+        while let Some(c) = chars.next() {
+            self.pos += c.len_utf8();
+
+            if c == quote {
+                return Ok(key);
+            }
+
+            if c == '\\' {
+                match chars.next() {
+                    Some(c) if c == quote || c == '\\' => {
+                        self.pos += 1;
+                        key.push(c);
+                    }
+                    _ => return Err(self.error(NbtPathErrorKind::InvalidEscapeSequence)),
+                }
+            } else {
+                key.push(c);
+            }
+        }
+
+        Err(self.error(NbtPathErrorKind::ReachEndOfStream))
+    }
+}
+
+fn is_unquoted(c: char) -> bool {
+    !matches!(c, ' ' | '"' | '\'' | '[' | ']' | '.' | '{' | '}')
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Copy)]
+pub enum NbtPathErrorKind {
+    ReachEndOfStream,
+    InvalidNode,
+    InvalidIndex,
+    InvalidEscapeSequence,
+    ExpectChar(char),
+    ExpectDot,
+    Snbt(SnbtErrorKind),
+}
+
+impl Display for NbtPathErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        use NbtPathErrorKind::*;
+        match self {
+            ReachEndOfStream => write!(f, "reach end of stream"),
+            InvalidNode => write!(f, "invalid node"),
+            InvalidIndex => write!(f, "invalid index"),
+            InvalidEscapeSequence => write!(f, "invalid escape sequence"),
+            ExpectChar(c) => write!(f, "expect '{c}'"),
+            ExpectDot => write!(f, "expect '.'"),
+            Snbt(e) => write!(f, "invalid compound: {e}"),
+        }
+    }
+}
+
+/// An error from parsing an [`NbtPath`].
+#[derive(Debug, Clone, PartialEq, Eq, Copy)]
+pub struct NbtPathError {
+    pub error_type: NbtPathErrorKind,
+    /// The byte offset in the path where the error was found.
+    pub position: usize,
+}
+
+impl Display for NbtPathError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "@ {}: {}", self.position, self.error_type)
+    }
+}
+
+impl Error for NbtPathError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snbt::{from_snbt_str, to_snbt_string};
+
+    fn snbt(s: &str) -> Compound {
+        from_snbt_str(s).unwrap().into_compound().unwrap()
+    }
+
+    fn get_all(c: &Compound, path: &str) -> Vec<String> {
+        path.parse::<NbtPath>()
+            .unwrap()
+            .get_all(c)
+            .into_iter()
+            .map(|v| to_snbt_string(&v.to_value()))
+            .collect()
+    }
+
+    #[test]
+    fn wiki_examples() {
+        let c = snbt(
+            r#"{
+                foo: {
+                    bar: [
+                        {baz: 5b, "A [crazy name]!": {baz: "x"}},
+                        {baz: 3b, qux: [1, 2]}
+                    ]
+                },
+                "A [crazy name]!": {baz: 1},
+                ints: [I; 10, 20, 30]
+            }"#,
+        );
+
+        assert_eq!(get_all(&c, "foo.bar[0].baz"), ["5b"]);
+        assert_eq!(get_all(&c, "foo.bar[-1].baz"), ["3b"]);
+        assert_eq!(get_all(&c, "foo.bar[].baz"), ["5b", "3b"]);
+        assert_eq!(get_all(&c, "foo.bar[{baz:3b}].qux[1]"), ["2"]);
+        assert_eq!(get_all(&c, r#""A [crazy name]!".baz"#), ["1"]);
+        assert_eq!(get_all(&c, r#"foo.bar[0]."A [crazy name]!".baz"#), ["x"]);
+        assert_eq!(get_all(&c, "{ints:[I;10,20,30]}.ints[1]"), ["20"]);
+        assert_eq!(get_all(&c, "ints[]"), ["10", "20", "30"]);
+        assert_eq!(get_all(&c, "foo{bar:[{baz:3b}]}.bar[0].baz"), ["5b"]);
+        assert_eq!(get_all(&c, "foo.bar[{qux:[2]}].baz"), ["3b"]);
+
+        // Paths that don't lead anywhere.
+        assert!(get_all(&c, "foo.bar[2]").is_empty());
+        assert!(get_all(&c, "foo.bar[-3]").is_empty());
+        assert!(get_all(&c, "foo{bar:[]}").is_empty());
+        assert!(get_all(&c, "{foo:{}, missing:1}").is_empty());
+        assert!(get_all(&c, "foo.bar[{baz:4b}]").is_empty());
+        assert!(get_all(&c, "foo.bar.baz").is_empty());
+    }
+
+    #[test]
+    fn item_paths() {
+        let player = snbt(
+            r#"{
+                Inventory: [
+                    {Slot: 0b, id: "minecraft:diamond_sword", Count: 1b, tag: {display: {Name: '{"text":"Sword"}', Lore: ['"a"', '"b"']}}},
+                    {Slot: 1b, id: "minecraft:apple", Count: 3b}
+                ]
+            }"#,
+        );
+
+        assert_eq!(
+            player.get_path("Inventory[{Slot:1b}].id"),
+            Some(ValueRef::String("minecraft:apple"))
+        );
+        assert_eq!(
+            player.get_path("Inventory[0].tag.display.Lore[0]"),
+            Some(ValueRef::String(r#""a""#))
+        );
+        assert_eq!(player.get_path("Inventory[{Slot:2b}].id"), None);
+        assert_eq!(player.get_path("Inventory[{Slot:"), None);
+
+        let path: NbtPath = "Inventory[].Count".parse().unwrap();
+        assert_eq!(path.to_string(), "Inventory[].Count");
+        assert_eq!(
+            path.get_all(&player),
+            [ValueRef::Byte(1), ValueRef::Byte(3)]
+        );
+    }
+
+    #[test]
+    fn set_values() {
+        let mut c = snbt("{Items: [{Slot: 0b, id: \"stone\"}, {Slot: 1b, id: \"dirt\"}]}");
+
+        assert_eq!(c.set_path("Items[{Slot:1b}].id", "grass"), Ok(1));
+        assert_eq!(c.set_path("Items[].Count", 64_i8), Ok(2));
+        assert_eq!(c.set_path("Items[].Count", 64_i8), Ok(0));
+        assert_eq!(c.set_path("Items[-1].Slot", 5_i8), Ok(1));
+        assert_eq!(
+            c,
+            snbt(
+                r#"{Items: [{Slot: 0b, id: "stone", Count: 64b}, {Slot: 5b, id: "grass", Count: 64b}]}"#
+            )
+        );
+
+        // Elements of a different type aren't set.
+        let mut c = snbt("{list: [1, 2], ints: [I; 1, 2]}");
+        assert_eq!(c.set_path("list[0]", "str"), Ok(0));
+        assert_eq!(c.set_path("list[]", 7), Ok(2));
+        assert_eq!(c.set_path("ints[5]", 7), Ok(0));
+        assert_eq!(c.set_path("ints[-2]", 3), Ok(1));
+        assert_eq!(c, snbt("{list: [7, 7], ints: [I; 3, 2]}"));
+
+        assert!(c.set_path("list[", 1).is_err());
+    }
+
+    #[test]
+    fn set_creates_parents() {
+        let mut c = Compound::new();
+
+        assert_eq!(c.set_path("tag.display.Name", "\"x\""), Ok(1));
+        assert_eq!(c.set_path("tag.display.Lore[]", "\"a\""), Ok(1));
+        assert_eq!(c.set_path("Items[{Slot:3b}].id", "stone"), Ok(1));
+        assert_eq!(c.set_path("Items[{Slot:4b}].id", "dirt"), Ok(1));
+        assert_eq!(c.set_path("a{b:1}.c", 2), Ok(1));
+        assert_eq!(c.set_path("grid[][]", 1), Ok(1));
+
+        // Indices don't create anything.
+        assert_eq!(c.set_path("missing[0].a", 1), Ok(0));
+
+        assert_eq!(
+            c,
+            snbt(
+                r#"{
+                    tag: {display: {Name: '"x"', Lore: ['"a"']}},
+                    Items: [{Slot: 3b, id: "stone"}, {Slot: 4b, id: "dirt"}],
+                    a: {b: 1, c: 2},
+                    grid: [[1]],
+                    missing: []
+                }"#
+            )
+        );
+
+        // A matching root is required.
+        assert_eq!(c.set_path("{a:{b:2}}.x", 1), Ok(0));
+        assert_eq!(c.set_path("{a:{b:1}}.x", 1), Ok(1));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = |path: &str| path.parse::<NbtPath>().unwrap_err();
+
+        assert_eq!(err("").error_type, NbtPathErrorKind::InvalidNode);
+        assert_eq!(err("foo.").position, 4);
+        assert_eq!(err("foo..bar").error_type, NbtPathErrorKind::InvalidNode);
+        assert_eq!(err("foo[").error_type, NbtPathErrorKind::InvalidIndex);
+        assert_eq!(err("foo[x]").error_type, NbtPathErrorKind::InvalidIndex);
+        assert_eq!(err("foo[1").error_type, NbtPathErrorKind::ExpectChar(']'));
+        assert_eq!(err("foo.{a:1}").error_type, NbtPathErrorKind::InvalidNode);
+        assert_eq!(err("foo bar").error_type, NbtPathErrorKind::ExpectDot);
+        assert_eq!(err("\"foo").error_type, NbtPathErrorKind::ReachEndOfStream);
+        assert_eq!(
+            err(r#""fo\o""#).error_type,
+            NbtPathErrorKind::InvalidEscapeSequence
+        );
+        assert!(matches!(
+            err("foo[{a:}]").error_type,
+            NbtPathErrorKind::Snbt(_)
+        ));
+
+        let path: NbtPath = r#"'it\'s'."a \"b\"".c"#.parse().unwrap();
+        assert_eq!(
+            path.nodes,
+            [
+                Node::Key("it's".into()),
+                Node::Key("a \"b\"".into()),
+                Node::Key("c".into())
+            ]
+        );
+    }
+}
@@ -0,0 +1,508 @@
+//! Human readable, indented output for NBT values.
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::{Compound, List, Value, ValueRef};
+
+impl Value {
+    /// Returns a [`PrettyDisplay`] that formats this value over multiple
+    /// indented lines. See [`PrettyDisplay`] for the available options.
+    pub fn display_pretty(&self) -> PrettyDisplay<'_> {
+        PrettyDisplay::new(self.into())
+    }
+
+    /// Formats this value over multiple lines, indenting nested values by
+    /// `indent` spaces. The output is valid SNBT.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use valence_nbt::{compound, List, Value};
+    ///
+    /// let item = Value::from(compound! {
+    ///     "Count" => 1_i8,
+    ///     "id" => "minecraft:bow",
+    ///     "tag" => compound! {
+    ///         "Enchantments" => List::Compound(vec![compound! {
+    ///             "id" => "minecraft:power",
+    ///             "lvl" => 5_i16,
+    ///         }]),
+    ///     },
+    /// });
+    ///
+    /// assert_eq!(
+    ///     item.fmt_pretty(2),
+    ///     r#"{
+    ///   Count: 1b,
+    ///   id: "minecraft:bow",
+    ///   tag: {
+    ///     Enchantments: [
+    ///       {
+    ///         id: "minecraft:power",
+    ///         lvl: 5s
+    ///       }
+    ///     ]
+    ///   }
+    /// }"#
+    /// );
+    /// ```
+    pub fn fmt_pretty(&self, indent: usize) -> String {
+        self.display_pretty().indent(indent).to_string()
+    }
+}
+
+impl Compound {
+    /// Returns a [`PrettyDisplay`] that formats this compound over multiple
+    /// indented lines. See [`PrettyDisplay`] for the available options.
+    pub fn display_pretty(&self) -> PrettyDisplay<'_> {
+        PrettyDisplay::new(ValueRef::Compound(self))
+    }
+}
+
+/// Formats an NBT value in SNBT over multiple indented lines, for logging and
+/// debugging.
+///
+/// Compounds and lists of compounds, lists or arrays are written with one
+/// element per line, while arrays and lists of other values are written on a
+/// single line.
+///
+/// The output can be colored with ANSI escape codes, and large values can be
+/// truncated with [`max_depth`](Self::max_depth) and
+/// [`max_len`](Self::max_len). The output is only valid SNBT if it is neither
+/// colored nor truncated.
+///
+/// # Example
+///
+/// ```
+/// use valence_nbt::compound;
+///
+/// let chunk = compound! {
+///     "xPos" => 3,
+///     "sections" => valence_nbt::List::Compound(vec![compound! {
+///         "Y" => 0_i8,
+///         "SkyLight" => vec![-1_i8; 2048],
+///     }]),
+/// };
+///
+/// let output = chunk.display_pretty().max_len(3).color(true).to_string();
+/// println!("{output}");
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct PrettyDisplay<'a> {
+    value: ValueRef<'a>,
+    indent: usize,
+    color: bool,
+    max_depth: usize,
+    max_len: usize,
+}
+
+impl<'a> PrettyDisplay<'a> {
+    fn new(value: ValueRef<'a>) -> Self {
+        Self {
+            value,
+            indent: 4,
+            color: false,
+            max_depth: usize::MAX,
+            max_len: usize::MAX,
+        }
+    }
+
+    /// Sets the number of spaces that nested values are indented by. The
+    /// default is 4.
+    pub fn indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Sets whether the output is colored with ANSI escape codes. Each type of
+    /// value gets its own color and keys are dimmed. The default is `false`.
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Sets the depth of compounds, lists and arrays beyond which their
+    /// elements are replaced with `...`. The outermost value has a depth of
+    /// zero. The default is unlimited.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets the maximum number of elements written for each compound, list
+    /// and array. The remaining elements are replaced with a line stating how
+    /// many were left out. The default is unlimited.
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+}
+
+impl Display for PrettyDisplay<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Printer { f, opts: self }.value(self.value, 0)
+    }
+}
+
+// ANSI select graphic rendition parameters for each kind of output.
+const KEY_STYLE: &str = "2";
+const STRING_STYLE: &str = "32";
+const INTEGER_STYLE: &str = "33";
+const FLOAT_STYLE: &str = "35";
+const ARRAY_STYLE: &str = "36";
+const TRUNCATED_STYLE: &str = "2;3";
+
+struct Printer<'a, 'b, 'c> {
+    f: &'a mut Formatter<'b>,
+    opts: &'a PrettyDisplay<'c>,
+}
+
+impl Printer<'_, '_, '_> {
+    fn value(&mut self, value: ValueRef, depth: usize) -> fmt::Result {
+        match value {
+            ValueRef::Byte(v) => self.styled(INTEGER_STYLE, format_args!("{v}b")),
+            ValueRef::Short(v) => self.styled(INTEGER_STYLE, format_args!("{v}s")),
+            ValueRef::Int(v) => self.styled(INTEGER_STYLE, format_args!("{v}")),
+            ValueRef::Long(v) => self.styled(INTEGER_STYLE, format_args!("{v}L")),
+            ValueRef::Float(v) => self.styled(FLOAT_STYLE, format_args!("{v}f")),
+            ValueRef::Double(v) => self.styled(FLOAT_STYLE, format_args!("{v}d")),
+            ValueRef::ByteArray(a) => self.array("B", a.iter().map(|&v| ValueRef::Byte(v)), depth),
+            ValueRef::String(s) => self.string(STRING_STYLE, s),
+            ValueRef::List(l) => self.list(l, depth),
+            ValueRef::Compound(c) => self.compound(c, depth),
+            ValueRef::IntArray(a) => self.array("I", a.iter().map(|&v| ValueRef::Int(v)), depth),
+            ValueRef::LongArray(a) => self.array("L", a.iter().map(|&v| ValueRef::Long(v)), depth),
+        }
+    }
+
+    fn compound(&mut self, compound: &Compound, depth: usize) -> fmt::Result {
+        if compound.is_empty() {
+            return self.f.write_str("{}");
+        }
+
+        if depth >= self.opts.max_depth {
+            return self.truncated(format_args!("{{...}}"));
+        }
+
+        self.f.write_str("{")?;
+
+        for (i, (k, v)) in compound.iter().take(self.opts.max_len).enumerate() {
+            if i > 0 {
+                self.f.write_str(",")?;
+            }
+
+            self.new_line(depth + 1)?;
+
+            if needs_quotes(k) {
+                self.string(KEY_STYLE, k)?;
+            } else {
+                self.styled(KEY_STYLE, format_args!("{k}"))?;
+            }
+
+            self.f.write_str(": ")?;
+            self.value(v.into(), depth + 1)?;
+        }
+
+        self.more(compound.len(), depth)?;
+        self.new_line(depth)?;
+        self.f.write_str("}")
+    }
+
+    fn list(&mut self, list: &List, depth: usize) -> fmt::Result {
+        if list.is_empty() {
+            return self.f.write_str("[]");
+        }
+
+        if depth >= self.opts.max_depth {
+            return self.truncated(format_args!("[...]"));
+        }
+
+        let multiline = matches!(
+            list,
+            List::ByteArray(_)
+                | List::List(_)
+                | List::Compound(_)
+                | List::IntArray(_)
+                | List::LongArray(_)
+        );
+
+        if !multiline {
+            return self.inline_elements("[", list.iter(), list.len(), depth);
+        }
+
+        self.f.write_str("[")?;
+
+        for (i, v) in list.iter().take(self.opts.max_len).enumerate() {
+            if i > 0 {
+                self.f.write_str(",")?;
+            }
+
+            self.new_line(depth + 1)?;
+            self.value(v, depth + 1)?;
+        }
+
+        self.more(list.len(), depth)?;
+        self.new_line(depth)?;
+        self.f.write_str("]")
+    }
+
+    fn array<'v>(
+        &mut self,
+        prefix: &str,
+        elems: impl ExactSizeIterator<Item = ValueRef<'v>>,
+        depth: usize,
+    ) -> fmt::Result {
+        self.f.write_str("[")?;
+        self.styled(ARRAY_STYLE, format_args!("{prefix};"))?;
+
+        if elems.len() == 0 {
+            return self.f.write_str("]");
+        }
+
+        if depth >= self.opts.max_depth {
+            self.f.write_str(" ")?;
+            self.truncated(format_args!("..."))?;
+            return self.f.write_str("]");
+        }
+
+        let len = elems.len();
+        self.inline_elements(" ", elems, len, depth)
+    }
+
+    /// Writes `open` followed by the elements on the current line and a
+    /// closing bracket.
+    fn inline_elements<'v>(
+        &mut self,
+        open: &str,
+        elems: impl Iterator<Item = ValueRef<'v>>,
+        len: usize,
+        depth: usize,
+    ) -> fmt::Result {
+        self.f.write_str(open)?;
+
+        for (i, v) in elems.take(self.opts.max_len).enumerate() {
+            if i > 0 {
+                self.f.write_str(", ")?;
+            }
+
+            self.value(v, depth + 1)?;
+        }
+
+        if len > self.opts.max_len {
+            if self.opts.max_len > 0 {
+                self.f.write_str(", ")?;
+            }
+
+            self.truncated(format_args!("... {} more", len - self.opts.max_len))?;
+        }
+
+        self.f.write_str("]")
+    }
+
+    /// Writes the line stating how many elements of a multiline value were
+    /// left out, if any.
+    fn more(&mut self, len: usize, depth: usize) -> fmt::Result {
+        if len > self.opts.max_len {
+            if self.opts.max_len > 0 {
+                self.f.write_str(",")?;
+            }
+
+            self.new_line(depth + 1)?;
+            self.truncated(format_args!("... {} more", len - self.opts.max_len))?;
+        }
+
+        Ok(())
+    }
+
+    fn new_line(&mut self, depth: usize) -> fmt::Result {
+        write!(self.f, "\n{:1$}", "", depth * self.opts.indent)
+    }
+
+    fn string(&mut self, style: &str, s: &str) -> fmt::Result {
+        self.start_style(style)?;
+        self.f.write_str("\"")?;
+
+        for c in s.chars() {
+            match c {
+                '"' => self.f.write_str("\\\"")?,
+                '\\' => self.f.write_str("\\\\")?,
+                _ => write!(self.f, "{c}")?,
+            }
+        }
+
+        self.f.write_str("\"")?;
+        self.end_style()
+    }
+
+    fn truncated(&mut self, args: fmt::Arguments) -> fmt::Result {
+        self.styled(TRUNCATED_STYLE, args)
+    }
+
+    fn styled(&mut self, style: &str, args: fmt::Arguments) -> fmt::Result {
+        self.start_style(style)?;
+        self.f.write_fmt(args)?;
+        self.end_style()
+    }
+
+    fn start_style(&mut self, style: &str) -> fmt::Result {
+        if self.opts.color {
+            write!(self.f, "\x1b[{style}m")?;
+        }
+
+        Ok(())
+    }
+
+    fn end_style(&mut self) -> fmt::Result {
+        if self.opts.color {
+            self.f.write_str("\x1b[0m")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns whether a compound key must be quoted in SNBT.
+fn needs_quotes(key: &str) -> bool {
+    key.is_empty()
+        || !key
+            .chars()
+            .all(|c| matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-' | '+' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compound;
+
+    fn fixture() -> Compound {
+        // The keys are sorted so that the order is the same with the
+        // `preserve_order` feature.
+        compound! {
+            "byte" => 1_i8,
+            "byte_array" => vec![1_i8, 2, 3],
+            "double" => -1.25,
+            "empty" => List::End,
+            "float" => 0.5_f32,
+            "int" => 3,
+            "int_array" => Vec::<i32>::new(),
+            "ints" => List::Int(vec![1, 2, 3, 4, 5]),
+            "key with spaces" => compound! {},
+            "long" => 4_i64,
+            "long_array" => vec![7_i64; 5],
+            "nested" => compound! {
+                "arrays" => List::IntArray(vec![vec![1, 2], vec![]]),
+                "list" => List::Compound(vec![
+                    compound! { "a" => 1 },
+                    compound! { "b" => List::String(vec!["x".into(), "y".into()]) },
+                ]),
+            },
+            "short" => -2_i16,
+            "string" => "say \"hi\"",
+        }
+    }
+
+    #[test]
+    fn pretty() {
+        assert_eq!(
+            fixture().display_pretty().to_string(),
+            r#"{
+    byte: 1b,
+    byte_array: [B; 1b, 2b, 3b],
+    double: -1.25d,
+    empty: [],
+    float: 0.5f,
+    int: 3,
+    int_array: [I;],
+    ints: [1, 2, 3, 4, 5],
+    "key with spaces": {},
+    long: 4L,
+    long_array: [L; 7L, 7L, 7L, 7L, 7L],
+    nested: {
+        arrays: [
+            [I; 1, 2],
+            [I;]
+        ],
+        list: [
+            {
+                a: 1
+            },
+            {
+                b: ["x", "y"]
+            }
+        ]
+    },
+    short: -2s,
+    string: "say \"hi\""
+}"#
+        );
+    }
+
+    #[cfg(feature = "snbt")]
+    #[test]
+    fn pretty_is_snbt() {
+        let value = Value::from(fixture());
+
+        assert_eq!(
+            crate::snbt::from_snbt_str(&value.fmt_pretty(2)).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        assert_eq!(
+            fixture()
+                .display_pretty()
+                .max_len(2)
+                .max_depth(2)
+                .to_string(),
+            r#"{
+    byte: 1b,
+    byte_array: [B; 1b, 2b, ... 1 more],
+    ... 12 more
+}"#
+        );
+
+        assert_eq!(
+            fixture()
+                .display_pretty()
+                .indent(1)
+                .max_len(1)
+                .max_depth(1)
+                .to_string(),
+            "{\n byte: 1b,\n ... 13 more\n}"
+        );
+
+        let nested = Value::from(compound! {
+            "empty" => compound! {},
+            "ints" => List::Int(vec![1]),
+            "list" => List::Compound(vec![compound! { "a" => 1 }]),
+            "longs" => vec![1_i64],
+        });
+
+        assert_eq!(
+            nested.display_pretty().indent(2).max_depth(1).to_string(),
+            "{\n  empty: {},\n  ints: [...],\n  list: [...],\n  longs: [L; ...]\n}"
+        );
+
+        assert_eq!(
+            nested.display_pretty().max_len(0).to_string(),
+            "{\n    ... 4 more\n}"
+        );
+    }
+
+    #[test]
+    fn colored() {
+        let value = Value::from(compound! {
+            "a b" => "x",
+            "c" => List::Double(vec![1.0]),
+            "d" => vec![2_i8],
+        });
+
+        assert_eq!(
+            value.display_pretty().indent(1).color(true).to_string(),
+            "{\n \x1b[2m\"a b\"\x1b[0m: \x1b[32m\"x\"\x1b[0m,\n \x1b[2mc\x1b[0m: \
+             [\x1b[35m1d\x1b[0m],\n \x1b[2md\x1b[0m: [\x1b[36mB;\x1b[0m \x1b[33m2b\x1b[0m]\n}"
+        );
+    }
+}
@@ -1,9 +1,13 @@
 use std::fmt;
 use std::mem::ManuallyDrop;
 
+#[cfg(feature = "binary")]
+pub use binary::*;
 pub use ser::*;
 use thiserror::Error;
 
+#[cfg(feature = "binary")]
+mod binary;
 mod de;
 mod ser;
 #[cfg(test)]
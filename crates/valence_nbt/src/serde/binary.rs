@@ -0,0 +1,574 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use byteorder::{BigEndian, ReadBytesExt};
+use serde::de::value::{BorrowedStrDeserializer, StringDeserializer};
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::{forward_to_deserialize_any, Deserialize};
+
+use super::Error;
+use crate::tag::Tag;
+
+/// Maximum recursion depth to prevent overflowing the call stack.
+const MAX_DEPTH: usize = 512;
+
+/// Deserializes a value directly from uncompressed binary NBT, without
+/// decoding it into a [`Compound`](crate::Compound) first.
+///
+/// Strings and byte arrays are borrowed from `slice` where possible, so types
+/// with `&str` and `&[u8]` fields can be deserialized without copying. Strings
+/// can't be borrowed if they contain characters that are encoded differently
+/// in Java's modified UTF-8, so use `Cow<str>` for strings that may contain
+/// them. Fields that `T` doesn't have are skipped without being decoded.
+///
+/// The slice is advanced past the NBT data. The string returned in the tuple
+/// is the name of the root compound (typically the empty string).
+///
+/// Like with [`Compound`](crate::Compound)s, int and long arrays are
+/// deserialized as sequences, so deserializing a [`Value`](crate::Value) gives
+/// lists in their place. Use [`Compound::from_binary`](crate::Compound) if the
+/// exact types are needed.
+///
+/// # Examples
+///
+/// ```
+/// use serde::Deserialize;
+/// use valence_nbt::compound;
+/// use valence_nbt::serde::from_binary_slice;
+///
+/// #[derive(Deserialize)]
+/// struct Section<'a> {
+///     #[serde(rename = "Y")]
+///     y: i8,
+///     biome: &'a str,
+/// }
+///
+/// let mut buf = vec![];
+///
+/// compound! {
+///     "Y" => 3_i8,
+///     "biome" => "minecraft:plains",
+///     "BlockLight" => vec![0_i8; 2048],
+/// }
+/// .to_binary(&mut buf, "")
+/// .unwrap();
+///
+/// let (section, _) = from_binary_slice::<Section>(&mut buf.as_slice()).unwrap();
+///
+/// assert_eq!(section.y, 3);
+/// assert_eq!(section.biome, "minecraft:plains");
+/// ```
+pub fn from_binary_slice<'de, T>(slice: &mut &'de [u8]) -> Result<(T, Cow<'de, str>), Error>
+where
+    T: Deserialize<'de>,
+{
+    let mut state = DecodeState { slice, depth: 0 };
+
+    let root_tag = state.read_tag()?;
+
+    // For cases such as Block Entity Data in the chunk packet.
+    // https://wiki.vg/Protocol#Chunk_Data_and_Update_Light
+    if root_tag == Tag::End {
+        let value = T::deserialize(EmptyCompoundDeserializer)?;
+        return Ok((value, Cow::Borrowed("")));
+    }
+
+    if root_tag != Tag::Compound {
+        return Err(Error::new(format!(
+            "expected root tag for compound (got {root_tag})"
+        )));
+    }
+
+    let root_name = state.read_string()?;
+    let value = T::deserialize(ValueDeserializer {
+        state: &mut state,
+        tag: Tag::Compound,
+    })?;
+
+    debug_assert_eq!(state.depth, 0);
+
+    Ok((value, root_name))
+}
+
+struct DecodeState<'a, 'de> {
+    slice: &'a mut &'de [u8],
+    /// Current recursion depth.
+    depth: usize,
+}
+
+impl<'de> DecodeState<'_, 'de> {
+    #[inline]
+    fn check_depth<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        if self.depth >= MAX_DEPTH {
+            return Err(Error::new("reached maximum recursion depth"));
+        }
+
+        self.depth += 1;
+        let res = f(self);
+        self.depth -= 1;
+        res
+    }
+
+    fn read_tag(&mut self) -> Result<Tag, Error> {
+        match self.read_u8()? {
+            0 => Ok(Tag::End),
+            1 => Ok(Tag::Byte),
+            2 => Ok(Tag::Short),
+            3 => Ok(Tag::Int),
+            4 => Ok(Tag::Long),
+            5 => Ok(Tag::Float),
+            6 => Ok(Tag::Double),
+            7 => Ok(Tag::ByteArray),
+            8 => Ok(Tag::String),
+            9 => Ok(Tag::List),
+            10 => Ok(Tag::Compound),
+            11 => Ok(Tag::IntArray),
+            12 => Ok(Tag::LongArray),
+            byte => Err(Error::new(format!("invalid tag byte of {byte:#x}"))),
+        }
+    }
+
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        self.slice.read_u8().map_err(eof)
+    }
+
+    fn read_short(&mut self) -> Result<i16, Error> {
+        self.slice.read_i16::<BigEndian>().map_err(eof)
+    }
+
+    fn read_int(&mut self) -> Result<i32, Error> {
+        self.slice.read_i32::<BigEndian>().map_err(eof)
+    }
+
+    fn read_long(&mut self) -> Result<i64, Error> {
+        self.slice.read_i64::<BigEndian>().map_err(eof)
+    }
+
+    fn read_float(&mut self) -> Result<f32, Error> {
+        self.slice.read_f32::<BigEndian>().map_err(eof)
+    }
+
+    fn read_double(&mut self) -> Result<f64, Error> {
+        self.slice.read_f64::<BigEndian>().map_err(eof)
+    }
+
+    fn take(&mut self, len: usize, what: &str) -> Result<&'de [u8], Error> {
+        if len > self.slice.len() {
+            return Err(Error::new(format!(
+                "{what} of length {len} exceeds remainder of input"
+            )));
+        }
+
+        let (left, right) = self.slice.split_at(len);
+        *self.slice = right;
+
+        Ok(left)
+    }
+
+    /// Reads the length of an array or list with elements of at least
+    /// `min_elem_size` bytes.
+    fn read_len(&mut self, what: impl fmt::Display, min_elem_size: usize) -> Result<usize, Error> {
+        let len = self.read_int()?;
+
+        if len.is_negative() {
+            return Err(Error::new(format!("negative {what} length of {len}")));
+        }
+
+        if len as u64 * min_elem_size as u64 > self.slice.len() as u64 {
+            return Err(Error::new(format!(
+                "{what} of length {len} exceeds remainder of input"
+            )));
+        }
+
+        Ok(len as usize)
+    }
+
+    fn read_byte_array(&mut self) -> Result<&'de [u8], Error> {
+        let len = self.read_len("byte array", 1)?;
+        self.take(len, "byte array")
+    }
+
+    fn read_string(&mut self) -> Result<Cow<'de, str>, Error> {
+        let len = self.read_string_len()?;
+        let bytes = self.take(len, "string")?;
+
+        cesu8::from_java_cesu8(bytes)
+            .map_err(|_| Error::new("could not convert CESU-8 data to UTF-8"))
+    }
+
+    fn read_string_len(&mut self) -> Result<usize, Error> {
+        Ok(self.slice.read_u16::<BigEndian>().map_err(eof)?.into())
+    }
+
+    /// Reads the header of a list and returns the type and number of its
+    /// elements.
+    fn read_list_header(&mut self) -> Result<(Tag, usize), Error> {
+        let tag = self.read_tag()?;
+
+        if tag == Tag::End {
+            return match self.read_int()? {
+                0 => Ok((Tag::End, 0)),
+                len => Err(Error::new(format!(
+                    "TAG_End list with nonzero length of {len}"
+                ))),
+            };
+        }
+
+        let len = self.read_len(format_args!("{tag} list"), min_size(tag))?;
+
+        Ok((tag, len))
+    }
+
+    /// Advances past a value without decoding it.
+    fn skip_value(&mut self, tag: Tag) -> Result<(), Error> {
+        match tag {
+            Tag::End => return Err(Error::new("unexpected TAG_End")),
+            Tag::Byte | Tag::Short | Tag::Int | Tag::Long | Tag::Float | Tag::Double => {
+                self.take(min_size(tag), tag.name())?;
+            }
+            Tag::ByteArray => {
+                self.read_byte_array()?;
+            }
+            Tag::String => {
+                let len = self.read_string_len()?;
+                self.take(len, "string")?;
+            }
+            Tag::List => self.check_depth(|st| {
+                let (tag, len) = st.read_list_header()?;
+
+                match tag {
+                    Tag::End => {}
+                    Tag::Byte | Tag::Short | Tag::Int | Tag::Long | Tag::Float | Tag::Double => {
+                        // The length was checked against the remainder of the input already.
+                        st.take(len * min_size(tag), "list")?;
+                    }
+                    _ => {
+                        for _ in 0..len {
+                            st.skip_value(tag)?;
+                        }
+                    }
+                }
+
+                Ok(())
+            })?,
+            Tag::Compound => self.check_depth(|st| loop {
+                let tag = st.read_tag()?;
+
+                if tag == Tag::End {
+                    return Ok(());
+                }
+
+                st.skip_value(Tag::String)?;
+                st.skip_value(tag)?;
+            })?,
+            Tag::IntArray => {
+                let len = self.read_len("int array", 4)?;
+                self.take(len * 4, "int array")?;
+            }
+            Tag::LongArray => {
+                let len = self.read_len("long array", 8)?;
+                self.take(len * 8, "long array")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The minimum size of a value with the given tag when encoded.
+fn min_size(tag: Tag) -> usize {
+    match tag {
+        Tag::End => 0,
+        Tag::Byte => 1,
+        Tag::Short => 2,
+        Tag::Int => 4,
+        Tag::Long => 8,
+        Tag::Float => 4,
+        Tag::Double => 8,
+        Tag::ByteArray => 4,
+        Tag::String => 2,
+        Tag::List => 5,
+        Tag::Compound => 1,
+        Tag::IntArray => 4,
+        Tag::LongArray => 4,
+    }
+}
+
+fn eof(_: std::io::Error) -> Error {
+    Error::new("unexpected end of input")
+}
+
+/// Deserializes the value with the given tag at the front of the input.
+struct ValueDeserializer<'a, 'b, 'de> {
+    state: &'a mut DecodeState<'b, 'de>,
+    tag: Tag,
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'_, '_, 'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let state = self.state;
+
+        match self.tag {
+            Tag::End => Err(Error::new("unexpected TAG_End")),
+            Tag::Byte => visitor.visit_i8(state.read_u8()? as i8),
+            Tag::Short => visitor.visit_i16(state.read_short()?),
+            Tag::Int => visitor.visit_i32(state.read_int()?),
+            Tag::Long => visitor.visit_i64(state.read_long()?),
+            Tag::Float => visitor.visit_f32(state.read_float()?),
+            Tag::Double => visitor.visit_f64(state.read_double()?),
+            Tag::ByteArray => visitor.visit_borrowed_bytes(state.read_byte_array()?),
+            Tag::String => match state.read_string()? {
+                Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
+                Cow::Owned(s) => visitor.visit_string(s),
+            },
+            Tag::List => state.check_depth(|st| {
+                let (tag, len) = st.read_list_header()?;
+                visit_seq(st, tag, len, visitor)
+            }),
+            Tag::Compound => state.check_depth(|st| {
+                let mut access = CompoundAccess {
+                    state: st,
+                    value_tag: None,
+                    done: false,
+                };
+
+                let value = visitor.visit_map(&mut access)?;
+                access.finish()?;
+
+                Ok(value)
+            }),
+            Tag::IntArray => {
+                let len = state.read_len("int array", 4)?;
+                visit_seq(state, Tag::Int, len, visitor)
+            }
+            Tag::LongArray => {
+                let len = state.read_len("long array", 8)?;
+                visit_seq(state, Tag::Long, len, visitor)
+            }
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.tag {
+            Tag::Byte => visitor.visit_bool(self.state.read_u8()? != 0),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.tag {
+            // Unit variant.
+            Tag::String => match self.state.read_string()? {
+                Cow::Borrowed(s) => visitor.visit_enum(BorrowedStrDeserializer::new(s)),
+                Cow::Owned(s) => visitor.visit_enum(StringDeserializer::new(s)),
+            },
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.state.skip_value(self.tag)?;
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier
+    }
+}
+
+fn visit_seq<'de, V>(
+    state: &mut DecodeState<'_, 'de>,
+    tag: Tag,
+    len: usize,
+    visitor: V,
+) -> Result<V::Value, Error>
+where
+    V: Visitor<'de>,
+{
+    let mut access = ListAccess {
+        state,
+        tag,
+        remaining: len,
+    };
+
+    let value = visitor.visit_seq(&mut access)?;
+
+    // Skip the elements the visitor didn't want.
+    for _ in 0..access.remaining {
+        access.state.skip_value(tag)?;
+    }
+
+    Ok(value)
+}
+
+struct ListAccess<'a, 'b, 'de> {
+    state: &'a mut DecodeState<'b, 'de>,
+    tag: Tag,
+    remaining: usize,
+}
+
+impl<'de> SeqAccess<'de> for ListAccess<'_, '_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        self.remaining -= 1;
+
+        seed.deserialize(ValueDeserializer {
+            state: self.state,
+            tag: self.tag,
+        })
+        .map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+struct CompoundAccess<'a, 'b, 'de> {
+    state: &'a mut DecodeState<'b, 'de>,
+    /// The tag of the value after the key that was just read.
+    value_tag: Option<Tag>,
+    done: bool,
+}
+
+impl CompoundAccess<'_, '_, '_> {
+    /// Skips the entries the visitor didn't want.
+    fn finish(&mut self) -> Result<(), Error> {
+        if let Some(tag) = self.value_tag.take() {
+            self.state.skip_value(tag)?;
+        }
+
+        while !self.done {
+            let tag = self.state.read_tag()?;
+
+            if tag == Tag::End {
+                self.done = true;
+            } else {
+                self.state.skip_value(Tag::String)?;
+                self.state.skip_value(tag)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'de> MapAccess<'de> for CompoundAccess<'_, '_, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        if self.done {
+            return Ok(None);
+        }
+
+        if let Some(tag) = self.value_tag.take() {
+            self.state.skip_value(tag)?;
+        }
+
+        let tag = self.state.read_tag()?;
+
+        if tag == Tag::End {
+            self.done = true;
+            return Ok(None);
+        }
+
+        self.value_tag = Some(tag);
+
+        match self.state.read_string()? {
+            Cow::Borrowed(name) => seed.deserialize(BorrowedStrDeserializer::new(name)),
+            Cow::Owned(name) => seed.deserialize(StringDeserializer::new(name)),
+        }
+        .map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let tag = self
+            .value_tag
+            .take()
+            .ok_or_else(|| Error::new("compound value requested before its key"))?;
+
+        seed.deserialize(ValueDeserializer {
+            state: self.state,
+            tag,
+        })
+    }
+}
+
+/// Deserializes the empty compound used in place of a missing root compound.
+struct EmptyCompoundDeserializer;
+
+impl<'de> de::Deserializer<'de> for EmptyCompoundDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_map(de::value::MapDeserializer::new(std::iter::empty::<(
+            &str,
+            i8,
+        )>()))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
@@ -107,3 +107,188 @@ fn compound_to_json() {
 
     assert_eq!(j, make_json());
 }
+
+#[cfg(feature = "binary")]
+mod binary {
+    use std::borrow::Cow;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::serde::from_binary_slice;
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Borrowed<'a> {
+        name: &'a str,
+        #[serde(with = "serde_bytes_borrowed")]
+        data: &'a [u8],
+        escaped: Cow<'a, str>,
+        numbers: Vec<i64>,
+        nested: Vec<BorrowedInner<'a>>,
+        flag: bool,
+        missing: Option<i32>,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct BorrowedInner<'a> {
+        id: &'a str,
+        #[serde(default)]
+        count: i8,
+    }
+
+    /// Borrows byte arrays without requiring another dependency.
+    mod serde_bytes_borrowed {
+        use std::fmt;
+
+        use serde::de::{Deserializer, Visitor};
+
+        pub(super) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<&'de [u8], D::Error> {
+            struct BytesVisitor;
+
+            impl<'de> Visitor<'de> for BytesVisitor {
+                type Value = &'de [u8];
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    write!(f, "borrowed bytes")
+                }
+
+                fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+                    Ok(v)
+                }
+            }
+
+            d.deserialize_bytes(BytesVisitor)
+        }
+    }
+
+    fn to_binary(c: &Compound) -> Vec<u8> {
+        let mut buf = vec![];
+        c.to_binary(&mut buf, "root").unwrap();
+        buf
+    }
+
+    #[test]
+    fn binary_slice_borrows() {
+        let buf = to_binary(&compound! {
+            "name" => "minecraft:stone",
+            "data" => vec![1_i8, -2, 3],
+            "escaped" => "nul \0 and 🤨",
+            "numbers" => vec![1_i64, -2, i64::MAX],
+            "nested" => List::Compound(vec![
+                compound! { "id" => "a", "count" => 3_i8, "extra" => compound! { "x" => 1 } },
+                compound! { "unknown" => List::String(vec!["x".into()]), "id" => "b" },
+            ]),
+            "flag" => true,
+            "ignored" => List::List(vec![List::Int(vec![1, 2]), List::End]),
+        });
+
+        let mut slice = buf.as_slice();
+        let (b, root_name) = from_binary_slice::<Borrowed>(&mut slice).unwrap();
+
+        assert!(slice.is_empty());
+        assert_eq!(root_name, "root");
+        assert_eq!(
+            b,
+            Borrowed {
+                name: "minecraft:stone",
+                data: &[1, 0xfe, 3],
+                escaped: "nul \0 and 🤨".into(),
+                numbers: vec![1, -2, i64::MAX],
+                nested: vec![
+                    BorrowedInner { id: "a", count: 3 },
+                    BorrowedInner { id: "b", count: 0 },
+                ],
+                flag: true,
+                missing: None,
+            }
+        );
+
+        // Strings are only copied when they have to be decoded.
+        assert!(matches!(root_name, Cow::Borrowed(_)));
+        assert!(matches!(b.escaped, Cow::Owned(_)));
+    }
+
+    #[test]
+    fn binary_slice_matches_owned() {
+        let buf = to_binary(&make_compound());
+
+        let (s, _) = from_binary_slice::<Struct>(&mut buf.as_slice()).unwrap();
+        assert_eq!(s, make_struct());
+
+        let compound = example_compound();
+        let (c, _) = from_binary_slice::<Compound>(&mut to_binary(&compound).as_slice()).unwrap();
+        assert_eq!(c, compound);
+
+        // The missing root compound of block entities in the chunk packet.
+        let (c, name) = from_binary_slice::<Compound>(&mut [0].as_slice()).unwrap();
+        assert_eq!(c, Compound::new());
+        assert_eq!(name, "");
+    }
+
+    /// A compound without int or long arrays, which become lists when read
+    /// without their types.
+    fn example_compound() -> Compound {
+        compound! {
+            "byte" => 123_i8,
+            "short" => -1234_i16,
+            "float" => 1.5_f32,
+            "double" => -0.25_f64,
+            "byte_array" => vec![0_i8, 1, 2],
+            "string" => "aé日\0",
+            "byte_list" => List::Byte(vec![1, 2]),
+            "empty_list" => List::End,
+            "list_of_lists" => List::List(vec![List::String(vec!["a".into()]), List::End]),
+            "compound" => compound! { "a" => 1_i64, "b" => compound! {} },
+        }
+    }
+
+    #[test]
+    fn binary_slice_malformed_input() {
+        let buf = to_binary(&compound! {
+            "nested" => List::Compound(vec![compound! {
+                "name" => "stone",
+                "data" => vec![1_i64, 2, 3],
+                "bytes" => vec![1_i8, 2],
+                "list" => List::List(vec![List::Short(vec![1, 2]), List::End]),
+            }]),
+            "value" => 1.5_f64,
+        });
+
+        // Every truncation is an error.
+        for len in 0..buf.len() {
+            from_binary_slice::<Compound>(&mut &buf[..len]).unwrap_err();
+        }
+
+        // Random corruptions never panic, whether the values are decoded or
+        // skipped.
+        let mut rng = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = || {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            rng
+        };
+
+        for _ in 0..20_000 {
+            let mut input = buf.clone();
+
+            for _ in 0..next() % 4 + 1 {
+                let idx = next() as usize % input.len();
+                input[idx] = next() as u8;
+            }
+
+            let _ = from_binary_slice::<Compound>(&mut input.as_slice());
+            let _ = from_binary_slice::<serde::de::IgnoredAny>(&mut input.as_slice());
+        }
+
+        // Deeply nested lists are rejected instead of overflowing the stack.
+        let mut deep = vec![10, 0, 0, 9, 0, 0];
+        for _ in 0..10_000 {
+            deep.extend([9, 0, 0, 0, 1]);
+        }
+        assert!(from_binary_slice::<Compound>(&mut deep.as_slice()).is_err());
+        assert!(from_binary_slice::<serde::de::IgnoredAny>(&mut deep.as_slice()).is_err());
+
+        assert!(from_binary_slice::<Compound>(&mut [8, 0, 0].as_slice()).is_err());
+    }
+}
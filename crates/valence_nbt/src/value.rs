@@ -96,6 +96,128 @@ impl List {
             List::LongArray(_) => Tag::LongArray,
         }
     }
+
+    /// Returns the element at `idx`, or `None` if it is out of bounds.
+    pub fn get(&self, idx: usize) -> Option<ValueRef<'_>> {
+        match self {
+            List::End => None,
+            List::Byte(l) => l.get(idx).map(|v| ValueRef::Byte(*v)),
+            List::Short(l) => l.get(idx).map(|v| ValueRef::Short(*v)),
+            List::Int(l) => l.get(idx).map(|v| ValueRef::Int(*v)),
+            List::Long(l) => l.get(idx).map(|v| ValueRef::Long(*v)),
+            List::Float(l) => l.get(idx).map(|v| ValueRef::Float(*v)),
+            List::Double(l) => l.get(idx).map(|v| ValueRef::Double(*v)),
+            List::ByteArray(l) => l.get(idx).map(|v| ValueRef::ByteArray(v)),
+            List::String(l) => l.get(idx).map(|v| ValueRef::String(v)),
+            List::List(l) => l.get(idx).map(ValueRef::List),
+            List::Compound(l) => l.get(idx).map(ValueRef::Compound),
+            List::IntArray(l) => l.get(idx).map(|v| ValueRef::IntArray(v)),
+            List::LongArray(l) => l.get(idx).map(|v| ValueRef::LongArray(v)),
+        }
+    }
+
+    /// Returns an iterator over the elements of this list.
+    pub fn iter(&self) -> impl Iterator<Item = ValueRef<'_>> + '_ {
+        (0..self.len()).filter_map(|idx| self.get(idx))
+    }
+}
+
+/// A reference to a [`Value`] or to an element of a [`List`].
+///
+/// Since list elements aren't stored as [`Value`]s, this is what you get when
+/// looking up a value that may be inside of a list.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ValueRef<'a> {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(&'a [i8]),
+    String(&'a str),
+    List(&'a List),
+    Compound(&'a Compound),
+    IntArray(&'a [i32]),
+    LongArray(&'a [i64]),
+}
+
+impl<'a> ValueRef<'a> {
+    /// Returns the type of the referenced value.
+    pub fn get_tag(&self) -> Tag {
+        match self {
+            Self::Byte(_) => Tag::Byte,
+            Self::Short(_) => Tag::Short,
+            Self::Int(_) => Tag::Int,
+            Self::Long(_) => Tag::Long,
+            Self::Float(_) => Tag::Float,
+            Self::Double(_) => Tag::Double,
+            Self::ByteArray(_) => Tag::ByteArray,
+            Self::String(_) => Tag::String,
+            Self::List(_) => Tag::List,
+            Self::Compound(_) => Tag::Compound,
+            Self::IntArray(_) => Tag::IntArray,
+            Self::LongArray(_) => Tag::LongArray,
+        }
+    }
+
+    /// Clones the referenced value.
+    pub fn to_value(&self) -> Value {
+        match *self {
+            Self::Byte(v) => Value::Byte(v),
+            Self::Short(v) => Value::Short(v),
+            Self::Int(v) => Value::Int(v),
+            Self::Long(v) => Value::Long(v),
+            Self::Float(v) => Value::Float(v),
+            Self::Double(v) => Value::Double(v),
+            Self::ByteArray(v) => Value::ByteArray(v.to_vec()),
+            Self::String(v) => Value::String(v.to_owned()),
+            Self::List(v) => Value::List(v.clone()),
+            Self::Compound(v) => Value::Compound(v.clone()),
+            Self::IntArray(v) => Value::IntArray(v.to_vec()),
+            Self::LongArray(v) => Value::LongArray(v.to_vec()),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&'a str> {
+        match *self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&'a List> {
+        match *self {
+            Self::List(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound(&self) -> Option<&'a Compound> {
+        match *self {
+            Self::Compound(c) => Some(c),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> From<&'a Value> for ValueRef<'a> {
+    fn from(value: &'a Value) -> Self {
+        match value {
+            Value::Byte(v) => Self::Byte(*v),
+            Value::Short(v) => Self::Short(*v),
+            Value::Int(v) => Self::Int(*v),
+            Value::Long(v) => Self::Long(*v),
+            Value::Float(v) => Self::Float(*v),
+            Value::Double(v) => Self::Double(*v),
+            Value::ByteArray(v) => Self::ByteArray(v),
+            Value::String(v) => Self::String(v),
+            Value::List(v) => Self::List(v),
+            Value::Compound(v) => Self::Compound(v),
+            Value::IntArray(v) => Self::IntArray(v),
+            Value::LongArray(v) => Self::LongArray(v),
+        }
+    }
 }
 
 macro_rules! nbt_conversion {
@@ -162,6 +284,25 @@ impl Value {
             Self::LongArray(_) => Tag::LongArray,
         }
     }
+
+    /// Returns the UUID stored in this value if it is an int array of length
+    /// four, which is how UUIDs are stored in NBT. The most significant int
+    /// comes first.
+    ///
+    /// This is the inverse of the `From<Uuid>` impl.
+    #[cfg(feature = "uuid")]
+    pub fn as_uuid(&self) -> Option<Uuid> {
+        match self {
+            Self::IntArray(a) => match **a {
+                [first, second, third, fourth] => Some(Uuid::from_u64_pair(
+                    (first as u32 as u64) << 32 | second as u32 as u64,
+                    (third as u32 as u64) << 32 | fourth as u32 as u64,
+                )),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
 }
 
 impl From<i8> for Value {
@@ -346,3 +487,24 @@ impl From<Vec<Vec<i64>>> for List {
         List::LongArray(v)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid_round_trip() {
+        use super::*;
+
+        let uuid = Uuid::from_u128(0xc35a_b33f_77b9_4ce5_95e0_7924_a404_81fb);
+        let value = Value::from(uuid);
+
+        assert_eq!(
+            value,
+            Value::IntArray(vec![-1017466049, 2008632549, -1780451036, -1543208453])
+        );
+        assert_eq!(value.as_uuid(), Some(uuid));
+
+        assert_eq!(Value::IntArray(vec![1, 2, 3]).as_uuid(), None);
+        assert_eq!(Value::LongArray(vec![1, 2, 3, 4]).as_uuid(), None);
+    }
+}
@@ -0,0 +1,406 @@
+//! Authentication of players with a session server in online mode.
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use base64::prelude::*;
+use reqwest::StatusCode;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::RsaPublicKey;
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+use valence_core::property::Property;
+use valence_core::text::Text;
+use valence_core::translation_key;
+
+/// Verifies that players have joined the server with the session server in
+/// [online mode]. Set this with [`NetworkSettings::session_authenticator`].
+///
+/// Implement this to authenticate players with something other than Mojang's
+/// session server, or to mock authentication in tests. Servers that implement
+/// the same API as Mojang, such as authlib-injector servers, can be used with
+/// [`SessionServerAuthenticator::new`].
+///
+/// This trait uses [`mod@async_trait`].
+///
+/// [online mode]: crate::ConnectionMode::Online
+/// [`NetworkSettings::session_authenticator`]: crate::NetworkSettings::session_authenticator
+#[async_trait]
+pub trait SessionAuthenticator: Send + Sync + 'static {
+    /// Returns the game profile of the player with the given username if they
+    /// have joined the server identified by `server_hash`.
+    ///
+    /// `ip` is the address of the player, and is only provided if
+    /// `prevent_proxy_connections` is enabled in [online mode]. The session
+    /// server should then reject players who authenticated from a different
+    /// address.
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// [online mode]: crate::ConnectionMode::Online
+    async fn has_joined(
+        &self,
+        username: &str,
+        server_hash: &str,
+        ip: Option<IpAddr>,
+    ) -> Result<GameProfile, AuthError>;
+
+    /// Returns the keys that sign the chat session keys of players, which
+    /// become the [`ChatSessionSettings::trusted_keys`] in [online mode].
+    ///
+    /// The default implementation returns no keys, so no chat sessions are
+    /// trusted.
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// [`ChatSessionSettings::trusted_keys`]: valence_client::chat_session::ChatSessionSettings::trusted_keys
+    /// [online mode]: crate::ConnectionMode::Online
+    async fn player_certificate_keys(&self) -> anyhow::Result<Vec<RsaPublicKey>> {
+        Ok(vec![])
+    }
+}
+
+/// The game profile of an authenticated player.
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize)]
+pub struct GameProfile {
+    /// The player's UUID.
+    pub id: Uuid,
+    /// The player's username.
+    pub name: String,
+    /// The player's properties. Typically contains a `textures` property with
+    /// the skin and cape of the player.
+    #[serde(default)]
+    pub properties: Vec<Property>,
+}
+
+/// The reasons a player can fail to authenticate. The player is disconnected
+/// with a message depending on the reason.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum AuthError {
+    /// The session server does not know of the player joining the server,
+    /// usually because they are not using a genuine account.
+    #[error("the session server could not verify the username")]
+    UnverifiedUsername,
+    /// The session server did not respond in time.
+    #[error("the session server did not respond in time")]
+    Timeout,
+    /// The session server responded with an unexpected status code.
+    #[error("the session server responded with status code {0}")]
+    Status(u16),
+    /// The session server responded with an invalid game profile.
+    #[error("invalid game profile from the session server")]
+    InvalidProfile(#[source] anyhow::Error),
+    /// Any other error, such as being unable to connect to the session server.
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl AuthError {
+    /// The message the player is disconnected with.
+    pub fn disconnect_reason(&self) -> Text {
+        match self {
+            AuthError::UnverifiedUsername => Text::translate(
+                translation_key::MULTIPLAYER_DISCONNECT_UNVERIFIED_USERNAME,
+                [],
+            ),
+            AuthError::Timeout => "Timed out while contacting the authentication servers. Please \
+                                   try again later."
+                .into(),
+            AuthError::Status(status) => {
+                format!("The authentication servers responded with an error ({status}).").into()
+            }
+            AuthError::InvalidProfile(_) => {
+                "The authentication servers sent an invalid profile.".into()
+            }
+            AuthError::Other(_) => {
+                Text::translate(translation_key::MULTIPLAYER_DISCONNECT_AUTHSERVERS_DOWN, [])
+            }
+        }
+    }
+}
+
+/// A [`SessionAuthenticator`] for session servers with the same API as
+/// Mojang's.
+///
+/// The [`Default`] implementation uses Mojang's session server.
+#[derive(Clone, Debug)]
+pub struct SessionServerAuthenticator {
+    url: String,
+    public_keys_url: String,
+    timeout: Duration,
+    client: reqwest::Client,
+}
+
+impl SessionServerAuthenticator {
+    /// The URL of Mojang's `hasJoined` endpoint.
+    pub const MOJANG_URL: &'static str =
+        "https://sessionserver.mojang.com/session/minecraft/hasJoined";
+
+    /// The URL of Mojang's endpoint for the public keys of its services.
+    pub const MOJANG_PUBLIC_KEYS_URL: &'static str = "https://api.minecraftservices.com/publickeys";
+
+    /// Creates an authenticator for the `hasJoined` endpoint at `url`. The
+    /// `username`, `serverId` and `ip` query parameters are appended to it.
+    ///
+    /// The player certificate keys are fetched from Mojang unless changed with
+    /// [`Self::with_public_keys_url`].
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            public_keys_url: Self::MOJANG_PUBLIC_KEYS_URL.into(),
+            timeout: Duration::from_secs(10),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Sets how long to wait for the session server to respond before
+    /// failing with [`AuthError::Timeout`]. The default value is left
+    /// unspecified and may change in future versions.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the URL of the endpoint with the same API as
+    /// [`Self::MOJANG_PUBLIC_KEYS_URL`] the player certificate keys are
+    /// fetched from.
+    pub fn with_public_keys_url(mut self, url: impl Into<String>) -> Self {
+        self.public_keys_url = url.into();
+        self
+    }
+}
+
+impl Default for SessionServerAuthenticator {
+    fn default() -> Self {
+        Self::new(Self::MOJANG_URL)
+    }
+}
+
+#[async_trait]
+impl SessionAuthenticator for SessionServerAuthenticator {
+    async fn has_joined(
+        &self,
+        username: &str,
+        server_hash: &str,
+        ip: Option<IpAddr>,
+    ) -> Result<GameProfile, AuthError> {
+        let mut req = self
+            .client
+            .get(&self.url)
+            .timeout(self.timeout)
+            .query(&[("username", username), ("serverId", server_hash)]);
+
+        if let Some(ip) = ip {
+            req = req.query(&[("ip", ip.to_string())]);
+        }
+
+        let map_err = |e: reqwest::Error| {
+            if e.is_timeout() {
+                AuthError::Timeout
+            } else {
+                AuthError::Other(anyhow::Error::new(e).context("session server request failed"))
+            }
+        };
+
+        let resp = req.send().await.map_err(map_err)?;
+
+        match resp.status() {
+            StatusCode::OK => {}
+            StatusCode::NO_CONTENT => return Err(AuthError::UnverifiedUsername),
+            status => return Err(AuthError::Status(status.as_u16())),
+        }
+
+        let body = resp.bytes().await.map_err(map_err)?;
+
+        serde_json::from_slice(&body)
+            .context("failed to parse game profile")
+            .map_err(AuthError::InvalidProfile)
+    }
+
+    async fn player_certificate_keys(&self) -> anyhow::Result<Vec<RsaPublicKey>> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct PublicKeys {
+            player_certificate_keys: Vec<PublicKey>,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct PublicKey {
+            public_key: String,
+        }
+
+        let keys: PublicKeys = self
+            .client
+            .get(&self.public_keys_url)
+            .timeout(self.timeout)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        keys.player_certificate_keys
+            .iter()
+            .map(|key| {
+                let der = BASE64_STANDARD.decode(&key.public_key)?;
+                RsaPublicKey::from_public_key_der(&der).context("invalid player certificate key")
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::oneshot;
+
+    use super::*;
+
+    const PROFILE: &str = r#"{
+        "id": "069a79f444e94726a5befca90e38aaf5",
+        "name": "Notch",
+        "properties": [{ "name": "textures", "value": "dGV4dHVyZXM=", "signature": "c2ln" }]
+    }"#;
+
+    /// Starts an HTTP server that answers a single request with `response`,
+    /// and returns its URL and the first line of the request. If `response` is
+    /// `None`, the server never responds.
+    async fn stub_server(response: Option<String>) -> (String, oneshot::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hasJoined", listener.local_addr().unwrap());
+        let (request_send, request_recv) = oneshot::channel();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            let mut buf = vec![0; 4096];
+            let mut len = 0;
+
+            while !buf[..len].windows(4).any(|w| w == b"\r\n\r\n") {
+                len += stream.read(&mut buf[len..]).await.unwrap();
+            }
+
+            let request = String::from_utf8_lossy(&buf[..len]);
+            let _ = request_send.send(request.lines().next().unwrap().to_owned());
+
+            match response {
+                Some(response) => stream.write_all(response.as_bytes()).await.unwrap(),
+                None => std::future::pending().await,
+            }
+        });
+
+        (url, request_recv)
+    }
+
+    fn http_response(status: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    #[tokio::test]
+    async fn authenticated() {
+        let (url, request) = stub_server(Some(http_response("200 OK", PROFILE))).await;
+
+        let profile = SessionServerAuthenticator::new(url)
+            .has_joined("Notch", "-2b1a4f3c", Some("192.168.0.42".parse().unwrap()))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            request.await.unwrap(),
+            "GET /hasJoined?username=Notch&serverId=-2b1a4f3c&ip=192.168.0.42 HTTP/1.1"
+        );
+
+        assert_eq!(
+            profile,
+            GameProfile {
+                id: "069a79f4-44e9-4726-a5be-fca90e38aaf5".parse().unwrap(),
+                name: "Notch".into(),
+                properties: vec![Property {
+                    name: "textures".into(),
+                    value: "dGV4dHVyZXM=".into(),
+                    signature: Some("c2ln".into()),
+                }],
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn unverified_username() {
+        let (url, request) = stub_server(Some(http_response("204 No Content", ""))).await;
+
+        let res = SessionServerAuthenticator::new(url)
+            .has_joined("Notch", "-2b1a4f3c", None)
+            .await;
+
+        // The IP is not sent unless it is provided.
+        assert_eq!(
+            request.await.unwrap(),
+            "GET /hasJoined?username=Notch&serverId=-2b1a4f3c HTTP/1.1"
+        );
+
+        assert!(matches!(res, Err(AuthError::UnverifiedUsername)));
+    }
+
+    #[tokio::test]
+    async fn error_status() {
+        let (url, _) = stub_server(Some(http_response("503 Service Unavailable", ""))).await;
+
+        let res = SessionServerAuthenticator::new(url)
+            .has_joined("Notch", "-2b1a4f3c", None)
+            .await;
+
+        assert!(matches!(res, Err(AuthError::Status(503))));
+    }
+
+    #[tokio::test]
+    async fn invalid_profile() {
+        let (url, _) = stub_server(Some(http_response("200 OK", r#"{"name": "Notch"}"#))).await;
+
+        let res = SessionServerAuthenticator::new(url)
+            .has_joined("Notch", "-2b1a4f3c", None)
+            .await;
+
+        assert!(matches!(res, Err(AuthError::InvalidProfile(_))));
+    }
+
+    #[tokio::test]
+    async fn player_certificate_keys() {
+        const KEYS: &str = r#"{
+            "profilePropertyKeys": [],
+            "playerCertificateKeys": [{ "publicKey": "MFwwDQYJKoZIhvcNAQEBBQADSwAwSAJBAMOMVlsySr4Ioojy+rPC3Za+BoM0o3C9kJM1AHV8xBVfdr2OUpqLVrbRGi55l1b2q61M4P06M0oS7G6pryQjUPUCAwEAAQ==" }],
+            "authenticationKeys": []
+        }"#;
+
+        let (url, request) = stub_server(Some(http_response("200 OK", KEYS))).await;
+
+        let keys = SessionServerAuthenticator::default()
+            .with_public_keys_url(url.replace("hasJoined", "publickeys"))
+            .player_certificate_keys()
+            .await
+            .unwrap();
+
+        assert_eq!(request.await.unwrap(), "GET /publickeys HTTP/1.1");
+        assert_eq!(keys.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn timeout() {
+        let (url, _) = stub_server(None).await;
+
+        let res = SessionServerAuthenticator::new(url)
+            .with_timeout(Duration::from_millis(100))
+            .has_joined("Notch", "-2b1a4f3c", None)
+            .await;
+
+        assert!(matches!(res, Err(AuthError::Timeout)));
+    }
+}
@@ -0,0 +1,945 @@
+//! Player and IP bans, backed by files in the format of vanilla's
+//! `banned-players.json` and `banned-ips.json`.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{fmt, fs, io};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize, Serializer};
+use thiserror::Error;
+use uuid::Uuid;
+use valence_core::text::Text;
+use valence_core::translation_key;
+
+/// The bans of the server. See [`NetworkSettings::ban_list`].
+///
+/// Players are banned by UUID and addresses are banned by [`IpCidr`] range.
+/// Bans can expire, after which they are ignored. Expired bans are removed
+/// when they are looked up and when the list is saved.
+///
+/// Clones share the same bans, so changes made through any clone take effect
+/// immediately. The ban list of the server can be accessed from systems
+/// through [`SharedNetworkState::ban_list`].
+///
+/// Changes are only written to the files by [`BanList::save`].
+///
+/// [`NetworkSettings::ban_list`]: crate::NetworkSettings::ban_list
+/// [`SharedNetworkState::ban_list`]: crate::SharedNetworkState::ban_list
+#[derive(Clone, Default, Debug)]
+pub struct BanList(Arc<BanListInner>);
+
+#[derive(Default, Debug)]
+struct BanListInner {
+    /// The paths of the player and IP ban files, if the list is backed by
+    /// files.
+    paths: Option<(PathBuf, PathBuf)>,
+    bans: Mutex<Bans>,
+    /// Incremented whenever bans are added, so that online clients can be
+    /// checked again.
+    additions: AtomicU64,
+}
+
+#[derive(Default, Debug)]
+struct Bans {
+    players: HashMap<Uuid, PlayerBan>,
+    ips: Vec<IpBan>,
+}
+
+/// A ban of the player with [`Self::uuid`].
+#[derive(Clone, PartialEq, Debug)]
+pub struct PlayerBan {
+    pub uuid: Uuid,
+    /// The username of the player when they were banned. This is only
+    /// informational.
+    pub name: String,
+    /// When the ban was created.
+    pub created: SystemTime,
+    /// Who created the ban, such as the name of an operator.
+    pub source: String,
+    /// When the ban expires, or `None` if it is permanent.
+    pub expires: Option<SystemTime>,
+    /// The reason displayed to the player.
+    pub reason: Text,
+}
+
+/// A ban of every address in [`Self::range`].
+#[derive(Clone, PartialEq, Debug)]
+pub struct IpBan {
+    pub range: IpCidr,
+    /// When the ban was created.
+    pub created: SystemTime,
+    /// Who created the ban, such as the name of an operator.
+    pub source: String,
+    /// When the ban expires, or `None` if it is permanent.
+    pub expires: Option<SystemTime>,
+    /// The reason displayed to banned players.
+    pub reason: Text,
+}
+
+impl PlayerBan {
+    /// Returns a permanent ban created now by the server.
+    pub fn new(uuid: Uuid, name: impl Into<String>, reason: impl Into<Text>) -> Self {
+        Self {
+            uuid,
+            name: name.into(),
+            created: SystemTime::now(),
+            source: DEFAULT_SOURCE.into(),
+            expires: None,
+            reason: reason.into(),
+        }
+    }
+
+    /// Returns if the ban has expired at `now`.
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        is_expired(self.expires, now)
+    }
+
+    /// The message the player is disconnected with.
+    pub fn disconnect_reason(&self) -> Text {
+        let mut reason = Text::translate(
+            translation_key::MULTIPLAYER_DISCONNECT_BANNED_REASON,
+            [self.reason.clone()],
+        );
+
+        if let Some(expires) = self.expires {
+            reason += Text::translate(
+                translation_key::MULTIPLAYER_DISCONNECT_BANNED_EXPIRATION,
+                [format_date(expires).into()],
+            );
+        }
+
+        reason
+    }
+}
+
+impl IpBan {
+    /// Returns a permanent ban created now by the server.
+    pub fn new(range: impl Into<IpCidr>, reason: impl Into<Text>) -> Self {
+        Self {
+            range: range.into(),
+            created: SystemTime::now(),
+            source: DEFAULT_SOURCE.into(),
+            expires: None,
+            reason: reason.into(),
+        }
+    }
+
+    /// Returns if the ban has expired at `now`.
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        is_expired(self.expires, now)
+    }
+
+    /// The message banned players are disconnected with.
+    pub fn disconnect_reason(&self) -> Text {
+        let mut reason = Text::translate(
+            translation_key::MULTIPLAYER_DISCONNECT_BANNED_IP_REASON,
+            [self.reason.clone()],
+        );
+
+        if let Some(expires) = self.expires {
+            reason += Text::translate(
+                translation_key::MULTIPLAYER_DISCONNECT_BANNED_IP_EXPIRATION,
+                [format_date(expires).into()],
+            );
+        }
+
+        reason
+    }
+}
+
+/// Bans stay in effect until the second they expire, like in vanilla.
+fn is_expired(expires: Option<SystemTime>, now: SystemTime) -> bool {
+    expires.is_some_and(|expires| expires < now)
+}
+
+impl BanList {
+    /// Returns an empty ban list that isn't backed by files.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the bans from the player and IP ban files at the given paths.
+    /// Files that don't exist yet are treated as empty.
+    pub fn load(
+        players_path: impl Into<PathBuf>,
+        ips_path: impl Into<PathBuf>,
+    ) -> anyhow::Result<Self> {
+        let players_path = players_path.into();
+        let ips_path = ips_path.into();
+
+        let bans = read_bans(&players_path, &ips_path)?;
+
+        Ok(Self(Arc::new(BanListInner {
+            paths: Some((players_path, ips_path)),
+            bans: Mutex::new(bans),
+            additions: AtomicU64::new(0),
+        })))
+    }
+
+    /// Loads the files again, replacing the bans of this list. The bans are
+    /// left unchanged if the files can't be read. Does nothing if the list
+    /// isn't backed by files.
+    pub fn reload(&self) -> anyhow::Result<()> {
+        if let Some((players_path, ips_path)) = &self.0.paths {
+            let bans = read_bans(players_path, ips_path)?;
+            *self.0.bans.lock().unwrap() = bans;
+            self.0.additions.fetch_add(1, Ordering::Relaxed);
+        }
+
+        Ok(())
+    }
+
+    /// Writes the bans that haven't expired to the files. Does nothing if the
+    /// list isn't backed by files.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let Some((players_path, ips_path)) = &self.0.paths else {
+            return Ok(());
+        };
+
+        let (players, ips) = {
+            let mut bans = self.0.bans.lock().unwrap();
+            bans.prune(SystemTime::now());
+
+            let mut players: Vec<_> = bans.players.values().collect();
+            players.sort_by_key(|b| b.created);
+            let players: Vec<_> = players.into_iter().map(PlayerBanJson::from).collect();
+
+            let ips: Vec<_> = bans.ips.iter().map(IpBanJson::from).collect();
+
+            (players, ips)
+        };
+
+        write_json(players_path, &players)?;
+        write_json(ips_path, &ips)
+    }
+
+    /// Bans a player, replacing their previous ban if any.
+    pub fn ban_player(&self, ban: PlayerBan) {
+        self.0.bans.lock().unwrap().players.insert(ban.uuid, ban);
+        self.0.additions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Removes the ban of the player with the given UUID, returning it if it
+    /// hadn't expired.
+    pub fn pardon_player(&self, uuid: Uuid) -> Option<PlayerBan> {
+        let ban = self.0.bans.lock().unwrap().players.remove(&uuid)?;
+        (!ban.is_expired(SystemTime::now())).then_some(ban)
+    }
+
+    /// Returns the ban of the player with the given UUID, if they are banned.
+    pub fn player_ban(&self, uuid: Uuid) -> Option<PlayerBan> {
+        self.0
+            .bans
+            .lock()
+            .unwrap()
+            .player_ban(uuid, SystemTime::now())
+            .cloned()
+    }
+
+    /// Returns the player bans that haven't expired.
+    pub fn player_bans(&self) -> Vec<PlayerBan> {
+        let mut bans = self.0.bans.lock().unwrap();
+        bans.prune(SystemTime::now());
+        bans.players.values().cloned().collect()
+    }
+
+    /// Bans a range of addresses, replacing the previous ban of the same range
+    /// if any.
+    pub fn ban_ip(&self, ban: IpBan) {
+        let mut bans = self.0.bans.lock().unwrap();
+
+        match bans.ips.iter_mut().find(|b| b.range == ban.range) {
+            Some(existing) => *existing = ban,
+            None => bans.ips.push(ban),
+        }
+
+        drop(bans);
+        self.0.additions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Removes the ban of the given range, returning it if it hadn't expired.
+    /// Bans of other ranges containing the range are left in place.
+    pub fn pardon_ip(&self, range: impl Into<IpCidr>) -> Option<IpBan> {
+        let range = range.into();
+        let mut bans = self.0.bans.lock().unwrap();
+
+        let idx = bans.ips.iter().position(|b| b.range == range)?;
+        let ban = bans.ips.remove(idx);
+
+        (!ban.is_expired(SystemTime::now())).then_some(ban)
+    }
+
+    /// Returns a ban of a range containing `ip`, if the address is banned.
+    pub fn ip_ban(&self, ip: IpAddr) -> Option<IpBan> {
+        self.0
+            .bans
+            .lock()
+            .unwrap()
+            .ip_ban(ip, SystemTime::now())
+            .cloned()
+    }
+
+    /// Returns the IP bans that haven't expired.
+    pub fn ip_bans(&self) -> Vec<IpBan> {
+        let mut bans = self.0.bans.lock().unwrap();
+        bans.prune(SystemTime::now());
+        bans.ips.clone()
+    }
+
+    /// Returns `Ok(())` if neither the player with the given UUID nor `ip`
+    /// are banned, or the vanilla disconnect message otherwise.
+    pub fn check(&self, uuid: Uuid, ip: IpAddr) -> Result<(), Text> {
+        self.check_at(uuid, ip, SystemTime::now())
+    }
+
+    fn check_at(&self, uuid: Uuid, ip: IpAddr, now: SystemTime) -> Result<(), Text> {
+        let mut bans = self.0.bans.lock().unwrap();
+
+        if let Some(ban) = bans.player_ban(uuid, now) {
+            return Err(ban.disconnect_reason());
+        }
+
+        if let Some(ban) = bans.ip_ban(ip, now) {
+            return Err(ban.disconnect_reason());
+        }
+
+        Ok(())
+    }
+
+    /// A counter incremented whenever bans are added.
+    pub(crate) fn additions(&self) -> u64 {
+        self.0.additions.load(Ordering::Relaxed)
+    }
+}
+
+impl Bans {
+    fn player_ban(&mut self, uuid: Uuid, now: SystemTime) -> Option<&PlayerBan> {
+        if self.players.get(&uuid)?.is_expired(now) {
+            self.players.remove(&uuid);
+            return None;
+        }
+
+        self.players.get(&uuid)
+    }
+
+    fn ip_ban(&mut self, ip: IpAddr, now: SystemTime) -> Option<&IpBan> {
+        self.ips
+            .retain(|b| !b.range.contains(ip) || !b.is_expired(now));
+        self.ips.iter().find(|b| b.range.contains(ip))
+    }
+
+    fn prune(&mut self, now: SystemTime) {
+        self.players.retain(|_, b| !b.is_expired(now));
+        self.ips.retain(|b| !b.is_expired(now));
+    }
+}
+
+/// A range of IP addresses in CIDR notation, such as `192.168.0.0/16`.
+///
+/// IPv4-mapped IPv6 addresses are treated as the IPv4 addresses they map to.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct IpCidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpCidr {
+    /// Returns the range of addresses whose first `prefix_len` bits are the
+    /// same as `addr`'s, or `None` if the prefix is longer than the address.
+    /// IPv4-mapped addresses are converted to IPv4 first, so the prefix
+    /// applies to the IPv4 address.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Option<Self> {
+        let addr = match addr.to_canonical() {
+            IpAddr::V4(addr) if prefix_len <= 32 => {
+                IpAddr::V4((u32::from(addr) & v4_mask(prefix_len)).into())
+            }
+            IpAddr::V6(addr) if prefix_len <= 128 => {
+                IpAddr::V6((u128::from(addr) & v6_mask(prefix_len)).into())
+            }
+            _ => return None,
+        };
+
+        Some(Self { addr, prefix_len })
+    }
+
+    /// The first address of the range.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Returns if `ip` is in the range.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                u32::from(ip) & v4_mask(self.prefix_len) == u32::from(net)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                u128::from(ip) & v6_mask(self.prefix_len) == u128::from(net)
+            }
+            _ => false,
+        }
+    }
+
+    fn is_single_addr(&self) -> bool {
+        match self.addr {
+            IpAddr::V4(_) => self.prefix_len == 32,
+            IpAddr::V6(_) => self.prefix_len == 128,
+        }
+    }
+}
+
+fn v4_mask(prefix_len: u8) -> u32 {
+    u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0)
+}
+
+fn v6_mask(prefix_len: u8) -> u128 {
+    u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0)
+}
+
+/// The range containing only `addr`.
+impl From<IpAddr> for IpCidr {
+    fn from(addr: IpAddr) -> Self {
+        let addr = addr.to_canonical();
+        let prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+
+        Self { addr, prefix_len }
+    }
+}
+
+/// Formats the range in CIDR notation, or as a plain address if it contains a
+/// single address.
+impl fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_single_addr() {
+            write!(f, "{}", self.addr)
+        } else {
+            write!(f, "{}/{}", self.addr, self.prefix_len)
+        }
+    }
+}
+
+/// Parses a range in CIDR notation, or a plain address as the range containing
+/// only that address.
+impl FromStr for IpCidr {
+    type Err = IpCidrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((addr, prefix_len)) = s.split_once('/') else {
+            let addr = s.parse::<IpAddr>().map_err(|_| IpCidrParseError)?;
+            return Ok(addr.into());
+        };
+
+        let addr = addr.parse::<IpAddr>().map_err(|_| IpCidrParseError)?;
+        let prefix_len = prefix_len.parse::<u8>().map_err(|_| IpCidrParseError)?;
+
+        // The prefix applies to the address as it is written.
+        let max_len = if addr.is_ipv4() { 32 } else { 128 };
+
+        if prefix_len > max_len {
+            return Err(IpCidrParseError);
+        }
+
+        // IPv4-mapped IPv6 prefixes cover the last 32 bits.
+        let prefix_len = match addr {
+            IpAddr::V6(v6) if v6.to_ipv4_mapped().is_some() => prefix_len.saturating_sub(96),
+            _ => prefix_len,
+        };
+
+        IpCidr::new(addr, prefix_len).ok_or(IpCidrParseError)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Error)]
+#[error("invalid IP address or CIDR range")]
+pub struct IpCidrParseError;
+
+const DEFAULT_SOURCE: &str = "Server";
+
+/// A player ban as it is stored in `banned-players.json`.
+#[derive(Serialize, Deserialize)]
+struct PlayerBanJson {
+    uuid: Uuid,
+    #[serde(default)]
+    name: String,
+    #[serde(flatten)]
+    common: BanJson,
+}
+
+/// An IP ban as it is stored in `banned-ips.json`.
+#[derive(Serialize, Deserialize)]
+struct IpBanJson {
+    ip: String,
+    #[serde(flatten)]
+    common: BanJson,
+}
+
+/// The fields shared by both kinds of bans.
+#[derive(Serialize, Deserialize)]
+struct BanJson {
+    #[serde(default)]
+    created: Option<String>,
+    #[serde(default = "default_source")]
+    source: String,
+    #[serde(default = "default_expires")]
+    expires: String,
+    #[serde(default = "default_reason", serialize_with = "serialize_reason")]
+    reason: Text,
+}
+
+fn default_source() -> String {
+    "(Unknown)".into()
+}
+
+fn default_expires() -> String {
+    FOREVER.into()
+}
+
+fn default_reason() -> Text {
+    "Banned by an operator.".into()
+}
+
+/// Writes reasons without formatting as plain strings, which is what vanilla
+/// expects.
+fn serialize_reason<S: Serializer>(reason: &Text, serializer: S) -> Result<S::Ok, S::Error> {
+    let plain = reason.to_string();
+
+    if *reason == Text::text(plain.clone()) {
+        serializer.serialize_str(&plain)
+    } else {
+        reason.serialize(serializer)
+    }
+}
+
+const FOREVER: &str = "forever";
+
+impl BanJson {
+    fn new(created: SystemTime, source: &str, expires: Option<SystemTime>, reason: &Text) -> Self {
+        Self {
+            created: Some(format_date(created)),
+            source: source.into(),
+            expires: expires.map_or_else(default_expires, format_date),
+            reason: reason.clone(),
+        }
+    }
+
+    /// Returns the creation time and expiry of the ban.
+    fn parse_dates(&self) -> anyhow::Result<(SystemTime, Option<SystemTime>)> {
+        // Vanilla treats bans without a valid creation date as created now.
+        let created = self
+            .created
+            .as_deref()
+            .and_then(parse_date)
+            .unwrap_or_else(SystemTime::now);
+
+        let expires = if self.expires == FOREVER {
+            None
+        } else {
+            match parse_date(&self.expires) {
+                Some(expires) => Some(expires),
+                None => bail!("invalid ban expiry date \"{}\"", self.expires),
+            }
+        };
+
+        Ok((created, expires))
+    }
+}
+
+impl From<&PlayerBan> for PlayerBanJson {
+    fn from(ban: &PlayerBan) -> Self {
+        Self {
+            uuid: ban.uuid,
+            name: ban.name.clone(),
+            common: BanJson::new(ban.created, &ban.source, ban.expires, &ban.reason),
+        }
+    }
+}
+
+impl From<&IpBan> for IpBanJson {
+    fn from(ban: &IpBan) -> Self {
+        Self {
+            ip: ban.range.to_string(),
+            common: BanJson::new(ban.created, &ban.source, ban.expires, &ban.reason),
+        }
+    }
+}
+
+impl TryFrom<PlayerBanJson> for PlayerBan {
+    type Error = anyhow::Error;
+
+    fn try_from(json: PlayerBanJson) -> anyhow::Result<Self> {
+        let (created, expires) = json.common.parse_dates()?;
+
+        Ok(Self {
+            uuid: json.uuid,
+            name: json.name,
+            created,
+            source: json.common.source,
+            expires,
+            reason: json.common.reason,
+        })
+    }
+}
+
+impl TryFrom<IpBanJson> for IpBan {
+    type Error = anyhow::Error;
+
+    fn try_from(json: IpBanJson) -> anyhow::Result<Self> {
+        let (created, expires) = json.common.parse_dates()?;
+
+        Ok(Self {
+            range: json
+                .ip
+                .parse()
+                .with_context(|| format!("invalid banned IP \"{}\"", json.ip))?,
+            created,
+            source: json.common.source,
+            expires,
+            reason: json.common.reason,
+        })
+    }
+}
+
+fn read_bans(players_path: &Path, ips_path: &Path) -> anyhow::Result<Bans> {
+    let players = read_json::<Vec<PlayerBanJson>>(players_path)?
+        .into_iter()
+        .map(|json| PlayerBan::try_from(json).map(|ban| (ban.uuid, ban)))
+        .collect::<anyhow::Result<_>>()
+        .with_context(|| format!("invalid ban in {}", players_path.display()))?;
+
+    let ips = read_json::<Vec<IpBanJson>>(ips_path)?
+        .into_iter()
+        .map(IpBan::try_from)
+        .collect::<anyhow::Result<_>>()
+        .with_context(|| format!("invalid ban in {}", ips_path.display()))?;
+
+    Ok(Bans { players, ips })
+}
+
+/// Reads a JSON array from the file at `path`, or returns an empty array if
+/// the file doesn't exist.
+fn read_json<T: Default + for<'de> Deserialize<'de>>(path: &Path) -> anyhow::Result<T> {
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("failed to read bans at {}", path.display()))
+        }
+    };
+
+    serde_json::from_str(&json)
+        .with_context(|| format!("failed to parse bans at {}", path.display()))
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> anyhow::Result<()> {
+    let json = serde_json::to_string_pretty(value)?;
+
+    fs::write(path, json).with_context(|| format!("failed to write bans to {}", path.display()))
+}
+
+/// Formats a time in UTC like vanilla does, e.g. `2023-06-07 18:30:00 +0000`.
+fn format_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let secs_of_day = secs.rem_euclid(86400);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} +0000",
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Parses a time in the format vanilla writes, e.g. `2023-06-07 20:30:00
+/// +0200`.
+fn parse_date(s: &str) -> Option<SystemTime> {
+    let (date, rest) = s.trim().split_once(' ')?;
+    let (time, zone) = rest.split_once(' ')?;
+
+    let mut date = date.splitn(3, '-').map(|n| n.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+
+    let mut time = time.splitn(3, ':').map(|n| n.parse::<i64>().ok());
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || !(0..24).contains(&hour)
+        || !(0..60).contains(&minute)
+        || !(0..=60).contains(&second)
+    {
+        return None;
+    }
+
+    let sign = match zone.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+
+    let zone = zone.get(1..).filter(|z| z.len() == 4)?;
+    let offset_hours = zone[..2].parse::<i64>().ok()?;
+    let offset_minutes = zone[2..].parse::<i64>().ok()?;
+    let offset = sign * (offset_hours * 3600 + offset_minutes * 60);
+
+    let secs =
+        days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset;
+
+    UNIX_EPOCH.checked_add(Duration::from_secs(secs.try_into().ok()?))
+}
+
+/// Returns the number of days since 1970-01-01 of a date in the proleptic
+/// Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+/// The inverse of [`days_from_civil`].
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(s: &str) -> IpCidr {
+        s.parse().unwrap()
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn cidr_matching() {
+        let range = cidr("192.168.0.0/16");
+        assert!(range.contains(ip("192.168.0.1")));
+        assert!(range.contains(ip("192.168.255.255")));
+        assert!(!range.contains(ip("192.169.0.0")));
+        assert!(!range.contains(ip("::1")));
+
+        // Host bits are cleared.
+        assert_eq!(cidr("10.1.2.3/8"), cidr("10.0.0.0/8"));
+        assert_eq!(cidr("10.1.2.3/8").to_string(), "10.0.0.0/8");
+
+        assert!(cidr("0.0.0.0/0").contains(ip("8.8.8.8")));
+        assert!(cidr("1.2.3.4").contains(ip("1.2.3.4")));
+        assert!(!cidr("1.2.3.4").contains(ip("1.2.3.5")));
+        assert_eq!(cidr("1.2.3.4").to_string(), "1.2.3.4");
+
+        let range = cidr("2001:db8::/32");
+        assert!(range.contains(ip("2001:db8:ffff::1")));
+        assert!(!range.contains(ip("2001:db9::1")));
+
+        // IPv4-mapped addresses match IPv4 ranges and the other way around.
+        assert!(cidr("127.0.0.0/8").contains(ip("::ffff:127.0.0.1")));
+        assert!(cidr("::ffff:127.0.0.0/104").contains(ip("127.1.2.3")));
+        assert_eq!(cidr("::ffff:1.2.3.4"), cidr("1.2.3.4"));
+
+        assert!("1.2.3.4/33".parse::<IpCidr>().is_err());
+        assert!("::/129".parse::<IpCidr>().is_err());
+        assert!("1.2.3/8".parse::<IpCidr>().is_err());
+        assert!("localhost".parse::<IpCidr>().is_err());
+    }
+
+    #[test]
+    fn dates() {
+        let time = UNIX_EPOCH + Duration::from_secs(1686162600);
+
+        assert_eq!(format_date(time), "2023-06-07 18:30:00 +0000");
+        assert_eq!(parse_date("2023-06-07 18:30:00 +0000"), Some(time));
+        assert_eq!(parse_date("2023-06-07 20:30:00 +0200"), Some(time));
+        assert_eq!(parse_date("2023-06-07 13:00:00 -0530"), Some(time));
+
+        let leap_day = parse_date("2024-02-29 00:00:00 +0000").unwrap();
+        assert_eq!(format_date(leap_day), "2024-02-29 00:00:00 +0000");
+        assert_eq!(format_date(UNIX_EPOCH), "1970-01-01 00:00:00 +0000");
+
+        assert_eq!(parse_date("forever"), None);
+        assert_eq!(parse_date("2023-13-01 00:00:00 +0000"), None);
+        assert_eq!(parse_date("2023-06-07 18:30:00"), None);
+    }
+
+    #[test]
+    fn expiry_boundaries() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let uuid = Uuid::from_u128(1);
+        let addr = ip("10.0.0.1");
+
+        let bans = BanList::new();
+        bans.ban_player(PlayerBan {
+            expires: Some(now),
+            ..PlayerBan::new(uuid, "Notch", "Griefing")
+        });
+
+        // Bans are in effect until the moment they expire.
+        assert!(bans
+            .check_at(uuid, addr, now - Duration::from_secs(1))
+            .is_err());
+        assert!(bans.check_at(uuid, addr, now).is_err());
+        assert!(bans
+            .check_at(uuid, addr, now + Duration::from_secs(1))
+            .is_ok());
+
+        // The expired ban was removed when it was looked up.
+        assert!(bans.0.bans.lock().unwrap().players.is_empty());
+
+        bans.ban_ip(IpBan {
+            expires: Some(now),
+            ..IpBan::new(cidr("10.0.0.0/24"), "Bots")
+        });
+        bans.ban_ip(IpBan::new(cidr("10.0.0.0/8"), "Bots"));
+
+        assert!(bans.check_at(uuid, addr, now).is_err());
+        assert!(bans
+            .check_at(uuid, addr, now + Duration::from_secs(1))
+            .is_err());
+        assert_eq!(bans.0.bans.lock().unwrap().ips.len(), 1);
+
+        assert!(bans.pardon_ip(cidr("10.0.0.0/8")).is_some());
+        assert!(bans.check(uuid, addr).is_ok());
+    }
+
+    #[test]
+    fn disconnect_reasons() {
+        let ban = PlayerBan::new(Uuid::nil(), "Notch", "Griefing");
+        assert_eq!(
+            ban.disconnect_reason(),
+            Text::translate(
+                translation_key::MULTIPLAYER_DISCONNECT_BANNED_REASON,
+                ["Griefing".into()]
+            )
+        );
+
+        let ban = IpBan {
+            expires: Some(UNIX_EPOCH),
+            ..IpBan::new(ip("1.2.3.4"), "Bots")
+        };
+        assert_eq!(
+            ban.disconnect_reason(),
+            Text::translate(
+                translation_key::MULTIPLAYER_DISCONNECT_BANNED_IP_REASON,
+                ["Bots".into()]
+            ) + Text::translate(
+                translation_key::MULTIPLAYER_DISCONNECT_BANNED_IP_EXPIRATION,
+                ["1970-01-01 00:00:00 +0000".into()]
+            )
+        );
+    }
+
+    #[test]
+    fn vanilla_files() {
+        let dir = std::env::temp_dir().join(format!("valence-bans-{:016x}", rand::random::<u64>()));
+        fs::create_dir(&dir).unwrap();
+
+        let players_path = dir.join("banned-players.json");
+        let ips_path = dir.join("banned-ips.json");
+
+        // Missing files are empty.
+        let bans = BanList::load(&players_path, &ips_path).unwrap();
+        assert!(bans.player_bans().is_empty());
+
+        fs::write(
+            &players_path,
+            r#"[
+                {
+                    "uuid": "069a79f4-44e9-4726-a5be-fca90e38aaf5",
+                    "name": "Notch",
+                    "created": "2023-06-07 20:30:00 +0200",
+                    "source": "Server",
+                    "expires": "forever",
+                    "reason": "Banned by an operator."
+                },
+                {
+                    "uuid": "853c80ef-3c37-49fd-aa49-938b674adae6",
+                    "name": "jeb_",
+                    "created": "2000-01-01 00:00:00 +0000",
+                    "source": "Server",
+                    "expires": "2000-01-02 00:00:00 +0000",
+                    "reason": "Expired"
+                }
+            ]"#,
+        )
+        .unwrap();
+
+        fs::write(
+            &ips_path,
+            r#"[
+                {
+                    "ip": "1.2.3.4",
+                    "created": "2023-06-07 18:30:00 +0000",
+                    "source": "Server",
+                    "expires": "forever",
+                    "reason": "Bots"
+                }
+            ]"#,
+        )
+        .unwrap();
+
+        bans.reload().unwrap();
+
+        let notch = Uuid::parse_str("069a79f4-44e9-4726-a5be-fca90e38aaf5").unwrap();
+
+        let ban = bans.player_ban(notch).unwrap();
+        assert_eq!(ban.name, "Notch");
+        assert_eq!(ban.created, UNIX_EPOCH + Duration::from_secs(1686162600));
+        assert_eq!(ban.expires, None);
+        assert_eq!(ban.reason, "Banned by an operator.".into());
+
+        assert!(bans.ip_ban(ip("1.2.3.4")).is_some());
+        assert!(bans.ip_ban(ip("1.2.3.5")).is_none());
+
+        bans.ban_ip(IpBan::new(cidr("10.0.0.0/8"), "Bots"));
+        bans.save().unwrap();
+
+        // Expired bans are not saved.
+        let saved: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&players_path).unwrap()).unwrap();
+        assert_eq!(saved.as_array().unwrap().len(), 1);
+        assert_eq!(saved[0]["name"], "Notch");
+        assert_eq!(saved[0]["created"], "2023-06-07 18:30:00 +0000");
+        assert_eq!(saved[0]["expires"], "forever");
+        assert_eq!(saved[0]["reason"], "Banned by an operator.");
+
+        let saved: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&ips_path).unwrap()).unwrap();
+        assert_eq!(saved[0]["ip"], "1.2.3.4");
+        assert_eq!(saved[1]["ip"], "10.0.0.0/8");
+
+        let loaded = BanList::load(&players_path, &ips_path).unwrap();
+        assert_eq!(loaded.player_bans(), bans.player_bans());
+        assert_eq!(loaded.ip_bans().len(), 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -122,6 +122,11 @@ impl ByteSender {
     pub(crate) fn limit(&self) -> usize {
         self.shared.limit
     }
+
+    /// The number of bytes in the channel that have not been received yet.
+    pub(crate) fn len(&self) -> usize {
+        self.shared.mtx.lock().unwrap().bytes.len()
+    }
 }
 
 /// Contains any excess bytes not sent.
@@ -161,6 +166,10 @@ impl ByteReceiver {
 
     pub(crate) async fn recv_async(&mut self) -> Result<BytesMut, RecvError> {
         loop {
+            // Created before checking the channel so that a notification sent in
+            // between isn't missed.
+            let notified = self.shared.notify.notified();
+
             {
                 let mut lck = self.shared.mtx.lock().unwrap();
 
@@ -174,7 +183,7 @@ impl ByteReceiver {
                 }
             }
 
-            self.shared.notify.notified().await;
+            notified.await;
         }
     }
 
@@ -204,6 +213,8 @@ pub(crate) enum RecvError {
 impl Drop for ByteSender {
     fn drop(&mut self) {
         self.shared.mtx.lock().unwrap().disconnected = true;
+        // Wake up the receiver so it can take the remaining bytes.
+        self.shared.notify.notify_waiters();
     }
 }
 
@@ -251,4 +262,25 @@ mod tests {
 
         assert!(sender.is_disconnected());
     }
+
+    #[tokio::test]
+    async fn byte_channel_drain_after_sender_dropped() {
+        let (mut sender, mut receiver) = byte_channel(16);
+
+        let t = tokio::spawn(async move {
+            let bytes = receiver.recv_async().await.unwrap();
+            assert_eq!(&bytes[..], b"bye");
+
+            assert_eq!(receiver.recv_async().await, Err(RecvError::Disconnected));
+        });
+
+        sender.try_send("bye".as_bytes().into()).unwrap();
+
+        // Let the receiver take the bytes and wait for more.
+        tokio::task::yield_now().await;
+
+        drop(sender);
+
+        t.await.unwrap();
+    }
 }
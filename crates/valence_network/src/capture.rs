@@ -0,0 +1,454 @@
+//! Recording of the packets sent to and received from clients, for debugging.
+//!
+//! When [`NetworkSettings::packet_capture`] is enabled, every connection
+//! writes its packets to files in [`PacketCaptureSettings::directory`]. Each
+//! file starts with [`MAGIC`], followed by records of this form:
+//!
+//! | Field       | Type                                   |
+//! |-------------|----------------------------------------|
+//! | Length      | `u32`, the length of the rest          |
+//! | Timestamp   | `u64`, microseconds since the connection was accepted |
+//! | Side        | `u8`, 0 for serverbound, 1 for clientbound |
+//! | State       | `u8`, 0 to 3 for handshaking, status, login and play |
+//! | Packet ID   | VarInt                                 |
+//! | Packet body | The rest of the record                 |
+//!
+//! Integers are big-endian. Packets are recorded after decryption and
+//! decompression, so the bodies are the same as what the protocol types
+//! decode. Use [`CaptureReader`] to read the records back.
+//!
+//! [`NetworkSettings::packet_capture`]: crate::NetworkSettings::packet_capture
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, ensure, Context};
+use bytes::BytesMut;
+use tracing::warn;
+use valence_core::protocol::decode::PacketFrame;
+use valence_core::protocol::var_int::VarInt;
+use valence_core::protocol::{Decode, Encode, PacketSide, PacketState};
+
+/// The bytes every capture file starts with, including the version of the
+/// format.
+pub const MAGIC: [u8; 8] = *b"VLNCAP\0\x01";
+
+/// Settings for recording the packets of every connection. See the [module
+/// documentation](self) for the format of the files.
+///
+/// Recording is meant for debugging. The files are written from the tasks
+/// handling the connections, so slow disks will slow down the connections.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PacketCaptureSettings {
+    /// The directory to write the files to. It is created if it doesn't
+    /// exist.
+    pub directory: PathBuf,
+    /// The size in bytes after which a connection starts writing to a new
+    /// file.
+    pub max_file_size: u64,
+    /// The number of files kept for each connection. Once a connection has
+    /// written more files than this, its oldest file is deleted. `None`
+    /// keeps every file.
+    pub max_files: Option<usize>,
+}
+
+impl Default for PacketCaptureSettings {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("packet_captures"),
+            max_file_size: 64 * 1024 * 1024,
+            max_files: Some(4),
+        }
+    }
+}
+
+/// A packet read from a capture file.
+#[derive(Clone, Debug)]
+pub struct CaptureRecord {
+    /// The time from the connection being accepted until the packet was
+    /// recorded.
+    pub timestamp: Duration,
+    /// Whether the packet was sent by the client or the server.
+    pub side: PacketSide,
+    /// The state of the connection the packet was sent in.
+    pub state: PacketState,
+    /// The ID and body of the packet. Use [`PacketFrame::decode`] to decode
+    /// it.
+    pub frame: PacketFrame,
+}
+
+/// Iterates over the records in a capture file.
+#[derive(Debug)]
+pub struct CaptureReader<R> {
+    reader: R,
+    buf: Vec<u8>,
+}
+
+impl CaptureReader<BufReader<File>> {
+    /// Opens the capture file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .with_context(|| format!("failed to open capture file {}", path.display()))?;
+
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// Reads a capture from `reader`. Fails if it doesn't start with
+    /// [`MAGIC`].
+    pub fn new(mut reader: R) -> anyhow::Result<Self> {
+        let mut magic = [0; MAGIC.len()];
+        reader
+            .read_exact(&mut magic)
+            .context("failed to read capture header")?;
+
+        ensure!(magic == MAGIC, "not a capture file or unsupported version");
+
+        Ok(Self {
+            reader,
+            buf: vec![],
+        })
+    }
+
+    fn read_record(&mut self) -> anyhow::Result<Option<CaptureRecord>> {
+        let mut len = [0; 4];
+
+        match self.reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        self.buf.resize(u32::from_be_bytes(len) as usize, 0);
+        self.reader
+            .read_exact(&mut self.buf)
+            .context("truncated capture record")?;
+
+        let mut r = self.buf.as_slice();
+
+        let timestamp = Duration::from_micros(u64::decode(&mut r)?);
+
+        let side = match u8::decode(&mut r)? {
+            0 => PacketSide::Serverbound,
+            1 => PacketSide::Clientbound,
+            n => bail!("invalid packet side {n}"),
+        };
+
+        let state = match u8::decode(&mut r)? {
+            0 => PacketState::Handshaking,
+            1 => PacketState::Status,
+            2 => PacketState::Login,
+            3 => PacketState::Play,
+            n => bail!("invalid packet state {n}"),
+        };
+
+        let id = VarInt::decode(&mut r)?.0;
+
+        Ok(Some(CaptureRecord {
+            timestamp,
+            side,
+            state,
+            frame: PacketFrame {
+                id,
+                body: BytesMut::from(r),
+            },
+        }))
+    }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = anyhow::Result<CaptureRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// Writes the packets of one connection to capture files.
+#[derive(Debug)]
+pub(crate) struct PacketRecorder {
+    start: Instant,
+    inner: Mutex<RecorderInner>,
+}
+
+#[derive(Debug)]
+struct RecorderInner {
+    settings: PacketCaptureSettings,
+    /// The name shared by the files of this connection.
+    name: String,
+    /// The number of the current file.
+    file_number: usize,
+    /// The current file, or `None` if recording stopped because of an error.
+    file: Option<BufWriter<File>>,
+    /// The number of bytes written to the current file.
+    written: u64,
+    buf: Vec<u8>,
+}
+
+impl PacketRecorder {
+    /// Creates a recorder for a connection accepted at `start`. `name`
+    /// identifies the connection in the file names.
+    pub(crate) fn new(
+        settings: &PacketCaptureSettings,
+        name: String,
+        start: Instant,
+    ) -> anyhow::Result<Self> {
+        let mut inner = RecorderInner {
+            settings: settings.clone(),
+            name,
+            file_number: 0,
+            file: None,
+            written: 0,
+            buf: vec![],
+        };
+
+        fs::create_dir_all(&inner.settings.directory).with_context(|| {
+            format!(
+                "failed to create capture directory {}",
+                inner.settings.directory.display()
+            )
+        })?;
+
+        inner.open_file()?;
+
+        Ok(Self {
+            start,
+            inner: Mutex::new(inner),
+        })
+    }
+
+    /// The path of the first file of this connection.
+    pub(crate) fn first_path(&self) -> PathBuf {
+        self.inner.lock().unwrap().path(0)
+    }
+
+    /// Records a packet. If the file can't be written to, a warning is logged
+    /// and nothing more is recorded for this connection.
+    pub(crate) fn record(&self, side: PacketSide, state: PacketState, id: i32, body: &[u8]) {
+        let timestamp = self.start.elapsed();
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.file.is_none() {
+            return;
+        }
+
+        if let Err(e) = inner.write_record(timestamp, side, state, id, body) {
+            warn!("stopped capturing packets of {}: {e:#}", inner.name);
+            inner.file = None;
+        }
+    }
+
+    /// Writes buffered records to the file.
+    pub(crate) fn flush(&self) {
+        if let Some(file) = &mut self.inner.lock().unwrap().file {
+            let _ = file.flush();
+        }
+    }
+}
+
+impl RecorderInner {
+    fn path(&self, number: usize) -> PathBuf {
+        self.settings
+            .directory
+            .join(format!("{}.{number}.vcap", self.name))
+    }
+
+    fn open_file(&mut self) -> anyhow::Result<()> {
+        let path = self.path(self.file_number);
+
+        let mut file = BufWriter::new(
+            File::create(&path)
+                .with_context(|| format!("failed to create capture file {}", path.display()))?,
+        );
+
+        file.write_all(&MAGIC)?;
+
+        self.file = Some(file);
+        self.written = MAGIC.len() as u64;
+
+        if let Some(max) = self.settings.max_files {
+            if let Some(old) = self.file_number.checked_sub(max) {
+                let _ = fs::remove_file(self.path(old));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_record(
+        &mut self,
+        timestamp: Duration,
+        side: PacketSide,
+        state: PacketState,
+        id: i32,
+        body: &[u8],
+    ) -> anyhow::Result<()> {
+        if self.written >= self.settings.max_file_size {
+            if let Some(mut file) = self.file.take() {
+                file.flush()?;
+            }
+
+            self.file_number += 1;
+            self.open_file()?;
+        }
+
+        let buf = &mut self.buf;
+        buf.clear();
+
+        // Reserve space for the length.
+        buf.extend_from_slice(&[0; 4]);
+
+        (timestamp.as_micros() as u64).encode(&mut *buf)?;
+
+        let side = match side {
+            PacketSide::Serverbound => 0_u8,
+            PacketSide::Clientbound => 1,
+        };
+
+        let state = match state {
+            PacketState::Handshaking => 0_u8,
+            PacketState::Status => 1,
+            PacketState::Login => 2,
+            PacketState::Play => 3,
+        };
+
+        side.encode(&mut *buf)?;
+        state.encode(&mut *buf)?;
+        VarInt(id).encode(&mut *buf)?;
+        buf.extend_from_slice(body);
+
+        let len = u32::try_from(buf.len() - 4).context("packet too large to record")?;
+        buf[..4].copy_from_slice(&len.to_be_bytes());
+
+        self.file
+            .as_mut()
+            .context("capture file is closed")?
+            .write_all(buf)?;
+
+        self.written += buf.len() as u64;
+
+        Ok(())
+    }
+}
+
+impl Drop for RecorderInner {
+    fn drop(&mut self) {
+        if let Some(file) = &mut self.file {
+            let _ = file.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_core::protocol::Packet;
+
+    use super::*;
+    use crate::packet::{HandshakeC2s, HandshakeNextState};
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "valence-capture-test-{:016x}",
+            rand::random::<u64>()
+        ))
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let dir = temp_dir();
+        let settings = PacketCaptureSettings {
+            directory: dir.clone(),
+            ..Default::default()
+        };
+
+        let recorder = PacketRecorder::new(&settings, "conn".into(), Instant::now()).unwrap();
+
+        let mut body = vec![];
+        HandshakeC2s {
+            protocol_version: VarInt(763),
+            server_address: "localhost",
+            server_port: 25565,
+            next_state: HandshakeNextState::Login,
+        }
+        .encode(&mut body)
+        .unwrap();
+
+        recorder.record(
+            PacketSide::Serverbound,
+            PacketState::Handshaking,
+            HandshakeC2s::ID,
+            &body,
+        );
+        recorder.record(PacketSide::Clientbound, PacketState::Play, 0x42, &[1, 2, 3]);
+
+        let path = recorder.first_path();
+        drop(recorder);
+
+        let records = CaptureReader::open(path)
+            .unwrap()
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(records.len(), 2);
+
+        assert_eq!(records[0].side, PacketSide::Serverbound);
+        assert_eq!(records[0].state, PacketState::Handshaking);
+        let handshake = records[0].frame.decode::<HandshakeC2s>().unwrap();
+        assert_eq!(handshake.server_address, "localhost");
+
+        assert_eq!(records[1].side, PacketSide::Clientbound);
+        assert_eq!(records[1].state, PacketState::Play);
+        assert_eq!(records[1].frame.id, 0x42);
+        assert_eq!(&records[1].frame.body[..], &[1, 2, 3]);
+        assert!(records[1].timestamp >= records[0].timestamp);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn files_are_rotated() {
+        let dir = temp_dir();
+        let settings = PacketCaptureSettings {
+            directory: dir.clone(),
+            max_file_size: 100,
+            max_files: Some(2),
+        };
+
+        let recorder = PacketRecorder::new(&settings, "conn".into(), Instant::now()).unwrap();
+
+        for i in 0..10 {
+            recorder.record(PacketSide::Clientbound, PacketState::Play, i, &[0; 40]);
+        }
+
+        drop(recorder);
+
+        let mut files: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+
+        // Two records fit in each file, and only the last two files are kept.
+        assert_eq!(files, ["conn.3.vcap", "conn.4.vcap"]);
+
+        let ids: Vec<_> = files
+            .iter()
+            .flat_map(|f| CaptureReader::open(dir.join(f)).unwrap())
+            .map(|r| r.unwrap().frame.id)
+            .collect();
+
+        assert_eq!(ids, [6, 7, 8, 9]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(CaptureReader::new(&b"not a capture"[..]).is_err());
+    }
+}
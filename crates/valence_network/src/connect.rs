@@ -1,66 +1,166 @@
 //! Handles new connections to the server and the log-in process.
 
+use std::future::Future;
 use std::io;
-use std::net::SocketAddr;
-use std::time::Duration;
+use std::net::{IpAddr, SocketAddr};
+#[cfg(unix)]
+use std::net::{Ipv4Addr, SocketAddrV4};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::{bail, ensure, Context};
-use base64::prelude::*;
 use hmac::digest::Update;
 use hmac::{Hmac, Mac};
 use num_bigint::BigInt;
-use reqwest::StatusCode;
 use rsa::PaddingScheme;
-use serde::Deserialize;
 use serde_json::{json, Value};
 use sha1::Sha1;
 use sha2::{Digest, Sha256};
-use tokio::net::{TcpListener, TcpStream};
-use tracing::{error, info, trace, warn};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::sync::watch;
+use tracing::{debug, error, info, trace, warn};
 use uuid::Uuid;
 use valence_client::is_valid_username;
+use valence_core::ident;
 use valence_core::property::Property;
 use valence_core::protocol::decode::PacketDecoder;
 use valence_core::protocol::encode::PacketEncoder;
 use valence_core::protocol::raw::RawBytes;
 use valence_core::protocol::var_int::VarInt;
 use valence_core::protocol::Decode;
-use valence_core::text::Text;
-use valence_core::{ident, translation_key, PROTOCOL_VERSION};
 
+use crate::capture::PacketRecorder;
+use crate::connection_limit::IpGuard;
 use crate::legacy_ping::try_handle_legacy_ping;
+use crate::login_plugin::LoginPluginConnection;
 use crate::packet::{
-    HandshakeC2s, HandshakeNextState, LoginCompressionS2c, LoginDisconnectS2c, LoginHelloC2s,
-    LoginHelloS2c, LoginKeyC2s, LoginQueryRequestS2c, LoginQueryResponseC2s, LoginSuccessS2c,
-    QueryPingC2s, QueryPongS2c, QueryRequestC2s, QueryResponseS2c,
+    HandshakeC2s, HandshakeNextState, LoginCompressionS2c, LoginHelloC2s, LoginHelloS2c,
+    LoginKeyC2s, LoginQueryRequestS2c, LoginQueryResponseC2s, LoginSuccessS2c, QueryPingC2s,
+    QueryPongS2c, QueryRequestC2s, QueryResponseS2c,
 };
 use crate::packet_io::PacketIo;
-use crate::{CleanupOnDrop, ConnectionMode, NewClientInfo, ServerListPing, SharedNetworkState};
+use crate::proxy_protocol::read_proxy_header;
+use crate::stream::Stream;
+use crate::timeout::{timed_out_phase, ConnectionPhase, PhaseTimer};
+use crate::{
+    CleanupOnDrop, ConnectionMode, GameProfile, NewClientInfo, PlayerSampleEntry, ServerListPing,
+    SharedNetworkState,
+};
 
-/// Accepts new connections to the server as they occur.
-pub(super) async fn do_accept_loop(shared: SharedNetworkState) {
-    let listener = match TcpListener::bind(shared.0.address).await {
+/// Accepts new connections to the server on `address` as they occur.
+pub(super) async fn do_accept_loop(shared: SharedNetworkState, address: SocketAddr) {
+    let listener = match TcpListener::bind(address).await {
         Ok(listener) => listener,
         Err(e) => {
-            error!("failed to start TCP listener: {e}");
+            error!("failed to start TCP listener on {address}: {e}");
             return;
         }
     };
 
+    // The actual address, in case the port was chosen by the OS.
+    let listener_addr = listener.local_addr().unwrap_or(address);
+
+    let mut shutdown = shared.0.shutdown.subscribe();
+
     loop {
         match shared.0.connection_sema.clone().acquire_owned().await {
-            Ok(permit) => match listener.accept().await {
-                Ok((stream, remote_addr)) => {
+            Ok(permit) => match accept_until_shutdown(listener.accept(), &mut shutdown).await {
+                Some(Ok((stream, remote_addr))) => {
                     let shared = shared.clone();
 
+                    if let Err(e) = stream.set_nodelay(true) {
+                        error!("failed to set TCP_NODELAY: {e}");
+                    }
+
                     tokio::spawn(async move {
-                        handle_connection(shared, stream, remote_addr).await;
+                        handle_connection(
+                            shared,
+                            Stream::Tcp(stream),
+                            remote_addr,
+                            Some(listener_addr),
+                        )
+                        .await;
                         drop(permit);
                     });
                 }
-                Err(e) => {
+                Some(Err(e)) => {
                     error!("failed to accept incoming connection: {e}");
                 }
+                None => return,
+            },
+            // Closed semaphore indicates server shutdown.
+            Err(_) => return,
+        }
+    }
+}
+
+/// Waits for `accept` to finish, or returns `None` once the server shuts down
+/// so that the listener is dropped.
+async fn accept_until_shutdown<T>(
+    accept: impl Future<Output = io::Result<T>>,
+    shutdown: &mut watch::Receiver<bool>,
+) -> Option<io::Result<T>> {
+    if *shutdown.borrow() {
+        return None;
+    }
+
+    tokio::select! {
+        res = accept => Some(res),
+        // The sender is never dropped before the listeners, so any change is
+        // the shutdown.
+        _ = shutdown.changed() => None,
+    }
+}
+
+/// The remote address of clients connected to the unix socket listener, unless
+/// the PROXY protocol provides the real one.
+#[cfg(unix)]
+pub(crate) const UNIX_REMOTE_ADDR: SocketAddr =
+    SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0));
+
+/// Accepts new connections to the server on the unix socket at `path` as they
+/// occur.
+#[cfg(unix)]
+pub(super) async fn do_unix_accept_loop(shared: SharedNetworkState, path: PathBuf) {
+    if let Err(e) = remove_unix_socket(&path) {
+        error!("failed to start unix socket listener: {e:#}");
+        return;
+    }
+
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!(
+                "failed to start unix socket listener on {}: {e}",
+                path.display()
+            );
+            return;
+        }
+    };
+
+    let mut shutdown = shared.0.shutdown.subscribe();
+
+    loop {
+        match shared.0.connection_sema.clone().acquire_owned().await {
+            Ok(permit) => match accept_until_shutdown(listener.accept(), &mut shutdown).await {
+                Some(Ok((stream, _))) => {
+                    let shared = shared.clone();
+
+                    tokio::spawn(async move {
+                        handle_connection(shared, Stream::Unix(stream), UNIX_REMOTE_ADDR, None)
+                            .await;
+                        drop(permit);
+                    });
+                }
+                Some(Err(e)) => {
+                    error!("failed to accept incoming unix socket connection: {e}");
+                }
+                None => return,
             },
             // Closed semaphore indicates server shutdown.
             Err(_) => return,
@@ -68,37 +168,102 @@ pub(super) async fn do_accept_loop(shared: SharedNetworkState) {
     }
 }
 
+/// Removes the unix socket at `path`, such as one left behind by a previous
+/// run of the server. Returns an error without removing anything if `path`
+/// exists and is not a socket.
+#[cfg(unix)]
+pub(crate) fn remove_unix_socket(path: &Path) -> anyhow::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(path)
+            .with_context(|| format!("failed to remove unix socket {}", path.display())),
+        Ok(_) => bail!("{} exists and is not a socket", path.display()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("failed to inspect {}", path.display())),
+    }
+}
+
 async fn handle_connection(
     shared: SharedNetworkState,
-    mut stream: TcpStream,
+    mut stream: Stream,
     remote_addr: SocketAddr,
+    listener_addr: Option<SocketAddr>,
 ) {
     trace!("handling connection");
 
-    if let Err(e) = stream.set_nodelay(true) {
-        error!("failed to set TCP_NODELAY: {e}");
-    }
+    let accepted_at = Instant::now();
 
-    let timeout = Duration::from_secs(5);
+    let mut timer = PhaseTimer::new(ConnectionPhase::Handshake, shared.0.timeouts.handshake);
 
-    match tokio::time::timeout(
-        timeout,
-        try_handle_legacy_ping(&shared, &mut stream, remote_addr),
-    )
-    .await
-    .unwrap_or(Err(io::Error::new(io::ErrorKind::TimedOut, "timed out")))
-    {
-        Ok(true) => return,
-        Ok(false) => {}
-        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {}
-        Err(e) => {
-            warn!("connection ended with error: {e:#}");
+    let remote_addr = if shared.0.proxy_protocol {
+        match timer.run(read_proxy_header(&mut stream)).await {
+            // The header came from the load balancer itself, so the address of the
+            // socket is the real one.
+            Ok(Ok(None)) => remote_addr,
+            Ok(Ok(Some(source))) => source,
+            Ok(Err(e)) => {
+                warn!("dropping connection from {remote_addr}: {e:#}");
+                return;
+            }
+            Err(e) => {
+                trace!("dropping connection from {remote_addr}: {e}");
+                return;
+            }
+        }
+    } else {
+        remote_addr
+    };
+
+    let ip_guard = match &shared.0.connection_limiter {
+        Some(limiter) => match limiter.try_connect(remote_addr.ip(), Instant::now()) {
+            Some(guard) => Some(guard),
+            None => {
+                debug!("dropping connection from {remote_addr}: over connection limits");
+                return;
+            }
+        },
+        None => None,
+    };
+
+    // Legacy pings come straight from old clients, so they are only expected over
+    // TCP.
+    if let Stream::Tcp(stream) = &mut stream {
+        match try_handle_legacy_ping(&shared, stream, remote_addr, &mut timer).await {
+            Ok(true) => return,
+            Ok(false) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {}
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                trace!("dropping connection from {remote_addr}: {e}");
+                return;
+            }
+            Err(e) => {
+                warn!("connection ended with error: {e:#}");
+            }
         }
     }
 
-    let conn = PacketIo::new(stream, PacketEncoder::new(), PacketDecoder::new(), timeout);
+    let mut conn = PacketIo::new(stream, PacketEncoder::new(), PacketDecoder::new(), timer);
+
+    if let Some(settings) = &shared.0.packet_capture {
+        let number = shared.0.connection_count.fetch_add(1, Ordering::Relaxed);
+        let unix_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        match PacketRecorder::new(settings, format!("{unix_time}-{number}"), accepted_at) {
+            Ok(recorder) => {
+                debug!(
+                    "capturing packets from {remote_addr} to {}",
+                    recorder.first_path().display()
+                );
+                conn.set_recorder(recorder);
+            }
+            Err(e) => warn!("failed to capture packets from {remote_addr}: {e:#}"),
+        }
+    }
 
-    if let Err(e) = handle_handshake(shared, conn, remote_addr).await {
+    if let Err(e) = handle_handshake(shared, conn, remote_addr, listener_addr, ip_guard).await {
         // EOF can happen if the client disconnects while joining, which isn't
         // very erroneous.
         if let Some(e) = e.downcast_ref::<io::Error>() {
@@ -106,6 +271,12 @@ async fn handle_connection(
                 return;
             }
         }
+
+        if let Some(phase) = timed_out_phase(&e) {
+            trace!("dropping connection from {remote_addr}: timed out in the {phase} phase");
+            return;
+        }
+
         warn!("connection ended with error: {e:#}");
     }
 }
@@ -120,12 +291,22 @@ pub struct HandshakeData {
     pub server_address: String,
     /// The port that the client used to connect.
     pub server_port: u16,
+    /// The address of the listener the client connected to. See
+    /// [`NetworkSettings::addresses`]. This is `None` for connections to the
+    /// [unix socket listener], and if the data did not come from a connection
+    /// to a listener, such as for query requests.
+    ///
+    /// [`NetworkSettings::addresses`]: crate::NetworkSettings::addresses
+    /// [unix socket listener]: crate::NetworkSettings::unix_socket_path
+    pub listener_addr: Option<SocketAddr>,
 }
 
 async fn handle_handshake(
     shared: SharedNetworkState,
     mut io: PacketIo,
     remote_addr: SocketAddr,
+    listener_addr: Option<SocketAddr>,
+    ip_guard: Option<IpGuard>,
 ) -> anyhow::Result<()> {
     let handshake = io.recv_packet::<HandshakeC2s>().await?;
 
@@ -135,30 +316,32 @@ async fn handle_handshake(
         protocol_version: handshake.protocol_version.0,
         server_address: handshake.server_address.to_owned(),
         server_port: handshake.server_port,
+        listener_addr,
     };
 
     ensure!(
-        matches!(&shared.0.connection_mode, ConnectionMode::BungeeCord)
+        matches!(&shared.0.connection_mode, ConnectionMode::BungeeCord { .. })
             || handshake.server_address.chars().count() <= 255,
         "handshake server address is too long"
     );
 
     match next_state {
-        HandshakeNextState::Status => handle_status(shared, io, remote_addr, handshake)
-            .await
-            .context("error handling status"),
+        HandshakeNextState::Status => {
+            io.start_phase(ConnectionPhase::Status, shared.0.timeouts.status);
+
+            handle_status(shared, io, remote_addr, handshake)
+                .await
+                .context("error handling status")
+        }
         HandshakeNextState::Login => {
+            io.start_phase(ConnectionPhase::Login, shared.0.timeouts.login);
+
             match handle_login(&shared, &mut io, remote_addr, handshake)
                 .await
                 .context("error handling login")?
             {
                 Some((info, cleanup)) => {
-                    let client = io.into_client_args(
-                        info,
-                        shared.0.incoming_byte_limit,
-                        shared.0.outgoing_byte_limit,
-                        cleanup,
-                    );
+                    let client = io.into_client_args(info, cleanup, ip_guard, &shared);
 
                     let _ = shared.0.new_clients_send.send_async(client).await;
 
@@ -178,22 +361,52 @@ async fn handle_status(
 ) -> anyhow::Result<()> {
     io.recv_packet::<QueryRequestC2s>().await?;
 
-    match shared
+    let Some(json) = status_response(&shared, remote_addr, &handshake).await else {
+        return Ok(());
+    };
+
+    io.send_packet(&QueryResponseS2c { json: &json }).await?;
+
+    let QueryPingC2s { payload } = io.recv_packet().await?;
+
+    io.send_packet(&QueryPongS2c { payload }).await?;
+
+    Ok(())
+}
+
+/// Returns the JSON of the status response, or `None` if the ping should be
+/// ignored. Responses are reused until the status cache expires.
+async fn status_response(
+    shared: &SharedNetworkState,
+    remote_addr: SocketAddr,
+    handshake: &HandshakeData,
+) -> Option<Arc<str>> {
+    let cache = &shared.0.status_cache;
+
+    if let Some(response) = cache.get(handshake.protocol_version, Instant::now()) {
+        return response;
+    }
+
+    let response = match shared
         .0
         .callbacks
         .inner
-        .server_list_ping(&shared, remote_addr, &handshake)
+        .server_list_ping(shared, remote_addr, handshake)
         .await
     {
         ServerListPing::Respond {
             online_players,
             max_players,
-            player_sample,
+            mut player_sample,
             description,
             favicon_png,
             version_name,
             protocol,
+            enforces_secure_chat,
+            previews_chat,
         } => {
+            player_sample.truncate(ServerListPing::MAX_PLAYER_SAMPLE_LEN);
+
             let mut json = json!({
                 "version": {
                     "name": version_name,
@@ -205,27 +418,22 @@ async fn handle_status(
                     "sample": player_sample,
                 },
                 "description": description,
+                "enforcesSecureChat": enforces_secure_chat,
+                "previewsChat": previews_chat,
             });
 
             if !favicon_png.is_empty() {
-                let mut buf = "data:image/png;base64,".to_owned();
-                BASE64_STANDARD.encode_string(favicon_png, &mut buf);
-                json["favicon"] = Value::String(buf);
+                json["favicon"] = Value::String(cache.favicon_data_url(favicon_png).to_string());
             }
 
-            io.send_packet(&QueryResponseS2c {
-                json: &json.to_string(),
-            })
-            .await?;
+            Some(json.to_string().into())
         }
-        ServerListPing::Ignore => return Ok(()),
-    }
-
-    let QueryPingC2s { payload } = io.recv_packet().await?;
+        ServerListPing::Ignore => None,
+    };
 
-    io.send_packet(&QueryPongS2c { payload }).await?;
+    cache.insert(handshake.protocol_version, response.clone(), Instant::now());
 
-    Ok(())
+    response
 }
 
 /// Handle the login process and return the new client's data if successful.
@@ -235,8 +443,18 @@ async fn handle_login(
     remote_addr: SocketAddr,
     handshake: HandshakeData,
 ) -> anyhow::Result<Option<(NewClientInfo, CleanupOnDrop)>> {
-    if handshake.protocol_version != PROTOCOL_VERSION {
-        // TODO: send translated disconnect msg.
+    if let Err(reason) = shared
+        .0
+        .callbacks
+        .inner
+        .version_check(shared, remote_addr, &handshake)
+        .await
+    {
+        info!(
+            "disconnect at login: unsupported protocol version {}",
+            handshake.protocol_version
+        );
+        conn.disconnect(reason).await?;
         return Ok(None);
     }
 
@@ -247,38 +465,110 @@ async fn handle_login(
 
     ensure!(is_valid_username(username), "invalid username");
 
+    if let Some(limiter) = &shared.0.connection_limiter {
+        if !limiter.try_login(remote_addr.ip(), Instant::now()) {
+            info!("disconnect at login: too many login attempts from {remote_addr}");
+            conn.disconnect(limiter.disconnect_reason().clone()).await?;
+            return Ok(None);
+        }
+    }
+
     let username = username.to_owned();
 
     let info = match shared.connection_mode() {
         ConnectionMode::Online { .. } => login_online(shared, conn, remote_addr, username).await?,
         ConnectionMode::Offline => login_offline(remote_addr, username)?,
-        ConnectionMode::BungeeCord => {
-            login_bungeecord(remote_addr, &handshake.server_address, username)?
+        ConnectionMode::BungeeCord { allowed_proxies } => {
+            login_bungeecord(
+                conn,
+                remote_addr,
+                &handshake.server_address,
+                username,
+                allowed_proxies.as_deref(),
+            )
+            .await?
         }
         ConnectionMode::Velocity { secret } => login_velocity(conn, username, secret).await?,
     };
 
-    if let Some(threshold) = shared.0.compression_threshold {
+    if let Some(ban_list) = &shared.0.ban_list {
+        if let Err(reason) = ban_list.check(info.uuid, info.ip) {
+            info!("disconnect at login: {} is banned", info.username);
+            conn.disconnect(reason).await?;
+            return Ok(None);
+        }
+    }
+
+    let profile = GameProfile {
+        id: info.uuid,
+        name: info.username.clone(),
+        properties: info.properties.0.clone(),
+    };
+
+    if let Err(reason) = shared
+        .0
+        .callbacks
+        .inner
+        .should_admit(
+            shared,
+            &profile,
+            SocketAddr::new(info.ip, remote_addr.port()),
+        )
+        .await
+    {
+        info!(
+            "disconnect at login: {} was not admitted: \"{reason}\"",
+            info.username
+        );
+        conn.disconnect(reason).await?;
+        return Ok(None);
+    }
+
+    let compression = shared
+        .0
+        .callbacks
+        .inner
+        .compression(shared, remote_addr)
+        .await;
+
+    if let Some(threshold) = compression.threshold {
         conn.send_packet(&LoginCompressionS2c {
             threshold: VarInt(threshold as i32),
         })
         .await?;
-
-        conn.set_compression(Some(threshold));
     }
 
+    conn.set_compression(compression);
+
     let cleanup = match shared.0.callbacks.inner.login(shared, &info).await {
         Ok(f) => CleanupOnDrop(Some(f)),
         Err(reason) => {
             info!("disconnect at login: \"{reason}\"");
-            conn.send_packet(&LoginDisconnectS2c {
-                reason: reason.into(),
-            })
-            .await?;
+            conn.disconnect(reason).await?;
             return Ok(None);
         }
     };
 
+    conn.start_phase(
+        ConnectionPhase::Configuration,
+        shared.0.timeouts.configuration,
+    );
+
+    let mut plugin_conn = LoginPluginConnection::new(conn);
+
+    if let Err(reason) = shared
+        .0
+        .callbacks
+        .inner
+        .configure(shared, &info, &mut plugin_conn)
+        .await
+    {
+        info!("disconnect while configuring: \"{reason}\"");
+        // Fails without sending anything if the client timed out.
+        conn.disconnect(reason).await?;
+        return Ok(None);
+    }
+
     conn.send_packet(&LoginSuccessS2c {
         uuid: info.uuid,
         username: &info.username,
@@ -286,9 +576,44 @@ async fn handle_login(
     })
     .await?;
 
+    let cleanup = add_online_player(shared, &info, cleanup);
+
     Ok(Some((info, cleanup)))
 }
 
+/// Adds the client to the players in [`SharedNetworkState::player_sample`]
+/// until `cleanup` is dropped.
+fn add_online_player(
+    shared: &SharedNetworkState,
+    info: &NewClientInfo,
+    mut cleanup: CleanupOnDrop,
+) -> CleanupOnDrop {
+    let entry = PlayerSampleEntry {
+        name: info.username.clone(),
+        id: info.uuid,
+    };
+
+    shared.0.online_players.lock().unwrap().push(entry);
+
+    let shared = shared.clone();
+    let uuid = info.uuid;
+    let login_cleanup = cleanup.0.take();
+
+    CleanupOnDrop(Some(Box::new(move || {
+        let mut players = shared.0.online_players.lock().unwrap();
+
+        if let Some(idx) = players.iter().position(|p| p.id == uuid) {
+            players.swap_remove(idx);
+        }
+
+        drop(players);
+
+        if let Some(f) = login_cleanup {
+            f();
+        }
+    })))
+}
+
 /// Login procedure for online mode.
 async fn login_online(
     shared: &SharedNetworkState,
@@ -339,46 +664,26 @@ async fn login_online(
         .chain(&shared.0.public_key_der)
         .finalize();
 
-    let url = shared
-        .0
-        .callbacks
-        .inner
-        .session_server(
-            shared,
-            username.as_str(),
-            &auth_digest(&hash),
-            &remote_addr.ip(),
-        )
-        .await;
+    let ip = match shared.connection_mode() {
+        ConnectionMode::Online {
+            prevent_proxy_connections: true,
+        } => Some(remote_addr.ip()),
+        _ => None,
+    };
 
-    let resp = shared.0.http_client.get(url).send().await?;
+    let profile = match shared
+        .0
+        .session_authenticator
+        .has_joined(&username, &auth_digest(&hash), ip)
+        .await
+    {
+        Ok(profile) => profile,
+        Err(e) => {
+            conn.disconnect(e.disconnect_reason()).await?;
 
-    match resp.status() {
-        StatusCode::OK => {}
-        StatusCode::NO_CONTENT => {
-            let reason = Text::translate(
-                translation_key::MULTIPLAYER_DISCONNECT_UNVERIFIED_USERNAME,
-                [],
-            );
-            conn.send_packet(&LoginDisconnectS2c {
-                reason: reason.into(),
-            })
-            .await?;
-            bail!("session server could not verify username");
+            return Err(anyhow::Error::new(e).context("failed to authenticate"));
         }
-        status => {
-            bail!("session server GET request failed (status code {status})");
-        }
-    }
-
-    #[derive(Debug, Deserialize)]
-    struct GameProfile {
-        id: Uuid,
-        name: String,
-        properties: Vec<Property>,
-    }
-
-    let profile: GameProfile = resp.json().await.context("parsing game profile")?;
+    };
 
     ensure!(
         is_valid_username(&profile.name),
@@ -415,59 +720,100 @@ fn login_offline(remote_addr: SocketAddr, username: String) -> anyhow::Result<Ne
 }
 
 /// Login procedure for BungeeCord.
-fn login_bungeecord(
+async fn login_bungeecord(
+    conn: &mut PacketIo,
     remote_addr: SocketAddr,
     server_address: &str,
     username: String,
+    allowed_proxies: Option<&[IpAddr]>,
 ) -> anyhow::Result<NewClientInfo> {
+    if let Some(allowed_proxies) = allowed_proxies {
+        if !allowed_proxies.contains(&remote_addr.ip()) {
+            conn.disconnect("You must connect to this server through its proxy.")
+                .await?;
+            bail!("connection did not originate from an allowed BungeeCord proxy");
+        }
+    }
+
     // Get data from server_address field of the handshake
-    let data = server_address.split('\0').take(4).collect::<Vec<_>>();
+    let data = server_address.split('\0').collect::<Vec<_>>();
 
-    // Ip of player, only given if ip_forward on bungee is true
-    let ip = match data.get(1) {
-        Some(ip) => ip.parse()?,
-        None => remote_addr.ip(),
-    };
+    // A handshake without any forwarded data means the client connected directly
+    // or the proxy does not have `ip_forward` enabled.
+    if data.len() == 1 {
+        conn.disconnect(
+            "This server requires IP forwarding to be enabled in the BungeeCord config.",
+        )
+        .await?;
+        bail!("missing BungeeCord forwarding data");
+    }
 
-    // Uuid of player, only given if ip_forward on bungee is true
-    let uuid = match data.get(2) {
-        Some(uuid) => uuid.parse()?,
-        None => offline_uuid(username.as_str())?,
-    };
+    match parse_bungeecord_forwarding(&data) {
+        Ok((ip, uuid, properties)) => Ok(NewClientInfo {
+            uuid,
+            username,
+            properties: properties.into(),
+            ip,
+        }),
+        Err(e) => {
+            conn.disconnect("Invalid IP forwarding data from the proxy.")
+                .await?;
+            Err(e.context("malformed BungeeCord forwarding data"))
+        }
+    }
+}
+
+/// Parses the fields of the handshake server address smuggled in by
+/// BungeeCord. The fields are the original server address, the player's IP,
+/// the player's UUID and optionally the properties of the player's game
+/// profile.
+fn parse_bungeecord_forwarding(data: &[&str]) -> anyhow::Result<(IpAddr, Uuid, Vec<Property>)> {
+    ensure!(
+        data.len() == 3 || data.len() == 4,
+        "expected 3 or 4 fields, got {}",
+        data.len()
+    );
+
+    let ip = data[1].parse().context("failed to parse player IP")?;
 
-    // Read properties and get textures
-    // Properties of player's game profile, only given if ip_forward and online_mode
-    // on bungee both are true
-    let properties: Vec<Property> = match data.get(3) {
+    let uuid = data[2].parse().context("failed to parse player UUID")?;
+
+    // Properties are only given if `online_mode` is also enabled on the proxy.
+    let properties = match data.get(3) {
         Some(properties) => serde_json::from_str(properties)
             .context("failed to parse BungeeCord player properties")?,
         None => vec![],
     };
 
-    Ok(NewClientInfo {
-        uuid,
-        username,
-        properties: properties.into(),
-        ip,
-    })
+    Ok((ip, uuid, properties))
 }
 
+/// The version of Velocity's modern forwarding format that only includes the
+/// player's address, UUID, username and properties.
+const VELOCITY_MODERN_FORWARDING_DEFAULT: i32 = 1;
+/// Adds the player's signed chat key.
+const VELOCITY_MODERN_FORWARDING_WITH_KEY: i32 = 2;
+/// Adds the player's signed chat key and the UUID of the key's holder.
+const VELOCITY_MODERN_FORWARDING_WITH_KEY_V2: i32 = 3;
+/// The same data as [`VELOCITY_MODERN_FORWARDING_DEFAULT`], but the player's
+/// chat session is sent later in the play state.
+const VELOCITY_MODERN_FORWARDING_LAZY_SESSION: i32 = 4;
+
 /// Login procedure for Velocity.
 async fn login_velocity(
     io: &mut PacketIo,
     username: String,
     velocity_secret: &str,
 ) -> anyhow::Result<NewClientInfo> {
-    const VELOCITY_MIN_SUPPORTED_VERSION: u8 = 1;
-    const VELOCITY_MODERN_FORWARDING_WITH_KEY_V2: i32 = 3;
-
     let message_id: i32 = 0; // TODO: make this random?
 
-    // Send Player Info Request into the Plugin Channel
+    // Send Player Info Request into the Plugin Channel. The payload is the highest
+    // forwarding version we support, and Velocity responds with the highest
+    // version that both sides understand.
     io.send_packet(&LoginQueryRequestS2c {
         message_id: VarInt(message_id),
         channel: ident!("velocity:player_info").into(),
-        data: RawBytes(&[VELOCITY_MIN_SUPPORTED_VERSION]),
+        data: RawBytes(&[VELOCITY_MODERN_FORWARDING_LAZY_SESSION as u8]),
     })
     .await?;
 
@@ -480,57 +826,118 @@ async fn login_velocity(
         plugin_response.message_id.0,
     );
 
-    let data = plugin_response
-        .data
-        .context("missing plugin response data")?
-        .0;
+    // A vanilla client will not understand the request, which means the client
+    // did not connect through Velocity.
+    let Some(RawBytes(data)) = plugin_response.data else {
+        io.disconnect("This server requires you to connect with Velocity.")
+            .await?;
+        bail!("client did not connect through Velocity");
+    };
+
+    let Some(mut data) = verify_velocity_signature(data, velocity_secret) else {
+        io.disconnect("Unable to verify player details.").await?;
+        bail!("invalid Velocity player info signature");
+    };
 
-    ensure!(data.len() >= 32, "invalid plugin response data length");
-    let (signature, mut data_without_signature) = data.split_at(32);
+    let info = VelocityPlayerInfo::decode(&mut data).context("decoding Velocity player info")?;
+
+    trace!("Velocity forwarding version {}", info.version);
+
+    // Get username and validate
+    ensure!(username == info.username, "mismatched usernames");
+
+    Ok(NewClientInfo {
+        uuid: info.uuid,
+        username,
+        properties: info.properties.into(),
+        ip: info.remote_addr,
+    })
+}
 
-    // Verify signature
-    let mut mac = Hmac::<Sha256>::new_from_slice(velocity_secret.as_bytes())?;
+/// Checks the HMAC signature at the start of Velocity's player info response.
+/// Returns the data following the signature if the signature is valid.
+fn verify_velocity_signature<'a>(data: &'a [u8], secret: &str) -> Option<&'a [u8]> {
+    if data.len() < 32 {
+        return None;
+    }
+
+    let (signature, data_without_signature) = data.split_at(32);
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).ok()?;
     Mac::update(&mut mac, data_without_signature);
-    mac.verify_slice(signature)?;
+    mac.verify_slice(signature).ok()?;
 
-    // Check Velocity version
-    let version = VarInt::decode(&mut data_without_signature)
-        .context("failed to decode velocity version")?
-        .0;
+    Some(data_without_signature)
+}
 
-    // Get client address
-    let remote_addr = String::decode(&mut data_without_signature)?.parse()?;
+/// The player data forwarded by Velocity, excluding the signature.
+#[derive(Clone, PartialEq, Debug)]
+struct VelocityPlayerInfo<'a> {
+    version: i32,
+    remote_addr: IpAddr,
+    uuid: Uuid,
+    username: &'a str,
+    properties: Vec<Property>,
+}
 
-    // Get UUID
-    let uuid = Uuid::decode(&mut data_without_signature)?;
+impl<'a> Decode<'a> for VelocityPlayerInfo<'a> {
+    fn decode(r: &mut &'a [u8]) -> anyhow::Result<Self> {
+        let version = VarInt::decode(r)
+            .context("failed to decode velocity version")?
+            .0;
 
-    // Get username and validate
-    ensure!(
-        username == <&str>::decode(&mut data_without_signature)?,
-        "mismatched usernames"
-    );
+        ensure!(
+            (VELOCITY_MODERN_FORWARDING_DEFAULT..=VELOCITY_MODERN_FORWARDING_LAZY_SESSION)
+                .contains(&version),
+            "unsupported Velocity forwarding version {version}"
+        );
 
-    // Read game profile properties
-    let properties = Vec::<Property>::decode(&mut data_without_signature)
-        .context("decoding velocity game profile properties")?;
+        let remote_addr = <&str>::decode(r)?
+            .parse()
+            .context("failed to parse forwarded address")?;
+
+        let uuid = Uuid::decode(r)?;
+        let username = <&str>::decode(r)?;
+
+        let properties =
+            Vec::<Property>::decode(r).context("decoding velocity game profile properties")?;
+
+        // The player's chat signing key, which Velocity only forwards for 1.19 to 1.19.2
+        // clients. Clients on this version send their key in a `PlayerSessionC2s` packet
+        // after joining, which becomes their `ChatSession`, so the key is read and
+        // discarded.
+        if version == VELOCITY_MODERN_FORWARDING_WITH_KEY
+            || version == VELOCITY_MODERN_FORWARDING_WITH_KEY_V2
+        {
+            let _expires_at = i64::decode(r)?;
+            let _public_key = <&[u8]>::decode(r)?;
+            let _signature = <&[u8]>::decode(r)?;
+
+            if version == VELOCITY_MODERN_FORWARDING_WITH_KEY_V2 {
+                let _holder = Option::<Uuid>::decode(r)?;
+            }
+        }
 
-    if version >= VELOCITY_MODERN_FORWARDING_WITH_KEY_V2 {
-        // TODO
+        Ok(Self {
+            version,
+            remote_addr,
+            uuid,
+            username,
+            properties,
+        })
     }
-
-    Ok(NewClientInfo {
-        uuid,
-        username,
-        properties: properties.into(),
-        ip: remote_addr,
-    })
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use sha1::Digest;
+    use tokio::net::TcpStream;
+    use valence_core::protocol::Encode;
 
     use super::*;
+    use crate::packet::LoginDisconnectS2c;
 
     #[test]
     fn auth_digest_usernames() {
@@ -547,4 +954,294 @@ mod tests {
             "88e16a1019277b15d58faf0541e11910eb756f6"
         );
     }
+
+    /// Returns the server and client ends of a new loopback connection.
+    async fn connected_pair() -> (PacketIo, PacketIo) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let timeout = Duration::from_secs(5);
+
+        let (client, server) = tokio::join!(TcpStream::connect(addr), listener.accept());
+
+        let mut server = PacketIo::new(
+            Stream::Tcp(server.unwrap().0),
+            PacketEncoder::new(),
+            PacketDecoder::new(),
+            PhaseTimer::new(ConnectionPhase::Login, timeout),
+        );
+
+        // The server side sends login packets.
+        server.start_phase(ConnectionPhase::Login, timeout);
+
+        (
+            server,
+            PacketIo::new(
+                Stream::Tcp(client.unwrap()),
+                PacketEncoder::new(),
+                PacketDecoder::new(),
+                PhaseTimer::new(ConnectionPhase::Login, timeout),
+            ),
+        )
+    }
+
+    /// Returns the reason of the next packet if it is a login disconnect.
+    async fn recv_disconnect(io: &mut PacketIo) -> Option<String> {
+        io.recv_packet::<LoginDisconnectS2c>()
+            .await
+            .ok()
+            .map(|pkt| pkt.reason.to_legacy_lossy())
+    }
+
+    const VELOCITY_SECRET: &str = "hunter2";
+
+    fn velocity_properties() -> Vec<Property> {
+        vec![Property {
+            name: "textures".into(),
+            value: "e30=".into(),
+            signature: Some("c2lnbmF0dXJl".into()),
+        }]
+    }
+
+    /// Encodes the player info response Velocity would send for the given
+    /// forwarding version, signed with `secret`.
+    fn velocity_player_info(version: i32, secret: &str) -> Vec<u8> {
+        let mut body = vec![];
+
+        VarInt(version).encode(&mut body).unwrap();
+        "192.168.0.42".encode(&mut body).unwrap();
+        Uuid::from_u128(0xdead_beef).encode(&mut body).unwrap();
+        "Notch".encode(&mut body).unwrap();
+        velocity_properties().encode(&mut body).unwrap();
+
+        if version == VELOCITY_MODERN_FORWARDING_WITH_KEY
+            || version == VELOCITY_MODERN_FORWARDING_WITH_KEY_V2
+        {
+            1_700_000_000_000_i64.encode(&mut body).unwrap();
+            [1_u8, 2, 3].as_slice().encode(&mut body).unwrap();
+            [4_u8, 5, 6, 7].as_slice().encode(&mut body).unwrap();
+
+            if version == VELOCITY_MODERN_FORWARDING_WITH_KEY_V2 {
+                Some(Uuid::from_u128(0xdead_beef))
+                    .encode(&mut body)
+                    .unwrap();
+            }
+        }
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        Mac::update(&mut mac, &body);
+
+        let mut data = mac.finalize().into_bytes().to_vec();
+        data.extend_from_slice(&body);
+        data
+    }
+
+    #[test]
+    fn velocity_forwarding_versions() {
+        for version in VELOCITY_MODERN_FORWARDING_DEFAULT..=VELOCITY_MODERN_FORWARDING_LAZY_SESSION
+        {
+            let data = velocity_player_info(version, VELOCITY_SECRET);
+
+            let mut r = verify_velocity_signature(&data, VELOCITY_SECRET).unwrap();
+            let info = VelocityPlayerInfo::decode(&mut r).unwrap();
+
+            assert!(r.is_empty(), "trailing data for version {version}");
+            assert_eq!(
+                info,
+                VelocityPlayerInfo {
+                    version,
+                    remote_addr: "192.168.0.42".parse().unwrap(),
+                    uuid: Uuid::from_u128(0xdead_beef),
+                    username: "Notch",
+                    properties: velocity_properties(),
+                }
+            );
+        }
+
+        let data = velocity_player_info(5, VELOCITY_SECRET);
+        let mut r = verify_velocity_signature(&data, VELOCITY_SECRET).unwrap();
+        assert!(VelocityPlayerInfo::decode(&mut r).is_err());
+    }
+
+    #[test]
+    fn velocity_bad_signature() {
+        let data = velocity_player_info(VELOCITY_MODERN_FORWARDING_DEFAULT, "wrong secret");
+        assert!(verify_velocity_signature(&data, VELOCITY_SECRET).is_none());
+
+        let mut data = velocity_player_info(VELOCITY_MODERN_FORWARDING_DEFAULT, VELOCITY_SECRET);
+        *data.last_mut().unwrap() ^= 1;
+        assert!(verify_velocity_signature(&data, VELOCITY_SECRET).is_none());
+
+        assert!(verify_velocity_signature(&[0; 16], VELOCITY_SECRET).is_none());
+    }
+
+    /// Runs the Velocity login procedure against a fake proxy which responds
+    /// to the player info request with `response`. Returns the result of the
+    /// login along with the packet the proxy received after its response, if
+    /// any.
+    async fn fake_velocity_login(
+        response: Option<Vec<u8>>,
+    ) -> (anyhow::Result<NewClientInfo>, Option<String>) {
+        let (mut io, mut proxy_io) = connected_pair().await;
+
+        let proxy = tokio::spawn(async move {
+            let io = &mut proxy_io;
+
+            let request: LoginQueryRequestS2c = io.recv_packet().await.unwrap();
+            assert_eq!(request.channel.as_str(), "velocity:player_info");
+            assert_eq!(
+                request.data.0,
+                [VELOCITY_MODERN_FORWARDING_LAZY_SESSION as u8]
+            );
+            let message_id = request.message_id;
+
+            io.send_packet(&LoginQueryResponseC2s {
+                message_id,
+                data: response.as_deref().map(RawBytes),
+            })
+            .await
+            .unwrap();
+
+            recv_disconnect(io).await
+        });
+
+        let res = login_velocity(&mut io, "Notch".into(), VELOCITY_SECRET).await;
+        drop(io);
+
+        (res, proxy.await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn velocity_login() {
+        let data = velocity_player_info(VELOCITY_MODERN_FORWARDING_WITH_KEY_V2, VELOCITY_SECRET);
+
+        let (res, disconnect) = fake_velocity_login(Some(data)).await;
+        let info = res.unwrap();
+
+        assert_eq!(disconnect, None);
+        assert_eq!(info.username, "Notch");
+        assert_eq!(info.uuid, Uuid::from_u128(0xdead_beef));
+        assert_eq!(info.ip, "192.168.0.42".parse::<IpAddr>().unwrap());
+        assert_eq!(info.properties.0, velocity_properties());
+    }
+
+    #[tokio::test]
+    async fn velocity_login_rejected() {
+        let data = velocity_player_info(VELOCITY_MODERN_FORWARDING_DEFAULT, "wrong secret");
+
+        let (res, disconnect) = fake_velocity_login(Some(data)).await;
+        assert!(res.is_err());
+        assert_eq!(
+            disconnect.as_deref(),
+            Some("Unable to verify player details.")
+        );
+
+        // A client that didn't connect through Velocity doesn't understand the
+        // request.
+        let (res, disconnect) = fake_velocity_login(None).await;
+        assert!(res.is_err());
+        assert_eq!(
+            disconnect.as_deref(),
+            Some("This server requires you to connect with Velocity.")
+        );
+    }
+
+    const BUNGEECORD_PROPERTIES: &str =
+        r#"[{"name":"textures","value":"e30=","signature":"c2lnbmF0dXJl"}]"#;
+
+    const BUNGEECORD_UUID: &str = "069a79f444e94726a5befca90e38aaf5";
+
+    /// Runs the BungeeCord login procedure with a handshake server address made
+    /// of the given null-separated fields. Returns the result of the login
+    /// along with the disconnect reason sent to the client, if any.
+    async fn bungeecord_login(
+        fields: &[&str],
+        allowed_proxies: Option<&[IpAddr]>,
+    ) -> (anyhow::Result<NewClientInfo>, Option<String>) {
+        let (mut io, mut client_io) = connected_pair().await;
+
+        let res = login_bungeecord(
+            &mut io,
+            "127.0.0.1:12345".parse().unwrap(),
+            &fields.join("\0"),
+            "Notch".into(),
+            allowed_proxies,
+        )
+        .await;
+        drop(io);
+
+        (res, recv_disconnect(&mut client_io).await)
+    }
+
+    #[tokio::test]
+    async fn bungeecord_login_forwarded() {
+        let forwarded = [
+            "localhost",
+            "192.168.0.42",
+            BUNGEECORD_UUID,
+            BUNGEECORD_PROPERTIES,
+        ];
+
+        let (res, disconnect) = bungeecord_login(&forwarded, None).await;
+        let info = res.unwrap();
+
+        assert_eq!(disconnect, None);
+        assert_eq!(info.username, "Notch");
+        assert_eq!(
+            info.uuid,
+            Uuid::from_u128(0x069a79f4_44e9_4726_a5be_fca90e38aaf5)
+        );
+        assert_eq!(info.ip, "192.168.0.42".parse::<IpAddr>().unwrap());
+        assert_eq!(info.properties.0, velocity_properties());
+
+        // Properties are left out when the proxy is in offline mode.
+        let (res, _) = bungeecord_login(
+            &["localhost", "192.168.0.42", BUNGEECORD_UUID],
+            Some(&["127.0.0.1".parse().unwrap()]),
+        )
+        .await;
+        assert!(res.unwrap().properties.0.is_empty());
+    }
+
+    #[tokio::test]
+    async fn bungeecord_login_rejected() {
+        // Not forwarded.
+        let (res, disconnect) = bungeecord_login(&["localhost"], None).await;
+        assert!(res.is_err());
+        assert_eq!(
+            disconnect.as_deref(),
+            Some("This server requires IP forwarding to be enabled in the BungeeCord config.")
+        );
+
+        // Malformed.
+        for fields in [
+            &["localhost", "192.168.0.42"][..],
+            &["localhost", "not an ip", BUNGEECORD_UUID],
+            &["localhost", "192.168.0.42", "not a uuid"],
+            &["localhost", "192.168.0.42", BUNGEECORD_UUID, "{"],
+            &["localhost", "192.168.0.42", BUNGEECORD_UUID, "[]", "extra"],
+        ] {
+            let (res, disconnect) = bungeecord_login(fields, None).await;
+            assert!(res.is_err());
+            assert_eq!(
+                disconnect.as_deref(),
+                Some("Invalid IP forwarding data from the proxy.")
+            );
+        }
+
+        // Not from an allowed proxy.
+        let forwarded = [
+            "localhost",
+            "192.168.0.42",
+            BUNGEECORD_UUID,
+            BUNGEECORD_PROPERTIES,
+        ];
+
+        let (res, disconnect) =
+            bungeecord_login(&forwarded, Some(&["10.0.0.1".parse().unwrap()])).await;
+        assert!(res.is_err());
+        assert_eq!(
+            disconnect.as_deref(),
+            Some("You must connect to this server through its proxy.")
+        );
+    }
 }
@@ -0,0 +1,336 @@
+//! Limits on new connections, to slow down bots opening many connections from
+//! a few addresses.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use valence_core::text::Text;
+
+use crate::rate_limit::{RateLimit, TokenBucket};
+
+/// How often idle addresses are forgotten.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Limits on new connections to the server. See
+/// [`NetworkSettings::connection_limits`].
+///
+/// Limits are enforced as early as possible so that rejected connections cost
+/// very little. Connections over [`Self::max_connections_per_ip`] or
+/// [`Self::new_connections`] are dropped without a response as soon as they
+/// are accepted. Login attempts over [`Self::login_attempts_per_ip`] are
+/// disconnected with [`Self::disconnect_reason`] before encryption and
+/// authentication take place.
+///
+/// When [`NetworkSettings::proxy_protocol`] is enabled, the address from the
+/// PROXY header is limited. Otherwise, every client behind a proxy shares the
+/// address of the proxy, as do clients connected to the [unix socket].
+///
+/// [`NetworkSettings::connection_limits`]: crate::NetworkSettings::connection_limits
+/// [`NetworkSettings::proxy_protocol`]: crate::NetworkSettings::proxy_protocol
+/// [unix socket]: crate::NetworkSettings::unix_socket_path
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConnectionLimits {
+    /// The maximum number of simultaneous connections from a single IP
+    /// address, including clients in the play state.
+    pub max_connections_per_ip: Option<usize>,
+    /// The maximum number of login attempts from a single IP address in a
+    /// time window. Server list pings are not login attempts.
+    pub login_attempts_per_ip: Option<AttemptLimit>,
+    /// The maximum number of new connections to the server from any address
+    /// in a time window.
+    pub new_connections: Option<AttemptLimit>,
+    /// The message clients over [`Self::login_attempts_per_ip`] are
+    /// disconnected with.
+    pub disconnect_reason: Text,
+}
+
+impl Default for ConnectionLimits {
+    /// The default limits are generous enough for players sharing an address.
+    /// The exact values are left unspecified and may change in future
+    /// versions.
+    fn default() -> Self {
+        Self {
+            max_connections_per_ip: Some(16),
+            login_attempts_per_ip: Some(AttemptLimit::new(10, Duration::from_secs(60))),
+            new_connections: Some(AttemptLimit::new(200, Duration::from_secs(1))),
+            disconnect_reason: "Connection throttled! Please wait before reconnecting.".into(),
+        }
+    }
+}
+
+/// A limit of `max` attempts in any `window` of time. Attempts are forgotten
+/// gradually, at a rate of `max` per `window`, rather than all at once at the
+/// end of the window.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct AttemptLimit {
+    pub max: u32,
+    pub window: Duration,
+}
+
+impl AttemptLimit {
+    pub const fn new(max: u32, window: Duration) -> Self {
+        Self { max, window }
+    }
+
+    fn bucket(self, now: Instant) -> TokenBucket {
+        let limit = RateLimit::new(self.max as f64 / self.window.as_secs_f64(), self.max as f64);
+
+        TokenBucket::new(limit, now)
+    }
+}
+
+/// The state of the connection limits shared by all connections.
+#[derive(Debug)]
+pub(crate) struct ConnectionLimiter {
+    limits: ConnectionLimits,
+    state: Mutex<LimiterState>,
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    new_connections: Option<TokenBucket>,
+    ips: HashMap<IpAddr, IpState>,
+    last_prune: Instant,
+}
+
+#[derive(Debug, Default)]
+struct IpState {
+    connections: usize,
+    login_attempts: Option<TokenBucket>,
+}
+
+impl IpState {
+    /// Whether the address can be forgotten without loosening the limits.
+    fn is_idle(&self, now: Instant) -> bool {
+        self.connections == 0
+            && self
+                .login_attempts
+                .as_ref()
+                .is_none_or(|bucket| bucket.is_full(now))
+    }
+}
+
+impl ConnectionLimiter {
+    pub(crate) fn new(limits: ConnectionLimits, now: Instant) -> Self {
+        Self {
+            state: Mutex::new(LimiterState {
+                new_connections: limits.new_connections.map(|limit| limit.bucket(now)),
+                ips: HashMap::new(),
+                last_prune: now,
+            }),
+            limits,
+        }
+    }
+
+    pub(crate) fn disconnect_reason(&self) -> &Text {
+        &self.limits.disconnect_reason
+    }
+
+    /// Counts a new connection from `ip` at `now`. Returns a guard that keeps
+    /// the connection counted until it is dropped, or `None` if the
+    /// connection is over the limits and should be dropped.
+    pub(crate) fn try_connect(self: &Arc<Self>, ip: IpAddr, now: Instant) -> Option<IpGuard> {
+        let mut state = self.state.lock().unwrap();
+
+        if now.saturating_duration_since(state.last_prune) >= PRUNE_INTERVAL {
+            state.ips.retain(|_, ip| !ip.is_idle(now));
+            state.last_prune = now;
+        }
+
+        let connections = state.ips.get(&ip).map_or(0, |ip| ip.connections);
+
+        if self
+            .limits
+            .max_connections_per_ip
+            .is_some_and(|max| connections >= max)
+        {
+            return None;
+        }
+
+        if let Some(bucket) = &mut state.new_connections {
+            if !bucket.try_take(now) {
+                return None;
+            }
+        }
+
+        state.ips.entry(ip).or_default().connections += 1;
+
+        Some(IpGuard {
+            limiter: self.clone(),
+            ip,
+        })
+    }
+
+    /// Counts a login attempt from `ip` at `now`. Returns whether the attempt
+    /// is within the limits.
+    pub(crate) fn try_login(&self, ip: IpAddr, now: Instant) -> bool {
+        let Some(limit) = self.limits.login_attempts_per_ip else {
+            return true;
+        };
+
+        let mut state = self.state.lock().unwrap();
+
+        state
+            .ips
+            .entry(ip)
+            .or_default()
+            .login_attempts
+            .get_or_insert_with(|| limit.bucket(now))
+            .try_take(now)
+    }
+
+    fn disconnect(&self, ip: IpAddr, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(entry) = state.ips.get_mut(&ip) {
+            entry.connections -= 1;
+
+            if entry.is_idle(now) {
+                state.ips.remove(&ip);
+            }
+        }
+    }
+}
+
+/// Keeps a connection counted towards
+/// [`ConnectionLimits::max_connections_per_ip`] until dropped.
+#[derive(Debug)]
+pub(crate) struct IpGuard {
+    limiter: Arc<ConnectionLimiter>,
+    ip: IpAddr,
+}
+
+impl Drop for IpGuard {
+    fn drop(&mut self) {
+        self.limiter.disconnect(self.ip, Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(limits: ConnectionLimits) -> Arc<ConnectionLimiter> {
+        Arc::new(ConnectionLimiter::new(limits, Instant::now()))
+    }
+
+    fn no_limits() -> ConnectionLimits {
+        ConnectionLimits {
+            max_connections_per_ip: None,
+            login_attempts_per_ip: None,
+            new_connections: None,
+            disconnect_reason: Text::default(),
+        }
+    }
+
+    const IP_A: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1));
+    const IP_B: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 2));
+
+    #[test]
+    fn simultaneous_connections_per_ip() {
+        let limiter = limiter(ConnectionLimits {
+            max_connections_per_ip: Some(3),
+            ..no_limits()
+        });
+        let now = Instant::now();
+
+        let mut guards: Vec<_> = (0..3)
+            .map(|_| limiter.try_connect(IP_A, now).unwrap())
+            .collect();
+
+        // The rest of the burst is dropped, but other addresses are unaffected.
+        assert!(limiter.try_connect(IP_A, now).is_none());
+        assert!(limiter.try_connect(IP_B, now).is_some());
+
+        // Closing a connection makes room for another.
+        guards.pop();
+        guards.push(limiter.try_connect(IP_A, now).unwrap());
+        assert!(limiter.try_connect(IP_A, now).is_none());
+
+        // Addresses are forgotten once all of their connections are closed.
+        drop(guards);
+        assert!(limiter.state.lock().unwrap().ips.is_empty());
+    }
+
+    #[test]
+    fn login_attempts_decay() {
+        let limiter = limiter(ConnectionLimits {
+            login_attempts_per_ip: Some(AttemptLimit::new(5, Duration::from_secs(10))),
+            ..no_limits()
+        });
+        let start = Instant::now();
+
+        for _ in 0..5 {
+            assert!(limiter.try_login(IP_A, start));
+        }
+        assert!(!limiter.try_login(IP_A, start));
+        assert!(limiter.try_login(IP_B, start));
+
+        // One attempt is forgotten every two seconds.
+        let later = start + Duration::from_secs(5);
+        assert!(limiter.try_login(IP_A, later));
+        assert!(limiter.try_login(IP_A, later));
+        assert!(!limiter.try_login(IP_A, later));
+
+        // Attempts don't accumulate past the limit.
+        let much_later = later + Duration::from_secs(10);
+        for _ in 0..5 {
+            assert!(limiter.try_login(IP_A, much_later));
+        }
+        assert!(!limiter.try_login(IP_A, much_later));
+    }
+
+    #[test]
+    fn new_connection_rate() {
+        let limiter = limiter(ConnectionLimits {
+            new_connections: Some(AttemptLimit::new(10, Duration::from_secs(1))),
+            ..no_limits()
+        });
+        let start = Instant::now();
+
+        // The rate is shared by all addresses.
+        let guards: Vec<_> = (0..10)
+            .filter_map(|i| {
+                let ip = if i % 2 == 0 { IP_A } else { IP_B };
+                limiter.try_connect(ip, start)
+            })
+            .collect();
+
+        assert_eq!(guards.len(), 10);
+        assert!(limiter.try_connect(IP_A, start).is_none());
+
+        // Closing connections doesn't reset the rate.
+        drop(guards);
+        assert!(limiter.try_connect(IP_B, start).is_none());
+
+        let later = start + Duration::from_millis(300);
+        let guards: Vec<_> = (0..5)
+            .filter_map(|_| limiter.try_connect(IP_A, later))
+            .collect();
+
+        assert_eq!(guards.len(), 3);
+    }
+
+    #[test]
+    fn idle_addresses_are_pruned() {
+        let limiter = limiter(ConnectionLimits {
+            login_attempts_per_ip: Some(AttemptLimit::new(2, Duration::from_secs(10))),
+            ..no_limits()
+        });
+        let start = Instant::now();
+
+        assert!(limiter.try_login(IP_A, start));
+        assert!(limiter.try_login(IP_B, start));
+        assert_eq!(limiter.state.lock().unwrap().ips.len(), 2);
+
+        // The attempts have decayed by the time addresses are pruned.
+        let later = start + PRUNE_INTERVAL;
+        let _guard = limiter.try_connect(IP_A, later).unwrap();
+
+        let state = limiter.state.lock().unwrap();
+        assert_eq!(state.ips.len(), 1);
+        assert_eq!(state.ips[&IP_A].connections, 1);
+    }
+}
@@ -0,0 +1,64 @@
+use bytes::Bytes;
+use uuid::Uuid;
+
+/// A low-level hook that sees the raw data of every packet sent and received
+/// by clients in the play state. Packets can be forwarded unchanged, dropped,
+/// or replaced before the server or client handles them.
+///
+/// This is meant for debugging and for features which can't be implemented
+/// with the typed packet events, such as filtering a packet sent by a mod.
+/// Register an interceptor with [`NetworkSettings::packet_interceptor`].
+///
+/// The methods of this trait are called on the network threads, so they must
+/// not block. Packets are passed to the interceptor in the order they are
+/// sent or received, and the order of forwarded and replaced packets is
+/// preserved.
+///
+/// # Performance
+///
+/// Every packet is passed to the interceptor, so it should be fast. Enabling
+/// an interceptor also moves the encryption of outgoing packets from the main
+/// thread to the network threads, and each outgoing packet needs to be
+/// decompressed before it is passed to
+/// [`intercept_outgoing`](Self::intercept_outgoing). Replaced packets are
+/// compressed again. Forwarded packets are copied as they are.
+///
+/// [`NetworkSettings::packet_interceptor`]: crate::NetworkSettings::packet_interceptor
+pub trait PacketInterceptor: Send + Sync + 'static {
+    /// Called for every packet received from a client, before it is passed to
+    /// the server. `body` is the data of the packet following the ID.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns [`PacketAction::Forward`].
+    fn intercept_incoming(&self, client: Uuid, id: i32, body: &[u8]) -> PacketAction {
+        #![allow(unused_variables)]
+
+        PacketAction::Forward
+    }
+
+    /// Called for every packet sent to a client, before it is compressed and
+    /// encrypted. `body` is the data of the packet following the ID.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns [`PacketAction::Forward`].
+    fn intercept_outgoing(&self, client: Uuid, id: i32, body: &[u8]) -> PacketAction {
+        #![allow(unused_variables)]
+
+        PacketAction::Forward
+    }
+}
+
+/// What to do with a packet passed to a [`PacketInterceptor`].
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub enum PacketAction {
+    /// Sends or handles the packet as normal.
+    #[default]
+    Forward,
+    /// Discards the packet.
+    Drop,
+    /// Sends or handles the given packet instead. The bytes are the packet's
+    /// VarInt ID followed by the packet's body.
+    Replace(Bytes),
+}
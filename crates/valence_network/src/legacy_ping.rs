@@ -2,10 +2,11 @@ use std::io;
 use std::net::SocketAddr;
 use std::time::Duration;
 
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio::time::sleep;
 
+use crate::timeout::PhaseTimer;
 use crate::{ServerListLegacyPing, SharedNetworkState};
 
 /// The payload of the legacy server list ping.
@@ -55,7 +56,7 @@ pub struct ServerListLegacyPingResponse {
     description: String,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 enum PingFormat {
     Pre1_4, // Beta 1.8 to 1.3
     Pre1_6, // 1.4 to 1.5
@@ -67,9 +68,10 @@ pub(crate) async fn try_handle_legacy_ping(
     shared: &SharedNetworkState,
     stream: &mut TcpStream,
     remote_addr: SocketAddr,
+    timer: &mut PhaseTimer,
 ) -> io::Result<bool> {
     let mut temp_buf = [0u8; 3];
-    let mut n = stream.peek(&mut temp_buf).await?;
+    let mut n = timer.run(stream.peek(&mut temp_buf)).await??;
 
     if let [0xfe] | [0xfe, 0x01] = &temp_buf[..n] {
         // This could mean one of following things:
@@ -97,8 +99,8 @@ pub(crate) async fn try_handle_legacy_ping(
         // In my opinion, 1 is insignificant, and 2/3 are so rare that they are
         // effectively insignificant too. Network IO is just not that reliable
         // at this level, the connection may be lost as well or something at this point.
-        sleep(Duration::from_millis(10)).await;
-        n = stream.peek(&mut temp_buf).await?;
+        timer.run(sleep(Duration::from_millis(10))).await?;
+        n = timer.run(stream.peek(&mut temp_buf)).await??;
     }
 
     let format = match &temp_buf[..n] {
@@ -109,11 +111,17 @@ pub(crate) async fn try_handle_legacy_ping(
     };
 
     let payload = match format {
-        PingFormat::Pre1_7 => read_payload(stream).await?,
+        PingFormat::Pre1_7 => timer.run(read_payload(stream)).await??,
         PingFormat::Pre1_6 => ServerListLegacyPingPayload::Pre1_6,
         PingFormat::Pre1_4 => ServerListLegacyPingPayload::Pre1_4,
     };
 
+    if format != PingFormat::Pre1_7 {
+        // Consume the peeked bytes. Closing the connection with unread data would
+        // reset it, and the client might never see the response.
+        timer.run(stream.read_exact(&mut temp_buf[..n])).await??;
+    }
+
     if let ServerListLegacyPing::Respond(mut response) = shared
         .0
         .callbacks
@@ -126,63 +134,71 @@ pub(crate) async fn try_handle_legacy_ping(
             remove_formatting(&mut response.description);
         }
 
-        let separator = match format {
-            PingFormat::Pre1_4 => '§',
-            _ => '\0',
-        };
-
-        let mut buf = Vec::new();
-
-        // packet ID and length placeholder
-        buf.extend([0xff, 0x00, 0x00]);
-
-        if format != PingFormat::Pre1_4 {
-            // some constant bytes lol
-            buf.extend("§1\0".encode_utf16().flat_map(|c| c.to_be_bytes()));
-
-            // protocol and version
-            buf.extend(
-                format!(
-                    "{protocol}{separator}{version}{separator}",
-                    protocol = response.protocol,
-                    version = response.version
-                )
-                .encode_utf16()
-                .flat_map(|c| c.to_be_bytes()),
-            );
-        }
+        timer
+            .run(stream.write_all(&encode_response(format, &response)))
+            .await??;
+    }
 
-        // Description
-        buf.extend(
-            response
-                .description
-                .encode_utf16()
-                .flat_map(|c| c.to_be_bytes()),
-        );
+    Ok(true)
+}
+
+fn encode_response(format: PingFormat, response: &ServerListLegacyPingResponse) -> Vec<u8> {
+    let separator = match format {
+        PingFormat::Pre1_4 => '§',
+        _ => '\0',
+    };
 
-        // Online and max players
+    let mut buf = Vec::new();
+
+    // packet ID and length placeholder
+    buf.extend([0xff, 0x00, 0x00]);
+
+    if format != PingFormat::Pre1_4 {
+        // some constant bytes lol
+        buf.extend("§1\0".encode_utf16().flat_map(|c| c.to_be_bytes()));
+
+        // protocol and version
         buf.extend(
             format!(
-                "{separator}{online_players}{separator}{max_players}",
-                online_players = response.online_players,
-                max_players = response.max_players
+                "{protocol}{separator}{version}{separator}",
+                protocol = response.protocol,
+                version = response.version
             )
             .encode_utf16()
             .flat_map(|c| c.to_be_bytes()),
         );
+    }
 
-        // replace the length placeholder with the actual length
-        let chars = (buf.len() as u16 - 3) / 2; // -3 because of the packet prefix (id and length), and /2 because UTF16
-        buf[1..3].copy_from_slice(chars.to_be_bytes().as_slice());
+    // Description
+    buf.extend(
+        response
+            .description
+            .encode_utf16()
+            .flat_map(|c| c.to_be_bytes()),
+    );
 
-        stream.write_all(&buf).await?;
-    }
+    // Online and max players
+    buf.extend(
+        format!(
+            "{separator}{online_players}{separator}{max_players}",
+            online_players = response.online_players,
+            max_players = response.max_players
+        )
+        .encode_utf16()
+        .flat_map(|c| c.to_be_bytes()),
+    );
 
-    Ok(true)
+    // replace the length placeholder with the actual length
+    let chars = (buf.len() as u16 - 3) / 2; // -3 because of the packet prefix (id and length), and /2 because UTF16
+    buf[1..3].copy_from_slice(chars.to_be_bytes().as_slice());
+
+    buf
 }
 
 // Reads the payload of a 1.6 legacy ping
-async fn read_payload(stream: &mut TcpStream) -> io::Result<ServerListLegacyPingPayload> {
+async fn read_payload<R: AsyncRead + Unpin>(
+    stream: &mut R,
+) -> io::Result<ServerListLegacyPingPayload> {
     // consume the first 29 useless bytes of this amazing protocol
     stream.read_exact(&mut [0u8; 29]).await?;
 
@@ -311,17 +327,17 @@ impl ServerListLegacyPingResponse {
 
 // Returns the length of a string representation of a signed integer
 fn int_len(num: i32) -> usize {
-    let num_abs = num.abs() as f64;
+    let digits = num.unsigned_abs().checked_ilog10().unwrap_or(0) as usize + 1;
 
     if num < 0 {
-        (num_abs.log10() + 2.0) as usize // because minus sign
+        digits + 1 // because minus sign
     } else {
-        (num_abs.log10() + 1.0) as usize
+        digits
     }
 }
 
 // Removes all `§` and their modifiers, if any
-fn remove_formatting(string: &mut String) {
+pub(crate) fn remove_formatting(string: &mut String) {
     while let Some(pos) = string.find('§') {
         // + 2 because we know that `§` is 2 bytes
         if let Some(c) = string[(pos + 2)..].chars().next() {
@@ -332,3 +348,82 @@ fn remove_formatting(string: &mut String) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16_be(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(|c| c.to_be_bytes()).collect()
+    }
+
+    fn response() -> ServerListLegacyPingResponse {
+        ServerListLegacyPingResponse::new(127, 0, 20)
+            .version("1.4.2".into())
+            .description("A Minecraft Server".into())
+    }
+
+    #[tokio::test]
+    async fn read_1_6_payload() {
+        let mut ping: &[u8] = &[
+            0xfe, 0x01, 0xfa, 0x00, 0x0b, 0x00, 0x4d, 0x00, 0x43, 0x00, 0x7c, 0x00, 0x50, 0x00,
+            0x69, 0x00, 0x6e, 0x00, 0x67, 0x00, 0x48, 0x00, 0x6f, 0x00, 0x73, 0x00, 0x74, 0x00,
+            0x19, 0x4a, 0x00, 0x09, 0x00, 0x6c, 0x00, 0x6f, 0x00, 0x63, 0x00, 0x61, 0x00, 0x6c,
+            0x00, 0x68, 0x00, 0x6f, 0x00, 0x73, 0x00, 0x74, 0x00, 0x00, 0x63, 0xdd,
+        ];
+
+        assert_eq!(
+            read_payload(&mut ping).await.unwrap(),
+            ServerListLegacyPingPayload::Pre1_7 {
+                protocol: 74,
+                hostname: "localhost".into(),
+                port: 25565,
+            }
+        );
+        assert!(ping.is_empty());
+    }
+
+    #[test]
+    fn encode_responses() {
+        let body = "§1\x00127\x001.4.2\x00A Minecraft Server\x000\x0020";
+        let mut expected = vec![0xff, 0x00, 36];
+        expected.extend(utf16_be(body));
+
+        assert_eq!(encode_response(PingFormat::Pre1_7, &response()), expected);
+        assert_eq!(encode_response(PingFormat::Pre1_6, &response()), expected);
+
+        let body = "A Minecraft Server§0§20";
+        let mut expected = vec![0xff, 0x00, 23];
+        expected.extend(utf16_be(body));
+
+        assert_eq!(encode_response(PingFormat::Pre1_4, &response()), expected);
+    }
+
+    #[test]
+    fn response_length_limit() {
+        let response = ServerListLegacyPingResponse::new(127, 0, 20)
+            .version("1.4.2".into())
+            .description("a".repeat(300));
+
+        // 248 characters minus "127", "1.4.2", "0" and "20".
+        assert_eq!(response.description.len(), 237);
+        assert_eq!(response.max_description(), 237);
+    }
+
+    #[test]
+    fn int_lengths() {
+        assert_eq!(int_len(0), 1);
+        assert_eq!(int_len(9), 1);
+        assert_eq!(int_len(10), 2);
+        assert_eq!(int_len(-1), 2);
+        assert_eq!(int_len(i32::MAX), 10);
+        assert_eq!(int_len(i32::MIN), 11);
+    }
+
+    #[test]
+    fn strip_formatting() {
+        let mut s = "§aA §lValence§r Server§".to_owned();
+        remove_formatting(&mut s);
+        assert_eq!(s, "A Valence Server");
+    }
+}
@@ -17,38 +17,79 @@
     clippy::dbg_macro
 )]
 
+mod auth;
+mod ban_list;
 mod byte_channel;
+pub mod capture;
 mod connect;
+mod connection_limit;
+mod intercept;
 mod legacy_ping;
+mod login_plugin;
 pub mod packet;
 mod packet_io;
+mod proxy_protocol;
+mod query;
+mod rate_limit;
+mod rcon;
+mod status_cache;
+mod stream;
+mod timeout;
+mod whitelist;
 
 use std::borrow::Cow;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
-use std::time::Duration;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use anyhow::Context;
 pub use async_trait::async_trait;
+pub use auth::{AuthError, GameProfile, SessionAuthenticator, SessionServerAuthenticator};
+pub use ban_list::{BanList, IpBan, IpCidr, IpCidrParseError, PlayerBan};
 use bevy_app::prelude::*;
+use bevy_app::AppExit;
 use bevy_ecs::prelude::*;
+use capture::PacketCaptureSettings;
 use connect::do_accept_loop;
 pub use connect::HandshakeData;
+#[cfg(unix)]
+use connect::{do_unix_accept_loop, remove_unix_socket};
+use connection_limit::ConnectionLimiter;
+pub use connection_limit::{AttemptLimit, ConnectionLimits};
 use flume::{Receiver, Sender};
+pub use intercept::{PacketAction, PacketInterceptor};
 pub use legacy_ping::{ServerListLegacyPingPayload, ServerListLegacyPingResponse};
+pub use login_plugin::LoginPluginConnection;
+use query::do_query_loop;
 use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+pub use rate_limit::{
+    PacketCategory, PacketRateLimits, PacketRateStats, RateLimit, RateLimitPolicy,
+};
+use rcon::do_rcon_accept_loop;
+pub use rcon::{RconCommandEvent, RconSettings};
 use rsa::{PublicKeyParts, RsaPrivateKey};
 use serde::Serialize;
+use status_cache::StatusCache;
+pub use status_cache::StatusPingStats;
+pub use timeout::ConnectionTimeouts;
 use tokio::net::UdpSocket;
 use tokio::runtime::{Handle, Runtime};
-use tokio::sync::Semaphore;
+use tokio::sync::{watch, Semaphore};
 use tokio::time;
 use tracing::error;
 use uuid::Uuid;
-use valence_client::{ClientBundle, ClientBundleArgs, Properties, SpawnClientsSet};
+use valence_client::chat_session::ChatSessionSettings;
+use valence_client::{
+    Client, ClientBundle, ClientBundleArgs, DisconnectClient, Ip, Properties, SpawnClientsSet,
+};
 use valence_core::text::Text;
-use valence_core::{Server, MINECRAFT_VERSION, PROTOCOL_VERSION};
+use valence_core::uuid::UniqueId;
+use valence_core::{translation_key, Server, MINECRAFT_VERSION, PROTOCOL_VERSION};
+pub use whitelist::{Whitelist, WhitelistEntry};
 
 pub struct NetworkPlugin;
 
@@ -61,11 +102,15 @@ impl Plugin for NetworkPlugin {
 }
 
 fn build_plugin(app: &mut App) -> anyhow::Result<()> {
-    let compression_threshold = app
+    let server = app
         .world
         .get_resource::<Server>()
-        .context("missing server resource")?
-        .compression_threshold();
+        .context("missing server resource")?;
+
+    let compression = CompressionSettings {
+        threshold: server.compression_threshold(),
+        level: server.compression_level(),
+    };
 
     let settings = app
         .world
@@ -73,6 +118,8 @@ fn build_plugin(app: &mut App) -> anyhow::Result<()> {
 
     let (new_clients_send, new_clients_recv) = flume::bounded(64);
 
+    let (rcon_commands_send, rcon_commands_recv) = flume::bounded(64);
+
     let rsa_key = RsaPrivateKey::new(&mut OsRng, 1024)?;
 
     let public_key_der =
@@ -92,33 +139,101 @@ fn build_plugin(app: &mut App) -> anyhow::Result<()> {
 
     let shared = SharedNetworkState(Arc::new(SharedNetworkStateInner {
         callbacks: settings.callbacks.clone(),
-        address: settings.address,
+        addresses: settings.addresses.clone(),
         incoming_byte_limit: settings.incoming_byte_limit,
         outgoing_byte_limit: settings.outgoing_byte_limit,
         connection_sema: Arc::new(Semaphore::new(
             settings.max_connections.min(Semaphore::MAX_PERMITS),
         )),
+        shutdown: watch::channel(false).0,
         player_count: AtomicUsize::new(0),
+        online_players: Mutex::new(vec![]),
+        status_cache: StatusCache::new(settings.status_cache_ttl),
         max_players: settings.max_players,
         connection_mode: settings.connection_mode.clone(),
-        compression_threshold,
+        proxy_protocol: settings.proxy_protocol,
+        query_address: settings.query_address,
+        rcon: settings.rcon.clone(),
+        packet_interceptor: settings.packet_interceptor.clone(),
+        packet_rate_limits: settings.packet_rate_limits.clone(),
+        connection_limiter: settings
+            .connection_limits
+            .clone()
+            .map(|limits| Arc::new(ConnectionLimiter::new(limits, Instant::now()))),
+        ban_list: settings.ban_list.clone(),
+        timeouts: settings.timeouts,
+        packet_capture: settings.packet_capture.clone(),
+        connection_count: AtomicU64::new(0),
+        enforces_secure_chat: AtomicBool::new(false),
+        #[cfg(unix)]
+        unix_socket_path: settings.unix_socket_path.clone(),
+        compression,
         tokio_handle,
         _tokio_runtime: runtime,
         new_clients_send,
         new_clients_recv,
+        rcon_commands_send,
+        rcon_commands_recv,
         rsa_key,
         public_key_der,
-        http_client: reqwest::Client::new(),
+        session_authenticator: settings.session_authenticator.clone(),
     }));
 
     app.insert_resource(shared.clone());
 
-    // System for starting the accept loop.
+    // In online mode, chat sessions must be signed by the keys of the session
+    // authenticator. Sessions are rejected until the keys have been fetched.
+    let mut chat_settings = app
+        .world
+        .get_resource_or_insert_with(ChatSessionSettings::default);
+
+    let fetch_certificate_keys = matches!(shared.0.connection_mode, ConnectionMode::Online { .. })
+        && chat_settings.trusted_keys.is_none();
+
+    if fetch_certificate_keys {
+        chat_settings.trusted_keys = Some(vec![]);
+    }
+
+    shared.0.enforces_secure_chat.store(
+        chat_settings.signed_chat.enforces_secure_chat(),
+        Ordering::Relaxed,
+    );
+
+    // System for starting the accept loops.
     let start_accept_loop = move |shared: Res<SharedNetworkState>| {
         let _guard = shared.0.tokio_handle.enter();
 
-        // Start accepting new connections.
-        tokio::spawn(do_accept_loop(shared.clone()));
+        // Start accepting new connections on every address.
+        for &address in &shared.0.addresses {
+            tokio::spawn(do_accept_loop(shared.clone(), address));
+        }
+
+        #[cfg(unix)]
+        if let Some(path) = shared.0.unix_socket_path.clone() {
+            tokio::spawn(do_unix_accept_loop(shared.clone(), path));
+        }
+    };
+
+    // System for closing the listeners and removing the unix socket when the
+    // server stops.
+    let shut_down_on_exit = |shared: Res<SharedNetworkState>, mut exit: EventReader<AppExit>| {
+        if exit.is_empty() {
+            return;
+        }
+
+        exit.clear();
+
+        // Accept loops waiting for a permit stop when the semaphore is closed,
+        // and the ones waiting for a connection stop on the shutdown signal.
+        shared.0.connection_sema.close();
+        shared.0.shutdown.send_replace(true);
+
+        #[cfg(unix)]
+        if let Some(path) = &shared.0.unix_socket_path {
+            if let Err(e) = remove_unix_socket(path) {
+                error!("{e:#}");
+            }
+        }
     };
 
     let start_broadcast_to_lan_loop = move |shared: Res<SharedNetworkState>| {
@@ -127,11 +242,95 @@ fn build_plugin(app: &mut App) -> anyhow::Result<()> {
         tokio::spawn(do_broadcast_to_lan_loop(shared.clone()));
     };
 
+    let start_query_loop = move |shared: Res<SharedNetworkState>| {
+        if let Some(address) = shared.0.query_address {
+            let _guard = shared.0.tokio_handle.enter();
+
+            tokio::spawn(do_query_loop(shared.clone(), address));
+        }
+    };
+
+    let start_rcon_accept_loop = move |shared: Res<SharedNetworkState>| {
+        if let Some(settings) = shared.0.rcon.clone() {
+            let _guard = shared.0.tokio_handle.enter();
+
+            tokio::spawn(do_rcon_accept_loop(shared.clone(), settings));
+        }
+    };
+
+    let (certificate_keys_send, certificate_keys_recv) = flume::bounded(1);
+
+    let start_fetch_certificate_keys = move |shared: Res<SharedNetworkState>| {
+        let _guard = shared.0.tokio_handle.enter();
+
+        let authenticator = shared.0.session_authenticator.clone();
+        let keys_send = certificate_keys_send.clone();
+
+        tokio::spawn(async move {
+            match authenticator.player_certificate_keys().await {
+                Ok(keys) => {
+                    let _ = keys_send.send(keys);
+                }
+                Err(e) => error!("failed to fetch player certificate keys: {e:#}"),
+            }
+        });
+    };
+
+    let receive_certificate_keys = move |mut settings: ResMut<ChatSessionSettings>| {
+        if let Ok(keys) = certificate_keys_recv.try_recv() {
+            settings.trusted_keys = Some(keys);
+        }
+    };
+
+    // System for mirroring the chat session settings the network threads need.
+    let sync_chat_session_settings =
+        |shared: Res<SharedNetworkState>, settings: Res<ChatSessionSettings>| {
+            if settings.is_changed() {
+                shared.0.enforces_secure_chat.store(
+                    settings.signed_chat.enforces_secure_chat(),
+                    Ordering::Relaxed,
+                );
+            }
+        };
+
+    // System for disconnecting the clients in the play state once they are
+    // banned.
+    let has_ban_list = shared.0.ban_list.is_some();
+    let kick_banned_clients = |shared: Res<SharedNetworkState>,
+                               clients: Query<(Entity, &UniqueId, &Ip), With<Client>>,
+                               mut last_additions: Local<u64>,
+                               mut commands: Commands| {
+        let Some(ban_list) = &shared.0.ban_list else {
+            return;
+        };
+
+        let additions = ban_list.additions();
+
+        if additions == *last_additions {
+            return;
+        }
+
+        *last_additions = additions;
+
+        for (client, uuid, ip) in &clients {
+            if let Err(reason) = ban_list.check(uuid.0, ip.0) {
+                commands.add(DisconnectClient { client, reason });
+            }
+        }
+    };
+
+    // System for sending commands from RCON clients to the main schedule.
+    let send_rcon_commands =
+        |shared: Res<SharedNetworkState>, mut events: EventWriter<RconCommandEvent>| {
+            events.send_batch(shared.0.rcon_commands_recv.try_iter());
+        };
+
     // System for spawning new clients.
     let spawn_new_clients = move |world: &mut World| {
         for _ in 0..shared.0.new_clients_recv.len() {
             match shared.0.new_clients_recv.try_recv() {
-                Ok(args) => world.spawn(ClientBundle::new(args)),
+                Ok((args, Some(stats))) => world.spawn((ClientBundle::new(args), stats)),
+                Ok((args, None)) => world.spawn(ClientBundle::new(args)),
                 Err(_) => break,
             };
         }
@@ -141,12 +340,35 @@ fn build_plugin(app: &mut App) -> anyhow::Result<()> {
     // run first.
     app.add_systems(PostStartup, start_accept_loop);
 
+    app.add_systems(Last, shut_down_on_exit);
+
     // Start the loop that will broadcast messages for the LAN discovery list.
     app.add_systems(PostStartup, start_broadcast_to_lan_loop);
 
+    // Start answering query requests if enabled.
+    app.add_systems(PostStartup, start_query_loop);
+
+    // Start accepting RCON connections if enabled.
+    app.add_systems(PostStartup, start_rcon_accept_loop);
+
+    // Fetch the keys chat sessions are signed with if needed.
+    if fetch_certificate_keys {
+        app.add_systems(PostStartup, start_fetch_certificate_keys)
+            .add_systems(PreUpdate, receive_certificate_keys);
+    }
+
+    app.add_systems(PreUpdate, sync_chat_session_settings);
+
     // Spawn new clients before the event loop starts.
     app.add_systems(PreUpdate, spawn_new_clients.in_set(SpawnClientsSet));
 
+    app.add_event::<RconCommandEvent>()
+        .add_systems(PreUpdate, send_rcon_commands);
+
+    if has_ban_list {
+        app.add_systems(PostUpdate, kick_banned_clients);
+    }
+
     Ok(())
 }
 
@@ -165,35 +387,117 @@ impl SharedNetworkState {
     pub fn max_players(&self) -> usize {
         self.0.max_players
     }
+
+    /// Returns up to [`ServerListPing::MAX_PLAYER_SAMPLE_LEN`] players chosen
+    /// at random from the clients that have logged in and are still
+    /// connected, like the player sample of vanilla servers.
+    pub fn player_sample(&self) -> Vec<PlayerSampleEntry> {
+        self.0
+            .online_players
+            .lock()
+            .unwrap()
+            .choose_multiple(
+                &mut rand::thread_rng(),
+                ServerListPing::MAX_PLAYER_SAMPLE_LEN,
+            )
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the usernames of all clients that have logged in and are still
+    /// connected.
+    pub fn player_names(&self) -> Vec<String> {
+        self.0
+            .online_players
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|p| p.name.clone())
+            .collect()
+    }
+
+    /// Discards the cached server list ping response, if any, so that the next
+    /// ping calls [`NetworkCallbacks::server_list_ping`] again. Call this when
+    /// the response would change, such as when a player joins. See
+    /// [`NetworkSettings::status_cache_ttl`].
+    pub fn invalidate_status_cache(&self) {
+        self.0.status_cache.invalidate();
+    }
+
+    /// Returns how many server list pings were answered from the cache and
+    /// by the callback.
+    pub fn status_ping_stats(&self) -> StatusPingStats {
+        self.0.status_cache.stats()
+    }
+
+    /// The compression settings of the [`Server`], which are used for
+    /// connections unless overridden by [`NetworkCallbacks::compression`].
+    pub fn compression(&self) -> CompressionSettings {
+        self.0.compression
+    }
+
+    /// The bans of the server, if [`NetworkSettings::ban_list`] is set.
+    pub fn ban_list(&self) -> Option<&BanList> {
+        self.0.ban_list.as_ref()
+    }
+
+    /// Whether the server enforces secure chat according to the
+    /// [`ChatSessionSettings`] as of the last tick.
+    pub fn enforces_secure_chat(&self) -> bool {
+        self.0.enforces_secure_chat.load(Ordering::Relaxed)
+    }
 }
 struct SharedNetworkStateInner {
     callbacks: ErasedNetworkCallbacks,
-    address: SocketAddr,
+    addresses: Vec<SocketAddr>,
     incoming_byte_limit: usize,
     outgoing_byte_limit: usize,
     /// Limits the number of simultaneous connections to the server before the
     /// play state.
     connection_sema: Arc<Semaphore>,
+    /// Set to `true` when the server stops to close the listeners.
+    shutdown: watch::Sender<bool>,
     //// The number of clients in the play state, past the login state.
     player_count: AtomicUsize,
+    /// The clients past the login state, for the player sample.
+    online_players: Mutex<Vec<PlayerSampleEntry>>,
+    status_cache: StatusCache,
     max_players: usize,
     connection_mode: ConnectionMode,
-    compression_threshold: Option<u32>,
+    proxy_protocol: bool,
+    query_address: Option<SocketAddr>,
+    rcon: Option<RconSettings>,
+    packet_interceptor: Option<Arc<dyn PacketInterceptor>>,
+    packet_rate_limits: Option<PacketRateLimits>,
+    connection_limiter: Option<Arc<ConnectionLimiter>>,
+    ban_list: Option<BanList>,
+    timeouts: ConnectionTimeouts,
+    packet_capture: Option<PacketCaptureSettings>,
+    /// The number of connections accepted so far, to name capture files.
+    connection_count: AtomicU64,
+    /// Mirrors the chat session settings for the status response.
+    enforces_secure_chat: AtomicBool,
+    #[cfg(unix)]
+    unix_socket_path: Option<PathBuf>,
+    compression: CompressionSettings,
     tokio_handle: Handle,
     // Holding a runtime handle is not enough to keep tokio working. We need
     // to store the runtime here so we don't drop it.
     _tokio_runtime: Option<Runtime>,
     /// Sender for new clients past the login stage.
-    new_clients_send: Sender<ClientBundleArgs>,
+    new_clients_send: Sender<(ClientBundleArgs, Option<PacketRateStats>)>,
     /// Receiver for new clients past the login stage.
-    new_clients_recv: Receiver<ClientBundleArgs>,
+    new_clients_recv: Receiver<(ClientBundleArgs, Option<PacketRateStats>)>,
+    /// Sender for commands from authenticated RCON clients.
+    rcon_commands_send: Sender<RconCommandEvent>,
+    /// Receiver for commands from authenticated RCON clients.
+    rcon_commands_recv: Receiver<RconCommandEvent>,
     /// The RSA keypair used for encryption with clients.
     rsa_key: RsaPrivateKey,
     /// The public part of `rsa_key` encoded in DER, which is an ASN.1 format.
     /// This is sent to clients during the authentication process.
     public_key_der: Box<[u8]>,
-    /// For session server requests.
-    http_client: reqwest::Client,
+    session_authenticator: Arc<dyn SessionAuthenticator>,
 }
 
 /// Contains information about a new client joining the server.
@@ -236,12 +540,33 @@ pub struct NetworkSettings {
     ///
     /// `20`
     pub max_players: usize,
-    /// The socket address the server will be bound to.
+    /// The socket addresses the server will listen on. Connections to every
+    /// address are handled the same way. For example, to listen on both IPv4
+    /// and IPv6, add `0.0.0.0:25565` and `[::]:25565`.
+    ///
+    /// If an address can't be bound, an error is logged and the server keeps
+    /// listening on the others. The listeners are closed when an [`AppExit`]
+    /// event is sent.
+    ///
+    /// # Default Value
+    ///
+    /// `[0.0.0.0:25565]`, which will listen on every available IPv4 network
+    /// interface.
+    pub addresses: Vec<SocketAddr>,
+    /// The path of a unix socket the server will also listen on. This is
+    /// useful when the server is behind a proxy on the same machine.
+    ///
+    /// Unix sockets have no IP addresses, so clients connected to the socket
+    /// have a remote address of `0.0.0.0:0` unless [`Self::proxy_protocol`]
+    /// is enabled. The file is removed when an [`AppExit`] event is sent. A
+    /// socket left at the path by a previous run is replaced, but the server
+    /// won't listen on the socket if anything else exists at the path.
     ///
     /// # Default Value
     ///
-    /// `0.0.0.0:25565`, which will listen on every available network interface.
-    pub address: SocketAddr,
+    /// `None`
+    #[cfg(unix)]
+    pub unix_socket_path: Option<PathBuf>,
     /// The connection mode. This determines if client authentication and
     /// encryption should take place and if the server should get the player
     /// data from a proxy.
@@ -252,6 +577,68 @@ pub struct NetworkSettings {
     ///
     /// [`ConnectionMode::Online`]
     pub connection_mode: ConnectionMode,
+    /// Verifies that players have joined the server with the session server
+    /// in [online mode].
+    ///
+    /// # Default Value
+    ///
+    /// A [`SessionServerAuthenticator`] using Mojang's session server.
+    ///
+    /// [online mode]: ConnectionMode::Online
+    pub session_authenticator: Arc<dyn SessionAuthenticator>,
+    /// Whether every accepted connection is expected to begin with a v1 or v2
+    /// [PROXY protocol] header, as sent by load balancers such as HAProxy.
+    ///
+    /// The source address in the header is used in place of the address of
+    /// the socket everywhere the client's address is exposed. Connections
+    /// that do not begin with a valid header are dropped, so this must only
+    /// be enabled when all connections go through the load balancer.
+    ///
+    /// # Default Value
+    ///
+    /// `false`
+    ///
+    /// [PROXY protocol]: https://www.haproxy.org/download/2.8/doc/proxy-protocol.txt
+    pub proxy_protocol: bool,
+    /// The socket address of the UDP socket used to answer [query protocol]
+    /// requests from server lists and hosting panels. If `None`, query
+    /// requests are not answered. The response is determined by
+    /// [`NetworkCallbacks::server_query`].
+    ///
+    /// Vanilla servers use the same port for queries as the game, which is
+    /// possible because the query protocol uses UDP instead of TCP.
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    ///
+    /// [query protocol]: https://wiki.vg/Query
+    pub query_address: Option<SocketAddr>,
+    /// Settings for the [RCON] server, which lets administrators run commands
+    /// remotely. If `None`, the RCON server is not started.
+    ///
+    /// Commands from authenticated RCON clients are sent as
+    /// [`RconCommandEvent`]s in [`PreUpdate`].
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    ///
+    /// [RCON]: https://wiki.vg/RCON
+    pub rcon: Option<RconSettings>,
+    /// How long a response from [`NetworkCallbacks::server_list_ping`] is
+    /// reused for. If `None`, the callback is called for every ping.
+    ///
+    /// While a response is cached, every client with the same protocol version
+    /// gets the same response regardless of its address or the rest of its
+    /// handshake. Use
+    /// [`SharedNetworkState::invalidate_status_cache`] to discard the cached
+    /// response early.
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    pub status_cache_ttl: Option<Duration>,
     /// The maximum capacity (in bytes) of the buffer used to hold incoming
     /// packet data.
     ///
@@ -274,6 +661,57 @@ pub struct NetworkSettings {
     ///
     /// The default value is left unspecified and may change in future versions.
     pub outgoing_byte_limit: usize,
+    /// A hook that sees the raw data of every packet sent and received by
+    /// clients in the play state. See [`PacketInterceptor`] for the
+    /// performance cost of enabling this.
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    pub packet_interceptor: Option<Arc<dyn PacketInterceptor>>,
+    /// Limits on the rate of packets received from each client in the play
+    /// state. If this is `Some`, a [`PacketRateStats`] component is added to
+    /// every client so that throttled clients can be inspected.
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    pub packet_rate_limits: Option<PacketRateLimits>,
+    /// Limits on new connections from each IP address and on the rate of new
+    /// connections overall. See [`ConnectionLimits`].
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    pub connection_limits: Option<ConnectionLimits>,
+    /// The player and IP bans of the server. Banned players are disconnected
+    /// right after they authenticate, before
+    /// [`NetworkCallbacks::should_admit`] is called. Players in the play state
+    /// are disconnected at the end of the tick they are banned in.
+    ///
+    /// Keep a clone of the [`BanList`] or use [`SharedNetworkState::ban_list`]
+    /// to ban players while the server is running.
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    pub ban_list: Option<BanList>,
+    /// How long clients may take in each phase of the connection before the
+    /// play state. See [`ConnectionTimeouts`].
+    ///
+    /// # Default Value
+    ///
+    /// 10 seconds for the handshake and status phases, and 30 seconds for the
+    /// login and configuration phases.
+    pub timeouts: ConnectionTimeouts,
+    /// Records the packets sent and received by every connection to files,
+    /// for debugging. See [`capture`] for the format of the files and how to
+    /// read them.
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    pub packet_capture: Option<PacketCaptureSettings>,
 }
 
 impl Default for NetworkSettings {
@@ -283,12 +721,25 @@ impl Default for NetworkSettings {
             tokio_handle: None,
             max_connections: 1024,
             max_players: 20,
-            address: SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 25565).into(),
+            addresses: vec![SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 25565).into()],
+            #[cfg(unix)]
+            unix_socket_path: None,
             connection_mode: ConnectionMode::Online {
                 prevent_proxy_connections: false,
             },
+            session_authenticator: Arc::new(SessionServerAuthenticator::default()),
+            proxy_protocol: false,
+            query_address: None,
+            rcon: None,
+            status_cache_ttl: None,
             incoming_byte_limit: 2097152, // 2 MiB
             outgoing_byte_limit: 8388608, // 8 MiB
+            packet_interceptor: None,
+            packet_rate_limits: None,
+            connection_limits: None,
+            ban_list: None,
+            timeouts: ConnectionTimeouts::default(),
+            packet_capture: None,
         }
     }
 }
@@ -328,11 +779,19 @@ pub trait NetworkCallbacks: Send + Sync + 'static {
     /// Called when the server receives a Server List Ping query.
     /// Data for the response can be provided or the query can be ignored.
     ///
+    /// The response can depend on the client's
+    /// [`HandshakeData::protocol_version`], such as to ask clients with an old
+    /// version to update in the description and with
+    /// [`ServerListPing::Respond::version_name`]. Clients show the version
+    /// name in place of the player count when the protocol in the response is
+    /// different from theirs.
+    ///
     /// This function is called from within a tokio runtime.
     ///
     /// # Default Implementation
     ///
-    /// A default placeholder response is returned.
+    /// A default placeholder response is returned, with a sample of the
+    /// players on the server from [`SharedNetworkState::player_sample`].
     async fn server_list_ping(
         &self,
         shared: &SharedNetworkState,
@@ -344,11 +803,13 @@ pub trait NetworkCallbacks: Send + Sync + 'static {
         ServerListPing::Respond {
             online_players: shared.player_count().load(Ordering::Relaxed) as i32,
             max_players: shared.max_players() as i32,
-            player_sample: vec![],
+            player_sample: shared.player_sample(),
             description: "A Valence Server".into(),
             favicon_png: &[],
             version_name: MINECRAFT_VERSION.to_owned(),
             protocol: PROTOCOL_VERSION,
+            enforces_secure_chat: shared.enforces_secure_chat(),
+            previews_chat: false,
         }
     }
 
@@ -359,15 +820,14 @@ pub trait NetworkCallbacks: Send + Sync + 'static {
     ///
     /// # Default Implementation
     ///
-    /// [`server_list_ping`][Self::server_list_ping] re-used.
+    /// [`server_list_ping`][Self::server_list_ping] re-used, with the
+    /// description converted to plain text.
     async fn server_list_legacy_ping(
         &self,
         shared: &SharedNetworkState,
         remote_addr: SocketAddr,
         payload: ServerListLegacyPingPayload,
     ) -> ServerListLegacyPing {
-        #![allow(unused_variables)]
-
         let handshake_data = match payload {
             ServerListLegacyPingPayload::Pre1_7 {
                 protocol,
@@ -377,6 +837,7 @@ pub trait NetworkCallbacks: Send + Sync + 'static {
                 protocol_version: protocol,
                 server_address: hostname,
                 server_port: port,
+                listener_addr: None,
             },
             _ => HandshakeData::default(),
         };
@@ -388,20 +849,71 @@ pub trait NetworkCallbacks: Send + Sync + 'static {
             ServerListPing::Respond {
                 online_players,
                 max_players,
-                player_sample,
                 description,
-                favicon_png,
                 version_name,
                 protocol,
-            } => ServerListLegacyPing::Respond(
-                ServerListLegacyPingResponse::new(protocol, online_players, max_players)
-                    .version(version_name)
-                    .description(description.to_legacy_lossy()),
-            ),
+                ..
+            } => {
+                let mut description = description.to_legacy_lossy();
+                legacy_ping::remove_formatting(&mut description);
+
+                ServerListLegacyPing::Respond(
+                    ServerListLegacyPingResponse::new(protocol, online_players, max_players)
+                        .version(version_name)
+                        .description(description),
+                )
+            }
             ServerListPing::Ignore => ServerListLegacyPing::Ignore,
         }
     }
 
+    /// Called when the server receives a [query protocol] stat request. Data
+    /// for the response can be provided or the request can be ignored. This
+    /// is not called unless [`NetworkSettings::query_address`] is set.
+    ///
+    /// Unlike the server list ping, the response to a query contains the names
+    /// of all players on the server. This can be provided by overriding this
+    /// method.
+    ///
+    /// This function is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// [`server_list_ping`][Self::server_list_ping] re-used, with the names
+    /// of all players from [`SharedNetworkState::player_names`] as the player
+    /// list.
+    ///
+    /// [query protocol]: https://wiki.vg/Query
+    async fn server_query(
+        &self,
+        shared: &SharedNetworkState,
+        remote_addr: SocketAddr,
+    ) -> ServerQuery {
+        match self
+            .server_list_ping(shared, remote_addr, &HandshakeData::default())
+            .await
+        {
+            ServerListPing::Respond {
+                online_players,
+                max_players,
+                description,
+                version_name,
+                ..
+            } => ServerQuery::Respond(ServerQueryResponse {
+                motd: description.to_legacy_lossy(),
+                game_type: "SMP".into(),
+                map: "world".into(),
+                online_players,
+                max_players,
+                version: version_name,
+                plugins: String::new(),
+                // The player sample is a random subset, so it isn't used here.
+                players: shared.player_names(),
+            }),
+            ServerListPing::Ignore => ServerQuery::Ignore,
+        }
+    }
+
     /// This function is called every 1.5 seconds to broadcast a packet over the
     /// local network in order to advertise the server to the multiplayer
     /// screen with a configurable MOTD.
@@ -416,6 +928,97 @@ pub trait NetworkCallbacks: Send + Sync + 'static {
         BroadcastToLan::Disabled
     }
 
+    /// Called for each client logging in to determine how packets sent over
+    /// the connection are compressed. This can be used to disable compression
+    /// for connections from a proxy on the same machine, for instance.
+    ///
+    /// Packets shared between clients are compressed with the settings of the
+    /// [`Server`] ahead of time. They are compressed again for connections
+    /// with different settings, which costs some CPU time.
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// The settings of the [`Server`] are returned.
+    async fn compression(
+        &self,
+        shared: &SharedNetworkState,
+        remote_addr: SocketAddr,
+    ) -> CompressionSettings {
+        #![allow(unused_variables)]
+
+        shared.compression()
+    }
+
+    /// Called when a client starts logging in to determine if its protocol
+    /// version, [`HandshakeData::protocol_version`], is supported.
+    ///
+    /// If `Err(reason)` is returned, the client is disconnected with `reason`
+    /// as the displayed message. Clients with a supported version still get
+    /// the packets of [`PROTOCOL_VERSION`], so accepting other versions is only
+    /// useful if something in front of the server translates the packets.
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// Only [`PROTOCOL_VERSION`] is accepted. Other clients are disconnected
+    /// with vanilla's message asking them to use [`MINECRAFT_VERSION`].
+    async fn version_check(
+        &self,
+        shared: &SharedNetworkState,
+        remote_addr: SocketAddr,
+        handshake_data: &HandshakeData,
+    ) -> Result<(), Text> {
+        #![allow(unused_variables)]
+
+        if handshake_data.protocol_version == PROTOCOL_VERSION {
+            return Ok(());
+        }
+
+        let key = if handshake_data.protocol_version < PROTOCOL_VERSION {
+            translation_key::MULTIPLAYER_DISCONNECT_OUTDATED_CLIENT
+        } else {
+            translation_key::MULTIPLAYER_DISCONNECT_INCOMPATIBLE
+        };
+
+        Err(Text::translate(key, [MINECRAFT_VERSION.into()]))
+    }
+
+    /// Called for each client right after it has authenticated (or right after
+    /// it has sent its username if online mode is disabled) to determine if it
+    /// is admitted to the server, such as by checking a whitelist or a list of
+    /// bans. Nothing else is done for the client before this, so rejecting
+    /// clients here is cheap.
+    ///
+    /// `remote_addr` is the address of the client. Its IP is the one forwarded
+    /// by the proxy if the server is behind one.
+    ///
+    /// If `Err(reason)` is returned, the client is disconnected with `reason`
+    /// as the displayed message.
+    ///
+    /// This method is called from within a tokio runtime, and is the
+    /// appropriate place to perform asynchronous operations such as
+    /// database queries which may take some time to complete.
+    ///
+    /// # Default Implementation
+    ///
+    /// Every client is admitted.
+    ///
+    /// [`Whitelist`] can be used to only admit the players in a whitelist
+    /// file.
+    async fn should_admit(
+        &self,
+        shared: &SharedNetworkState,
+        profile: &GameProfile,
+        remote_addr: SocketAddr,
+    ) -> Result<(), Text> {
+        #![allow(unused_variables)]
+
+        Ok(())
+    }
+
     /// Called for each client (after successful authentication if online mode
     /// is enabled) to determine if they can join the server.
     /// - If `Err(reason)` is returned, then the client is immediately
@@ -471,39 +1074,30 @@ pub trait NetworkCallbacks: Send + Sync + 'static {
         }
     }
 
-    /// Called upon every client login to obtain the full URL to use for session
-    /// server requests. This is done to authenticate player accounts. This
-    /// method is not called unless [online mode] is enabled.
+    /// Called for each client after [`Self::login`] succeeds and before the
+    /// client enters the play state and is spawned. Use `conn` to exchange
+    /// login plugin messages with the client, such as the handshake of a
+    /// modded client.
     ///
-    /// It is assumed that upon successful request, a structure matching the
-    /// description in the [wiki](https://wiki.vg/Protocol_Encryption#Server) was obtained.
-    /// Providing a URL that does not return such a structure will result in a
-    /// disconnect for every client that connects.
+    /// If `Err(reason)` is returned, the client is disconnected with `reason`
+    /// as the displayed message. Clients are also disconnected if they take
+    /// longer than [`ConnectionTimeouts::configuration`] to respond to the
+    /// messages.
     ///
-    /// The arguments are described in the linked wiki article.
+    /// This method is called from within a tokio runtime.
     ///
     /// # Default Implementation
     ///
-    /// Uses the official Minecraft session server. This is formatted as
-    /// `https://sessionserver.mojang.com/session/minecraft/hasJoined?username=<username>&serverId=<auth-digest>&ip=<player-ip>`.
-    ///
-    /// [online mode]: ConnectionMode::Online
-    async fn session_server(
+    /// Nothing is sent and `Ok(())` is returned.
+    async fn configure(
         &self,
         shared: &SharedNetworkState,
-        username: &str,
-        auth_digest: &str,
-        player_ip: &IpAddr,
-    ) -> String {
-        if shared.connection_mode()
-            == (&ConnectionMode::Online {
-                prevent_proxy_connections: true,
-            })
-        {
-            format!("https://sessionserver.mojang.com/session/minecraft/hasJoined?username={username}&serverId={auth_digest}&ip={player_ip}")
-        } else {
-            format!("https://sessionserver.mojang.com/session/minecraft/hasJoined?username={username}&serverId={auth_digest}")
-        }
+        info: &NewClientInfo,
+        conn: &mut LoginPluginConnection<'_>,
+    ) -> Result<(), Text> {
+        #![allow(unused_variables)]
+
+        Ok(())
     }
 }
 
@@ -534,7 +1128,7 @@ pub enum ConnectionMode {
     /// This mode should be used by all publicly exposed servers which are not
     /// behind a proxy.
     ///
-    /// [configured session server]: NetworkCallbacks::session_server
+    /// [configured session server]: NetworkSettings::session_authenticator
     Online {
         /// Determines if client IP validation should take place during
         /// authentication.
@@ -543,9 +1137,8 @@ pub enum ConnectionMode {
         /// log-in if they connected to the Yggdrasil server using a different
         /// IP than the one used to connect to this server.
         ///
-        /// This is used by the default implementation of
-        /// [`NetworkCallbacks::session_server`]. A different implementation may
-        /// choose to ignore this value.
+        /// The client's IP is only passed to
+        /// [`SessionAuthenticator::has_joined`] when this is enabled.
         prevent_proxy_connections: bool,
     },
     /// Disables client authentication with the configured session server.
@@ -563,19 +1156,29 @@ pub enum ConnectionMode {
     ///   forwarding mode.
     ///
     /// All player data (username, UUID, and properties) is fetched from the
-    /// proxy, but no attempt is made to stop connections originating from
-    /// elsewhere. As a result, you must ensure clients connect through the
-    /// proxy and are unable to connect to the server directly. Otherwise,
-    /// clients can use any username or UUID they choose similar to
-    /// [`ConnectionMode::Offline`].
+    /// proxy, but by default no attempt is made to stop connections
+    /// originating from elsewhere. As a result, you must ensure clients connect
+    /// through the proxy and are unable to connect to the server directly.
+    /// Otherwise, clients can use any username or UUID they choose similar
+    /// to [`ConnectionMode::Offline`].
     ///
-    /// To protect against this, a firewall can be used. However,
-    /// [`ConnectionMode::Velocity`] is recommended as a secure alternative.
+    /// To protect against this, a firewall can be used or `allowed_proxies`
+    /// can be set. However, [`ConnectionMode::Velocity`] is recommended as a
+    /// secure alternative.
+    ///
+    /// Clients that connect without forwarded data or with malformed
+    /// forwarded data are disconnected.
     ///
     /// [BungeeCord]: https://www.spigotmc.org/wiki/bungeecord/
     /// [Waterfall]: https://github.com/PaperMC/Waterfall
     /// [Velocity]: https://velocitypowered.com/
-    BungeeCord,
+    BungeeCord {
+        /// The IP addresses of the proxies that are allowed to connect to the
+        /// server. Connections from any other address are disconnected before
+        /// the forwarded data is used. If `None`, connections are accepted
+        /// from any address.
+        allowed_proxies: Option<Arc<[IpAddr]>>,
+    },
     /// This mode is used when the server is behind a [Velocity] proxy
     /// configured with the forwarding mode `modern`.
     ///
@@ -604,8 +1207,11 @@ pub enum ServerListPing<'a> {
         /// a time.
         max_players: i32,
         /// The list of players visible by hovering over the player count.
+        /// Servers often use this to show announcements instead, see
+        /// [`PlayerSampleEntry::from_lines`].
         ///
-        /// Has no effect if this list is empty.
+        /// Has no effect if this list is empty. Only the first
+        /// [`ServerListPing::MAX_PLAYER_SAMPLE_LEN`] entries are sent.
         player_sample: Vec<PlayerSampleEntry>,
         /// A description of the server.
         description: Text,
@@ -622,12 +1228,24 @@ pub enum ServerListPing<'a> {
         version_name: String,
         /// The protocol version of the server.
         protocol: i32,
+        /// Whether the server requires chat messages to be signed. Clients
+        /// show a warning for servers that don't.
+        enforces_secure_chat: bool,
+        /// Whether the server previews chat messages. Only used by 1.19
+        /// clients.
+        previews_chat: bool,
     },
     /// Ignores the query and disconnects from the client.
     #[default]
     Ignore,
 }
 
+impl ServerListPing<'_> {
+    /// The maximum number of entries sent in the player sample. This is the
+    /// size of the sample on vanilla servers.
+    pub const MAX_PLAYER_SAMPLE_LEN: usize = 12;
+}
+
 /// The result of the Server List Legacy Ping [callback].
 ///
 /// [callback]: NetworkCallbacks::server_list_legacy_ping
@@ -640,6 +1258,57 @@ pub enum ServerListLegacyPing {
     Ignore,
 }
 
+/// The result of the Server Query [callback].
+///
+/// [callback]: NetworkCallbacks::server_query
+#[derive(Clone, Default, Debug)]
+pub enum ServerQuery {
+    /// Responds to the query with the given information.
+    Respond(ServerQueryResponse),
+    /// Ignores the query.
+    #[default]
+    Ignore,
+}
+
+/// Response data of a [query protocol] stat request. Basic stat responses
+/// only contain some of these fields.
+///
+/// [query protocol]: https://wiki.vg/Query
+#[derive(Clone, Default, Debug)]
+pub struct ServerQueryResponse {
+    /// The message of the day.
+    pub motd: String,
+    /// The game type. Vanilla servers always use `SMP`.
+    pub game_type: String,
+    /// The name of the world.
+    pub map: String,
+    /// The number of players on the server.
+    pub online_players: i32,
+    /// The maximum number of players allowed on the server at a time.
+    pub max_players: i32,
+    /// The version name of the server. Only sent in full stat responses.
+    pub version: String,
+    /// The server software and plugins. Bukkit servers use the format
+    /// `<server>: <plugin 1>; <plugin 2>; ...`. Only sent in full stat
+    /// responses.
+    pub plugins: String,
+    /// The names of the players on the server. Only sent in full stat
+    /// responses.
+    pub players: Vec<String>,
+}
+
+/// How packets sent over a connection are compressed. See
+/// [`NetworkCallbacks::compression`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct CompressionSettings {
+    /// Packets with an uncompressed length of at least this many bytes are
+    /// compressed. `None` disables compression.
+    pub threshold: Option<u32>,
+    /// The zlib compression level, from 0 (no compression) to 9 (best
+    /// compression).
+    pub level: u32,
+}
+
 /// The result of the Broadcast To Lan [callback].
 ///
 /// [callback]: NetworkCallbacks::broadcast_to_lan
@@ -653,7 +1322,7 @@ pub enum BroadcastToLan<'a> {
 }
 
 /// Represents an individual entry in the player sample.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
 pub struct PlayerSampleEntry {
     /// The name of the player.
     ///
@@ -664,8 +1333,29 @@ pub struct PlayerSampleEntry {
     pub id: Uuid,
 }
 
+impl PlayerSampleEntry {
+    /// Creates a player sample with an entry for each line of text, which is
+    /// how servers show announcements in the sample. The entries have nil
+    /// UUIDs.
+    pub fn from_lines<I>(lines: I) -> Vec<Self>
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        lines
+            .into_iter()
+            .map(|line| Self {
+                name: line.into(),
+                id: Uuid::nil(),
+            })
+            .collect()
+    }
+}
+
 async fn do_broadcast_to_lan_loop(shared: SharedNetworkState) {
-    let port = shared.0.address.port();
+    let Some(port) = shared.0.addresses.first().map(|addr| addr.port()) else {
+        return;
+    };
 
     let Ok(socket) = UdpSocket::bind("0.0.0.0:0").await else {
         tracing::error!("Failed to bind to UDP socket for broadcast to LAN");
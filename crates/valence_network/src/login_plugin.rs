@@ -0,0 +1,66 @@
+//! Login plugin messages, used to exchange data with clients before they
+//! enter the play state.
+
+use anyhow::ensure;
+use bytes::Bytes;
+use valence_core::ident::Ident;
+use valence_core::protocol::raw::RawBytes;
+use valence_core::protocol::var_int::VarInt;
+
+use crate::packet::{LoginQueryRequestS2c, LoginQueryResponseC2s};
+use crate::packet_io::PacketIo;
+
+/// The connection of a client in the login state, given to
+/// [`NetworkCallbacks::configure`]. Only login plugin messages can be
+/// exchanged at this point.
+///
+/// [`NetworkCallbacks::configure`]: crate::NetworkCallbacks::configure
+pub struct LoginPluginConnection<'a> {
+    io: &'a mut PacketIo,
+    next_message_id: i32,
+}
+
+impl<'a> LoginPluginConnection<'a> {
+    pub(crate) fn new(io: &'a mut PacketIo) -> Self {
+        Self {
+            io,
+            // ID 0 is used for Velocity's player info request.
+            next_message_id: 1,
+        }
+    }
+
+    /// Sends a login plugin request on `channel` and waits for the client's
+    /// response.
+    ///
+    /// Returns `Ok(None)` if the client did not understand the request, which
+    /// is always the case for vanilla clients. Returns an error if the client
+    /// disconnects or takes too long to respond.
+    pub async fn query(
+        &mut self,
+        channel: Ident<&str>,
+        data: &[u8],
+    ) -> anyhow::Result<Option<Bytes>> {
+        let message_id = self.next_message_id;
+        self.next_message_id = self.next_message_id.wrapping_add(1);
+
+        self.io
+            .send_packet(&LoginQueryRequestS2c {
+                message_id: VarInt(message_id),
+                channel: channel.into(),
+                data: RawBytes(data),
+            })
+            .await?;
+
+        let response: LoginQueryResponseC2s = self.io.recv_packet().await?;
+
+        ensure!(
+            response.message_id.0 == message_id,
+            "mismatched plugin response ID (got {}, expected {message_id})",
+            response.message_id.0,
+        );
+
+        Ok(response
+            .data
+            .map(|RawBytes(data)| Bytes::copy_from_slice(data)))
+    }
+}
@@ -1,40 +1,60 @@
 use std::io::ErrorKind;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use std::{io, mem};
 
-use anyhow::bail;
-use bytes::BytesMut;
+use anyhow::{bail, Context};
+use bytes::{Bytes, BytesMut};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
-use tokio::sync::Semaphore;
+use tokio::sync::{oneshot, Semaphore};
 use tokio::task::JoinHandle;
-use tokio::time::timeout;
 use tracing::{debug, warn};
+use uuid::Uuid;
+use valence_client::stats::TrafficStats;
 use valence_client::{ClientBundleArgs, ClientConnection, ReceivedPacket};
 use valence_core::protocol::decode::{PacketDecoder, PacketFrame};
 use valence_core::protocol::encode::PacketEncoder;
-use valence_core::protocol::{Decode, Encode, Packet};
+use valence_core::protocol::var_int::{VarInt, VarIntDecodeError};
+use valence_core::protocol::{Decode, Encode, Packet, PacketSide, PacketState};
+use valence_core::text::Text;
 
 use crate::byte_channel::{byte_channel, ByteSender, TrySendError};
-use crate::{CleanupOnDrop, NewClientInfo};
+use crate::capture::PacketRecorder;
+use crate::connection_limit::IpGuard;
+use crate::packet::LoginDisconnectS2c;
+use crate::rate_limit::{PacketRateLimiter, RateLimitOutcome};
+use crate::stream::Stream;
+use crate::timeout::{ConnectionPhase, PhaseTimer};
+use crate::{
+    CleanupOnDrop, CompressionSettings, NewClientInfo, PacketAction, PacketInterceptor,
+    PacketRateStats, SharedNetworkState,
+};
 
 pub(crate) struct PacketIo {
-    stream: TcpStream,
+    stream: Stream,
     enc: PacketEncoder,
     dec: PacketDecoder,
     frame: PacketFrame,
-    timeout: Duration,
+    timer: PhaseTimer,
+    /// Records the packets of this connection, if capturing is enabled.
+    recorder: Option<Arc<PacketRecorder>>,
+    /// The state of the connection, for the recorder.
+    state: PacketState,
 }
 
 const READ_BUF_SIZE: usize = 4096;
 
+/// How long the packets left to send to a disconnected client are given to
+/// be sent.
+const DISCONNECT_LINGER: Duration = Duration::from_secs(5);
+
 impl PacketIo {
     pub(crate) fn new(
-        stream: TcpStream,
+        stream: Stream,
         enc: PacketEncoder,
         dec: PacketDecoder,
-        timeout: Duration,
+        timer: PhaseTimer,
     ) -> Self {
         Self {
             stream,
@@ -44,17 +64,60 @@ impl PacketIo {
                 id: -1,
                 body: BytesMut::new(),
             },
-            timeout,
+            timer,
+            recorder: None,
+            state: PacketState::Handshaking,
         }
     }
 
+    pub(crate) fn set_recorder(&mut self, recorder: PacketRecorder) {
+        self.recorder = Some(Arc::new(recorder));
+    }
+
+    /// Starts timing a new phase of the connection. Sending and receiving
+    /// packets fails once the client has taken longer than `timeout` in the
+    /// phase.
+    pub(crate) fn start_phase(&mut self, phase: ConnectionPhase, timeout: Duration) {
+        self.timer = PhaseTimer::new(phase, timeout);
+        self.state = match phase {
+            ConnectionPhase::Handshake => PacketState::Handshaking,
+            ConnectionPhase::Status => PacketState::Status,
+            ConnectionPhase::Login | ConnectionPhase::Configuration => PacketState::Login,
+        };
+    }
+
     pub(crate) async fn send_packet<P>(&mut self, pkt: &P) -> anyhow::Result<()>
     where
         P: Packet + Encode,
     {
         self.enc.append_packet(pkt)?;
+
+        if let Some(recorder) = &self.recorder {
+            let mut body = vec![];
+            pkt.encode(&mut body)?;
+            recorder.record(PacketSide::Clientbound, self.state, P::ID, &body);
+        }
+
         let bytes = self.enc.take();
-        timeout(self.timeout, self.stream.write_all(&bytes)).await??;
+        self.timer.run(self.stream.write_all(&bytes)).await??;
+        Ok(())
+    }
+
+    /// Sends the disconnect packet of the current state with `reason` as the
+    /// displayed message, then closes the stream for writing so the client
+    /// receives everything before the connection is dropped.
+    pub(crate) async fn disconnect(&mut self, reason: impl Into<Text>) -> anyhow::Result<()> {
+        match self.state {
+            PacketState::Login => {
+                self.send_packet(&LoginDisconnectS2c {
+                    reason: reason.into().into(),
+                })
+                .await?
+            }
+            state => bail!("clients cannot be disconnected with a reason in the {state:?} state"),
+        }
+
+        self.timer.run(self.stream.shutdown()).await??;
         Ok(())
     }
 
@@ -62,33 +125,49 @@ impl PacketIo {
     where
         P: Packet + Decode<'a>,
     {
-        timeout(self.timeout, async {
-            loop {
-                if let Some(frame) = self.dec.try_next_packet()? {
-                    self.frame = frame;
+        let Self {
+            stream, dec, timer, ..
+        } = self;
+
+        let frame = timer
+            .run(async {
+                loop {
+                    if let Some(frame) = dec.try_next_packet()? {
+                        return anyhow::Ok(frame);
+                    }
 
-                    return self.frame.decode();
-                }
+                    dec.reserve(READ_BUF_SIZE);
+                    let mut buf = dec.take_capacity();
 
-                self.dec.reserve(READ_BUF_SIZE);
-                let mut buf = self.dec.take_capacity();
+                    if stream.read_buf(&mut buf).await? == 0 {
+                        return Err(io::Error::from(ErrorKind::UnexpectedEof).into());
+                    }
 
-                if self.stream.read_buf(&mut buf).await? == 0 {
-                    return Err(io::Error::from(ErrorKind::UnexpectedEof).into());
+                    // This should always be an O(1) unsplit because we reserved space earlier
+                    // and the call to `read_buf` shouldn't have grown the allocation.
+                    dec.queue_bytes(buf);
                 }
+            })
+            .await??;
+
+        self.frame = frame;
+
+        if let Some(recorder) = &self.recorder {
+            recorder.record(
+                PacketSide::Serverbound,
+                self.state,
+                self.frame.id,
+                &self.frame.body,
+            );
+        }
 
-                // This should always be an O(1) unsplit because we reserved space earlier and
-                // the call to `read_buf` shouldn't have grown the allocation.
-                self.dec.queue_bytes(buf);
-            }
-        })
-        .await?
+        self.frame.decode()
     }
 
-    #[allow(dead_code)]
-    pub(crate) fn set_compression(&mut self, threshold: Option<u32>) {
-        self.enc.set_compression(threshold);
-        self.dec.set_compression(threshold);
+    pub(crate) fn set_compression(&mut self, compression: CompressionSettings) {
+        self.enc.set_compression(compression.threshold);
+        self.enc.set_compression_level(compression.level);
+        self.dec.set_compression(compression.threshold);
     }
 
     pub(crate) fn enable_encryption(&mut self, key: &[u8; 16]) {
@@ -99,19 +178,36 @@ impl PacketIo {
     pub(crate) fn into_client_args(
         mut self,
         info: NewClientInfo,
-        incoming_byte_limit: usize,
-        outgoing_byte_limit: usize,
         cleanup: CleanupOnDrop,
-    ) -> ClientBundleArgs {
+        ip_guard: Option<IpGuard>,
+        shared: &SharedNetworkState,
+    ) -> (ClientBundleArgs, Option<PacketRateStats>) {
         let (incoming_sender, incoming_receiver) = flume::unbounded();
 
-        let incoming_byte_limit = incoming_byte_limit.min(Semaphore::MAX_PERMITS);
+        let incoming_byte_limit = shared.0.incoming_byte_limit.min(Semaphore::MAX_PERMITS);
+        let outgoing_byte_limit = shared.0.outgoing_byte_limit;
+        let server_compression = shared.compression();
+        let interceptor = shared.0.packet_interceptor.clone();
+        let rate_limits = shared.0.packet_rate_limits.as_ref();
 
         let recv_sem = Arc::new(Semaphore::new(incoming_byte_limit));
         let recv_sem_clone = recv_sem.clone();
 
         let (mut reader, mut writer) = self.stream.into_split();
 
+        let uuid = info.uuid;
+        let reader_interceptor = interceptor.clone();
+
+        let mut rate_limiter =
+            rate_limits.map(|limits| PacketRateLimiter::new(limits, Instant::now()));
+        let rate_stats = rate_limiter.as_ref().map(|l| l.stats().clone());
+
+        let received = Arc::new(ReceivedCounters::default());
+        let reader_received = received.clone();
+
+        let recorder = self.recorder.take();
+        let reader_recorder = recorder.clone();
+
         let reader_task = tokio::spawn(async move {
             let mut buf = BytesMut::new();
 
@@ -124,7 +220,7 @@ impl PacketIo {
                         buf.reserve(READ_BUF_SIZE);
                         match reader.read_buf(&mut buf).await {
                             Ok(0) => break, // Reader is at EOF.
-                            Ok(_) => {}
+                            Ok(n) => reader_received.add_bytes(n),
                             Err(e) => {
                                 debug!("error reading data from stream: {e}");
                                 break;
@@ -141,10 +237,53 @@ impl PacketIo {
                     }
                 };
 
+                reader_received.add_packet(VarInt(frame.id).written_size() + frame.body.len());
+
+                if let Some(recorder) = &reader_recorder {
+                    recorder.record(
+                        PacketSide::Serverbound,
+                        PacketState::Play,
+                        frame.id,
+                        &frame.body,
+                    );
+                }
+
                 let timestamp = Instant::now();
 
+                if let Some(limiter) = &mut rate_limiter {
+                    match limiter.check(frame.id, timestamp) {
+                        RateLimitOutcome::Accept => {}
+                        RateLimitOutcome::Drop => continue,
+                        RateLimitOutcome::Disconnect => {
+                            warn!(
+                                "disconnecting client {uuid} for exceeding packet rate limits ({} \
+                                 packets dropped)",
+                                limiter.stats().dropped()
+                            );
+                            break;
+                        }
+                    }
+                }
+
+                let (id, body) = match &reader_interceptor {
+                    Some(interceptor) => {
+                        match interceptor.intercept_incoming(uuid, frame.id, &frame.body) {
+                            PacketAction::Forward => (frame.id, frame.body.freeze()),
+                            PacketAction::Drop => continue,
+                            PacketAction::Replace(data) => match split_packet_id(data) {
+                                Ok(packet) => packet,
+                                Err(e) => {
+                                    warn!("invalid replacement for incoming packet: {e:#}");
+                                    continue;
+                                }
+                            },
+                        }
+                    }
+                    None => (frame.id, frame.body.freeze()),
+                };
+
                 // Estimate memory usage of this packet.
-                let cost = mem::size_of::<ReceivedPacket>() + frame.body.len();
+                let cost = mem::size_of::<ReceivedPacket>() + body.len();
 
                 if cost > incoming_byte_limit {
                     debug!(
@@ -168,8 +307,8 @@ impl PacketIo {
 
                 let packet = ReceivedPacket {
                     timestamp,
-                    id: frame.id,
-                    body: frame.body.freeze(),
+                    id,
+                    body,
                 };
 
                 if incoming_sender.try_send(packet).is_err() {
@@ -181,23 +320,110 @@ impl PacketIo {
 
         let (outgoing_sender, mut outgoing_receiver) = byte_channel(outgoing_byte_limit);
 
-        let writer_task = tokio::spawn(async move {
-            loop {
-                let bytes = match outgoing_receiver.recv_async().await {
-                    Ok(bytes) => bytes,
-                    Err(e) => {
-                        debug!("error receiving packet data: {e}");
+        // Packets from the client's encoder and packets shared between clients are
+        // compressed with the server's settings. If the settings of this connection
+        // are different, the packets need to be compressed again.
+        let threshold = self.enc.compression();
+        let recompress = threshold != server_compression.threshold
+            || (threshold.is_some() && self.enc.compression_level() != server_compression.level);
+
+        let (writer_task, enc) = if interceptor.is_some() || recompress || recorder.is_some() {
+            // The packets from the client's encoder need to be intercepted,
+            // recompressed or recorded before they are encrypted, so encryption is
+            // done here instead.
+            let mut client_enc = PacketEncoder::new();
+            client_enc.set_compression(server_compression.threshold);
+            client_enc.set_compression_level(server_compression.level);
+
+            let mut dec = PacketDecoder::new();
+            dec.set_compression(server_compression.threshold);
+
+            let mut enc = self.enc;
+
+            let writer_task = tokio::spawn(async move {
+                let mut pending = BytesMut::new();
+                let mut scratch = vec![];
+
+                loop {
+                    let bytes = match outgoing_receiver.recv_async().await {
+                        Ok(bytes) => bytes,
+                        Err(e) => {
+                            debug!("error receiving packet data: {e}");
+                            break;
+                        }
+                    };
+
+                    pending.unsplit(bytes);
+
+                    if let Err(e) = reframe_outgoing(
+                        interceptor.as_deref(),
+                        recorder.as_deref(),
+                        uuid,
+                        &mut pending,
+                        &mut dec,
+                        &mut enc,
+                        recompress.then_some(&mut scratch),
+                    ) {
+                        warn!("error processing outgoing packets: {e:#}");
                         break;
                     }
-                };
 
-                if let Err(e) = writer.write_all(&bytes).await {
-                    debug!("error writing data to stream: {e}");
+                    let bytes = enc.take();
+
+                    if bytes.is_empty() {
+                        continue;
+                    }
+
+                    // Everything the client has been sent is in the capture by the time
+                    // it arrives.
+                    if let Some(recorder) = &recorder {
+                        recorder.flush();
+                    }
+
+                    if let Err(e) = writer.write_all(&bytes).await {
+                        debug!("error writing data to stream: {e}");
+                    }
                 }
-            }
+
+                _ = writer.shutdown().await;
+            });
+
+            (writer_task, client_enc)
+        } else {
+            let writer_task = tokio::spawn(async move {
+                loop {
+                    let bytes = match outgoing_receiver.recv_async().await {
+                        Ok(bytes) => bytes,
+                        Err(e) => {
+                            debug!("error receiving packet data: {e}");
+                            break;
+                        }
+                    };
+
+                    if let Err(e) = writer.write_all(&bytes).await {
+                        debug!("error writing data to stream: {e}");
+                    }
+                }
+
+                _ = writer.shutdown().await;
+            });
+
+            (writer_task, self.enc)
+        };
+
+        // The writer task sends the packets left in the channel once the connection is
+        // dropped, such as the disconnect packet. It is given some time to do so
+        // before the connection is closed regardless.
+        let (closed_send, closed_recv) = oneshot::channel::<()>();
+        let writer_abort = writer_task.abort_handle();
+
+        tokio::spawn(async move {
+            _ = closed_recv.await;
+            tokio::time::sleep(DISCONNECT_LINGER).await;
+            writer_abort.abort();
         });
 
-        ClientBundleArgs {
+        let args = ClientBundleArgs {
             username: info.username,
             uuid: info.uuid,
             ip: info.ip,
@@ -206,24 +432,124 @@ impl PacketIo {
                 send: outgoing_sender,
                 recv: incoming_receiver,
                 recv_sem: recv_sem_clone,
+                received,
                 reader_task,
-                writer_task,
+                _closed: closed_send,
                 _cleanup: cleanup,
+                _ip_guard: ip_guard,
             }),
-            enc: self.enc,
+            enc,
+        };
+
+        (args, rate_stats)
+    }
+}
+
+/// Passes every complete packet in `pending` to the interceptor, if any, and
+/// appends the packets that should be sent to `enc`. The packets that are sent
+/// are recorded by `recorder`, if any. Incomplete packets are left in
+/// `pending`.
+///
+/// Packets are only compressed again if `recompress_buf` is provided, which is
+/// used as scratch space.
+fn reframe_outgoing(
+    interceptor: Option<&dyn PacketInterceptor>,
+    recorder: Option<&PacketRecorder>,
+    uuid: Uuid,
+    pending: &mut BytesMut,
+    dec: &mut PacketDecoder,
+    enc: &mut PacketEncoder,
+    mut recompress_buf: Option<&mut Vec<u8>>,
+) -> anyhow::Result<()> {
+    loop {
+        let mut r = &pending[..];
+
+        let packet_len = match VarInt::decode_partial(&mut r) {
+            Ok(len) => len as usize,
+            Err(VarIntDecodeError::Incomplete) => return Ok(()),
+            Err(VarIntDecodeError::TooLarge) => bail!("malformed packet length VarInt"),
+        };
+
+        let frame_len = pending.len() - r.len() + packet_len;
+
+        if pending.len() < frame_len {
+            return Ok(());
+        }
+
+        let raw = pending.split_to(frame_len);
+
+        dec.queue_slice(&raw);
+
+        let frame = dec
+            .try_next_packet()?
+            .context("missing outgoing packet frame")?;
+
+        let action = match interceptor {
+            Some(interceptor) => interceptor.intercept_outgoing(uuid, frame.id, &frame.body),
+            None => PacketAction::Forward,
+        };
+
+        if let Some(recorder) = recorder {
+            match &action {
+                PacketAction::Forward => {
+                    recorder.record(
+                        PacketSide::Clientbound,
+                        PacketState::Play,
+                        frame.id,
+                        &frame.body,
+                    );
+                }
+                PacketAction::Drop => {}
+                PacketAction::Replace(data) => {
+                    if let Ok((id, body)) = split_packet_id(data.clone()) {
+                        recorder.record(PacketSide::Clientbound, PacketState::Play, id, &body);
+                    }
+                }
+            }
+        }
+
+        match action {
+            PacketAction::Forward => match recompress_buf.as_deref_mut() {
+                Some(buf) => {
+                    buf.clear();
+                    VarInt(frame.id).encode(&mut *buf)?;
+                    buf.extend_from_slice(&frame.body);
+                    enc.append_packet_bytes(buf)?;
+                }
+                // Forward the packet as it is to avoid compressing it again.
+                None => enc.append_bytes(&raw),
+            },
+            PacketAction::Drop => {}
+            PacketAction::Replace(data) => enc.append_packet_bytes(&data)?,
         }
     }
 }
 
+/// Splits packet data into the packet's VarInt ID and the packet's body.
+fn split_packet_id(mut data: Bytes) -> anyhow::Result<(i32, Bytes)> {
+    let mut r = &data[..];
+    let id = VarInt::decode(&mut r)?.0;
+
+    let body = data.split_off(data.len() - r.len());
+
+    Ok((id, body))
+}
+
 struct RealClientConnection {
     send: ByteSender,
     recv: flume::Receiver<ReceivedPacket>,
     /// Limits the amount of data queued in the `recv` channel. Each permit
     /// represents one byte.
     recv_sem: Arc<Semaphore>,
+    /// Updated by the reader task.
+    received: Arc<ReceivedCounters>,
     _cleanup: CleanupOnDrop,
+    /// Keeps the client counted towards the connection limits.
+    _ip_guard: Option<IpGuard>,
     reader_task: JoinHandle<()>,
-    writer_task: JoinHandle<()>,
+    /// Lets the writer task know the connection was dropped when this is
+    /// dropped.
+    _closed: oneshot::Sender<()>,
 }
 
 impl ClientConnection for RealClientConnection {
@@ -253,14 +579,53 @@ impl ClientConnection for RealClientConnection {
         }
     }
 
+    fn buffered_bytes(&self) -> usize {
+        self.send.len()
+    }
+
     fn len(&self) -> usize {
         self.recv.len()
     }
+
+    fn received_stats(&self) -> TrafficStats {
+        self.received.load()
+    }
+}
+
+/// The [`TrafficStats`] of the packets received from a client.
+#[derive(Default, Debug)]
+struct ReceivedCounters {
+    packets: AtomicU64,
+    bytes: AtomicU64,
+    uncompressed_bytes: AtomicU64,
+    largest_packet: AtomicU64,
+}
+
+impl ReceivedCounters {
+    fn add_bytes(&self, n: usize) {
+        self.bytes.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    fn add_packet(&self, data_len: usize) {
+        self.packets.fetch_add(1, Ordering::Relaxed);
+        self.uncompressed_bytes
+            .fetch_add(data_len as u64, Ordering::Relaxed);
+        self.largest_packet
+            .fetch_max(data_len as u64, Ordering::Relaxed);
+    }
+
+    fn load(&self) -> TrafficStats {
+        TrafficStats {
+            packets: self.packets.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            uncompressed_bytes: self.uncompressed_bytes.load(Ordering::Relaxed),
+            largest_packet: self.largest_packet.load(Ordering::Relaxed),
+        }
+    }
 }
 
 impl Drop for RealClientConnection {
     fn drop(&mut self) {
-        self.writer_task.abort();
         self.reader_task.abort();
     }
 }
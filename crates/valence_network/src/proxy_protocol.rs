@@ -0,0 +1,333 @@
+//! Decoding of the [PROXY protocol] header sent by load balancers such as
+//! HAProxy at the start of a connection.
+//!
+//! [PROXY protocol]: https://www.haproxy.org/download/2.8/doc/proxy-protocol.txt
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use anyhow::{bail, ensure, Context};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+const V1_PREFIX: &[u8] = b"PROXY ";
+/// The longest possible v1 header, including the trailing CRLF.
+const V1_MAX_LEN: usize = 107;
+/// The shortest possible v1 header, `PROXY UNKNOWN\r\n`.
+const V1_MIN_LEN: usize = 15;
+
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+/// The length of the fixed part of a v2 header.
+const V2_HEADER_LEN: usize = 16;
+
+/// The result of [`decode_proxy_header`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum ProxyHeader {
+    /// More bytes are needed to decode the header. Reading exactly this many
+    /// more bytes will never read past the end of the header.
+    Incomplete(usize),
+    Complete {
+        /// The address of the client that connected to the proxy. This is
+        /// `None` if the proxy did not forward an address, such as for
+        /// health checks made by the proxy itself.
+        source: Option<SocketAddr>,
+        /// The length of the header in bytes.
+        len: usize,
+    },
+}
+
+/// Reads a v1 or v2 PROXY protocol header from `r` and returns the source
+/// address in it. No bytes past the end of the header are read.
+pub(crate) async fn read_proxy_header<R>(r: &mut R) -> anyhow::Result<Option<SocketAddr>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut buf = vec![];
+
+    loop {
+        match decode_proxy_header(&buf)? {
+            ProxyHeader::Incomplete(needed) => {
+                let start = buf.len();
+                buf.resize(start + needed, 0);
+                r.read_exact(&mut buf[start..]).await?;
+            }
+            ProxyHeader::Complete { source, len } => {
+                debug_assert_eq!(len, buf.len());
+                return Ok(source);
+            }
+        }
+    }
+}
+
+/// Decodes a v1 or v2 PROXY protocol header from the start of `buf`.
+pub(crate) fn decode_proxy_header(buf: &[u8]) -> anyhow::Result<ProxyHeader> {
+    if buf.starts_with(V2_SIGNATURE) {
+        return decode_v2(buf);
+    }
+
+    if buf.starts_with(V1_PREFIX) {
+        return decode_v1(buf);
+    }
+
+    ensure!(
+        V1_PREFIX.starts_with(buf) || V2_SIGNATURE.starts_with(buf),
+        "missing PROXY protocol header"
+    );
+
+    // Both headers are at least this long, so this can't read too much.
+    Ok(ProxyHeader::Incomplete(V1_MIN_LEN - buf.len()))
+}
+
+fn decode_v1(buf: &[u8]) -> anyhow::Result<ProxyHeader> {
+    let Some(end) = buf.windows(2).position(|w| w == b"\r\n") else {
+        ensure!(
+            buf.len() < V1_MAX_LEN,
+            "PROXY protocol v1 header is too long"
+        );
+
+        return Ok(ProxyHeader::Incomplete(
+            V1_MIN_LEN.saturating_sub(buf.len()).max(1),
+        ));
+    };
+
+    ensure!(
+        end + 2 <= V1_MAX_LEN,
+        "PROXY protocol v1 header is too long"
+    );
+
+    let line = std::str::from_utf8(&buf[V1_PREFIX.len()..end])
+        .context("PROXY protocol v1 header is not valid UTF-8")?;
+
+    let mut fields = line.split(' ');
+
+    let source = match fields.next() {
+        Some("TCP4" | "TCP6") => {
+            let (Some(src_ip), Some(_dst_ip), Some(src_port), Some(_dst_port), None) = (
+                fields.next(),
+                fields.next(),
+                fields.next(),
+                fields.next(),
+                fields.next(),
+            ) else {
+                bail!("wrong number of fields in PROXY protocol v1 header");
+            };
+
+            let ip: IpAddr = src_ip.parse().context("invalid source address")?;
+            let port = src_port.parse().context("invalid source port")?;
+
+            Some(SocketAddr::new(ip, port))
+        }
+        // The rest of the line is ignored for unknown protocols.
+        Some("UNKNOWN") => None,
+        _ => bail!("unknown protocol in PROXY protocol v1 header"),
+    };
+
+    Ok(ProxyHeader::Complete {
+        source,
+        len: end + 2,
+    })
+}
+
+fn decode_v2(buf: &[u8]) -> anyhow::Result<ProxyHeader> {
+    if buf.len() < V2_HEADER_LEN {
+        return Ok(ProxyHeader::Incomplete(V2_HEADER_LEN - buf.len()));
+    }
+
+    let version_command = buf[12];
+    let family = buf[13];
+    let addr_len = u16::from_be_bytes([buf[14], buf[15]]) as usize;
+
+    ensure!(
+        version_command >> 4 == 2,
+        "unsupported PROXY protocol version {}",
+        version_command >> 4
+    );
+
+    let len = V2_HEADER_LEN + addr_len;
+
+    if buf.len() < len {
+        return Ok(ProxyHeader::Incomplete(len - buf.len()));
+    }
+
+    let addrs = &buf[V2_HEADER_LEN..len];
+
+    let source = match version_command & 0xf {
+        // LOCAL: the connection was made by the proxy itself.
+        0x0 => None,
+        // PROXY
+        0x1 => match family >> 4 {
+            // AF_INET
+            0x1 => {
+                ensure!(addrs.len() >= 12, "PROXY protocol v2 address is too short");
+
+                let ip = Ipv4Addr::from(<[u8; 4]>::try_from(&addrs[..4]).unwrap());
+                let port = u16::from_be_bytes([addrs[8], addrs[9]]);
+
+                Some(SocketAddr::new(ip.into(), port))
+            }
+            // AF_INET6
+            0x2 => {
+                ensure!(addrs.len() >= 36, "PROXY protocol v2 address is too short");
+
+                let ip = Ipv6Addr::from(<[u8; 16]>::try_from(&addrs[..16]).unwrap());
+                let port = u16::from_be_bytes([addrs[32], addrs[33]]);
+
+                Some(SocketAddr::new(ip.into(), port))
+            }
+            // AF_UNSPEC and AF_UNIX have no usable address.
+            _ => None,
+        },
+        command => bail!("unknown PROXY protocol v2 command {command:#x}"),
+    };
+
+    Ok(ProxyHeader::Complete { source, len })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v2_header(command: u8, family: u8, addrs: &[u8]) -> Vec<u8> {
+        let mut buf = V2_SIGNATURE.to_vec();
+        buf.push(0x20 | command);
+        buf.push(family);
+        buf.extend_from_slice(&(addrs.len() as u16).to_be_bytes());
+        buf.extend_from_slice(addrs);
+        buf
+    }
+
+    #[test]
+    fn decode_v1_headers() {
+        let header = b"PROXY TCP4 192.168.0.42 10.0.0.1 56324 25565\r\n";
+        assert_eq!(
+            decode_proxy_header(header).unwrap(),
+            ProxyHeader::Complete {
+                source: Some("192.168.0.42:56324".parse().unwrap()),
+                len: header.len(),
+            }
+        );
+
+        let header = b"PROXY TCP6 2001:db8::1 ::1 56324 25565\r\n";
+        assert_eq!(
+            decode_proxy_header(header).unwrap(),
+            ProxyHeader::Complete {
+                source: Some("[2001:db8::1]:56324".parse().unwrap()),
+                len: header.len(),
+            }
+        );
+
+        assert_eq!(
+            decode_proxy_header(b"PROXY UNKNOWN\r\n").unwrap(),
+            ProxyHeader::Complete {
+                source: None,
+                len: 15,
+            }
+        );
+
+        // Incomplete headers.
+        assert_eq!(
+            decode_proxy_header(b"").unwrap(),
+            ProxyHeader::Incomplete(15)
+        );
+        assert_eq!(
+            decode_proxy_header(b"PROX").unwrap(),
+            ProxyHeader::Incomplete(11)
+        );
+        assert_eq!(
+            decode_proxy_header(b"PROXY TCP4 192.168.0.42").unwrap(),
+            ProxyHeader::Incomplete(1)
+        );
+
+        // Invalid headers.
+        assert!(decode_proxy_header(b"PROXY TCP4 192.168.0.42 10.0.0.1 56324\r\n").is_err());
+        assert!(decode_proxy_header(b"PROXY TCP4 nope 10.0.0.1 56324 25565\r\n").is_err());
+        assert!(decode_proxy_header(b"PROXY UDP4 192.168.0.42 10.0.0.1 1 2\r\n").is_err());
+        assert!(decode_proxy_header(&[V1_PREFIX, &[b'0'; 120]].concat()).is_err());
+    }
+
+    #[test]
+    fn decode_v2_headers() {
+        let mut addrs = vec![192, 168, 0, 42, 10, 0, 0, 1];
+        addrs.extend_from_slice(&56324_u16.to_be_bytes());
+        addrs.extend_from_slice(&25565_u16.to_be_bytes());
+
+        let header = v2_header(0x1, 0x11, &addrs);
+        assert_eq!(
+            decode_proxy_header(&header).unwrap(),
+            ProxyHeader::Complete {
+                source: Some("192.168.0.42:56324".parse().unwrap()),
+                len: 28,
+            }
+        );
+
+        let mut addrs = "2001:db8::1".parse::<Ipv6Addr>().unwrap().octets().to_vec();
+        addrs.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        addrs.extend_from_slice(&56324_u16.to_be_bytes());
+        addrs.extend_from_slice(&25565_u16.to_be_bytes());
+        // TLVs after the addresses are skipped.
+        addrs.extend_from_slice(&[0x04, 0x00, 0x01, 0xff]);
+
+        let header = v2_header(0x1, 0x21, &addrs);
+        assert_eq!(
+            decode_proxy_header(&header).unwrap(),
+            ProxyHeader::Complete {
+                source: Some("[2001:db8::1]:56324".parse().unwrap()),
+                len: 16 + 40,
+            }
+        );
+
+        // Health checks from the proxy itself.
+        assert_eq!(
+            decode_proxy_header(&v2_header(0x0, 0x00, &[])).unwrap(),
+            ProxyHeader::Complete {
+                source: None,
+                len: 16,
+            }
+        );
+
+        // Incomplete headers.
+        assert_eq!(
+            decode_proxy_header(&header[..15]).unwrap(),
+            ProxyHeader::Incomplete(1)
+        );
+        assert_eq!(
+            decode_proxy_header(&header[..20]).unwrap(),
+            ProxyHeader::Incomplete(36)
+        );
+
+        // Invalid headers.
+        assert!(decode_proxy_header(&v2_header(0x1, 0x11, &[1, 2, 3])).is_err());
+        assert!(decode_proxy_header(&v2_header(0x2, 0x11, &[])).is_err());
+
+        let mut header = v2_header(0x1, 0x00, &[]);
+        header[12] = 0x11;
+        assert!(decode_proxy_header(&header).is_err());
+    }
+
+    #[test]
+    fn missing_header() {
+        // The start of a handshake packet.
+        assert!(decode_proxy_header(&[0x10, 0x00, 0xf8, 0x05]).is_err());
+        // A legacy ping.
+        assert!(decode_proxy_header(&[0xfe, 0x01]).is_err());
+    }
+
+    #[tokio::test]
+    async fn read_header_only() {
+        let mut stream: &[u8] = b"PROXY TCP4 192.168.0.42 10.0.0.1 56324 25565\r\n\x10\x00";
+
+        assert_eq!(
+            read_proxy_header(&mut stream).await.unwrap(),
+            Some("192.168.0.42:56324".parse().unwrap())
+        );
+        assert_eq!(stream, b"\x10\x00");
+
+        let mut header = v2_header(0x0, 0x00, &[]);
+        header.extend_from_slice(b"\x10\x00");
+        let mut stream = header.as_slice();
+
+        assert_eq!(read_proxy_header(&mut stream).await.unwrap(), None);
+        assert_eq!(stream, b"\x10\x00");
+
+        let mut stream: &[u8] = b"\x10\x00\xf8\x05\x09localhost";
+        assert!(read_proxy_header(&mut stream).await.is_err());
+    }
+}
@@ -0,0 +1,373 @@
+//! A responder for the UDP [query protocol] (also known as GS4 or UT3 query)
+//! used by server lists and hosting panels to get the server's status and
+//! player list.
+//!
+//! [query protocol]: https://wiki.vg/Query
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use tokio::net::UdpSocket;
+use tracing::{debug, error, warn};
+
+use crate::{ServerQuery, ServerQueryResponse, SharedNetworkState};
+
+const MAGIC: [u8; 2] = [0xfe, 0xfd];
+const TYPE_HANDSHAKE: u8 = 9;
+const TYPE_STAT: u8 = 0;
+
+/// How long a challenge token stays valid after it is issued.
+const CHALLENGE_LIFETIME: Duration = Duration::from_secs(30);
+/// The number of outstanding challenges at which expired challenges are
+/// removed.
+const CHALLENGE_PRUNE_THRESHOLD: usize = 1024;
+
+/// The padding between the session ID and the key/value section of a full stat
+/// response.
+const FULL_STAT_KV_PADDING: &[u8] = b"splitnum\0\x80\0";
+/// The padding between the key/value section and the player list of a full
+/// stat response.
+const FULL_STAT_PLAYERS_PADDING: &[u8] = b"\x01player_\0\0";
+
+/// Answers query requests on a UDP socket bound to `address` until the
+/// server stops.
+pub(crate) async fn do_query_loop(shared: SharedNetworkState, address: SocketAddr) {
+    let socket = match UdpSocket::bind(address).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            error!("failed to bind query socket on {address}: {e}");
+            return;
+        }
+    };
+
+    let mut challenges = Challenges::default();
+    let mut buf = [0; 64];
+
+    loop {
+        let (len, remote_addr) = match socket.recv_from(&mut buf).await {
+            Ok(res) => res,
+            Err(e) => {
+                warn!("failed to receive query packet: {e}");
+                continue;
+            }
+        };
+
+        let Some(request) = decode_request(&buf[..len]) else {
+            debug!("ignoring malformed query packet from {remote_addr}");
+            continue;
+        };
+
+        let response = match request {
+            QueryRequest::Handshake { session_id } => {
+                let token = challenges.issue(remote_addr, session_id, Instant::now());
+                encode_handshake_response(session_id, token)
+            }
+            QueryRequest::Stat {
+                session_id,
+                token,
+                full,
+            } => {
+                if !challenges.verify(remote_addr, session_id, token, Instant::now()) {
+                    debug!("ignoring query with invalid challenge token from {remote_addr}");
+                    continue;
+                }
+
+                let res = match shared
+                    .0
+                    .callbacks
+                    .inner
+                    .server_query(&shared, remote_addr)
+                    .await
+                {
+                    ServerQuery::Respond(res) => res,
+                    ServerQuery::Ignore => continue,
+                };
+
+                // Report the game's address rather than the query socket's.
+                let host = shared.0.addresses.first().copied().unwrap_or(address);
+
+                if full {
+                    encode_full_stat(session_id, &res, host)
+                } else {
+                    encode_basic_stat(session_id, &res, host)
+                }
+            }
+        };
+
+        if let Err(e) = socket.send_to(&response, remote_addr).await {
+            debug!("failed to send query response to {remote_addr}: {e}");
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum QueryRequest {
+    Handshake {
+        session_id: [u8; 4],
+    },
+    Stat {
+        session_id: [u8; 4],
+        token: i32,
+        /// Whether the full stat was requested instead of the basic stat.
+        full: bool,
+    },
+}
+
+fn decode_request(buf: &[u8]) -> Option<QueryRequest> {
+    let rest = buf.strip_prefix(&MAGIC)?;
+    let (&ty, rest) = rest.split_first()?;
+    let session_id = rest.get(..4)?.try_into().unwrap();
+    let rest = &rest[4..];
+
+    match ty {
+        TYPE_HANDSHAKE if rest.is_empty() => Some(QueryRequest::Handshake { session_id }),
+        TYPE_STAT => {
+            let token = i32::from_be_bytes(rest.get(..4)?.try_into().unwrap());
+
+            // Full stat requests are padded with four extra bytes.
+            let full = match rest.len() {
+                4 => false,
+                8 => true,
+                _ => return None,
+            };
+
+            Some(QueryRequest::Stat {
+                session_id,
+                token,
+                full,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// The challenge tokens issued to each remote address.
+#[derive(Default, Debug)]
+struct Challenges {
+    map: HashMap<SocketAddr, Challenge>,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Challenge {
+    session_id: [u8; 4],
+    token: i32,
+    issued: Instant,
+}
+
+impl Challenges {
+    /// Issues a new challenge token to `addr`, replacing any previous token.
+    fn issue(&mut self, addr: SocketAddr, session_id: [u8; 4], now: Instant) -> i32 {
+        let token = rand::thread_rng().gen_range(0..0x1000000);
+        self.insert(addr, session_id, token, now);
+        token
+    }
+
+    fn insert(&mut self, addr: SocketAddr, session_id: [u8; 4], token: i32, now: Instant) {
+        if self.map.len() >= CHALLENGE_PRUNE_THRESHOLD {
+            self.map
+                .retain(|_, c| now.saturating_duration_since(c.issued) < CHALLENGE_LIFETIME);
+        }
+
+        self.map.insert(
+            addr,
+            Challenge {
+                session_id,
+                token,
+                issued: now,
+            },
+        );
+    }
+
+    /// Returns whether `token` is the unexpired token issued to `addr` for
+    /// the session.
+    fn verify(&self, addr: SocketAddr, session_id: [u8; 4], token: i32, now: Instant) -> bool {
+        self.map.get(&addr).is_some_and(|c| {
+            c.session_id == session_id
+                && c.token == token
+                && now.saturating_duration_since(c.issued) < CHALLENGE_LIFETIME
+        })
+    }
+}
+
+fn encode_handshake_response(session_id: [u8; 4], token: i32) -> Vec<u8> {
+    let mut buf = vec![TYPE_HANDSHAKE];
+    buf.extend_from_slice(&session_id);
+    write_string(&mut buf, &token.to_string());
+    buf
+}
+
+fn encode_basic_stat(session_id: [u8; 4], res: &ServerQueryResponse, host: SocketAddr) -> Vec<u8> {
+    let mut buf = vec![TYPE_STAT];
+    buf.extend_from_slice(&session_id);
+
+    write_string(&mut buf, &res.motd);
+    write_string(&mut buf, &res.game_type);
+    write_string(&mut buf, &res.map);
+    write_string(&mut buf, &res.online_players.to_string());
+    write_string(&mut buf, &res.max_players.to_string());
+    // The port is the only little-endian value in the protocol.
+    buf.extend_from_slice(&host.port().to_le_bytes());
+    write_string(&mut buf, &host.ip().to_string());
+
+    buf
+}
+
+fn encode_full_stat(session_id: [u8; 4], res: &ServerQueryResponse, host: SocketAddr) -> Vec<u8> {
+    let mut buf = vec![TYPE_STAT];
+    buf.extend_from_slice(&session_id);
+    buf.extend_from_slice(FULL_STAT_KV_PADDING);
+
+    let kv = [
+        ("hostname", res.motd.as_str()),
+        ("gametype", &res.game_type),
+        ("game_id", "MINECRAFT"),
+        ("version", &res.version),
+        ("plugins", &res.plugins),
+        ("map", &res.map),
+        ("numplayers", &res.online_players.to_string()),
+        ("maxplayers", &res.max_players.to_string()),
+        ("hostport", &host.port().to_string()),
+        ("hostip", &host.ip().to_string()),
+    ];
+
+    for (key, value) in kv {
+        write_string(&mut buf, key);
+        write_string(&mut buf, value);
+    }
+    buf.push(0);
+
+    buf.extend_from_slice(FULL_STAT_PLAYERS_PADDING);
+
+    for player in &res.players {
+        write_string(&mut buf, player);
+    }
+    buf.push(0);
+
+    buf
+}
+
+/// Writes a null-terminated string. Null bytes in the string are removed so
+/// they can't end it early.
+fn write_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend(s.bytes().filter(|&b| b != 0));
+    buf.push(0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The exchanges below are from https://wiki.vg/Query.
+
+    const SESSION_ID: [u8; 4] = [0x00, 0x00, 0x00, 0x01];
+    const TOKEN: i32 = 9513307;
+
+    fn response() -> ServerQueryResponse {
+        ServerQueryResponse {
+            motd: "A Minecraft Server".into(),
+            game_type: "SMP".into(),
+            map: "world".into(),
+            online_players: 2,
+            max_players: 20,
+            version: "1.2.5".into(),
+            plugins: "".into(),
+            players: vec!["barneygale".into(), "Vivalahelvig".into()],
+        }
+    }
+
+    fn host() -> SocketAddr {
+        "127.0.0.1:25565".parse().unwrap()
+    }
+
+    #[test]
+    fn handshake() {
+        assert_eq!(
+            decode_request(b"\xfe\xfd\x09\x00\x00\x00\x01"),
+            Some(QueryRequest::Handshake {
+                session_id: SESSION_ID
+            })
+        );
+
+        assert_eq!(
+            encode_handshake_response(SESSION_ID, TOKEN),
+            b"\x09\x00\x00\x00\x019513307\0"
+        );
+    }
+
+    #[test]
+    fn basic_stat() {
+        assert_eq!(
+            decode_request(b"\xfe\xfd\x00\x00\x00\x00\x01\x00\x91\x29\x5b"),
+            Some(QueryRequest::Stat {
+                session_id: SESSION_ID,
+                token: TOKEN,
+                full: false,
+            })
+        );
+
+        assert_eq!(
+            encode_basic_stat(SESSION_ID, &response(), host()),
+            b"\x00\x00\x00\x00\x01A Minecraft Server\0SMP\0world\x002\x0020\0\xdd\x63127.0.0.1\0"
+        );
+    }
+
+    #[test]
+    fn full_stat() {
+        assert_eq!(
+            decode_request(b"\xfe\xfd\x00\x00\x00\x00\x01\x00\x91\x29\x5b\x00\x00\x00\x00"),
+            Some(QueryRequest::Stat {
+                session_id: SESSION_ID,
+                token: TOKEN,
+                full: true,
+            })
+        );
+
+        let expected: &[u8] = b"\x00\x00\x00\x00\x01splitnum\0\x80\0\
+            hostname\0A Minecraft Server\0gametype\0SMP\0game_id\0MINECRAFT\0\
+            version\x001.2.5\0plugins\0\0map\0world\0numplayers\x002\0maxplayers\x0020\0\
+            hostport\x0025565\0hostip\x00127.0.0.1\0\0\
+            \x01player_\0\0barneygale\0Vivalahelvig\0\0";
+
+        assert_eq!(encode_full_stat(SESSION_ID, &response(), host()), expected);
+    }
+
+    #[test]
+    fn malformed_requests() {
+        // Wrong magic.
+        assert_eq!(decode_request(b"\xfe\xfe\x09\x00\x00\x00\x01"), None);
+        // Truncated session ID.
+        assert_eq!(decode_request(b"\xfe\xfd\x09\x00\x00"), None);
+        // Handshake with trailing data.
+        assert_eq!(decode_request(b"\xfe\xfd\x09\x00\x00\x00\x01\x00"), None);
+        // Stat without a challenge token.
+        assert_eq!(
+            decode_request(b"\xfe\xfd\x00\x00\x00\x00\x01\x00\x91"),
+            None
+        );
+        // Unknown packet type.
+        assert_eq!(decode_request(b"\xfe\xfd\x01\x00\x00\x00\x01"), None);
+    }
+
+    #[test]
+    fn challenge_tokens() {
+        let now = Instant::now();
+        let addr: SocketAddr = "192.168.0.42:56324".parse().unwrap();
+        let other: SocketAddr = "192.168.0.43:56324".parse().unwrap();
+
+        let mut challenges = Challenges::default();
+        let token = challenges.issue(addr, SESSION_ID, now);
+
+        assert!(challenges.verify(addr, SESSION_ID, token, now));
+        assert!(!challenges.verify(addr, SESSION_ID, token.wrapping_add(1), now));
+        assert!(!challenges.verify(addr, [0, 0, 0, 2], token, now));
+        assert!(!challenges.verify(other, SESSION_ID, token, now));
+        assert!(!challenges.verify(addr, SESSION_ID, token, now + CHALLENGE_LIFETIME));
+
+        // A new handshake replaces the old token.
+        challenges.insert(addr, SESSION_ID, TOKEN, now);
+        assert!(challenges.verify(addr, SESSION_ID, TOKEN, now));
+        assert!(TOKEN == token || !challenges.verify(addr, SESSION_ID, token, now));
+    }
+}
@@ -0,0 +1,355 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use bevy_ecs::prelude::*;
+use valence_core::protocol::packet_id;
+
+/// Limits on the rate of packets received from each client in the play state.
+/// See [`NetworkSettings::packet_rate_limits`].
+///
+/// Limits are enforced on the network threads before packets are decoded, so
+/// excess packets cost very little. A packet must be within both the limit of
+/// its [`PacketCategory`] and the global limit to be accepted.
+///
+/// [`NetworkSettings::packet_rate_limits`]: crate::NetworkSettings::packet_rate_limits
+#[derive(Clone, PartialEq, Debug)]
+pub struct PacketRateLimits {
+    /// The limit on all packets.
+    pub global: Option<RateLimit>,
+    /// The limit on [`PacketCategory::Chat`] packets.
+    pub chat: Option<RateLimit>,
+    /// The limit on [`PacketCategory::Interaction`] packets.
+    pub interaction: Option<RateLimit>,
+    /// The limit on [`PacketCategory::Movement`] packets.
+    pub movement: Option<RateLimit>,
+    /// What to do with packets over the limit.
+    pub policy: RateLimitPolicy,
+}
+
+impl Default for PacketRateLimits {
+    /// The default limits are generous enough for vanilla clients. The exact
+    /// values are left unspecified and may change in future versions.
+    fn default() -> Self {
+        Self {
+            global: Some(RateLimit::new(500.0, 1000.0)),
+            chat: Some(RateLimit::new(2.0, 10.0)),
+            interaction: Some(RateLimit::new(40.0, 100.0)),
+            movement: Some(RateLimit::new(60.0, 100.0)),
+            policy: RateLimitPolicy::Disconnect {
+                max_violations: 100,
+            },
+        }
+    }
+}
+
+/// A token bucket rate limit. Short bursts of packets are allowed as long as
+/// the average rate stays below the limit.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct RateLimit {
+    /// The number of packets per second allowed on average.
+    pub per_second: f64,
+    /// The maximum number of packets allowed at once after a period of
+    /// inactivity.
+    pub burst: f64,
+}
+
+impl RateLimit {
+    pub const fn new(per_second: f64, burst: f64) -> Self {
+        Self { per_second, burst }
+    }
+}
+
+/// What to do with packets over a [`RateLimit`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RateLimitPolicy {
+    /// Excess packets are dropped.
+    Drop,
+    /// Excess packets are dropped, and the client is disconnected once
+    /// `max_violations` packets have been dropped.
+    Disconnect { max_violations: u64 },
+}
+
+/// The kinds of packets with separate rate limits.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum PacketCategory {
+    /// Chat messages and commands.
+    Chat,
+    /// Using and interacting with blocks, items and entities.
+    Interaction,
+    /// Player and vehicle movement.
+    Movement,
+    /// Every other packet. These are only subject to the global limit.
+    Other,
+}
+
+impl PacketCategory {
+    /// Returns the category of the serverbound play packet with the given ID.
+    pub fn of(packet_id: i32) -> Self {
+        match packet_id {
+            packet_id::CHAT_MESSAGE_C2S | packet_id::COMMAND_EXECUTION_C2S => Self::Chat,
+            packet_id::PLAYER_INTERACT_BLOCK_C2S
+            | packet_id::PLAYER_INTERACT_ITEM_C2S
+            | packet_id::PLAYER_INTERACT_ENTITY_C2S
+            | packet_id::PLAYER_ACTION_C2S
+            | packet_id::HAND_SWING_C2S
+            | packet_id::CLICK_SLOT_C2S
+            | packet_id::CREATIVE_INVENTORY_ACTION_C2S => Self::Interaction,
+            packet_id::POSITION_AND_ON_GROUND
+            | packet_id::FULL
+            | packet_id::LOOK_AND_ON_GROUND
+            | packet_id::ON_GROUND_ONLY
+            | packet_id::VEHICLE_MOVE_C2S => Self::Movement,
+            _ => Self::Other,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Counters for the packets received from a client with
+/// [`NetworkSettings::packet_rate_limits`] enabled. This component is added
+/// to clients when they are spawned and is updated from the network threads.
+///
+/// [`NetworkSettings::packet_rate_limits`]: crate::NetworkSettings::packet_rate_limits
+#[derive(Component, Clone, Default, Debug)]
+pub struct PacketRateStats(Arc<PacketRateStatsInner>);
+
+#[derive(Default, Debug)]
+struct PacketRateStatsInner {
+    received: AtomicU64,
+    dropped: [AtomicU64; 4],
+}
+
+impl PacketRateStats {
+    /// The total number of packets received from the client, including
+    /// dropped packets.
+    pub fn received(&self) -> u64 {
+        self.0.received.load(Ordering::Relaxed)
+    }
+
+    /// The total number of packets dropped for being over a rate limit.
+    pub fn dropped(&self) -> u64 {
+        self.0
+            .dropped
+            .iter()
+            .map(|n| n.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// The number of packets in the given category dropped for being over a
+    /// rate limit.
+    pub fn dropped_in(&self, category: PacketCategory) -> u64 {
+        self.0.dropped[category.index()].load(Ordering::Relaxed)
+    }
+}
+
+/// The outcome of [`PacketRateLimiter::check`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum RateLimitOutcome {
+    Accept,
+    Drop,
+    Disconnect,
+}
+
+/// The per-connection state of the rate limits.
+#[derive(Debug)]
+pub(crate) struct PacketRateLimiter {
+    global: Option<TokenBucket>,
+    /// Indexed by [`PacketCategory::index`].
+    categories: [Option<TokenBucket>; 3],
+    policy: RateLimitPolicy,
+    stats: PacketRateStats,
+}
+
+impl PacketRateLimiter {
+    pub(crate) fn new(limits: &PacketRateLimits, now: Instant) -> Self {
+        let bucket = |limit: Option<RateLimit>| limit.map(|limit| TokenBucket::new(limit, now));
+
+        Self {
+            global: bucket(limits.global),
+            categories: [
+                bucket(limits.chat),
+                bucket(limits.interaction),
+                bucket(limits.movement),
+            ],
+            policy: limits.policy,
+            stats: PacketRateStats::default(),
+        }
+    }
+
+    pub(crate) fn stats(&self) -> &PacketRateStats {
+        &self.stats
+    }
+
+    /// Determines what to do with a packet with the given ID received at
+    /// `now`.
+    pub(crate) fn check(&mut self, packet_id: i32, now: Instant) -> RateLimitOutcome {
+        let stats = &self.stats.0;
+
+        stats.received.fetch_add(1, Ordering::Relaxed);
+
+        let category = PacketCategory::of(packet_id);
+
+        let within_category = match self.categories.get_mut(category.index()) {
+            Some(Some(bucket)) => bucket.try_take(now),
+            _ => true,
+        };
+
+        let within_global = within_category
+            && match &mut self.global {
+                Some(bucket) => bucket.try_take(now),
+                None => true,
+            };
+
+        if within_global {
+            return RateLimitOutcome::Accept;
+        }
+
+        stats.dropped[category.index()].fetch_add(1, Ordering::Relaxed);
+
+        match self.policy {
+            RateLimitPolicy::Disconnect { max_violations }
+                if self.stats.dropped() >= max_violations =>
+            {
+                RateLimitOutcome::Disconnect
+            }
+            _ => RateLimitOutcome::Drop,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst,
+            last_refill: now,
+        }
+    }
+
+    pub(crate) fn try_take(&mut self, now: Instant) -> bool {
+        self.tokens = self.tokens_at(now);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Whether the bucket has refilled completely by `now`.
+    pub(crate) fn is_full(&self, now: Instant) -> bool {
+        self.tokens_at(now) >= self.limit.burst
+    }
+
+    fn tokens_at(&self, now: Instant) -> f64 {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+
+        (self.tokens + elapsed * self.limit.per_second).min(self.limit.burst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn token_bucket_allows_bursts() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(RateLimit::new(10.0, 5.0), start);
+
+        for _ in 0..5 {
+            assert!(bucket.try_take(start));
+        }
+        assert!(!bucket.try_take(start));
+
+        // One token is added every 100ms.
+        let later = start + Duration::from_millis(250);
+        assert!(bucket.try_take(later));
+        assert!(bucket.try_take(later));
+        assert!(!bucket.try_take(later));
+
+        // Tokens don't accumulate past the burst size.
+        let much_later = later + Duration::from_secs(60);
+        for _ in 0..5 {
+            assert!(bucket.try_take(much_later));
+        }
+        assert!(!bucket.try_take(much_later));
+    }
+
+    #[test]
+    fn rate_limiter_categories() {
+        let now = Instant::now();
+
+        let mut limiter = PacketRateLimiter::new(
+            &PacketRateLimits {
+                global: Some(RateLimit::new(1.0, 10.0)),
+                chat: Some(RateLimit::new(1.0, 2.0)),
+                interaction: None,
+                movement: None,
+                policy: RateLimitPolicy::Drop,
+            },
+            now,
+        );
+
+        let chat = packet_id::CHAT_MESSAGE_C2S;
+        let movement = packet_id::FULL;
+
+        assert_eq!(limiter.check(chat, now), RateLimitOutcome::Accept);
+        assert_eq!(limiter.check(chat, now), RateLimitOutcome::Accept);
+        assert_eq!(limiter.check(chat, now), RateLimitOutcome::Drop);
+
+        // Other categories are unaffected by the chat limit, but are still subject
+        // to the global limit.
+        for _ in 0..8 {
+            assert_eq!(limiter.check(movement, now), RateLimitOutcome::Accept);
+        }
+        assert_eq!(limiter.check(movement, now), RateLimitOutcome::Drop);
+
+        let stats = limiter.stats();
+        assert_eq!(stats.received(), 12);
+        assert_eq!(stats.dropped(), 2);
+        assert_eq!(stats.dropped_in(PacketCategory::Chat), 1);
+        assert_eq!(stats.dropped_in(PacketCategory::Movement), 1);
+        assert_eq!(stats.dropped_in(PacketCategory::Interaction), 0);
+    }
+
+    #[test]
+    fn rate_limiter_disconnects() {
+        let now = Instant::now();
+
+        let mut limiter = PacketRateLimiter::new(
+            &PacketRateLimits {
+                global: Some(RateLimit::new(1.0, 1.0)),
+                chat: None,
+                interaction: None,
+                movement: None,
+                policy: RateLimitPolicy::Disconnect { max_violations: 3 },
+            },
+            now,
+        );
+
+        let id = packet_id::KEEP_ALIVE_C2S;
+
+        assert_eq!(limiter.check(id, now), RateLimitOutcome::Accept);
+        assert_eq!(limiter.check(id, now), RateLimitOutcome::Drop);
+        assert_eq!(limiter.check(id, now), RateLimitOutcome::Drop);
+        assert_eq!(limiter.check(id, now), RateLimitOutcome::Disconnect);
+    }
+}
@@ -0,0 +1,338 @@
+//! A server for the [RCON protocol], which lets administrators run commands
+//! remotely.
+//!
+//! [RCON protocol]: https://wiki.vg/RCON
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::ensure;
+use bevy_ecs::prelude::*;
+use flume::Sender;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, error, warn};
+
+use crate::SharedNetworkState;
+
+const SERVERDATA_AUTH: i32 = 3;
+const SERVERDATA_AUTH_RESPONSE: i32 = 2;
+const SERVERDATA_EXECCOMMAND: i32 = 2;
+const SERVERDATA_RESPONSE_VALUE: i32 = 0;
+
+/// The size of the buffer vanilla reads packets into, including the length
+/// prefix. Larger packets are rejected.
+const MAX_INCOMING_PACKET_LEN: usize = 1460;
+/// The maximum number of body bytes in a single response packet. Longer
+/// responses are split into multiple packets, like in vanilla.
+const MAX_OUTGOING_BODY_LEN: usize = 4096;
+/// The length of a packet with an empty body, excluding the length prefix.
+const MIN_PACKET_LEN: usize = 10;
+
+/// Settings for the RCON server. See [`NetworkSettings::rcon`].
+///
+/// [`NetworkSettings::rcon`]: crate::NetworkSettings::rcon
+#[derive(Clone, Debug)]
+pub struct RconSettings {
+    /// The socket address the RCON server will be bound to.
+    pub address: SocketAddr,
+    /// The password clients must authenticate with before running commands.
+    /// The RCON server is not started if this is empty.
+    pub password: Arc<str>,
+}
+
+/// Sent when an authenticated RCON client runs a command. The command should
+/// be handled and the output sent back with [`respond`](Self::respond).
+///
+/// If the event is dropped without a response, an empty response is sent.
+#[derive(Event, Clone, Debug)]
+pub struct RconCommandEvent {
+    /// The command to run.
+    pub command: String,
+    /// The address of the RCON client.
+    pub remote_addr: SocketAddr,
+    response: Sender<String>,
+}
+
+impl RconCommandEvent {
+    /// Sends the output of the command to the RCON client. Only the first
+    /// response to a command is sent.
+    pub fn respond(&self, output: impl Into<String>) {
+        let _ = self.response.try_send(output.into());
+    }
+}
+
+/// Accepts RCON connections until the server stops.
+pub(crate) async fn do_rcon_accept_loop(shared: SharedNetworkState, settings: RconSettings) {
+    if settings.password.is_empty() {
+        error!("not starting RCON server because the RCON password is empty");
+        return;
+    }
+
+    let listener = match TcpListener::bind(settings.address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("failed to start RCON listener: {e}");
+            return;
+        }
+    };
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, remote_addr)) => {
+                let commands_send = shared.0.rcon_commands_send.clone();
+                let password = settings.password.clone();
+
+                tokio::spawn(async move {
+                    if let Err(e) =
+                        handle_rcon_connection(stream, remote_addr, &password, commands_send).await
+                    {
+                        debug!("RCON connection from {remote_addr} ended with error: {e:#}");
+                    }
+                });
+            }
+            Err(e) => {
+                warn!("failed to accept incoming RCON connection: {e}");
+            }
+        }
+    }
+}
+
+async fn handle_rcon_connection(
+    mut stream: TcpStream,
+    remote_addr: SocketAddr,
+    password: &str,
+    commands_send: Sender<RconCommandEvent>,
+) -> anyhow::Result<()> {
+    let mut authenticated = false;
+
+    loop {
+        let Some(pkt) = read_packet(&mut stream).await? else {
+            return Ok(());
+        };
+
+        match pkt.ty {
+            SERVERDATA_AUTH => {
+                if pkt.body != password {
+                    warn!("RCON client {remote_addr} failed to authenticate");
+                    write_packet(&mut stream, -1, SERVERDATA_AUTH_RESPONSE, "").await?;
+                    return Ok(());
+                }
+
+                authenticated = true;
+                write_packet(&mut stream, pkt.id, SERVERDATA_AUTH_RESPONSE, "").await?;
+            }
+            SERVERDATA_EXECCOMMAND => {
+                if !authenticated {
+                    write_packet(&mut stream, -1, SERVERDATA_AUTH_RESPONSE, "").await?;
+                    return Ok(());
+                }
+
+                let (response_send, response_recv) = flume::bounded(1);
+
+                commands_send
+                    .send_async(RconCommandEvent {
+                        command: pkt.body,
+                        remote_addr,
+                        response: response_send,
+                    })
+                    .await?;
+
+                let output = response_recv.recv_async().await.unwrap_or_default();
+
+                write_response(&mut stream, pkt.id, &output).await?;
+            }
+            ty => {
+                write_response(&mut stream, pkt.id, &format!("Unknown request {ty:x}")).await?;
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct RconPacket {
+    id: i32,
+    ty: i32,
+    body: String,
+}
+
+/// Reads a packet, or returns `None` if the connection was closed before the
+/// start of the packet.
+async fn read_packet<R: AsyncRead + Unpin>(r: &mut R) -> anyhow::Result<Option<RconPacket>> {
+    let mut len_buf = [0; 4];
+
+    match r.read_exact(&mut len_buf).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let len = i32::from_le_bytes(len_buf);
+
+    ensure!(
+        (MIN_PACKET_LEN as i32..=(MAX_INCOMING_PACKET_LEN - 4) as i32).contains(&len),
+        "invalid RCON packet length of {len}"
+    );
+
+    let mut buf = vec![0; len as usize];
+    r.read_exact(&mut buf).await?;
+
+    let id = i32::from_le_bytes(buf[..4].try_into().unwrap());
+    let ty = i32::from_le_bytes(buf[4..8].try_into().unwrap());
+
+    // The body is followed by a null terminator and an empty string.
+    let body = &buf[8..];
+    let body = &body[..body.iter().position(|&b| b == 0).unwrap_or(body.len())];
+
+    Ok(Some(RconPacket {
+        id,
+        ty,
+        body: String::from_utf8_lossy(body).into_owned(),
+    }))
+}
+
+async fn write_packet<W: AsyncWrite + Unpin>(
+    w: &mut W,
+    id: i32,
+    ty: i32,
+    body: &str,
+) -> anyhow::Result<()> {
+    let mut buf = Vec::with_capacity(4 + MIN_PACKET_LEN + body.len());
+
+    buf.extend_from_slice(&((MIN_PACKET_LEN + body.len()) as i32).to_le_bytes());
+    buf.extend_from_slice(&id.to_le_bytes());
+    buf.extend_from_slice(&ty.to_le_bytes());
+    buf.extend_from_slice(body.as_bytes());
+    buf.extend_from_slice(&[0, 0]);
+
+    w.write_all(&buf).await?;
+
+    Ok(())
+}
+
+/// Writes the output of a command, split into as many packets as needed.
+async fn write_response<W: AsyncWrite + Unpin>(
+    w: &mut W,
+    id: i32,
+    output: &str,
+) -> anyhow::Result<()> {
+    let mut rest = output;
+
+    loop {
+        let mut end = rest.len().min(MAX_OUTGOING_BODY_LEN);
+
+        // Don't split a character across packets.
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        let (chunk, remaining) = rest.split_at(end);
+
+        write_packet(w, id, SERVERDATA_RESPONSE_VALUE, chunk).await?;
+
+        if remaining.is_empty() {
+            return Ok(());
+        }
+
+        rest = remaining;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read_all_packets(mut buf: &[u8]) -> Vec<RconPacket> {
+        let mut packets = vec![];
+
+        while let Some(pkt) = read_packet(&mut buf).await.unwrap() {
+            packets.push(pkt);
+        }
+
+        packets
+    }
+
+    #[tokio::test]
+    async fn packet_round_trip() {
+        let mut buf = vec![];
+        write_packet(&mut buf, 42, SERVERDATA_EXECCOMMAND, "say hello")
+            .await
+            .unwrap();
+
+        assert_eq!(buf, b"\x13\0\0\0\x2a\0\0\0\x02\0\0\0say hello\0\0");
+
+        assert_eq!(
+            read_all_packets(&buf).await,
+            [RconPacket {
+                id: 42,
+                ty: SERVERDATA_EXECCOMMAND,
+                body: "say hello".into(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn packet_size_limits() {
+        // Too short to contain the ID, type and terminators.
+        let mut buf: &[u8] = b"\x09\0\0\0\x01\0\0\0\x02\0\0\0\0";
+        assert!(read_packet(&mut buf).await.is_err());
+
+        // Larger than vanilla accepts.
+        let mut buf = vec![];
+        write_packet(&mut buf, 1, SERVERDATA_EXECCOMMAND, &"a".repeat(1447))
+            .await
+            .unwrap();
+        assert!(read_packet(&mut buf.as_slice()).await.is_err());
+
+        // The largest packet vanilla accepts.
+        let mut buf = vec![];
+        write_packet(&mut buf, 1, SERVERDATA_EXECCOMMAND, &"a".repeat(1446))
+            .await
+            .unwrap();
+        assert_eq!(buf.len(), MAX_INCOMING_PACKET_LEN);
+        assert!(read_packet(&mut buf.as_slice()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn long_responses_are_split() {
+        let output = format!("{}é{}", "a".repeat(4095), "b".repeat(5000));
+
+        let mut buf = vec![];
+        write_response(&mut buf, 7, &output).await.unwrap();
+
+        let packets = read_all_packets_unchecked(&buf);
+
+        assert_eq!(packets.len(), 3);
+        assert!(packets.iter().all(|(id, ty, _)| *id == 7 && *ty == 0));
+        // The two-byte character is moved to the second packet.
+        assert_eq!(packets[0].2.len(), 4095);
+        assert_eq!(packets[1].2.len(), 4096);
+        assert_eq!(
+            packets
+                .iter()
+                .map(|(_, _, body)| body.as_str())
+                .collect::<String>(),
+            output
+        );
+    }
+
+    /// Splits packets without the incoming size limit.
+    fn read_all_packets_unchecked(mut buf: &[u8]) -> Vec<(i32, i32, String)> {
+        let mut packets = vec![];
+
+        while !buf.is_empty() {
+            let len = i32::from_le_bytes(buf[..4].try_into().unwrap()) as usize;
+            let pkt = &buf[4..4 + len];
+
+            packets.push((
+                i32::from_le_bytes(pkt[..4].try_into().unwrap()),
+                i32::from_le_bytes(pkt[4..8].try_into().unwrap()),
+                String::from_utf8(pkt[8..len - 2].to_vec()).unwrap(),
+            ));
+
+            buf = &buf[4 + len..];
+        }
+
+        packets
+    }
+}
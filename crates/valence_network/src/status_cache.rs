@@ -0,0 +1,203 @@
+//! Caching of server list ping responses.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use base64::prelude::*;
+
+/// The number of server list pings answered since the server started. See
+/// [`SharedNetworkState::status_ping_stats`].
+///
+/// [`SharedNetworkState::status_ping_stats`]: crate::SharedNetworkState::status_ping_stats
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct StatusPingStats {
+    /// Pings answered with a cached response.
+    pub from_cache: u64,
+    /// Pings answered by calling [`NetworkCallbacks::server_list_ping`].
+    ///
+    /// [`NetworkCallbacks::server_list_ping`]: crate::NetworkCallbacks::server_list_ping
+    pub from_callback: u64,
+}
+
+/// The maximum number of protocol versions responses are cached for at a
+/// time.
+const MAX_CACHED_VERSIONS: usize = 16;
+
+/// A response and when it expires. A `None` response means the ping was
+/// ignored.
+type CachedResponse = (Option<Arc<str>>, Instant);
+
+/// The encoded status responses shared by all connections.
+#[derive(Debug)]
+pub(crate) struct StatusCache {
+    ttl: Option<Duration>,
+    /// The last response for each protocol version.
+    responses: Mutex<HashMap<i32, CachedResponse>>,
+    /// The last favicon and its data URL, so the same favicon isn't encoded
+    /// again for every ping.
+    favicon: Mutex<Option<(Vec<u8>, Arc<str>)>>,
+    from_cache: AtomicU64,
+    from_callback: AtomicU64,
+}
+
+impl StatusCache {
+    pub(crate) fn new(ttl: Option<Duration>) -> Self {
+        Self {
+            ttl,
+            responses: Mutex::new(HashMap::new()),
+            favicon: Mutex::new(None),
+            from_cache: AtomicU64::new(0),
+            from_callback: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the cached response for clients with the protocol version
+    /// `protocol` if it hasn't expired by `now`. Counts the ping towards the
+    /// stats either way.
+    pub(crate) fn get(&self, protocol: i32, now: Instant) -> Option<Option<Arc<str>>> {
+        let cached = match self.responses.lock().unwrap().get(&protocol) {
+            Some((response, expires_at)) if now < *expires_at => Some(response.clone()),
+            _ => None,
+        };
+
+        let counter = match cached {
+            Some(_) => &self.from_cache,
+            None => &self.from_callback,
+        };
+
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        cached
+    }
+
+    /// Caches a response from the callback for clients with the protocol
+    /// version `protocol` at `now`, if caching is enabled.
+    pub(crate) fn insert(&self, protocol: i32, response: Option<Arc<str>>, now: Instant) {
+        let Some(ttl) = self.ttl else {
+            return;
+        };
+
+        let mut responses = self.responses.lock().unwrap();
+
+        // Clients can claim any protocol version, so the number of cached
+        // responses is bounded.
+        responses.retain(|_, (_, expires_at)| now < *expires_at);
+
+        if responses.len() < MAX_CACHED_VERSIONS || responses.contains_key(&protocol) {
+            responses.insert(protocol, (response, now + ttl));
+        }
+    }
+
+    pub(crate) fn invalidate(&self) {
+        self.responses.lock().unwrap().clear();
+    }
+
+    /// Returns the favicon as a `data:` URL.
+    pub(crate) fn favicon_data_url(&self, favicon_png: &[u8]) -> Arc<str> {
+        let mut favicon = self.favicon.lock().unwrap();
+
+        match &*favicon {
+            Some((png, url)) if png.as_slice() == favicon_png => url.clone(),
+            _ => {
+                let mut buf = "data:image/png;base64,".to_owned();
+                BASE64_STANDARD.encode_string(favicon_png, &mut buf);
+
+                let url: Arc<str> = buf.into();
+                *favicon = Some((favicon_png.to_vec(), url.clone()));
+                url
+            }
+        }
+    }
+
+    pub(crate) fn stats(&self) -> StatusPingStats {
+        StatusPingStats {
+            from_cache: self.from_cache.load(Ordering::Relaxed),
+            from_callback: self.from_callback.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn responses_expire() {
+        let cache = StatusCache::new(Some(Duration::from_secs(2)));
+        let start = Instant::now();
+
+        assert_eq!(cache.get(763, start), None);
+        cache.insert(763, Some("{}".into()), start);
+
+        assert_eq!(
+            cache.get(763, start + Duration::from_secs(1)),
+            Some(Some("{}".into()))
+        );
+        assert_eq!(cache.get(763, start + Duration::from_secs(2)), None);
+
+        // Ignored pings are cached too.
+        cache.insert(763, None, start);
+        assert_eq!(cache.get(763, start), Some(None));
+
+        cache.invalidate();
+        assert_eq!(cache.get(763, start), None);
+
+        assert_eq!(
+            cache.stats(),
+            StatusPingStats {
+                from_cache: 2,
+                from_callback: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn disabled_cache() {
+        let cache = StatusCache::new(None);
+        let now = Instant::now();
+
+        cache.insert(763, Some("{}".into()), now);
+        assert_eq!(cache.get(763, now), None);
+    }
+
+    #[test]
+    fn responses_per_protocol_version() {
+        let cache = StatusCache::new(Some(Duration::from_secs(2)));
+        let start = Instant::now();
+
+        cache.insert(763, Some("new".into()), start);
+        cache.insert(47, Some("old".into()), start);
+
+        assert_eq!(cache.get(763, start), Some(Some("new".into())));
+        assert_eq!(cache.get(47, start), Some(Some("old".into())));
+        assert_eq!(cache.get(340, start), None);
+
+        // The number of cached versions is bounded until responses expire.
+        for protocol in 0..MAX_CACHED_VERSIONS as i32 {
+            cache.insert(1000 + protocol, None, start);
+        }
+
+        assert_eq!(cache.responses.lock().unwrap().len(), MAX_CACHED_VERSIONS);
+        assert_eq!(cache.get(763, start), Some(Some("new".into())));
+
+        cache.insert(340, None, start + Duration::from_secs(2));
+        assert_eq!(cache.get(340, start + Duration::from_secs(2)), Some(None));
+        assert_eq!(cache.responses.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn favicon_is_encoded_once() {
+        let cache = StatusCache::new(None);
+
+        let url = cache.favicon_data_url(b"png");
+        assert_eq!(&*url, "data:image/png;base64,cG5n");
+        assert!(Arc::ptr_eq(&url, &cache.favicon_data_url(b"png")));
+
+        assert_eq!(
+            &*cache.favicon_data_url(b"other"),
+            "data:image/png;base64,b3RoZXI="
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! Timeouts for the phases of a connection before the play state.
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+use std::{fmt, io};
+
+use thiserror::Error;
+
+/// How long clients may take in each phase of the connection before the play
+/// state. See [`NetworkSettings::timeouts`]. Clients over a timeout are
+/// disconnected without a message.
+///
+/// The timeouts only measure the time spent waiting to receive data from or
+/// send data to the client. Time spent in [`NetworkCallbacks`] and waiting for
+/// the session server is not counted, so slow callbacks don't cause clients to
+/// time out.
+///
+/// [`NetworkSettings::timeouts`]: crate::NetworkSettings::timeouts
+/// [`NetworkCallbacks`]: crate::NetworkCallbacks
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ConnectionTimeouts {
+    /// The time from the connection being accepted until the handshake is
+    /// received. This includes the PROXY protocol header and legacy pings.
+    pub handshake: Duration,
+    /// The time from the handshake until the server list ping is complete.
+    pub status: Duration,
+    /// The time from the handshake until the client has logged in, including
+    /// the encryption round trip in online mode.
+    pub login: Duration,
+    /// The time spent exchanging login plugin messages in
+    /// [`NetworkCallbacks::configure`].
+    ///
+    /// [`NetworkCallbacks::configure`]: crate::NetworkCallbacks::configure
+    pub configuration: Duration,
+}
+
+impl Default for ConnectionTimeouts {
+    fn default() -> Self {
+        Self {
+            handshake: Duration::from_secs(10),
+            status: Duration::from_secs(10),
+            login: Duration::from_secs(30),
+            configuration: Duration::from_secs(30),
+        }
+    }
+}
+
+/// The phases of a connection with separate timeouts.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum ConnectionPhase {
+    Handshake,
+    Status,
+    Login,
+    Configuration,
+}
+
+impl fmt::Display for ConnectionPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ConnectionPhase::Handshake => "handshake",
+            ConnectionPhase::Status => "status",
+            ConnectionPhase::Login => "login",
+            ConnectionPhase::Configuration => "configuration",
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, Error)]
+#[error("timed out in the {0} phase")]
+pub(crate) struct PhaseTimeout(pub(crate) ConnectionPhase);
+
+impl From<PhaseTimeout> for io::Error {
+    fn from(e: PhaseTimeout) -> Self {
+        io::Error::new(io::ErrorKind::TimedOut, e)
+    }
+}
+
+/// The time a client has left in the current phase.
+#[derive(Debug)]
+pub(crate) struct PhaseTimer {
+    phase: ConnectionPhase,
+    remaining: Duration,
+}
+
+impl PhaseTimer {
+    pub(crate) fn new(phase: ConnectionPhase, timeout: Duration) -> Self {
+        Self {
+            phase,
+            remaining: timeout,
+        }
+    }
+
+    /// Runs `fut`, which waits on the client, and subtracts the time it takes
+    /// from the time left. Fails once no time is left.
+    pub(crate) async fn run<F: Future>(&mut self, fut: F) -> Result<F::Output, PhaseTimeout> {
+        if self.remaining.is_zero() {
+            return Err(PhaseTimeout(self.phase));
+        }
+
+        let start = Instant::now();
+        let res = tokio::time::timeout(self.remaining, fut).await;
+        self.remaining = self.remaining.saturating_sub(start.elapsed());
+
+        res.map_err(|_| {
+            self.remaining = Duration::ZERO;
+            PhaseTimeout(self.phase)
+        })
+    }
+}
+
+/// Returns the phase if `e` is a [`PhaseTimeout`].
+pub(crate) fn timed_out_phase(e: &anyhow::Error) -> Option<ConnectionPhase> {
+    if let Some(PhaseTimeout(phase)) = e.downcast_ref() {
+        return Some(*phase);
+    }
+
+    e.downcast_ref::<io::Error>()
+        .and_then(|e| e.get_ref())
+        .and_then(|e| e.downcast_ref::<PhaseTimeout>())
+        .map(|e| e.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn time_waiting_is_cumulative() {
+        let mut timer = PhaseTimer::new(ConnectionPhase::Login, Duration::from_millis(150));
+
+        let wait = || tokio::time::sleep(Duration::from_millis(50));
+
+        assert!(timer.run(wait()).await.is_ok());
+        // Time between waits is not counted.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(timer.run(wait()).await.is_ok());
+
+        assert!(matches!(
+            timer
+                .run(tokio::time::sleep(Duration::from_millis(100)))
+                .await,
+            Err(PhaseTimeout(ConnectionPhase::Login))
+        ));
+
+        // Nothing is run once the time is up.
+        assert!(timer.run(async {}).await.is_err());
+    }
+}
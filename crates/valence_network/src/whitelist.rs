@@ -0,0 +1,204 @@
+//! A whitelist of the players allowed to join, backed by a JSON file.
+
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use valence_core::text::Text;
+use valence_core::translation_key;
+
+use crate::{GameProfile, NetworkCallbacks, SharedNetworkState};
+
+/// The players allowed to join the server, loaded from a file in the format of
+/// vanilla's `whitelist.json`:
+///
+/// ```json
+/// [
+///     { "uuid": "069a79f4-44e9-4726-a5be-fca90e38aaf5", "name": "Notch" },
+///     { "name": "jeb_" }
+/// ]
+/// ```
+///
+/// Entries with a UUID only admit the player with that UUID, since usernames
+/// can change owners. Entries without a UUID admit the player with that
+/// username, ignoring case.
+///
+/// `Whitelist` implements [`NetworkCallbacks`] by only admitting the players in
+/// the list, so it can be used as [`NetworkSettings::callbacks`] directly.
+/// Other callbacks can call [`Whitelist::check`] from their
+/// [`NetworkCallbacks::should_admit`]. Clones share the same list, so a clone
+/// can be kept to [reload](Self::reload) the file while the server is running.
+///
+/// [`NetworkSettings::callbacks`]: crate::NetworkSettings::callbacks
+#[derive(Clone, Debug)]
+pub struct Whitelist(Arc<WhitelistInner>);
+
+#[derive(Debug)]
+struct WhitelistInner {
+    path: PathBuf,
+    entries: RwLock<Vec<WhitelistEntry>>,
+}
+
+/// An entry of a [`Whitelist`].
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct WhitelistEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl WhitelistEntry {
+    /// Returns if this entry admits the player with the given profile.
+    pub fn matches(&self, profile: &GameProfile) -> bool {
+        match (&self.uuid, &self.name) {
+            (Some(uuid), _) => *uuid == profile.id,
+            (None, Some(name)) => name.eq_ignore_ascii_case(&profile.name),
+            (None, None) => false,
+        }
+    }
+}
+
+impl Whitelist {
+    /// Loads the whitelist from the file at `path`.
+    pub fn load(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        let entries = read_entries(&path)?;
+
+        Ok(Self(Arc::new(WhitelistInner {
+            path,
+            entries: RwLock::new(entries),
+        })))
+    }
+
+    /// The path of the file the whitelist is loaded from.
+    pub fn path(&self) -> &Path {
+        &self.0.path
+    }
+
+    /// Loads the file again, replacing the entries of this whitelist and its
+    /// clones. The entries are left unchanged if the file can't be read.
+    pub fn reload(&self) -> anyhow::Result<()> {
+        let entries = read_entries(&self.0.path)?;
+        *self.0.entries.write().unwrap() = entries;
+        Ok(())
+    }
+
+    /// Returns a copy of the current entries.
+    pub fn entries(&self) -> Vec<WhitelistEntry> {
+        self.0.entries.read().unwrap().clone()
+    }
+
+    /// Returns if the player with the given profile is in the whitelist.
+    pub fn contains(&self, profile: &GameProfile) -> bool {
+        self.0
+            .entries
+            .read()
+            .unwrap()
+            .iter()
+            .any(|entry| entry.matches(profile))
+    }
+
+    /// Returns `Ok(())` if the player with the given profile is in the
+    /// whitelist, or the vanilla disconnect message otherwise.
+    pub fn check(&self, profile: &GameProfile) -> Result<(), Text> {
+        if self.contains(profile) {
+            Ok(())
+        } else {
+            Err(Text::translate(
+                translation_key::MULTIPLAYER_DISCONNECT_NOT_WHITELISTED,
+                [],
+            ))
+        }
+    }
+}
+
+fn read_entries(path: &Path) -> anyhow::Result<Vec<WhitelistEntry>> {
+    let json = fs::read_to_string(path)
+        .with_context(|| format!("failed to read whitelist at {}", path.display()))?;
+
+    serde_json::from_str(&json)
+        .with_context(|| format!("failed to parse whitelist at {}", path.display()))
+}
+
+#[async_trait]
+impl NetworkCallbacks for Whitelist {
+    async fn should_admit(
+        &self,
+        _shared: &SharedNetworkState,
+        profile: &GameProfile,
+        _remote_addr: SocketAddr,
+    ) -> Result<(), Text> {
+        self.check(profile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(id: u128, name: &str) -> GameProfile {
+        GameProfile {
+            id: Uuid::from_u128(id),
+            name: name.into(),
+            properties: vec![],
+        }
+    }
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "valence-whitelist-{:016x}.json",
+            rand::random::<u64>()
+        ))
+    }
+
+    #[test]
+    fn whitelist_matches_uuids_and_names() {
+        let path = temp_path();
+        fs::write(
+            &path,
+            r#"[
+                { "uuid": "00000000-0000-0000-0000-000000000001", "name": "Notch" },
+                { "name": "jeb_" }
+            ]"#,
+        )
+        .unwrap();
+
+        let whitelist = Whitelist::load(&path).unwrap();
+
+        assert!(whitelist.contains(&profile(1, "Notch")));
+        // Entries with a UUID don't match other players with the same name.
+        assert!(!whitelist.contains(&profile(2, "Notch")));
+        assert!(whitelist.contains(&profile(3, "JEB_")));
+        assert!(whitelist.check(&profile(4, "Dinnerbone")).is_err());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn whitelist_reload() {
+        let path = temp_path();
+        fs::write(&path, r#"[{ "name": "Notch" }]"#).unwrap();
+
+        let whitelist = Whitelist::load(&path).unwrap();
+        let clone = whitelist.clone();
+
+        fs::write(&path, r#"[{ "name": "jeb_" }]"#).unwrap();
+        whitelist.reload().unwrap();
+
+        assert!(!clone.contains(&profile(1, "Notch")));
+        assert!(clone.contains(&profile(1, "jeb_")));
+
+        // Invalid files leave the entries unchanged.
+        fs::write(&path, "not json").unwrap();
+        assert!(whitelist.reload().is_err());
+        assert!(clone.contains(&profile(1, "jeb_")));
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -21,15 +21,21 @@
 pub mod packet;
 
 use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::mem;
 
 use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
-use packet::{PlayerListActions, PlayerListHeaderS2c, PlayerListS2c};
+use packet::{ChatData, PlayerListActions, PlayerListHeaderS2c, PlayerListS2c};
 use uuid::Uuid;
+use valence_client::chat_session::{ChatSession, ChatSessionSettings};
 use valence_client::{Client, Ping, Properties, Username};
 use valence_core::despawn::Despawned;
 use valence_core::game_mode::GameMode;
 use valence_core::protocol::encode::{PacketWriter, WritePacket};
+use valence_core::protocol::packet::scoreboard::{
+    CollisionRule, Mode, NameTagVisibility, TeamColor, TeamFlags, TeamS2c,
+};
 use valence_core::text::Text;
 use valence_core::uuid::UniqueId;
 use valence_core::Server;
@@ -59,9 +65,13 @@ impl Plugin for PlayerListPlugin {
                     add_new_clients_to_player_list,
                     apply_deferred, // So new clients get the packets for their own entry.
                     update_entries,
+                    update_latencies.run_if(latency_update_due),
+                    reset_latency_overrides,
+                    update_sort_teams,
                     init_player_list_for_clients,
                     remove_despawned_entries,
                     write_player_list_changes,
+                    update_display_name_overrides,
                 )
                     .in_set(PlayerListSet)
                     .chain(),
@@ -72,22 +82,36 @@ impl Plugin for PlayerListPlugin {
 #[derive(Resource)]
 pub struct PlayerList {
     cached_update_packets: Vec<u8>,
+    /// Entries whose display name was sent to all clients this tick.
+    display_name_updates: Vec<Uuid>,
+    /// The members of the team of each sort key.
+    sort_teams: BTreeMap<i32, BTreeSet<String>>,
+    /// The sort key and team member name of each entry in a sort team.
+    sort_team_members: HashMap<Entity, (i32, String)>,
     header: Text,
     footer: Text,
     changed_header_or_footer: bool,
     /// If clients should be automatically added and removed from the player
     /// list with the proper components inserted. Enabled by default.
     pub manage_clients: bool,
+    /// The number of ticks between updates of the latency of player list
+    /// entries. Changes to the [`Ping`] of entries are sent together once per
+    /// interval. Defaults to 60 ticks.
+    pub latency_update_interval: u32,
 }
 
 impl PlayerList {
     fn new() -> Self {
         Self {
             cached_update_packets: vec![],
+            display_name_updates: vec![],
+            sort_teams: BTreeMap::new(),
+            sort_team_members: HashMap::new(),
             header: Text::default(),
             footer: Text::default(),
             changed_header_or_footer: false,
             manage_clients: true,
+            latency_update_interval: 60,
         }
     }
 
@@ -147,7 +171,82 @@ pub struct PlayerListEntry;
 #[derive(Component, Default, Debug)]
 pub struct DisplayName(pub Option<Text>);
 
+/// Display names of player list entries that only the client with this
+/// component sees, in place of the entries' [`DisplayName`]s.
+///
+/// Entries are identified by their [`UniqueId`]. Changes are only sent to this
+/// client, and the overrides are sent again whenever the shared display names
+/// of their entries are.
+#[derive(Component, Default, Debug)]
+pub struct DisplayNameOverrides {
+    names: HashMap<Uuid, Option<Text>>,
+    changed: HashSet<Uuid>,
+}
+
+impl DisplayNameOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the display name this client sees for an entry, or `None` if
+    /// the display name of the entry isn't overridden.
+    pub fn get(&self, uuid: Uuid) -> Option<Option<&Text>> {
+        self.names.get(&uuid).map(Option::as_ref)
+    }
+
+    /// Shows `name` as the display name of an entry to this client. The entry
+    /// appears as its [`Username`] if `name` is `None`.
+    pub fn set(&mut self, uuid: Uuid, name: Option<Text>) {
+        if self.names.get(&uuid) != Some(&name) {
+            self.changed.insert(uuid);
+            self.names.insert(uuid, name);
+        }
+    }
+
+    /// Shows the shared display name of an entry to this client again. Returns
+    /// the removed override.
+    pub fn remove(&mut self, uuid: Uuid) -> Option<Option<Text>> {
+        let name = self.names.remove(&uuid);
+
+        if name.is_some() {
+            self.changed.insert(uuid);
+        }
+
+        name
+    }
+}
+
+/// Orders a player list entry among the others. Entries with a lower key are
+/// listed first, and entries with the same key are listed by name.
+///
+/// Clients sort the player list by team name, so every sort key has a team
+/// named by [`sort_team_name`] that the entries with that key are put on.
+/// These teams are managed by the player list. Since a player can only be on
+/// one team, entries with a sort key can't be put on other teams. Entries
+/// without a sort key are listed before all entries with one.
+#[derive(Component, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Debug)]
+pub struct SortKey(pub i32);
+
+/// Returns the name of the team that orders the player list entries with a
+/// [`SortKey`].
+///
+/// The names of teams with lower keys come first alphabetically.
+pub fn sort_team_name(key: i32) -> String {
+    // Flipping the sign bit maps the keys to unsigned integers in the same order.
+    format!("sort_{:08x}", key as u32 ^ 0x8000_0000)
+}
+
+/// Latency in milliseconds shown for a player list entry in place of its
+/// [`Ping`]. Changes are sent immediately, and changes to the ping of the entry
+/// aren't sent while this is present.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct LatencyOverride(pub i32);
+
 /// If a player list entry is visible. Defaults to `true`.
+///
+/// Unlisted entries are hidden from the player list, but clients still know
+/// about them. Their skins and chat sessions keep working, and changes to
+/// their game mode and display name are still sent.
 #[derive(Component, Copy, Clone, Debug)]
 pub struct Listed(pub bool);
 
@@ -164,7 +263,8 @@ fn update_header_footer(player_list: ResMut<PlayerList>, server: Res<Server>) {
         let mut w = PacketWriter::new(
             &mut player_list.cached_update_packets,
             server.compression_threshold(),
-        );
+        )
+        .with_level(server.compression_level());
 
         w.write_packet(&PlayerListHeaderS2c {
             header: (&player_list.header).into(),
@@ -192,7 +292,10 @@ fn add_new_clients_to_player_list(
 }
 
 fn init_player_list_for_clients(
-    mut clients: Query<&mut Client, (Added<Client>, Without<Despawned>)>,
+    mut clients: Query<
+        (&mut Client, Option<&DisplayNameOverrides>),
+        (Added<Client>, Without<Despawned>),
+    >,
     player_list: Res<PlayerList>,
     entries: Query<
         (
@@ -201,16 +304,22 @@ fn init_player_list_for_clients(
             &Properties,
             &GameMode,
             &Ping,
+            Option<&LatencyOverride>,
             &DisplayName,
             &Listed,
+            Option<&ChatSession>,
         ),
         With<PlayerListEntry>,
     >,
+    chat_settings: Res<ChatSessionSettings>,
 ) {
     if player_list.manage_clients {
-        for mut client in &mut clients {
+        let send_sessions = chat_settings.signed_chat.preserves_signatures();
+
+        for (mut client, overrides) in &mut clients {
             let actions = PlayerListActions::new()
                 .with_add_player(true)
+                .with_initialize_chat(send_sessions)
                 .with_update_game_mode(true)
                 .with_update_listed(true)
                 .with_update_latency(true)
@@ -219,16 +328,29 @@ fn init_player_list_for_clients(
             let entries: Vec<_> = entries
                 .iter()
                 .map(
-                    |(uuid, username, props, game_mode, ping, display_name, listed)| {
+                    |(
+                        uuid,
+                        username,
+                        props,
+                        game_mode,
+                        ping,
+                        latency_override,
+                        display_name,
+                        listed,
+                        session,
+                    )| {
                         packet::PlayerListEntry {
                             player_uuid: uuid.0,
                             username: &username.0,
                             properties: Cow::Borrowed(&props.0),
-                            chat_data: None,
+                            chat_data: session.filter(|_| send_sessions).and_then(chat_data),
                             listed: listed.0,
-                            ping: ping.0,
+                            ping: latency(ping, latency_override),
                             game_mode: *game_mode,
-                            display_name: display_name.0.as_ref().map(Cow::Borrowed),
+                            display_name: overrides
+                                .and_then(|o| o.get(uuid.0))
+                                .unwrap_or(display_name.0.as_ref())
+                                .map(Cow::Borrowed),
                         }
                     },
                 )
@@ -241,6 +363,13 @@ fn init_player_list_for_clients(
                 });
             }
 
+            for (&key, members) in &player_list.sort_teams {
+                client.write_packet(&TeamS2c {
+                    team_name: &sort_team_name(key),
+                    mode: create_sort_team(members.iter().map(String::as_str).collect()),
+                });
+            }
+
             if !player_list.header.is_empty() || !player_list.footer.is_empty() {
                 client.write_packet(&PlayerListHeaderS2c {
                     header: Cow::Borrowed(&player_list.header),
@@ -268,7 +397,8 @@ fn remove_despawned_entries(
             let mut w = PacketWriter::new(
                 &mut player_list.cached_update_packets,
                 server.compression_threshold(),
-            );
+            )
+            .with_level(server.compression_level());
 
             w.write_packet(&PlayerRemoveS2c {
                 uuids: Cow::Borrowed(&removed),
@@ -286,9 +416,11 @@ fn update_entries(
             Ref<Username>,
             Ref<Properties>,
             Ref<GameMode>,
-            Ref<Ping>,
+            &Ping,
+            Option<Ref<LatencyOverride>>,
             Ref<DisplayName>,
             Ref<Listed>,
+            Option<Ref<ChatSession>>,
         ),
         (
             With<PlayerListEntry>,
@@ -297,24 +429,36 @@ fn update_entries(
                 Changed<Username>,
                 Changed<Properties>,
                 Changed<GameMode>,
-                Changed<Ping>,
+                Changed<LatencyOverride>,
                 Changed<DisplayName>,
                 Changed<Listed>,
+                Changed<ChatSession>,
             )>,
         ),
     >,
     server: Res<Server>,
     player_list: ResMut<PlayerList>,
+    chat_settings: Res<ChatSessionSettings>,
 ) {
+    let send_sessions = chat_settings.signed_chat.preserves_signatures();
+
     let player_list = player_list.into_inner();
 
     let mut writer = PacketWriter::new(
         &mut player_list.cached_update_packets,
         server.compression_threshold(),
-    );
+    )
+    .with_level(server.compression_level());
 
-    for (uuid, username, props, game_mode, ping, display_name, listed) in &entries {
+    for (uuid, username, props, game_mode, ping, latency_override, display_name, listed, session) in
+        &entries
+    {
         let mut actions = PlayerListActions::new();
+        let ping = latency(ping, latency_override.as_deref());
+        let chat_data = session
+            .as_deref()
+            .filter(|_| send_sessions)
+            .and_then(chat_data);
 
         // Did a change occur that would force us to overwrite the entry? This also adds
         // new entries.
@@ -325,7 +469,7 @@ fn update_entries(
                 actions.set_update_game_mode(true);
             }
 
-            if ping.0 != 0 {
+            if ping != 0 {
                 actions.set_update_latency(true);
             }
 
@@ -336,12 +480,17 @@ fn update_entries(
             if listed.0 {
                 actions.set_update_listed(true);
             }
+
+            if chat_data.is_some() {
+                actions.set_initialize_chat(true);
+            }
         } else {
             if game_mode.is_changed() {
                 actions.set_update_game_mode(true);
             }
 
-            if ping.is_changed() {
+            // Changes to the ping are sent in `update_latencies`.
+            if latency_override.is_some_and(|l| l.is_changed()) {
                 actions.set_update_latency(true);
             }
 
@@ -353,16 +502,26 @@ fn update_entries(
                 actions.set_update_listed(true);
             }
 
-            debug_assert_ne!(u8::from(actions), 0);
+            if send_sessions && session.as_ref().is_some_and(|s| s.is_changed()) {
+                actions.set_initialize_chat(true);
+            }
+
+            if u8::from(actions) == 0 {
+                continue;
+            }
+        }
+
+        if actions.add_player() || actions.update_display_name() {
+            player_list.display_name_updates.push(uuid.0);
         }
 
         let entry = packet::PlayerListEntry {
             player_uuid: uuid.0,
             username: &username.0,
             properties: (&props.0).into(),
-            chat_data: None,
+            chat_data,
             listed: listed.0,
-            ping: ping.0,
+            ping,
             game_mode: *game_mode,
             display_name: display_name.0.as_ref().map(|x| x.into()),
         };
@@ -374,6 +533,187 @@ fn update_entries(
     }
 }
 
+/// Returns the latency shown for a player list entry.
+fn latency(ping: &Ping, latency_override: Option<&LatencyOverride>) -> i32 {
+    latency_override.map_or(ping.0, |l| l.0)
+}
+
+fn latency_update_due(player_list: Res<PlayerList>, server: Res<Server>) -> bool {
+    server.current_tick() % i64::from(player_list.latency_update_interval.max(1)) == 0
+}
+
+/// Sends the pings of the player list entries that changed since the last
+/// latency update in a single packet.
+fn update_latencies(
+    entries: Query<
+        (&UniqueId, &Ping),
+        (
+            With<PlayerListEntry>,
+            Without<LatencyOverride>,
+            Without<Despawned>,
+            Changed<Ping>,
+        ),
+    >,
+    server: Res<Server>,
+    player_list: ResMut<PlayerList>,
+) {
+    write_latencies(entries.iter(), server, player_list);
+}
+
+/// Sends the pings of the player list entries that no longer have a
+/// [`LatencyOverride`].
+fn reset_latency_overrides(
+    mut removed: RemovedComponents<LatencyOverride>,
+    entries: Query<(&UniqueId, &Ping), (With<PlayerListEntry>, Without<LatencyOverride>)>,
+    server: Res<Server>,
+    player_list: ResMut<PlayerList>,
+) {
+    write_latencies(entries.iter_many(removed.iter()), server, player_list);
+}
+
+fn write_latencies<'a>(
+    entries: impl Iterator<Item = (&'a UniqueId, &'a Ping)>,
+    server: Res<Server>,
+    player_list: ResMut<PlayerList>,
+) {
+    let entries: Vec<_> = entries
+        .map(|(uuid, ping)| packet::PlayerListEntry {
+            player_uuid: uuid.0,
+            ping: ping.0,
+            ..Default::default()
+        })
+        .collect();
+
+    if entries.is_empty() {
+        return;
+    }
+
+    let player_list = player_list.into_inner();
+
+    let mut writer = PacketWriter::new(
+        &mut player_list.cached_update_packets,
+        server.compression_threshold(),
+    )
+    .with_level(server.compression_level());
+
+    writer.write_packet(&PlayerListS2c {
+        actions: PlayerListActions::new().with_update_latency(true),
+        entries: Cow::Owned(entries),
+    });
+}
+
+/// Moves player list entries to the teams of their changed [`SortKey`]s.
+fn update_sort_teams(
+    entries: Query<
+        (Entity, &Username, &SortKey),
+        (
+            With<PlayerListEntry>,
+            Without<Despawned>,
+            Or<(Changed<SortKey>, Changed<Username>)>,
+        ),
+    >,
+    mut removed: RemovedComponents<SortKey>,
+    server: Res<Server>,
+    player_list: ResMut<PlayerList>,
+) {
+    let mut moves = vec![];
+
+    for entity in &mut removed {
+        if !entries.contains(entity) {
+            moves.push((entity, None));
+        }
+    }
+
+    for (entity, username, key) in &entries {
+        moves.push((entity, Some((key.0, username.0.clone()))));
+    }
+
+    if moves.is_empty() {
+        return;
+    }
+
+    let player_list = player_list.into_inner();
+
+    let mut writer = PacketWriter::new(
+        &mut player_list.cached_update_packets,
+        server.compression_threshold(),
+    )
+    .with_level(server.compression_level());
+
+    for (entity, new) in moves {
+        if player_list.sort_team_members.get(&entity) == new.as_ref() {
+            continue;
+        }
+
+        let old = player_list.sort_team_members.remove(&entity);
+
+        if let Some((key, name)) = old {
+            let members = player_list.sort_teams.entry(key).or_default();
+            members.remove(&name);
+
+            let mode = if members.is_empty() {
+                player_list.sort_teams.remove(&key);
+                Mode::RemoveTeam
+            } else {
+                Mode::RemoveEntities {
+                    entities: vec![name.as_str()],
+                }
+            };
+
+            writer.write_packet(&TeamS2c {
+                team_name: &sort_team_name(key),
+                mode,
+            });
+        }
+
+        if let Some((key, name)) = new {
+            let members = player_list.sort_teams.entry(key).or_default();
+
+            let mode = if members.is_empty() {
+                create_sort_team(vec![name.as_str()])
+            } else {
+                Mode::AddEntities {
+                    entities: vec![name.as_str()],
+                }
+            };
+
+            writer.write_packet(&TeamS2c {
+                team_name: &sort_team_name(key),
+                mode,
+            });
+
+            members.insert(name.clone());
+            player_list.sort_team_members.insert(entity, (key, name));
+        }
+    }
+}
+
+/// Returns the mode that creates a sort team, which has no effect on its
+/// members besides their order in the player list.
+fn create_sort_team(entities: Vec<&str>) -> Mode<'_> {
+    Mode::CreateTeam {
+        team_display_name: Cow::Owned(Text::default()),
+        friendly_flags: TeamFlags::new().with_friendly_fire(true),
+        name_tag_visibility: NameTagVisibility::Always,
+        collision_rule: CollisionRule::Always,
+        team_color: TeamColor::Reset,
+        team_prefix: Cow::Owned(Text::default()),
+        team_suffix: Cow::Owned(Text::default()),
+        entities,
+    }
+}
+
+/// Returns the chat data of `session` for the player list. Clients verify
+/// signed messages from a player with it.
+fn chat_data(session: &ChatSession) -> Option<ChatData<'_>> {
+    session.session().map(|s| ChatData {
+        session_id: s.session_id(),
+        key_expiry_time: s.expires_at(),
+        public_key: s.public_key_der(),
+        public_key_signature: s.key_signature(),
+    })
+}
+
 fn write_player_list_changes(
     mut player_list: ResMut<PlayerList>,
     mut clients: Query<&mut Client, Without<Despawned>>,
@@ -388,3 +728,61 @@ fn write_player_list_changes(
         player_list.cached_update_packets.clear();
     }
 }
+
+/// Sends the changed [`DisplayNameOverrides`] of clients, and the overrides
+/// whose shared display names were just sent.
+fn update_display_name_overrides(
+    mut clients: Query<(&mut Client, &mut DisplayNameOverrides), Without<Despawned>>,
+    entries: Query<(&UniqueId, &DisplayName), With<PlayerListEntry>>,
+    mut player_list: ResMut<PlayerList>,
+) {
+    let updated = mem::take(&mut player_list.display_name_updates);
+
+    for (mut client, mut overrides) in &mut clients {
+        if overrides.changed.is_empty()
+            && !updated
+                .iter()
+                .any(|uuid| overrides.names.contains_key(uuid))
+        {
+            continue;
+        }
+
+        let overrides = overrides.into_inner();
+        let changed = mem::take(&mut overrides.changed);
+
+        // New clients got the overrides with the rest of the player list.
+        if client.is_added() {
+            continue;
+        }
+
+        let uuids: BTreeSet<Uuid> = changed
+            .into_iter()
+            .chain(
+                updated
+                    .iter()
+                    .copied()
+                    .filter(|uuid| overrides.names.contains_key(uuid)),
+            )
+            .collect();
+
+        let entries: Vec<_> = entries
+            .iter()
+            .filter(|(uuid, _)| uuids.contains(&uuid.0))
+            .map(|(uuid, display_name)| packet::PlayerListEntry {
+                player_uuid: uuid.0,
+                display_name: overrides
+                    .get(uuid.0)
+                    .unwrap_or(display_name.0.as_ref())
+                    .map(Cow::Borrowed),
+                ..Default::default()
+            })
+            .collect();
+
+        if !entries.is_empty() {
+            client.write_packet(&PlayerListS2c {
+                actions: PlayerListActions::new().with_update_display_name(true),
+                entries: Cow::Owned(entries),
+            });
+        }
+    }
+}
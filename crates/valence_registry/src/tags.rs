@@ -65,7 +65,8 @@ pub(crate) fn cache_tags_packet(server: Res<Server>, tags: ResMut<TagsRegistry>)
         let tags = tags.into_inner();
         let packet = tags.build_synchronize_tags();
         let mut bytes = vec![];
-        let mut writer = PacketWriter::new(&mut bytes, server.compression_threshold());
+        let mut writer = PacketWriter::new(&mut bytes, server.compression_threshold())
+            .with_level(server.compression_level());
         writer.write_packet(&packet);
         tags.cached_packet = bytes;
     }
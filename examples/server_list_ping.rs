@@ -46,6 +46,8 @@ impl NetworkCallbacks for MyCallbacks {
             version_name: ("Valence ".color(Color::GOLD) + MINECRAFT_VERSION.color(Color::RED))
                 .to_legacy_lossy(),
             protocol: handshake_data.protocol_version,
+            enforces_secure_chat: false,
+            previews_chat: false,
         }
     }
 
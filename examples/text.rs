@@ -52,16 +52,21 @@ fn init_clients(
 
         // Text examples
         client.send_chat_message("\nText");
-        client.send_chat_message(" - ".into_text() + Text::text("Plain text"));
-        client.send_chat_message(" - ".into_text() + Text::text("Styled text").italic());
-        client.send_chat_message(" - ".into_text() + Text::text("Colored text").color(Color::GOLD));
-        client.send_chat_message(
-            " - ".into_text()
-                + Text::text("Colored and styled text")
-                    .color(Color::GOLD)
-                    .italic()
-                    .underlined(),
-        );
+        client.send_chat_message(text![" - ", "Plain text"]);
+        client.send_chat_message(text![" - ", (italic): "Styled text"]);
+        client.send_chat_message(text![" - ", (color = GOLD): "Colored text"]);
+        client.send_chat_message(text![
+            " - ",
+            (color = GOLD, italic, underlined): "Colored and styled text"
+        ]);
+        client.send_chat_message(text![
+            " - ",
+            (
+                color = AQUA,
+                on_click_suggest_command("/help"),
+                on_hover_show_text(text!["Click to type ", (color = GOLD): "/help"]),
+            ): "Clickable text"
+        ]);
 
         // Translated text examples
         client.send_chat_message("\nTranslated Text");
@@ -118,9 +123,8 @@ fn init_clients(
                 + Text::storage_nbt(ident!("storage.key"), "@a", None, None),
         );
 
-        client.send_chat_message(
-            "\n\n↑ ".into_text().bold().color(Color::GOLD)
-                + "Scroll up to see the full example!".into_text().not_bold(),
-        );
+        client.send_chat_message(text![
+            (bold, color = GOLD): ["\n\n↑ ", (not_bold): "Scroll up to see the full example!"]
+        ]);
     }
 }
@@ -94,6 +94,7 @@ public class Main implements ModInitializer {
 
             var startupExtractors = new Extractor[]{
                 new Tags(server),
+                new Recipes(server),
             };
 
             for (var ext : startupExtractors) {
@@ -0,0 +1,109 @@
+package rs.valence.extractor.extractors;
+
+import com.google.gson.JsonArray;
+import com.google.gson.JsonElement;
+import com.google.gson.JsonObject;
+import net.minecraft.item.ItemStack;
+import net.minecraft.recipe.AbstractCookingRecipe;
+import net.minecraft.recipe.Ingredient;
+import net.minecraft.recipe.ShapedRecipe;
+import net.minecraft.recipe.ShapelessRecipe;
+import net.minecraft.recipe.SmithingTransformRecipe;
+import net.minecraft.recipe.SmithingTrimRecipe;
+import net.minecraft.recipe.StonecuttingRecipe;
+import net.minecraft.registry.Registries;
+import net.minecraft.server.MinecraftServer;
+import rs.valence.extractor.Main;
+
+public class Recipes implements Main.Extractor {
+    private final MinecraftServer server;
+
+    public Recipes(MinecraftServer server) {
+        this.server = server;
+    }
+
+    @Override
+    public String fileName() {
+        return "recipes.json";
+    }
+
+    @Override
+    public JsonElement extract() {
+        var registryManager = this.server.getRegistryManager();
+        var recipesJson = new JsonArray();
+
+        for (var recipe : this.server.getRecipeManager().values()) {
+            var recipeJson = new JsonObject();
+
+            recipeJson.addProperty("id", recipe.getId().toString());
+            recipeJson.addProperty("type", Registries.RECIPE_SERIALIZER.getId(recipe.getSerializer()).getPath());
+            recipeJson.addProperty("group", recipe.getGroup());
+
+            if (recipe instanceof ShapedRecipe shaped) {
+                recipeJson.addProperty("category", shaped.getCategory().asString());
+                recipeJson.addProperty("width", shaped.getWidth());
+                recipeJson.addProperty("height", shaped.getHeight());
+                recipeJson.add("ingredients", ingredientsJson(shaped.getIngredients()));
+                recipeJson.addProperty("show_notification", shaped.showNotification());
+            } else if (recipe instanceof ShapelessRecipe shapeless) {
+                recipeJson.addProperty("category", shapeless.getCategory().asString());
+                recipeJson.add("ingredients", ingredientsJson(shapeless.getIngredients()));
+            } else if (recipe instanceof AbstractCookingRecipe cooking) {
+                recipeJson.addProperty("category", cooking.getCategory().asString());
+                recipeJson.add("ingredient", ingredientJson(cooking.getIngredients().get(0)));
+                recipeJson.addProperty("experience", cooking.getExperience());
+                recipeJson.addProperty("cooking_time", cooking.getCookTime());
+            } else if (recipe instanceof StonecuttingRecipe stonecutting) {
+                recipeJson.add("ingredient", ingredientJson(stonecutting.getIngredients().get(0)));
+            } else if (recipe instanceof SmithingTransformRecipe || recipe instanceof SmithingTrimRecipe) {
+                recipeJson.add("template", ingredientJson(ingredientField(recipe, "template")));
+                recipeJson.add("base", ingredientJson(ingredientField(recipe, "base")));
+                recipeJson.add("addition", ingredientJson(ingredientField(recipe, "addition")));
+            } else {
+                // Special recipes are handled by the client itself.
+                continue;
+            }
+
+            if (!(recipe instanceof SmithingTrimRecipe)) {
+                recipeJson.add("result", resultJson(recipe.getOutput(registryManager)));
+            }
+            recipesJson.add(recipeJson);
+        }
+
+        return recipesJson;
+    }
+
+    private static JsonArray ingredientsJson(Iterable<Ingredient> ingredients) {
+        var ingredientsJson = new JsonArray();
+        for (var ingredient : ingredients) {
+            ingredientsJson.add(ingredientJson(ingredient));
+        }
+        return ingredientsJson;
+    }
+
+    private static JsonArray ingredientJson(Ingredient ingredient) {
+        var itemsJson = new JsonArray();
+        for (var stack : ingredient.getMatchingStacks()) {
+            itemsJson.add(Registries.ITEM.getId(stack.getItem()).getPath());
+        }
+        return itemsJson;
+    }
+
+    // The ingredients of smithing recipes aren't exposed.
+    private static Ingredient ingredientField(Object recipe, String name) {
+        try {
+            var field = recipe.getClass().getDeclaredField(name);
+            field.setAccessible(true);
+            return (Ingredient) field.get(recipe);
+        } catch (ReflectiveOperationException e) {
+            throw new RuntimeException("Failed to get \"" + name + "\" of " + recipe.getClass().getSimpleName(), e);
+        }
+    }
+
+    private static JsonObject resultJson(ItemStack stack) {
+        var resultJson = new JsonObject();
+        resultJson.addProperty("item", Registries.ITEM.getId(stack.getItem()).getPath());
+        resultJson.addProperty("count", stack.getCount());
+        return resultJson;
+    }
+}
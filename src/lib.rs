@@ -27,28 +27,34 @@ pub mod testing;
 #[cfg(test)]
 mod tests;
 
+pub use bevy_app as app;
+pub use bevy_ecs as ecs;
 #[cfg(feature = "log")]
 pub use bevy_log as log;
+pub use glam;
 #[cfg(feature = "advancement")]
 pub use valence_advancement as advancement;
 #[cfg(feature = "anvil")]
 pub use valence_anvil as anvil;
+pub use valence_biome as biome;
+pub use valence_block as block;
 #[cfg(feature = "boss_bar")]
 pub use valence_boss_bar as boss_bar;
+pub use valence_client as client;
 pub use valence_core::*;
+pub use valence_dimension as dimension;
+pub use valence_entity as entity;
+pub use valence_instance as instance;
 #[cfg(feature = "inventory")]
 pub use valence_inventory as inventory;
+pub use valence_nbt as nbt;
 #[cfg(feature = "network")]
 pub use valence_network as network;
 #[cfg(feature = "player_list")]
 pub use valence_player_list as player_list;
+pub use valence_registry as registry;
 #[cfg(feature = "world_border")]
 pub use valence_world_border as world_border;
-pub use {
-    bevy_app as app, bevy_ecs as ecs, glam, valence_biome as biome, valence_block as block,
-    valence_client as client, valence_dimension as dimension, valence_entity as entity,
-    valence_instance as instance, valence_nbt as nbt, valence_registry as registry,
-};
 
 /// Contains the most frequently used items in Valence projects.
 ///
@@ -101,7 +107,7 @@ pub mod prelude {
     pub use valence_core::particle::Particle;
     pub use valence_core::text::{Color, Text, TextFormat};
     pub use valence_core::uuid::UniqueId;
-    pub use valence_core::{translation_key, CoreSettings, Server};
+    pub use valence_core::{text, translation_key, CoreSettings, Server};
     pub use valence_dimension::{DimensionType, DimensionTypeRegistry};
     pub use valence_entity::hitbox::{Hitbox, HitboxShape};
     pub use valence_entity::{
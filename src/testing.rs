@@ -14,9 +14,10 @@ use valence_client::ClientBundleArgs;
 use valence_core::protocol::decode::{PacketDecoder, PacketFrame};
 use valence_core::protocol::encode::PacketEncoder;
 use valence_core::protocol::var_int::VarInt;
-use valence_core::protocol::{Decode, Encode, Packet};
+use valence_core::protocol::{Decode, Encode, Packet, PacketSide, PacketState};
 use valence_core::{ident, CoreSettings, Server};
 use valence_dimension::DimensionTypeRegistry;
+use valence_network::capture::CaptureRecord;
 use valence_network::NetworkPlugin;
 
 use crate::client::{ClientBundle, ClientConnection, ReceivedPacket};
@@ -119,6 +120,19 @@ impl MockClientConnection {
             });
     }
 
+    /// Injects a packet frame to be received by the server.
+    fn inject_frame(&mut self, frame: PacketFrame) {
+        self.inner
+            .lock()
+            .unwrap()
+            .recv_buf
+            .push_back(ReceivedPacket {
+                timestamp: Instant::now(),
+                id: frame.id,
+                body: frame.body.freeze(),
+            });
+    }
+
     fn take_received(&mut self) -> BytesMut {
         self.inner.lock().unwrap().send_buf.split()
     }
@@ -138,6 +152,10 @@ impl ClientConnection for MockClientConnection {
         Ok(self.inner.lock().unwrap().recv_buf.pop_front())
     }
 
+    fn buffered_bytes(&self) -> usize {
+        self.inner.lock().unwrap().send_buf.len()
+    }
+
     fn len(&self) -> usize {
         self.inner.lock().unwrap().recv_buf.len()
     }
@@ -180,6 +198,24 @@ impl MockClientHelper {
         self.conn.inject_send(self.scratch.split());
     }
 
+    /// Injects the serverbound play packets of a [packet capture], in the
+    /// order they were recorded, as if they were sent by this client. Other
+    /// packets are skipped. Returns the number of packets injected.
+    ///
+    /// [packet capture]: valence_network::capture
+    pub fn replay(&mut self, records: impl IntoIterator<Item = CaptureRecord>) -> usize {
+        let mut count = 0;
+
+        for record in records {
+            if record.side == PacketSide::Serverbound && record.state == PacketState::Play {
+                self.conn.inject_frame(record.frame);
+                count += 1;
+            }
+        }
+
+        count
+    }
+
     /// Collect all packets that have been received by the client.
     #[track_caller]
     pub fn collect_received(&mut self) -> PacketFrames {
@@ -1,8 +1,12 @@
+mod anvil;
 mod boss_bar;
+mod chat_session;
 mod client;
 mod example;
 mod instance;
 mod inventory;
+mod network;
 mod player_list;
+mod recipe;
 mod weather;
 mod world_border;
@@ -0,0 +1,153 @@
+use std::path::Path;
+use std::time::Duration;
+use std::{fs, thread};
+
+use bevy_app::App;
+use bevy_ecs::event::{Events, ManualEventReader};
+use bevy_ecs::prelude::*;
+use valence_anvil::{AnvilLevel, ChunkLoadEvent, RegionFolder, ReloadConflictPolicy};
+use valence_biome::BiomeRegistry;
+use valence_core::chunk_pos::ChunkPos;
+use valence_instance::packet::ChunkDataS2c;
+use valence_instance::Instance;
+use valence_nbt::{compound, List};
+
+use crate::testing::scenario_single_client;
+
+/// Updates the app until a [`ChunkLoadEvent`] has been sent for every position
+/// in `positions`. Returns the positions of all the load events seen.
+fn update_until_loaded(
+    app: &mut App,
+    reader: &mut ManualEventReader<ChunkLoadEvent>,
+    positions: &[ChunkPos],
+) -> Vec<ChunkPos> {
+    let mut loaded = vec![];
+
+    for _ in 0..500 {
+        app.update();
+
+        let events = app.world.resource::<Events<ChunkLoadEvent>>();
+        loaded.extend(reader.iter(events).map(|e| e.pos));
+
+        if positions.iter().all(|pos| loaded.contains(pos)) {
+            return loaded;
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    panic!("chunks at {positions:?} were not loaded");
+}
+
+/// Simulates an external edit of a chunk by bumping its timestamp in the
+/// region header.
+fn touch_chunk(region_path: &Path, pos: ChunkPos) {
+    let mut bytes = fs::read(region_path).unwrap();
+    let offset = 4096 + (pos.x.rem_euclid(32) + pos.z.rem_euclid(32) * 32) as usize * 4;
+
+    let timestamp = u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    bytes[offset..offset + 4].copy_from_slice(&(timestamp + 1).to_be_bytes());
+
+    fs::write(region_path, bytes).unwrap();
+}
+
+#[test]
+fn reload_changed_chunks() {
+    let mut app = App::new();
+
+    let (_client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    let dir = tempfile::tempdir().unwrap();
+    let region_path = dir.path().join("region/r.0.0.mca");
+
+    let mut folder = RegionFolder::new(dir.path().join("region"));
+    let empty_chunk = compound! { "sections" => List::Compound(vec![]) };
+    folder.set_chunk(ChunkPos::new(0, 0), &empty_chunk).unwrap();
+    folder.set_chunk(ChunkPos::new(1, 0), &empty_chunk).unwrap();
+    drop(folder);
+
+    let mut reader = ManualEventReader::<ChunkLoadEvent>::default();
+
+    let inst_ent = app
+        .world
+        .query_filtered::<Entity, With<Instance>>()
+        .single(&app.world);
+
+    let level = AnvilLevel::new(dir.path(), app.world.resource::<BiomeRegistry>());
+    app.world.entity_mut(inst_ent).insert(level);
+
+    update_until_loaded(
+        &mut app,
+        &mut reader,
+        &[ChunkPos::new(0, 0), ChunkPos::new(1, 0)],
+    );
+
+    client_helper.clear_received();
+
+    touch_chunk(&region_path, ChunkPos::new(1, 0));
+
+    let (inst, mut level) = app
+        .world
+        .query::<(&Instance, &mut AnvilLevel)>()
+        .single_mut(&mut app.world);
+
+    assert_eq!(
+        level
+            .reload_changed(inst, ReloadConflictPolicy::Skip)
+            .unwrap(),
+        1
+    );
+
+    let reloaded = update_until_loaded(&mut app, &mut reader, &[ChunkPos::new(1, 0)]);
+    assert_eq!(reloaded, [ChunkPos::new(1, 0)]);
+
+    // Only the changed chunk is resent.
+    let recvd = client_helper.collect_received();
+    recvd.assert_count::<ChunkDataS2c>(1);
+    assert_eq!(recvd.first::<ChunkDataS2c>().pos, ChunkPos::new(1, 0));
+
+    // Nothing changed since the last reload.
+    let (inst, mut level) = app
+        .world
+        .query::<(&Instance, &mut AnvilLevel)>()
+        .single_mut(&mut app.world);
+
+    assert_eq!(
+        level
+            .reload_changed(inst, ReloadConflictPolicy::Skip)
+            .unwrap(),
+        0
+    );
+
+    // Chunks modified by the server are skipped.
+    touch_chunk(&region_path, ChunkPos::new(0, 0));
+    touch_chunk(&region_path, ChunkPos::new(1, 0));
+
+    level.modified_chunks.insert(ChunkPos::new(0, 0));
+
+    assert_eq!(
+        level
+            .reload_changed(inst, ReloadConflictPolicy::Skip)
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        update_until_loaded(&mut app, &mut reader, &[ChunkPos::new(1, 0)]),
+        [ChunkPos::new(1, 0)]
+    );
+
+    // Unless they are overwritten.
+    let (inst, mut level) = app
+        .world
+        .query::<(&Instance, &mut AnvilLevel)>()
+        .single_mut(&mut app.world);
+
+    touch_chunk(&region_path, ChunkPos::new(1, 0));
+
+    assert_eq!(
+        level
+            .reload_changed(inst, ReloadConflictPolicy::Overwrite)
+            .unwrap(),
+        2
+    );
+}
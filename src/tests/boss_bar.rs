@@ -1,14 +1,16 @@
 use bevy_app::App;
 use bevy_ecs::entity::Entity;
-use valence_boss_bar::packet::BossBarS2c;
+use valence_boss_bar::packet::{BossBarAction, BossBarS2c};
 use valence_boss_bar::{
-    BossBarBundle, BossBarColor, BossBarDivision, BossBarFlags, BossBarHealth, BossBarStyle,
-    BossBarTitle, BossBarViewers,
+    BossBarAnimation, BossBarBundle, BossBarColor, BossBarDivision, BossBarFlags, BossBarHealth,
+    BossBarPriority, BossBarStyle, BossBarTitle, BossBarViewers,
 };
 use valence_core::despawn::Despawned;
+use valence_core::protocol::Packet;
 use valence_core::text::Text;
+use valence_core::uuid::UniqueId;
 
-use crate::testing::{scenario_single_client, MockClientHelper};
+use crate::testing::{scenario_single_client, MockClientHelper, PacketFrames};
 
 #[test]
 fn test_intialize_on_join() {
@@ -149,6 +151,191 @@ fn test_flags_update() {
     // should be an UpdateFlags packet
     let frames = client_helper.collect_received();
     frames.assert_count::<BossBarS2c>(2);
+
+    let pkt = &boss_bar_packets(&frames)[1];
+    assert_eq!(pkt.action, BossBarAction::UpdateFlags(new_flags));
+}
+
+#[test]
+fn test_flags_coalesced() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper, instance_ent) = prepare(&mut app);
+
+    // Add our mock client to the viewers list
+    let mut boss_bar = app.world.get_mut::<BossBarViewers>(instance_ent).unwrap();
+    assert!(boss_bar.viewers.insert(client_ent));
+
+    app.update();
+    client_helper.clear_received();
+
+    // Toggle the flags several times in the same tick
+    let mut flags = app.world.get_mut::<BossBarFlags>(instance_ent).unwrap();
+    flags.set_darken_sky(true);
+    flags.set_create_fog(true);
+    flags.set_darken_sky(false);
+    flags.set_dragon_bar(true);
+
+    app.update();
+
+    // Only the final flags should be sent
+    let frames = client_helper.collect_received();
+    frames.assert_count::<BossBarS2c>(1);
+
+    let expected = BossBarFlags::new()
+        .with_create_fog(true)
+        .with_dragon_bar(true);
+    let pkt = frames.first::<BossBarS2c>();
+    assert_eq!(pkt.action, BossBarAction::UpdateFlags(expected));
+}
+
+#[test]
+fn test_flags_in_add() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper, instance_ent) = prepare(&mut app);
+
+    // Add our mock client to the viewers list and change the flags in the same
+    // tick
+    let mut boss_bar = app.world.get_mut::<BossBarViewers>(instance_ent).unwrap();
+    assert!(boss_bar.viewers.insert(client_ent));
+
+    let flags = BossBarFlags::new().with_darken_sky(true);
+    app.world.entity_mut(instance_ent).insert(flags);
+
+    app.update();
+
+    // The new viewer only gets the ADD packet, which has the new flags
+    let frames = client_helper.collect_received();
+    frames.assert_count::<BossBarS2c>(1);
+
+    let BossBarAction::Add { flags: sent, .. } = frames.first::<BossBarS2c>().action else {
+        panic!("expected an add action");
+    };
+    assert_eq!(sent, flags);
+}
+
+#[test]
+fn test_animation() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper, instance_ent) = prepare(&mut app);
+
+    // Add our mock client to the viewers list
+    let mut boss_bar = app.world.get_mut::<BossBarViewers>(instance_ent).unwrap();
+    assert!(boss_bar.viewers.insert(client_ent));
+
+    app.update();
+    client_helper.clear_received();
+
+    // Animate the health to zero over 10 ticks, updating every 3 ticks
+    app.world
+        .entity_mut(instance_ent)
+        .insert(BossBarAnimation::new(0.0, 10).with_interval(3));
+
+    for _ in 0..10 {
+        app.update();
+    }
+
+    // The health is sent after 3, 6 and 9 ticks, and once more at the end
+    let frames = client_helper.collect_received();
+    frames.assert_count::<BossBarS2c>(4);
+
+    let pkt = &boss_bar_packets(&frames)[3];
+    assert_eq!(pkt.action, BossBarAction::UpdateHealth(0.0));
+
+    // The finished animation removes itself
+    let boss_bar = app.world.entity(instance_ent);
+    assert_eq!(boss_bar.get::<BossBarHealth>().unwrap().0, 0.0);
+    assert!(boss_bar.get::<BossBarAnimation>().is_none());
+
+    app.update();
+    client_helper
+        .collect_received()
+        .assert_count::<BossBarS2c>(0);
+}
+
+#[test]
+fn test_animation_restart() {
+    let mut app = App::new();
+    let (_, _, instance_ent) = prepare(&mut app);
+
+    app.world
+        .entity_mut(instance_ent)
+        .insert(BossBarAnimation::new(0.0, 4));
+
+    app.update();
+    app.update();
+
+    assert_eq!(app.world.get::<BossBarHealth>(instance_ent).unwrap().0, 0.5);
+
+    // A new target starts from the current health
+    app.world
+        .entity_mut(instance_ent)
+        .insert(BossBarAnimation::new(1.0, 2));
+
+    app.update();
+
+    assert_eq!(
+        app.world.get::<BossBarHealth>(instance_ent).unwrap().0,
+        0.75
+    );
+}
+
+#[test]
+fn test_priority_order() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper, _) = prepare(&mut app);
+
+    // Spawn three more boss bars with different priorities that the client sees
+    let boss_bars: Vec<Entity> = [1, 3, 2]
+        .into_iter()
+        .map(|priority| {
+            let mut bundle = BossBarBundle::new(
+                Text::text("Test"),
+                BossBarColor::Blue,
+                BossBarDivision::SixNotches,
+                BossBarFlags::new(),
+            );
+            bundle.viewers.viewers.insert(client_ent);
+
+            app.world.spawn((bundle, BossBarPriority(priority))).id()
+        })
+        .collect();
+
+    let id = |app: &App, boss_bar: Entity| app.world.get::<UniqueId>(boss_bar).unwrap().0;
+
+    app.update();
+
+    // The boss bars are added from the highest priority to the lowest
+    let frames = client_helper.collect_received();
+    frames.assert_count::<BossBarS2c>(3);
+
+    let added: Vec<_> = boss_bar_packets(&frames)
+        .into_iter()
+        .map(|pkt| pkt.id)
+        .collect();
+    assert_eq!(
+        added,
+        [
+            id(&app, boss_bars[1]),
+            id(&app, boss_bars[2]),
+            id(&app, boss_bars[0])
+        ]
+    );
+
+    // Moving the middle boss bar to the bottom only moves that boss bar
+    app.world
+        .entity_mut(boss_bars[2])
+        .insert(BossBarPriority(0));
+
+    app.update();
+
+    let frames = client_helper.collect_received();
+    frames.assert_count::<BossBarS2c>(2);
+
+    let packets = boss_bar_packets(&frames);
+    assert_eq!(packets[0].id, id(&app, boss_bars[2]));
+    assert_eq!(packets[0].action, BossBarAction::Remove);
+    assert_eq!(packets[1].id, id(&app, boss_bars[2]));
+    assert!(matches!(packets[1].action, BossBarAction::Add { .. }));
 }
 
 #[test]
@@ -206,3 +393,12 @@ fn prepare(app: &mut App) -> (Entity, MockClientHelper, Entity) {
     client_helper.clear_received();
     (client_ent, client_helper, boss_bar)
 }
+
+fn boss_bar_packets(frames: &PacketFrames) -> Vec<BossBarS2c> {
+    frames
+        .0
+        .iter()
+        .filter(|frame| frame.id == BossBarS2c::ID)
+        .map(|frame| frame.decode().unwrap())
+        .collect()
+}
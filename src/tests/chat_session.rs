@@ -0,0 +1,429 @@
+use std::borrow::Cow;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy_app::App;
+use bevy_ecs::event::Events;
+use bevy_ecs::prelude::*;
+use rsa::pkcs8::{DecodePrivateKey, EncodePublicKey};
+use rsa::{PaddingScheme, RsaPrivateKey};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use valence_client::chat_session::{
+    message_hash, write_player_chat, ChatSessionError, ChatSessionSettings, ChatSignature,
+    PlayerChat, SignedChatMode, SignedChatState, SignedMessage,
+};
+use valence_client::message::{ChatMessageEvent, ChatType, SendMessage};
+use valence_client::packet::{DisconnectS2c, ServerMetadataS2c};
+use valence_client::Client;
+use valence_core::protocol::packet::chat::{
+    ChatMessageC2s, ChatMessageS2c, GameMessageS2c, PlayerSessionC2s, RemoveMessageS2c,
+};
+use valence_core::protocol::var_int::VarInt;
+use valence_core::text::Text;
+use valence_core::translation_key;
+use valence_core::uuid::UniqueId;
+use valence_player_list::packet::PlayerListS2c;
+
+use crate::testing::{create_mock_client, scenario_single_client, MockClientHelper};
+
+// Generated for these tests. The services key stands in for Mojang's key that
+// signs the chat keys of players.
+const SERVICES_KEY: &[u8] = include_bytes!("fixtures/services_key.der");
+const PLAYER_KEY: &[u8] = include_bytes!("fixtures/player_key.der");
+
+struct SigningClient {
+    uuid: Uuid,
+    session_id: Uuid,
+    key: RsaPrivateKey,
+    next_index: i32,
+}
+
+impl SigningClient {
+    fn new(uuid: Uuid) -> Self {
+        Self {
+            uuid,
+            session_id: Uuid::from_u128(0xc4a7),
+            key: RsaPrivateKey::from_pkcs8_der(PLAYER_KEY).unwrap(),
+            next_index: 0,
+        }
+    }
+
+    /// Sends the chat session with the key signed by `signer`.
+    fn send_session(&self, helper: &mut MockClientHelper, signer: &RsaPrivateKey) {
+        let expires_at = unix_millis() as i64 + 24 * 60 * 60 * 1000;
+        let public_key = self.key.to_public_key().to_public_key_der().unwrap();
+
+        let hash = Sha1::new()
+            .chain_update(self.uuid.as_bytes())
+            .chain_update(expires_at.to_be_bytes())
+            .chain_update(public_key.as_bytes())
+            .finalize();
+
+        let key_signature = signer
+            .sign(PaddingScheme::new_pkcs1v15_sign::<Sha1>(), &hash)
+            .unwrap();
+
+        helper.send(&PlayerSessionC2s {
+            session_id: self.session_id,
+            expires_at,
+            public_key_data: public_key.as_bytes(),
+            key_signature: &key_signature,
+        });
+    }
+
+    /// Sends a signed chat message, acknowledging the messages in `last_seen`.
+    fn send_chat(
+        &mut self,
+        helper: &mut MockClientHelper,
+        message: &str,
+        last_seen: Vec<[u8; 256]>,
+        message_count: i32,
+        acknowledgement: [u8; 3],
+    ) -> SignedMessage {
+        let mut msg = SignedMessage {
+            sender: self.uuid,
+            session_id: self.session_id,
+            index: self.next_index,
+            salt: 0x5a17,
+            timestamp: unix_millis(),
+            signature: Box::new([0; 256]),
+            last_seen,
+        };
+
+        self.next_index += 1;
+
+        let signature = self
+            .key
+            .sign(
+                PaddingScheme::new_pkcs1v15_sign::<Sha256>(),
+                &message_hash(&msg, message),
+            )
+            .unwrap();
+
+        msg.signature.copy_from_slice(&signature);
+
+        helper.send(&ChatMessageC2s {
+            message,
+            timestamp: msg.timestamp,
+            salt: msg.salt,
+            signature: Some(&msg.signature),
+            message_count: VarInt(message_count),
+            acknowledgement,
+        });
+
+        msg
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+fn setup(signed_chat: SignedChatMode) -> (App, [(Entity, MockClientHelper); 2], RsaPrivateKey) {
+    let mut app = App::new();
+
+    let (client_ent_1, helper_1) = scenario_single_client(&mut app);
+
+    let location = app
+        .world
+        .get::<valence_entity::Location>(client_ent_1)
+        .unwrap()
+        .0;
+
+    let (mut client_2, helper_2) = create_mock_client("test_2");
+    client_2.player.location.0 = location;
+    let client_ent_2 = app.world.spawn(client_2).id();
+
+    let services_key = RsaPrivateKey::from_pkcs8_der(SERVICES_KEY).unwrap();
+
+    app.insert_resource(ChatSessionSettings {
+        trusted_keys: Some(vec![services_key.to_public_key()]),
+        signed_chat,
+    });
+
+    app.update();
+
+    let mut clients = [(client_ent_1, helper_1), (client_ent_2, helper_2)];
+
+    for (_, helper) in &mut clients {
+        helper.clear_received();
+    }
+
+    (app, clients, services_key)
+}
+
+fn uuid_of(app: &App, client: Entity) -> Uuid {
+    app.world.get::<UniqueId>(client).unwrap().0
+}
+
+fn chat_events(app: &App) -> Vec<ChatMessageEvent> {
+    app.world
+        .resource::<Events<ChatMessageEvent>>()
+        .iter_current_update_events()
+        .cloned()
+        .collect()
+}
+
+/// Relays the chat message in `event` to `recipient` like a chat plugin would.
+fn relay(app: &mut App, event: &ChatMessageEvent, recipient: Entity) {
+    let sender = uuid_of(app, event.client);
+    let settings = app.world.resource::<ChatSessionSettings>().clone();
+
+    let mut query = app.world.query::<(&mut Client, &mut SignedChatState)>();
+    let (mut client, mut state) = query.get_mut(&mut app.world, recipient).unwrap();
+
+    write_player_chat(
+        &mut *client,
+        &mut state,
+        &settings,
+        &PlayerChat {
+            sender,
+            sender_name: Cow::Owned("test".into()),
+            message: &event.message,
+            timestamp: event.timestamp,
+            chat_type: ChatType::CHAT,
+            signature: &event.signature,
+        },
+    );
+}
+
+#[test]
+fn signed_chat_is_verified_and_relayed() {
+    let (mut app, [(client_1, mut helper_1), (client_2, mut helper_2)], services_key) =
+        setup(SignedChatMode::Preserve);
+
+    let mut signer_1 = SigningClient::new(uuid_of(&app, client_1));
+    signer_1.send_session(&mut helper_1, &services_key);
+
+    app.update();
+
+    // The session is sent to the other clients so they can verify messages.
+    {
+        let recvd = helper_2.collect_received();
+        let pkt = recvd.first::<PlayerListS2c>();
+
+        assert!(pkt.actions.initialize_chat());
+        assert_eq!(
+            pkt.entries[0].chat_data.as_ref().unwrap().session_id,
+            signer_1.session_id
+        );
+    }
+
+    let sent = signer_1.send_chat(&mut helper_1, "hello", vec![], 0, [0; 3]);
+
+    app.update();
+
+    let events = chat_events(&app);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].salt, sent.salt);
+    assert_eq!(
+        events[0].signature,
+        ChatSignature::Valid(Box::new(sent.clone()))
+    );
+
+    relay(&mut app, &events[0], client_2);
+    app.update();
+
+    {
+        let recvd = helper_2.collect_received();
+        let pkt = recvd.first::<ChatMessageS2c>();
+
+        assert_eq!(pkt.sender, signer_1.uuid);
+        assert_eq!(pkt.index.0, 0);
+        assert_eq!(pkt.message_signature, Some(&*sent.signature));
+        assert_eq!(pkt.message, "hello");
+    }
+
+    // The second client acknowledges the relayed message in its own message.
+    let mut signer_2 = SigningClient::new(uuid_of(&app, client_2));
+    signer_2.send_session(&mut helper_2, &services_key);
+
+    let reply = signer_2.send_chat(
+        &mut helper_2,
+        "hi",
+        vec![*sent.signature],
+        1,
+        [0, 0, 0b1000],
+    );
+
+    app.update();
+
+    let events = chat_events(&app);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].signature, ChatSignature::Valid(Box::new(reply)));
+}
+
+#[test]
+fn invalid_chat_signatures() {
+    let (mut app, [(client_1, mut helper_1), _], services_key) = setup(SignedChatMode::Preserve);
+
+    let mut signer = SigningClient::new(uuid_of(&app, client_1));
+
+    // Messages can't be signed without a session.
+    signer.send_chat(&mut helper_1, "no session", vec![], 0, [0; 3]);
+
+    // Sessions must be signed by a trusted key.
+    signer.send_session(&mut helper_1, &signer.key);
+    signer.send_chat(&mut helper_1, "untrusted", vec![], 0, [0; 3]);
+
+    signer.send_session(&mut helper_1, &services_key);
+    signer.next_index = 0;
+
+    // Acknowledging messages that were never sent.
+    signer.send_chat(&mut helper_1, "bad ack", vec![], 0, [0, 0, 0b1000]);
+
+    // A message signed with the wrong index.
+    signer.send_chat(&mut helper_1, "valid", vec![], 0, [0; 3]);
+    signer.next_index = 0;
+    signer.send_chat(&mut helper_1, "replayed", vec![], 0, [0; 3]);
+
+    helper_1.send(&ChatMessageC2s {
+        message: "unsigned",
+        timestamp: unix_millis(),
+        salt: 0,
+        signature: None,
+        message_count: VarInt(0),
+        acknowledgement: [0; 3],
+    });
+
+    app.update();
+
+    let signatures: Vec<_> = chat_events(&app)
+        .into_iter()
+        .map(|event| match event.signature {
+            ChatSignature::Valid(_) => Ok(()),
+            ChatSignature::Invalid(e) => Err(Some(e)),
+            ChatSignature::Unsigned => Err(None),
+        })
+        .collect();
+
+    assert_eq!(
+        signatures,
+        [
+            Err(Some(ChatSessionError::MissingSession)),
+            Err(Some(ChatSessionError::MissingSession)),
+            Err(Some(ChatSessionError::InvalidAcknowledgements)),
+            Ok(()),
+            Err(Some(ChatSessionError::InvalidSignature)),
+            Err(None),
+        ]
+    );
+}
+
+#[test]
+fn stripped_signatures() {
+    let (mut app, [(client_1, mut helper_1), (client_2, mut helper_2)], services_key) =
+        setup(SignedChatMode::Strip);
+
+    let mut signer = SigningClient::new(uuid_of(&app, client_1));
+    signer.send_session(&mut helper_1, &services_key);
+    signer.send_chat(&mut helper_1, "hello", vec![], 0, [0; 3]);
+
+    app.update();
+
+    let events = chat_events(&app);
+    assert!(events[0].signature.is_valid());
+
+    // Sessions are not shared with other clients.
+    helper_2.collect_received().assert_count::<PlayerListS2c>(0);
+
+    relay(&mut app, &events[0], client_2);
+    app.update();
+
+    let recvd = helper_2.collect_received();
+    let pkt = recvd.first::<ChatMessageS2c>();
+
+    assert_eq!(pkt.message_signature, None);
+    assert!(pkt.previous_messages.is_empty());
+    assert_eq!(pkt.message, "hello");
+}
+
+#[test]
+fn player_chat_and_system_messages() {
+    let (mut app, [(client_1, mut helper_1), (client_2, _)], _) = setup(SignedChatMode::Strip);
+
+    let sender = uuid_of(&app, client_2);
+    let mut client = app.world.get_mut::<Client>(client_1).unwrap();
+
+    client.send_chat_message("system");
+    client.send_player_chat(sender, ChatType::EMOTE_COMMAND, "test_2", "waves");
+    client.delete_chat_message(&[7; 256]);
+
+    app.update();
+
+    let recvd = helper_1.collect_received();
+
+    recvd.assert_count::<GameMessageS2c>(1);
+    recvd.assert_count::<ChatMessageS2c>(1);
+    recvd.assert_count::<RemoveMessageS2c>(1);
+    recvd.assert_order::<(GameMessageS2c, ChatMessageS2c, RemoveMessageS2c)>();
+
+    let pkt = recvd.first::<ChatMessageS2c>();
+    assert_eq!(pkt.sender, sender);
+    assert_eq!(pkt.chat_type, VarInt(1));
+    assert_eq!(pkt.message_signature, None);
+    assert_eq!(pkt.message, "waves");
+
+    let pkt = recvd.first::<RemoveMessageS2c>();
+    assert_eq!(pkt.signature.message_id, -1);
+    assert_eq!(pkt.signature.signature, Some(&[7; 256]));
+}
+
+#[test]
+fn enforced_secure_chat() {
+    let (mut app, [(client_1, mut helper_1), (client_2, mut helper_2)], services_key) =
+        setup(SignedChatMode::Enforce);
+
+    // Joining clients are told that secure chat is enforced.
+    let (mut client_3, mut helper_3) = create_mock_client("test_3");
+    client_3.player.location.0 = app
+        .world
+        .get::<valence_entity::Location>(client_1)
+        .unwrap()
+        .0;
+    app.world.spawn(client_3);
+    app.update();
+
+    assert!(
+        helper_3
+            .collect_received()
+            .first::<ServerMetadataS2c>()
+            .enforce_secure_chat
+    );
+
+    // Signed messages are accepted.
+    let mut signer = SigningClient::new(uuid_of(&app, client_1));
+    signer.send_session(&mut helper_1, &services_key);
+    signer.send_chat(&mut helper_1, "signed", vec![], 0, [0; 3]);
+
+    app.update();
+
+    assert_eq!(chat_events(&app).len(), 1);
+    assert!(app.world.get::<Client>(client_1).is_some());
+
+    // Unsigned messages are not.
+    helper_2.send(&ChatMessageC2s {
+        message: "unsigned",
+        timestamp: unix_millis(),
+        salt: 0,
+        signature: None,
+        message_count: VarInt(0),
+        acknowledgement: [0; 3],
+    });
+
+    helper_2.clear_received();
+    app.update();
+
+    assert!(chat_events(&app).is_empty());
+    assert!(app.world.get::<Client>(client_2).is_none());
+
+    let recvd = helper_2.collect_received();
+    assert_eq!(
+        recvd.first::<DisconnectS2c>().reason.as_ref(),
+        &Text::translate(translation_key::MULTIPLAYER_DISCONNECT_UNSIGNED_CHAT, [])
+    );
+}
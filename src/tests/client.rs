@@ -4,13 +4,24 @@ use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
 use bevy_ecs::world::EntityMut;
 use glam::DVec3;
+use valence_client::backpressure::{BackpressurePolicy, BackpressureSettings, PendingChunks};
+use valence_client::custom_payload::{
+    ClientPluginChannels, CustomPayloadC2s, CustomPayloadS2c, PluginChannels, PluginMessageEvent,
+    MAX_PLUGIN_MESSAGE_SIZE_S2C,
+};
 use valence_client::movement::FullC2s;
+use valence_client::packet::{BundleSplitterS2c, DisconnectS2c, GameJoinS2c};
 use valence_client::teleport::{PlayerPositionLookS2c, TeleportConfirmC2s};
-use valence_client::ViewDistance;
+use valence_client::{broadcast, Client, ViewDistance};
 use valence_core::chunk_pos::{ChunkPos, ChunkView};
+use valence_core::ident;
+use valence_core::protocol::var_int::VarInt;
 use valence_core::protocol::Packet;
 use valence_entity::cow::CowEntityBundle;
-use valence_entity::packet::{EntitiesDestroyS2c, EntitySpawnS2c, MoveRelativeS2c};
+use valence_entity::entity::NameVisible;
+use valence_entity::packet::{
+    EntitiesDestroyS2c, EntitySpawnS2c, EntityTrackerUpdateS2c, MoveRelativeS2c,
+};
 use valence_entity::{Location, Position};
 use valence_instance::chunk::UnloadedChunk;
 use valence_instance::packet::{ChunkDataS2c, UnloadChunkS2c};
@@ -255,6 +266,189 @@ fn entity_chunk_spawn_despawn() {
     }
 }
 
+#[test]
+fn entity_spawn_is_bundled() {
+    let mut app = App::new();
+
+    let (_, mut client_helper) = scenario_single_client(&mut app);
+
+    let (inst_ent, mut inst) = app
+        .world
+        .query::<(Entity, &mut Instance)>()
+        .single_mut(&mut app.world);
+
+    inst.insert_chunk([0, 0], UnloadedChunk::new());
+
+    app.update();
+    client_helper.clear_received();
+
+    // Spawn an entity with non-default tracked data.
+    app.world.spawn(CowEntityBundle {
+        position: Position::new([8.0, 0.0, 8.0]),
+        location: Location(inst_ent),
+        entity_name_visible: NameVisible(true),
+        ..Default::default()
+    });
+
+    app.update();
+
+    let ids: Vec<_> = client_helper
+        .collect_received()
+        .0
+        .iter()
+        .map(|f| f.id)
+        .collect();
+
+    let spawn = ids
+        .iter()
+        .position(|&id| id == EntitySpawnS2c::ID)
+        .expect("missing entity spawn packet");
+
+    // The spawn and metadata packets are surrounded by bundle delimiters.
+    assert_eq!(
+        ids[spawn - 1..spawn + 3],
+        [
+            BundleSplitterS2c::ID,
+            EntitySpawnS2c::ID,
+            EntityTrackerUpdateS2c::ID,
+            BundleSplitterS2c::ID
+        ]
+    );
+}
+
+#[test]
+fn slow_client_chunks_are_paused() {
+    let mut app = App::new();
+
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    // Any buffered data puts the client over the limit.
+    app.insert_resource(BackpressureSettings {
+        max_buffered_bytes: Some(1),
+        policy: BackpressurePolicy::PauseChunks,
+    });
+
+    let mut instance = app
+        .world
+        .query::<&mut Instance>()
+        .single_mut(&mut app.world);
+
+    for z in -10..10 {
+        for x in -10..20 {
+            instance.insert_chunk([x, z], UnloadedChunk::new());
+        }
+    }
+
+    let mut client = app.world.entity_mut(client_ent);
+    client.get_mut::<Position>().unwrap().set([8.0, 0.0, 8.0]);
+    client.get_mut::<ViewDistance>().unwrap().set(2);
+
+    let view = ChunkView::new(ChunkPos::new(0, 0), 2);
+    let view_len = view.iter().len();
+
+    // The join packets are still buffered when the view is loaded, so every chunk
+    // is deferred. The client doesn't read anything, so nothing is sent on the
+    // next tick either.
+    app.update();
+    app.update();
+
+    assert_eq!(
+        app.world.get::<PendingChunks>(client_ent).unwrap().len(),
+        view_len
+    );
+    assert!(
+        app.world
+            .get::<Client>(client_ent)
+            .unwrap()
+            .buffered_bytes()
+            > 0
+    );
+
+    let recvd = client_helper.collect_received();
+    recvd.assert_count::<GameJoinS2c>(1);
+    recvd.assert_count::<ChunkDataS2c>(0);
+
+    // With the buffer drained, one chunk is sent per tick, nearest first.
+    app.update();
+
+    let recvd = client_helper.collect_received();
+    recvd.assert_count::<ChunkDataS2c>(1);
+    assert_eq!(
+        recvd.first::<ChunkDataS2c>().pos,
+        ChunkPos::new(0, 0),
+        "nearest chunk should be sent first"
+    );
+
+    let mut loaded_chunks = BTreeSet::from([ChunkPos::new(0, 0)]);
+
+    // Moving while paused defers the chunks entering the view and forgets the
+    // pending chunks leaving it.
+    app.world
+        .get_mut::<Position>(client_ent)
+        .unwrap()
+        .set([8.0 + 16.0 * 7.0, 0.0, 8.0]);
+
+    app.update();
+
+    let view = ChunkView::new(ChunkPos::new(7, 0), 2);
+    let pending = app.world.get::<PendingChunks>(client_ent).unwrap();
+
+    assert!(pending.iter().all(|pos| view.contains(pos)));
+    assert_eq!(pending.len(), view_len);
+
+    let recvd = client_helper.collect_received();
+    recvd.assert_count::<ChunkDataS2c>(0);
+    recvd.assert_count::<UnloadChunkS2c>(1);
+    loaded_chunks.clear();
+
+    // Lifting the limit sends everything that's left.
+    app.insert_resource(BackpressureSettings::default());
+
+    app.update();
+
+    assert!(app
+        .world
+        .get::<PendingChunks>(client_ent)
+        .unwrap()
+        .is_empty());
+
+    for f in client_helper.collect_received().0 {
+        match f.id {
+            ChunkDataS2c::ID => {
+                let ChunkDataS2c { pos, .. } = f.decode().unwrap();
+                assert!(loaded_chunks.insert(pos), "({pos:?})");
+            }
+            UnloadChunkS2c::ID => {
+                let UnloadChunkS2c { pos } = f.decode().unwrap();
+                assert!(loaded_chunks.remove(&pos), "({pos:?})");
+            }
+            _ => {}
+        }
+    }
+
+    assert_eq!(loaded_chunks, view.iter().collect());
+}
+
+#[test]
+fn overloaded_client_is_disconnected() {
+    let mut app = App::new();
+
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    app.insert_resource(BackpressureSettings {
+        max_buffered_bytes: Some(1),
+        policy: BackpressurePolicy::Disconnect,
+    });
+
+    app.update();
+
+    assert!(app.world.get::<Client>(client_ent).is_none());
+
+    client_helper
+        .collect_received()
+        .assert_count::<DisconnectS2c>(1);
+}
+
 #[test]
 fn client_teleport_and_move() {
     let mut app = App::new();
@@ -305,3 +499,135 @@ fn client_teleport_and_move() {
         .collect_received()
         .assert_count::<MoveRelativeS2c>(1);
 }
+
+#[test]
+fn client_plugin_messages() {
+    let mut app = App::new();
+
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    app.world
+        .resource_mut::<PluginChannels>()
+        .register(ident!("valence:test").into());
+
+    app.update();
+
+    // The server's channels are registered after the client joins.
+    let frames = client_helper.collect_received();
+    frames.assert_order::<(GameJoinS2c, CustomPayloadS2c)>();
+
+    let pkt = frames.first::<CustomPayloadS2c>();
+    assert_eq!(pkt.channel, ident!("minecraft:register"));
+    assert_eq!(pkt.data.0, b"valence:test");
+
+    // The client registers its own channels and sends a message.
+    client_helper.send(&CustomPayloadC2s {
+        channel: ident!("minecraft:register").into(),
+        data: b"mod:a\0mod:b\0not a channel"[..].into(),
+    });
+    client_helper.send(&CustomPayloadC2s {
+        channel: ident!("valence:test").into(),
+        data: b"hello"[..].into(),
+    });
+
+    app.update();
+
+    let channels = app
+        .world
+        .get::<ClientPluginChannels>(client_ent)
+        .unwrap()
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>();
+
+    assert_eq!(channels, ["mod:a", "mod:b"]);
+
+    let events = app
+        .world
+        .resource::<Events<PluginMessageEvent>>()
+        .iter_current_update_events()
+        .collect::<Vec<_>>();
+
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[1].client, client_ent);
+    assert_eq!(events[1].channel, ident!("valence:test"));
+    assert_eq!(&events[1].data[..], b"hello");
+
+    // Changes to the server's channels are sent to connected clients.
+    let mut plugin_channels = app.world.resource_mut::<PluginChannels>();
+    plugin_channels.register(ident!("valence:other").into());
+    plugin_channels.unregister(ident!("valence:test"));
+
+    app.update();
+
+    let frames = client_helper.collect_received();
+    frames.assert_count::<CustomPayloadS2c>(2);
+
+    let payloads = frames
+        .0
+        .iter()
+        .filter_map(|f| f.decode::<CustomPayloadS2c>().ok())
+        .map(|pkt| (pkt.channel.to_string(), pkt.data.0.to_vec()))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        payloads,
+        [
+            ("minecraft:register".into(), b"valence:other".to_vec()),
+            ("minecraft:unregister".into(), b"valence:test".to_vec()),
+        ]
+    );
+
+    // Oversized messages are rejected.
+    let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+    let data = vec![0; MAX_PLUGIN_MESSAGE_SIZE_S2C + 1];
+
+    assert!(client
+        .send_plugin_message(ident!("valence:test"), &data)
+        .is_err());
+    assert!(client
+        .send_plugin_message(ident!("valence:test"), &data[1..])
+        .is_ok());
+}
+
+#[test]
+fn broadcast_shared_packet() {
+    let mut app = App::new();
+
+    let (_, mut helper_1) = scenario_single_client(&mut app);
+
+    let (mut bundle, mut helper_2) = create_mock_client("other");
+    bundle.player.location.0 = app
+        .world
+        .query_filtered::<Entity, With<Instance>>()
+        .single(&app.world);
+    app.world.spawn(bundle);
+
+    app.update();
+
+    helper_1.clear_received();
+    helper_2.clear_received();
+
+    let pkt = MoveRelativeS2c {
+        entity_id: VarInt(123),
+        delta: [1, 2, 3],
+        on_ground: true,
+    };
+
+    app.world
+        .resource_scope(|world, server: Mut<valence_core::Server>| {
+            let mut clients = world.query::<&mut Client>();
+            broadcast(&server, clients.iter_mut(world), &pkt);
+        });
+
+    app.update();
+
+    for helper in [&mut helper_1, &mut helper_2] {
+        let frames = helper.collect_received();
+        frames.assert_count::<MoveRelativeS2c>(1);
+
+        let received = frames.first::<MoveRelativeS2c>();
+        assert_eq!(received.entity_id, VarInt(123));
+        assert_eq!(received.delta, [1, 2, 3]);
+    }
+}
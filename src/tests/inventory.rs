@@ -1,18 +1,35 @@
 use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
+use valence_client::event_loop::EventLoopUpdate;
+use valence_client::packet::OpenWrittenBookS2c;
 use valence_core::game_mode::GameMode;
-use valence_core::item::{ItemKind, ItemStack};
+use valence_core::ident;
+use valence_core::item::{DamageResult, ItemKind, ItemStack};
 use valence_core::protocol::var_int::VarInt;
+use valence_core::protocol::Packet;
+use valence_inventory::anvil::{self, AnvilRenameEvent, PendingAnvilTakes};
+use valence_inventory::beacon::{self, BeaconBundle, BeaconSelectEvent};
+use valence_inventory::book::{BookReader, WrittenBook};
+use valence_inventory::bundle::Bundle;
+use valence_inventory::lectern::{self, LecternPageEvent, LecternTakeBookEvent};
 use valence_inventory::packet::{
-    ClickMode, ClickSlotC2s, CloseScreenS2c, CreativeInventoryActionC2s, InventoryS2c,
-    OpenScreenS2c, ScreenHandlerSlotUpdateS2c, SlotChange, UpdateSelectedSlotC2s,
+    ButtonClickC2s, ClickMode, ClickSlotC2s, CloseHandledScreenC2s, CloseScreenS2c,
+    CreativeInventoryActionC2s, InventoryS2c, OpenScreenS2c, RenameItemC2s,
+    ScreenHandlerPropertyUpdateS2c, ScreenHandlerSlotUpdateS2c, SlotChange, UpdateBeaconC2s,
+    UpdateSelectedSlotC2s, UpdateSelectedSlotS2c,
 };
+use valence_inventory::recipe::{
+    Ingredient, RecipeData, RecipeRegistry, SmithingTransformRecipe, StonecuttingRecipe,
+};
+use valence_inventory::stonecutter::{self, StonecutterSelectEvent};
 use valence_inventory::{
-    convert_to_player_slot_id, ClientInventoryState, CursorItem, DropItemStackEvent, HeldItem,
-    Inventory, InventoryKind, OpenInventory,
+    convert_to_player_slot_id, smithing, ClientInventoryState, ContainerProperties, CursorItem,
+    CursorItemOnClose, DropItemEvent, HeldItem, HeldItemChangeEvent, Inventory, InventoryKind,
+    InventorySettings, ItemBreakEvent, OpenInventory, PendingCreativeSetSlots,
 };
+use valence_nbt::{compound, List};
 
-use crate::testing::scenario_single_client;
+use crate::testing::{scenario_single_client, MockClientHelper, PacketFrames};
 
 #[test]
 fn test_should_open_inventory() {
@@ -43,6 +60,150 @@ fn test_should_open_inventory() {
     sent_packets.assert_order::<(OpenScreenS2c, InventoryS2c)>();
 }
 
+mod opening_containers {
+    use valence_inventory::packet::WindowType;
+
+    use super::*;
+
+    fn open(app: &mut App, client_ent: Entity, inventory: Inventory) -> Entity {
+        let inventory_ent = app.world.spawn(inventory).id();
+
+        app.world
+            .entity_mut(client_ent)
+            .insert(OpenInventory::new(inventory_ent));
+
+        inventory_ent
+    }
+
+    #[test]
+    fn open_each_kind() {
+        let containers = [
+            (Inventory::generic_9x(1), WindowType::Generic9x1, 9),
+            (Inventory::generic_9x(2), WindowType::Generic9x2, 18),
+            (Inventory::chest(), WindowType::Generic9x3, 27),
+            (Inventory::generic_9x(4), WindowType::Generic9x4, 36),
+            (Inventory::generic_9x(5), WindowType::Generic9x5, 45),
+            (Inventory::double_chest(), WindowType::Generic9x6, 54),
+            (Inventory::barrel(), WindowType::Generic9x3, 27),
+            (Inventory::ender_chest(), WindowType::Generic9x3, 27),
+            (Inventory::shulker_box(), WindowType::ShulkerBox, 27),
+            (Inventory::hopper(), WindowType::Hopper, 5),
+            (Inventory::dispenser(), WindowType::Generic3x3, 9),
+            (Inventory::dropper(), WindowType::Generic3x3, 9),
+        ];
+
+        for (inventory, window_type, slot_count) in containers {
+            let mut app = App::new();
+            let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+            app.update();
+            client_helper.clear_received();
+
+            let title = inventory.title().clone();
+            open(&mut app, client_ent, inventory);
+
+            app.update();
+
+            let sent_packets = client_helper.collect_received();
+            sent_packets.assert_count::<OpenScreenS2c>(1);
+
+            let pkt = sent_packets.first::<OpenScreenS2c>();
+            assert_eq!(pkt.window_type, window_type);
+            assert_eq!(pkt.window_title.into_owned(), title);
+
+            // The window also shows the player's main inventory.
+            let pkt = sent_packets.first::<InventoryS2c>();
+            assert_eq!(pkt.slots.len(), slot_count + 36);
+        }
+    }
+
+    #[test]
+    fn player_inventory_is_not_opened() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+        client_helper.clear_received();
+
+        open(&mut app, client_ent, Inventory::new(InventoryKind::Player));
+
+        app.update();
+
+        let sent_packets = client_helper.collect_received();
+        sent_packets.assert_count::<OpenScreenS2c>(0);
+        sent_packets.assert_count::<InventoryS2c>(0);
+
+        assert!(app.world.get::<OpenInventory>(client_ent).is_none());
+    }
+
+    #[test]
+    fn shift_click_into_hopper() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+
+        let mut hopper = Inventory::hopper();
+        hopper.set_slot(0, ItemStack::new(ItemKind::Dirt, 64, None));
+        hopper.set_slot(1, ItemStack::new(ItemKind::Stone, 60, None));
+        let hopper_ent = open(&mut app, client_ent, hopper);
+
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .set_slot(9, ItemStack::new(ItemKind::Stone, 10, None));
+
+        app.update();
+        client_helper.clear_received();
+
+        // Slot 9 of the player's inventory is the first slot after the hopper.
+        send_window_click(
+            &mut app,
+            client_ent,
+            &mut client_helper,
+            5,
+            ClickMode::ShiftClick,
+            vec![
+                SlotChange {
+                    idx: 1,
+                    item: Some(ItemStack::new(ItemKind::Stone, 64, None)),
+                },
+                SlotChange {
+                    idx: 2,
+                    item: Some(ItemStack::new(ItemKind::Stone, 6, None)),
+                },
+                SlotChange { idx: 5, item: None },
+            ],
+            None,
+        );
+
+        app.update();
+
+        let hopper = app.world.get::<Inventory>(hopper_ent).unwrap();
+        assert_eq!(
+            hopper.slot(1),
+            Some(&ItemStack::new(ItemKind::Stone, 64, None))
+        );
+        assert_eq!(
+            hopper.slot(2),
+            Some(&ItemStack::new(ItemKind::Stone, 6, None))
+        );
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(9), None);
+
+        let sent_packets = client_helper.collect_received();
+        sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(0);
+        sent_packets.assert_count::<InventoryS2c>(0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_many_rows() {
+        Inventory::generic_9x(7);
+    }
+}
+
 #[test]
 fn test_should_close_inventory() {
     let mut app = App::new();
@@ -79,981 +240,4704 @@ fn test_should_close_inventory() {
     sent_packets.assert_count::<CloseScreenS2c>(1);
 }
 
+/// Returns the `(property, value)` pairs of the property updates that were
+/// sent, in order.
+fn sent_properties(sent_packets: &PacketFrames) -> Vec<(i16, i16)> {
+    sent_packets
+        .0
+        .iter()
+        .filter(|f| f.id == ScreenHandlerPropertyUpdateS2c::ID)
+        .map(|f| {
+            let pkt = f.decode::<ScreenHandlerPropertyUpdateS2c>().unwrap();
+            (pkt.property, pkt.value)
+        })
+        .collect()
+}
+
 #[test]
-fn test_should_remove_invalid_open_inventory() {
+fn container_properties_sent_when_changed() {
     let mut app = App::new();
     let (client_ent, mut client_helper) = scenario_single_client(&mut app);
 
-    let inventory = Inventory::new(InventoryKind::Generic3x3);
-    let inventory_ent = app.world.spawn(inventory).id();
+    let inventory_ent = app
+        .world
+        .spawn((
+            Inventory::new(InventoryKind::Furnace),
+            ContainerProperties::new(InventoryKind::Furnace),
+        ))
+        .id();
 
     // Process a tick to get past the "on join" logic.
     app.update();
     client_helper.clear_received();
 
-    // Open the inventory.
-    let open_inventory = OpenInventory::new(inventory_ent);
     app.world
-        .get_entity_mut(client_ent)
-        .expect("could not find client")
-        .insert(open_inventory);
+        .entity_mut(client_ent)
+        .insert(OpenInventory::new(inventory_ent));
 
     app.update();
-    client_helper.clear_received();
 
-    // Remove the inventory.
-    app.world.despawn(inventory_ent);
+    // All properties are sent when the inventory is opened.
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_order::<(OpenScreenS2c, InventoryS2c, ScreenHandlerPropertyUpdateS2c)>();
+    assert_eq!(
+        sent_properties(&sent_packets),
+        [(0, 0), (1, 0), (2, 0), (3, 0)]
+    );
+
+    app.world
+        .get_mut::<ContainerProperties>(inventory_ent)
+        .unwrap()
+        .set_furnace_progress(0, 200);
 
     app.update();
 
-    // Make assertions
-    assert!(app.world.get::<OpenInventory>(client_ent).is_none());
+    // Only the changed property is sent.
+    let sent_packets = client_helper.collect_received();
+    assert_eq!(sent_properties(&sent_packets), [(3, 200)]);
+
+    for progress in (20..=200).step_by(20) {
+        app.world
+            .get_mut::<ContainerProperties>(inventory_ent)
+            .unwrap()
+            .set_furnace_progress(progress, 200);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_received();
+        assert_eq!(sent_properties(&sent_packets), [(2, progress)]);
+    }
+
+    // Setting a property to its current value sends nothing.
+    app.world
+        .get_mut::<ContainerProperties>(inventory_ent)
+        .unwrap()
+        .set_furnace_progress(200, 200);
+
+    app.update();
 
     let sent_packets = client_helper.collect_received();
-    sent_packets.assert_count::<CloseScreenS2c>(1);
+    sent_packets.assert_count::<ScreenHandlerPropertyUpdateS2c>(0);
 }
 
 #[test]
-fn test_should_modify_player_inventory_click_slot() {
+fn container_properties_resent_on_reopen() {
     let mut app = App::new();
     let (client_ent, mut client_helper) = scenario_single_client(&mut app);
 
-    // Process a tick to get past the "on join" logic.
-    app.update();
-    client_helper.clear_received();
+    let mut properties = ContainerProperties::new(InventoryKind::BrewingStand);
+    properties.set_brewing_time(300);
+    properties.set_brewing_fuel(20);
 
-    let mut inventory = app
+    let inventory_ent = app
         .world
-        .get_mut::<Inventory>(client_ent)
-        .expect("could not find inventory for client");
-    inventory.set_slot(20, ItemStack::new(ItemKind::Diamond, 2, None));
+        .spawn((Inventory::new(InventoryKind::BrewingStand), properties))
+        .id();
 
-    // Make the client click the slot and pick up the item.
-    let state_id = app
-        .world
-        .get::<ClientInventoryState>(client_ent)
-        .unwrap()
-        .state_id();
+    app.update();
 
-    client_helper.send(&ClickSlotC2s {
-        window_id: 0,
-        button: 0,
-        mode: ClickMode::Click,
-        state_id: VarInt(state_id.0),
-        slot_idx: 20,
-        slot_changes: vec![SlotChange {
-            idx: 20,
-            item: None,
-        }],
-        carried_item: Some(ItemStack::new(ItemKind::Diamond, 2, None)),
-    });
+    app.world
+        .entity_mut(client_ent)
+        .insert(OpenInventory::new(inventory_ent));
 
     app.update();
 
-    // Make assertions
-    let sent_packets = client_helper.collect_received();
-
-    // because the inventory was changed as a result of the client's click, the
-    // server should not send any packets to the client because the client
-    // already knows about the change.
-    sent_packets.assert_count::<InventoryS2c>(0);
-    sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(0);
+    app.world.entity_mut(client_ent).remove::<OpenInventory>();
 
-    let inventory = app
-        .world
-        .get::<Inventory>(client_ent)
-        .expect("could not find inventory for client");
+    app.update();
+    client_helper.clear_received();
 
-    assert_eq!(inventory.slot(20), None);
+    app.world
+        .entity_mut(client_ent)
+        .insert(OpenInventory::new(inventory_ent));
 
-    let cursor_item = app
-        .world
-        .get::<CursorItem>(client_ent)
-        .expect("could not find client");
+    app.update();
 
-    assert_eq!(
-        cursor_item.0,
-        Some(ItemStack::new(ItemKind::Diamond, 2, None))
-    );
+    let sent_packets = client_helper.collect_received();
+    assert_eq!(sent_properties(&sent_packets), [(0, 300), (1, 20)]);
 }
 
+/// Opens an anvil for the client and returns the anvil's entity.
 #[test]
-fn test_should_modify_player_inventory_server_side() {
+fn raw_properties_sent_after_open() {
     let mut app = App::new();
     let (client_ent, mut client_helper) = scenario_single_client(&mut app);
 
-    // Process a tick to get past the "on join" logic.
-    app.update();
-
-    let mut inventory = app
-        .world
-        .get_mut::<Inventory>(client_ent)
-        .expect("could not find inventory for client");
-    inventory.set_slot(20, ItemStack::new(ItemKind::Diamond, 2, None));
-
     app.update();
     client_helper.clear_received();
 
-    // Modify the inventory.
-    let mut inventory = app
+    let inventory_ent = app
         .world
-        .get_mut::<Inventory>(client_ent)
-        .expect("could not find inventory for client");
-    inventory.set_slot(21, ItemStack::new(ItemKind::IronIngot, 1, None));
+        .spawn(Inventory::new(InventoryKind::Enchantment))
+        .id();
+
+    // The property is set before the window is opened.
+    let mut open_inventory = OpenInventory::new(inventory_ent);
+    open_inventory.set_property(3, 1234);
+    app.world.entity_mut(client_ent).insert(open_inventory);
 
     app.update();
 
-    // Make assertions
     let sent_packets = client_helper.collect_received();
-    // because the inventory was modified server side, the client needs to be
-    // updated with the change.
-    sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(1);
+    sent_packets.assert_order::<(OpenScreenS2c, InventoryS2c, ScreenHandlerPropertyUpdateS2c)>();
+    assert_eq!(sent_properties(&sent_packets), [(3, 1234)]);
+
+    let open_inventory = app.world.get::<OpenInventory>(client_ent).unwrap();
+    assert_eq!(open_inventory.sent_property(3), Some(1234));
+    assert_eq!(open_inventory.sent_property(4), None);
 }
 
 #[test]
-fn test_should_sync_entire_player_inventory() {
+fn raw_properties_are_not_resent() {
     let mut app = App::new();
     let (client_ent, mut client_helper) = scenario_single_client(&mut app);
 
-    // Process a tick to get past the "on join" logic.
+    let mut properties = ContainerProperties::new(InventoryKind::Furnace);
+    properties.set_furnace_progress(50, 200);
+
+    let inventory_ent = app
+        .world
+        .spawn((Inventory::new(InventoryKind::Furnace), properties))
+        .id();
+
+    app.world
+        .entity_mut(client_ent)
+        .insert(OpenInventory::new(inventory_ent));
+
     app.update();
     client_helper.clear_received();
 
-    let mut inventory = app
-        .world
-        .get_mut::<Inventory>(client_ent)
-        .expect("could not find inventory for client");
-    inventory.changed = u64::MAX;
+    // The properties of the inventory were sent when it was opened.
+    let mut open_inventory = app.world.get_mut::<OpenInventory>(client_ent).unwrap();
+    assert_eq!(open_inventory.sent_property(2), Some(50));
+
+    open_inventory.set_property(2, 50);
+    open_inventory.set_property(0, 10);
+    open_inventory.set_property(0, 20);
 
     app.update();
 
-    // Make assertions
+    // Only the last value is sent, and only if the client doesn't have it yet.
     let sent_packets = client_helper.collect_received();
-    sent_packets.assert_count::<InventoryS2c>(1);
-}
+    assert_eq!(sent_properties(&sent_packets), [(0, 20)]);
 
-fn set_up_open_inventory(app: &mut App, client_ent: Entity) -> Entity {
-    let inventory = Inventory::new(InventoryKind::Generic9x3);
-    let inventory_ent = app.world.spawn(inventory).id();
+    // The properties for the client are sent after the changes to the
+    // inventory's properties.
+    app.world
+        .get_mut::<ContainerProperties>(inventory_ent)
+        .unwrap()
+        .set_furnace_progress(60, 200);
 
-    // Open the inventory.
-    let open_inventory = OpenInventory::new(inventory_ent);
     app.world
-        .get_entity_mut(client_ent)
-        .expect("could not find client")
-        .insert(open_inventory);
+        .get_mut::<OpenInventory>(client_ent)
+        .unwrap()
+        .set_property(2, 70);
 
-    inventory_ent
+    app.update();
+
+    let sent_packets = client_helper.collect_received();
+    assert_eq!(sent_properties(&sent_packets), [(2, 60), (2, 70)]);
 }
 
-#[test]
-fn test_should_modify_open_inventory_click_slot() {
-    let mut app = App::new();
-    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
-    let inventory_ent = set_up_open_inventory(&mut app, client_ent);
-    let mut inventory = app
+fn open_anvil(app: &mut App, client_ent: Entity) -> Entity {
+    let anvil_ent = app
         .world
-        .get_mut::<Inventory>(inventory_ent)
-        .expect("could not find inventory for client");
-    inventory.set_slot(20, ItemStack::new(ItemKind::Diamond, 2, None));
+        .spawn((
+            Inventory::new(InventoryKind::Anvil),
+            ContainerProperties::new(InventoryKind::Anvil),
+        ))
+        .id();
+
+    app.world
+        .entity_mut(client_ent)
+        .insert(OpenInventory::new(anvil_ent));
 
-    // Process a tick to get past the "on join" logic.
     app.update();
-    client_helper.clear_received();
 
-    // Make the client click the slot and pick up the item.
+    anvil_ent
+}
+
+fn send_window_click(
+    app: &mut App,
+    client_ent: Entity,
+    client_helper: &mut MockClientHelper,
+    slot_idx: i16,
+    mode: ClickMode,
+    slot_changes: Vec<SlotChange>,
+    carried_item: Option<ItemStack>,
+) {
     let inv_state = app.world.get::<ClientInventoryState>(client_ent).unwrap();
-    let state_id = inv_state.state_id();
-    let window_id = inv_state.window_id();
+
     client_helper.send(&ClickSlotC2s {
-        window_id,
-        state_id: VarInt(state_id.0),
-        slot_idx: 20,
+        window_id: inv_state.window_id(),
+        state_id: VarInt(inv_state.state_id().0),
+        slot_idx,
         button: 0,
-        mode: ClickMode::Click,
-        slot_changes: vec![SlotChange {
-            idx: 20,
-            item: None,
-        }],
-        carried_item: Some(ItemStack::new(ItemKind::Diamond, 2, None)),
+        mode,
+        slot_changes,
+        carried_item,
     });
-
-    app.update();
-
-    // Make assertions
-    let sent_packets = client_helper.collect_received();
-
-    // because the inventory was modified as a result of the client's click, the
-    // server should not send any packets to the client because the client
-    // already knows about the change.
-    sent_packets.assert_count::<InventoryS2c>(0);
-    sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(0);
-
-    let inventory = app
-        .world
-        .get::<Inventory>(inventory_ent)
-        .expect("could not find inventory");
-    assert_eq!(inventory.slot(20), None);
-    let cursor_item = app
-        .world
-        .get::<CursorItem>(client_ent)
-        .expect("could not find client");
-    assert_eq!(
-        cursor_item.0,
-        Some(ItemStack::new(ItemKind::Diamond, 2, None))
-    );
 }
 
 #[test]
-fn test_should_modify_open_inventory_server_side() {
+fn anvil_rename_and_take_result() {
+    #[derive(Resource, Default)]
+    struct LevelsTaken(i16);
+
     let mut app = App::new();
     let (client_ent, mut client_helper) = scenario_single_client(&mut app);
-    let inventory_ent = set_up_open_inventory(&mut app, client_ent);
 
-    // Process a tick to get past the "on join" logic.
-    app.update();
-    client_helper.clear_received();
+    app.init_resource::<LevelsTaken>();
+    app.add_systems(
+        EventLoopUpdate,
+        |mut pending: ResMut<PendingAnvilTakes>, mut taken: ResMut<LevelsTaken>| {
+            for event in &mut pending.0 {
+                taken.0 += event.level_cost;
+            }
+        },
+    );
 
-    // Modify the inventory.
-    let mut inventory = app
-        .world
-        .get_mut::<Inventory>(inventory_ent)
-        .expect("could not find inventory for client");
-    inventory.set_slot(5, ItemStack::new(ItemKind::IronIngot, 1, None));
+    // Rename the anvil's output whenever the client types a name.
+    app.add_systems(
+        EventLoopUpdate,
+        |mut events: EventReader<AnvilRenameEvent>,
+         mut anvils: Query<(&mut Inventory, &mut ContainerProperties)>| {
+            for event in events.iter() {
+                let (mut anvil, mut properties) = anvils.get_mut(event.inventory).unwrap();
+
+                let output = anvil
+                    .slot(anvil::FIRST_INPUT_SLOT)
+                    .zip(event.name.clone())
+                    .map(|(input, name)| input.clone().with_name(name));
+
+                properties.set_anvil_repair_cost(if output.is_some() { 1 } else { 0 });
+                anvil.set_slot(anvil::OUTPUT_SLOT, output);
+            }
+        },
+    );
 
     app.update();
 
-    // Make assertions
-    let sent_packets = client_helper.collect_received();
+    let anvil_ent = open_anvil(&mut app, client_ent);
 
-    // because the inventory was modified server side, the client needs to be
-    // updated with the change.
-    sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(1);
+    let sword = ItemStack::new(ItemKind::DiamondSword, 1, None);
+    app.world.get_mut::<CursorItem>(client_ent).unwrap().0 = Some(sword.clone());
 
-    let inventory = app
-        .world
-        .get::<Inventory>(inventory_ent)
-        .expect("could not find inventory for client");
+    app.update();
 
-    assert_eq!(
-        inventory.slot(5),
-        Some(&ItemStack::new(ItemKind::IronIngot, 1, None))
+    // Place the sword in the first input slot.
+    send_window_click(
+        &mut app,
+        client_ent,
+        &mut client_helper,
+        0,
+        ClickMode::Click,
+        vec![SlotChange {
+            idx: 0,
+            item: Some(sword.clone()),
+        }],
+        None,
     );
-}
 
-#[test]
-fn test_should_sync_entire_open_inventory() {
-    let mut app = App::new();
-    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
-    let inventory_ent = set_up_open_inventory(&mut app, client_ent);
+    app.update();
+
+    let anvil = app.world.get::<Inventory>(anvil_ent).unwrap();
+    assert_eq!(anvil.slot(anvil::FIRST_INPUT_SLOT), Some(&sword));
+
+    // Type a name.
+    client_helper.send(&RenameItemC2s {
+        item_name: "Excalibur",
+    });
 
-    // Process a tick to get past the "on join" logic.
     app.update();
+
+    let renamed = sword.clone().with_name("Excalibur");
+
+    let anvil = app.world.get::<Inventory>(anvil_ent).unwrap();
+    assert_eq!(anvil.slot(anvil::OUTPUT_SLOT), Some(&renamed));
+
+    let properties = app.world.get::<ContainerProperties>(anvil_ent).unwrap();
+    assert_eq!(properties.get(0), Some(1));
+
+    // Take the result.
     client_helper.clear_received();
 
-    let mut inventory = app
-        .world
-        .get_mut::<Inventory>(inventory_ent)
-        .expect("could not find inventory");
-    inventory.changed = u64::MAX;
+    send_window_click(
+        &mut app,
+        client_ent,
+        &mut client_helper,
+        anvil::OUTPUT_SLOT as i16,
+        ClickMode::Click,
+        vec![
+            SlotChange { idx: 0, item: None },
+            SlotChange { idx: 2, item: None },
+        ],
+        Some(renamed.clone()),
+    );
 
     app.update();
 
-    // Make assertions
+    assert_eq!(app.world.resource::<LevelsTaken>().0, 1);
+
+    let cursor_item = app.world.get::<CursorItem>(client_ent).unwrap();
+    assert_eq!(cursor_item.0, Some(renamed));
+
+    let anvil = app.world.get::<Inventory>(anvil_ent).unwrap();
+    assert_eq!(anvil.slot(anvil::FIRST_INPUT_SLOT), None);
+    assert_eq!(anvil.slot(anvil::OUTPUT_SLOT), None);
+
+    let properties = app.world.get::<ContainerProperties>(anvil_ent).unwrap();
+    assert_eq!(properties.get(0), Some(0));
+
     let sent_packets = client_helper.collect_received();
-    sent_packets.assert_count::<InventoryS2c>(1);
+    sent_packets.assert_count::<InventoryS2c>(0);
+    assert_eq!(sent_properties(&sent_packets), [(0, 0)]);
 }
 
 #[test]
-fn test_set_creative_mode_slot_handling() {
+fn anvil_take_result_cancelled() {
     let mut app = App::new();
     let (client_ent, mut client_helper) = scenario_single_client(&mut app);
-    let mut game_mode = app
-        .world
-        .get_mut::<GameMode>(client_ent)
-        .expect("could not find client");
-    *game_mode.as_mut() = GameMode::Creative;
-
-    // Process a tick to get past the "on join" logic.
-    app.update();
-    client_helper.clear_received();
 
-    client_helper.send(&CreativeInventoryActionC2s {
-        slot: 36,
-        clicked_item: Some(ItemStack::new(ItemKind::Diamond, 2, None)),
+    app.add_systems(EventLoopUpdate, |mut pending: ResMut<PendingAnvilTakes>| {
+        for event in &mut pending.0 {
+            event.cancel();
+        }
     });
 
     app.update();
 
-    // Make assertions
-    let inventory = app
-        .world
-        .get::<Inventory>(client_ent)
-        .expect("could not find inventory for client");
+    let anvil_ent = open_anvil(&mut app, client_ent);
 
-    assert_eq!(
-        inventory.slot(36),
-        Some(&ItemStack::new(ItemKind::Diamond, 2, None))
-    );
-}
+    let sword = ItemStack::new(ItemKind::DiamondSword, 1, None);
+    let renamed = sword.clone().with_name("Excalibur");
 
-#[test]
-fn test_ignore_set_creative_mode_slot_if_not_creative() {
-    let mut app = App::new();
-    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
-    let mut game_mode = app
-        .world
-        .get_mut::<GameMode>(client_ent)
-        .expect("could not find client");
-    *game_mode.as_mut() = GameMode::Survival;
+    let mut anvil = app.world.get_mut::<Inventory>(anvil_ent).unwrap();
+    anvil.set_slot(anvil::FIRST_INPUT_SLOT, sword.clone());
+    anvil.set_slot(anvil::OUTPUT_SLOT, renamed.clone());
+
+    app.world
+        .get_mut::<ContainerProperties>(anvil_ent)
+        .unwrap()
+        .set_anvil_repair_cost(1);
 
-    // Process a tick to get past the "on join" logic.
     app.update();
     client_helper.clear_received();
 
-    client_helper.send(&CreativeInventoryActionC2s {
-        slot: 36,
-        clicked_item: Some(ItemStack::new(ItemKind::Diamond, 2, None)),
-    });
+    // Shift click the result into the hotbar.
+    send_window_click(
+        &mut app,
+        client_ent,
+        &mut client_helper,
+        anvil::OUTPUT_SLOT as i16,
+        ClickMode::ShiftClick,
+        vec![
+            SlotChange { idx: 0, item: None },
+            SlotChange { idx: 2, item: None },
+            SlotChange {
+                idx: 38,
+                item: Some(renamed.clone()),
+            },
+        ],
+        None,
+    );
 
     app.update();
 
-    // Make assertions
-    let inventory = app
-        .world
-        .get::<Inventory>(client_ent)
-        .expect("could not find inventory for client");
-    assert_eq!(inventory.slot(36), None);
+    let anvil = app.world.get::<Inventory>(anvil_ent).unwrap();
+    assert_eq!(anvil.slot(anvil::FIRST_INPUT_SLOT), Some(&sword));
+    assert_eq!(anvil.slot(anvil::OUTPUT_SLOT), Some(&renamed));
+
+    let player_inventory = app.world.get::<Inventory>(client_ent).unwrap();
+    assert_eq!(player_inventory.slot(44), None);
+
+    // The client is told what is actually in the slots.
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<InventoryS2c>(1);
+
+    let pkt = sent_packets.first::<ScreenHandlerSlotUpdateS2c>();
+    assert_eq!(pkt.slot_idx, 38);
+    assert_eq!(pkt.slot_data.as_ref(), &None);
 }
 
 #[test]
-fn test_window_id_increments() {
+fn anvil_result_cannot_be_dropped() {
     let mut app = App::new();
     let (client_ent, mut client_helper) = scenario_single_client(&mut app);
-    let inventory = Inventory::new(InventoryKind::Generic9x3);
-    let inventory_ent = app.world.spawn(inventory).id();
 
-    // Process a tick to get past the "on join" logic.
+    app.update();
+
+    let anvil_ent = open_anvil(&mut app, client_ent);
+
+    let sword = ItemStack::new(ItemKind::DiamondSword, 1, None);
+    let renamed = sword.clone().with_name("Excalibur");
+
+    let mut anvil = app.world.get_mut::<Inventory>(anvil_ent).unwrap();
+    anvil.set_slot(anvil::FIRST_INPUT_SLOT, sword.clone());
+    anvil.set_slot(anvil::OUTPUT_SLOT, renamed.clone());
+
+    app.world
+        .get_mut::<ContainerProperties>(anvil_ent)
+        .unwrap()
+        .set_anvil_repair_cost(1);
+
     app.update();
     client_helper.clear_received();
 
-    for _ in 0..3 {
-        let open_inventory = OpenInventory::new(inventory_ent);
-        app.world
-            .get_entity_mut(client_ent)
-            .expect("could not find client")
-            .insert(open_inventory);
+    // The drop key would drop the result without paying for it.
+    send_window_click(
+        &mut app,
+        client_ent,
+        &mut client_helper,
+        anvil::OUTPUT_SLOT as i16,
+        ClickMode::DropKey,
+        vec![SlotChange {
+            idx: anvil::OUTPUT_SLOT as i16,
+            item: None,
+        }],
+        None,
+    );
 
-        app.update();
+    app.update();
 
-        app.world
-            .get_entity_mut(client_ent)
-            .expect("could not find client")
-            .remove::<OpenInventory>();
+    let events = app.world.resource::<Events<DropItemEvent>>();
+    assert_eq!(events.iter_current_update_events().count(), 0);
 
-        app.update();
-    }
+    let anvil = app.world.get::<Inventory>(anvil_ent).unwrap();
+    assert_eq!(anvil.slot(anvil::FIRST_INPUT_SLOT), Some(&sword));
+    assert_eq!(anvil.slot(anvil::OUTPUT_SLOT), Some(&renamed));
 
-    // Make assertions
-    let inv_state = app
-        .world
-        .get::<ClientInventoryState>(client_ent)
-        .expect("could not find client");
-    assert_eq!(inv_state.window_id(), 3);
+    // The client is told what is actually in the anvil.
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<InventoryS2c>(1);
 }
 
 #[test]
-fn test_should_handle_set_held_item() {
+fn anvil_rename_text() {
     let mut app = App::new();
     let (client_ent, mut client_helper) = scenario_single_client(&mut app);
 
-    // Process a tick to get past the "on join" logic.
     app.update();
-    client_helper.clear_received();
 
-    client_helper.send(&UpdateSelectedSlotC2s { slot: 4 });
+    let anvil_ent = open_anvil(&mut app, client_ent);
+
+    let names = [
+        "§cRed\x7f",
+        "   ",
+        "",
+        &"a".repeat(50),
+        &"a".repeat(51),
+        "Excalibur",
+    ];
+
+    for item_name in names {
+        client_helper.send(&RenameItemC2s { item_name });
+    }
 
     app.update();
 
-    // Make assertions
-    let held = app
-        .world
-        .get::<HeldItem>(client_ent)
-        .expect("could not find client");
+    let events = app.world.resource::<Events<AnvilRenameEvent>>();
+    let names: Vec<_> = events
+        .iter_current_update_events()
+        .inspect(|e| {
+            assert_eq!(e.client, client_ent);
+            assert_eq!(e.inventory, anvil_ent);
+        })
+        .map(|e| e.name.as_deref())
+        .collect();
 
-    assert_eq!(held.slot(), 40);
+    assert_eq!(
+        names,
+        [
+            Some("cRed"),
+            None,
+            None,
+            Some("a".repeat(50).as_str()),
+            Some("Excalibur")
+        ]
+    );
 }
 
 #[test]
-fn should_not_increment_state_id_on_cursor_item_change() {
+fn beacon_select_effects() {
     let mut app = App::new();
     let (client_ent, mut client_helper) = scenario_single_client(&mut app);
 
-    // Process a tick to get past the "on join" logic.
     app.update();
-    client_helper.clear_received();
 
-    let inv_state = app
-        .world
-        .get::<ClientInventoryState>(client_ent)
-        .expect("could not find client");
-    let expected_state_id = inv_state.state_id().0;
+    let beacon_ent = app.world.spawn(BeaconBundle::new(1)).id();
 
-    let mut cursor_item = app.world.get_mut::<CursorItem>(client_ent).unwrap();
-    cursor_item.0 = Some(ItemStack::new(ItemKind::Diamond, 2, None));
+    app.world
+        .entity_mut(client_ent)
+        .insert(OpenInventory::new(beacon_ent));
+
+    let iron = ItemStack::new(ItemKind::IronIngot, 2, None);
+    app.world.get_mut::<CursorItem>(client_ent).unwrap().0 = Some(iron.clone());
 
     app.update();
 
-    // Make assertions
-    let inv_state = app
-        .world
-        .get::<ClientInventoryState>(client_ent)
-        .expect("could not find client");
-    assert_eq!(
-        inv_state.state_id().0,
-        expected_state_id,
-        "state id should not have changed"
+    // The power level and the lack of effects are shown to the client.
+    let sent_packets = client_helper.collect_received();
+    assert_eq!(sent_properties(&sent_packets), [(0, 1), (1, -1), (2, -1)]);
+
+    // Pay with the iron.
+    send_window_click(
+        &mut app,
+        client_ent,
+        &mut client_helper,
+        beacon::PAYMENT_SLOT as i16,
+        ClickMode::Click,
+        vec![SlotChange {
+            idx: beacon::PAYMENT_SLOT as i16,
+            item: Some(iron),
+        }],
+        None,
     );
-}
 
-mod dropping_items {
-    use valence_client::packet::{PlayerAction, PlayerActionC2s};
-    use valence_core::block_pos::BlockPos;
-    use valence_core::direction::Direction;
-    use valence_inventory::convert_to_player_slot_id;
+    app.update();
 
-    use super::*;
+    // Slowness can't be chosen in a beacon.
+    client_helper.send(&UpdateBeaconC2s {
+        primary_effect: Some(VarInt(2)),
+        secondary_effect: None,
+    });
 
-    #[test]
-    fn should_drop_item_player_action() {
-        let mut app = App::new();
-        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+    // Choose speed.
+    client_helper.send(&UpdateBeaconC2s {
+        primary_effect: Some(VarInt(1)),
+        secondary_effect: None,
+    });
 
-        // Process a tick to get past the "on join" logic.
-        app.update();
-        client_helper.clear_received();
+    app.update();
 
-        let mut inventory = app
-            .world
-            .get_mut::<Inventory>(client_ent)
-            .expect("could not find inventory");
-        inventory.set_slot(36, ItemStack::new(ItemKind::IronIngot, 3, None));
+    let events = app.world.resource::<Events<BeaconSelectEvent>>();
+    let events: Vec<_> = events.iter_current_update_events().collect();
 
-        client_helper.send(&PlayerActionC2s {
-            action: PlayerAction::DropItem,
-            position: BlockPos::new(0, 0, 0),
-            direction: Direction::Down,
-            sequence: VarInt(0),
-        });
+    assert_eq!(
+        events,
+        [&BeaconSelectEvent {
+            client: client_ent,
+            inventory: beacon_ent,
+            primary: Some(ident!("speed")),
+            secondary: None,
+        }]
+    );
 
-        app.update();
+    // Only one item is paid.
+    let beacon = app.world.get::<Inventory>(beacon_ent).unwrap();
+    assert_eq!(
+        beacon.slot(beacon::PAYMENT_SLOT),
+        Some(&ItemStack::new(ItemKind::IronIngot, 1, None))
+    );
 
-        // Make assertions
-        let inventory = app
-            .world
-            .get::<Inventory>(client_ent)
-            .expect("could not find client");
+    let properties = app.world.get::<ContainerProperties>(beacon_ent).unwrap();
+    assert_eq!(properties.get(1), Some(1));
+    assert_eq!(properties.get(2), Some(-1));
+}
 
-        assert_eq!(
-            inventory.slot(36),
-            Some(&ItemStack::new(ItemKind::IronIngot, 2, None))
+#[test]
+fn stonecutter_select_and_take_result() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    let mut registry = app.world.resource_mut::<RecipeRegistry>();
+
+    for (id, result) in [
+        (ident!("stone_slab_from_stonecutting"), ItemKind::StoneSlab),
+        (
+            ident!("stone_bricks_from_stonecutting"),
+            ItemKind::StoneBricks,
+        ),
+    ] {
+        registry.insert(
+            id,
+            RecipeData::Stonecutting(StonecuttingRecipe {
+                group: String::new(),
+                ingredient: Ingredient(vec![ItemKind::Stone]),
+                result: ItemStack::new(result, 2, None),
+            }),
         );
+    }
 
-        let events = app
-            .world
-            .get_resource::<Events<DropItemStackEvent>>()
-            .expect("expected drop item stack events");
+    // Put the selected recipe's result in the output slot.
+    app.add_systems(
+        EventLoopUpdate,
+        |mut events: EventReader<StonecutterSelectEvent>, mut windows: Query<&mut Inventory>| {
+            for event in events.iter() {
+                let mut stonecutter = windows.get_mut(event.inventory).unwrap();
+                stonecutter.set_slot(stonecutter::OUTPUT_SLOT, event.result.clone());
+            }
+        },
+    );
 
-        let events = events.iter_current_update_events().collect::<Vec<_>>();
+    app.update();
 
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0].client, client_ent);
-        assert_eq!(events[0].from_slot, Some(36));
-        assert_eq!(
-            events[0].stack,
-            ItemStack::new(ItemKind::IronIngot, 1, None)
-        );
+    let mut stonecutter = Inventory::new(InventoryKind::Stonecutter);
+    stonecutter.set_slot(
+        stonecutter::INPUT_SLOT,
+        ItemStack::new(ItemKind::Stone, 3, None),
+    );
 
-        let sent_packets = client_helper.collect_received();
+    let stonecutter_ent = app
+        .world
+        .spawn((
+            stonecutter,
+            ContainerProperties::new(InventoryKind::Stonecutter),
+        ))
+        .id();
 
-        sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(0);
-    }
+    app.world
+        .entity_mut(client_ent)
+        .insert(OpenInventory::new(stonecutter_ent));
 
-    #[test]
-    fn should_drop_item_stack_player_action() {
-        let mut app = App::new();
-        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+    app.update();
 
-        // Process a tick to get past the "on join" logic.
-        app.update();
-        client_helper.clear_received();
+    let window_id = app
+        .world
+        .get::<ClientInventoryState>(client_ent)
+        .unwrap()
+        .window_id() as i8;
+
+    // There are only two recipes to choose from.
+    client_helper.send(&ButtonClickC2s {
+        window_id,
+        button_id: 2,
+    });
+
+    // The client sorts the recipes by their results, so the slabs come second.
+    client_helper.send(&ButtonClickC2s {
+        window_id,
+        button_id: 1,
+    });
+
+    app.update();
+
+    let events = app.world.resource::<Events<StonecutterSelectEvent>>();
+    let events: Vec<_> = events.iter_current_update_events().collect();
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].index, 1);
+    assert_eq!(events[0].recipe, ident!("stone_slab_from_stonecutting"));
+
+    let slabs = ItemStack::new(ItemKind::StoneSlab, 2, None);
+
+    let stonecutter = app.world.get::<Inventory>(stonecutter_ent).unwrap();
+    assert_eq!(stonecutter.slot(stonecutter::OUTPUT_SLOT), Some(&slabs));
+
+    let properties = app
+        .world
+        .get::<ContainerProperties>(stonecutter_ent)
+        .unwrap();
+    assert_eq!(properties.get(0), Some(1));
+
+    // Take the slabs. The client expects the output to be filled again.
+    client_helper.clear_received();
+
+    send_window_click(
+        &mut app,
+        client_ent,
+        &mut client_helper,
+        stonecutter::OUTPUT_SLOT as i16,
+        ClickMode::Click,
+        vec![
+            SlotChange {
+                idx: 0,
+                item: Some(ItemStack::new(ItemKind::Stone, 2, None)),
+            },
+            SlotChange {
+                idx: 1,
+                item: Some(slabs.clone()),
+            },
+        ],
+        Some(slabs.clone()),
+    );
+
+    app.update();
+
+    let cursor_item = app.world.get::<CursorItem>(client_ent).unwrap();
+    assert_eq!(cursor_item.0, Some(slabs));
+
+    let stonecutter = app.world.get::<Inventory>(stonecutter_ent).unwrap();
+    assert_eq!(
+        stonecutter.slot(stonecutter::INPUT_SLOT),
+        Some(&ItemStack::new(ItemKind::Stone, 2, None))
+    );
+    assert_eq!(stonecutter.slot(stonecutter::OUTPUT_SLOT), None);
+
+    // The client is told that the output is empty until the server fills it.
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<InventoryS2c>(0);
+
+    let updates: Vec<_> = sent_packets
+        .0
+        .iter()
+        .filter(|f| f.id == ScreenHandlerSlotUpdateS2c::ID)
+        .map(|f| f.decode::<ScreenHandlerSlotUpdateS2c>().unwrap())
+        .map(|pkt| (pkt.slot_idx, pkt.slot_data.into_owned()))
+        .collect();
+
+    assert!(updates.contains(&(stonecutter::OUTPUT_SLOT as i16, None)));
+}
+
+#[test]
+fn smithing_table_take_result() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    app.world.resource_mut::<RecipeRegistry>().insert(
+        ident!("netherite_sword_smithing"),
+        RecipeData::SmithingTransform(SmithingTransformRecipe {
+            template: Ingredient(vec![ItemKind::NetheriteUpgradeSmithingTemplate]),
+            base: Ingredient(vec![ItemKind::DiamondSword]),
+            addition: Ingredient(vec![ItemKind::NetheriteIngot]),
+            result: ItemStack::new(ItemKind::NetheriteSword, 1, None),
+        }),
+    );
+
+    app.update();
+
+    let damaged = compound! { "Damage" => 10 };
+
+    let mut smithing_table = Inventory::new(InventoryKind::Smithing);
+    smithing_table.set_slot(
+        smithing::TEMPLATE_SLOT,
+        ItemStack::new(ItemKind::NetheriteUpgradeSmithingTemplate, 2, None),
+    );
+    smithing_table.set_slot(
+        smithing::BASE_SLOT,
+        ItemStack::new(ItemKind::DiamondSword, 1, Some(damaged.clone())),
+    );
+    smithing_table.set_slot(
+        smithing::ADDITION_SLOT,
+        ItemStack::new(ItemKind::NetheriteIngot, 1, None),
+    );
+
+    let result =
+        smithing::transform_result(app.world.resource::<RecipeRegistry>(), &smithing_table);
+    let netherite_sword = ItemStack::new(ItemKind::NetheriteSword, 1, Some(damaged));
+    assert_eq!(result, Some(netherite_sword.clone()));
+
+    smithing_table.set_slot(smithing::OUTPUT_SLOT, result);
+
+    let smithing_ent = app.world.spawn(smithing_table).id();
+
+    app.world
+        .entity_mut(client_ent)
+        .insert(OpenInventory::new(smithing_ent));
+
+    app.update();
+
+    // Shift click the result into the last hotbar slot.
+    send_window_click(
+        &mut app,
+        client_ent,
+        &mut client_helper,
+        smithing::OUTPUT_SLOT as i16,
+        ClickMode::ShiftClick,
+        vec![
+            SlotChange {
+                idx: 0,
+                item: Some(ItemStack::new(
+                    ItemKind::NetheriteUpgradeSmithingTemplate,
+                    1,
+                    None,
+                )),
+            },
+            SlotChange { idx: 1, item: None },
+            SlotChange { idx: 2, item: None },
+            SlotChange { idx: 3, item: None },
+            SlotChange {
+                idx: 39,
+                item: Some(netherite_sword.clone()),
+            },
+        ],
+        None,
+    );
+
+    app.update();
+
+    let player_inventory = app.world.get::<Inventory>(client_ent).unwrap();
+    assert_eq!(player_inventory.slot(44), Some(&netherite_sword));
+
+    let smithing_table = app.world.get::<Inventory>(smithing_ent).unwrap();
+    assert_eq!(
+        smithing_table.slot(smithing::TEMPLATE_SLOT),
+        Some(&ItemStack::new(
+            ItemKind::NetheriteUpgradeSmithingTemplate,
+            1,
+            None
+        ))
+    );
+    assert_eq!(smithing_table.slot(smithing::BASE_SLOT), None);
+    assert_eq!(smithing_table.slot(smithing::ADDITION_SLOT), None);
+    assert_eq!(smithing_table.slot(smithing::OUTPUT_SLOT), None);
+}
+
+/// Presses the drop key on the output slot of a window and checks that the
+/// click is rejected.
+fn assert_result_not_dropped(kind: InventoryKind, input_slot: u16, output_slot: u16) {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    app.update();
+
+    let input = ItemStack::new(ItemKind::Stone, 3, None);
+    let result = ItemStack::new(ItemKind::StoneSlab, 2, None);
+
+    let mut window = Inventory::new(kind);
+    window.set_slot(input_slot, input.clone());
+    window.set_slot(output_slot, result.clone());
+
+    let window_ent = app.world.spawn(window).id();
+
+    app.world
+        .entity_mut(client_ent)
+        .insert(OpenInventory::new(window_ent));
+
+    app.update();
+    client_helper.clear_received();
+
+    send_window_click(
+        &mut app,
+        client_ent,
+        &mut client_helper,
+        output_slot as i16,
+        ClickMode::DropKey,
+        vec![SlotChange {
+            idx: output_slot as i16,
+            item: Some(result.clone().with_count(1)),
+        }],
+        None,
+    );
+
+    app.update();
+
+    let events = app.world.resource::<Events<DropItemEvent>>();
+    assert_eq!(events.iter_current_update_events().count(), 0);
+
+    let window = app.world.get::<Inventory>(window_ent).unwrap();
+    assert_eq!(window.slot(input_slot), Some(&input));
+    assert_eq!(window.slot(output_slot), Some(&result));
+
+    // The client is told what is actually in the window.
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<InventoryS2c>(1);
+}
+
+#[test]
+fn stonecutter_result_cannot_be_dropped() {
+    assert_result_not_dropped(
+        InventoryKind::Stonecutter,
+        stonecutter::INPUT_SLOT,
+        stonecutter::OUTPUT_SLOT,
+    );
+}
+
+#[test]
+fn smithing_table_result_cannot_be_dropped() {
+    assert_result_not_dropped(
+        InventoryKind::Smithing,
+        smithing::BASE_SLOT,
+        smithing::OUTPUT_SLOT,
+    );
+}
+
+#[test]
+fn open_book_restores_hand_slot() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    app.update();
+
+    let stick = ItemStack::new(ItemKind::Stick, 1, None);
+    let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+    inventory.set_slot(36, stick.clone());
+
+    app.update();
+    client_helper.clear_received();
+
+    let book = ItemStack::from(WrittenBook::new(
+        "Quests",
+        "Guide",
+        vec!["Find the castle".into()],
+    ));
+
+    app.world
+        .query::<BookReader>()
+        .get_mut(&mut app.world, client_ent)
+        .unwrap()
+        .open_book(&book, false);
+
+    app.update();
+
+    let sent_packets = client_helper.collect_received();
+    let order: Vec<_> = sent_packets
+        .0
+        .iter()
+        .map(|f| f.id)
+        .filter(|&id| id == ScreenHandlerSlotUpdateS2c::ID || id == OpenWrittenBookS2c::ID)
+        .collect();
+
+    assert_eq!(
+        order,
+        [
+            ScreenHandlerSlotUpdateS2c::ID,
+            OpenWrittenBookS2c::ID,
+            ScreenHandlerSlotUpdateS2c::ID
+        ]
+    );
+
+    let updates: Vec<_> = sent_packets
+        .0
+        .iter()
+        .filter(|f| f.id == ScreenHandlerSlotUpdateS2c::ID)
+        .map(|f| f.decode::<ScreenHandlerSlotUpdateS2c>().unwrap())
+        .map(|pkt| (pkt.slot_idx, pkt.slot_data.into_owned()))
+        .collect();
+
+    // The book is only in the hand while the screen opens.
+    assert_eq!(updates, vec![(36, Some(book)), (36, Some(stick.clone()))]);
+
+    let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+    assert_eq!(inventory.slot(36), Some(&stick));
+}
+
+#[test]
+fn lectern_turn_pages_and_take_book() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    app.update();
+
+    let mut lectern = Inventory::new(InventoryKind::Lectern);
+    lectern.set_slot(
+        lectern::BOOK_SLOT,
+        ItemStack::from(WrittenBook::new(
+            "Rules",
+            "Admin",
+            vec!["One".into(), "Two".into(), "Three".into()],
+        )),
+    );
+
+    let lectern_ent = app
+        .world
+        .spawn((lectern, ContainerProperties::new(InventoryKind::Lectern)))
+        .id();
+
+    app.world
+        .entity_mut(client_ent)
+        .insert(OpenInventory::new(lectern_ent));
+
+    app.update();
+
+    let window_id = app
+        .world
+        .get::<ClientInventoryState>(client_ent)
+        .unwrap()
+        .window_id() as i8;
+
+    let mut press = |button_id| {
+        client_helper.send(&ButtonClickC2s {
+            window_id,
+            button_id,
+        });
+        app.update();
+
+        let pages: Vec<_> = app
+            .world
+            .resource::<Events<LecternPageEvent>>()
+            .iter_current_update_events()
+            .map(|event| event.page)
+            .collect();
+
+        let page = app
+            .world
+            .get::<ContainerProperties>(lectern_ent)
+            .unwrap()
+            .get(0);
+
+        (pages, page)
+    };
+
+    // Next page, then jump past the last page, then back before the first.
+    assert_eq!(press(2), (vec![1], Some(1)));
+    assert_eq!(press(105), (vec![2], Some(2)));
+    assert_eq!(press(2), (vec![], Some(2)));
+    assert_eq!(press(100), (vec![0], Some(0)));
+    assert_eq!(press(1), (vec![], Some(0)));
+
+    client_helper.send(&ButtonClickC2s {
+        window_id,
+        button_id: 3,
+    });
+
+    app.update();
+
+    let events = app.world.resource::<Events<LecternTakeBookEvent>>();
+    let events: Vec<_> = events.iter_current_update_events().collect();
+
+    assert_eq!(
+        events,
+        [&LecternTakeBookEvent {
+            client: client_ent,
+            inventory: lectern_ent,
+        }]
+    );
+}
+
+#[test]
+fn test_should_remove_invalid_open_inventory() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    let inventory = Inventory::new(InventoryKind::Generic3x3);
+    let inventory_ent = app.world.spawn(inventory).id();
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+    client_helper.clear_received();
+
+    // Open the inventory.
+    let open_inventory = OpenInventory::new(inventory_ent);
+    app.world
+        .get_entity_mut(client_ent)
+        .expect("could not find client")
+        .insert(open_inventory);
+
+    app.update();
+    client_helper.clear_received();
+
+    // Remove the inventory.
+    app.world.despawn(inventory_ent);
+
+    app.update();
+
+    // Make assertions
+    assert!(app.world.get::<OpenInventory>(client_ent).is_none());
+
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<CloseScreenS2c>(1);
+}
+
+#[test]
+fn test_should_modify_player_inventory_click_slot() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+    client_helper.clear_received();
+
+    let mut inventory = app
+        .world
+        .get_mut::<Inventory>(client_ent)
+        .expect("could not find inventory for client");
+    inventory.set_slot(20, ItemStack::new(ItemKind::Diamond, 2, None));
+
+    // Make the client click the slot and pick up the item.
+    let state_id = app
+        .world
+        .get::<ClientInventoryState>(client_ent)
+        .unwrap()
+        .state_id();
+
+    client_helper.send(&ClickSlotC2s {
+        window_id: 0,
+        button: 0,
+        mode: ClickMode::Click,
+        state_id: VarInt(state_id.0),
+        slot_idx: 20,
+        slot_changes: vec![SlotChange {
+            idx: 20,
+            item: None,
+        }],
+        carried_item: Some(ItemStack::new(ItemKind::Diamond, 2, None)),
+    });
+
+    app.update();
+
+    // Make assertions
+    let sent_packets = client_helper.collect_received();
+
+    // because the inventory was changed as a result of the client's click, the
+    // server should not send any packets to the client because the client
+    // already knows about the change.
+    sent_packets.assert_count::<InventoryS2c>(0);
+    sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(0);
+
+    let inventory = app
+        .world
+        .get::<Inventory>(client_ent)
+        .expect("could not find inventory for client");
+
+    assert_eq!(inventory.slot(20), None);
+
+    let cursor_item = app
+        .world
+        .get::<CursorItem>(client_ent)
+        .expect("could not find client");
+
+    assert_eq!(
+        cursor_item.0,
+        Some(ItemStack::new(ItemKind::Diamond, 2, None))
+    );
+}
+
+#[test]
+fn test_should_modify_player_inventory_server_side() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+
+    let mut inventory = app
+        .world
+        .get_mut::<Inventory>(client_ent)
+        .expect("could not find inventory for client");
+    inventory.set_slot(20, ItemStack::new(ItemKind::Diamond, 2, None));
+
+    app.update();
+    client_helper.clear_received();
+
+    // Modify the inventory.
+    let mut inventory = app
+        .world
+        .get_mut::<Inventory>(client_ent)
+        .expect("could not find inventory for client");
+    inventory.set_slot(21, ItemStack::new(ItemKind::IronIngot, 1, None));
+
+    app.update();
+
+    // Make assertions
+    let sent_packets = client_helper.collect_received();
+    // because the inventory was modified server side, the client needs to be
+    // updated with the change.
+    sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(1);
+}
+
+#[test]
+fn test_should_sync_entire_player_inventory() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+    client_helper.clear_received();
+
+    let mut inventory = app
+        .world
+        .get_mut::<Inventory>(client_ent)
+        .expect("could not find inventory for client");
+    inventory.changed = u64::MAX;
+
+    app.update();
+
+    // Make assertions
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<InventoryS2c>(1);
+}
+
+fn set_up_open_inventory(app: &mut App, client_ent: Entity) -> Entity {
+    let inventory = Inventory::new(InventoryKind::Generic9x3);
+    let inventory_ent = app.world.spawn(inventory).id();
+
+    // Open the inventory.
+    let open_inventory = OpenInventory::new(inventory_ent);
+    app.world
+        .get_entity_mut(client_ent)
+        .expect("could not find client")
+        .insert(open_inventory);
+
+    inventory_ent
+}
+
+#[test]
+fn test_should_modify_open_inventory_click_slot() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+    let inventory_ent = set_up_open_inventory(&mut app, client_ent);
+    let mut inventory = app
+        .world
+        .get_mut::<Inventory>(inventory_ent)
+        .expect("could not find inventory for client");
+    inventory.set_slot(20, ItemStack::new(ItemKind::Diamond, 2, None));
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+    client_helper.clear_received();
+
+    // Make the client click the slot and pick up the item.
+    let inv_state = app.world.get::<ClientInventoryState>(client_ent).unwrap();
+    let state_id = inv_state.state_id();
+    let window_id = inv_state.window_id();
+    client_helper.send(&ClickSlotC2s {
+        window_id,
+        state_id: VarInt(state_id.0),
+        slot_idx: 20,
+        button: 0,
+        mode: ClickMode::Click,
+        slot_changes: vec![SlotChange {
+            idx: 20,
+            item: None,
+        }],
+        carried_item: Some(ItemStack::new(ItemKind::Diamond, 2, None)),
+    });
+
+    app.update();
+
+    // Make assertions
+    let sent_packets = client_helper.collect_received();
+
+    // because the inventory was modified as a result of the client's click, the
+    // server should not send any packets to the client because the client
+    // already knows about the change.
+    sent_packets.assert_count::<InventoryS2c>(0);
+    sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(0);
+
+    let inventory = app
+        .world
+        .get::<Inventory>(inventory_ent)
+        .expect("could not find inventory");
+    assert_eq!(inventory.slot(20), None);
+    let cursor_item = app
+        .world
+        .get::<CursorItem>(client_ent)
+        .expect("could not find client");
+    assert_eq!(
+        cursor_item.0,
+        Some(ItemStack::new(ItemKind::Diamond, 2, None))
+    );
+}
+
+#[test]
+fn test_should_modify_open_inventory_server_side() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+    let inventory_ent = set_up_open_inventory(&mut app, client_ent);
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+    client_helper.clear_received();
+
+    // Modify the inventory.
+    let mut inventory = app
+        .world
+        .get_mut::<Inventory>(inventory_ent)
+        .expect("could not find inventory for client");
+    inventory.set_slot(5, ItemStack::new(ItemKind::IronIngot, 1, None));
+
+    app.update();
+
+    // Make assertions
+    let sent_packets = client_helper.collect_received();
+
+    // because the inventory was modified server side, the client needs to be
+    // updated with the change.
+    sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(1);
+
+    let inventory = app
+        .world
+        .get::<Inventory>(inventory_ent)
+        .expect("could not find inventory for client");
+
+    assert_eq!(
+        inventory.slot(5),
+        Some(&ItemStack::new(ItemKind::IronIngot, 1, None))
+    );
+}
+
+#[test]
+fn test_should_sync_entire_open_inventory() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+    let inventory_ent = set_up_open_inventory(&mut app, client_ent);
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+    client_helper.clear_received();
+
+    let mut inventory = app
+        .world
+        .get_mut::<Inventory>(inventory_ent)
+        .expect("could not find inventory");
+    inventory.changed = u64::MAX;
+
+    app.update();
+
+    // Make assertions
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<InventoryS2c>(1);
+}
+
+#[test]
+fn test_set_creative_mode_slot_handling() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+    let mut game_mode = app
+        .world
+        .get_mut::<GameMode>(client_ent)
+        .expect("could not find client");
+    *game_mode.as_mut() = GameMode::Creative;
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+    client_helper.clear_received();
+
+    client_helper.send(&CreativeInventoryActionC2s {
+        slot: 36,
+        clicked_item: Some(ItemStack::new(ItemKind::Diamond, 2, None)),
+    });
+
+    app.update();
+
+    // Make assertions
+    let inventory = app
+        .world
+        .get::<Inventory>(client_ent)
+        .expect("could not find inventory for client");
+
+    assert_eq!(
+        inventory.slot(36),
+        Some(&ItemStack::new(ItemKind::Diamond, 2, None))
+    );
+}
+
+#[test]
+fn test_ignore_set_creative_mode_slot_if_not_creative() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+    let mut game_mode = app
+        .world
+        .get_mut::<GameMode>(client_ent)
+        .expect("could not find client");
+    *game_mode.as_mut() = GameMode::Survival;
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+    client_helper.clear_received();
+
+    client_helper.send(&CreativeInventoryActionC2s {
+        slot: 36,
+        clicked_item: Some(ItemStack::new(ItemKind::Diamond, 2, None)),
+    });
+
+    app.update();
+
+    // Make assertions
+    let inventory = app
+        .world
+        .get::<Inventory>(client_ent)
+        .expect("could not find inventory for client");
+    assert_eq!(inventory.slot(36), None);
+}
+
+#[test]
+fn test_cancel_set_creative_mode_slot() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+    app.world.entity_mut(client_ent).insert(GameMode::Creative);
+
+    app.add_systems(
+        EventLoopUpdate,
+        |mut pending: ResMut<PendingCreativeSetSlots>| {
+            for event in &mut pending.0 {
+                match event.slot {
+                    36 => event.cancel(),
+                    37 => event.proposed = Some(ItemStack::new(ItemKind::Stone, 1, None)),
+                    _ => {}
+                }
+            }
+        },
+    );
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+
+    app.world
+        .get_mut::<Inventory>(client_ent)
+        .unwrap()
+        .set_slot(36, ItemStack::new(ItemKind::GoldIngot, 3, None));
+
+    app.update();
+    client_helper.clear_received();
+
+    for slot in [36, 37] {
+        client_helper.send(&CreativeInventoryActionC2s {
+            slot,
+            clicked_item: Some(ItemStack::new(ItemKind::Diamond, 2, None)),
+        });
+    }
+
+    app.update();
+
+    let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+    assert_eq!(
+        inventory.slot(36),
+        Some(&ItemStack::new(ItemKind::GoldIngot, 3, None))
+    );
+    assert_eq!(
+        inventory.slot(37),
+        Some(&ItemStack::new(ItemKind::Stone, 1, None))
+    );
+
+    // The client is told what is actually in the slots.
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(2);
+    sent_packets.assert_count::<InventoryS2c>(0);
+
+    let pkt = sent_packets.first::<ScreenHandlerSlotUpdateS2c>();
+    assert_eq!(pkt.slot_idx, 36);
+    assert_eq!(
+        pkt.slot_data.as_ref(),
+        &Some(ItemStack::new(ItemKind::GoldIngot, 3, None))
+    );
+}
+
+#[test]
+fn test_set_creative_mode_slot_validation() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+    app.world.entity_mut(client_ent).insert(GameMode::Creative);
+
+    app.world
+        .resource_mut::<InventorySettings>()
+        .max_creative_nbt_size = 100;
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+    client_helper.clear_received();
+
+    let small_nbt = compound! { "Damage" => 5 };
+    let large_nbt = compound! { "Items" => List::Int(vec![0; 100]) };
+
+    client_helper.send(&CreativeInventoryActionC2s {
+        slot: 36,
+        clicked_item: Some(ItemStack::new(ItemKind::EnderPearl, 64, None)),
+    });
+    client_helper.send(&CreativeInventoryActionC2s {
+        slot: 37,
+        clicked_item: Some(ItemStack::new(
+            ItemKind::DiamondSword,
+            1,
+            Some(small_nbt.clone()),
+        )),
+    });
+    client_helper.send(&CreativeInventoryActionC2s {
+        slot: 38,
+        clicked_item: Some(ItemStack::new(
+            ItemKind::ShulkerBox,
+            1,
+            Some(large_nbt.clone()),
+        )),
+    });
+
+    app.update();
+
+    let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+
+    // Stacks are shrunk to the max stack size.
+    assert_eq!(
+        inventory.slot(36),
+        Some(&ItemStack::new(ItemKind::EnderPearl, 16, None))
+    );
+    assert_eq!(
+        inventory.slot(37),
+        Some(&ItemStack::new(ItemKind::DiamondSword, 1, Some(small_nbt)))
+    );
+    // Items with NBT over the limit are rejected.
+    assert_eq!(inventory.slot(38), None);
+
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(3);
+
+    // The NBT can be stripped instead.
+    app.world
+        .resource_mut::<InventorySettings>()
+        .strip_oversized_nbt = true;
+
+    client_helper.send(&CreativeInventoryActionC2s {
+        slot: 38,
+        clicked_item: Some(ItemStack::new(ItemKind::ShulkerBox, 1, Some(large_nbt))),
+    });
+
+    app.update();
+
+    let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+    assert_eq!(
+        inventory.slot(38),
+        Some(&ItemStack::new(ItemKind::ShulkerBox, 1, None))
+    );
+}
+
+#[test]
+fn test_window_id_increments() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+    let inventory = Inventory::new(InventoryKind::Generic9x3);
+    let inventory_ent = app.world.spawn(inventory).id();
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+    client_helper.clear_received();
+
+    for _ in 0..3 {
+        let open_inventory = OpenInventory::new(inventory_ent);
+        app.world
+            .get_entity_mut(client_ent)
+            .expect("could not find client")
+            .insert(open_inventory);
+
+        app.update();
+
+        app.world
+            .get_entity_mut(client_ent)
+            .expect("could not find client")
+            .remove::<OpenInventory>();
+
+        app.update();
+    }
+
+    // Make assertions
+    let inv_state = app
+        .world
+        .get::<ClientInventoryState>(client_ent)
+        .expect("could not find client");
+    assert_eq!(inv_state.window_id(), 3);
+}
+
+#[test]
+fn test_should_handle_set_held_item() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+    client_helper.clear_received();
+
+    client_helper.send(&UpdateSelectedSlotC2s { slot: 4 });
+
+    app.update();
+
+    // Make assertions
+    let held = app
+        .world
+        .get::<HeldItem>(client_ent)
+        .expect("could not find client");
+
+    assert_eq!(held.slot(), 40);
+
+    let events = app
+        .world
+        .resource::<Events<HeldItemChangeEvent>>()
+        .iter_current_update_events()
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        events,
+        [&HeldItemChangeEvent {
+            client: client_ent,
+            old_slot: 36,
+            new_slot: 40,
+        }]
+    );
+
+    // The client already knows about the slot it switched to.
+    client_helper
+        .collect_received()
+        .assert_count::<UpdateSelectedSlotS2c>(0);
+}
+
+#[test]
+fn server_sets_held_slot() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+    client_helper.clear_received();
+
+    app.world
+        .get_mut::<HeldItem>(client_ent)
+        .unwrap()
+        .set_held_slot(7);
+
+    app.update();
+
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<UpdateSelectedSlotS2c>(1);
+    assert_eq!(sent_packets.first::<UpdateSelectedSlotS2c>().slot, 7);
+
+    let held = app.world.get::<HeldItem>(client_ent).unwrap();
+    assert_eq!(held.slot(), 43);
+    assert_eq!(held.hotbar_slot(), 7);
+
+    let events = app
+        .world
+        .resource::<Events<HeldItemChangeEvent>>()
+        .iter_current_update_events()
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        events,
+        [&HeldItemChangeEvent {
+            client: client_ent,
+            old_slot: 36,
+            new_slot: 43,
+        }]
+    );
+
+    // Setting the slot the client already holds doesn't send anything.
+    client_helper.clear_received();
+
+    app.world
+        .get_mut::<HeldItem>(client_ent)
+        .unwrap()
+        .set_held_slot(7);
+
+    app.update();
+
+    client_helper
+        .collect_received()
+        .assert_count::<UpdateSelectedSlotS2c>(0);
+}
+
+#[test]
+#[should_panic]
+fn out_of_range_held_slot_is_rejected() {
+    let mut app = App::new();
+    let (client_ent, _client_helper) = scenario_single_client(&mut app);
+
+    app.update();
+
+    app.world
+        .get_mut::<HeldItem>(client_ent)
+        .unwrap()
+        .set_held_slot(9);
+}
+
+#[test]
+fn held_tool_breaks() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+
+    let shears = ItemStack::new(ItemKind::Shears, 1, None);
+    app.world
+        .get_mut::<Inventory>(client_ent)
+        .unwrap()
+        .set_slot(36, shears);
+
+    app.update();
+    client_helper.clear_received();
+
+    let mut rng = rand::thread_rng();
+    let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+
+    assert_eq!(
+        inventory.damage_slot(36, 237, &mut rng),
+        Some(DamageResult::Damaged(237))
+    );
+    assert_eq!(
+        inventory.damage_slot(36, 1, &mut rng),
+        Some(DamageResult::Broken)
+    );
+    assert_eq!(inventory.slot(36), None);
+    assert_eq!(inventory.damage_slot(36, 1, &mut rng), None);
+
+    app.update();
+
+    let events = app
+        .world
+        .resource::<Events<ItemBreakEvent>>()
+        .iter_current_update_events()
+        .collect::<Vec<_>>();
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].inventory, client_ent);
+    assert_eq!(events[0].slot, 36);
+    assert_eq!(events[0].item.damage_taken(), 238);
+
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(1);
+    assert_eq!(
+        sent_packets
+            .first::<ScreenHandlerSlotUpdateS2c>()
+            .slot_data
+            .as_ref(),
+        &None
+    );
+}
+
+#[test]
+fn should_not_increment_state_id_on_cursor_item_change() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+    client_helper.clear_received();
+
+    let inv_state = app
+        .world
+        .get::<ClientInventoryState>(client_ent)
+        .expect("could not find client");
+    let expected_state_id = inv_state.state_id().0;
+
+    let mut cursor_item = app.world.get_mut::<CursorItem>(client_ent).unwrap();
+    cursor_item.0 = Some(ItemStack::new(ItemKind::Diamond, 2, None));
+
+    app.update();
+
+    // Make assertions
+    let inv_state = app
+        .world
+        .get::<ClientInventoryState>(client_ent)
+        .expect("could not find client");
+    assert_eq!(
+        inv_state.state_id().0,
+        expected_state_id,
+        "state id should not have changed"
+    );
+}
+
+mod swapping_hands {
+    use valence_client::packet::{PlayerAction, PlayerActionC2s};
+    use valence_core::block_pos::BlockPos;
+    use valence_core::direction::Direction;
+    use valence_entity::packet::{EntityEquipmentUpdateS2c, EquipmentEntry};
+    use valence_entity::{EntityId, Location};
+    use valence_inventory::{PendingHandSwaps, SwapHandsEvent};
+
+    use super::*;
+    use crate::testing::create_mock_client;
+
+    fn send_swap(client_helper: &mut MockClientHelper) {
+        client_helper.send(&PlayerActionC2s {
+            action: PlayerAction::SwapItemWithOffhand,
+            position: BlockPos::new(0, 0, 0),
+            direction: Direction::Down,
+            sequence: VarInt(0),
+        });
+    }
+
+    #[test]
+    fn swap_hands_with_key() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.world
+            .resource_mut::<InventorySettings>()
+            .mirror_equipment = true;
+
+        // Another client that can see the first one.
+        let (mut other, mut other_helper) = create_mock_client("other");
+        other.player.location = *app.world.get::<Location>(client_ent).unwrap();
+        app.world.spawn(other);
+
+        app.update();
+
+        let sword = ItemStack::new(ItemKind::DiamondSword, 1, None);
+        let shield = ItemStack::new(ItemKind::Shield, 1, None);
+
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        inventory.set_slot(36, sword.clone());
+        inventory.set_slot(45, shield.clone());
+
+        app.update();
+        client_helper.clear_received();
+        other_helper.clear_received();
+
+        send_swap(&mut client_helper);
+
+        app.update();
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(36), Some(&shield));
+        assert_eq!(inventory.slot(45), Some(&sword));
+
+        let events = app
+            .world
+            .resource::<Events<SwapHandsEvent>>()
+            .iter_current_update_events()
+            .collect::<Vec<_>>();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].main_hand, Some(shield.clone()));
+        assert_eq!(events[0].off_hand, Some(sword.clone()));
+
+        // The client waits for the server to swap the items.
+        let sent_packets = client_helper.collect_received();
+        sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(2);
+        sent_packets.assert_count::<EntityEquipmentUpdateS2c>(0);
+
+        let entity_id = app.world.get::<EntityId>(client_ent).unwrap().get();
+
+        let pkt = other_helper
+            .collect_received()
+            .first::<EntityEquipmentUpdateS2c>();
+
+        assert_eq!(pkt.entity_id.0, entity_id);
+        assert_eq!(
+            pkt.equipment,
+            vec![
+                EquipmentEntry {
+                    slot: 0,
+                    item: Some(shield),
+                },
+                EquipmentEntry {
+                    slot: 1,
+                    item: Some(sword),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn swap_hands_cancelled() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.add_systems(EventLoopUpdate, |mut pending: ResMut<PendingHandSwaps>| {
+            for event in &mut pending.0 {
+                event.cancel();
+            }
+        });
+
+        app.update();
+
+        let sword = ItemStack::new(ItemKind::DiamondSword, 1, None);
+
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        inventory.set_slot(36, sword.clone());
+
+        app.update();
+        client_helper.clear_received();
+
+        send_swap(&mut client_helper);
+
+        app.update();
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(36), Some(&sword));
+        assert_eq!(inventory.slot(45), None);
+
+        assert!(app.world.resource::<Events<SwapHandsEvent>>().is_empty());
+
+        // The client is sent what is actually in its hands.
+        let updates: Vec<_> = client_helper
+            .collect_received()
+            .0
+            .iter()
+            .filter(|f| f.id == ScreenHandlerSlotUpdateS2c::ID)
+            .map(|f| f.decode::<ScreenHandlerSlotUpdateS2c>().unwrap())
+            .map(|pkt| (pkt.slot_idx, pkt.slot_data.into_owned()))
+            .collect();
+
+        assert_eq!(updates, vec![(36, Some(sword)), (45, None)]);
+    }
+
+    #[test]
+    fn swap_offhand_in_open_window() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let inventory_ent = set_up_open_inventory(&mut app, client_ent);
+
+        app.update();
+
+        let apple = ItemStack::new(ItemKind::Apple, 5, None);
+        let torch = ItemStack::new(ItemKind::Torch, 10, None);
+
+        app.world
+            .get_mut::<Inventory>(inventory_ent)
+            .unwrap()
+            .set_slot(3, apple.clone());
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .set_slot(45, torch.clone());
+
+        app.update();
+        client_helper.clear_received();
+
+        // Pressing the swap key over a slot of the chest. Only the chest slot is part
+        // of the window.
+        let inv_state = app.world.get::<ClientInventoryState>(client_ent).unwrap();
+
+        client_helper.send(&ClickSlotC2s {
+            window_id: inv_state.window_id(),
+            state_id: VarInt(inv_state.state_id().0),
+            slot_idx: 3,
+            button: 40,
+            mode: ClickMode::Hotbar,
+            slot_changes: vec![SlotChange {
+                idx: 3,
+                item: Some(torch.clone()),
+            }],
+            carried_item: None,
+        });
+
+        app.update();
+
+        let chest = app.world.get::<Inventory>(inventory_ent).unwrap();
+        assert_eq!(chest.slot(3), Some(&torch));
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(45), Some(&apple));
+
+        // The swap was accepted without resyncing the window.
+        client_helper
+            .collect_received()
+            .assert_count::<InventoryS2c>(0);
+    }
+}
+
+mod equipping_armor {
+    use valence_client::interact_item::PlayerInteractItemC2s;
+    use valence_core::hand::Hand;
+    use valence_inventory::armor::{ArmorEquipEvent, PendingArmorEquips};
+    use valence_inventory::equipment::EquipmentSlot;
+
+    use super::*;
+
+    fn send_use_item(client_helper: &mut MockClientHelper) {
+        client_helper.send(&PlayerInteractItemC2s {
+            hand: Hand::Main,
+            sequence: VarInt(0),
+        });
+    }
+
+    #[test]
+    fn use_armor_to_equip() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+
+        let iron = ItemStack::new(ItemKind::IronHelmet, 1, None);
+        let leather = ItemStack::new(ItemKind::LeatherHelmet, 1, None);
+
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        inventory.set_slot(36, iron.clone());
+        inventory.set_slot(5, leather.clone());
+
+        app.update();
+        client_helper.clear_received();
+
+        send_use_item(&mut client_helper);
+
+        app.update();
+
+        // The helmets are swapped.
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(5), Some(&iron));
+        assert_eq!(inventory.slot(36), Some(&leather));
+
+        let events = app
+            .world
+            .resource::<Events<ArmorEquipEvent>>()
+            .iter_current_update_events()
+            .collect::<Vec<_>>();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].hand, Hand::Main);
+        assert_eq!(events[0].slot, EquipmentSlot::Head);
+        assert_eq!(events[0].item, iron);
+        assert_eq!(events[0].replaced, Some(leather));
+
+        // The client already put on the armor.
+        client_helper
+            .collect_received()
+            .assert_count::<ScreenHandlerSlotUpdateS2c>(0);
+    }
+
+    #[test]
+    fn use_armor_cancelled() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.add_systems(
+            EventLoopUpdate,
+            |mut pending: ResMut<PendingArmorEquips>| {
+                for event in &mut pending.0 {
+                    event.cancel();
+                }
+            },
+        );
+
+        app.update();
+
+        let boots = ItemStack::new(ItemKind::DiamondBoots, 1, None);
+
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .set_slot(36, boots.clone());
+
+        app.update();
+        client_helper.clear_received();
+
+        send_use_item(&mut client_helper);
+
+        app.update();
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(36), Some(&boots));
+        assert_eq!(inventory.slot(8), None);
+
+        assert!(app.world.resource::<Events<ArmorEquipEvent>>().is_empty());
+
+        // The client is sent what is actually in its hand and armor slot.
+        let updates: Vec<_> = client_helper
+            .collect_received()
+            .0
+            .iter()
+            .filter(|f| f.id == ScreenHandlerSlotUpdateS2c::ID)
+            .map(|f| f.decode::<ScreenHandlerSlotUpdateS2c>().unwrap())
+            .map(|pkt| (pkt.slot_idx, pkt.slot_data.into_owned()))
+            .collect();
+
+        assert_eq!(updates, vec![(8, None), (36, Some(boots))]);
+    }
+
+    #[test]
+    fn using_non_armor_does_nothing() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+
+        // Heads are worn, but using them doesn't put them on.
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .set_slot(36, ItemStack::new(ItemKind::ZombieHead, 1, None));
+
+        app.update();
+
+        send_use_item(&mut client_helper);
+
+        app.update();
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(5), None);
+        assert!(app.world.resource::<Events<ArmorEquipEvent>>().is_empty());
+    }
+
+    #[test]
+    fn shift_click_armor_into_armor_slot() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+
+        let chestplate = ItemStack::new(ItemKind::ChainmailChestplate, 1, None);
+
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .set_slot(20, chestplate.clone());
+
+        app.update();
+        client_helper.clear_received();
+
+        send_window_click(
+            &mut app,
+            client_ent,
+            &mut client_helper,
+            20,
+            ClickMode::ShiftClick,
+            vec![
+                SlotChange {
+                    idx: 6,
+                    item: Some(chestplate.clone()),
+                },
+                SlotChange {
+                    idx: 20,
+                    item: None,
+                },
+            ],
+            None,
+        );
+
+        app.update();
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(6), Some(&chestplate));
+        assert_eq!(inventory.slot(20), None);
+
+        // The client predicted the same move, so nothing is resent.
+        let sent_packets = client_helper.collect_received();
+        sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(0);
+        sent_packets.assert_count::<InventoryS2c>(0);
+    }
+
+    #[test]
+    fn shift_click_armor_out_of_chest() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let inventory_ent = set_up_open_inventory(&mut app, client_ent);
+
+        let leggings = ItemStack::new(ItemKind::GoldenLeggings, 1, None);
+
+        app.world
+            .get_mut::<Inventory>(inventory_ent)
+            .unwrap()
+            .set_slot(4, leggings.clone());
+
+        app.update();
+        client_helper.clear_received();
+
+        // Stacks from the chest go to the end of the hotbar.
+        send_window_click(
+            &mut app,
+            client_ent,
+            &mut client_helper,
+            4,
+            ClickMode::ShiftClick,
+            vec![
+                SlotChange { idx: 4, item: None },
+                SlotChange {
+                    idx: 62,
+                    item: Some(leggings.clone()),
+                },
+            ],
+            None,
+        );
+
+        app.update();
+
+        let chest = app.world.get::<Inventory>(inventory_ent).unwrap();
+        assert_eq!(chest.slot(4), None);
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(44), Some(&leggings));
+
+        let sent_packets = client_helper.collect_received();
+        sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(0);
+        sent_packets.assert_count::<InventoryS2c>(0);
+    }
+}
+
+mod mirroring_equipment {
+    use valence_entity::packet::{EntityEquipmentUpdateS2c, EquipmentEntry};
+    use valence_entity::Location;
+    use valence_inventory::equipment::{EquipmentOverride, EquipmentSlot, MirrorEquipment};
+
+    use super::*;
+    use crate::testing::create_mock_client;
+
+    /// Sets up a client with [`MirrorEquipment`] and another client that can
+    /// see it.
+    fn scenario_with_viewer(app: &mut App) -> (Entity, MockClientHelper, MockClientHelper) {
+        let (client_ent, client_helper) = scenario_single_client(app);
+        app.world.entity_mut(client_ent).insert(MirrorEquipment);
+
+        let (mut other, mut other_helper) = create_mock_client("other");
+        other.player.location = *app.world.get::<Location>(client_ent).unwrap();
+        app.world.spawn(other);
+
+        app.update();
+        other_helper.clear_received();
+
+        (client_ent, client_helper, other_helper)
+    }
+
+    fn received_equipment(helper: &mut MockClientHelper) -> Vec<EquipmentEntry> {
+        let sent_packets = helper.collect_received();
+        sent_packets.assert_count::<EntityEquipmentUpdateS2c>(1);
+        sent_packets.first::<EntityEquipmentUpdateS2c>().equipment
+    }
+
+    #[test]
+    fn armor_change_is_mirrored() {
+        let mut app = App::new();
+        let (client_ent, _client_helper, mut other_helper) = scenario_with_viewer(&mut app);
+
+        let helmet = ItemStack::new(ItemKind::DiamondHelmet, 1, None);
+        let boots = ItemStack::new(ItemKind::IronBoots, 1, None);
+
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        inventory.set_slot(5, helmet.clone());
+        inventory.set_slot(8, boots.clone());
+
+        app.update();
+
+        assert_eq!(
+            received_equipment(&mut other_helper),
+            vec![
+                EquipmentEntry {
+                    slot: EquipmentSlot::Feet as i8,
+                    item: Some(boots),
+                },
+                EquipmentEntry {
+                    slot: EquipmentSlot::Head as i8,
+                    item: Some(helmet),
+                },
+            ]
+        );
+
+        // Changing other slots sends nothing.
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .set_slot(20, ItemStack::new(ItemKind::Dirt, 1, None));
+
+        app.update();
+
+        other_helper
+            .collect_received()
+            .assert_count::<EntityEquipmentUpdateS2c>(0);
+    }
+
+    #[test]
+    fn hotbar_selection_is_mirrored() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper, mut other_helper) = scenario_with_viewer(&mut app);
+
+        let sword = ItemStack::new(ItemKind::DiamondSword, 1, None);
+        let pickaxe = ItemStack::new(ItemKind::IronPickaxe, 1, None);
+
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        inventory.set_slot(36, sword.clone());
+        inventory.set_slot(38, pickaxe.clone());
+
+        app.update();
+
+        assert_eq!(
+            received_equipment(&mut other_helper),
+            vec![EquipmentEntry {
+                slot: EquipmentSlot::MainHand as i8,
+                item: Some(sword),
+            }]
+        );
+
+        client_helper.send(&UpdateSelectedSlotC2s { slot: 2 });
+
+        app.update();
+
+        assert_eq!(
+            received_equipment(&mut other_helper),
+            vec![EquipmentEntry {
+                slot: EquipmentSlot::MainHand as i8,
+                item: Some(pickaxe),
+            }]
+        );
+
+        // The client is never sent its own equipment.
+        client_helper
+            .collect_received()
+            .assert_count::<EntityEquipmentUpdateS2c>(0);
+    }
+
+    #[test]
+    fn not_mirrored_without_opt_in() {
+        let mut app = App::new();
+        let (client_ent, _client_helper, mut other_helper) = scenario_with_viewer(&mut app);
+
+        app.world.entity_mut(client_ent).remove::<MirrorEquipment>();
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .set_slot(5, ItemStack::new(ItemKind::DiamondHelmet, 1, None));
+
+        app.update();
+
+        other_helper
+            .collect_received()
+            .assert_count::<EntityEquipmentUpdateS2c>(0);
+    }
+
+    #[test]
+    fn override_wins_over_mirror() {
+        let mut app = App::new();
+        let (client_ent, _client_helper, mut other_helper) = scenario_with_viewer(&mut app);
+
+        let helmet = ItemStack::new(ItemKind::DiamondHelmet, 1, None);
+        let pumpkin = ItemStack::new(ItemKind::CarvedPumpkin, 1, None);
+
+        let mut overrides = EquipmentOverride::default();
+        overrides.set(EquipmentSlot::Head, pumpkin.clone());
+
+        app.world.entity_mut(client_ent).insert(overrides);
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .set_slot(5, helmet.clone());
+
+        app.update();
+
+        assert_eq!(
+            received_equipment(&mut other_helper),
+            vec![EquipmentEntry {
+                slot: EquipmentSlot::Head as i8,
+                item: Some(pumpkin),
+            }]
+        );
+
+        // The real helmet is shown again once the override is removed.
+        app.world
+            .entity_mut(client_ent)
+            .remove::<EquipmentOverride>();
+
+        app.update();
+
+        assert_eq!(
+            received_equipment(&mut other_helper),
+            vec![EquipmentEntry {
+                slot: EquipmentSlot::Head as i8,
+                item: Some(helmet),
+            }]
+        );
+    }
+}
+
+mod dropping_items {
+    use glam::DVec3;
+    use valence_client::packet::{PlayerAction, PlayerActionC2s};
+    use valence_core::block_pos::BlockPos;
+    use valence_core::direction::Direction;
+    use valence_entity::{Location, Look, Position};
+    use valence_inventory::{convert_to_player_slot_id, PendingItemDrops};
+
+    use super::*;
+
+    #[test]
+    fn should_drop_item_player_action() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+        client_helper.clear_received();
+
+        let mut inventory = app
+            .world
+            .get_mut::<Inventory>(client_ent)
+            .expect("could not find inventory");
+        inventory.set_slot(36, ItemStack::new(ItemKind::IronIngot, 3, None));
+
+        client_helper.send(&PlayerActionC2s {
+            action: PlayerAction::DropItem,
+            position: BlockPos::new(0, 0, 0),
+            direction: Direction::Down,
+            sequence: VarInt(0),
+        });
+
+        app.update();
+
+        // Make assertions
+        let inventory = app
+            .world
+            .get::<Inventory>(client_ent)
+            .expect("could not find client");
+
+        assert_eq!(
+            inventory.slot(36),
+            Some(&ItemStack::new(ItemKind::IronIngot, 2, None))
+        );
+
+        let events = app
+            .world
+            .get_resource::<Events<DropItemEvent>>()
+            .expect("expected drop item events");
+
+        let events = events.iter_current_update_events().collect::<Vec<_>>();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].client, client_ent);
+        assert_eq!(events[0].slot, Some(36));
+        assert_eq!(
+            events[0].stack,
+            ItemStack::new(ItemKind::IronIngot, 1, None)
+        );
+
+        let sent_packets = client_helper.collect_received();
+
+        sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(0);
+    }
+
+    #[test]
+    fn should_drop_item_stack_player_action() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+        client_helper.clear_received();
+
+        let mut inventory = app
+            .world
+            .get_mut::<Inventory>(client_ent)
+            .expect("could not find inventory");
+        inventory.set_slot(36, ItemStack::new(ItemKind::IronIngot, 32, None));
+
+        client_helper.send(&PlayerActionC2s {
+            action: PlayerAction::DropAllItems,
+            position: BlockPos::new(0, 0, 0),
+            direction: Direction::Down,
+            sequence: VarInt(0),
+        });
+
+        app.update();
+
+        // Make assertions
+        let held = app
+            .world
+            .get::<HeldItem>(client_ent)
+            .expect("could not find client");
+        assert_eq!(held.slot(), 36);
+        let inventory = app
+            .world
+            .get::<Inventory>(client_ent)
+            .expect("could not find inventory");
+        assert_eq!(inventory.slot(36), None);
+        let events = app
+            .world
+            .get_resource::<Events<DropItemEvent>>()
+            .expect("expected drop item events");
+        let events = events.iter_current_update_events().collect::<Vec<_>>();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].client, client_ent);
+        assert_eq!(events[0].slot, Some(36));
+        assert_eq!(
+            events[0].stack,
+            ItemStack::new(ItemKind::IronIngot, 32, None)
+        );
+    }
+
+    #[test]
+    fn should_drop_item_stack_set_creative_mode_slot() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+        client_helper.clear_received();
+
+        app.world.entity_mut(client_ent).insert(GameMode::Creative);
+
+        client_helper.send(&CreativeInventoryActionC2s {
+            slot: -1,
+            clicked_item: Some(ItemStack::new(ItemKind::IronIngot, 32, None)),
+        });
+
+        app.update();
+
+        // Make assertions
+        let events = app
+            .world
+            .get_resource::<Events<DropItemEvent>>()
+            .expect("expected drop item events")
+            .iter_current_update_events()
+            .collect::<Vec<_>>();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].client, client_ent);
+        assert_eq!(events[0].slot, None);
+        assert!(events[0].whole_stack);
+        assert_eq!(
+            events[0].stack,
+            ItemStack::new(ItemKind::IronIngot, 32, None)
+        );
+    }
+
+    #[test]
+    fn should_drop_item_stack_click_container_outside() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+        client_helper.clear_received();
+
+        let mut cursor_item = app
+            .world
+            .get_mut::<CursorItem>(client_ent)
+            .expect("could not find client");
+        cursor_item.0 = Some(ItemStack::new(ItemKind::IronIngot, 32, None));
+        let inv_state = app
+            .world
+            .get_mut::<ClientInventoryState>(client_ent)
+            .expect("could not find client");
+        let state_id = inv_state.state_id().0;
+
+        client_helper.send(&ClickSlotC2s {
+            window_id: 0,
+            state_id: VarInt(state_id),
+            slot_idx: -999,
+            button: 0,
+            mode: ClickMode::Click,
+            slot_changes: vec![],
+            carried_item: None,
+        });
+
+        app.update();
+
+        // Make assertions
+        let cursor_item = app
+            .world
+            .get::<CursorItem>(client_ent)
+            .expect("could not find client");
+
+        assert_eq!(cursor_item.0, None);
+
+        let events = app
+            .world
+            .get_resource::<Events<DropItemEvent>>()
+            .expect("expected drop item events");
+
+        let events = events.iter_current_update_events().collect::<Vec<_>>();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].client, client_ent);
+        assert_eq!(events[0].slot, None);
+        assert_eq!(
+            events[0].stack,
+            ItemStack::new(ItemKind::IronIngot, 32, None)
+        );
+    }
+
+    #[test]
+    fn should_drop_single_item_click_container_outside_right_click() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+        client_helper.clear_received();
+
+        app.world.get_mut::<CursorItem>(client_ent).unwrap().0 =
+            Some(ItemStack::new(ItemKind::IronIngot, 32, None));
+
+        let state_id = app
+            .world
+            .get::<ClientInventoryState>(client_ent)
+            .unwrap()
+            .state_id()
+            .0;
+
+        client_helper.send(&ClickSlotC2s {
+            window_id: 0,
+            state_id: VarInt(state_id),
+            slot_idx: -999,
+            button: 1,
+            mode: ClickMode::Click,
+            slot_changes: vec![],
+            carried_item: Some(ItemStack::new(ItemKind::IronIngot, 31, None)),
+        });
+
+        app.update();
+
+        let cursor_item = app.world.get::<CursorItem>(client_ent).unwrap();
+        assert_eq!(
+            cursor_item.0,
+            Some(ItemStack::new(ItemKind::IronIngot, 31, None))
+        );
+
+        let events = app
+            .world
+            .resource::<Events<DropItemEvent>>()
+            .iter_current_update_events()
+            .collect::<Vec<_>>();
+
+        assert_eq!(events.len(), 1);
+        assert!(!events[0].whole_stack);
+        assert_eq!(
+            events[0].stack,
+            ItemStack::new(ItemKind::IronIngot, 1, None)
+        );
+
+        // Facing south, so the item is thrown towards positive Z.
+        let item = events[0].item_entity(
+            Location::default(),
+            Position::new([0.0, 64.0, 0.0]),
+            Look::new(0.0, 0.0),
+        );
+
+        assert_eq!(item.item_stack.0, events[0].stack);
+        assert_eq!(item.position.0, DVec3::new(0.0, 65.32, 0.0));
+        assert!(item.velocity.0.z > 0.0);
+    }
+
+    #[test]
+    fn cancelled_drop_is_put_back() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.add_systems(EventLoopUpdate, |mut pending: ResMut<PendingItemDrops>| {
+            for event in &mut pending.0 {
+                event.cancel();
+            }
+        });
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        inventory.set_slot(36, ItemStack::new(ItemKind::IronIngot, 3, None));
+
+        app.update();
+        client_helper.clear_received();
+
+        client_helper.send(&PlayerActionC2s {
+            action: PlayerAction::DropAllItems,
+            position: BlockPos::new(0, 0, 0),
+            direction: Direction::Down,
+            sequence: VarInt(0),
+        });
+
+        app.update();
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(
+            inventory.slot(36),
+            Some(&ItemStack::new(ItemKind::IronIngot, 3, None))
+        );
+
+        assert!(app.world.resource::<Events<DropItemEvent>>().is_empty());
+
+        // The client is told that the stack is back.
+        let sent_packets = client_helper.collect_received();
+        let pkt = sent_packets.first::<ScreenHandlerSlotUpdateS2c>();
+
+        assert_eq!(pkt.slot_idx, 36);
+        assert_eq!(
+            pkt.slot_data.as_ref(),
+            &Some(ItemStack::new(ItemKind::IronIngot, 3, None))
+        );
+    }
+
+    #[test]
+    fn should_drop_item_click_container_with_dropkey_single() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+        client_helper.clear_received();
+
+        let inv_state = app
+            .world
+            .get_mut::<ClientInventoryState>(client_ent)
+            .expect("could not find client");
+
+        let state_id = inv_state.state_id().0;
+
+        let mut inventory = app
+            .world
+            .get_mut::<Inventory>(client_ent)
+            .expect("could not find inventory");
+
+        inventory.set_slot(40, ItemStack::new(ItemKind::IronIngot, 32, None));
+
+        client_helper.send(&ClickSlotC2s {
+            window_id: 0,
+            slot_idx: 40,
+            button: 0,
+            mode: ClickMode::DropKey,
+            state_id: VarInt(state_id),
+            slot_changes: vec![SlotChange {
+                idx: 40,
+                item: Some(ItemStack::new(ItemKind::IronIngot, 31, None)),
+            }],
+            carried_item: None,
+        });
+
+        app.update();
+
+        // Make assertions
+        let events = app
+            .world
+            .get_resource::<Events<DropItemEvent>>()
+            .expect("expected drop item events");
+
+        let events = events.iter_current_update_events().collect::<Vec<_>>();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].client, client_ent);
+        assert_eq!(events[0].slot, Some(40));
+        assert_eq!(
+            events[0].stack,
+            ItemStack::new(ItemKind::IronIngot, 1, None)
+        );
+    }
+
+    #[test]
+    fn should_drop_item_stack_click_container_with_dropkey() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+        client_helper.clear_received();
+
+        let inv_state = app
+            .world
+            .get_mut::<ClientInventoryState>(client_ent)
+            .expect("could not find client");
+
+        let state_id = inv_state.state_id().0;
+
+        let mut inventory = app
+            .world
+            .get_mut::<Inventory>(client_ent)
+            .expect("could not find inventory");
+
+        inventory.set_slot(40, ItemStack::new(ItemKind::IronIngot, 32, None));
+
+        client_helper.send(&ClickSlotC2s {
+            window_id: 0,
+            slot_idx: 40,
+            button: 1, // pressing control
+            mode: ClickMode::DropKey,
+            state_id: VarInt(state_id),
+            slot_changes: vec![SlotChange {
+                idx: 40,
+                item: None,
+            }],
+            carried_item: None,
+        });
+
+        app.update();
+
+        // Make assertions
+        let events = app
+            .world
+            .get_resource::<Events<DropItemEvent>>()
+            .expect("expected drop item events");
+
+        let events = events.iter_current_update_events().collect::<Vec<_>>();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].client, client_ent);
+        assert_eq!(events[0].slot, Some(40));
+        assert_eq!(
+            events[0].stack,
+            ItemStack::new(ItemKind::IronIngot, 32, None)
+        );
+    }
+
+    #[test]
+    fn should_drop_item_player_open_inventory_with_dropkey() {
+        // The item should be dropped successfully, if the player has an inventory open
+        // and the slot id points to his inventory.
+
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+
+        let mut inventory = app
+            .world
+            .get_mut::<Inventory>(client_ent)
+            .expect("could not find inventory");
+
+        inventory.set_slot(
+            convert_to_player_slot_id(InventoryKind::Generic9x3, 50),
+            ItemStack::new(ItemKind::IronIngot, 32, None),
+        );
+
+        let _inventory_ent = set_up_open_inventory(&mut app, client_ent);
+
+        app.update();
+
+        client_helper.clear_received();
+
+        let inv_state = app
+            .world
+            .get_mut::<ClientInventoryState>(client_ent)
+            .expect("could not find client");
+
+        let state_id = inv_state.state_id().0;
+        let window_id = inv_state.window_id();
+
+        client_helper.send(&ClickSlotC2s {
+            window_id,
+            state_id: VarInt(state_id),
+            slot_idx: 50, // not pressing control
+            button: 0,
+            mode: ClickMode::DropKey,
+            slot_changes: vec![SlotChange {
+                idx: 50,
+                item: Some(ItemStack::new(ItemKind::IronIngot, 31, None)),
+            }],
+            carried_item: None,
+        });
+
+        app.update();
+
+        // Make assertions
+        let events = app
+            .world
+            .get_resource::<Events<DropItemEvent>>()
+            .expect("expected drop item events");
+
+        let player_inventory = app
+            .world
+            .get::<Inventory>(client_ent)
+            .expect("could not find inventory");
+
+        let events = events.iter_current_update_events().collect::<Vec<_>>();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].client, client_ent);
+        assert_eq!(
+            events[0].slot,
+            Some(convert_to_player_slot_id(InventoryKind::Generic9x3, 50))
+        );
+
+        assert_eq!(
+            events[0].stack,
+            ItemStack::new(ItemKind::IronIngot, 1, None)
+        );
+
+        // Also make sure that the player inventory was updated correctly.
+        let expected_player_slot_id = convert_to_player_slot_id(InventoryKind::Generic9x3, 50);
+        assert_eq!(
+            player_inventory.slot(expected_player_slot_id),
+            Some(&ItemStack::new(ItemKind::IronIngot, 31, None))
+        );
+    }
+}
+
+#[test]
+fn should_drop_item_stack_player_open_inventory_with_dropkey() {
+    // The item stack should be dropped successfully, if the player has an inventory
+    // open and the slot id points to his inventory.
+
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+
+    let mut inventory = app
+        .world
+        .get_mut::<Inventory>(client_ent)
+        .expect("could not find inventory");
+
+    inventory.set_slot(
+        convert_to_player_slot_id(InventoryKind::Generic9x3, 50),
+        ItemStack::new(ItemKind::IronIngot, 32, None),
+    );
+
+    let _inventory_ent = set_up_open_inventory(&mut app, client_ent);
+
+    app.update();
+    client_helper.clear_received();
+
+    let inv_state = app
+        .world
+        .get_mut::<ClientInventoryState>(client_ent)
+        .expect("could not find client");
+
+    let state_id = inv_state.state_id().0;
+    let window_id = inv_state.window_id();
+
+    client_helper.send(&ClickSlotC2s {
+        window_id,
+        state_id: VarInt(state_id),
+        slot_idx: 50, // pressing control, the whole stack is dropped
+        button: 1,
+        mode: ClickMode::DropKey,
+        slot_changes: vec![SlotChange {
+            idx: 50,
+            item: None,
+        }],
+        carried_item: None,
+    });
+
+    app.update();
+
+    // Make assertions
+    let events = app
+        .world
+        .get_resource::<Events<DropItemEvent>>()
+        .expect("expected drop item events");
+
+    let player_inventory = app
+        .world
+        .get::<Inventory>(client_ent)
+        .expect("could not find inventory");
+
+    let events = events.iter_current_update_events().collect::<Vec<_>>();
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].client, client_ent);
+    assert_eq!(
+        events[0].slot,
+        Some(convert_to_player_slot_id(InventoryKind::Generic9x3, 50))
+    );
+    assert_eq!(
+        events[0].stack,
+        ItemStack::new(ItemKind::IronIngot, 32, None)
+    );
+
+    // Also make sure that the player inventory was updated correctly.
+    let expected_player_slot_id = convert_to_player_slot_id(InventoryKind::Generic9x3, 50);
+    assert_eq!(player_inventory.slot(expected_player_slot_id), None);
+}
+
+/// Sends the packets for dragging the cursor item over `slots`, ending with
+/// `end`, which has the changes that the client predicts.
+fn send_drag(
+    client_helper: &mut crate::testing::MockClientHelper,
+    slots: &[i16],
+    cursor_item: Option<ItemStack>,
+    end: ClickSlotC2s,
+) {
+    let packet = |slot_idx, stage| ClickSlotC2s {
+        slot_idx,
+        button: end.button & !3 | stage,
+        slot_changes: vec![],
+        carried_item: cursor_item.clone(),
+        ..end.clone()
+    };
+
+    client_helper.send(&packet(-999, 0));
+
+    for &slot in slots {
+        client_helper.send(&packet(slot, 1));
+    }
+
+    client_helper.send(&end);
+}
+
+#[test]
+fn dragging_items() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+    client_helper.clear_received();
+
+    let cursor = Some(ItemStack::new(ItemKind::Diamond, 64, None));
+    app.world.get_mut::<CursorItem>(client_ent).unwrap().0 = cursor.clone();
+
+    let inv_state = app.world.get::<ClientInventoryState>(client_ent).unwrap();
+    let window_id = inv_state.window_id();
+    let state_id = inv_state.state_id().0;
+
+    send_drag(
+        &mut client_helper,
+        &[9, 10, 11],
+        cursor,
+        ClickSlotC2s {
+            window_id,
+            state_id: VarInt(state_id),
+            slot_idx: -999,
+            button: 2,
+            mode: ClickMode::Drag,
+            slot_changes: vec![
+                SlotChange {
+                    idx: 9,
+                    item: Some(ItemStack::new(ItemKind::Diamond, 21, None)),
+                },
+                SlotChange {
+                    idx: 10,
+                    item: Some(ItemStack::new(ItemKind::Diamond, 21, None)),
+                },
+                SlotChange {
+                    idx: 11,
+                    item: Some(ItemStack::new(ItemKind::Diamond, 21, None)),
+                },
+            ],
+            carried_item: Some(ItemStack::new(ItemKind::Diamond, 1, None)),
+        },
+    );
+
+    app.update();
+    let sent_packets = client_helper.collect_received();
+    assert_eq!(sent_packets.0.len(), 0);
+
+    let cursor_item = app
+        .world
+        .get::<CursorItem>(client_ent)
+        .expect("could not find client");
+
+    assert_eq!(
+        cursor_item.0,
+        Some(ItemStack::new(ItemKind::Diamond, 1, None))
+    );
+
+    let inventory = app
+        .world
+        .get::<Inventory>(client_ent)
+        .expect("could not find inventory");
+
+    for i in 9..12 {
+        assert_eq!(
+            inventory.slot(i),
+            Some(&ItemStack::new(ItemKind::Diamond, 21, None))
+        );
+    }
+}
+
+#[test]
+fn dragging_items_in_open_inventory() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+    let inventory_ent = set_up_open_inventory(&mut app, client_ent);
+
+    app.world
+        .get_mut::<Inventory>(inventory_ent)
+        .unwrap()
+        .set_slot(1, ItemStack::new(ItemKind::Diamond, 63, None));
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+    client_helper.clear_received();
+
+    let cursor = Some(ItemStack::new(ItemKind::Diamond, 5, None));
+    app.world.get_mut::<CursorItem>(client_ent).unwrap().0 = cursor.clone();
+
+    let inv_state = app.world.get::<ClientInventoryState>(client_ent).unwrap();
+    let window_id = inv_state.window_id();
+    let state_id = inv_state.state_id().0;
+
+    // Right drag over two slots of the chest and one of the player's inventory.
+    send_drag(
+        &mut client_helper,
+        &[0, 1, 30],
+        cursor,
+        ClickSlotC2s {
+            window_id,
+            state_id: VarInt(state_id),
+            slot_idx: -999,
+            button: 6,
+            mode: ClickMode::Drag,
+            slot_changes: vec![
+                SlotChange {
+                    idx: 0,
+                    item: Some(ItemStack::new(ItemKind::Diamond, 1, None)),
+                },
+                SlotChange {
+                    idx: 1,
+                    item: Some(ItemStack::new(ItemKind::Diamond, 64, None)),
+                },
+                SlotChange {
+                    idx: 30,
+                    item: Some(ItemStack::new(ItemKind::Diamond, 1, None)),
+                },
+            ],
+            carried_item: Some(ItemStack::new(ItemKind::Diamond, 2, None)),
+        },
+    );
+
+    app.update();
+
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<InventoryS2c>(0);
+    sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(0);
+
+    let inventory = app.world.get::<Inventory>(inventory_ent).unwrap();
+    assert_eq!(
+        inventory.slot(0),
+        Some(&ItemStack::new(ItemKind::Diamond, 1, None))
+    );
+    assert_eq!(
+        inventory.slot(1),
+        Some(&ItemStack::new(ItemKind::Diamond, 64, None))
+    );
+
+    let player_inventory = app.world.get::<Inventory>(client_ent).unwrap();
+    assert_eq!(
+        player_inventory.slot(convert_to_player_slot_id(InventoryKind::Generic9x3, 30)),
+        Some(&ItemStack::new(ItemKind::Diamond, 1, None))
+    );
+
+    assert_eq!(
+        app.world.get::<CursorItem>(client_ent).unwrap().0,
+        Some(ItemStack::new(ItemKind::Diamond, 2, None))
+    );
+}
+
+#[test]
+fn dragging_items_mispredicted() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+    client_helper.clear_received();
+
+    let cursor = Some(ItemStack::new(ItemKind::Diamond, 10, None));
+    app.world.get_mut::<CursorItem>(client_ent).unwrap().0 = cursor.clone();
+
+    let inv_state = app.world.get::<ClientInventoryState>(client_ent).unwrap();
+    let window_id = inv_state.window_id();
+    let state_id = inv_state.state_id().0;
+
+    // The client claims to have put all the items in slot 9, but the drag was over
+    // slots 9 and 10.
+    send_drag(
+        &mut client_helper,
+        &[9, 10],
+        cursor.clone(),
+        ClickSlotC2s {
+            window_id,
+            state_id: VarInt(state_id),
+            slot_idx: -999,
+            button: 2,
+            mode: ClickMode::Drag,
+            slot_changes: vec![SlotChange {
+                idx: 9,
+                item: Some(ItemStack::new(ItemKind::Diamond, 10, None)),
+            }],
+            carried_item: None,
+        },
+    );
+
+    // An end without a start does nothing.
+    client_helper.send(&ClickSlotC2s {
+        window_id,
+        state_id: VarInt(state_id),
+        slot_idx: -999,
+        button: 2,
+        mode: ClickMode::Drag,
+        slot_changes: vec![SlotChange {
+            idx: 12,
+            item: Some(ItemStack::new(ItemKind::Diamond, 10, None)),
+        }],
+        carried_item: None,
+    });
+
+    app.update();
+
+    let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+    for i in 9..11 {
+        assert_eq!(
+            inventory.slot(i),
+            Some(&ItemStack::new(ItemKind::Diamond, 5, None))
+        );
+    }
+    assert_eq!(inventory.slot(12), None);
+    assert_eq!(app.world.get::<CursorItem>(client_ent).unwrap().0, None);
+
+    // The slots that the client got wrong are sent again.
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<InventoryS2c>(0);
+    sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(3);
+}
 
-        let mut inventory = app
+fn send_right_click(
+    app: &mut App,
+    client_ent: Entity,
+    client_helper: &mut MockClientHelper,
+    slot_idx: i16,
+    slot_changes: Vec<SlotChange>,
+    carried_item: Option<ItemStack>,
+) {
+    let inv_state = app.world.get::<ClientInventoryState>(client_ent).unwrap();
+
+    client_helper.send(&ClickSlotC2s {
+        window_id: inv_state.window_id(),
+        state_id: VarInt(inv_state.state_id().0),
+        slot_idx,
+        button: 1,
+        mode: ClickMode::Click,
+        slot_changes,
+        carried_item,
+    });
+}
+
+#[test]
+fn bundle_clicks() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+
+    let dirt = ItemStack::new(ItemKind::Dirt, 32, None);
+    let empty_bundle = ItemStack::new(ItemKind::Bundle, 1, None);
+
+    let mut full_bundle = empty_bundle.clone();
+    Bundle::try_insert(&mut full_bundle, &dirt).unwrap();
+
+    app.world
+        .get_mut::<Inventory>(client_ent)
+        .unwrap()
+        .set_slot(9, dirt.clone());
+    app.world.get_mut::<CursorItem>(client_ent).unwrap().0 = Some(empty_bundle.clone());
+
+    app.update();
+    client_helper.clear_received();
+
+    // Right clicking the dirt with the bundle puts it in the bundle.
+    send_right_click(
+        &mut app,
+        client_ent,
+        &mut client_helper,
+        9,
+        vec![SlotChange { idx: 9, item: None }],
+        Some(full_bundle.clone()),
+    );
+
+    app.update();
+
+    assert_eq!(
+        app.world.get::<Inventory>(client_ent).unwrap().slot(9),
+        None
+    );
+    assert_eq!(
+        app.world.get::<CursorItem>(client_ent).unwrap().0,
+        Some(full_bundle.clone())
+    );
+
+    // The client predicted the click correctly.
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(0);
+    sent_packets.assert_count::<InventoryS2c>(0);
+
+    // Right clicking an empty slot takes the dirt out again. The client claims to
+    // have gotten more dirt than was in the bundle.
+    send_right_click(
+        &mut app,
+        client_ent,
+        &mut client_helper,
+        10,
+        vec![SlotChange {
+            idx: 10,
+            item: Some(dirt.clone().with_count(64)),
+        }],
+        Some(empty_bundle.clone()),
+    );
+
+    app.update();
+
+    let emptied_bundle = empty_bundle.clone().with_nbt(compound! {});
+
+    assert_eq!(
+        app.world.get::<Inventory>(client_ent).unwrap().slot(10),
+        Some(&dirt)
+    );
+    assert_eq!(
+        app.world.get::<CursorItem>(client_ent).unwrap().0,
+        Some(emptied_bundle.clone())
+    );
+
+    // The slot and the cursor item that the client got wrong are sent again.
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(2);
+    sent_packets.assert_count::<InventoryS2c>(0);
+
+    // Right clicking a bundle in a slot with ender pearls puts as many as fit in
+    // it.
+    let pearls = ItemStack::new(ItemKind::EnderPearl, 16, None);
+
+    let mut bundle = empty_bundle.clone();
+    Bundle::try_insert(&mut bundle, &dirt).unwrap();
+
+    app.world
+        .get_mut::<Inventory>(client_ent)
+        .unwrap()
+        .set_slot(11, bundle.clone());
+    app.world.get_mut::<CursorItem>(client_ent).unwrap().0 = Some(pearls.clone());
+
+    app.update();
+    client_helper.clear_received();
+
+    Bundle::try_insert(&mut bundle, &pearls).unwrap();
+
+    send_right_click(
+        &mut app,
+        client_ent,
+        &mut client_helper,
+        11,
+        vec![SlotChange {
+            idx: 11,
+            item: Some(bundle.clone()),
+        }],
+        Some(pearls.clone().with_count(8)),
+    );
+
+    app.update();
+
+    assert_eq!(
+        Bundle::contents(
+            app.world
+                .get::<Inventory>(client_ent)
+                .unwrap()
+                .slot(11)
+                .unwrap()
+        ),
+        [pearls.clone().with_count(8), dirt.clone()]
+    );
+    assert_eq!(
+        app.world.get::<CursorItem>(client_ent).unwrap().0,
+        Some(pearls.with_count(8))
+    );
+
+    // Right clicking a full bundle does nothing.
+    send_right_click(
+        &mut app,
+        client_ent,
+        &mut client_helper,
+        11,
+        vec![],
+        Some(ItemStack::new(ItemKind::EnderPearl, 8, None)),
+    );
+
+    app.update();
+
+    assert_eq!(
+        app.world.get::<Inventory>(client_ent).unwrap().slot(11),
+        Some(&bundle)
+    );
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(0);
+}
+
+#[test]
+fn cursor_item_follows_clicks() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+
+    app.world
+        .get_mut::<Inventory>(client_ent)
+        .unwrap()
+        .set_slot(20, ItemStack::new(ItemKind::Diamond, 10, None));
+
+    app.update();
+    client_helper.clear_received();
+
+    let click = |slot_idx, slot_item: Option<ItemStack>, carried_item, state_id| ClickSlotC2s {
+        window_id: 0,
+        state_id: VarInt(state_id),
+        slot_idx,
+        button: 0,
+        mode: ClickMode::Click,
+        slot_changes: vec![SlotChange {
+            idx: slot_idx,
+            item: slot_item,
+        }],
+        carried_item,
+    };
+
+    // Pick up the stack.
+    let state_id = app
+        .world
+        .get::<ClientInventoryState>(client_ent)
+        .unwrap()
+        .state_id()
+        .0;
+
+    client_helper.send(&click(
+        20,
+        None,
+        Some(ItemStack::new(ItemKind::Diamond, 10, None)),
+        state_id,
+    ));
+
+    app.update();
+
+    assert_eq!(
+        app.world.get::<CursorItem>(client_ent).unwrap().0,
+        Some(ItemStack::new(ItemKind::Diamond, 10, None))
+    );
+    assert_eq!(
+        app.world.get::<Inventory>(client_ent).unwrap().slot(20),
+        None
+    );
+
+    // Put it down somewhere else.
+    client_helper.send(&click(
+        25,
+        Some(ItemStack::new(ItemKind::Diamond, 10, None)),
+        None,
+        state_id,
+    ));
+
+    app.update();
+
+    assert_eq!(app.world.get::<CursorItem>(client_ent).unwrap().0, None);
+    assert_eq!(
+        app.world.get::<Inventory>(client_ent).unwrap().slot(25),
+        Some(&ItemStack::new(ItemKind::Diamond, 10, None))
+    );
+
+    // The client already knows about all of this.
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<InventoryS2c>(0);
+    sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(0);
+}
+
+#[test]
+fn server_sets_cursor_item_in_open_inventory() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+    set_up_open_inventory(&mut app, client_ent);
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+    client_helper.clear_received();
+
+    app.world.get_mut::<CursorItem>(client_ent).unwrap().0 =
+        Some(ItemStack::new(ItemKind::Apple, 3, None));
+
+    app.update();
+
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(1);
+
+    let pkt = sent_packets.first::<ScreenHandlerSlotUpdateS2c>();
+    assert_eq!(pkt.window_id, -1);
+    assert_eq!(pkt.slot_idx, -1);
+    assert_eq!(
+        pkt.slot_data.as_ref(),
+        &Some(ItemStack::new(ItemKind::Apple, 3, None))
+    );
+}
+
+#[test]
+fn cursor_item_returned_on_close() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+    set_up_open_inventory(&mut app, client_ent);
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+
+    let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+    inventory.set_slot(36, ItemStack::new(ItemKind::Diamond, 60, None));
+    for idx in 37..45 {
+        inventory.set_slot(idx, ItemStack::new(ItemKind::Stone, 64, None));
+    }
+
+    app.world.get_mut::<CursorItem>(client_ent).unwrap().0 =
+        Some(ItemStack::new(ItemKind::Diamond, 10, None));
+
+    app.update();
+    client_helper.clear_received();
+
+    client_helper.send(&CloseHandledScreenC2s { window_id: 1 });
+
+    app.update();
+
+    assert!(app.world.get::<OpenInventory>(client_ent).is_none());
+    assert_eq!(app.world.get::<CursorItem>(client_ent).unwrap().0, None);
+
+    // The stack in the hotbar is filled first, then the first empty slot is used.
+    let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+    assert_eq!(
+        inventory.slot(36),
+        Some(&ItemStack::new(ItemKind::Diamond, 64, None))
+    );
+    assert_eq!(
+        inventory.slot(9),
+        Some(&ItemStack::new(ItemKind::Diamond, 6, None))
+    );
+
+    assert!(app.world.resource::<Events<DropItemEvent>>().is_empty());
+}
+
+#[test]
+fn cursor_item_dropped_on_close() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+    set_up_open_inventory(&mut app, client_ent);
+
+    app.world
+        .resource_mut::<InventorySettings>()
+        .cursor_item_on_close = CursorItemOnClose::Drop;
+
+    // Process a tick to get past the "on join" logic.
+    app.update();
+
+    app.world.get_mut::<CursorItem>(client_ent).unwrap().0 =
+        Some(ItemStack::new(ItemKind::Diamond, 10, None));
+
+    app.update();
+    client_helper.clear_received();
+
+    // Closing the window from the server also empties the cursor.
+    app.world.entity_mut(client_ent).remove::<OpenInventory>();
+
+    app.update();
+
+    // The drop can be cancelled in the event loop of the next tick.
+    app.update();
+
+    assert_eq!(app.world.get::<CursorItem>(client_ent).unwrap().0, None);
+    assert_eq!(
+        app.world
+            .get::<Inventory>(client_ent)
+            .unwrap()
+            .first_empty_slot(),
+        Some(0)
+    );
+
+    let events = app
+        .world
+        .resource::<Events<DropItemEvent>>()
+        .iter_current_update_events()
+        .collect::<Vec<_>>();
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].client, client_ent);
+    assert_eq!(events[0].slot, None);
+    assert_eq!(events[0].stack, ItemStack::new(ItemKind::Diamond, 10, None));
+}
+
+mod rejecting_clicks {
+    use std::collections::HashMap;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use valence_inventory::{ClickRejectedEvent, ClickRejection};
+
+    use super::*;
+
+    fn rejections(app: &App) -> Vec<ClickRejection> {
+        app.world
+            .resource::<Events<ClickRejectedEvent>>()
+            .iter_current_update_events()
+            .map(|event| event.reason.clone())
+            .collect()
+    }
+
+    #[test]
+    fn stale_state_id_resyncs() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .set_slot(20, ItemStack::new(ItemKind::Diamond, 2, None));
+
+        app.update();
+        client_helper.clear_received();
+
+        let state_id = app
+            .world
+            .get::<ClientInventoryState>(client_ent)
+            .unwrap()
+            .state_id();
+
+        client_helper.send(&ClickSlotC2s {
+            window_id: 0,
+            state_id: VarInt(state_id.0 - 1),
+            slot_idx: 20,
+            button: 0,
+            mode: ClickMode::Click,
+            slot_changes: vec![SlotChange {
+                idx: 20,
+                item: None,
+            }],
+            carried_item: Some(ItemStack::new(ItemKind::Diamond, 2, None)),
+        });
+
+        app.update();
+
+        assert_eq!(rejections(&app), [ClickRejection::StaleStateId]);
+        client_helper
+            .collect_received()
+            .assert_count::<InventoryS2c>(1);
+
+        assert_eq!(
+            app.world.get::<Inventory>(client_ent).unwrap().slot(20),
+            Some(&ItemStack::new(ItemKind::Diamond, 2, None))
+        );
+        assert_eq!(app.world.get::<CursorItem>(client_ent).unwrap().0, None);
+    }
+
+    #[test]
+    fn click_in_closed_window_is_ignored() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .set_slot(20, ItemStack::new(ItemKind::Diamond, 2, None));
+
+        app.update();
+
+        let state_id = app
             .world
-            .get_mut::<Inventory>(client_ent)
-            .expect("could not find inventory");
-        inventory.set_slot(36, ItemStack::new(ItemKind::IronIngot, 32, None));
+            .get::<ClientInventoryState>(client_ent)
+            .unwrap()
+            .state_id();
 
-        client_helper.send(&PlayerActionC2s {
-            action: PlayerAction::DropAllItems,
-            position: BlockPos::new(0, 0, 0),
-            direction: Direction::Down,
-            sequence: VarInt(0),
+        client_helper.send(&ClickSlotC2s {
+            window_id: 1,
+            state_id: VarInt(state_id.0),
+            slot_idx: 20,
+            button: 0,
+            mode: ClickMode::Click,
+            slot_changes: vec![SlotChange {
+                idx: 20,
+                item: None,
+            }],
+            carried_item: Some(ItemStack::new(ItemKind::Diamond, 2, None)),
         });
 
         app.update();
 
-        // Make assertions
-        let held = app
-            .world
-            .get::<HeldItem>(client_ent)
-            .expect("could not find client");
-        assert_eq!(held.slot(), 36);
-        let inventory = app
-            .world
-            .get::<Inventory>(client_ent)
-            .expect("could not find inventory");
-        assert_eq!(inventory.slot(36), None);
-        let events = app
-            .world
-            .get_resource::<Events<DropItemStackEvent>>()
-            .expect("expected drop item stack events");
-        let events = events.iter_current_update_events().collect::<Vec<_>>();
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0].client, client_ent);
-        assert_eq!(events[0].from_slot, Some(36));
+        assert_eq!(rejections(&app), [ClickRejection::WrongWindow]);
+        assert_eq!(app.world.get::<CursorItem>(client_ent).unwrap().0, None);
+    }
+
+    #[test]
+    fn invalid_and_mispredicted_clicks_are_reported() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let inventory_ent = set_up_open_inventory(&mut app, client_ent);
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+
+        app.world
+            .get_mut::<Inventory>(inventory_ent)
+            .unwrap()
+            .set_slot(0, ItemStack::new(ItemKind::Diamond, 2, None));
+
+        app.update();
+
+        // One past the last slot of the window.
+        send_window_click(
+            &mut app,
+            client_ent,
+            &mut client_helper,
+            63,
+            ClickMode::Click,
+            vec![SlotChange {
+                idx: 63,
+                item: None,
+            }],
+            None,
+        );
+
+        app.update();
+
+        assert!(matches!(
+            rejections(&app).as_slice(),
+            [ClickRejection::Invalid(_)]
+        ));
+
+        // The client claims that right clicking the diamonds with dirt turns a
+        // dirt into a diamond, but the stacks are swapped instead.
+        app.world.get_mut::<CursorItem>(client_ent).unwrap().0 =
+            Some(ItemStack::new(ItemKind::Dirt, 2, None));
+
+        app.update();
+
+        send_right_click(
+            &mut app,
+            client_ent,
+            &mut client_helper,
+            0,
+            vec![SlotChange {
+                idx: 0,
+                item: Some(ItemStack::new(ItemKind::Diamond, 3, None)),
+            }],
+            Some(ItemStack::new(ItemKind::Dirt, 1, None)),
+        );
+
+        app.update();
+
+        assert_eq!(rejections(&app), [ClickRejection::Mispredicted]);
         assert_eq!(
-            events[0].stack,
-            ItemStack::new(ItemKind::IronIngot, 32, None)
+            app.world.get::<Inventory>(inventory_ent).unwrap().slot(0),
+            Some(&ItemStack::new(ItemKind::Dirt, 2, None))
         );
+        assert_eq!(
+            app.world.get::<CursorItem>(client_ent).unwrap().0,
+            Some(ItemStack::new(ItemKind::Diamond, 2, None))
+        );
+    }
+
+    fn random_stack(rng: &mut StdRng) -> Option<ItemStack> {
+        let kind = match rng.gen_range(0..5) {
+            0 => return None,
+            1 => ItemKind::Diamond,
+            2 => ItemKind::Dirt,
+            3 => ItemKind::EnderPearl,
+            _ => ItemKind::DiamondHelmet,
+        };
+
+        Some(ItemStack::new(kind, rng.gen_range(1..=64), None))
+    }
+
+    fn random_click(
+        rng: &mut StdRng,
+        window_id: u8,
+        state_id: i32,
+        slot_count: i16,
+    ) -> ClickSlotC2s {
+        let mode = match rng.gen_range(0..7) {
+            0 => ClickMode::Click,
+            1 => ClickMode::ShiftClick,
+            2 => ClickMode::Hotbar,
+            3 => ClickMode::CreativeMiddleClick,
+            4 => ClickMode::DropKey,
+            5 => ClickMode::Drag,
+            _ => ClickMode::DoubleClick,
+        };
+
+        let random_slot = |rng: &mut StdRng| {
+            if rng.gen_bool(0.05) {
+                -999
+            } else {
+                rng.gen_range(-1..=slot_count)
+            }
+        };
+
+        let slot_idx = random_slot(rng);
+
+        let slot_changes = (0..rng.gen_range(0..4))
+            .map(|_| SlotChange {
+                idx: if rng.gen_bool(0.5) {
+                    slot_idx
+                } else {
+                    random_slot(rng)
+                },
+                item: random_stack(rng),
+            })
+            .collect();
+
+        ClickSlotC2s {
+            window_id: if rng.gen_bool(0.05) { 7 } else { window_id },
+            state_id: VarInt(if rng.gen_bool(0.1) {
+                state_id - 1
+            } else {
+                state_id
+            }),
+            slot_idx,
+            button: rng.gen_range(-1..=10),
+            mode,
+            slot_changes,
+            carried_item: random_stack(rng),
+        }
+    }
+
+    fn count_items<'a>(
+        counts: &mut HashMap<ItemKind, u32>,
+        stacks: impl IntoIterator<Item = &'a ItemStack>,
+    ) {
+        for stack in stacks {
+            *counts.entry(stack.item).or_default() += stack.count() as u32;
+        }
     }
 
+    /// Sends lots of random clicks and checks that no items are ever created.
     #[test]
-    fn should_drop_item_stack_set_creative_mode_slot() {
+    fn random_clicks_never_duplicate_items() {
         let mut app = App::new();
         let (client_ent, mut client_helper) = scenario_single_client(&mut app);
 
         // Process a tick to get past the "on join" logic.
         app.update();
+
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        inventory.set_slot(9, ItemStack::new(ItemKind::Diamond, 64, None));
+        inventory.set_slot(10, ItemStack::new(ItemKind::Diamond, 20, None));
+        inventory.set_slot(11, ItemStack::new(ItemKind::Dirt, 33, None));
+        inventory.set_slot(36, ItemStack::new(ItemKind::EnderPearl, 16, None));
+        inventory.set_slot(37, ItemStack::new(ItemKind::DiamondHelmet, 1, None));
+
+        let mut chest = Inventory::new(InventoryKind::Generic9x3);
+        chest.set_slot(0, ItemStack::new(ItemKind::Dirt, 64, None));
+        chest.set_slot(1, ItemStack::new(ItemKind::EnderPearl, 5, None));
+        let chest_ent = app.world.spawn(chest).id();
+
+        let totals = |app: &App, dropped: &HashMap<ItemKind, u32>| {
+            let mut counts = dropped.clone();
+            let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+            let chest = app.world.get::<Inventory>(chest_ent).unwrap();
+            let cursor_item = app.world.get::<CursorItem>(client_ent).unwrap();
+
+            count_items(&mut counts, inventory.slots().flatten());
+            count_items(&mut counts, chest.slots().flatten());
+            count_items(&mut counts, &cursor_item.0);
+            counts
+        };
+
+        let initial = totals(&app, &HashMap::new());
+        let mut dropped = HashMap::new();
+        let mut rng = StdRng::seed_from_u64(1);
+
+        for open_chest in [false, true] {
+            if open_chest {
+                app.world
+                    .entity_mut(client_ent)
+                    .insert(OpenInventory::new(chest_ent));
+            }
+
+            app.update();
+
+            let slot_count = if open_chest { 63 } else { 46 };
+
+            for _ in 0..1000 {
+                let inv_state = app.world.get::<ClientInventoryState>(client_ent).unwrap();
+                let pkt = random_click(
+                    &mut rng,
+                    inv_state.window_id(),
+                    inv_state.state_id().0,
+                    slot_count,
+                );
+
+                client_helper.send(&pkt);
+
+                app.update();
+
+                count_items(
+                    &mut dropped,
+                    app.world
+                        .resource::<Events<DropItemEvent>>()
+                        .iter_current_update_events()
+                        .map(|event| &event.stack),
+                );
+
+                let counts = totals(&app, &dropped);
+
+                for (kind, count) in &counts {
+                    let initial = initial.get(kind).copied().unwrap_or(0);
+                    assert!(
+                        *count <= initial,
+                        "{count} {kind:?} after {pkt:#?}, but there were only {initial}"
+                    );
+                }
+            }
+        }
+
         client_helper.clear_received();
+    }
+}
 
-        app.world.entity_mut(client_ent).insert(GameMode::Creative);
+mod slot_change_events {
+    use valence_inventory::{SlotChangeCause, SlotChangeEvent};
 
-        client_helper.send(&CreativeInventoryActionC2s {
-            slot: -1,
-            clicked_item: Some(ItemStack::new(ItemKind::IronIngot, 32, None)),
-        });
+    use super::*;
 
+    fn slot_changes(app: &App) -> Vec<SlotChangeEvent> {
+        app.world
+            .resource::<Events<SlotChangeEvent>>()
+            .iter_current_update_events()
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn click_and_server_set_are_labeled() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        // Process a tick to get past the "on join" logic.
         app.update();
 
-        // Make assertions
-        let events = app
-            .world
-            .get_resource::<Events<DropItemStackEvent>>()
-            .expect("expected drop item stack events")
-            .iter_current_update_events()
-            .collect::<Vec<_>>();
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .set_slot(20, ItemStack::new(ItemKind::Diamond, 2, None));
+
+        app.update();
 
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0].client, client_ent);
-        assert_eq!(events[0].from_slot, None);
         assert_eq!(
-            events[0].stack,
-            ItemStack::new(ItemKind::IronIngot, 32, None)
+            slot_changes(&app),
+            [SlotChangeEvent::Slot {
+                inventory: client_ent,
+                slot: 20,
+                old: None,
+                new: Some(ItemStack::new(ItemKind::Diamond, 2, None)),
+                cause: SlotChangeCause::Server,
+            }]
+        );
+
+        send_window_click(
+            &mut app,
+            client_ent,
+            &mut client_helper,
+            20,
+            ClickMode::Click,
+            vec![SlotChange {
+                idx: 20,
+                item: None,
+            }],
+            Some(ItemStack::new(ItemKind::Diamond, 2, None)),
+        );
+
+        app.update();
+
+        assert_eq!(
+            slot_changes(&app),
+            [SlotChangeEvent::Slot {
+                inventory: client_ent,
+                slot: 20,
+                old: Some(ItemStack::new(ItemKind::Diamond, 2, None)),
+                new: None,
+                cause: SlotChangeCause::Click(client_ent),
+            }]
         );
     }
 
     #[test]
-    fn should_drop_item_stack_click_container_outside() {
+    fn drag_is_labeled() {
         let mut app = App::new();
         let (client_ent, mut client_helper) = scenario_single_client(&mut app);
 
         // Process a tick to get past the "on join" logic.
         app.update();
-        client_helper.clear_received();
 
-        let mut cursor_item = app
-            .world
-            .get_mut::<CursorItem>(client_ent)
-            .expect("could not find client");
-        cursor_item.0 = Some(ItemStack::new(ItemKind::IronIngot, 32, None));
-        let inv_state = app
-            .world
-            .get_mut::<ClientInventoryState>(client_ent)
-            .expect("could not find client");
+        let cursor = Some(ItemStack::new(ItemKind::Diamond, 2, None));
+        app.world.get_mut::<CursorItem>(client_ent).unwrap().0 = cursor.clone();
+
+        let inv_state = app.world.get::<ClientInventoryState>(client_ent).unwrap();
+        let window_id = inv_state.window_id();
         let state_id = inv_state.state_id().0;
 
-        client_helper.send(&ClickSlotC2s {
-            window_id: 0,
-            state_id: VarInt(state_id),
-            slot_idx: -999,
-            button: 0,
-            mode: ClickMode::Click,
-            slot_changes: vec![],
-            carried_item: None,
-        });
+        send_drag(
+            &mut client_helper,
+            &[9, 10],
+            cursor,
+            ClickSlotC2s {
+                window_id,
+                state_id: VarInt(state_id),
+                slot_idx: -999,
+                button: 2,
+                mode: ClickMode::Drag,
+                slot_changes: vec![
+                    SlotChange {
+                        idx: 9,
+                        item: Some(ItemStack::new(ItemKind::Diamond, 1, None)),
+                    },
+                    SlotChange {
+                        idx: 10,
+                        item: Some(ItemStack::new(ItemKind::Diamond, 1, None)),
+                    },
+                ],
+                carried_item: None,
+            },
+        );
 
         app.update();
 
-        // Make assertions
-        let cursor_item = app
-            .world
-            .get::<CursorItem>(client_ent)
-            .expect("could not find client");
+        let events = slot_changes(&app);
 
-        assert_eq!(cursor_item.0, None);
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .all(|event| event.cause() == SlotChangeCause::Drag(client_ent)));
+    }
 
-        let events = app
-            .world
-            .get_resource::<Events<DropItemStackEvent>>()
-            .expect("expected drop item stack events");
+    #[test]
+    fn clear_sends_one_bulk_change() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
 
-        let events = events.iter_current_update_events().collect::<Vec<_>>();
+        // Process a tick to get past the "on join" logic.
+        app.update();
+
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        for idx in 9..36 {
+            inventory.set_slot(idx, ItemStack::new(ItemKind::Diamond, 1, None));
+        }
+
+        app.update();
+
+        assert_eq!(slot_changes(&app).len(), 27);
+
+        app.world.get_mut::<Inventory>(client_ent).unwrap().clear();
+
+        app.update();
 
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0].client, client_ent);
-        assert_eq!(events[0].from_slot, None);
         assert_eq!(
-            events[0].stack,
-            ItemStack::new(ItemKind::IronIngot, 32, None)
+            slot_changes(&app),
+            [SlotChangeEvent::BulkChange {
+                inventory: client_ent,
+                cause: SlotChangeCause::Server,
+            }]
         );
+        assert_eq!(
+            app.world
+                .get::<Inventory>(client_ent)
+                .unwrap()
+                .slots()
+                .flatten()
+                .count(),
+            0
+        );
+    }
+}
+
+mod quick_moving {
+    use valence_inventory::quick_move::{QuickMove, QuickMoveRules, QuickMoveTarget};
+    use valence_inventory::InventoryWindow;
+
+    use super::*;
+
+    fn stack(kind: ItemKind, count: u8) -> Option<ItemStack> {
+        Some(ItemStack::new(kind, count, None))
+    }
+
+    /// Sends a shift click with the changes that the vanilla client predicts,
+    /// and checks that the server agrees with them.
+    fn shift_click(
+        app: &mut App,
+        client_ent: Entity,
+        client_helper: &mut MockClientHelper,
+        slot_idx: i16,
+        slot_changes: Vec<(i16, Option<ItemStack>)>,
+    ) {
+        client_helper.clear_received();
+
+        send_window_click(
+            app,
+            client_ent,
+            client_helper,
+            slot_idx,
+            ClickMode::ShiftClick,
+            slot_changes
+                .into_iter()
+                .map(|(idx, item)| SlotChange { idx, item })
+                .collect(),
+            None,
+        );
+
+        app.update();
+
+        let sent_packets = client_helper.collect_received();
+        sent_packets.assert_count::<ScreenHandlerSlotUpdateS2c>(0);
+        sent_packets.assert_count::<InventoryS2c>(0);
     }
 
     #[test]
-    fn should_drop_item_click_container_with_dropkey_single() {
+    fn vanilla_chest_sequence() {
         let mut app = App::new();
         let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let chest_ent = set_up_open_inventory(&mut app, client_ent);
+
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        inventory.set_slot(9, stack(ItemKind::OakLog, 64));
+        inventory.set_slot(36, stack(ItemKind::Cobblestone, 64));
+        inventory.set_slot(37, stack(ItemKind::Cobblestone, 20));
+
+        let mut chest = app.world.get_mut::<Inventory>(chest_ent).unwrap();
+        chest.set_slot(0, stack(ItemKind::Cobblestone, 50));
+        chest.set_slot(5, stack(ItemKind::OakLog, 10));
 
-        // Process a tick to get past the "on join" logic.
         app.update();
-        client_helper.clear_received();
 
-        let inv_state = app
-            .world
-            .get_mut::<ClientInventoryState>(client_ent)
-            .expect("could not find client");
+        // The first hotbar slot fills up the chest's stack and the rest goes in the
+        // first empty slot.
+        shift_click(
+            &mut app,
+            client_ent,
+            &mut client_helper,
+            54,
+            vec![
+                (0, stack(ItemKind::Cobblestone, 64)),
+                (1, stack(ItemKind::Cobblestone, 50)),
+                (54, None),
+            ],
+        );
 
-        let state_id = inv_state.state_id().0;
+        // The full stack of logs is skipped, and the logs go in the last empty slot
+        // of the hotbar.
+        shift_click(
+            &mut app,
+            client_ent,
+            &mut client_helper,
+            5,
+            vec![(5, None), (62, stack(ItemKind::OakLog, 10))],
+        );
 
-        let mut inventory = app
-            .world
-            .get_mut::<Inventory>(client_ent)
-            .expect("could not find inventory");
+        shift_click(
+            &mut app,
+            client_ent,
+            &mut client_helper,
+            1,
+            vec![
+                (1, None),
+                (55, stack(ItemKind::Cobblestone, 64)),
+                (61, stack(ItemKind::Cobblestone, 6)),
+            ],
+        );
 
-        inventory.set_slot(40, ItemStack::new(ItemKind::IronIngot, 32, None));
+        let chest = app.world.get::<Inventory>(chest_ent).unwrap();
+        let chest_slots: Vec<_> = chest.slots().collect();
 
-        client_helper.send(&ClickSlotC2s {
-            window_id: 0,
-            slot_idx: 40,
-            button: 0,
-            mode: ClickMode::DropKey,
-            state_id: VarInt(state_id),
-            slot_changes: vec![SlotChange {
-                idx: 40,
-                item: Some(ItemStack::new(ItemKind::IronIngot, 31, None)),
-            }],
-            carried_item: None,
-        });
+        let mut expected_chest = vec![None; 27];
+        expected_chest[0] = stack(ItemKind::Cobblestone, 64);
+        assert_eq!(
+            chest_slots,
+            expected_chest
+                .iter()
+                .map(Option::as_ref)
+                .collect::<Vec<_>>()
+        );
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        let player_slots: Vec<_> = inventory.slots().collect();
+
+        let mut expected_player = vec![None; 46];
+        expected_player[9] = stack(ItemKind::OakLog, 64);
+        expected_player[37] = stack(ItemKind::Cobblestone, 64);
+        expected_player[43] = stack(ItemKind::Cobblestone, 6);
+        expected_player[44] = stack(ItemKind::OakLog, 10);
+        assert_eq!(
+            player_slots,
+            expected_player
+                .iter()
+                .map(Option::as_ref)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn vanilla_player_inventory_sequence() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        inventory.set_slot(9, stack(ItemKind::Dirt, 60));
+        inventory.set_slot(36, stack(ItemKind::Dirt, 30));
+        inventory.set_slot(40, stack(ItemKind::Shield, 1));
 
         app.update();
 
-        // Make assertions
-        let events = app
-            .world
-            .get_resource::<Events<DropItemStackEvent>>()
-            .expect("expected drop item stack events");
+        // The hotbar goes to the main inventory.
+        shift_click(
+            &mut app,
+            client_ent,
+            &mut client_helper,
+            36,
+            vec![
+                (9, stack(ItemKind::Dirt, 64)),
+                (10, stack(ItemKind::Dirt, 26)),
+                (36, None),
+            ],
+        );
 
-        let events = events.iter_current_update_events().collect::<Vec<_>>();
+        // Shields go to the offhand when it's empty.
+        shift_click(
+            &mut app,
+            client_ent,
+            &mut client_helper,
+            40,
+            vec![(40, None), (45, stack(ItemKind::Shield, 1))],
+        );
+
+        // The main inventory goes to the hotbar.
+        shift_click(
+            &mut app,
+            client_ent,
+            &mut client_helper,
+            10,
+            vec![(10, None), (36, stack(ItemKind::Dirt, 26))],
+        );
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(9), stack(ItemKind::Dirt, 64).as_ref());
+        assert_eq!(inventory.slot(10), None);
+        assert_eq!(inventory.slot(36), stack(ItemKind::Dirt, 26).as_ref());
+        assert_eq!(inventory.slot(40), None);
+        assert_eq!(inventory.slot(45), stack(ItemKind::Shield, 1).as_ref());
+    }
+
+    #[test]
+    fn shift_click_with_nowhere_to_go() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let chest_ent = set_up_open_inventory(&mut app, client_ent);
+
+        let mut chest = app.world.get_mut::<Inventory>(chest_ent).unwrap();
+        for idx in 0..27 {
+            chest.set_slot(idx, stack(ItemKind::Stone, 64));
+        }
+
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .set_slot(9, stack(ItemKind::Dirt, 1));
+
+        app.update();
+
+        // Nothing changes, so the client doesn't send any changes.
+        shift_click(&mut app, client_ent, &mut client_helper, 27, vec![]);
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(9), stack(ItemKind::Dirt, 1).as_ref());
+    }
+
+    /// Moves everything to the middle slot of a chest.
+    struct MiddleSlot;
+
+    impl QuickMove for MiddleSlot {
+        fn target(
+            &self,
+            _window: &InventoryWindow,
+            _slots: &[Option<ItemStack>],
+            idx: u16,
+            _stack: &ItemStack,
+        ) -> Option<QuickMoveTarget> {
+            (idx != 13).then(|| QuickMoveTarget::new(13..14))
+        }
+    }
+
+    #[test]
+    fn custom_rules() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let chest_ent = set_up_open_inventory(&mut app, client_ent);
+
+        app.world
+            .entity_mut(chest_ent)
+            .insert(QuickMoveRules::new(MiddleSlot));
+
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .set_slot(9, stack(ItemKind::Dirt, 16));
+
+        app.update();
+        client_helper.clear_received();
 
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0].client, client_ent);
-        assert_eq!(events[0].from_slot, Some(40));
-        assert_eq!(
-            events[0].stack,
-            ItemStack::new(ItemKind::IronIngot, 1, None)
+        // The client predicts that the dirt goes in the first slot.
+        send_window_click(
+            &mut app,
+            client_ent,
+            &mut client_helper,
+            27,
+            ClickMode::ShiftClick,
+            vec![
+                SlotChange {
+                    idx: 0,
+                    item: stack(ItemKind::Dirt, 16),
+                },
+                SlotChange {
+                    idx: 27,
+                    item: None,
+                },
+            ],
+            None,
         );
+
+        app.update();
+
+        let chest = app.world.get::<Inventory>(chest_ent).unwrap();
+        assert_eq!(chest.slot(0), None);
+        assert_eq!(chest.slot(13), stack(ItemKind::Dirt, 16).as_ref());
+
+        // The client is sent the slots that it got wrong.
+        let updates: Vec<_> = client_helper
+            .collect_received()
+            .0
+            .iter()
+            .filter(|f| f.id == ScreenHandlerSlotUpdateS2c::ID)
+            .map(|f| f.decode::<ScreenHandlerSlotUpdateS2c>().unwrap())
+            .map(|pkt| (pkt.slot_idx, pkt.slot_data.into_owned()))
+            .collect();
+
+        assert_eq!(updates, vec![(0, None), (13, stack(ItemKind::Dirt, 16))]);
+    }
+
+    /// Moves everything to the first slot of the player's main inventory.
+    struct FirstPlayerSlot;
+
+    impl QuickMove for FirstPlayerSlot {
+        fn target(
+            &self,
+            window: &InventoryWindow,
+            _slots: &[Option<ItemStack>],
+            _idx: u16,
+            _stack: &ItemStack,
+        ) -> Option<QuickMoveTarget> {
+            let start = window.slot_count() - 36;
+            Some(QuickMoveTarget::new(start..start + 1))
+        }
     }
 
     #[test]
-    fn should_drop_item_stack_click_container_with_dropkey() {
+    fn custom_rules_apply_to_results() {
         let mut app = App::new();
         let (client_ent, mut client_helper) = scenario_single_client(&mut app);
 
-        // Process a tick to get past the "on join" logic.
         app.update();
-        client_helper.clear_received();
 
-        let inv_state = app
-            .world
-            .get_mut::<ClientInventoryState>(client_ent)
-            .expect("could not find client");
+        let slabs = stack(ItemKind::StoneSlab, 2);
 
-        let state_id = inv_state.state_id().0;
+        let mut stonecutter = Inventory::new(InventoryKind::Stonecutter);
+        stonecutter.set_slot(stonecutter::INPUT_SLOT, stack(ItemKind::Stone, 3));
+        stonecutter.set_slot(stonecutter::OUTPUT_SLOT, slabs.clone());
 
-        let mut inventory = app
+        let stonecutter_ent = app
             .world
-            .get_mut::<Inventory>(client_ent)
-            .expect("could not find inventory");
-
-        inventory.set_slot(40, ItemStack::new(ItemKind::IronIngot, 32, None));
+            .spawn((stonecutter, QuickMoveRules::new(FirstPlayerSlot)))
+            .id();
 
-        client_helper.send(&ClickSlotC2s {
-            window_id: 0,
-            slot_idx: 40,
-            button: 1, // pressing control
-            mode: ClickMode::DropKey,
-            state_id: VarInt(state_id),
-            slot_changes: vec![SlotChange {
-                idx: 40,
-                item: None,
-            }],
-            carried_item: None,
-        });
+        app.world
+            .entity_mut(client_ent)
+            .insert(OpenInventory::new(stonecutter_ent));
 
         app.update();
+        client_helper.clear_received();
 
-        // Make assertions
-        let events = app
-            .world
-            .get_resource::<Events<DropItemStackEvent>>()
-            .expect("expected drop item stack events");
+        // The client predicts that the slabs go to the end of the hotbar.
+        send_window_click(
+            &mut app,
+            client_ent,
+            &mut client_helper,
+            stonecutter::OUTPUT_SLOT as i16,
+            ClickMode::ShiftClick,
+            vec![
+                SlotChange {
+                    idx: 0,
+                    item: stack(ItemKind::Stone, 2),
+                },
+                SlotChange { idx: 1, item: None },
+                SlotChange {
+                    idx: 37,
+                    item: slabs.clone(),
+                },
+            ],
+            None,
+        );
 
-        let events = events.iter_current_update_events().collect::<Vec<_>>();
+        app.update();
 
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0].client, client_ent);
-        assert_eq!(events[0].from_slot, Some(40));
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(9), slabs.as_ref());
+        assert_eq!(inventory.slot(44), None);
+
+        let stonecutter = app.world.get::<Inventory>(stonecutter_ent).unwrap();
         assert_eq!(
-            events[0].stack,
-            ItemStack::new(ItemKind::IronIngot, 32, None)
+            stonecutter.slot(stonecutter::INPUT_SLOT),
+            stack(ItemKind::Stone, 2).as_ref()
         );
+        assert_eq!(stonecutter.slot(stonecutter::OUTPUT_SLOT), None);
+
+        // The client is sent the player slots that it got wrong.
+        let updates: Vec<_> = client_helper
+            .collect_received()
+            .0
+            .iter()
+            .filter(|f| f.id == ScreenHandlerSlotUpdateS2c::ID)
+            .map(|f| f.decode::<ScreenHandlerSlotUpdateS2c>().unwrap())
+            .filter(|pkt| pkt.slot_idx >= 2)
+            .map(|pkt| (pkt.slot_idx, pkt.slot_data.into_owned()))
+            .collect();
+
+        assert_eq!(updates, vec![(2, slabs), (37, None)]);
     }
+}
 
-    #[test]
-    fn should_drop_item_player_open_inventory_with_dropkey() {
-        // The item should be dropped successfully, if the player has an inventory open
-        // and the slot id points to his inventory.
+mod crafting {
+    use valence_core::ident::Ident;
+    use valence_inventory::crafting::RESULT_SLOT;
+    use valence_inventory::packet::{CraftFailedResponseS2c, CraftRequestC2s};
+    use valence_inventory::recipe::{
+        CraftingCategory, ShapedRecipe, ShapelessRecipe, UnlockedRecipes,
+    };
 
-        let mut app = App::new();
-        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+    use super::*;
 
-        // Process a tick to get past the "on join" logic.
-        app.update();
+    fn stack(kind: ItemKind, count: u8) -> Option<ItemStack> {
+        Some(ItemStack::new(kind, count, None))
+    }
 
-        let mut inventory = app
-            .world
-            .get_mut::<Inventory>(client_ent)
-            .expect("could not find inventory");
+    fn crafting_table_recipe() -> RecipeData {
+        RecipeData::CraftingShaped(ShapedRecipe {
+            group: String::new(),
+            category: CraftingCategory::Misc,
+            width: 2,
+            height: 2,
+            ingredients: vec![Ingredient(vec![ItemKind::OakPlanks]); 4],
+            result: ItemStack::new(ItemKind::CraftingTable, 1, None),
+            show_notification: true,
+        })
+    }
 
-        inventory.set_slot(
-            convert_to_player_slot_id(InventoryKind::Generic9x3, 50),
-            ItemStack::new(ItemKind::IronIngot, 32, None),
-        );
+    fn slot_updates(client_helper: &mut MockClientHelper) -> Vec<(i16, Option<ItemStack>)> {
+        client_helper
+            .collect_received()
+            .0
+            .iter()
+            .filter(|f| f.id == ScreenHandlerSlotUpdateS2c::ID)
+            .map(|f| f.decode::<ScreenHandlerSlotUpdateS2c>().unwrap())
+            .map(|pkt| (pkt.slot_idx, pkt.slot_data.into_owned()))
+            .collect()
+    }
 
-        let _inventory_ent = set_up_open_inventory(&mut app, client_ent);
+    #[test]
+    fn craft_shaped_recipe() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
 
-        app.update();
+        app.world
+            .resource_mut::<RecipeRegistry>()
+            .insert(ident!("crafting_table"), crafting_table_recipe());
 
+        app.update();
         client_helper.clear_received();
 
-        let inv_state = app
-            .world
-            .get_mut::<ClientInventoryState>(client_ent)
-            .expect("could not find client");
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        for idx in 1..=4 {
+            inventory.set_slot(idx, ItemStack::new(ItemKind::OakPlanks, 3, None));
+        }
 
-        let state_id = inv_state.state_id().0;
-        let window_id = inv_state.window_id();
+        app.update();
 
-        client_helper.send(&ClickSlotC2s {
-            window_id,
-            state_id: VarInt(state_id),
-            slot_idx: 50, // not pressing control
-            button: 0,
-            mode: ClickMode::DropKey,
-            slot_changes: vec![SlotChange {
-                idx: 50,
-                item: Some(ItemStack::new(ItemKind::IronIngot, 31, None)),
-            }],
-            carried_item: None,
-        });
+        // The client is sent the result along with the grid.
+        let table = stack(ItemKind::CraftingTable, 1);
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(RESULT_SLOT), table.as_ref());
+        assert!(slot_updates(&mut client_helper).contains(&(0, table.clone())));
+
+        // Take the result. The client doesn't know what the grid makes next.
+        send_window_click(
+            &mut app,
+            client_ent,
+            &mut client_helper,
+            0,
+            ClickMode::Click,
+            (0..=4)
+                .map(|idx| SlotChange {
+                    idx,
+                    item: if idx == 0 {
+                        None
+                    } else {
+                        stack(ItemKind::OakPlanks, 2)
+                    },
+                })
+                .collect(),
+            table.clone(),
+        );
 
         app.update();
 
-        // Make assertions
-        let events = app
-            .world
-            .get_resource::<Events<DropItemStackEvent>>()
-            .expect("expected drop item stack events");
-
-        let player_inventory = app
-            .world
-            .get::<Inventory>(client_ent)
-            .expect("could not find inventory");
+        let cursor_item = app.world.get::<CursorItem>(client_ent).unwrap();
+        assert_eq!(cursor_item.0, table);
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(RESULT_SLOT), table.as_ref());
+        for idx in 1..=4 {
+            assert_eq!(inventory.slot(idx), stack(ItemKind::OakPlanks, 2).as_ref());
+        }
+
+        // Shift clicking crafts until the planks run out, starting at the end of the
+        // hotbar.
+        send_window_click(
+            &mut app,
+            client_ent,
+            &mut client_helper,
+            0,
+            ClickMode::ShiftClick,
+            vec![],
+            None,
+        );
 
-        let events = events.iter_current_update_events().collect::<Vec<_>>();
+        app.update();
 
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0].client, client_ent);
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
         assert_eq!(
-            events[0].from_slot,
-            Some(convert_to_player_slot_id(InventoryKind::Generic9x3, 50))
+            inventory.slot(44),
+            stack(ItemKind::CraftingTable, 2).as_ref()
         );
+        for idx in 0..=4 {
+            assert_eq!(inventory.slot(idx), None);
+        }
 
-        assert_eq!(
-            events[0].stack,
-            ItemStack::new(ItemKind::IronIngot, 1, None)
-        );
+        // The cursor item isn't touched.
+        let cursor_item = app.world.get::<CursorItem>(client_ent).unwrap();
+        assert_eq!(cursor_item.0, table);
+    }
 
-        // Also make sure that the player inventory was updated correctly.
-        let expected_player_slot_id = convert_to_player_slot_id(InventoryKind::Generic9x3, 50);
-        assert_eq!(
-            player_inventory.slot(expected_player_slot_id),
-            Some(&ItemStack::new(ItemKind::IronIngot, 31, None))
+    #[test]
+    fn craft_shapeless_recipe() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.world.resource_mut::<RecipeRegistry>().insert(
+            ident!("cake"),
+            RecipeData::CraftingShapeless(ShapelessRecipe {
+                group: String::new(),
+                category: CraftingCategory::Misc,
+                ingredients: vec![
+                    Ingredient(vec![ItemKind::MilkBucket]),
+                    Ingredient(vec![ItemKind::Sugar]),
+                    Ingredient(vec![ItemKind::Egg]),
+                ],
+                result: ItemStack::new(ItemKind::Cake, 1, None),
+            }),
         );
-    }
-}
 
-#[test]
-fn should_drop_item_stack_player_open_inventory_with_dropkey() {
-    // The item stack should be dropped successfully, if the player has an inventory
-    // open and the slot id points to his inventory.
+        let mut crafting_table = Inventory::new(InventoryKind::Crafting);
+        crafting_table.set_slot(1, ItemStack::new(ItemKind::MilkBucket, 1, None));
+        crafting_table.set_slot(5, ItemStack::new(ItemKind::Sugar, 2, None));
 
-    let mut app = App::new();
-    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let crafting_table_ent = app.world.spawn(crafting_table).id();
 
-    // Process a tick to get past the "on join" logic.
-    app.update();
+        app.world
+            .entity_mut(client_ent)
+            .insert(OpenInventory::new(crafting_table_ent));
+        app.world.get_mut::<CursorItem>(client_ent).unwrap().0 = stack(ItemKind::Egg, 1);
 
-    let mut inventory = app
-        .world
-        .get_mut::<Inventory>(client_ent)
-        .expect("could not find inventory");
+        app.update();
+        client_helper.clear_received();
 
-    inventory.set_slot(
-        convert_to_player_slot_id(InventoryKind::Generic9x3, 50),
-        ItemStack::new(ItemKind::IronIngot, 32, None),
-    );
+        let crafting_table = app.world.get::<Inventory>(crafting_table_ent).unwrap();
+        assert_eq!(crafting_table.slot(RESULT_SLOT), None);
+
+        // Put the egg in the corner of the grid.
+        send_window_click(
+            &mut app,
+            client_ent,
+            &mut client_helper,
+            9,
+            ClickMode::Click,
+            vec![SlotChange {
+                idx: 9,
+                item: stack(ItemKind::Egg, 1),
+            }],
+            None,
+        );
 
-    let _inventory_ent = set_up_open_inventory(&mut app, client_ent);
+        app.update();
 
-    app.update();
-    client_helper.clear_received();
+        let cake = stack(ItemKind::Cake, 1);
+
+        let crafting_table = app.world.get::<Inventory>(crafting_table_ent).unwrap();
+        assert_eq!(crafting_table.slot(RESULT_SLOT), cake.as_ref());
+        assert_eq!(slot_updates(&mut client_helper), vec![(0, cake.clone())]);
+
+        // Take the cake. The milk bucket leaves an empty bucket behind.
+        send_window_click(
+            &mut app,
+            client_ent,
+            &mut client_helper,
+            0,
+            ClickMode::Click,
+            vec![
+                SlotChange { idx: 0, item: None },
+                SlotChange {
+                    idx: 1,
+                    item: stack(ItemKind::Bucket, 1),
+                },
+                SlotChange {
+                    idx: 5,
+                    item: stack(ItemKind::Sugar, 1),
+                },
+                SlotChange { idx: 9, item: None },
+            ],
+            cake.clone(),
+        );
 
-    let inv_state = app
-        .world
-        .get_mut::<ClientInventoryState>(client_ent)
-        .expect("could not find client");
+        app.update();
 
-    let state_id = inv_state.state_id().0;
-    let window_id = inv_state.window_id();
+        let cursor_item = app.world.get::<CursorItem>(client_ent).unwrap();
+        assert_eq!(cursor_item.0, cake);
 
-    client_helper.send(&ClickSlotC2s {
-        window_id,
-        state_id: VarInt(state_id),
-        slot_idx: 50, // pressing control, the whole stack is dropped
-        button: 1,
-        mode: ClickMode::DropKey,
-        slot_changes: vec![SlotChange {
-            idx: 50,
-            item: None,
-        }],
-        carried_item: None,
-    });
+        let crafting_table = app.world.get::<Inventory>(crafting_table_ent).unwrap();
+        assert_eq!(crafting_table.slot(RESULT_SLOT), None);
+        assert_eq!(crafting_table.slot(1), stack(ItemKind::Bucket, 1).as_ref());
+        assert_eq!(crafting_table.slot(5), stack(ItemKind::Sugar, 1).as_ref());
+        assert_eq!(crafting_table.slot(9), None);
 
-    app.update();
+        // The client predicted all of that.
+        assert_eq!(slot_updates(&mut client_helper), vec![]);
+    }
 
-    // Make assertions
-    let events = app
-        .world
-        .get_resource::<Events<DropItemStackEvent>>()
-        .expect("expected drop item stack events");
+    fn request(
+        app: &mut App,
+        client_helper: &mut MockClientHelper,
+        recipe: Ident<&str>,
+        make_all: bool,
+    ) {
+        client_helper.send(&CraftRequestC2s {
+            window_id: 0,
+            recipe: recipe.into(),
+            make_all,
+        });
 
-    let player_inventory = app
-        .world
-        .get::<Inventory>(client_ent)
-        .expect("could not find inventory");
+        app.update();
+    }
 
-    let events = events.iter_current_update_events().collect::<Vec<_>>();
+    #[test]
+    fn recipe_book_places_ingredients() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
 
-    assert_eq!(events.len(), 1);
-    assert_eq!(events[0].client, client_ent);
-    assert_eq!(
-        events[0].from_slot,
-        Some(convert_to_player_slot_id(InventoryKind::Generic9x3, 50))
-    );
-    assert_eq!(
-        events[0].stack,
-        ItemStack::new(ItemKind::IronIngot, 32, None)
-    );
+        let mut registry = app.world.resource_mut::<RecipeRegistry>();
+        registry.insert(ident!("crafting_table"), crafting_table_recipe());
+        registry.insert(
+            ident!("chest"),
+            RecipeData::CraftingShaped(ShapedRecipe {
+                group: String::new(),
+                category: CraftingCategory::Misc,
+                width: 3,
+                height: 3,
+                ingredients: vec![Ingredient(vec![ItemKind::OakPlanks]); 9],
+                result: ItemStack::new(ItemKind::Chest, 1, None),
+                show_notification: true,
+            }),
+        );
 
-    // Also make sure that the player inventory was updated correctly.
-    let expected_player_slot_id = convert_to_player_slot_id(InventoryKind::Generic9x3, 50);
-    assert_eq!(player_inventory.slot(expected_player_slot_id), None);
-}
+        let mut unlocked = app.world.get_mut::<UnlockedRecipes>(client_ent).unwrap();
+        unlocked.unlock(ident!("crafting_table"));
+        unlocked.unlock(ident!("chest"));
 
-#[test]
-fn dragging_items() {
-    let mut app = App::new();
-    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        inventory.set_slot(9, ItemStack::new(ItemKind::OakPlanks, 10, None));
+        inventory.set_slot(1, ItemStack::new(ItemKind::Stick, 1, None));
 
-    // Process a tick to get past the "on join" logic.
-    app.update();
-    client_helper.clear_received();
+        app.update();
 
-    app.world.get_mut::<CursorItem>(client_ent).unwrap().0 =
-        Some(ItemStack::new(ItemKind::Diamond, 64, None));
+        // The stick in the grid is put back first.
+        request(
+            &mut app,
+            &mut client_helper,
+            ident!("crafting_table"),
+            false,
+        );
 
-    let inv_state = app.world.get::<ClientInventoryState>(client_ent).unwrap();
-    let window_id = inv_state.window_id();
-    let state_id = inv_state.state_id().0;
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(36), stack(ItemKind::Stick, 1).as_ref());
+        assert_eq!(inventory.slot(9), stack(ItemKind::OakPlanks, 6).as_ref());
+        for idx in 1..=4 {
+            assert_eq!(inventory.slot(idx), stack(ItemKind::OakPlanks, 1).as_ref());
+        }
+        assert_eq!(
+            inventory.slot(RESULT_SLOT),
+            stack(ItemKind::CraftingTable, 1).as_ref()
+        );
 
-    let drag_packet = ClickSlotC2s {
-        window_id,
-        state_id: VarInt(state_id),
-        slot_idx: -999,
-        button: 2,
-        mode: ClickMode::Drag,
-        slot_changes: vec![
-            SlotChange {
-                idx: 9,
-                item: Some(ItemStack::new(ItemKind::Diamond, 21, None)),
-            },
-            SlotChange {
-                idx: 10,
-                item: Some(ItemStack::new(ItemKind::Diamond, 21, None)),
-            },
-            SlotChange {
-                idx: 11,
-                item: Some(ItemStack::new(ItemKind::Diamond, 21, None)),
-            },
-        ],
-        carried_item: Some(ItemStack::new(ItemKind::Diamond, 1, None)),
-    };
-    client_helper.send(&drag_packet);
+        // As many planks as possible are put in each slot.
+        request(&mut app, &mut client_helper, ident!("crafting_table"), true);
 
-    app.update();
-    let sent_packets = client_helper.collect_received();
-    assert_eq!(sent_packets.0.len(), 0);
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(9), stack(ItemKind::OakPlanks, 2).as_ref());
+        for idx in 1..=4 {
+            assert_eq!(inventory.slot(idx), stack(ItemKind::OakPlanks, 2).as_ref());
+        }
 
-    let cursor_item = app
-        .world
-        .get::<CursorItem>(client_ent)
-        .expect("could not find client");
+        // The chest recipe doesn't fit in the player's crafting grid, so the client
+        // shows the recipe in the grid instead.
+        client_helper.clear_received();
 
-    assert_eq!(
-        cursor_item.0,
-        Some(ItemStack::new(ItemKind::Diamond, 1, None))
-    );
+        request(&mut app, &mut client_helper, ident!("chest"), false);
 
-    let inventory = app
-        .world
-        .get::<Inventory>(client_ent)
-        .expect("could not find inventory");
+        let sent_packets = client_helper.collect_received();
+        sent_packets.assert_count::<CraftFailedResponseS2c>(1);
 
-    for i in 9..12 {
-        assert_eq!(
-            inventory.slot(i),
-            Some(&ItemStack::new(ItemKind::Diamond, 21, None))
-        );
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(9), stack(ItemKind::OakPlanks, 2).as_ref());
     }
 }
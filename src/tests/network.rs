@@ -0,0 +1,1852 @@
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bevy_app::prelude::*;
+use bevy_app::AppExit;
+use bevy_ecs::prelude::*;
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use valence_biome::BiomeRegistry;
+use valence_client::chat_session::{ChatSessionSettings, SignedChatMode};
+use valence_client::keepalive::{KeepAliveC2s, KeepAliveS2c, KeepaliveSettings};
+use valence_client::movement::OnGroundOnlyC2s;
+use valence_client::packet::DisconnectS2c;
+use valence_client::stats::{ConnectionStats, NetworkStats, TrafficStats};
+use valence_client::{Client, DisconnectClient};
+use valence_core::protocol::decode::PacketDecoder;
+use valence_core::protocol::encode::{PacketEncoder, WritePacket};
+use valence_core::protocol::var_int::VarInt;
+use valence_core::protocol::{Decode, Packet, PacketSide, PacketState};
+use valence_core::text::Text;
+use valence_core::{
+    ident, translation_key, CoreSettings, Server, MINECRAFT_VERSION, PROTOCOL_VERSION,
+};
+use valence_dimension::DimensionTypeRegistry;
+use valence_entity::{Location, OnGround};
+use valence_instance::Instance;
+use valence_network::capture::{CaptureReader, PacketCaptureSettings};
+use valence_network::packet::{
+    HandshakeC2s, HandshakeNextState, LoginCompressionS2c, LoginDisconnectS2c, LoginHelloC2s,
+    LoginQueryRequestS2c, LoginQueryResponseC2s, LoginSuccessS2c, QueryRequestC2s,
+    QueryResponseS2c,
+};
+use valence_network::{
+    async_trait, AttemptLimit, BanList, CleanupFn, CompressionSettings, ConnectionLimits,
+    ConnectionMode, ConnectionTimeouts, HandshakeData, IpBan, IpCidr, LoginPluginConnection,
+    NetworkCallbacks, NetworkSettings, NewClientInfo, PacketAction, PacketCategory,
+    PacketInterceptor, PacketRateLimits, PacketRateStats, PlayerBan, PlayerSampleEntry, RateLimit,
+    RateLimitPolicy, RconCommandEvent, RconSettings, ServerListPing, SharedNetworkState,
+    StatusPingStats, Whitelist,
+};
+
+use crate::testing::scenario_single_client;
+use crate::DefaultPlugins;
+
+/// Returns a local address with a port that is currently free.
+fn free_local_addr() -> SocketAddr {
+    TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+/// Sets up valence listening on a free local port with the given settings.
+/// Clients are spawned into an instance as they join.
+fn scenario_listening(settings: NetworkSettings, keepalive_period: Duration) -> (App, SocketAddr) {
+    let addr = free_local_addr();
+
+    (
+        scenario_listening_on(settings, keepalive_period, vec![addr]),
+        addr,
+    )
+}
+
+/// Like [`scenario_listening`], but listening on the given addresses.
+fn scenario_listening_on(
+    mut settings: NetworkSettings,
+    keepalive_period: Duration,
+    addresses: Vec<SocketAddr>,
+) -> App {
+    settings.addresses = addresses;
+    settings.connection_mode = ConnectionMode::Offline;
+
+    let mut app = App::new();
+
+    app.insert_resource(CoreSettings {
+        compression_threshold: None,
+        ..Default::default()
+    })
+    .insert_resource(KeepaliveSettings {
+        period: keepalive_period,
+    })
+    .insert_resource(settings)
+    .add_plugins(DefaultPlugins);
+
+    app.update(); // Initialize plugins and start listening.
+
+    let instance = Instance::new(
+        ident!("overworld"),
+        app.world.resource::<DimensionTypeRegistry>(),
+        app.world.resource::<BiomeRegistry>(),
+        app.world.resource::<Server>(),
+    );
+
+    let instance_ent = app.world.spawn(instance).id();
+
+    app.add_systems(
+        Update,
+        move |mut clients: Query<&mut Location, Added<Client>>| {
+            for mut loc in &mut clients {
+                loc.0 = instance_ent;
+            }
+        },
+    );
+
+    app
+}
+
+/// Updates the app until the client thread has finished.
+fn update_until_finished<T>(app: &mut App, client_thread: thread::JoinHandle<T>) -> T {
+    let start = Instant::now();
+
+    while !client_thread.is_finished() {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "client was not disconnected"
+        );
+
+        app.update();
+
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    client_thread.join().unwrap()
+}
+
+/// A client connected to the server over TCP.
+struct TestClient {
+    stream: Box<dyn ClientStream>,
+    enc: PacketEncoder,
+    dec: PacketDecoder,
+}
+
+/// A stream a [`TestClient`] can be connected with.
+trait ClientStream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> ClientStream for T {}
+
+/// Calls `connect` until it succeeds, since the server might not be listening
+/// yet.
+fn retry_connect<S>(mut connect: impl FnMut() -> io::Result<S>) -> S {
+    let start = Instant::now();
+
+    loop {
+        match connect() {
+            Ok(stream) => return stream,
+            Err(e) if start.elapsed() > Duration::from_secs(5) => {
+                panic!("failed to connect: {e}")
+            }
+            Err(_) => thread::sleep(Duration::from_millis(10)),
+        }
+    }
+}
+
+impl TestClient {
+    /// Connects to the server at `addr`.
+    fn connect(addr: SocketAddr) -> Self {
+        let stream = retry_connect(|| TcpStream::connect(addr));
+
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+
+        Self::with_stream(stream)
+    }
+
+    fn with_stream(stream: impl ClientStream + 'static) -> Self {
+        Self {
+            stream: Box::new(stream),
+            enc: PacketEncoder::new(),
+            dec: PacketDecoder::new(),
+        }
+    }
+
+    /// Connects to the server at `addr` and logs in.
+    fn login(addr: SocketAddr) -> Self {
+        Self::connect(addr).finish_login(addr.port())
+    }
+
+    /// Logs in on an open connection.
+    fn finish_login(mut self, server_port: u16) -> Self {
+        self.enc
+            .append_packet(&HandshakeC2s {
+                protocol_version: VarInt(PROTOCOL_VERSION),
+                server_address: "localhost",
+                server_port,
+                next_state: HandshakeNextState::Login,
+            })
+            .unwrap();
+
+        self.enc
+            .append_packet(&LoginHelloC2s {
+                username: "test",
+                profile_id: None,
+            })
+            .unwrap();
+
+        self.flush();
+
+        loop {
+            let frame = self.next_frame().expect("disconnected during login");
+
+            match frame.0 {
+                LoginCompressionS2c::ID => {
+                    let threshold = LoginCompressionS2c::decode(&mut &frame.1[..])
+                        .unwrap()
+                        .threshold
+                        .0 as u32;
+
+                    self.enc.set_compression(Some(threshold));
+                    self.dec.set_compression(Some(threshold));
+                }
+                LoginQueryRequestS2c::ID => {
+                    let request = LoginQueryRequestS2c::decode(&mut &frame.1[..]).unwrap();
+
+                    // Answer like a vanilla client, which doesn't understand any requests.
+                    self.send(&LoginQueryResponseC2s {
+                        message_id: request.message_id,
+                        data: None,
+                    });
+                    self.flush();
+                }
+                id => {
+                    assert_eq!(id, LoginSuccessS2c::ID);
+                    break;
+                }
+            }
+        }
+
+        self
+    }
+
+    /// Connects to the server at `addr` and returns the JSON of its server
+    /// list ping response.
+    fn status(addr: SocketAddr) -> String {
+        Self::status_with_version(addr, PROTOCOL_VERSION)
+    }
+
+    /// Like [`Self::status`], but pings with the given protocol version.
+    fn status_with_version(addr: SocketAddr, protocol_version: i32) -> String {
+        let mut client = Self::connect(addr);
+
+        client.send(&HandshakeC2s {
+            protocol_version: VarInt(protocol_version),
+            server_address: "localhost",
+            server_port: addr.port(),
+            next_state: HandshakeNextState::Status,
+        });
+        client.send(&QueryRequestC2s);
+        client.flush();
+
+        let (id, body) = client.next_frame().expect("disconnected during status");
+        assert_eq!(id, QueryResponseS2c::ID);
+
+        QueryResponseS2c::decode(&mut body.as_slice())
+            .unwrap()
+            .json
+            .to_owned()
+    }
+
+    fn send<P: Packet + valence_core::protocol::Encode>(&mut self, pkt: &P) {
+        self.enc.append_packet(pkt).unwrap();
+    }
+
+    fn flush(&mut self) {
+        // Writes can fail once the server has closed the connection.
+        let _ = self.stream.write_all(&self.enc.take());
+    }
+
+    /// Returns the ID and body of the next packet, or `None` if the
+    /// connection was closed.
+    fn next_frame(&mut self) -> Option<(i32, Vec<u8>)> {
+        let mut buf = [0; 4096];
+
+        loop {
+            if let Some(frame) = self.dec.try_next_packet().unwrap() {
+                return Some((frame.id, frame.body.to_vec()));
+            }
+
+            match self.stream.read(&mut buf) {
+                Ok(0) | Err(_) => return None,
+                Ok(n) => self.dec.queue_slice(&buf[..n]),
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct DropKeepalives {
+    dropped: AtomicUsize,
+}
+
+impl PacketInterceptor for DropKeepalives {
+    fn intercept_incoming(&self, _client: Uuid, id: i32, _body: &[u8]) -> PacketAction {
+        if id == KeepAliveC2s::ID {
+            self.dropped.fetch_add(1, Ordering::SeqCst);
+            PacketAction::Drop
+        } else {
+            PacketAction::Forward
+        }
+    }
+}
+
+#[test]
+fn packet_interceptor_drops_keepalives() {
+    let interceptor = Arc::new(DropKeepalives::default());
+
+    let (mut app, addr) = scenario_listening(
+        NetworkSettings {
+            packet_interceptor: Some(interceptor.clone()),
+            ..Default::default()
+        },
+        Duration::from_millis(100),
+    );
+
+    // The client answers every keepalive, but the answers never reach the server
+    // so the client is timed out.
+    let client_thread = thread::spawn(move || {
+        let mut client = TestClient::login(addr);
+        let mut answered = 0;
+
+        while let Some((id, body)) = client.next_frame() {
+            if id == KeepAliveS2c::ID {
+                let mut r = &body[..];
+                let id = u64::decode(&mut r).unwrap();
+
+                client.send(&KeepAliveC2s { id });
+                client.flush();
+
+                answered += 1;
+            }
+        }
+
+        answered
+    });
+
+    let answered = update_until_finished(&mut app, client_thread);
+
+    assert!(answered > 0);
+    assert_eq!(interceptor.dropped.load(Ordering::SeqCst), answered);
+}
+
+#[test]
+fn packet_rate_limit_disconnects_flood() {
+    let (mut app, addr) = scenario_listening(
+        NetworkSettings {
+            packet_rate_limits: Some(PacketRateLimits {
+                global: None,
+                chat: None,
+                interaction: None,
+                movement: Some(RateLimit::new(20.0, 20.0)),
+                policy: RateLimitPolicy::Disconnect { max_violations: 50 },
+            }),
+            ..Default::default()
+        },
+        Duration::MAX,
+    );
+
+    let client_thread = thread::spawn(move || {
+        let mut client = TestClient::login(addr);
+
+        for _ in 0..1000 {
+            client.send(&OnGroundOnlyC2s { on_ground: true });
+        }
+
+        client.flush();
+
+        while client.next_frame().is_some() {}
+    });
+
+    update_until_finished(&mut app, client_thread);
+
+    let stats = app
+        .world
+        .query::<&PacketRateStats>()
+        .single(&app.world)
+        .clone();
+
+    assert_eq!(stats.dropped(), 50);
+    assert_eq!(stats.dropped_in(PacketCategory::Movement), 50);
+    // At least the burst of 20 packets was accepted before the client was
+    // disconnected.
+    assert!(stats.received() - stats.dropped() >= 20);
+
+    // The client was disconnected.
+    assert_eq!(app.world.query::<&Client>().iter(&app.world).count(), 0);
+}
+
+#[test]
+fn connection_stats() {
+    let (mut app, addr) = scenario_listening(NetworkSettings::default(), Duration::MAX);
+
+    let (done_send, done_recv) = std::sync::mpsc::channel::<()>();
+
+    let client_thread = thread::spawn(move || {
+        let mut client = TestClient::login(addr);
+
+        for _ in 0..10 {
+            client.send(&OnGroundOnlyC2s { on_ground: true });
+        }
+
+        client.flush();
+
+        // Stay connected until the server is done checking.
+        let _ = done_recv.recv();
+    });
+
+    let start = Instant::now();
+
+    let stats = loop {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "packets not received"
+        );
+
+        app.update();
+
+        let stats = app.world.query::<&ConnectionStats>().get_single(&app.world);
+
+        if let Ok(stats) = stats.copied() {
+            if stats.received.packets == 10 {
+                break stats;
+            }
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    // One byte of length prefix, one byte of packet ID and one byte of body.
+    assert_eq!(
+        stats.received,
+        TrafficStats {
+            packets: 10,
+            bytes: 30,
+            uncompressed_bytes: 20,
+            largest_packet: 2,
+        }
+    );
+    // The client was sent the packets to join the game.
+    assert!(stats.sent.packets > 0);
+
+    let mut client = app.world.query::<&mut Client>().single_mut(&mut app.world);
+    client.write_packet(&KeepAliveS2c { id: 0 });
+
+    app.update();
+
+    let new_stats = *app.world.query::<&ConnectionStats>().single(&app.world);
+
+    assert!(new_stats.sent.packets > stats.sent.packets);
+    assert!(new_stats.sent.uncompressed_bytes >= stats.sent.uncompressed_bytes + 9);
+    assert!(new_stats.sent.bytes > new_stats.sent.uncompressed_bytes);
+
+    // The only client accounts for all the traffic.
+    let totals = app.world.resource::<NetworkStats>();
+    assert_eq!(totals.sent, new_stats.sent);
+    assert_eq!(totals.received, new_stats.received);
+
+    done_send.send(()).unwrap();
+    client_thread.join().unwrap();
+}
+
+#[test]
+fn packet_capture_replay() {
+    let dir = std::env::temp_dir().join(format!(
+        "valence-test-capture-{:016x}",
+        rand::random::<u64>()
+    ));
+
+    let (mut app, addr) = scenario_listening(
+        NetworkSettings {
+            packet_capture: Some(PacketCaptureSettings {
+                directory: dir.clone(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        Duration::MAX,
+    );
+
+    let client_thread = thread::spawn(move || {
+        let mut client = TestClient::login(addr);
+
+        for _ in 0..3 {
+            client.send(&OnGroundOnlyC2s { on_ground: true });
+        }
+
+        client.flush();
+
+        // Everything sent to the client is captured by the time it arrives.
+        while let Some((id, _)) = client.next_frame() {
+            if id == KeepAliveS2c::ID {
+                break;
+            }
+        }
+    });
+
+    let start = Instant::now();
+
+    loop {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "packets not received"
+        );
+
+        app.update();
+
+        let stats = app.world.query::<&ConnectionStats>().get_single(&app.world);
+
+        if stats.is_ok_and(|stats| stats.received.packets == 3) {
+            break;
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let mut client = app.world.query::<&mut Client>().single_mut(&mut app.world);
+    client.write_packet(&KeepAliveS2c { id: 7 });
+
+    update_until_finished(&mut app, client_thread);
+
+    let files: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect();
+    assert_eq!(files.len(), 1);
+
+    let records = CaptureReader::open(&files[0])
+        .unwrap()
+        .collect::<anyhow::Result<Vec<_>>>()
+        .unwrap();
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let find = |side, state, id| {
+        records
+            .iter()
+            .find(|r| r.side == side && r.state == state && r.frame.id == id)
+            .unwrap_or_else(|| panic!("packet {id:#x} not captured"))
+    };
+
+    assert_eq!(records[0].frame.id, HandshakeC2s::ID);
+    assert_eq!(records[0].state, PacketState::Handshaking);
+
+    let hello = find(
+        PacketSide::Serverbound,
+        PacketState::Login,
+        LoginHelloC2s::ID,
+    );
+    assert_eq!(
+        hello.frame.decode::<LoginHelloC2s>().unwrap().username,
+        "test"
+    );
+
+    find(
+        PacketSide::Clientbound,
+        PacketState::Login,
+        LoginSuccessS2c::ID,
+    );
+
+    let keepalive = find(PacketSide::Clientbound, PacketState::Play, KeepAliveS2c::ID);
+    assert_eq!(keepalive.frame.decode::<KeepAliveS2c>().unwrap().id, 7);
+
+    assert!(records.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+
+    // Replaying the capture has the same effect as the original packets.
+    let mut app = App::new();
+    let (client_ent, mut helper) = scenario_single_client(&mut app);
+
+    app.update();
+    helper.confirm_initial_pending_teleports();
+    app.update();
+
+    assert!(!app.world.get::<OnGround>(client_ent).unwrap().0);
+    assert_eq!(helper.replay(records), 3);
+
+    app.update();
+
+    assert!(app.world.get::<OnGround>(client_ent).unwrap().0);
+}
+
+#[test]
+fn query_full_stat() {
+    let query_addr = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let (_app, addr) = scenario_listening(
+        NetworkSettings {
+            query_address: Some(query_addr),
+            ..Default::default()
+        },
+        Duration::MAX,
+    );
+
+    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    socket.connect(query_addr).unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_millis(100)))
+        .unwrap();
+
+    let mut buf = [0; 1024];
+    let start = Instant::now();
+
+    // The server might not be listening yet, so retry the handshake.
+    let len = loop {
+        assert!(
+            start.elapsed() < Duration::from_secs(5),
+            "no handshake response"
+        );
+
+        socket.send(b"\xfe\xfd\x09\x00\x00\x00\x01").unwrap();
+
+        if let Ok(len) = socket.recv(&mut buf) {
+            break len;
+        }
+    };
+
+    assert_eq!(&buf[..5], b"\x09\x00\x00\x00\x01");
+    assert_eq!(buf[len - 1], 0);
+
+    let token: i32 = std::str::from_utf8(&buf[5..len - 1])
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    let mut request = b"\xfe\xfd\x00\x00\x00\x00\x01".to_vec();
+    request.extend_from_slice(&token.to_be_bytes());
+    request.extend_from_slice(&[0; 4]);
+
+    socket.send(&request).unwrap();
+
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    let len = socket.recv(&mut buf).unwrap();
+    let response = &buf[..len];
+
+    assert!(
+        response.starts_with(b"\x00\x00\x00\x00\x01splitnum\0\x80\0hostname\0A Valence Server\0")
+    );
+    assert!(response.ends_with(b"\x01player_\0\0\0"));
+
+    let port = format!("hostport\0{}\0", addr.port());
+    assert!(response.windows(port.len()).any(|w| w == port.as_bytes()));
+
+    // Requests with the wrong challenge token are ignored.
+    request[7..11].copy_from_slice(&token.wrapping_add(1).to_be_bytes());
+    socket.send(&request).unwrap();
+
+    socket
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .unwrap();
+
+    assert!(socket.recv(&mut buf).is_err());
+}
+
+fn rcon_send(stream: &mut TcpStream, id: i32, ty: i32, body: &str) {
+    let mut buf = vec![];
+    buf.extend_from_slice(&(10 + body.len() as i32).to_le_bytes());
+    buf.extend_from_slice(&id.to_le_bytes());
+    buf.extend_from_slice(&ty.to_le_bytes());
+    buf.extend_from_slice(body.as_bytes());
+    buf.extend_from_slice(&[0, 0]);
+
+    stream.write_all(&buf).unwrap();
+}
+
+/// Returns the ID, type and body of the next RCON packet, or `None` if the
+/// connection was closed.
+fn rcon_recv(stream: &mut TcpStream) -> Option<(i32, i32, String)> {
+    let mut len = [0; 4];
+    stream.read_exact(&mut len).ok()?;
+
+    let mut buf = vec![0; i32::from_le_bytes(len) as usize];
+    stream.read_exact(&mut buf).unwrap();
+
+    let id = i32::from_le_bytes(buf[..4].try_into().unwrap());
+    let ty = i32::from_le_bytes(buf[4..8].try_into().unwrap());
+    let body = String::from_utf8(buf[8..buf.len() - 2].to_vec()).unwrap();
+
+    Some((id, ty, body))
+}
+
+#[test]
+fn rcon_commands() {
+    let rcon_addr = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let (mut app, _) = scenario_listening(
+        NetworkSettings {
+            rcon: Some(RconSettings {
+                address: rcon_addr,
+                password: "hunter2".into(),
+            }),
+            ..Default::default()
+        },
+        Duration::MAX,
+    );
+
+    app.add_systems(Update, |mut events: EventReader<RconCommandEvent>| {
+        for event in events.iter() {
+            if event.command == "long" {
+                event.respond("x".repeat(5000));
+            } else {
+                event.respond(format!("ran {}", event.command));
+            }
+        }
+    });
+
+    let connect = move || {
+        let start = Instant::now();
+
+        // The server might not be listening yet.
+        let stream = loop {
+            match TcpStream::connect(rcon_addr) {
+                Ok(stream) => break stream,
+                Err(e) if start.elapsed() > Duration::from_secs(5) => {
+                    panic!("failed to connect: {e}")
+                }
+                Err(_) => thread::sleep(Duration::from_millis(10)),
+            }
+        };
+
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+
+        stream
+    };
+
+    let client_thread = thread::spawn(move || {
+        // Failed authentication closes the connection.
+        let mut stream = connect();
+
+        rcon_send(&mut stream, 1, 3, "hunter3");
+        assert_eq!(rcon_recv(&mut stream), Some((-1, 2, "".into())));
+        assert_eq!(rcon_recv(&mut stream), None);
+
+        let mut stream = connect();
+
+        rcon_send(&mut stream, 1, 3, "hunter2");
+        assert_eq!(rcon_recv(&mut stream), Some((1, 2, "".into())));
+
+        rcon_send(&mut stream, 2, 2, "say hello");
+        assert_eq!(rcon_recv(&mut stream), Some((2, 0, "ran say hello".into())));
+
+        // Long outputs are split into multiple packets.
+        rcon_send(&mut stream, 3, 2, "long");
+
+        let (id, ty, first) = rcon_recv(&mut stream).unwrap();
+        assert_eq!((id, ty, first.len()), (3, 0, 4096));
+
+        let (id, ty, second) = rcon_recv(&mut stream).unwrap();
+        assert_eq!((id, ty, second.len()), (3, 0, 904));
+    });
+
+    update_until_finished(&mut app, client_thread);
+}
+
+#[test]
+fn legacy_pings() {
+    let (mut app, addr) = scenario_listening(NetworkSettings::default(), Duration::MAX);
+
+    let client_thread = thread::spawn(move || {
+        let ping = |request: &[u8]| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+
+            stream.write_all(request).unwrap();
+
+            let mut response = vec![];
+            stream.read_to_end(&mut response).unwrap();
+
+            assert_eq!(response[0], 0xff);
+
+            let chars = response[3..]
+                .chunks(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect::<Vec<_>>();
+
+            assert_eq!(
+                u16::from_be_bytes([response[1], response[2]]) as usize,
+                chars.len()
+            );
+
+            String::from_utf16(&chars).unwrap()
+        };
+
+        let expected =
+            format!("§1\0{PROTOCOL_VERSION}\0{MINECRAFT_VERSION}\0A Valence Server\x000\x0020");
+
+        // 1.6
+        let mut request = vec![0xfe, 0x01, 0xfa, 0x00, 0x0b];
+        request.extend("MC|PingHost".encode_utf16().flat_map(|c| c.to_be_bytes()));
+        request.extend_from_slice(&[0x00, 0x19, 0x4a, 0x00, 0x09]);
+        request.extend("localhost".encode_utf16().flat_map(|c| c.to_be_bytes()));
+        request.extend_from_slice(&25565_i32.to_be_bytes());
+
+        assert_eq!(ping(&request), expected);
+
+        // 1.4 to 1.5
+        assert_eq!(ping(&[0xfe, 0x01]), expected);
+
+        // Beta 1.8 to 1.3
+        assert_eq!(ping(&[0xfe]), "A Valence Server§0§20");
+    });
+
+    update_until_finished(&mut app, client_thread);
+}
+
+struct CompressConnections;
+
+#[async_trait]
+impl NetworkCallbacks for CompressConnections {
+    async fn compression(
+        &self,
+        _shared: &SharedNetworkState,
+        _remote_addr: SocketAddr,
+    ) -> CompressionSettings {
+        CompressionSettings {
+            threshold: Some(64),
+            level: 9,
+        }
+    }
+}
+
+#[test]
+fn per_connection_compression() {
+    // Compression is disabled for the server, but enabled for the connection.
+    let (mut app, addr) = scenario_listening(
+        NetworkSettings {
+            callbacks: CompressConnections.into(),
+            ..Default::default()
+        },
+        Duration::from_millis(100),
+    );
+
+    let client_thread = thread::spawn(move || {
+        let mut client = TestClient::login(addr);
+
+        assert_eq!(client.dec.compression(), Some(64));
+
+        // The packets from the client's encoder and the shared packets (such as
+        // chunks) are decoded with the connection's compression settings.
+        let mut frames = 0;
+
+        while let Some((id, _)) = client.next_frame() {
+            frames += 1;
+
+            if id == KeepAliveS2c::ID {
+                break;
+            }
+        }
+
+        frames
+    });
+
+    let frames = update_until_finished(&mut app, client_thread);
+
+    assert!(frames > 1);
+}
+
+/// Sends a login plugin request to each client before letting it join.
+/// The responses are collected in the shared list.
+#[derive(Clone, Default)]
+struct QueryOnConfigure(Arc<Mutex<Vec<Option<Bytes>>>>);
+
+#[async_trait]
+impl NetworkCallbacks for QueryOnConfigure {
+    async fn configure(
+        &self,
+        _shared: &SharedNetworkState,
+        _info: &NewClientInfo,
+        conn: &mut LoginPluginConnection<'_>,
+    ) -> Result<(), Text> {
+        let response = conn
+            .query(ident!("valence:test"), b"hello")
+            .await
+            .map_err(|e| Text::from(e.to_string()))?;
+
+        self.0.lock().unwrap().push(response);
+
+        Ok(())
+    }
+}
+
+#[test]
+fn configure_waits_for_login_plugin_response() {
+    let callbacks = QueryOnConfigure::default();
+
+    let (mut app, addr) = scenario_listening(
+        NetworkSettings {
+            callbacks: callbacks.clone().into(),
+            ..Default::default()
+        },
+        Duration::from_millis(100),
+    );
+
+    let client_thread = thread::spawn(move || {
+        let mut client = TestClient::login(addr);
+
+        // The client is spawned and starts receiving play packets.
+        while let Some((id, _)) = client.next_frame() {
+            if id == KeepAliveS2c::ID {
+                return;
+            }
+        }
+
+        panic!("client was not spawned");
+    });
+
+    update_until_finished(&mut app, client_thread);
+
+    // The client didn't understand the request.
+    assert_eq!(*callbacks.0.lock().unwrap(), [None]);
+}
+
+/// Responds to server list pings with the port of the listener the ping
+/// arrived on.
+struct DescribeListener;
+
+#[async_trait]
+impl NetworkCallbacks for DescribeListener {
+    async fn server_list_ping(
+        &self,
+        _shared: &SharedNetworkState,
+        _remote_addr: SocketAddr,
+        handshake_data: &HandshakeData,
+    ) -> ServerListPing {
+        ServerListPing::Respond {
+            online_players: 0,
+            max_players: 0,
+            player_sample: vec![],
+            description: format!("port {}", handshake_data.listener_addr.unwrap().port()).into(),
+            favicon_png: &[],
+            version_name: MINECRAFT_VERSION.to_owned(),
+            protocol: PROTOCOL_VERSION,
+            enforces_secure_chat: false,
+            previews_chat: false,
+        }
+    }
+}
+
+#[test]
+fn multiple_listeners() {
+    let addrs = [free_local_addr(), free_local_addr()];
+
+    // The port of the second address is taken, so only the others are bound.
+    let taken = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+
+    let mut app = scenario_listening_on(
+        NetworkSettings {
+            callbacks: DescribeListener.into(),
+            ..Default::default()
+        },
+        Duration::from_millis(100),
+        vec![addrs[0], taken.local_addr().unwrap(), addrs[1]],
+    );
+
+    let client_thread = thread::spawn(move || {
+        for addr in addrs {
+            let json = TestClient::status(addr);
+            assert!(json.contains(&format!("port {}", addr.port())), "{json}");
+
+            let mut client = TestClient::login(addr);
+
+            while let Some((id, _)) = client.next_frame() {
+                if id == KeepAliveS2c::ID {
+                    break;
+                }
+            }
+        }
+    });
+
+    update_until_finished(&mut app, client_thread);
+}
+
+#[test]
+fn listeners_close_on_exit() {
+    let addrs = [free_local_addr(), free_local_addr()];
+
+    let mut app = scenario_listening_on(NetworkSettings::default(), Duration::MAX, addrs.to_vec());
+
+    let start = Instant::now();
+
+    for addr in addrs {
+        while TcpStream::connect(addr).is_err() {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "not listening on {addr}"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    app.world.send_event(AppExit);
+    app.update();
+
+    // Every listener is closed, so new connections are refused.
+    let start = Instant::now();
+
+    for addr in addrs {
+        while TcpStream::connect(addr).is_ok() {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "still listening on {addr}"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+/// Returns a path for a unix socket in the temporary directory.
+#[cfg(unix)]
+fn temp_socket_path() -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("valence-test-{:016x}.sock", rand::random::<u64>()))
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_socket_listener() {
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let path = temp_socket_path();
+
+    // Leave a socket behind like a previous run that crashed. It is replaced.
+    drop(UnixListener::bind(&path).unwrap());
+
+    let (mut app, _) = scenario_listening(
+        NetworkSettings {
+            unix_socket_path: Some(path.clone()),
+            ..Default::default()
+        },
+        Duration::from_millis(100),
+    );
+
+    let client_path = path.clone();
+
+    let client_thread = thread::spawn(move || {
+        let stream = retry_connect(|| UnixStream::connect(&client_path));
+
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+
+        let mut client = TestClient::with_stream(stream).finish_login(25565);
+
+        while let Some((id, _)) = client.next_frame() {
+            if id == KeepAliveS2c::ID {
+                return;
+            }
+        }
+
+        panic!("client was not spawned");
+    });
+
+    update_until_finished(&mut app, client_thread);
+
+    assert!(path.exists());
+
+    // The socket is removed when the server stops.
+    app.world.send_event(AppExit);
+    app.update();
+
+    assert!(!path.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_socket_path_is_not_replaced() {
+    use std::os::unix::net::UnixStream;
+
+    let path = temp_socket_path();
+    std::fs::write(&path, "not a socket").unwrap();
+
+    let (mut app, _) = scenario_listening(
+        NetworkSettings {
+            unix_socket_path: Some(path.clone()),
+            ..Default::default()
+        },
+        Duration::from_millis(100),
+    );
+
+    // Give the listener a chance to start.
+    thread::sleep(Duration::from_millis(100));
+    app.update();
+
+    assert!(UnixStream::connect(&path).is_err());
+
+    app.world.send_event(AppExit);
+    app.update();
+
+    assert_eq!(std::fs::read(&path).unwrap(), b"not a socket");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn connection_limits() {
+    let (mut app, addr) = scenario_listening(
+        NetworkSettings {
+            connection_limits: Some(ConnectionLimits {
+                max_connections_per_ip: Some(2),
+                login_attempts_per_ip: Some(AttemptLimit::new(1, Duration::from_secs(60))),
+                new_connections: None,
+                disconnect_reason: "Slow down".into(),
+            }),
+            ..Default::default()
+        },
+        Duration::MAX,
+    );
+
+    let client_thread = thread::spawn(move || {
+        let player = TestClient::login(addr);
+
+        let mut pinging = TestClient::connect(addr);
+        pinging.send(&HandshakeC2s {
+            protocol_version: VarInt(PROTOCOL_VERSION),
+            server_address: "localhost",
+            server_port: addr.port(),
+            next_state: HandshakeNextState::Status,
+        });
+        pinging.send(&QueryRequestC2s);
+        pinging.flush();
+
+        // The connection is counted once it gets a response.
+        assert_eq!(pinging.next_frame().unwrap().0, QueryResponseS2c::ID);
+
+        // A third connection from the same address is dropped without a response.
+        let mut dropped = TestClient::connect(addr);
+        dropped.send(&HandshakeC2s {
+            protocol_version: VarInt(PROTOCOL_VERSION),
+            server_address: "localhost",
+            server_port: addr.port(),
+            next_state: HandshakeNextState::Status,
+        });
+        dropped.send(&QueryRequestC2s);
+        dropped.flush();
+        assert!(dropped.next_frame().is_none());
+
+        drop(pinging);
+
+        // The second login attempt is over the limit. The connection is retried
+        // until the server has noticed the closed connection.
+        let start = Instant::now();
+
+        let (id, body) = loop {
+            let mut client = TestClient::connect(addr);
+            client.send(&HandshakeC2s {
+                protocol_version: VarInt(PROTOCOL_VERSION),
+                server_address: "localhost",
+                server_port: addr.port(),
+                next_state: HandshakeNextState::Login,
+            });
+            client.send(&LoginHelloC2s {
+                username: "test2",
+                profile_id: None,
+            });
+            client.flush();
+
+            if let Some(frame) = client.next_frame() {
+                break frame;
+            }
+
+            assert!(start.elapsed() < Duration::from_secs(5), "still dropped");
+            thread::sleep(Duration::from_millis(10));
+        };
+
+        assert_eq!(id, LoginDisconnectS2c::ID);
+        let disconnect = LoginDisconnectS2c::decode(&mut body.as_slice()).unwrap();
+        assert_eq!(disconnect.reason.into_owned(), Text::from("Slow down"));
+
+        // Keep the player connected until it is counted below.
+        player
+    });
+
+    let _player = update_until_finished(&mut app, client_thread);
+    app.update();
+
+    assert_eq!(app.world.query::<&Client>().iter(&app.world).count(), 1);
+}
+
+/// Timeouts short enough to be reached in tests, except for the phases that
+/// shouldn't time out.
+fn short_timeouts(phase: &str) -> ConnectionTimeouts {
+    let timeout = |p| {
+        if p == phase {
+            Duration::from_millis(200)
+        } else {
+            Duration::from_secs(60)
+        }
+    };
+
+    ConnectionTimeouts {
+        handshake: timeout("handshake"),
+        status: timeout("status"),
+        login: timeout("login"),
+        configuration: timeout("configuration"),
+    }
+}
+
+/// Connects a client that stops responding in `phase`, and asserts that the
+/// server drops it without a message once the phase's timeout is reached.
+fn assert_stalled_client_dropped(phase: &'static str, stall: fn(&mut TestClient, SocketAddr)) {
+    let (mut app, addr) = scenario_listening(
+        NetworkSettings {
+            callbacks: QueryOnConfigure::default().into(),
+            timeouts: short_timeouts(phase),
+            ..Default::default()
+        },
+        Duration::MAX,
+    );
+
+    let client_thread = thread::spawn(move || {
+        let mut client = TestClient::connect(addr);
+        let start = Instant::now();
+
+        stall(&mut client, addr);
+
+        assert!(
+            client.next_frame().is_none(),
+            "got a packet in the {phase} phase"
+        );
+        assert!(
+            start.elapsed() < Duration::from_secs(5),
+            "not dropped in the {phase} phase"
+        );
+    });
+
+    update_until_finished(&mut app, client_thread);
+}
+
+fn send_handshake(client: &mut TestClient, addr: SocketAddr, next_state: HandshakeNextState) {
+    client.send(&HandshakeC2s {
+        protocol_version: VarInt(PROTOCOL_VERSION),
+        server_address: "localhost",
+        server_port: addr.port(),
+        next_state,
+    });
+}
+
+#[test]
+fn stalled_clients_time_out() {
+    assert_stalled_client_dropped("handshake", |_, _| {});
+
+    assert_stalled_client_dropped("status", |client, addr| {
+        send_handshake(client, addr, HandshakeNextState::Status);
+        client.flush();
+    });
+
+    assert_stalled_client_dropped("login", |client, addr| {
+        send_handshake(client, addr, HandshakeNextState::Login);
+        client.flush();
+    });
+
+    assert_stalled_client_dropped("configuration", |client, addr| {
+        send_handshake(client, addr, HandshakeNextState::Login);
+        client.send(&LoginHelloC2s {
+            username: "test",
+            profile_id: None,
+        });
+        client.flush();
+
+        // The login plugin request is never answered.
+        assert_eq!(client.next_frame().unwrap().0, LoginQueryRequestS2c::ID);
+    });
+}
+
+/// Takes longer than the timeouts in the login and configuration phases.
+struct SlowCallbacks;
+
+#[async_trait]
+impl NetworkCallbacks for SlowCallbacks {
+    async fn compression(
+        &self,
+        shared: &SharedNetworkState,
+        _remote_addr: SocketAddr,
+    ) -> CompressionSettings {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        shared.compression()
+    }
+
+    async fn configure(
+        &self,
+        _shared: &SharedNetworkState,
+        _info: &NewClientInfo,
+        _conn: &mut LoginPluginConnection<'_>,
+    ) -> Result<(), Text> {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        Ok(())
+    }
+}
+
+#[test]
+fn slow_callbacks_do_not_time_out() {
+    let timeout = Duration::from_millis(200);
+
+    let (mut app, addr) = scenario_listening(
+        NetworkSettings {
+            callbacks: SlowCallbacks.into(),
+            timeouts: ConnectionTimeouts {
+                handshake: timeout,
+                status: timeout,
+                login: timeout,
+                configuration: timeout,
+            },
+            ..Default::default()
+        },
+        Duration::MAX,
+    );
+
+    let client_thread = thread::spawn(move || TestClient::login(addr));
+
+    update_until_finished(&mut app, client_thread);
+}
+
+/// Rejects clients with a reason in the given phase.
+struct RejectIn(&'static str);
+
+#[async_trait]
+impl NetworkCallbacks for RejectIn {
+    async fn login(
+        &self,
+        _shared: &SharedNetworkState,
+        _info: &NewClientInfo,
+    ) -> Result<CleanupFn, Text> {
+        if self.0 == "login" {
+            return Err("You are banned".into());
+        }
+
+        Ok(Box::new(|| {}))
+    }
+
+    async fn configure(
+        &self,
+        _shared: &SharedNetworkState,
+        _info: &NewClientInfo,
+        _conn: &mut LoginPluginConnection<'_>,
+    ) -> Result<(), Text> {
+        if self.0 == "configuration" {
+            return Err("Under maintenance".into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Logs in a client that is rejected in `phase`, and asserts that it is sent
+/// `reason` before the connection is closed.
+fn assert_rejected_with(phase: &'static str, reason: &'static str) {
+    let (mut app, addr) = scenario_listening(
+        NetworkSettings {
+            callbacks: RejectIn(phase).into(),
+            ..Default::default()
+        },
+        Duration::MAX,
+    );
+
+    let client_thread = thread::spawn(move || {
+        let mut client = TestClient::connect(addr);
+
+        send_handshake(&mut client, addr, HandshakeNextState::Login);
+        client.send(&LoginHelloC2s {
+            username: "test",
+            profile_id: None,
+        });
+        client.flush();
+
+        let (id, body) = client.next_frame().expect("disconnected without a reason");
+        assert_eq!(id, LoginDisconnectS2c::ID, "in the {phase} phase");
+
+        let disconnect = LoginDisconnectS2c::decode(&mut body.as_slice()).unwrap();
+        assert_eq!(disconnect.reason.into_owned(), Text::from(reason));
+
+        assert!(client.next_frame().is_none(), "not closed after {phase}");
+    });
+
+    update_until_finished(&mut app, client_thread);
+}
+
+#[test]
+fn login_and_configure_rejections() {
+    assert_rejected_with("login", "You are banned");
+    assert_rejected_with("configuration", "Under maintenance");
+}
+
+#[test]
+fn disconnect_in_play_is_sent_before_closing() {
+    let (mut app, addr) = scenario_listening(NetworkSettings::default(), Duration::MAX);
+
+    app.add_systems(
+        Update,
+        |clients: Query<Entity, Added<Client>>, mut commands: Commands| {
+            for client in &clients {
+                commands.add(DisconnectClient {
+                    client,
+                    reason: "Kicked".into(),
+                });
+            }
+        },
+    );
+
+    let client_thread = thread::spawn(move || {
+        let mut client = TestClient::login(addr);
+
+        while let Some((id, body)) = client.next_frame() {
+            if id == DisconnectS2c::ID {
+                let disconnect = DisconnectS2c::decode(&mut body.as_slice()).unwrap();
+                assert_eq!(disconnect.reason.into_owned(), Text::from("Kicked"));
+
+                assert!(client.next_frame().is_none(), "not closed after disconnect");
+                return;
+            }
+        }
+
+        panic!("closed without a disconnect packet");
+    });
+
+    update_until_finished(&mut app, client_thread);
+}
+
+/// Logs in with `username`, returning the reason if the client was
+/// disconnected.
+fn try_login_as(addr: SocketAddr, username: &'static str) -> Option<Text> {
+    try_login_with_version(addr, PROTOCOL_VERSION, username)
+}
+
+/// Like [`try_login_as`], but with the given protocol version.
+fn try_login_with_version(
+    addr: SocketAddr,
+    protocol_version: i32,
+    username: &'static str,
+) -> Option<Text> {
+    let mut client = TestClient::connect(addr);
+
+    client.send(&HandshakeC2s {
+        protocol_version: VarInt(protocol_version),
+        server_address: "localhost",
+        server_port: addr.port(),
+        next_state: HandshakeNextState::Login,
+    });
+    client.send(&LoginHelloC2s {
+        username,
+        profile_id: None,
+    });
+    client.flush();
+
+    match client.next_frame().expect("disconnected without a reason") {
+        (LoginSuccessS2c::ID, _) => None,
+        (LoginDisconnectS2c::ID, body) => Some(
+            LoginDisconnectS2c::decode(&mut body.as_slice())
+                .unwrap()
+                .reason
+                .into_owned(),
+        ),
+        (id, _) => panic!("unexpected packet {id}"),
+    }
+}
+
+#[test]
+fn whitelist_admits_listed_players() {
+    let path = std::env::temp_dir().join(format!(
+        "valence-test-whitelist-{:016x}.json",
+        rand::random::<u64>()
+    ));
+    std::fs::write(&path, r#"[{ "name": "allowed" }]"#).unwrap();
+
+    let whitelist = Whitelist::load(&path).unwrap();
+
+    let (mut app, addr) = scenario_listening(
+        NetworkSettings {
+            callbacks: whitelist.clone().into(),
+            ..Default::default()
+        },
+        Duration::MAX,
+    );
+
+    let client_thread = thread::spawn(move || {
+        assert_eq!(try_login_as(addr, "allowed"), None);
+
+        let reason = try_login_as(addr, "denied").expect("denied player was admitted");
+        assert_eq!(
+            reason,
+            Text::translate(translation_key::MULTIPLAYER_DISCONNECT_NOT_WHITELISTED, [])
+        );
+    });
+
+    update_until_finished(&mut app, client_thread);
+
+    // Players added to the file are admitted once it is reloaded.
+    std::fs::write(&path, r#"[{ "name": "denied" }]"#).unwrap();
+    whitelist.reload().unwrap();
+
+    let client_thread = thread::spawn(move || assert_eq!(try_login_as(addr, "denied"), None));
+
+    update_until_finished(&mut app, client_thread);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn ban_list_rejects_and_kicks_banned_players() {
+    let ban_list = BanList::new();
+    ban_list.ban_player(PlayerBan::new(
+        // The UUID of the player in offline mode.
+        Uuid::from_slice(&Sha256::digest("banned")[..16]).unwrap(),
+        "banned",
+        "Griefing",
+    ));
+
+    let (mut app, addr) = scenario_listening(
+        NetworkSettings {
+            ban_list: Some(ban_list.clone()),
+            ..Default::default()
+        },
+        Duration::MAX,
+    );
+
+    let client_thread = thread::spawn(move || {
+        let reason = try_login_as(addr, "banned").expect("banned player was admitted");
+        assert_eq!(
+            reason,
+            Text::translate(
+                translation_key::MULTIPLAYER_DISCONNECT_BANNED_REASON,
+                ["Griefing".into()]
+            )
+        );
+
+        TestClient::login(addr)
+    });
+
+    let mut client = update_until_finished(&mut app, client_thread);
+
+    // Wait for the client to be spawned.
+    while app.world.query::<&Client>().iter(&app.world).count() == 0 {
+        app.update();
+    }
+
+    // Banning the online player's address kicks them.
+    ban_list.ban_ip(IpBan::new("127.0.0.0/8".parse::<IpCidr>().unwrap(), "Bots"));
+
+    let client_thread = thread::spawn(move || {
+        while let Some((id, body)) = client.next_frame() {
+            if id == DisconnectS2c::ID {
+                return DisconnectS2c::decode(&mut body.as_slice())
+                    .unwrap()
+                    .reason
+                    .into_owned();
+            }
+        }
+
+        panic!("closed without a disconnect packet");
+    });
+
+    let reason = update_until_finished(&mut app, client_thread);
+
+    assert_eq!(
+        reason,
+        Text::translate(
+            translation_key::MULTIPLAYER_DISCONNECT_BANNED_IP_REASON,
+            ["Bots".into()]
+        )
+    );
+}
+
+/// Asks clients with a different protocol version to update.
+struct RequireUpdate;
+
+#[async_trait]
+impl NetworkCallbacks for RequireUpdate {
+    async fn server_list_ping(
+        &self,
+        _shared: &SharedNetworkState,
+        _remote_addr: SocketAddr,
+        handshake_data: &HandshakeData,
+    ) -> ServerListPing {
+        let description = if handshake_data.protocol_version == PROTOCOL_VERSION {
+            "Welcome"
+        } else {
+            "Please update to 1.20.1"
+        };
+
+        ServerListPing::Respond {
+            online_players: 0,
+            max_players: 0,
+            player_sample: vec![],
+            description: description.into(),
+            favicon_png: &[],
+            version_name: "Valence 1.20.1".into(),
+            protocol: PROTOCOL_VERSION,
+            enforces_secure_chat: false,
+            previews_chat: false,
+        }
+    }
+
+    async fn version_check(
+        &self,
+        _shared: &SharedNetworkState,
+        _remote_addr: SocketAddr,
+        handshake_data: &HandshakeData,
+    ) -> Result<(), Text> {
+        if handshake_data.protocol_version == PROTOCOL_VERSION {
+            Ok(())
+        } else {
+            Err("Please update to 1.20.1".into())
+        }
+    }
+}
+
+#[test]
+fn version_aware_status_and_login() {
+    let (mut app, addr) = scenario_listening(
+        NetworkSettings {
+            callbacks: RequireUpdate.into(),
+            status_cache_ttl: Some(Duration::from_secs(60)),
+            ..Default::default()
+        },
+        Duration::MAX,
+    );
+
+    let client_thread = thread::spawn(move || {
+        let description = |protocol_version| {
+            let json: serde_json::Value =
+                serde_json::from_str(&TestClient::status_with_version(addr, protocol_version))
+                    .unwrap();
+
+            assert_eq!(json["version"]["name"], "Valence 1.20.1");
+            assert_eq!(json["version"]["protocol"], PROTOCOL_VERSION);
+
+            serde_json::from_value::<Text>(json["description"].clone()).unwrap()
+        };
+
+        // Responses are cached separately for each protocol version.
+        for _ in 0..2 {
+            assert_eq!(description(47), "Please update to 1.20.1".into());
+            assert_eq!(description(PROTOCOL_VERSION), "Welcome".into());
+        }
+
+        assert_eq!(
+            try_login_with_version(addr, 47, "old"),
+            Some("Please update to 1.20.1".into())
+        );
+        assert_eq!(try_login_with_version(addr, PROTOCOL_VERSION, "new"), None);
+    });
+
+    update_until_finished(&mut app, client_thread);
+}
+
+#[test]
+fn default_version_check() {
+    let (mut app, addr) = scenario_listening(NetworkSettings::default(), Duration::MAX);
+
+    let client_thread = thread::spawn(move || {
+        assert_eq!(
+            try_login_with_version(addr, PROTOCOL_VERSION - 1, "old"),
+            Some(Text::translate(
+                translation_key::MULTIPLAYER_DISCONNECT_OUTDATED_CLIENT,
+                [MINECRAFT_VERSION.into()]
+            ))
+        );
+        assert_eq!(
+            try_login_with_version(addr, PROTOCOL_VERSION + 1, "new"),
+            Some(Text::translate(
+                translation_key::MULTIPLAYER_DISCONNECT_INCOMPATIBLE,
+                [MINECRAFT_VERSION.into()]
+            ))
+        );
+        assert_eq!(try_login_as(addr, "current"), None);
+    });
+
+    update_until_finished(&mut app, client_thread);
+}
+
+/// Responds to server list pings with an announcement in the player sample.
+struct Announcement;
+
+#[async_trait]
+impl NetworkCallbacks for Announcement {
+    async fn server_list_ping(
+        &self,
+        _shared: &SharedNetworkState,
+        _remote_addr: SocketAddr,
+        _handshake_data: &HandshakeData,
+    ) -> ServerListPing {
+        ServerListPing::Respond {
+            online_players: 0,
+            max_players: 0,
+            player_sample: PlayerSampleEntry::from_lines((0..20).map(|i| format!("line {i}"))),
+            description: "".into(),
+            favicon_png: &[],
+            version_name: MINECRAFT_VERSION.to_owned(),
+            protocol: PROTOCOL_VERSION,
+            enforces_secure_chat: true,
+            previews_chat: false,
+        }
+    }
+}
+
+#[test]
+fn status_player_sample() {
+    let (mut app, addr) = scenario_listening(
+        NetworkSettings {
+            callbacks: Announcement.into(),
+            ..Default::default()
+        },
+        Duration::MAX,
+    );
+
+    let client_thread = thread::spawn(move || {
+        let json: serde_json::Value = serde_json::from_str(&TestClient::status(addr)).unwrap();
+
+        let sample = json["players"]["sample"].as_array().unwrap();
+
+        // The sample is truncated.
+        assert_eq!(sample.len(), ServerListPing::MAX_PLAYER_SAMPLE_LEN);
+        assert_eq!(sample[0]["name"], "line 0");
+        assert_eq!(sample[0]["id"], Uuid::nil().to_string());
+
+        assert_eq!(json["enforcesSecureChat"], true);
+        assert_eq!(json["previewsChat"], false);
+    });
+
+    update_until_finished(&mut app, client_thread);
+}
+
+#[test]
+fn status_enforces_secure_chat() {
+    let (mut app, addr) = scenario_listening(NetworkSettings::default(), Duration::MAX);
+
+    let enforces_secure_chat = |addr| {
+        let json: serde_json::Value = serde_json::from_str(&TestClient::status(addr)).unwrap();
+        json["enforcesSecureChat"].as_bool().unwrap()
+    };
+
+    assert!(!enforces_secure_chat(addr));
+
+    app.world.resource_mut::<ChatSessionSettings>().signed_chat = SignedChatMode::Enforce;
+    app.update();
+
+    assert!(enforces_secure_chat(addr));
+}
+
+#[test]
+fn status_samples_online_players() {
+    let (mut app, addr) = scenario_listening(NetworkSettings::default(), Duration::MAX);
+
+    let sample = |addr| {
+        let json: serde_json::Value = serde_json::from_str(&TestClient::status(addr)).unwrap();
+        json["players"]["sample"].as_array().unwrap().clone()
+    };
+
+    let client_thread = thread::spawn(move || {
+        assert!(sample(addr).is_empty());
+
+        let client = TestClient::login(addr);
+
+        let players = sample(addr);
+        assert_eq!(players.len(), 1);
+        assert_eq!(players[0]["name"], "test");
+
+        drop(client);
+
+        // The player is removed once the server notices the disconnect.
+        let start = Instant::now();
+
+        while !sample(addr).is_empty() {
+            assert!(start.elapsed() < Duration::from_secs(5), "still sampled");
+            thread::sleep(Duration::from_millis(10));
+        }
+    });
+
+    update_until_finished(&mut app, client_thread);
+}
+
+/// Counts the calls to `server_list_ping`.
+#[derive(Clone, Default)]
+struct CountPings(Arc<AtomicUsize>);
+
+#[async_trait]
+impl NetworkCallbacks for CountPings {
+    async fn server_list_ping(
+        &self,
+        _shared: &SharedNetworkState,
+        _remote_addr: SocketAddr,
+        _handshake_data: &HandshakeData,
+    ) -> ServerListPing {
+        let count = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+
+        ServerListPing::Respond {
+            online_players: 0,
+            max_players: 0,
+            player_sample: vec![],
+            description: format!("ping {count}").into(),
+            favicon_png: &[],
+            version_name: MINECRAFT_VERSION.to_owned(),
+            protocol: PROTOCOL_VERSION,
+            enforces_secure_chat: false,
+            previews_chat: false,
+        }
+    }
+}
+
+#[test]
+fn status_cache() {
+    let callbacks = CountPings::default();
+
+    let (mut app, addr) = scenario_listening(
+        NetworkSettings {
+            callbacks: callbacks.clone().into(),
+            status_cache_ttl: Some(Duration::from_secs(60)),
+            ..Default::default()
+        },
+        Duration::MAX,
+    );
+
+    let shared = app.world.resource::<SharedNetworkState>().clone();
+    let count = callbacks.0.clone();
+
+    let client_thread = thread::spawn(move || {
+        let first = TestClient::status(addr);
+        assert!(first.contains("ping 1"), "{first}");
+
+        // The second ping within the TTL gets the same response.
+        assert_eq!(TestClient::status(addr), first);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        shared.invalidate_status_cache();
+
+        let third = TestClient::status(addr);
+        assert!(third.contains("ping 2"), "{third}");
+
+        assert_eq!(
+            shared.status_ping_stats(),
+            StatusPingStats {
+                from_cache: 1,
+                from_callback: 2,
+            }
+        );
+    });
+
+    update_until_finished(&mut app, client_thread);
+}
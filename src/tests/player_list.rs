@@ -1,9 +1,18 @@
 use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
 use valence_client::packet::PlayerSpawnS2c;
+use valence_client::{Ping, Username};
+use valence_core::protocol::packet::scoreboard::{Mode, TeamS2c};
+use valence_core::text::{Color, Text, TextFormat};
+use valence_core::uuid::UniqueId;
+use valence_entity::Location;
 use valence_instance::chunk::UnloadedChunk;
 use valence_instance::Instance;
-use valence_player_list::packet::PlayerListS2c;
+use valence_player_list::packet::{PlayerListActions, PlayerListS2c};
+use valence_player_list::{
+    sort_team_name, DisplayName, DisplayNameOverrides, LatencyOverride, Listed, PlayerList,
+    PlayerListEntryBundle, SortKey,
+};
 
 use crate::testing::{create_mock_client, scenario_single_client};
 
@@ -90,3 +99,334 @@ fn player_list_arrives_before_player_spawn() {
         assert_eq!(pkt.entries.len(), 2);
     }*/
 }
+
+#[test]
+fn latency_updates_are_batched() {
+    let mut app = App::new();
+
+    let (_client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    let entries = [
+        app.world.spawn(PlayerListEntryBundle::default()).id(),
+        app.world.spawn(PlayerListEntryBundle::default()).id(),
+    ];
+
+    let interval = app.world.resource::<PlayerList>().latency_update_interval;
+
+    // Wait for the pings of the new entries to be sent.
+    for _ in 0..interval {
+        app.update();
+    }
+
+    client_helper.clear_received();
+
+    for (entry, ping) in entries.into_iter().zip([50, 150]) {
+        app.world.get_mut::<Ping>(entry).unwrap().0 = ping;
+    }
+
+    for _ in 0..interval {
+        app.update();
+    }
+
+    let recvd = client_helper.collect_received();
+    recvd.assert_count::<PlayerListS2c>(1);
+
+    let pkt = recvd.first::<PlayerListS2c>();
+    assert_eq!(
+        u8::from(pkt.actions),
+        u8::from(PlayerListActions::new().with_update_latency(true))
+    );
+    assert_eq!(pkt.entries.len(), 2);
+}
+
+#[test]
+fn latency_override_suppresses_ping() {
+    let mut app = App::new();
+
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    let interval = app.world.resource::<PlayerList>().latency_update_interval;
+
+    app.update();
+    client_helper.clear_received();
+
+    // The override is sent right away.
+    app.world.entity_mut(client_ent).insert(LatencyOverride(0));
+    app.update();
+
+    {
+        let recvd = client_helper.collect_received();
+        recvd.assert_count::<PlayerListS2c>(1);
+
+        let pkt = recvd.first::<PlayerListS2c>();
+        assert!(pkt.actions.update_latency());
+        assert_eq!(pkt.entries[0].ping, 0);
+    }
+
+    // The ping isn't sent while the override is present.
+    app.world.get_mut::<Ping>(client_ent).unwrap().0 = 100;
+
+    for _ in 0..interval {
+        app.update();
+    }
+
+    client_helper
+        .collect_received()
+        .assert_count::<PlayerListS2c>(0);
+
+    // The ping is sent again once the override is removed.
+    app.world.entity_mut(client_ent).remove::<LatencyOverride>();
+    app.update();
+
+    let recvd = client_helper.collect_received();
+    recvd.assert_count::<PlayerListS2c>(1);
+    assert_eq!(recvd.first::<PlayerListS2c>().entries[0].ping, 100);
+}
+
+#[test]
+fn toggle_listed() {
+    let mut app = App::new();
+
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    app.update();
+    client_helper.clear_received();
+
+    for listed in [false, true] {
+        app.world.get_mut::<Listed>(client_ent).unwrap().0 = listed;
+        app.update();
+
+        let recvd = client_helper.collect_received();
+        recvd.assert_count::<PlayerListS2c>(1);
+
+        let pkt = recvd.first::<PlayerListS2c>();
+        assert_eq!(
+            u8::from(pkt.actions),
+            u8::from(PlayerListActions::new().with_update_listed(true))
+        );
+        assert_eq!(pkt.entries[0].listed, listed);
+    }
+}
+
+#[test]
+fn unlisted_entry_on_join() {
+    let mut app = App::new();
+
+    let (client_ent, _client_helper) = scenario_single_client(&mut app);
+
+    app.world.spawn(PlayerListEntryBundle {
+        listed: Listed(false),
+        ..Default::default()
+    });
+
+    app.update();
+
+    let (mut client_2, mut client_helper_2) = create_mock_client("test_2");
+    client_2.player.location.0 = app.world.get::<Location>(client_ent).unwrap().0;
+    app.world.spawn(client_2);
+
+    app.update();
+
+    let recvd = client_helper_2.collect_received();
+    let pkt = recvd.first::<PlayerListS2c>();
+    assert!(pkt.actions.update_listed());
+
+    let listed: Vec<_> = pkt.entries.iter().map(|entry| entry.listed).collect();
+    assert_eq!(listed.iter().filter(|&&listed| !listed).count(), 1);
+    assert_eq!(listed.len(), 3);
+}
+
+#[test]
+fn display_name_override() {
+    let mut app = App::new();
+
+    let (client_ent_1, mut client_helper_1) = scenario_single_client(&mut app);
+
+    let (mut client_2, mut client_helper_2) = create_mock_client("test_2");
+    client_2.player.location.0 = app.world.get::<Location>(client_ent_1).unwrap().0;
+    app.world.spawn(client_2);
+
+    let entry = app.world.spawn(PlayerListEntryBundle::default()).id();
+    let uuid = app.world.get::<UniqueId>(entry).unwrap().0;
+
+    app.update();
+
+    client_helper_1.clear_received();
+    client_helper_2.clear_received();
+
+    let gold_name = "Friend".color(Color::GOLD);
+
+    // Only the first client sees the override.
+    let mut overrides = DisplayNameOverrides::new();
+    overrides.set(uuid, Some(gold_name.clone()));
+    app.world.entity_mut(client_ent_1).insert(overrides);
+
+    app.update();
+
+    {
+        let recvd = client_helper_1.collect_received();
+        recvd.assert_count::<PlayerListS2c>(1);
+
+        let pkt = recvd.first::<PlayerListS2c>();
+        assert!(pkt.actions.update_display_name());
+        assert_eq!(pkt.entries[0].player_uuid, uuid);
+        assert_eq!(pkt.entries[0].display_name.as_deref(), Some(&gold_name));
+
+        client_helper_2
+            .collect_received()
+            .assert_count::<PlayerListS2c>(0);
+    }
+
+    // The override is sent again after changes to the shared name.
+    app.world.get_mut::<DisplayName>(entry).unwrap().0 = Some("Shared".into());
+
+    app.update();
+
+    {
+        let recvd = client_helper_1.collect_received();
+        recvd.assert_count::<PlayerListS2c>(2);
+
+        let last = recvd
+            .0
+            .iter()
+            .rev()
+            .find_map(|frame| frame.decode::<PlayerListS2c>().ok());
+        assert_eq!(
+            last.unwrap().entries[0].display_name.as_deref(),
+            Some(&gold_name)
+        );
+
+        let recvd = client_helper_2.collect_received();
+        recvd.assert_count::<PlayerListS2c>(1);
+        assert_eq!(
+            recvd.first::<PlayerListS2c>().entries[0]
+                .display_name
+                .as_deref(),
+            Some(&Text::text("Shared"))
+        );
+    }
+
+    // Clearing the override sends the shared name.
+    app.world
+        .get_mut::<DisplayNameOverrides>(client_ent_1)
+        .unwrap()
+        .remove(uuid);
+
+    app.update();
+
+    {
+        let recvd = client_helper_1.collect_received();
+        recvd.assert_count::<PlayerListS2c>(1);
+        assert_eq!(
+            recvd.first::<PlayerListS2c>().entries[0]
+                .display_name
+                .as_deref(),
+            Some(&Text::text("Shared"))
+        );
+
+        client_helper_2
+            .collect_received()
+            .assert_count::<PlayerListS2c>(0);
+    }
+}
+
+#[test]
+fn sort_keys_order_entries_with_teams() {
+    let mut app = App::new();
+
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    app.update();
+    client_helper.clear_received();
+
+    let entries: Vec<Entity> = [("a", 10), ("b", -5), ("c", 0)]
+        .into_iter()
+        .map(|(name, key)| {
+            app.world
+                .spawn((
+                    PlayerListEntryBundle {
+                        username: Username(name.into()),
+                        ..Default::default()
+                    },
+                    SortKey(key),
+                ))
+                .id()
+        })
+        .collect();
+
+    app.update();
+
+    let team_names = [sort_team_name(10), sort_team_name(-5), sort_team_name(0)];
+
+    assert_eq!(team_names[1], "sort_7ffffffb");
+    assert_eq!(team_names[2], "sort_80000000");
+    assert_eq!(team_names[0], "sort_8000000a");
+
+    // Every entry is put on the team of its key.
+    {
+        let recvd = client_helper.collect_received();
+        recvd.assert_count::<TeamS2c>(3);
+
+        let mut teams: Vec<_> = recvd
+            .0
+            .iter()
+            .filter_map(|frame| frame.decode::<TeamS2c>().ok())
+            .map(|pkt| {
+                let Mode::CreateTeam { entities, .. } = pkt.mode else {
+                    panic!("expected a new team");
+                };
+
+                (pkt.team_name.to_owned(), entities.concat())
+            })
+            .collect();
+
+        // Lower keys come first.
+        teams.sort();
+
+        assert_eq!(
+            teams,
+            [
+                (team_names[1].clone(), "b".to_owned()),
+                (team_names[2].clone(), "c".to_owned()),
+                (team_names[0].clone(), "a".to_owned()),
+            ]
+        );
+    }
+
+    // Moving an entry to an existing key only moves that entry.
+    app.world.get_mut::<SortKey>(entries[2]).unwrap().0 = -5;
+
+    app.update();
+
+    {
+        let recvd = client_helper.collect_received();
+        recvd.assert_count::<TeamS2c>(2);
+
+        let packets: Vec<_> = recvd
+            .0
+            .iter()
+            .filter_map(|frame| frame.decode::<TeamS2c>().ok())
+            .collect();
+
+        assert_eq!(packets[0].team_name, team_names[2]);
+        assert_eq!(packets[0].mode, Mode::RemoveTeam);
+        assert_eq!(packets[1].team_name, team_names[1]);
+        assert_eq!(
+            packets[1].mode,
+            Mode::AddEntities {
+                entities: vec!["c"]
+            }
+        );
+    }
+
+    // New clients are sent the teams.
+    let (mut client_2, mut client_helper_2) = create_mock_client("test_2");
+    client_2.player.location.0 = app.world.get::<Location>(client_ent).unwrap().0;
+    app.world.spawn(client_2);
+
+    app.update();
+
+    client_helper_2
+        .collect_received()
+        .assert_count::<TeamS2c>(2);
+}
@@ -0,0 +1,81 @@
+use bevy_app::App;
+use valence_core::ident;
+use valence_core::item::{ItemKind, ItemStack};
+use valence_inventory::packet::{SynchronizeRecipesS2c, UnlockRecipesS2c, UpdateRecipeBookAction};
+use valence_inventory::recipe::{
+    CraftingCategory, Ingredient, RecipeData, RecipeRegistry, ShapelessRecipe, UnlockedRecipes,
+};
+
+use crate::testing::scenario_single_client;
+
+#[test]
+fn recipes_sent_and_unlocked() {
+    let mut app = App::new();
+    let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+    let recipe = RecipeData::CraftingShapeless(ShapelessRecipe {
+        group: String::new(),
+        category: CraftingCategory::Misc,
+        ingredients: vec![
+            Ingredient(vec![ItemKind::Stick]),
+            Ingredient(vec![ItemKind::Coal]),
+        ],
+        result: ItemStack::new(ItemKind::Torch, 4, None),
+    });
+
+    app.world
+        .resource_mut::<RecipeRegistry>()
+        .insert(ident!("mygame:cheap_torch"), recipe.clone());
+
+    app.update();
+
+    // The recipes and an empty recipe book are sent on join.
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<SynchronizeRecipesS2c>(1);
+    sent_packets.assert_count::<UnlockRecipesS2c>(1);
+    sent_packets.assert_order::<(SynchronizeRecipesS2c, UnlockRecipesS2c)>();
+
+    let sync = sent_packets.first::<SynchronizeRecipesS2c>();
+    assert_eq!(sync.recipes.len(), 1);
+    assert_eq!(sync.recipes[0].id, ident!("mygame:cheap_torch"));
+    assert_eq!(sync.recipes[0].data, recipe);
+
+    let unlock = sent_packets.first::<UnlockRecipesS2c>();
+    assert!(matches!(unlock.action, UpdateRecipeBookAction::Init { .. }));
+    assert!(unlock.recipe_ids.is_empty());
+
+    // Nothing is sent when nothing changed.
+    app.update();
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<SynchronizeRecipesS2c>(0);
+    sent_packets.assert_count::<UnlockRecipesS2c>(0);
+
+    // Unlock the recipe.
+    app.world
+        .get_mut::<UnlockedRecipes>(client_ent)
+        .unwrap()
+        .unlock(ident!("mygame:cheap_torch"));
+
+    app.update();
+
+    let sent_packets = client_helper.collect_received();
+    sent_packets.assert_count::<SynchronizeRecipesS2c>(0);
+    sent_packets.assert_count::<UnlockRecipesS2c>(1);
+
+    let unlock = sent_packets.first::<UnlockRecipesS2c>();
+    assert_eq!(unlock.action, UpdateRecipeBookAction::Add);
+    assert_eq!(unlock.recipe_ids, vec![ident!("mygame:cheap_torch")]);
+
+    // Lock it again.
+    app.world
+        .get_mut::<UnlockedRecipes>(client_ent)
+        .unwrap()
+        .lock(ident!("mygame:cheap_torch"));
+
+    app.update();
+
+    let sent_packets = client_helper.collect_received();
+    let unlock = sent_packets.first::<UnlockRecipesS2c>();
+    assert_eq!(unlock.action, UpdateRecipeBookAction::Remove);
+    assert_eq!(unlock.recipe_ids, vec![ident!("mygame:cheap_torch")]);
+}